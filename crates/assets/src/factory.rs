@@ -18,8 +18,11 @@ pub struct LoadFactoryMessage {
 
 #[derive(Debug)]
 pub enum ToFactoryMessage {
-    Load(AssetTaskID, AssetID, LoadFactoryMessage),
+    Load(AssetTaskID, AssetID, Box<LoadFactoryMessage>),
     Free(AssetTaskID, AssetID),
+    /// New data of the loaded asset (see `hot_reload` module).
+    /// The asset must be replaced in place, keeping its address.
+    Reload(AssetTaskID, AssetID, Box<LoadFactoryMessage>),
 }
 
 #[derive(Debug)]
@@ -33,6 +36,7 @@ pub struct LoadedFactoryMessage {
 pub enum FromFactoryMessage {
     Load(AssetTaskID, AssetID, anyhow::Result<LoadedFactoryMessage>),
     Free(AssetTaskID, AssetID, anyhow::Result<()>),
+    Reload(AssetTaskID, AssetID, anyhow::Result<AssetMemoryUsage>),
 }
 
 // Make rust happy with sending NonNull
@@ -81,6 +85,12 @@ pub struct BasicFactory<T> {
     binding: Option<FactoryBinding>,
 }
 
+impl<T: 'static> Default for BasicFactory<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: 'static> BasicFactory<T> {
     pub fn new() -> Self {
         BasicFactory {
@@ -107,10 +117,38 @@ impl<T: 'static> BasicFactory<T> {
         self.binding = Some(binding);
     }
 
+    /// Reloaded assets are parsed anew and swapped with the previous ones in place,
+    /// then the previous ones are freed. If the parsing fails, the previous asset stays.
     pub fn process_events<F, P>(&mut self, parse: P, free: F, timeout: Duration)
     where
         P: Fn(LoadFactoryMessage) -> anyhow::Result<(T, AssetMemoryUsage)>,
         F: Fn(&T),
+    {
+        self.process_events_with_reload(
+            &parse,
+            |asset, message| {
+                let (object, usage) = parse(message)?;
+                free(&std::mem::replace(asset, object));
+                Ok(usage)
+            },
+            &free,
+            timeout,
+        )
+    }
+
+    /// Same as `process_events`, but the reloaded assets are updated by `reload`,
+    /// e.g. to upload the new data into the same GPU resource.
+    /// Leave the asset intact if the reload fails.
+    pub fn process_events_with_reload<F, P, R>(
+        &mut self,
+        parse: P,
+        reload: R,
+        free: F,
+        timeout: Duration,
+    ) where
+        P: Fn(LoadFactoryMessage) -> anyhow::Result<(T, AssetMemoryUsage)>,
+        R: Fn(&mut T, LoadFactoryMessage) -> anyhow::Result<AssetMemoryUsage>,
+        F: Fn(&T),
     {
         if self.binding.is_none() {
             error!("Factory not bound to any queues.");
@@ -120,9 +158,8 @@ impl<T: 'static> BasicFactory<T> {
         while let Some(msg) = self.recv(timeout) {
             match msg {
                 ToFactoryMessage::Load(tid, aid, payload) => {
-                    let tid = tid.clone();
                     let aid = aid.clone();
-                    match parse(payload) {
+                    match parse(*payload) {
                         Ok((object, usage)) => {
                             // Move the parsed asset to the Heap and take ir pointer of it.
                             let ptr = NonNull::new(Box::into_raw(Box::new(object))).unwrap();
@@ -146,7 +183,6 @@ impl<T: 'static> BasicFactory<T> {
                 }
                 ToFactoryMessage::Free(tid, aid) => {
                     let aid = aid.clone();
-                    let tid = tid.clone();
 
                     let asset = self.storage.get(&aid).unwrap();
 
@@ -162,6 +198,14 @@ impl<T: 'static> BasicFactory<T> {
 
                     // Box will be dropped here, freeing the memory
                 }
+                ToFactoryMessage::Reload(tid, aid, payload) => {
+                    let result = match self.storage.get(&aid) {
+                        // The hub reloads only the assets loaded by this factory
+                        Some(asset) => reload(unsafe { &mut *asset.as_ptr() }, *payload),
+                        None => Err(anyhow::anyhow!("Asset {} is not loaded", aid)),
+                    };
+                    self.send(FromFactoryMessage::Reload(tid, aid, result));
+                }
             }
         }
     }
@@ -0,0 +1,130 @@
+use crate::AssetID;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Prefix of the asset IDs referring to the asset by its GUID.
+pub const GUID_PREFIX: &str = "guid:";
+
+/// Stable identifier of the asset. Assigned when the asset is packed for the first
+/// time and kept when its string ID changes, so the references stored by the GUID
+/// (e.g. in the scenes or the materials) survive the renames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AssetGuid(u128);
+
+#[derive(Debug, Error)]
+#[error("Invalid asset GUID: {0}")]
+pub struct InvalidGuid(String);
+
+impl AssetGuid {
+    pub fn from_u128(value: u128) -> AssetGuid {
+        AssetGuid(value)
+    }
+
+    pub fn as_u128(&self) -> u128 {
+        self.0
+    }
+
+    /// ID referring to the asset by the GUID, e.g. `guid:0123…`.
+    /// The hub resolves it to the current string ID of the asset.
+    pub fn to_id(self) -> AssetID {
+        format!("{}{}", GUID_PREFIX, self).into()
+    }
+
+    /// GUID of the ID made with `to_id`, `None` for the string IDs.
+    pub fn from_id(id: &AssetID) -> Option<AssetGuid> {
+        id.as_str().strip_prefix(GUID_PREFIX)?.parse().ok()
+    }
+}
+
+impl Display for AssetGuid {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:032x}", self.0)
+    }
+}
+
+impl FromStr for AssetGuid {
+    type Err = InvalidGuid;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 32 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(InvalidGuid(s.to_string()));
+        }
+        u128::from_str_radix(s, 16)
+            .map(AssetGuid)
+            .map_err(|_| InvalidGuid(s.to_string()))
+    }
+}
+
+// Stored as the hex string, so the GUIDs are readable in the TOML lock files
+impl Serialize for AssetGuid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for AssetGuid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        string.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// GUIDs of the assets of the container.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct AssetGuids {
+    ids: HashMap<AssetGuid, AssetID>,
+}
+
+impl AssetGuids {
+    pub fn insert(&mut self, guid: AssetGuid, id: AssetID) {
+        self.ids.insert(guid, id);
+    }
+
+    /// Current string ID of the asset.
+    pub fn id(&self, guid: AssetGuid) -> Option<&AssetID> {
+        self.ids.get(&guid)
+    }
+
+    /// GUID of the asset, `None` if it has no GUID assigned.
+    pub fn guid(&self, id: &AssetID) -> Option<AssetGuid> {
+        self.ids
+            .iter()
+            .find(|(_, known)| *known == id)
+            .map(|(guid, _)| *guid)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (AssetGuid, &AssetID)> {
+        self.ids.iter().map(|(guid, id)| (*guid, id))
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Resolves the ID referring to the asset by the GUID to its string ID.
+    /// String IDs and unknown GUIDs are returned as is.
+    /// ```
+    /// use dawn_assets::guid::{AssetGuid, AssetGuids};
+    ///
+    /// let guid = AssetGuid::from_u128(0x2a);
+    /// let mut guids = AssetGuids::default();
+    /// guids.insert(guid, "stone".into());
+    ///
+    /// assert_eq!(guid.to_id().as_str(), "guid:0000000000000000000000000000002a");
+    /// assert_eq!(guids.resolve(&guid.to_id()), "stone".into());
+    /// assert_eq!(guids.resolve(&"stone".into()), "stone".into());
+    /// ```
+    pub fn resolve(&self, id: &AssetID) -> AssetID {
+        AssetGuid::from_id(id)
+            .and_then(|guid| self.id(guid))
+            .unwrap_or(id)
+            .clone()
+    }
+}
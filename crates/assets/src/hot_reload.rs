@@ -0,0 +1,152 @@
+//! Reloading of the assets changed on disk while the application runs,
+//! e.g. the shaders and the textures being tuned.
+//!
+//! The reader watches its storage (see `FileWatcher`), and once the data
+//! of some assets has changed, reports them with `BasicReader::notify_changed`.
+//! The hub reads the read and the loaded ones again and hands the new IRs
+//! to the factories (see `BasicFactory::process_events_with_reload`).
+//! The loaded assets are replaced in place, so the `Asset` instances held by the
+//! application stay valid. Each reloaded asset is reported with the
+//! `AssetHubEvent::AssetReloaded` event.
+//!
+//! Only the data of the assets is reloaded. The new assets and the changed headers
+//! (e.g. dependencies) require the enumeration, as on start.
+
+use crate::{AssetChecksum, AssetHeader, AssetID};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    length: u64,
+}
+
+/// Polls the files and the directories (recursively) for the changes.
+/// Polling needs no platform support and works on the network drives,
+/// at the cost of walking the directories every `interval`.
+///
+/// The file is reported once it stays unchanged for `settle`, so the files
+/// still being written (e.g. the container being rebuilt) are not reported too early.
+pub struct FileWatcher {
+    paths: Vec<PathBuf>,
+    interval: Duration,
+    settle: Duration,
+    known: HashMap<PathBuf, Option<FileStamp>>,
+    // Changed files waiting to settle, with the time of the last change
+    pending: HashMap<PathBuf, Instant>,
+    last_poll: Instant,
+}
+
+impl FileWatcher {
+    /// Starts watching the paths. The files existing now are not reported.
+    pub fn new(paths: Vec<PathBuf>, interval: Duration, settle: Duration) -> Self {
+        let mut watcher = FileWatcher {
+            paths,
+            interval,
+            settle,
+            known: HashMap::new(),
+            pending: HashMap::new(),
+            last_poll: Instant::now(),
+        };
+        watcher.known = watcher.scan();
+        watcher
+    }
+
+    fn stamp(path: &Path) -> Option<FileStamp> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(FileStamp {
+            modified: metadata.modified().ok(),
+            length: metadata.len(),
+        })
+    }
+
+    fn scan_into(path: &Path, files: &mut HashMap<PathBuf, Option<FileStamp>>) {
+        match std::fs::read_dir(path) {
+            Ok(entries) => {
+                for entry in entries.flatten() {
+                    Self::scan_into(&entry.path(), files);
+                }
+            }
+            // Missing files are watched too, they may be created later
+            Err(_) => {
+                files.insert(path.to_path_buf(), Self::stamp(path));
+            }
+        }
+    }
+
+    fn scan(&self) -> HashMap<PathBuf, Option<FileStamp>> {
+        let mut files = HashMap::new();
+        for path in &self.paths {
+            Self::scan_into(path, &mut files);
+        }
+        files
+    }
+
+    /// Files created, changed or removed since they were reported last time.
+    /// Cheap to call often, the paths are walked only once per `interval`.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        if self.last_poll.elapsed() < self.interval {
+            return Vec::new();
+        }
+        self.last_poll = Instant::now();
+
+        let current = self.scan();
+        let paths: HashSet<&PathBuf> = current.keys().chain(self.known.keys()).collect();
+        for path in paths {
+            let before = self.known.get(path).copied().flatten();
+            let now = current.get(path).copied().flatten();
+            if before != now {
+                // Every change restarts the settling
+                self.pending.insert(path.clone(), self.last_poll);
+            }
+        }
+        self.known = current;
+
+        let settle = self.settle;
+        let mut changed = Vec::new();
+        self.pending.retain(|path, since| {
+            if since.elapsed() < settle {
+                return true;
+            }
+            changed.push(path.clone());
+            false
+        });
+        changed.sort();
+        changed
+    }
+}
+
+/// Assets of the `before` headers whose data differ in the `after` ones,
+/// judging by the checksums. The assets without the checksums are counted
+/// as changed. New and removed assets are not included.
+/// ```
+/// use dawn_assets::hot_reload::changed_assets;
+/// use dawn_assets::{AssetChecksum, AssetHeader};
+///
+/// let header = |id: &str, checksum: u8| AssetHeader {
+///     id: id.into(),
+///     checksum: AssetChecksum::from_bytes(&[checksum]),
+///     ..Default::default()
+/// };
+/// let before = [header("rock", 1), header("tree", 2)];
+/// let after = [header("rock", 1), header("tree", 3), header("bush", 4)];
+/// assert_eq!(changed_assets(&before, &after), vec!["tree".into()]);
+/// ```
+pub fn changed_assets(before: &[AssetHeader], after: &[AssetHeader]) -> Vec<AssetID> {
+    let before: HashMap<&AssetID, AssetChecksum> = before
+        .iter()
+        .map(|header| (&header.id, header.checksum))
+        .collect();
+    after
+        .iter()
+        .filter(|header| match before.get(&header.id) {
+            Some(checksum) => {
+                *checksum != header.checksum || header.checksum == AssetChecksum::default()
+            }
+            None => false,
+        })
+        .map(|header| header.id.clone())
+        .collect()
+}
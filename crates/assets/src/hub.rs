@@ -1,11 +1,19 @@
 use crate::factory::{FactoryBinding, FromFactoryMessage, LoadFactoryMessage, ToFactoryMessage};
+use crate::guid::AssetGuid;
+use crate::ir::custom::CustomAssetTag;
+use crate::ir::IRAsset;
 use crate::reader::{FromReaderMessage, ReaderBinding, ToReaderMessage};
 use crate::registry::{AssetRegistry, AssetState};
-use crate::requests::scheduler::{PeekResult, Scheduler, TaskDoneResult};
+use crate::requests::scheduler::{PeekResult, Scheduler};
 use crate::requests::task::{AssetTaskID, TaskCommand};
-use crate::requests::{AssetRequest, AssetRequestID};
-use crate::{Asset, AssetCastable, AssetHeader, AssetID, AssetMemoryUsage, AssetType, TypedAsset};
+use crate::requests::{AssetRequest, AssetRequestID, AssetRequestQuery, Priority};
+use crate::validation::{ValidationError, ValidationRules};
+use crate::variants::DeviceProfile;
+use crate::{
+    Asset, AssetCastable, AssetHeader, AssetID, AssetMemoryUsage, AssetType, ReadTiming, TypedAsset,
+};
 use dawn_ecs::events::TickEvent;
+use dawn_util::profile::{Counter, MonitorSample, Stopwatch};
 use evenio::component::Component;
 use evenio::event::{GlobalEvent, Receiver, Sender};
 use evenio::fetch::Single;
@@ -13,7 +21,8 @@ use evenio::handler::IntoHandler;
 use evenio::prelude::World;
 use log::{debug, error, info};
 use smallvec::{smallvec, SmallVec};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
 /// AssetHub events are used to notify the ECS world about asset-related events.
@@ -25,6 +34,103 @@ pub enum AssetHubEvent {
     AssetRead(AssetID),
     AssetLoaded(AssetID),
     AssetFreed(AssetID),
+    /// The data of the read or loaded asset has changed on disk and was reloaded.
+    /// The loaded asset is replaced in place (see `hot_reload` module).
+    AssetReloaded(AssetID),
+    /// The asset nobody used was freed automatically (see `AssetHub::set_gc`).
+    /// Sent after the `AssetFreed` of the asset.
+    AssetEvicted(AssetID),
+    /// The IR of the asset failed the validation (see `AssetHub::set_validation`)
+    /// and was not passed to the factory. The load or reload request fails too.
+    LoadFailed(AssetID, ValidationError),
+}
+
+/// Event sent every second with monitoring data about the asset reads.
+/// Sent only if the monitoring is enabled (see `AssetHub::enable_monitoring`).
+/// The timings are reported only by the readers measuring them
+/// (see `BasicReader::process_events_with_prefetch`).
+#[derive(GlobalEvent)]
+pub struct AssetHubMonitorEvent {
+    /// Number of assets read per second
+    pub reads: MonitorSample<f32>,
+    /// Time the reader waited for the asset data from the storage
+    pub io_wait: MonitorSample<Duration>,
+    /// Time the reader spent decompressing and deserializing the asset
+    pub decode: MonitorSample<Duration>,
+}
+
+struct HubMonitor {
+    reads: Counter,
+    io_wait: Stopwatch,
+    decode: Stopwatch,
+    last_update: Instant,
+    counter: usize,
+}
+
+impl HubMonitor {
+    fn new() -> Self {
+        HubMonitor {
+            reads: Counter::new(Duration::from_secs(1), 0.5),
+            io_wait: Stopwatch::new(0.5),
+            decode: Stopwatch::new(0.5),
+            last_update: Instant::now(),
+            counter: 0,
+        }
+    }
+
+    fn record(&mut self, timing: ReadTiming) {
+        self.reads.count(1);
+        self.io_wait.record(timing.io_wait);
+        self.decode.record(timing.decode);
+    }
+
+    fn cycle(&mut self) -> Option<AssetHubMonitorEvent> {
+        if self.last_update.elapsed().as_secs_f32() < 1.0 {
+            return None;
+        }
+        self.last_update = Instant::now();
+        self.reads.update();
+
+        let event = AssetHubMonitorEvent {
+            reads: self.reads.get(),
+            io_wait: self.io_wait.get(),
+            decode: self.decode.get(),
+        };
+
+        // Reset the counters each 5 seconds to get more smooth data
+        if self.counter.is_multiple_of(5) {
+            self.reads.reset();
+            self.io_wait.reset();
+            self.decode.reset();
+        }
+        self.counter += 1;
+
+        Some(event)
+    }
+}
+
+/// How often the loaded assets are checked for the usage.
+const GC_INTERVAL: Duration = Duration::from_millis(250);
+
+// Frees the loaded assets nobody holds (see `AssetHub::set_gc`)
+struct AssetGc {
+    grace: Duration,
+    // Unused loaded assets, with the time they were found unused
+    unused: HashMap<AssetID, Instant>,
+    // Free requests of the assets being evicted
+    evicting: HashMap<AssetRequestID, AssetID>,
+    last_scan: Instant,
+}
+
+impl AssetGc {
+    fn new(grace: Duration) -> Self {
+        AssetGc {
+            grace,
+            unused: HashMap::new(),
+            evicting: HashMap::new(),
+            last_scan: Instant::now(),
+        }
+    }
 }
 
 /// Error type for retrieving assets from the AssetHub.
@@ -52,6 +158,10 @@ pub enum HubError {
     RegistryError(#[from] crate::registry::RegistryError),
     #[error("Dependencies collect error: {0}")]
     DependenciesError(#[from] GetAssetError),
+    #[error("Request {0} was cancelled")]
+    RequestCancelled(AssetRequestID),
+    #[error("Asset {0} is invalid: {1}")]
+    InvalidAsset(AssetID, ValidationError),
 }
 
 struct ReadStorage {
@@ -135,6 +245,16 @@ pub struct AssetHub {
     factories: HashMap<AssetType, FactoryStorage>,
     registry: AssetRegistry,
     scheduler: Scheduler,
+    monitor: Option<HubMonitor>,
+    gc: Option<AssetGc>,
+    // Reads of the reload tasks, the read data replaces the current one
+    reloads: HashSet<AssetTaskID>,
+    // Cancelled requests, reported on the next tick
+    cancelled: Vec<AssetRequestID>,
+    // Reads of the cancelled requests, their results are discarded
+    cancelled_reads: HashSet<AssetTaskID>,
+    // Checks of the IRs before they are passed to the factories
+    validation: Option<ValidationRules>,
 }
 
 #[derive(Debug, Clone)]
@@ -149,6 +269,14 @@ pub struct AssetInfo {
     pub id: AssetID,
     pub header: AssetHeader,
     pub state: AssetInfoState,
+    /// RAM used by the IR kept for the procedural asset, if the asset is procedural.
+    pub procedural: Option<usize>,
+}
+
+impl Default for AssetHub {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AssetHub {
@@ -158,6 +286,12 @@ impl AssetHub {
             factories: HashMap::new(),
             registry: AssetRegistry::new(),
             scheduler: Scheduler::new(),
+            monitor: None,
+            gc: None,
+            reloads: HashSet::new(),
+            cancelled: Vec::new(),
+            cancelled_reads: HashSet::new(),
+            validation: None,
         }
     }
 
@@ -176,6 +310,14 @@ impl AssetHub {
         binding
     }
 
+    /// Creates a new factory binding for the user-defined asset type.
+    /// The factory will receive `IRAsset::Custom` payloads of the assets
+    /// tagged with the given tag.
+    pub fn get_custom_factory_binding(&mut self, tag: &CustomAssetTag) -> FactoryBinding {
+        info!("Registering custom asset type {} as {}", tag, tag.id());
+        self.get_factory_biding(AssetType::Custom(tag.id()))
+    }
+
     /// Creates a read binding used to communicate between the AssetHub and the asset reader.
     /// Read binding is the generic interface for bidirectional communication
     /// between the AssetHub and the asset reader.
@@ -192,7 +334,12 @@ impl AssetHub {
     }
 
     /// Lazily requests some action.
-    /// All requests are guaranteed to be executed in the order they were requested.
+    /// Consecutive reads and loads run concurrently: if several requests need
+    /// the same asset, it's read and loaded once and all of them are notified.
+    /// Frees and enumerations wait for all the previous requests to finish,
+    /// and the following requests wait for them.
+    /// Dependencies are always loaded before the assets depending on them;
+    /// if one fails, the request fails with the chain of the dependent assets.
     /// Returns a unique ID for the request that can be used to track its status.
     /// You can safely reference the assets that is not enumerated yet,
     /// since the actual validation and execution of the request is deferred
@@ -201,14 +348,155 @@ impl AssetHub {
     /// the request will fail and an `AssetHubEvent::RequestCompleted` event will be
     /// sent to the ECS world with the error message.
     pub fn request(&mut self, request: AssetRequest) -> AssetRequestID {
-        debug!("Requesting: {:?}", request);
-        self.scheduler.request(request)
+        self.request_with_priority(request, Priority::Normal)
+    }
+
+    /// Same as `request`, but the tasks of the more urgent requests are started
+    /// first, and their reads overtake the queued ones of the others
+    /// (see `BasicReader::process_events_pooled`). If the request joins
+    /// the read already queued by a less urgent one, the read is moved up.
+    pub fn request_with_priority(
+        &mut self,
+        request: AssetRequest,
+        priority: Priority,
+    ) -> AssetRequestID {
+        debug!("Requesting: {:?} ({:?})", request, priority);
+        self.scheduler.request(request, priority)
+    }
+
+    /// Loads the asset with its dependencies, see `request_with_priority`.
+    /// Wait for the `AssetHubEvent::AssetLoaded` of the asset or
+    /// the `RequestFinished` of the returned request.
+    ///
+    /// ```
+    /// use dawn_assets::hub::AssetHub;
+    /// use dawn_assets::requests::Priority;
+    ///
+    /// let mut hub = AssetHub::new();
+    /// let rid = hub.query_load("player".into(), Priority::High);
+    /// // The player has left before the asset was needed
+    /// assert!(hub.cancel(rid));
+    /// assert!(!hub.cancel(rid));
+    /// ```
+    pub fn query_load(&mut self, id: AssetID, priority: Priority) -> AssetRequestID {
+        self.request_with_priority(AssetRequest::Load(AssetRequestQuery::ByID(id)), priority)
+    }
+
+    /// Cancels the request whose assets are no longer needed.
+    /// The reads queued only for it are dropped by the readers supporting that,
+    /// the tasks already started still finish and their assets stay read or loaded.
+    /// The jobs shared with the other requests go on for them.
+    /// The request finishes with `HubError::RequestCancelled` on the next tick.
+    /// Returns `false` if the request is unknown or already finished.
+    pub fn cancel(&mut self, rid: AssetRequestID) -> bool {
+        let Some(dropped) = self.scheduler.cancel(rid) else {
+            return false;
+        };
+
+        info!("Cancelling request {}", rid);
+        if !dropped.is_empty() {
+            if let Some(reader) = &self.reader {
+                reader.send(ToReaderMessage::Cancel(dropped.clone()));
+            }
+            self.cancelled_reads.extend(dropped);
+        }
+        self.cancelled.push(rid);
+        true
+    }
+
+    /// Hints the reader that the assets (and their dependencies) will be requested soon,
+    /// so it can start fetching their data in the background.
+    /// Useful before requesting a large bundle, e.g. the next level.
+    /// Only the assets that are not read yet are hinted.
+    /// The hint is ignored by the readers not supporting it.
+    pub fn prefetch(&mut self, ids: Vec<AssetID>) -> Result<(), HubError> {
+        let reader = self.reader.as_ref().ok_or(HubError::ReaderNotRegistered)?;
+
+        // Walk the dependency closure, the requested assets go first
+        let mut queue: Vec<AssetID> = ids.iter().map(|id| self.registry.resolve(id)).collect();
+        let mut visited = HashSet::new();
+        let mut closure = Vec::new();
+        let mut index = 0;
+        while index < queue.len() {
+            let id = queue[index].clone();
+            index += 1;
+            if !visited.insert(id.clone()) {
+                continue;
+            }
+            let header = self.registry.get_header(&id)?;
+            queue.extend(
+                header
+                    .dependencies
+                    .iter()
+                    .map(|dep| self.registry.resolve(dep)),
+            );
+            if let AssetState::Empty = self.registry.get_state(&id)? {
+                closure.push(id);
+            }
+        }
+
+        if !closure.is_empty() {
+            reader.send(ToReaderMessage::Prefetch(closure));
+        }
+        Ok(())
+    }
+
+    /// Enables sending `AssetHubMonitorEvent` events to the ECS every second.
+    pub fn enable_monitoring(&mut self) {
+        self.monitor = Some(HubMonitor::new());
+    }
+
+    /// Frees the loaded assets automatically once they are unused for `grace`:
+    /// no `Asset` or `TypedAsset` of them is held outside of the hub
+    /// and no loaded asset depends on them. The dependencies of the freed asset
+    /// become unused in turn. Each freed asset is reported with `AssetHubEvent::AssetEvicted`.
+    /// Take the handles of the requested assets within the grace period,
+    /// otherwise they are freed before being used.
+    /// `None` (the default) keeps the assets loaded until they are freed by the request.
+    pub fn set_gc(&mut self, grace: Option<Duration>) {
+        self.gc = grace.map(AssetGc::new);
+    }
+
+    /// Checks the IRs before they are loaded or reloaded by the factories.
+    /// The invalid asset is reported with `AssetHubEvent::LoadFailed`
+    /// and its request fails with `HubError::InvalidAsset`.
+    /// `None` (the default) trusts the container.
+    pub fn set_validation(&mut self, rules: Option<ValidationRules>) {
+        self.validation = rules;
+    }
+
+    /// Sets the active locale used to resolve the logical asset IDs
+    /// to their locale variants. `None` selects the default variants.
+    /// Already loaded variants are not swapped automatically:
+    /// free them and request the logical IDs again.
+    pub fn set_locale(&mut self, locale: Option<String>) {
+        info!("Setting locale to {:?}", locale);
+        self.registry.set_locale(locale);
+    }
+
+    pub fn locale(&self) -> Option<&str> {
+        self.registry.locale()
+    }
+
+    /// Sets the device profile used to select the quality variants of the assets.
+    /// As with the locale, already loaded variants are not swapped automatically.
+    pub fn set_device_profile(&mut self, profile: DeviceProfile) {
+        info!("Setting device profile to {:?}", profile);
+        self.registry.set_device_profile(profile);
+    }
+
+    pub fn device_profile(&self) -> &DeviceProfile {
+        self.registry.device_profile()
     }
 
     /// Retrieves an asset by its ID.
+    /// Logical IDs of the assets with variants are resolved to the active variant
+    /// (see `set_locale` and `set_device_profile`), and the GUID references
+    /// (see `AssetGuid::to_id`) to the current string ID.
     /// If the asset is loaded, it returns an `Asset` instance.
     /// If the asset is not found or not loaded, it returns an error.
     pub fn get(&self, id: AssetID) -> Result<Asset, GetAssetError> {
+        let id = self.registry.resolve(&id);
         match self
             .registry
             .get_state(&id)
@@ -226,6 +514,80 @@ impl AssetHub {
         Ok(TypedAsset::new(self.get(id)?))
     }
 
+    /// Creates the asset from the IR made at runtime (e.g. the generated terrain
+    /// mesh), without the container. The IR goes through the same factory as
+    /// the assets read from the container: wait for the `RequestFinished`
+    /// of the returned request and get the asset with `get_typed`.
+    /// The type of the asset is taken from the IR. Use `create_procedural_with_header`
+    /// to set the dependencies, e.g. the materials of the mesh.
+    pub fn create_procedural(
+        &mut self,
+        id: AssetID,
+        ir: IRAsset,
+    ) -> Result<AssetRequestID, HubError> {
+        let header = AssetHeader {
+            id,
+            asset_type: ir.asset_type(),
+            ..Default::default()
+        };
+        self.create_procedural_with_header(header, ir)
+    }
+
+    /// Same as `create_procedural`, but with the full header.
+    /// The dependencies are loaded before the asset, as for the container assets.
+    /// The IR is kept, so the asset can be loaded again after freeing, and baked
+    /// into the container (see `procedural_assets`). The procedural assets
+    /// survive the enumeration and shadow the container assets of the same ID.
+    pub fn create_procedural_with_header(
+        &mut self,
+        header: AssetHeader,
+        ir: IRAsset,
+    ) -> Result<AssetRequestID, HubError> {
+        if !self.factories.contains_key(&header.asset_type) {
+            return Err(HubError::FactoryNotFound(header.asset_type));
+        }
+
+        let id = header.id.clone();
+        info!("Creating procedural asset {} ({})", id, header.asset_type);
+        self.registry.add_procedural(header, ir)?;
+        Ok(self.request(AssetRequest::Load(AssetRequestQuery::ByID(id))))
+    }
+
+    /// Forgets the procedural asset. It must be freed first.
+    pub fn remove_procedural(&mut self, id: AssetID) -> Result<(), HubError> {
+        if let AssetState::Loaded(_, _) = self.registry.get_state(&id)? {
+            return Err(HubError::InvalidAssetState(id));
+        }
+        self.registry.remove_procedural(&id)?;
+        Ok(())
+    }
+
+    /// Headers and IRs of the procedural assets, to bake them into a container
+    /// (e.g. with `dawn_dac::builder::ContainerBuilder::add_asset`).
+    pub fn procedural_assets(&self) -> Vec<(AssetHeader, IRAsset)> {
+        self.registry
+            .procedural()
+            .map(|(header, ir)| (header.clone(), ir.clone()))
+            .collect()
+    }
+
+    /// IDs and instances of all the loaded assets. To find the ID
+    /// of the asset instance, compare their `Asset::address`.
+    pub fn loaded_assets(&self) -> Vec<(AssetID, Asset)> {
+        self.registry
+            .keys()
+            .filter_map(|id| match self.registry.get_state(id) {
+                Ok(AssetState::Loaded(asset, _)) => Some((id.clone(), asset.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// GUID of the enumerated asset, `None` if the container assigned it none.
+    pub fn guid(&self, id: &AssetID) -> Option<AssetGuid> {
+        self.registry.guids().guid(id)
+    }
+
     /// Moves the Asset Hub into the ECS world.
     /// This will allow automatically processing async events on each main loop tick.
     /// This also will provide additional ECS events as `AssetHubEvent` that can be
@@ -234,6 +596,7 @@ impl AssetHub {
         let entity = world.spawn();
         world.insert(entity, self);
         world.add_handler(Self::tick_handler.low());
+        world.add_handler(Self::monitor_handler.low());
     }
 
     /// Collect debug information about all enumerated assets.
@@ -244,7 +607,7 @@ impl AssetHub {
             infos.push(AssetInfo {
                 id: id.clone(),
                 header: self.registry.get_header(id).unwrap().clone(),
-                state: match self.registry.get_state(&id).unwrap() {
+                state: match self.registry.get_state(id).unwrap() {
                     AssetState::Empty => AssetInfoState::Empty,
                     AssetState::Read(ir) => AssetInfoState::IR(ir.memory_usage()),
                     AssetState::Loaded(asset, usage) => AssetInfoState::Loaded {
@@ -252,6 +615,7 @@ impl AssetHub {
                         rc: asset.ref_count(),
                     },
                 },
+                procedural: self.registry.get_procedural(id).map(IRAsset::memory_usage),
             })
         }
 
@@ -268,7 +632,17 @@ impl AssetHub {
         mut hub: Single<&mut AssetHub>,
         mut sender: Sender<AssetHubEvent>,
     ) {
+        hub.collect_garbage();
+
+        for rid in std::mem::take(&mut hub.cancelled) {
+            sender.send(AssetHubEvent::RequestFinished(
+                rid,
+                Err(HubError::RequestCancelled(rid).into()),
+            ));
+        }
+
         // Peek tasks and route to the the factories
+        let mut reads = Vec::new();
         loop {
             let next = {
                 // TODO: Temporary workaround to satisfy the borrow checker.
@@ -279,13 +653,22 @@ impl AssetHub {
             match next {
                 PeekResult::Peeked(task) => {
                     let result = match task.command {
-                        TaskCommand::Enumerate => hub.send_enumerate(task.id.clone()),
-                        TaskCommand::Read(aid) => hub.send_read(task.id.clone(), aid),
-                        TaskCommand::Load(aid) => hub.send_load(task.id.clone(), aid),
-                        TaskCommand::Free(aid) => hub.send_free(task.id.clone(), aid),
+                        TaskCommand::Enumerate => hub.send_enumerate(task.id),
+                        TaskCommand::Read(aid) => {
+                            reads.push((task.id, aid));
+                            Ok(())
+                        }
+                        TaskCommand::Load(aid) => hub.send_load(task.id, aid),
+                        TaskCommand::Free(aid) => hub.send_free(task.id, aid),
+                        TaskCommand::Reload(aid) => {
+                            hub.reloads.insert(task.id);
+                            reads.push((task.id, aid));
+                            Ok(())
+                        }
                     };
                     if let Err(err) = result {
-                        hub.task_finished(task.id.clone(), Err(err.into()), &mut sender);
+                        Self::report_invalid(&err, &mut sender);
+                        hub.task_finished(task.id, Err(err.into()), &mut sender);
                     }
                 }
                 PeekResult::NoPendingTasks => break,
@@ -299,6 +682,21 @@ impl AssetHub {
             }
         }
 
+        // Raise the priority of the queued reads more urgent requests joined
+        for tid in hub.scheduler.take_boosted() {
+            let priority = hub.scheduler.job_priority(tid);
+            if let Some(reader) = &hub.reader {
+                reader.send(ToReaderMessage::Prioritize(tid, priority));
+            }
+        }
+
+        if let Err(err) = hub.send_reads(&reads) {
+            for (tid, _) in reads {
+                hub.reloads.remove(&tid);
+                hub.task_finished(tid, Err(err.clone().into()), &mut sender);
+            }
+        }
+
         // Process events from the reader
         while let Some(message) = ReadStorage::try_recv(hub.reader.as_ref()) {
             hub.recv_reader(message, &mut sender);
@@ -318,6 +716,16 @@ impl AssetHub {
         }
     }
 
+    fn monitor_handler(
+        _: Receiver<TickEvent>,
+        mut hub: Single<&mut AssetHub>,
+        mut sender: Sender<AssetHubMonitorEvent>,
+    ) {
+        if let Some(event) = hub.monitor.as_mut().and_then(|monitor| monitor.cycle()) {
+            sender.send(event);
+        }
+    }
+
     /// Processes the task completion.
     /// This updates the task pool and notifies the ECS world about the completed request.
     fn task_finished(
@@ -326,30 +734,106 @@ impl AssetHub {
         result: anyhow::Result<()>,
         sender: &mut Sender<AssetHubEvent>,
     ) {
-        // Update the task pool with the completed task
-        match self.scheduler.task_finished(tid, result) {
-            TaskDoneResult::RequestFinished(rid, result) => {
-                // Notify the ECS world about the completed request
-                sender.send(AssetHubEvent::RequestFinished(
-                    rid,
-                    result.map_err(|e| e.into()),
-                ))
+        // Update the task pool with the completed task.
+        // Several requests may be waiting for the same task
+        for (rid, result) in self.scheduler.task_finished(tid, result) {
+            if let Some(id) = self.gc.as_mut().and_then(|gc| gc.evicting.remove(&rid)) {
+                match &result {
+                    Ok(()) => sender.send(AssetHubEvent::AssetEvicted(id)),
+                    // Taken again before being freed
+                    Err(err) => debug!("Asset {} is not evicted: {}", id, err),
+                }
+            }
+
+            // Notify the ECS world about the completed request
+            sender.send(AssetHubEvent::RequestFinished(
+                rid,
+                result.map_err(|e| e.into()),
+            ))
+        }
+    }
+
+    /// Requests the free of the assets unused for the grace period, if enabled.
+    fn collect_garbage(&mut self) {
+        let Some(gc) = &mut self.gc else {
+            return;
+        };
+        if gc.last_scan.elapsed() < GC_INTERVAL {
+            return;
+        }
+        let now = Instant::now();
+        gc.last_scan = now;
+
+        // The dependencies of the loaded assets are kept with them
+        let mut loaded = Vec::new();
+        let mut required = HashSet::new();
+        for id in self.registry.keys() {
+            if let Ok(AssetState::Loaded(asset, _)) = self.registry.get_state(id) {
+                loaded.push((id, asset.ref_count()));
+                if let Ok(header) = self.registry.get_header(id) {
+                    required.extend(
+                        header
+                            .dependencies
+                            .iter()
+                            .map(|dep| self.registry.resolve(dep)),
+                    );
+                }
+            }
+        }
+
+        let mut unused = HashMap::new();
+        for (id, ref_count) in loaded {
+            // The only reference is the one of the registry
+            if ref_count > 1 || required.contains(id) || gc.evicting.values().any(|e| e == id) {
+                continue;
             }
-            _ => {}
+            let since = gc.unused.get(id).copied().unwrap_or(now);
+            if now - since < gc.grace {
+                unused.insert(id.clone(), since);
+                continue;
+            }
+
+            debug!("Evicting unused asset {}", id);
+            let rid = self.scheduler.request(
+                AssetRequest::FreeNoDeps(AssetRequestQuery::ByID(id.clone())),
+                Priority::Normal,
+            );
+            gc.evicting.insert(rid, id.clone());
         }
+        gc.unused = unused;
     }
 
     /// Receives messages from the reader and processes them.
     /// This updates the asset registry and notifies the ECS world about the asset state changes.
-    fn recv_reader(&mut self, message: FromReaderMessage, mut sender: &mut Sender<AssetHubEvent>) {
+    fn recv_reader(&mut self, message: FromReaderMessage, sender: &mut Sender<AssetHubEvent>) {
         match message {
-            FromReaderMessage::Enumerate(tid, Ok(headers)) => {
-                // Register all headers in the registry
-                self.registry.enumerate(headers);
-                self.task_finished(tid, Ok(()), &mut sender);
+            FromReaderMessage::Enumerate(tid, Ok(assets)) => {
+                // Register all headers and variants in the registry
+                self.registry
+                    .enumerate(assets.headers, assets.variants, assets.guids);
+                self.task_finished(tid, Ok(()), sender);
             }
             FromReaderMessage::Enumerate(tid, Err(err)) => {
-                self.task_finished(tid, Err(err), &mut sender);
+                self.task_finished(tid, Err(err), sender);
+            }
+            FromReaderMessage::Read(tid, aid, result) if self.cancelled_reads.remove(&tid) => {
+                // The asset may be read again by the next requests meanwhile
+                debug!("Discarding cancelled read of {}: {:?}", aid, result.err());
+                self.task_finished(tid, Ok(()), sender);
+            }
+            FromReaderMessage::Read(tid, aid, Ok(ir)) if self.reloads.remove(&tid) => {
+                match self.send_reload(tid, aid.clone(), ir) {
+                    // Finished by the factory
+                    Ok(true) => {}
+                    Ok(false) => {
+                        sender.send(AssetHubEvent::AssetReloaded(aid));
+                        self.task_finished(tid, Ok(()), sender);
+                    }
+                    Err(err) => {
+                        Self::report_invalid(&err, sender);
+                        self.task_finished(tid, Err(err.into()), sender)
+                    }
+                }
             }
             FromReaderMessage::Read(tid, aid, Ok(ir)) => {
                 // Save the IR asset to the registry
@@ -358,21 +842,34 @@ impl AssetHub {
                     .unwrap();
                 // Notify the ECS world about the read asset
                 sender.send(AssetHubEvent::AssetRead(aid.clone()));
-                self.task_finished(tid, Ok(()), &mut sender);
+                self.task_finished(tid, Ok(()), sender);
             }
             FromReaderMessage::Read(tid, _, Err(err)) => {
-                self.task_finished(tid, Err(err), &mut sender);
+                self.reloads.remove(&tid);
+                self.task_finished(tid, Err(err), sender);
+            }
+            FromReaderMessage::Changed(ids) => {
+                // New assets are picked up by the enumeration
+                let ids: Vec<AssetID> = ids
+                    .into_iter()
+                    .filter(|id| self.registry.get_header(id).is_ok())
+                    .collect();
+                if !ids.is_empty() {
+                    info!("Reloading {} changed assets", ids.len());
+                    self.request(AssetRequest::Reload(AssetRequestQuery::ByIDs(ids)));
+                }
+            }
+            FromReaderMessage::Timing(timing) => {
+                if let Some(monitor) = &mut self.monitor {
+                    monitor.record(timing);
+                }
             }
         };
     }
 
     /// Receives messages from the factories and processes them.
     /// This updates the asset registry and notifies the ECS world about the asset state changes.
-    fn recv_factory(
-        &mut self,
-        message: FromFactoryMessage,
-        mut sender: &mut Sender<AssetHubEvent>,
-    ) {
+    fn recv_factory(&mut self, message: FromFactoryMessage, sender: &mut Sender<AssetHubEvent>) {
         match message {
             FromFactoryMessage::Load(tid, aid, Ok(message)) => {
                 self.registry
@@ -387,41 +884,89 @@ impl AssetHub {
 
                 // Notify the ECS world about the loaded asset
                 sender.send(AssetHubEvent::AssetLoaded(aid.clone()));
-                self.task_finished(tid, Ok(()), &mut sender);
+                self.task_finished(tid, Ok(()), sender);
             }
             FromFactoryMessage::Load(tid, _aid, Err(err)) => {
-                self.task_finished(tid, Err(err), &mut sender);
+                self.task_finished(tid, Err(err), sender);
             }
             FromFactoryMessage::Free(tid, aid, Ok(())) => {
-                self.registry
-                    .update(aid.clone(), AssetState::Empty)
-                    .unwrap();
+                // The procedural assets cannot be read again, so their IR is restored
+                let state = match self.registry.get_procedural(&aid) {
+                    Some(ir) => AssetState::Read(ir.clone()),
+                    None => AssetState::Empty,
+                };
+                self.registry.update(aid.clone(), state).unwrap();
                 // Notify the ECS world about the loaded asset
                 sender.send(AssetHubEvent::AssetFreed(aid.clone()));
-                self.task_finished(tid, Ok(()), &mut sender);
+                self.task_finished(tid, Ok(()), sender);
             }
             FromFactoryMessage::Free(tid, _aid, Err(err)) => {
-                self.task_finished(tid, Err(err), &mut sender);
+                self.task_finished(tid, Err(err), sender);
+            }
+            FromFactoryMessage::Reload(tid, aid, Ok(usage)) => {
+                // The asset keeps its address, only the usage changes
+                if let Ok(AssetState::Loaded(asset, _)) = self.registry.get_state(&aid) {
+                    let state = AssetState::Loaded(asset.clone(), usage);
+                    self.registry.update(aid.clone(), state).unwrap();
+                }
+                sender.send(AssetHubEvent::AssetReloaded(aid));
+                self.task_finished(tid, Ok(()), sender);
+            }
+            FromFactoryMessage::Reload(tid, _aid, Err(err)) => {
+                self.task_finished(tid, Err(err), sender);
             }
         };
     }
 
+    /// Checks the IR with the rules of the hub, if enabled.
+    fn validate(&self, id: &AssetID, ir: &IRAsset) -> Result<(), HubError> {
+        match &self.validation {
+            Some(rules) => ir
+                .validate(rules)
+                .map_err(|err| HubError::InvalidAsset(id.clone(), err)),
+            None => Ok(()),
+        }
+    }
+
+    fn report_invalid(err: &HubError, sender: &mut Sender<AssetHubEvent>) {
+        if let HubError::InvalidAsset(id, reason) = err {
+            error!("Asset {} failed the validation: {}", id, reason);
+            sender.send(AssetHubEvent::LoadFailed(id.clone(), reason.clone()));
+        }
+    }
+
     /// Sends an enumerate request to the reader.
     fn send_enumerate(&mut self, task_id: AssetTaskID) -> Result<(), HubError> {
         let reader = self.reader.as_ref().ok_or(HubError::ReaderNotRegistered)?;
         for id in self.registry.keys() {
+            // The procedural assets are kept by the enumeration
+            if self.registry.get_procedural(id).is_some() {
+                continue;
+            }
             if let AssetState::Loaded(_, _) = self.registry.get_state(id)? {
                 return Err(HubError::EnumerateWhileInUse);
             }
         }
-        reader.send(ToReaderMessage::Enumerate(task_id.clone()));
+        reader.send(ToReaderMessage::Enumerate(task_id));
         Ok(())
     }
 
-    /// Sends a read request to the reader.
-    fn send_read(&mut self, task_id: AssetTaskID, id: AssetID) -> Result<(), HubError> {
+    /// Sends the read requests peeked during the tick to the reader.
+    fn send_reads(&mut self, reads: &[(AssetTaskID, AssetID)]) -> Result<(), HubError> {
+        if reads.is_empty() {
+            return Ok(());
+        }
+
         let reader = self.reader.as_ref().ok_or(HubError::ReaderNotRegistered)?;
-        reader.send(ToReaderMessage::Read(task_id.clone(), id.clone()));
+        // Hint the whole batch first, so the reader can fetch ahead
+        if reads.len() > 1 {
+            let ids = reads.iter().map(|(_, id)| id.clone()).collect();
+            reader.send(ToReaderMessage::Prefetch(ids));
+        }
+        for (task_id, id) in reads {
+            let priority = self.scheduler.job_priority(*task_id);
+            reader.send(ToReaderMessage::Read(*task_id, id.clone(), priority));
+        }
         Ok(())
     }
 
@@ -429,6 +974,7 @@ impl AssetHub {
     fn send_load(&mut self, task_id: AssetTaskID, id: AssetID) -> Result<(), HubError> {
         let header = self.registry.get_header(&id)?;
         if let AssetState::Read(ir) = self.registry.get_state(&id)? {
+            self.validate(&id, ir)?;
             let factory = self
                 .factories
                 .get(&header.asset_type)
@@ -441,13 +987,13 @@ impl AssetHub {
             }
 
             factory.send(ToFactoryMessage::Load(
-                task_id.clone(),
+                task_id,
                 id.clone(),
-                LoadFactoryMessage {
+                Box::new(LoadFactoryMessage {
                     asset_header: header.clone(),
                     ir: ir.clone(),
                     dependencies,
-                },
+                }),
             ));
             Ok(())
         } else {
@@ -455,6 +1001,48 @@ impl AssetHub {
         }
     }
 
+    /// Replaces the IR of the read asset, or sends the new IR of the loaded one
+    /// to the factory. Returns `true` if the factory finishes the task.
+    fn send_reload(
+        &mut self,
+        task_id: AssetTaskID,
+        id: AssetID,
+        ir: IRAsset,
+    ) -> Result<bool, HubError> {
+        let header = self.registry.get_header(&id)?;
+        match self.registry.get_state(&id)? {
+            AssetState::Loaded(_, _) => {
+                self.validate(&id, &ir)?;
+                let factory = self
+                    .factories
+                    .get(&header.asset_type)
+                    .ok_or(HubError::FactoryNotFound(header.asset_type))?;
+
+                let mut dependencies = HashMap::new();
+                for dep in &header.dependencies {
+                    dependencies.insert(dep.clone(), self.get(dep.clone())?);
+                }
+
+                factory.send(ToFactoryMessage::Reload(
+                    task_id,
+                    id.clone(),
+                    Box::new(LoadFactoryMessage {
+                        asset_header: header.clone(),
+                        ir,
+                        dependencies,
+                    }),
+                ));
+                Ok(true)
+            }
+            AssetState::Read(_) => {
+                self.registry.update(id, AssetState::Read(ir))?;
+                Ok(false)
+            }
+            // Freed while being read
+            AssetState::Empty => Ok(false),
+        }
+    }
+
     /// Sends a free request to the appropriate factory.
     fn send_free(&mut self, task_id: AssetTaskID, id: AssetID) -> Result<(), HubError> {
         let header = self.registry.get_header(&id)?;
@@ -468,10 +1056,281 @@ impl AssetHub {
                 .get(&header.asset_type)
                 .ok_or(HubError::FactoryNotFound(header.asset_type))?;
 
-            factory.send(ToFactoryMessage::Free(task_id.clone(), id.clone()));
+            factory.send(ToFactoryMessage::Free(task_id, id.clone()));
             Ok(())
         } else {
             Err(HubError::InvalidAssetState(id))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::any::TypeId;
+    use std::ptr::NonNull;
+
+    // Hub with the assets already loaded, each one with its dependencies.
+    // The registry holds the only handles of them.
+    fn loaded_hub(assets: &[(&str, &[&str])]) -> AssetHub {
+        let mut hub = AssetHub::new();
+        let headers = assets
+            .iter()
+            .map(|(id, deps)| AssetHeader {
+                id: (*id).into(),
+                dependencies: deps.iter().map(|dep| (*dep).into()).collect(),
+                ..Default::default()
+            })
+            .collect();
+        hub.registry
+            .enumerate(headers, Default::default(), Default::default());
+        for (id, _) in assets {
+            let asset = Asset::new(TypeId::of::<()>(), NonNull::dangling());
+            let state = AssetState::Loaded(asset, AssetMemoryUsage::new(0, 0));
+            hub.registry.update((*id).into(), state).unwrap();
+        }
+        hub
+    }
+
+    // Scans right away, whatever the GC_INTERVAL.
+    // Returns the assets being evicted, sorted
+    fn scan(hub: &mut AssetHub) -> Vec<String> {
+        hub.gc.as_mut().unwrap().last_scan -= GC_INTERVAL;
+        hub.collect_garbage();
+        let mut evicting: Vec<String> = hub
+            .gc
+            .as_ref()
+            .unwrap()
+            .evicting
+            .values()
+            .map(|id| id.as_str().to_string())
+            .collect();
+        evicting.sort();
+        evicting
+    }
+
+    // Finishes the eviction of the asset as the factory would
+    fn evicted(hub: &mut AssetHub, id: &str) {
+        let gc = hub.gc.as_mut().unwrap();
+        gc.evicting.retain(|_, evicting| evicting.as_str() != id);
+        hub.registry.update(id.into(), AssetState::Empty).unwrap();
+    }
+
+    #[test]
+    fn disabled_by_default() {
+        let mut hub = loaded_hub(&[("a", &[])]);
+        hub.collect_garbage();
+        assert!(hub.gc.is_none());
+        assert!(hub.get("a".into()).is_ok());
+    }
+
+    #[test]
+    fn evicts_unused_assets_after_their_dependents() {
+        let mut hub = loaded_hub(&[("a", &["b"]), ("b", &[]), ("c", &[])]);
+        hub.set_gc(Some(Duration::ZERO));
+
+        // The dependency is kept while the dependent is loaded
+        assert_eq!(scan(&mut hub), ["a", "c"]);
+        // Not requested twice while the eviction is in progress
+        assert_eq!(scan(&mut hub), ["a", "c"]);
+
+        evicted(&mut hub, "a");
+        evicted(&mut hub, "c");
+        assert_eq!(scan(&mut hub), ["b"]);
+    }
+
+    #[test]
+    fn keeps_held_assets() {
+        let mut hub = loaded_hub(&[("a", &[]), ("b", &[])]);
+        hub.set_gc(Some(Duration::ZERO));
+
+        let held = hub.get("a".into()).unwrap();
+        assert_eq!(scan(&mut hub), ["b"]);
+        assert_eq!(scan(&mut hub), ["b"]);
+
+        drop(held);
+        assert_eq!(scan(&mut hub), ["a", "b"]);
+    }
+
+    #[test]
+    fn waits_for_grace_period() {
+        let grace = Duration::from_secs(60);
+        let mut hub = loaded_hub(&[("a", &[]), ("b", &[])]);
+        hub.set_gc(Some(grace));
+
+        let half_way = |hub: &mut AssetHub| {
+            for since in hub.gc.as_mut().unwrap().unused.values_mut() {
+                *since -= grace / 2;
+            }
+        };
+
+        // Found unused, but not for long enough
+        assert!(scan(&mut hub).is_empty());
+        assert_eq!(hub.gc.as_ref().unwrap().unused.len(), 2);
+        half_way(&mut hub);
+
+        // Taking the asset within the grace period restarts it
+        let held = hub.get("a".into()).unwrap();
+        assert!(scan(&mut hub).is_empty());
+        assert_eq!(hub.gc.as_ref().unwrap().unused.len(), 1);
+        drop(held);
+        assert!(scan(&mut hub).is_empty());
+
+        // Only the one unused since the first scan is over it
+        half_way(&mut hub);
+        assert_eq!(scan(&mut hub), ["b"]);
+    }
+
+    // Requests with the priority, sent to the world with the hub
+    #[derive(GlobalEvent)]
+    struct PriorityRequest(&'static str, Priority);
+
+    // World with the hub of the assets not read yet, and the binding of its reader.
+    // Each of the requests is made before the first tick
+    fn hub_world(ids: &[&str], requests: &[(&'static str, Priority)]) -> (World, ReaderBinding) {
+        let mut hub = AssetHub::new();
+        let binding = hub.get_read_binding();
+        let headers = ids
+            .iter()
+            .map(|id| AssetHeader {
+                id: (*id).into(),
+                ..Default::default()
+            })
+            .collect();
+        hub.registry
+            .enumerate(headers, Default::default(), Default::default());
+        for (id, priority) in requests {
+            hub.request_with_priority(read(id), *priority);
+        }
+
+        let mut world = World::new();
+        hub.attach_to_ecs(&mut world);
+        world.add_handler(
+            |r: Receiver<PriorityRequest>, mut hub: Single<&mut AssetHub>| {
+                hub.request_with_priority(read(r.event.0), r.event.1);
+            },
+        );
+        (world, binding)
+    }
+
+    fn read(id: &str) -> AssetRequest {
+        AssetRequest::Read(AssetRequestQuery::ByID(id.into()))
+    }
+
+    // Ticks the hub, returning the reads and the priority changes sent to the reader
+    fn tick(world: &mut World, binding: &ReaderBinding) -> Vec<ToReaderMessage> {
+        world.send(TickEvent {
+            frame: 0,
+            delta: 0.0,
+            time: 0.0,
+            alpha: 1.0,
+        });
+        std::iter::from_fn(|| binding.recv(Duration::ZERO))
+            .filter(|m| !matches!(m, ToReaderMessage::Prefetch(_)))
+            .collect()
+    }
+
+    fn read_order(messages: &[ToReaderMessage]) -> Vec<(&str, Priority)> {
+        messages
+            .iter()
+            .map(|m| match m {
+                ToReaderMessage::Read(_, id, priority) => (id.as_str(), *priority),
+                other => panic!("Unexpected message {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn urgent_reads_are_sent_first() {
+        let requests = [
+            ("a", Priority::Low),
+            ("b", Priority::Low),
+            ("c", Priority::High),
+            ("d", Priority::Normal),
+        ];
+        let (mut world, binding) = hub_world(&["a", "b", "c", "d"], &requests);
+        assert_eq!(
+            read_order(&tick(&mut world, &binding)),
+            [
+                ("c", Priority::High),
+                ("d", Priority::Normal),
+                ("a", Priority::Low),
+                ("b", Priority::Low),
+            ]
+        );
+    }
+
+    #[test]
+    fn urgent_request_raises_queued_read() {
+        let (mut world, binding) = hub_world(&["a", "b"], &[("a", Priority::Low)]);
+        let sent = tick(&mut world, &binding);
+        let [ToReaderMessage::Read(task_id, _, Priority::Low)] = sent.as_slice() else {
+            panic!("Unexpected messages {:?}", sent);
+        };
+
+        // Joins the read of a instead of reading it again
+        world.send(PriorityRequest("a", Priority::High));
+        world.send(PriorityRequest("b", Priority::Low));
+        let sent = tick(&mut world, &binding);
+        assert_eq!(sent.len(), 2);
+        assert!(matches!(
+            sent[0],
+            ToReaderMessage::Prioritize(id, Priority::High) if id == *task_id
+        ));
+        assert_eq!(read_order(&sent[1..]), [("b", Priority::Low)]);
+    }
+
+    #[derive(Component, Default)]
+    struct Reported {
+        failed: Vec<(AssetID, ValidationError)>,
+        finished: Vec<(AssetRequestID, bool)>,
+    }
+
+    #[test]
+    fn invalid_asset_fails_to_load() {
+        let mut hub = AssetHub::new();
+        let binding = hub.get_read_binding();
+        let header = AssetHeader {
+            id: "texture".into(),
+            asset_type: AssetType::Texture,
+            ..Default::default()
+        };
+        hub.registry
+            .enumerate(vec![header], Default::default(), Default::default());
+        hub.set_validation(Some(ValidationRules::default()));
+        let rid = hub.query_load("texture".into(), Priority::Normal);
+
+        let mut world = World::new();
+        hub.attach_to_ecs(&mut world);
+        let entity = world.spawn();
+        world.insert(entity, Reported::default());
+        world.add_handler(
+            |r: Receiver<AssetHubEvent>, mut reported: Single<&mut Reported>| match r.event {
+                AssetHubEvent::LoadFailed(id, reason) => {
+                    reported.failed.push((id.clone(), reason.clone()))
+                }
+                AssetHubEvent::RequestFinished(rid, result) => {
+                    reported.finished.push((*rid, result.is_ok()))
+                }
+                _ => {}
+            },
+        );
+
+        let sent = tick(&mut world, &binding);
+        let [ToReaderMessage::Read(task_id, _, _)] = sent.as_slice() else {
+            panic!("Unexpected messages {:?}", sent);
+        };
+        // Rejected before it is passed to the factory, so none is registered
+        let ir = IRAsset::Texture(crate::ir::texture::IRTexture::default());
+        binding.send(FromReaderMessage::Read(*task_id, "texture".into(), Ok(ir)));
+        tick(&mut world, &binding);
+        tick(&mut world, &binding);
+
+        let reported = world.get::<Reported>(entity).unwrap();
+        assert_eq!(
+            reported.failed,
+            [("texture".into(), ValidationError::UnknownTextureType)]
+        );
+        assert_eq!(reported.finished, [(rid, false)]);
+    }
+}
@@ -0,0 +1,35 @@
+use serde::de::{Error, Visitor};
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Returns the shared copy of the string, allocating it on the first use.
+/// The pool is never shrunk: the IDs and tags are bounded by the containers
+/// loaded during the application lifetime.
+pub(crate) fn intern(str: &str) -> Arc<str> {
+    static POOL: OnceLock<Mutex<HashSet<Arc<str>>>> = OnceLock::new();
+
+    let mut pool = POOL.get_or_init(Default::default).lock().unwrap();
+    if let Some(existing) = pool.get(str) {
+        return existing.clone();
+    }
+
+    let new: Arc<str> = Arc::from(str);
+    pool.insert(new.clone());
+    new
+}
+
+/// Deserializes the string straight into the pool,
+/// without allocating it if it's already interned.
+pub(crate) struct InternVisitor;
+
+impl Visitor<'_> for InternVisitor {
+    type Value = Arc<str>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(intern(v))
+    }
+}
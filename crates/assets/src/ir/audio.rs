@@ -1,14 +1,63 @@
+use crate::AssetID;
+use claxon::FlacReader;
+use lewton::inside_ogg::OggStreamReader;
+use lewton::samples::InterleavedSamples;
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
+use std::io::{Cursor, Read, Seek, Write};
+
+/// Brotli quality of the chunks. The samples barely compress,
+/// so the higher qualities only slow down the packing.
+const CHUNK_QUALITY: i32 = 5;
+const CHUNK_LGWIN: i32 = 22;
+
+/// Fixed-duration part of the packed audio, compressed independently,
+/// so the playback can start from any chunk without decoding the previous ones.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct IRAudioChunk {
+    /// First frame of the chunk
+    pub start: usize,
+    /// Number of frames in the chunk
+    pub frames: usize,
+    /// Interleaved F32 LE samples, compressed with Brotli
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+}
+
+/// Codec of the encoded audio.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IRAudioCodec {
+    /// Ogg Vorbis
+    Vorbis,
+    Flac,
+}
+
+/// Source file of the streamed audio, kept as is and decoded block by block
+/// while playing, so the long clips take the size of the file in the memory.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct IREncodedAudio {
+    pub codec: IRAudioCodec,
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+}
 
 /// Internal representation of audio data
-/// Always storing samples in the F32 sample format
+/// Always storing samples in the F32 sample format, the channels are interleaved
 #[derive(Serialize, Deserialize, Clone)]
 pub struct IRAudio {
+    /// Samples of the unpacked audio. Empty if the audio is packed into the chunks
+    /// or encoded.
     pub data: Vec<f32>,
     pub sample_rate: u32,
     pub channels: u8,
     pub length: usize, // In samples
+    /// Chunks of the packed audio, ordered by the first frame (the seek table).
+    /// Empty if the audio is unpacked.
+    pub chunks: Vec<IRAudioChunk>,
+    /// Encoded source of the streamed audio, see `AudioStreamDecoder`.
+    pub encoded: Option<IREncodedAudio>,
+    /// Caption track of the clip. Must be declared as a dependency of the audio asset.
+    pub captions: Option<AssetID>,
 }
 
 impl Debug for IRAudio {
@@ -18,6 +67,9 @@ impl Debug for IRAudio {
             .field("sample_rate", &self.sample_rate)
             .field("channels", &self.channels)
             .field("length", &self.length)
+            .field("chunks", &self.chunks.len())
+            .field("encoded", &self.encoded.as_ref().map(|e| e.codec))
+            .field("captions", &self.captions)
             .finish()
     }
 }
@@ -29,6 +81,9 @@ impl Default for IRAudio {
             sample_rate: 44100,
             channels: 2,
             length: 0,
+            chunks: vec![],
+            encoded: None,
+            captions: None,
         }
     }
 }
@@ -37,6 +92,216 @@ impl IRAudio {
     pub fn memory_usage(&self) -> usize {
         let mut sum = size_of::<IRAudio>();
         sum += self.data.capacity() * size_of::<f32>();
+        sum += self.captions.as_ref().map_or(0, |id| id.memory_usage());
+        for chunk in &self.chunks {
+            sum += size_of::<IRAudioChunk>() + chunk.data.capacity();
+        }
+        sum += self.encoded.as_ref().map_or(0, |e| e.data.capacity());
         sum
     }
+
+    pub fn is_packed(&self) -> bool {
+        !self.chunks.is_empty()
+    }
+
+    pub fn is_encoded(&self) -> bool {
+        self.encoded.is_some()
+    }
+
+    /// Packs the samples into the chunks of the given number of frames.
+    /// Does nothing if the audio is already packed or empty.
+    pub fn pack(&mut self, chunk_frames: usize) -> anyhow::Result<()> {
+        if self.is_packed() || self.data.is_empty() {
+            return Ok(());
+        }
+        let chunk_frames = chunk_frames.max(1);
+        let channels = self.channels.max(1) as usize;
+
+        let mut chunks = Vec::with_capacity(self.data.len().div_ceil(chunk_frames * channels));
+        for (index, samples) in self.data.chunks(chunk_frames * channels).enumerate() {
+            let mut bytes = Vec::with_capacity(size_of_val(samples));
+            for sample in samples {
+                bytes.extend_from_slice(&sample.to_le_bytes());
+            }
+
+            let params = brotli::enc::BrotliEncoderParams {
+                quality: CHUNK_QUALITY,
+                lgwin: CHUNK_LGWIN,
+                ..Default::default()
+            };
+            let mut writer = brotli::CompressorWriter::with_params(Vec::new(), 4096, &params);
+            writer.write_all(&bytes)?;
+
+            chunks.push(IRAudioChunk {
+                start: index * chunk_frames,
+                frames: samples.len() / channels,
+                data: writer.into_inner(),
+            });
+        }
+
+        self.chunks = chunks;
+        self.data = vec![];
+        Ok(())
+    }
+
+    /// Index of the chunk containing the frame, `None` if it's past the end.
+    pub fn chunk_at(&self, frame: usize) -> Option<usize> {
+        let index = self
+            .chunks
+            .partition_point(|chunk| chunk.start <= frame)
+            .checked_sub(1)?;
+        (frame < self.chunks[index].start + self.chunks[index].frames).then_some(index)
+    }
+
+    /// Decodes the samples of the chunk into the buffer, replacing its content.
+    pub fn decode_chunk(&self, index: usize, output: &mut Vec<f32>) -> anyhow::Result<()> {
+        let chunk = self
+            .chunks
+            .get(index)
+            .ok_or_else(|| anyhow::anyhow!("Chunk {} out of range", index))?;
+
+        let mut bytes = Vec::new();
+        brotli::Decompressor::new(chunk.data.as_slice(), 4096).read_to_end(&mut bytes)?;
+        let expected = chunk.frames * self.channels.max(1) as usize;
+        if bytes.len() != expected * size_of::<f32>() {
+            return Err(anyhow::anyhow!(
+                "Chunk {} has {} bytes, expected {} samples",
+                index,
+                bytes.len(),
+                expected
+            ));
+        }
+
+        output.clear();
+        output.extend(
+            bytes
+                .chunks_exact(size_of::<f32>())
+                .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap())),
+        );
+        Ok(())
+    }
+
+    /// Decodes all the chunks back into the samples.
+    pub fn unpack(&mut self) -> anyhow::Result<()> {
+        let mut data = Vec::with_capacity(self.length * self.channels.max(1) as usize);
+        let mut chunk = Vec::new();
+        for index in 0..self.chunks.len() {
+            self.decode_chunk(index, &mut chunk)?;
+            data.extend_from_slice(&chunk);
+        }
+        self.data = data;
+        self.chunks = vec![];
+        Ok(())
+    }
+}
+
+enum DecoderInner<R: Read + Seek> {
+    Vorbis(Box<OggStreamReader<R>>),
+    Flac {
+        reader: Box<FlacReader<R>>,
+        // Reused between the blocks
+        buffer: Vec<i32>,
+        scale: f32,
+    },
+}
+
+/// Decodes the encoded audio (see `IREncodedAudio`) block by block.
+/// The size of the blocks is up to the codec, usually a few thousand frames.
+///
+/// ```no_run
+/// use dawn_assets::ir::audio::IRAudio;
+///
+/// # fn example(clip: &IRAudio) -> anyhow::Result<()> {
+/// let encoded = clip.encoded.as_ref().unwrap();
+/// let mut decoder = encoded.decoder()?;
+/// let mut block = Vec::new();
+/// while decoder.decode_block(&mut block)? {
+///     // Interleaved samples of `decoder.channels()` channels
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct AudioStreamDecoder<R: Read + Seek> {
+    inner: DecoderInner<R>,
+    sample_rate: u32,
+    channels: u8,
+}
+
+impl<R: Read + Seek> AudioStreamDecoder<R> {
+    /// Reads the headers of the stream.
+    pub fn new(codec: IRAudioCodec, reader: R) -> anyhow::Result<Self> {
+        Ok(match codec {
+            IRAudioCodec::Vorbis => {
+                let reader = OggStreamReader::new(reader)?;
+                AudioStreamDecoder {
+                    sample_rate: reader.ident_hdr.audio_sample_rate,
+                    channels: reader.ident_hdr.audio_channels,
+                    inner: DecoderInner::Vorbis(Box::new(reader)),
+                }
+            }
+            IRAudioCodec::Flac => {
+                let reader = FlacReader::new(reader)?;
+                let info = reader.streaminfo();
+                AudioStreamDecoder {
+                    sample_rate: info.sample_rate,
+                    channels: info.channels as u8,
+                    inner: DecoderInner::Flac {
+                        reader: Box::new(reader),
+                        buffer: Vec::new(),
+                        scale: 1.0 / (1u64 << (info.bits_per_sample - 1)) as f32,
+                    },
+                }
+            }
+        })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn channels(&self) -> u8 {
+        self.channels
+    }
+
+    /// Decodes the next block of the interleaved samples into the buffer,
+    /// replacing its content. Returns `false` at the end of the stream.
+    /// The block may be empty (e.g. the first packet of Vorbis).
+    pub fn decode_block(&mut self, output: &mut Vec<f32>) -> anyhow::Result<bool> {
+        output.clear();
+        match &mut self.inner {
+            DecoderInner::Vorbis(reader) => {
+                match reader.read_dec_packet_generic::<InterleavedSamples<f32>>()? {
+                    Some(block) => {
+                        output.extend_from_slice(&block.samples);
+                        Ok(true)
+                    }
+                    None => Ok(false),
+                }
+            }
+            DecoderInner::Flac {
+                reader,
+                buffer,
+                scale,
+            } => {
+                let Some(block) = reader.blocks().read_next_or_eof(std::mem::take(buffer))? else {
+                    return Ok(false);
+                };
+                output.reserve(block.len() as usize);
+                for frame in 0..block.duration() {
+                    for channel in 0..block.channels() {
+                        output.push(block.sample(channel, frame) as f32 * *scale);
+                    }
+                }
+                *buffer = block.into_buffer();
+                Ok(true)
+            }
+        }
+    }
+}
+
+impl IREncodedAudio {
+    /// Decoder reading the data of the asset.
+    pub fn decoder(&self) -> anyhow::Result<AudioStreamDecoder<Cursor<&[u8]>>> {
+        AudioStreamDecoder::new(self.codec, Cursor::new(self.data.as_slice()))
+    }
 }
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+/// Text shown while the audio plays the time range.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IRCaption {
+    /// Time from the start of the clip in seconds.
+    pub start: f32,
+    pub end: f32,
+    /// Who is speaking, if tagged in the source.
+    pub speaker: Option<String>,
+    pub text: String,
+}
+
+/// Caption track of the audio clip. The clip references the track
+/// (see `IRAudio::captions`), so the track is loaded with the clip.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct IRCaptions {
+    /// Ordered by the start time. The entries may overlap.
+    pub entries: Vec<IRCaption>,
+}
+
+impl IRCaptions {
+    pub fn memory_usage(&self) -> usize {
+        let mut sum = size_of::<IRCaptions>();
+        for entry in &self.entries {
+            sum += size_of::<IRCaption>() + entry.text.capacity();
+            sum += entry.speaker.as_ref().map_or(0, |s| s.capacity());
+        }
+        sum
+    }
+
+    /// Time the last entry ends at, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.entries.iter().map(|e| e.end).fold(0.0, f32::max)
+    }
+}
@@ -0,0 +1,118 @@
+use crate::CustomTypeID;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use thiserror::Error;
+
+/// Namespace reserved for the asset types defined by the engine itself.
+pub const RESERVED_NAMESPACE: &str = "dawn";
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CustomAssetTagError {
+    #[error("Tag '{0}' must have the form 'namespace:name'")]
+    InvalidFormat(String),
+    #[error("Tag part '{0}' must be non-empty and contain only [a-z0-9_-]")]
+    InvalidPart(String),
+    #[error("Namespace '{0}' is reserved")]
+    ReservedNamespace(String),
+}
+
+/// Namespaced tag of a user-defined asset type, e.g. `mygame:dialogue`.
+/// The namespace (usually the crate or the project name) prevents collisions
+/// between the asset types defined by different libraries.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CustomAssetTag {
+    namespace: String,
+    name: String,
+}
+
+fn validate_part(part: &str) -> Result<(), CustomAssetTagError> {
+    let valid = !part.is_empty()
+        && part
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(CustomAssetTagError::InvalidPart(part.to_string()))
+    }
+}
+
+impl CustomAssetTag {
+    pub fn new(namespace: &str, name: &str) -> Result<Self, CustomAssetTagError> {
+        validate_part(namespace)?;
+        validate_part(name)?;
+        if namespace == RESERVED_NAMESPACE {
+            return Err(CustomAssetTagError::ReservedNamespace(
+                namespace.to_string(),
+            ));
+        }
+
+        Ok(CustomAssetTag {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+        })
+    }
+
+    /// Parses the tag from the `namespace:name` form.
+    /// ```
+    /// use dawn_assets::ir::custom::CustomAssetTag;
+    ///
+    /// let tag = CustomAssetTag::parse("mygame:dialogue").unwrap();
+    /// assert_eq!(tag.namespace(), "mygame");
+    /// assert_eq!(tag.name(), "dialogue");
+    /// assert!(CustomAssetTag::parse("dawn:shader").is_err());
+    /// ```
+    pub fn parse(tag: &str) -> Result<Self, CustomAssetTagError> {
+        match tag.split_once(':') {
+            Some((namespace, name)) => Self::new(namespace, name),
+            None => Err(CustomAssetTagError::InvalidFormat(tag.to_string())),
+        }
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.namespace
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Stable identifier of the tag, used as the `AssetType::Custom` payload.
+    pub fn id(&self) -> CustomTypeID {
+        CustomTypeID::from_tag(&self.namespace, &self.name)
+    }
+}
+
+impl std::fmt::Display for CustomAssetTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.namespace, self.name)
+    }
+}
+
+/// Internal representation of a user-defined asset.
+/// The engine treats the payload as opaque bytes, it's up to the factory
+/// registered for the tag to decode it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct IRCustom {
+    pub tag: CustomAssetTag,
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+}
+
+impl Debug for IRCustom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IRCustom")
+            .field("tag", &self.tag)
+            .field("data_length", &self.data.len())
+            .finish()
+    }
+}
+
+impl IRCustom {
+    pub fn memory_usage(&self) -> usize {
+        let mut sum = size_of::<IRCustom>();
+        sum += self.tag.namespace.capacity() + self.tag.name.capacity();
+        sum += self.data.capacity();
+        sum
+    }
+}
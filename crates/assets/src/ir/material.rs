@@ -69,12 +69,12 @@ impl IRMaterial {
         } else {
             0
         };
-        sum += if let Some(ref texture) = self.normal.as_ref().map(|x| &x.texture) {
+        sum += if let Some(texture) = self.normal.as_ref().map(|x| &x.texture) {
             texture.memory_usage()
         } else {
             0
         };
-        sum += if let Some(ref texture) = self.occlusion.as_ref().map(|x| &x.texture) {
+        sum += if let Some(texture) = self.occlusion.as_ref().map(|x| &x.texture) {
             texture.memory_usage()
         } else {
             0
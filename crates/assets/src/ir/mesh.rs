@@ -100,7 +100,7 @@ pub enum IRTopology {
     Triangles,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub enum IRIndexType {
     U16,
     U32,
@@ -124,6 +124,13 @@ pub struct IRMesh {
     pub submesh: Vec<IRSubMesh>,
     pub bounds: IRMeshBounds,
     pub index_type: IRIndexType,
+    /// The mesh is static (e.g. the level geometry), so the submeshes sharing
+    /// the material can be merged into a single draw when loaded.
+    #[serde(default)]
+    pub static_batching: bool,
+    /// Hierarchy over the triangles for the precise picking and the collision
+    /// cooking. Built at the pack time if requested.
+    pub bvh: Option<IRMeshBvh>,
 }
 
 impl IRSubMesh {
@@ -194,6 +201,287 @@ impl IRMesh {
             sum += submesh.vertices.capacity() * size_of::<IRMeshVertex>();
             sum += submesh.indices.capacity() * size_of::<u32>();
         }
+        if let Some(bvh) = &self.bvh {
+            sum += bvh.memory_usage();
+        }
         sum
     }
 }
+
+/// Maximum number of the triangles in the leaf of the `IRMeshBvh`.
+pub const IR_BVH_LEAF_TRIANGLES: usize = 4;
+
+/// Node of the `IRMeshBvh`. The left child of the inner node
+/// is stored right after it, the right one at the `offset`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IRBvhNode {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    /// Index of the first triangle of the leaf, or of the right child of the inner node.
+    pub offset: u32,
+    /// Number of the triangles of the leaf, zero for the inner node.
+    pub count: u32,
+}
+
+/// Bounding volume hierarchy over the triangles of the mesh.
+/// The first node is the root.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct IRMeshBvh {
+    pub nodes: Vec<IRBvhNode>,
+    /// Triangles in the mesh space, ordered by the leaves.
+    pub triangles: Vec<[[f32; 3]; 3]>,
+}
+
+impl Debug for IRMeshBvh {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IRMeshBvh")
+            .field("nodes_count", &self.nodes.len())
+            .field("triangles_count", &self.triangles.len())
+            .finish()
+    }
+}
+
+impl IRMeshBvh {
+    /// Splits the triangles at the median of the centroids along the longest axis.
+    /// The same triangles always give the same hierarchy.
+    pub fn build(triangles: Vec<[Vec3; 3]>) -> Self {
+        let mut items = triangles
+            .into_iter()
+            .map(|triangle| (triangle, (triangle[0] + triangle[1] + triangle[2]) / 3.0))
+            .collect::<Vec<_>>();
+        let mut nodes = Vec::with_capacity(items.len().div_ceil(IR_BVH_LEAF_TRIANGLES) * 2);
+        if !items.is_empty() {
+            Self::build_node(&mut nodes, &mut items, 0);
+        }
+
+        IRMeshBvh {
+            nodes,
+            triangles: items
+                .into_iter()
+                .map(|(triangle, _)| triangle.map(|v| v.to_array()))
+                .collect(),
+        }
+    }
+
+    fn build_node(nodes: &mut Vec<IRBvhNode>, items: &mut [([Vec3; 3], Vec3)], first: usize) {
+        let mut min = Vec3::splat(f32::MAX);
+        let mut max = Vec3::splat(f32::MIN);
+        let mut centroid_min = Vec3::splat(f32::MAX);
+        let mut centroid_max = Vec3::splat(f32::MIN);
+        for (triangle, centroid) in items.iter() {
+            for vertex in triangle {
+                min = min.min(*vertex);
+                max = max.max(*vertex);
+            }
+            centroid_min = centroid_min.min(*centroid);
+            centroid_max = centroid_max.max(*centroid);
+        }
+
+        let index = nodes.len();
+        nodes.push(IRBvhNode {
+            min: min.to_array(),
+            max: max.to_array(),
+            offset: first as u32,
+            count: items.len() as u32,
+        });
+
+        // The triangles with the same centroid cannot be split
+        let extent = centroid_max - centroid_min;
+        if items.len() <= IR_BVH_LEAF_TRIANGLES || extent.max_element() <= 0.0 {
+            return;
+        }
+
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+        let middle = items.len() / 2;
+        items.select_nth_unstable_by(middle, |a, b| a.1[axis].total_cmp(&b.1[axis]));
+
+        let (left, right) = items.split_at_mut(middle);
+        Self::build_node(nodes, left, first);
+        let right_index = nodes.len();
+        Self::build_node(nodes, right, first + middle);
+        nodes[index].offset = right_index as u32;
+        nodes[index].count = 0;
+    }
+
+    /// Returns the distance along the ray to the nearest triangle (both sides)
+    /// and the index of the triangle in `triangles`, or `None` if the ray misses.
+    /// The distance is in the units of the direction length.
+    /// The nodes referring out of the hierarchy are skipped, so the corrupted
+    /// hierarchy gives the wrong result, but never panics or hangs
+    /// (it's rejected by the validation, see `dawn_assets::validation`).
+    pub fn intersect_ray(&self, origin: Vec3, direction: Vec3) -> Option<(f32, usize)> {
+        let inverse = direction.recip();
+        let mut nearest: Option<(f32, usize)> = None;
+        let mut stack = Vec::with_capacity(64);
+        if !self.nodes.is_empty() {
+            stack.push(0usize);
+        }
+
+        while let Some(index) = stack.pop() {
+            let Some(node) = self.nodes.get(index) else {
+                continue;
+            };
+            // Slab method, same as for the picking boxes
+            let t1 = (Vec3::from(node.min) - origin) * inverse;
+            let t2 = (Vec3::from(node.max) - origin) * inverse;
+            let near = t1.min(t2).max_element();
+            let far = t1.max(t2).min_element();
+            if near > far || far < 0.0 || nearest.is_some_and(|(t, _)| near > t) {
+                continue;
+            }
+
+            if node.count == 0 {
+                // The children are always after the parent, otherwise it's a cycle
+                if node.offset as usize > index + 1 {
+                    stack.push(node.offset as usize);
+                    stack.push(index + 1);
+                }
+                continue;
+            }
+
+            let first = node.offset as usize;
+            let end = first.saturating_add(node.count as usize);
+            let Some(triangles) = self.triangles.get(first..end) else {
+                continue;
+            };
+            for (i, triangle) in (first..).zip(triangles) {
+                let [a, b, c] = triangle.map(Vec3::from);
+                if let Some(t) = intersect_triangle(origin, direction, a, b, c) {
+                    if nearest.is_none_or(|(nearest, _)| t < nearest) {
+                        nearest = Some((t, i));
+                    }
+                }
+            }
+        }
+        nearest
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.nodes.capacity() * size_of::<IRBvhNode>()
+            + self.triangles.capacity() * size_of::<[[f32; 3]; 3]>()
+    }
+}
+
+// Möller–Trumbore
+fn intersect_triangle(origin: Vec3, direction: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < f32::EPSILON {
+        // Parallel to the triangle
+        return None;
+    }
+
+    let inverse = 1.0 / determinant;
+    let s = origin - a;
+    let u = s.dot(p) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = s.cross(edge1);
+    let v = direction.dot(q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(q) * inverse;
+    (t >= 0.0).then_some(t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Deterministic pseudo-random points in the unit cube
+    fn points(seed: u32) -> impl FnMut() -> Vec3 {
+        let mut state = seed;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            (state % 10_000) as f32 / 10_000.0
+        };
+        move || Vec3::new(next(), next(), next())
+    }
+
+    fn soup(count: usize) -> Vec<[Vec3; 3]> {
+        let mut point = points(0x9E3779B9);
+        (0..count)
+            .map(|_| {
+                let center = point() * 10.0;
+                [point(), point(), point()].map(|v| center + v - 0.5)
+            })
+            .collect()
+    }
+
+    fn brute_force(bvh: &IRMeshBvh, origin: Vec3, direction: Vec3) -> Option<f32> {
+        bvh.triangles
+            .iter()
+            .filter_map(|[a, b, c]| {
+                let [a, b, c] = [a, b, c].map(|v| Vec3::from(*v));
+                intersect_triangle(origin, direction, a, b, c)
+            })
+            .min_by(f32::total_cmp)
+    }
+
+    #[test]
+    fn bvh_matches_brute_force() {
+        let bvh = IRMeshBvh::build(soup(200));
+        assert_eq!(bvh.triangles.len(), 200);
+        assert!(bvh.nodes.len() > 1);
+
+        let mut point = points(12345);
+        let mut hits = 0;
+        for _ in 0..500 {
+            let origin = point() * 14.0 - 2.0;
+            let direction = (point() * 10.0 - origin).normalize();
+            let expected = brute_force(&bvh, origin, direction);
+            let found = bvh.intersect_ray(origin, direction);
+            assert_eq!(found.map(|(t, _)| t), expected);
+            if let Some((t, i)) = found {
+                let [a, b, c] = bvh.triangles[i].map(Vec3::from);
+                assert_eq!(intersect_triangle(origin, direction, a, b, c), Some(t));
+                hits += 1;
+            }
+        }
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn bvh_misses() {
+        let bvh = IRMeshBvh::build(soup(50));
+        // Away from the triangles, and past them
+        assert_eq!(bvh.intersect_ray(Vec3::splat(-5.0), Vec3::NEG_X), None);
+        assert_eq!(bvh.intersect_ray(Vec3::splat(20.0), Vec3::ONE), None);
+        assert_eq!(
+            IRMeshBvh::build(vec![]).intersect_ray(Vec3::ZERO, Vec3::X),
+            None
+        );
+    }
+
+    #[test]
+    fn corrupted_bvh_does_not_panic() {
+        let mut bvh = IRMeshBvh::build(soup(50));
+        let origin = Vec3::new(5.0, 5.0, -20.0);
+
+        // The root pointing at itself
+        bvh.nodes[0].offset = 0;
+        bvh.intersect_ray(origin, Vec3::Z);
+        bvh.nodes[0].offset = u32::MAX;
+        bvh.intersect_ray(origin, Vec3::Z);
+
+        // The leaves out of the triangles
+        for node in bvh.nodes.iter_mut().filter(|n| n.count > 0) {
+            node.offset = u32::MAX - 1;
+        }
+        bvh.nodes[0].offset = 2;
+        bvh.intersect_ray(origin, Vec3::Z);
+    }
+}
@@ -1,6 +1,10 @@
 pub mod audio;
+pub mod captions;
+pub mod custom;
 pub mod mesh;
+pub mod music;
 pub mod notes;
+pub mod settings;
 pub mod shader;
 pub mod texture;
 pub mod material;
@@ -8,16 +12,22 @@ pub mod font;
 
 use std::fmt::Debug;
 use crate::ir::audio::IRAudio;
+use crate::ir::captions::IRCaptions;
 use crate::ir::mesh::IRMesh;
+use crate::ir::music::IRMusic;
 use crate::ir::notes::IRNotes;
+use crate::ir::settings::IRSettings;
 use crate::ir::shader::IRShader;
 use crate::ir::texture::IRTexture;
 use crate::ir::material::IRMaterial;
 use serde::{Deserialize, Serialize};
 use crate::ir::font::IRFont;
+use crate::ir::custom::IRCustom;
+use crate::AssetType;
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub enum IRAsset {
+    #[default]
     Unknown,
     Shader(IRShader),
     Audio(IRAudio),
@@ -26,15 +36,31 @@ pub enum IRAsset {
     Mesh(IRMesh),
     Material(IRMaterial),
     Font(IRFont),
+    Custom(IRCustom),
+    Music(IRMusic),
+    Captions(IRCaptions),
+    Settings(IRSettings),
 }
 
-impl Default for IRAsset {
-    fn default() -> Self {
-        IRAsset::Unknown
+impl IRAsset {
+    /// Type of the asset the IR is loaded as.
+    pub fn asset_type(&self) -> AssetType {
+        match self {
+            IRAsset::Unknown => AssetType::Unknown,
+            IRAsset::Shader(_) => AssetType::Shader,
+            IRAsset::Audio(_) => AssetType::Audio,
+            IRAsset::Texture(_) => AssetType::Texture,
+            IRAsset::Notes(_) => AssetType::Notes,
+            IRAsset::Mesh(_) => AssetType::Mesh,
+            IRAsset::Material(_) => AssetType::Material,
+            IRAsset::Font(_) => AssetType::Font,
+            IRAsset::Custom(custom) => AssetType::Custom(custom.tag.id()),
+            IRAsset::Music(_) => AssetType::Music,
+            IRAsset::Captions(_) => AssetType::Captions,
+            IRAsset::Settings(_) => AssetType::Settings,
+        }
     }
-}
 
-impl IRAsset {
     pub fn memory_usage(&self) -> usize {
         match self {
             IRAsset::Unknown => 0,
@@ -45,6 +71,10 @@ impl IRAsset {
             IRAsset::Mesh(mesh) => mesh.memory_usage(),
             IRAsset::Material(material) => material.memory_usage(),
             IRAsset::Font(font) => font.memory_usage(),
+            IRAsset::Custom(custom) => custom.memory_usage(),
+            IRAsset::Music(music) => music.memory_usage(),
+            IRAsset::Captions(captions) => captions.memory_usage(),
+            IRAsset::Settings(settings) => settings.memory_usage(),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,124 @@
+use crate::AssetID;
+use serde::{Deserialize, Serialize};
+
+/// Point of the music the transition waits for.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IRMusicQuantize {
+    Immediate,
+    Beat,
+    #[default]
+    Bar,
+}
+
+/// Stem played as a part of the section.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IRMusicLayer {
+    /// Audio asset of the stem. Must be declared as a dependency of the music asset.
+    pub stem: AssetID,
+    /// The layer is silent below this intensity.
+    pub fade_in_start: f32,
+    /// The layer is played at full volume from this intensity.
+    pub fade_in_end: f32,
+}
+
+impl IRMusicLayer {
+    /// Volume of the layer at the given intensity.
+    pub fn volume(&self, intensity: f32) -> f32 {
+        if self.fade_in_end <= self.fade_in_start {
+            return if intensity >= self.fade_in_start {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        ((intensity - self.fade_in_start) / (self.fade_in_end - self.fade_in_start)).clamp(0.0, 1.0)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IRMusicSection {
+    pub name: String,
+    pub layers: Vec<IRMusicLayer>,
+    /// Start over when the longest stem ends, otherwise the section goes silent.
+    pub looped: bool,
+}
+
+/// Rule of the transition between the sections.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IRMusicTransition {
+    /// Section the rule applies from, any section if not set.
+    pub from: Option<String>,
+    /// Section the rule applies to, any section if not set.
+    pub to: Option<String>,
+    pub quantize: IRMusicQuantize,
+    /// Duration of the crossfade between the sections in seconds.
+    pub crossfade: f32,
+}
+
+impl Default for IRMusicTransition {
+    fn default() -> Self {
+        IRMusicTransition {
+            from: None,
+            to: None,
+            quantize: IRMusicQuantize::Bar,
+            crossfade: 0.5,
+        }
+    }
+}
+
+/// Adaptive music: sections of the layered stems. The intensity parameter
+/// sets the volumes of the layers, the transitions between the sections
+/// are quantized to the beats of the music.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IRMusic {
+    pub bpm: f32,
+    pub beats_per_bar: u32,
+    pub sections: Vec<IRMusicSection>,
+    /// The first matching rule is used, the default one if nothing matches.
+    pub transitions: Vec<IRMusicTransition>,
+    /// Time in seconds the layer takes to reach the volume after the intensity change.
+    pub intensity_fade: f32,
+}
+
+impl Default for IRMusic {
+    fn default() -> Self {
+        IRMusic {
+            bpm: 120.0,
+            beats_per_bar: 4,
+            sections: vec![],
+            transitions: vec![],
+            intensity_fade: 1.0,
+        }
+    }
+}
+
+impl IRMusic {
+    pub fn section(&self, name: &str) -> Option<usize> {
+        self.sections.iter().position(|s| s.name == name)
+    }
+
+    /// Finds the rule of the transition between the sections.
+    pub fn transition(&self, from: Option<&str>, to: &str) -> IRMusicTransition {
+        let matches = |rule: &Option<String>, name: Option<&str>| match rule {
+            Some(rule) => Some(rule.as_str()) == name,
+            None => true,
+        };
+        self.transitions
+            .iter()
+            .find(|t| matches(&t.from, from) && matches(&t.to, Some(to)))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        let mut sum = size_of::<IRMusic>();
+        for section in &self.sections {
+            sum += size_of::<IRMusicSection>() + section.name.capacity();
+            for layer in &section.layers {
+                sum += size_of::<IRMusicLayer>() + layer.stem.memory_usage();
+            }
+        }
+        sum += self.transitions.capacity() * size_of::<IRMusicTransition>();
+        sum
+    }
+}
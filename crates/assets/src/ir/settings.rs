@@ -0,0 +1,99 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum IRSettingValue {
+    Bool(bool),
+    Int(i64),
+    Float(f32),
+    /// E.g. the color or the curve points.
+    Vector(Vec<f32>),
+    Text(String),
+}
+
+/// Named values of the settings asset, e.g. the render settings preset of the level.
+/// The nested tables of the source are flattened into the dotted keys:
+///
+/// ```
+/// use dawn_assets::ir::settings::{IRSettingValue, IRSettings};
+///
+/// let mut settings = IRSettings::default();
+/// settings.values.insert("bloom.intensity".into(), IRSettingValue::Float(0.8));
+/// settings.values.insert("shadows.resolution".into(), IRSettingValue::Int(2048));
+///
+/// assert_eq!(settings.float("bloom.intensity"), Some(0.8));
+/// // The integers are read as floats too, not the other way round
+/// assert_eq!(settings.float("shadows.resolution"), Some(2048.0));
+/// assert_eq!(settings.int("bloom.intensity"), None);
+/// assert_eq!(settings.section("bloom").count(), 1);
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct IRSettings {
+    pub values: BTreeMap<String, IRSettingValue>,
+}
+
+impl IRSettings {
+    pub fn memory_usage(&self) -> usize {
+        let mut sum = size_of::<IRSettings>();
+        for (key, value) in &self.values {
+            sum += key.capacity() + size_of::<IRSettingValue>();
+            sum += match value {
+                IRSettingValue::Vector(vector) => vector.capacity() * size_of::<f32>(),
+                IRSettingValue::Text(text) => text.capacity(),
+                _ => 0,
+            };
+        }
+        sum
+    }
+
+    pub fn get(&self, key: &str) -> Option<&IRSettingValue> {
+        self.values.get(key)
+    }
+
+    pub fn bool(&self, key: &str) -> Option<bool> {
+        match self.get(key)? {
+            IRSettingValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn int(&self, key: &str) -> Option<i64> {
+        match self.get(key)? {
+            IRSettingValue::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn float(&self, key: &str) -> Option<f32> {
+        match self.get(key)? {
+            IRSettingValue::Float(value) => Some(*value),
+            IRSettingValue::Int(value) => Some(*value as f32),
+            _ => None,
+        }
+    }
+
+    pub fn vector(&self, key: &str) -> Option<&[f32]> {
+        match self.get(key)? {
+            IRSettingValue::Vector(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn text(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            IRSettingValue::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Values under the `prefix.` keys, with the prefix stripped.
+    pub fn section<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = (&'a str, &'a IRSettingValue)> + 'a {
+        self.values.iter().filter_map(move |(key, value)| {
+            let rest = key.strip_prefix(prefix)?.strip_prefix('.')?;
+            Some((rest, value))
+        })
+    }
+}
@@ -2,8 +2,9 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum IRShaderSourceKind {
+    #[default]
     Fragment,
     Geometry,
     Vertex,
@@ -18,14 +19,8 @@ pub enum IRShaderSourceKind {
     // PrecompiledTessellationControl,
 }
 
-impl Default for IRShaderSourceKind {
-    fn default() -> Self {
-        IRShaderSourceKind::Fragment
-    }
-}
-
 /// Internal representation of shader data
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct IRShader {
     pub compile_options: Vec<String>,
     pub sources: HashMap<IRShaderSourceKind, Vec<u8>>,
@@ -40,20 +35,11 @@ impl Debug for IRShader {
     }
 }
 
-impl Default for IRShader {
-    fn default() -> Self {
-        IRShader {
-            compile_options: vec![],
-            sources: Default::default(),
-        }
-    }
-}
-
 impl IRShader {
     pub fn memory_usage(&self) -> usize {
         let mut sum = 0;
         sum += size_of::<IRShader>();
-        for (_, source) in &self.sources {
+        for source in self.sources.values() {
             sum += source.capacity();
         }
         for option in &self.compile_options {
@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum IRTextureType {
+    #[default]
     Unknown,
     Texture1D {
         width: u32,
@@ -44,9 +45,55 @@ pub enum IRTextureType {
     },
 }
 
-impl Default for IRTextureType {
-    fn default() -> Self {
-        IRTextureType::Unknown
+impl IRTextureType {
+    /// Number of the texels of the base level, all faces and layers included.
+    /// `None` for the types without the data (unknown, multisample),
+    /// and if the number overflows (e.g. for the corrupted sizes).
+    pub fn texels(&self) -> Option<usize> {
+        let factors: &[u32] = match *self {
+            IRTextureType::Unknown
+            | IRTextureType::Texture2DMultisample { .. }
+            | IRTextureType::Texture2DMultisampleArray { .. } => return None,
+            IRTextureType::Texture1D { width } => &[width],
+            IRTextureType::Texture2D { width, height } => &[width, height],
+            IRTextureType::TextureCube { size } => &[size, size, 6],
+            IRTextureType::Texture3D {
+                width,
+                height,
+                depth,
+            } => &[width, height, depth],
+            IRTextureType::Texture2DArray {
+                width,
+                height,
+                layers,
+            } => &[width, height, layers],
+            IRTextureType::TextureCubeArray { size, layers } => &[size, size, 6, layers],
+            IRTextureType::TextureBuffer { size } => &[size],
+        };
+        factors.iter().try_fold(1usize, |texels, factor| {
+            texels.checked_mul(*factor as usize)
+        })
+    }
+
+    /// Width, height and depth of the base level (1 for the missing ones).
+    pub fn extent(&self) -> [u32; 3] {
+        match *self {
+            IRTextureType::Unknown => [0, 0, 0],
+            IRTextureType::Texture1D { width } => [width, 1, 1],
+            IRTextureType::Texture2D { width, height }
+            | IRTextureType::Texture2DArray { width, height, .. }
+            | IRTextureType::Texture2DMultisample { width, height, .. }
+            | IRTextureType::Texture2DMultisampleArray { width, height, .. } => [width, height, 1],
+            IRTextureType::TextureCube { size } | IRTextureType::TextureCubeArray { size, .. } => {
+                [size, size, 1]
+            }
+            IRTextureType::Texture3D {
+                width,
+                height,
+                depth,
+            } => [width, height, depth],
+            IRTextureType::TextureBuffer { size } => [size, 1, 1],
+        }
     }
 }
 
@@ -63,7 +110,7 @@ pub enum IRPixelDataType {
     F32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum IRPixelFormat {
     Unknown,
     /// Red only.
@@ -71,6 +118,7 @@ pub enum IRPixelFormat {
     /// Red, green.
     R8G8,
     /// Red, green, blue.
+    #[default]
     R8G8B8,
     /// Red, green, blue, alpha.
     R8G8B8A8,
@@ -88,40 +136,41 @@ pub enum IRPixelFormat {
     R32G32B32A32FLOAT,
 }
 
-impl Default for IRPixelFormat {
-    fn default() -> Self {
-        IRPixelFormat::R8G8B8
+impl IRPixelFormat {
+    /// Size of the pixel in bytes, `None` if the format is unknown.
+    pub fn bytes_per_pixel(&self) -> Option<usize> {
+        Some(match self {
+            IRPixelFormat::Unknown => return None,
+            IRPixelFormat::R8 => 1,
+            IRPixelFormat::R8G8 | IRPixelFormat::R16 => 2,
+            IRPixelFormat::R8G8B8 => 3,
+            IRPixelFormat::R8G8B8A8 | IRPixelFormat::R16G16 => 4,
+            IRPixelFormat::R16G16B16 => 6,
+            IRPixelFormat::R16G16B16A16 => 8,
+            IRPixelFormat::R32G32B32FLOAT => 12,
+            IRPixelFormat::R32G32B32A32FLOAT => 16,
+        })
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub enum IRTextureFilter {
     Nearest,
+    #[default]
     Linear,
 }
 
-impl Default for IRTextureFilter {
-    fn default() -> Self {
-        IRTextureFilter::Linear
-    }
-}
-
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub enum IRTextureWrap {
     ClampToEdge,
     ClampToBorder,
+    #[default]
     Repeat,
     MirroredRepeat,
 }
 
-impl Default for IRTextureWrap {
-    fn default() -> Self {
-        IRTextureWrap::Repeat
-    }
-}
-
 /// Internal representation of texture data
-#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Hash, Default)]
 pub struct IRTexture {
     // Texture data is stored as an interleaved byte array,
     // in GPU-friendly format
@@ -153,22 +202,6 @@ impl Debug for IRTexture {
     }
 }
 
-impl Default for IRTexture {
-    fn default() -> Self {
-        IRTexture {
-            data: vec![],
-            texture_type: Default::default(),
-            pixel_format: Default::default(),
-            use_mipmaps: false,
-            min_filter: Default::default(),
-            mag_filter: Default::default(),
-            wrap_s: Default::default(),
-            wrap_t: Default::default(),
-            wrap_r: Default::default(),
-        }
-    }
-}
-
 impl IRTexture {
     pub fn memory_usage(&self) -> usize {
         let mut sum = size_of::<IRTexture>();
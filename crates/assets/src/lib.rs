@@ -1,17 +1,26 @@
-use serde::{Deserialize, Serialize};
+use crate::intern::{intern, InternVisitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use smallvec::SmallVec;
 use std::any::TypeId;
-use std::collections::HashSet;
 use std::marker::PhantomData;
 use std::ptr::NonNull;
 use std::sync::Arc;
+use std::time::Duration;
 
+pub mod guid;
+mod intern;
 pub mod ir;
+pub mod units;
+pub mod validation;
+pub mod variants;
 
 #[cfg(feature = "hub")]
 pub mod binding;
 #[cfg(feature = "hub")]
 pub mod factory;
 #[cfg(feature = "hub")]
+pub mod hot_reload;
+#[cfg(feature = "hub")]
 pub mod hub;
 #[cfg(feature = "hub")]
 pub mod reader;
@@ -19,10 +28,19 @@ pub mod reader;
 pub(crate) mod registry;
 #[cfg(feature = "hub")]
 pub mod requests;
+#[cfg(feature = "hub")]
+pub mod snapshot;
+#[cfg(feature = "hub")]
+pub mod streaming;
+
+/// Version and the enabled features of the crate.
+pub fn build_info() -> dawn_util::build_info::CrateBuildInfo {
+    dawn_util::crate_build_info!["hub"]
+}
 
 /// Deterministic checksum of an asset's data and header.
 /// Can be used to verify that an asset hasn't been tampered with.
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub struct AssetChecksum([u8; 16]);
 
 impl AssetChecksum {
@@ -48,12 +66,6 @@ impl AssetChecksum {
     }
 }
 
-impl Default for AssetChecksum {
-    fn default() -> Self {
-        AssetChecksum([0; 16])
-    }
-}
-
 /// Metadata about an asset
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct AssetHeader {
@@ -61,12 +73,13 @@ pub struct AssetHeader {
     pub id: AssetID,
     /// Type of the asset.
     pub asset_type: AssetType,
-    /// Checksum of the asset's data and header.
+    /// Checksum of the asset's serialized data.
+    /// Default if the writer of the container does not calculate it.
     pub checksum: AssetChecksum,
     /// Dependencies of the asset required during loading.
-    pub dependencies: HashSet<AssetID>,
+    pub dependencies: AssetDependencies,
     /// Additional tags for the asset.
-    pub tags: Vec<String>,
+    pub tags: AssetTags,
     /// Author of the asset.
     pub author: Option<String>,
     /// Type of the Asset's license or link to it.
@@ -77,18 +90,29 @@ impl Default for AssetHeader {
     fn default() -> Self {
         AssetHeader {
             id: AssetID::default(),
-            tags: Vec::new(),
+            tags: AssetTags::new(),
             asset_type: AssetType::Unknown,
             checksum: AssetChecksum::default(),
-            dependencies: HashSet::new(),
+            dependencies: AssetDependencies::new(),
             license: None,
             author: None,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+/// Time spent by the reader on one asset.
+/// Reported to the hub monitor (see `hub::AssetHubMonitorEvent`).
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ReadTiming {
+    /// Time spent waiting for the data from the storage.
+    pub io_wait: Duration,
+    /// Time spent decompressing and deserializing the data.
+    pub decode: Duration,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum AssetType {
+    #[default]
     Unknown,
     Shader,
     Texture,
@@ -97,6 +121,12 @@ pub enum AssetType {
     Material,
     Mesh,
     Font,
+    /// User-defined asset type. See `ir::custom::CustomAssetTag`.
+    Custom(CustomTypeID),
+    Music,
+    Captions,
+    /// Named values, e.g. the render settings preset. See `ir::settings::IRSettings`.
+    Settings,
 }
 
 impl std::fmt::Display for AssetType {
@@ -110,16 +140,61 @@ impl std::fmt::Display for AssetType {
             AssetType::Material => write!(f, "Material"),
             AssetType::Mesh => write!(f, "Mesh"),
             AssetType::Font => write!(f, "Font"),
+            AssetType::Custom(id) => write!(f, "Custom({})", id),
+            AssetType::Music => write!(f, "Music"),
+            AssetType::Captions => write!(f, "Captions"),
+            AssetType::Settings => write!(f, "Settings"),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub struct AssetID(String);
+/// Identifier of a user-defined asset type.
+/// Derived from the namespaced tag using the FNV-1a hash,
+/// so it is stable across builds and platforms.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CustomTypeID(u64);
+
+impl CustomTypeID {
+    pub const fn from_tag(namespace: &str, name: &str) -> CustomTypeID {
+        const OFFSET: u64 = 0xcbf29ce484222325;
+        const PRIME: u64 = 0x100000001b3;
+
+        const fn feed(mut hash: u64, bytes: &[u8]) -> u64 {
+            let mut i = 0;
+            while i < bytes.len() {
+                hash ^= bytes[i] as u64;
+                hash = hash.wrapping_mul(PRIME);
+                i += 1;
+            }
+            hash
+        }
+
+        let hash = feed(OFFSET, namespace.as_bytes());
+        let hash = feed(hash, b":");
+        CustomTypeID(feed(hash, name.as_bytes()))
+    }
+
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl std::fmt::Display for CustomTypeID {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Identifier of the asset.
+/// IDs are interned: the equal IDs share the same allocation,
+/// so cloning them is cheap and thousands of headers referencing
+/// each other do not duplicate the strings.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct AssetID(Arc<str>);
 
 impl AssetID {
     pub fn new(str: String) -> AssetID {
-        AssetID(str)
+        AssetID(intern(&str))
     }
     pub fn as_str(&self) -> &str {
         &self.0
@@ -132,19 +207,25 @@ impl AssetID {
 
 impl From<String> for AssetID {
     fn from(str: String) -> Self {
-        AssetID(str)
+        AssetID(intern(&str))
     }
 }
 
 impl From<&str> for AssetID {
     fn from(str: &str) -> Self {
-        AssetID(str.to_string())
+        AssetID(intern(str))
     }
 }
 
-impl Default for AssetID {
-    fn default() -> Self {
-        AssetID(String::new())
+impl Serialize for AssetID {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for AssetID {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(InternVisitor).map(AssetID)
     }
 }
 
@@ -154,9 +235,144 @@ impl std::fmt::Display for AssetID {
     }
 }
 
-impl Default for AssetType {
-    fn default() -> Self {
-        AssetType::Unknown
+/// Tag of the asset, used to request groups of assets.
+/// Interned the same way as `AssetID`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct AssetTag(Arc<str>);
+
+impl AssetTag {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for AssetTag {
+    fn from(str: String) -> Self {
+        AssetTag(intern(&str))
+    }
+}
+
+impl From<&str> for AssetTag {
+    fn from(str: &str) -> Self {
+        AssetTag(intern(str))
+    }
+}
+
+impl PartialEq<str> for AssetTag {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl std::fmt::Display for AssetTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Serialize for AssetTag {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for AssetTag {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(InternVisitor).map(AssetTag)
+    }
+}
+
+/// Tags of the asset. Assets rarely have more than a couple of them,
+/// so they are stored inline.
+pub type AssetTags = SmallVec<[AssetTag; 2]>;
+
+/// Set of the asset dependencies.
+/// Assets usually depend on a few others, so the IDs are stored inline
+/// in a small vector instead of a hash set.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct AssetDependencies(SmallVec<[AssetID; 4]>);
+
+impl AssetDependencies {
+    pub fn new() -> Self {
+        AssetDependencies(SmallVec::new())
+    }
+
+    /// Adds the dependency. Returns `false` if it was already present.
+    pub fn insert(&mut self, id: AssetID) -> bool {
+        if self.contains(&id) {
+            return false;
+        }
+        self.0.push(id);
+        true
+    }
+
+    /// Removes the dependency. Returns `false` if it was not present.
+    pub fn remove(&mut self, id: &AssetID) -> bool {
+        match self.0.iter().position(|dep| dep == id) {
+            Some(index) => {
+                self.0.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn contains(&self, id: &AssetID) -> bool {
+        self.0.contains(id)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, AssetID> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+// Order does not matter, same as for the set
+impl PartialEq for AssetDependencies {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|id| other.contains(id))
+    }
+}
+
+impl Eq for AssetDependencies {}
+
+impl Extend<AssetID> for AssetDependencies {
+    fn extend<T: IntoIterator<Item = AssetID>>(&mut self, iter: T) {
+        for id in iter {
+            self.insert(id);
+        }
+    }
+}
+
+impl FromIterator<AssetID> for AssetDependencies {
+    fn from_iter<T: IntoIterator<Item = AssetID>>(iter: T) -> Self {
+        let mut dependencies = AssetDependencies::new();
+        dependencies.extend(iter);
+        dependencies
+    }
+}
+
+impl<'a> IntoIterator for &'a AssetDependencies {
+    type Item = &'a AssetID;
+    type IntoIter = std::slice::Iter<'a, AssetID>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+// Deduplicate the IDs, the containers are not trusted
+impl<'de> Deserialize<'de> for AssetDependencies {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let ids = SmallVec::<[AssetID; 4]>::deserialize(deserializer)?;
+        Ok(ids.into_iter().collect())
     }
 }
 
@@ -168,17 +384,25 @@ struct AssetInner {
     ptr: NonNull<()>,
 }
 
+// The pointee is owned by its factory, which keeps it alive until the last
+// reference is dropped, and it is only ever handed out as a shared reference.
+unsafe impl Send for AssetInner {}
+unsafe impl Sync for AssetInner {}
+
 #[derive(Debug, Clone)]
 pub struct Asset(Arc<AssetInner>);
 
-unsafe impl Send for Asset {}
-unsafe impl Sync for Asset {}
-
 impl Asset {
     pub fn new(tid: TypeId, ptr: NonNull<()>) -> Asset {
         Asset(Arc::new(AssetInner { tid, ptr }))
     }
 
+    /// Address identifying the loaded asset, shared by all its clones.
+    /// Used to find the ID of the asset (see `AssetHub::loaded_assets`).
+    pub fn address(&self) -> usize {
+        Arc::as_ptr(&self.0) as usize
+    }
+
     #[allow(dead_code)]
     pub(crate) fn ref_count(&self) -> usize {
         Arc::strong_count(&self.0)
@@ -234,6 +458,10 @@ impl<T: AssetCastable> TypedAsset<T> {
     pub fn cast(&self) -> &T {
         self.inner.cast()
     }
+
+    pub fn asset(&self) -> &Asset {
+        &self.inner
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -1,21 +1,63 @@
 use crate::binding::Binding;
+use crate::guid::AssetGuids;
 use crate::ir::IRAsset;
 use crate::requests::task::AssetTaskID;
-use crate::{AssetHeader, AssetID};
+use crate::requests::Priority;
+use crate::variants::AssetVariants;
+use crate::{AssetHeader, AssetID, ReadTiming};
 use crossbeam_channel::Receiver;
 use crossbeam_channel::Sender;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::{Condvar, Mutex};
 use std::time::Duration;
 
+// Reads taken while the queued read waits. Past that it's taken first,
+// so a stream of the more urgent reads can't starve it
+const MAX_WAIT: usize = 16;
+
 #[derive(Debug)]
 pub enum ToReaderMessage {
     Enumerate(AssetTaskID),
-    Read(AssetTaskID, AssetID),
+    Read(AssetTaskID, AssetID, Priority),
+    /// Hint that the assets will be read soon, in the given order.
+    /// Readers may start fetching their data in the background.
+    Prefetch(Vec<AssetID>),
+    /// The read is needed more urgently than it was sent with.
+    Prioritize(AssetTaskID, Priority),
+    /// The reads are not needed anymore. The readers queueing the reads
+    /// drop the ones not started yet, answering them with an error.
+    Cancel(Vec<AssetTaskID>),
+}
+
+/// Result of the enumeration: headers of all the available assets,
+/// the variants of the logical assets (see `variants` module)
+/// and the GUIDs of the assets (see `guid` module).
+#[derive(Debug, Default)]
+pub struct EnumeratedAssets {
+    pub headers: Vec<AssetHeader>,
+    pub variants: AssetVariants,
+    pub guids: AssetGuids,
+}
+
+impl From<Vec<AssetHeader>> for EnumeratedAssets {
+    fn from(headers: Vec<AssetHeader>) -> Self {
+        EnumeratedAssets {
+            headers,
+            variants: AssetVariants::default(),
+            guids: AssetGuids::default(),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum FromReaderMessage {
-    Enumerate(AssetTaskID, anyhow::Result<Vec<AssetHeader>>),
+    Enumerate(AssetTaskID, anyhow::Result<EnumeratedAssets>),
     Read(AssetTaskID, AssetID, anyhow::Result<IRAsset>),
+    /// Sent after a successful read by the readers measuring it.
+    Timing(ReadTiming),
+    /// Data of the assets has changed in the storage (see `hot_reload` module).
+    Changed(Vec<AssetID>),
 }
 
 pub struct ReaderBinding {
@@ -37,11 +79,92 @@ impl ReaderBinding {
     }
 }
 
+struct QueuedRead {
+    priority: Priority,
+    // Order of arrival, the reads of the same priority are taken in it
+    order: usize,
+    // Reads taken from the queue before this one arrived
+    taken_before: usize,
+    task_id: AssetTaskID,
+    asset_id: AssetID,
+}
+
+impl PartialEq for QueuedRead {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for QueuedRead {}
+
+impl PartialOrd for QueuedRead {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedRead {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+#[derive(Default)]
+struct ReadQueue {
+    reads: BinaryHeap<QueuedRead>,
+    in_flight: usize,
+    next_order: usize,
+    taken: usize,
+    closed: bool,
+}
+
+impl ReadQueue {
+    fn is_idle(&self) -> bool {
+        self.reads.is_empty() && self.in_flight == 0
+    }
+
+    fn push(&mut self, task_id: AssetTaskID, asset_id: AssetID, priority: Priority) {
+        self.reads.push(QueuedRead {
+            priority,
+            order: self.next_order,
+            taken_before: self.taken,
+            task_id,
+            asset_id,
+        });
+        self.next_order += 1;
+    }
+
+    // The most urgent read, unless the oldest one has waited for too long
+    fn take(&mut self) -> Option<QueuedRead> {
+        let oldest = self.reads.iter().min_by_key(|r| r.order)?;
+        let read = if self.taken - oldest.taken_before >= MAX_WAIT {
+            let order = oldest.order;
+            let mut reads = std::mem::take(&mut self.reads).into_vec();
+            let index = reads.iter().position(|r| r.order == order)?;
+            let read = reads.swap_remove(index);
+            self.reads = reads.into();
+            read
+        } else {
+            self.reads.pop()?
+        };
+        self.taken += 1;
+        Some(read)
+    }
+}
+
 // It uses a queue to receive load requests and another queue to send out read assets.
 pub struct BasicReader {
     binding: Option<ReaderBinding>,
 }
 
+impl Default for BasicReader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl BasicReader {
     pub fn new() -> Self {
         BasicReader { binding: None }
@@ -65,30 +188,303 @@ impl BasicReader {
         self.binding = Some(binding);
     }
 
-    pub fn process_events<E, R>(&self, enumerate: E, read: R, timeout: Duration)
+    /// Tells the hub the data of the assets has changed, so it reloads
+    /// the ones already read or loaded (see `hot_reload` module).
+    pub fn notify_changed(&self, ids: Vec<AssetID>) {
+        if !ids.is_empty() {
+            self.send(FromReaderMessage::Changed(ids));
+        }
+    }
+
+    pub fn process_events<E, H, R>(&self, enumerate: E, read: R, timeout: Duration)
     where
-        E: Fn() -> anyhow::Result<Vec<AssetHeader>>,
+        E: Fn() -> anyhow::Result<H>,
+        H: Into<EnumeratedAssets>,
         R: Fn(AssetID) -> anyhow::Result<IRAsset>,
+    {
+        self.process_events_inner(
+            enumerate,
+            |id| read(id).map(|asset| (asset, None)),
+            |_| {},
+            timeout,
+        )
+    }
+
+    /// Same as `process_events`, but for the readers able to fetch the data
+    /// in the background (see `dawn_dac::prefetch::PrefetchReader`).
+    /// `prefetch` receives the IDs the hub is going to read soon,
+    /// `read` also returns the time spent on the asset.
+    pub fn process_events_with_prefetch<E, H, R, P>(
+        &self,
+        enumerate: E,
+        read: R,
+        prefetch: P,
+        timeout: Duration,
+    ) where
+        E: Fn() -> anyhow::Result<H>,
+        H: Into<EnumeratedAssets>,
+        R: Fn(AssetID) -> anyhow::Result<(IRAsset, ReadTiming)>,
+        P: Fn(Vec<AssetID>),
+    {
+        self.process_events_inner(
+            enumerate,
+            |id| read(id).map(|(asset, timing)| (asset, Some(timing))),
+            prefetch,
+            timeout,
+        )
+    }
+
+    fn process_events_inner<E, H, R, P>(
+        &self,
+        enumerate: E,
+        read: R,
+        prefetch: P,
+        timeout: Duration,
+    ) where
+        E: Fn() -> anyhow::Result<H>,
+        H: Into<EnumeratedAssets>,
+        R: Fn(AssetID) -> anyhow::Result<(IRAsset, Option<ReadTiming>)>,
+        P: Fn(Vec<AssetID>),
     {
         while let Some(msg) = self.recv(timeout) {
             match msg {
                 ToReaderMessage::Enumerate(task_id) => match enumerate() {
-                    Ok(headers) => {
-                        self.send(FromReaderMessage::Enumerate(task_id, Ok(headers)));
+                    Ok(assets) => {
+                        self.send(FromReaderMessage::Enumerate(task_id, Ok(assets.into())));
                     }
                     Err(err) => {
                         self.send(FromReaderMessage::Enumerate(task_id, Err(err)));
                     }
                 },
-                ToReaderMessage::Read(task_id, asset_id) => match read(asset_id.clone()) {
-                    Ok(asset) => {
-                        self.send(FromReaderMessage::Read(task_id, asset_id, Ok(asset)));
+                ToReaderMessage::Read(task_id, asset_id, _) => {
+                    let result = read(asset_id.clone());
+                    self.send_read(task_id, asset_id, result);
+                }
+                ToReaderMessage::Prefetch(ids) => prefetch(ids),
+                // The reads are answered in the order of arrival,
+                // there is nothing queued to reorder or drop
+                ToReaderMessage::Prioritize(_, _) | ToReaderMessage::Cancel(_) => {}
+            }
+        }
+    }
+
+    fn send_read(
+        &self,
+        task_id: AssetTaskID,
+        asset_id: AssetID,
+        result: anyhow::Result<(IRAsset, Option<ReadTiming>)>,
+    ) {
+        match result {
+            Ok((asset, timing)) => {
+                self.send(FromReaderMessage::Read(task_id, asset_id, Ok(asset)));
+                if let Some(timing) = timing {
+                    self.send(FromReaderMessage::Timing(timing));
+                }
+            }
+            Err(err) => {
+                self.send(FromReaderMessage::Read(task_id, asset_id, Err(err)));
+            }
+        }
+    }
+
+    /// Same as `process_events_with_prefetch`, but the reads are queued
+    /// by their priority and run on up to `workers` threads, so the decompression
+    /// and deserialization of the assets run in parallel. The urgent reads
+    /// overtake the queued ones, but a read waiting for too many others is taken
+    /// first, so it's not starved. The cancelled reads are dropped
+    /// (see `AssetHub::request_with_priority` and `AssetHub::cancel`).
+    /// The workers are spawned on the first reads and joined once all
+    /// the reads are answered and no message arrives for the `timeout`.
+    pub fn process_events_pooled<E, H, R, P>(
+        &self,
+        enumerate: E,
+        read: R,
+        prefetch: P,
+        workers: usize,
+        timeout: Duration,
+    ) where
+        E: Fn() -> anyhow::Result<H>,
+        H: Into<EnumeratedAssets>,
+        R: Fn(AssetID) -> anyhow::Result<(IRAsset, ReadTiming)> + Sync,
+        P: Fn(Vec<AssetID>),
+    {
+        let queue = Mutex::new(ReadQueue::default());
+        let changed = Condvar::new();
+        std::thread::scope(|scope| {
+            let mut spawned = 0;
+            loop {
+                match self.recv(timeout) {
+                    Some(ToReaderMessage::Enumerate(task_id)) => {
+                        let assets = enumerate().map(Into::into);
+                        self.send(FromReaderMessage::Enumerate(task_id, assets));
                     }
-                    Err(err) => {
-                        self.send(FromReaderMessage::Read(task_id, asset_id, Err(err)));
+                    Some(ToReaderMessage::Read(task_id, asset_id, priority)) => {
+                        let mut state = queue.lock().unwrap();
+                        state.push(task_id, asset_id, priority);
+                        // Spawn a worker unless the running ones are enough
+                        if spawned < workers.max(1) && state.reads.len() + state.in_flight > spawned
+                        {
+                            std::thread::Builder::new()
+                                .name(format!("asset-read-{}", spawned))
+                                .spawn_scoped(scope, || self.read_worker(&queue, &changed, &read))
+                                .expect("Failed to spawn the asset read worker");
+                            spawned += 1;
+                        }
+                        changed.notify_all();
                     }
-                },
+                    Some(ToReaderMessage::Prefetch(ids)) => prefetch(ids),
+                    Some(ToReaderMessage::Prioritize(task_id, priority)) => {
+                        let mut state = queue.lock().unwrap();
+                        let mut reads = std::mem::take(&mut state.reads).into_vec();
+                        for read in reads.iter_mut().filter(|r| r.task_id == task_id) {
+                            read.priority = read.priority.max(priority);
+                        }
+                        state.reads = reads.into();
+                    }
+                    Some(ToReaderMessage::Cancel(ids)) => {
+                        let mut state = queue.lock().unwrap();
+                        let (dropped, kept) = std::mem::take(&mut state.reads)
+                            .into_iter()
+                            .partition::<Vec<_>, _>(|r| ids.contains(&r.task_id));
+                        state.reads = kept.into();
+                        drop(state);
+                        for read in dropped {
+                            let error = anyhow::anyhow!("Read of {} cancelled", read.asset_id);
+                            self.send(FromReaderMessage::Read(
+                                read.task_id,
+                                read.asset_id,
+                                Err(error),
+                            ));
+                        }
+                    }
+                    None => {
+                        // Keep listening while the reads are in progress,
+                        // the urgent ones may still overtake the queued ones
+                        let state = queue.lock().unwrap();
+                        if state.is_idle() {
+                            break;
+                        }
+                        let _ = changed.wait_timeout(state, timeout).unwrap();
+                    }
+                }
+            }
+
+            queue.lock().unwrap().closed = true;
+            changed.notify_all();
+        });
+    }
+
+    fn read_worker<R>(&self, queue: &Mutex<ReadQueue>, changed: &Condvar, read: &R)
+    where
+        R: Fn(AssetID) -> anyhow::Result<(IRAsset, ReadTiming)>,
+    {
+        let mut state = queue.lock().unwrap();
+        loop {
+            if let Some(next) = state.take() {
+                state.in_flight += 1;
+                drop(state);
+
+                let result =
+                    read(next.asset_id.clone()).map(|(asset, timing)| (asset, Some(timing)));
+                self.send_read(next.task_id, next.asset_id, result);
+
+                state = queue.lock().unwrap();
+                state.in_flight -= 1;
+                changed.notify_all();
+            } else if state.closed {
+                break;
+            } else {
+                state = changed.wait(state).unwrap();
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::requests::AssetRequestID;
+    use std::sync::mpsc;
+
+    // Reads the assets on a single worker. The first read blocks until
+    // the others are queued. Returns the order the assets are read in
+    fn read_order(queued: &[(String, Priority)]) -> Vec<String> {
+        let (binding, sender, _receiver) = ReaderBinding::new();
+        let mut reader = BasicReader::new();
+        reader.bind(binding);
+
+        let (started, order) = mpsc::channel();
+        let (release, gate) = mpsc::channel();
+        let gate = Mutex::new(gate);
+        let read = |id: AssetID| {
+            started.send(id.as_str().to_string()).unwrap();
+            if id.as_str() == "first" {
+                gate.lock().unwrap().recv().unwrap();
+            }
+            let timing = ReadTiming {
+                io_wait: Duration::ZERO,
+                decode: Duration::ZERO,
+            };
+            Ok((IRAsset::default(), timing))
+        };
+        // Called once all the reads sent before are queued
+        let prefetch = |_| release.send(()).unwrap();
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                reader.process_events_pooled(
+                    || Ok(Vec::<AssetHeader>::new()),
+                    read,
+                    prefetch,
+                    1,
+                    Duration::from_millis(10),
+                )
+            });
+
+            let task = || AssetTaskID::new(AssetRequestID::new());
+            let first = ToReaderMessage::Read(task(), "first".into(), Priority::Normal);
+            sender.send(first).unwrap();
+            assert_eq!(order.recv().unwrap(), "first");
+            for (id, priority) in queued {
+                let message = ToReaderMessage::Read(task(), id.as_str().into(), *priority);
+                sender.send(message).unwrap();
+            }
+            sender.send(ToReaderMessage::Prefetch(vec![])).unwrap();
+            drop(sender);
+        });
+
+        std::iter::once("first".to_string())
+            .chain(order.try_iter())
+            .collect()
+    }
+
+    #[test]
+    fn urgent_reads_overtake_queued_ones() {
+        let queued = [
+            ("low0", Priority::Low),
+            ("low1", Priority::Low),
+            ("normal", Priority::Normal),
+            ("high", Priority::High),
+        ]
+        .map(|(id, priority)| (id.to_string(), priority));
+        assert_eq!(
+            read_order(&queued),
+            vec!["first", "high", "normal", "low0", "low1"]
+        );
+    }
+
+    #[test]
+    fn waiting_reads_are_not_starved() {
+        let queued: Vec<(String, Priority)> = std::iter::once(("low".to_string(), Priority::Low))
+            .chain((0..MAX_WAIT + 4).map(|i| (format!("high{}", i), Priority::High)))
+            .collect();
+        let order = read_order(&queued);
+        assert_eq!(order.len(), queued.len() + 1);
+        // The first read was taken before the low one arrived
+        assert_eq!(order[MAX_WAIT + 1], "low");
+        assert!(order[1..MAX_WAIT + 1]
+            .iter()
+            .enumerate()
+            .all(|(i, id)| *id == format!("high{}", i)));
+    }
+}
@@ -1,5 +1,8 @@
+use crate::guid::AssetGuids;
 use crate::ir::IRAsset;
+use crate::variants::{AssetVariants, DeviceProfile};
 use crate::{Asset, AssetHeader, AssetID, AssetMemoryUsage};
+use log::warn;
 use std::collections::HashMap;
 use thiserror::Error;
 
@@ -14,35 +17,121 @@ pub(crate) enum AssetState {
 pub enum RegistryError {
     #[error("Asset with ID {0} not found")]
     NotFound(AssetID),
+    #[error("Asset with ID {0} already exists")]
+    AlreadyExists(AssetID),
+    #[error("Asset with ID {0} is not procedural")]
+    NotProcedural(AssetID),
 }
 
 pub(crate) struct AssetContainer {
     pub(crate) header: AssetHeader,
     pub(crate) state: AssetState,
+    /// IR of the asset created at runtime, kept to load it again after freeing.
+    pub(crate) procedural: Option<IRAsset>,
 }
 
-pub(crate) struct AssetRegistry(HashMap<AssetID, AssetContainer>);
+pub(crate) struct AssetRegistry {
+    assets: HashMap<AssetID, AssetContainer>,
+    variants: AssetVariants,
+    guids: AssetGuids,
+    locale: Option<String>,
+    profile: DeviceProfile,
+}
 
 impl AssetRegistry {
     pub fn new() -> Self {
-        AssetRegistry(HashMap::new())
+        AssetRegistry {
+            assets: HashMap::new(),
+            variants: AssetVariants::default(),
+            guids: AssetGuids::default(),
+            locale: None,
+            profile: DeviceProfile::default(),
+        }
     }
 
-    pub fn enumerate(&mut self, headers: Vec<AssetHeader>) {
-        self.0.clear();
+    pub fn enumerate(
+        &mut self,
+        headers: Vec<AssetHeader>,
+        variants: AssetVariants,
+        guids: AssetGuids,
+    ) {
+        // The procedural assets are not in the container
+        self.assets
+            .retain(|_, container| container.procedural.is_some());
+        self.variants = variants;
+        self.guids = guids;
         for header in headers {
-            self.0.insert(
+            if self.assets.contains_key(&header.id) {
+                warn!(
+                    "Asset {} of the container is shadowed by the procedural one",
+                    header.id
+                );
+                continue;
+            }
+            self.assets.insert(
                 header.id.clone(),
                 AssetContainer {
                     header,
                     state: AssetState::Empty,
+                    procedural: None,
                 },
             );
         }
     }
 
+    /// Registers the asset created at runtime as already read.
+    /// Replaces the procedural asset of the same ID if it is not loaded.
+    pub fn add_procedural(
+        &mut self,
+        header: AssetHeader,
+        ir: IRAsset,
+    ) -> Result<(), RegistryError> {
+        if let Some(container) = self.assets.get(&header.id) {
+            if container.procedural.is_none() || matches!(container.state, AssetState::Loaded(_, _))
+            {
+                return Err(RegistryError::AlreadyExists(header.id));
+            }
+        }
+        self.assets.insert(
+            header.id.clone(),
+            AssetContainer {
+                header,
+                state: AssetState::Read(ir.clone()),
+                procedural: Some(ir),
+            },
+        );
+        Ok(())
+    }
+
+    pub fn remove_procedural(&mut self, id: &AssetID) -> Result<(), RegistryError> {
+        match self.assets.get(id) {
+            None => Err(RegistryError::NotFound(id.clone())),
+            Some(container) if container.procedural.is_none() => {
+                Err(RegistryError::NotProcedural(id.clone()))
+            }
+            Some(_) => {
+                self.assets.remove(id);
+                Ok(())
+            }
+        }
+    }
+
+    /// The IR of the procedural asset, `None` for the assets of the container.
+    pub fn get_procedural(&self, id: &AssetID) -> Option<&IRAsset> {
+        self.assets.get(id)?.procedural.as_ref()
+    }
+
+    pub fn procedural(&self) -> impl Iterator<Item = (&AssetHeader, &IRAsset)> {
+        self.assets.values().filter_map(|container| {
+            container
+                .procedural
+                .as_ref()
+                .map(|ir| (&container.header, ir))
+        })
+    }
+
     pub fn update(&mut self, id: AssetID, state: AssetState) -> Result<(), RegistryError> {
-        if let Some(container) = self.0.get_mut(&id) {
+        if let Some(container) = self.assets.get_mut(&id) {
             container.state = state;
             Ok(())
         } else {
@@ -51,20 +140,47 @@ impl AssetRegistry {
     }
 
     pub fn get_header(&self, id: &AssetID) -> Result<&AssetHeader, RegistryError> {
-        self.0
+        self.assets
             .get(id)
             .map(|container| &container.header)
             .ok_or_else(|| RegistryError::NotFound(id.clone()))
     }
 
     pub fn get_state(&self, id: &AssetID) -> Result<&AssetState, RegistryError> {
-        self.0
+        self.assets
             .get(id)
             .map(|container| &container.state)
             .ok_or_else(|| RegistryError::NotFound(id.clone()))
     }
 
     pub fn keys(&self) -> impl Iterator<Item = &AssetID> {
-        self.0.keys()
+        self.assets.keys()
+    }
+
+    pub fn guids(&self) -> &AssetGuids {
+        &self.guids
+    }
+
+    pub fn set_locale(&mut self, locale: Option<String>) {
+        self.locale = locale;
+    }
+
+    pub fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+
+    pub fn set_device_profile(&mut self, profile: DeviceProfile) {
+        self.profile = profile;
+    }
+
+    pub fn device_profile(&self) -> &DeviceProfile {
+        &self.profile
+    }
+
+    /// Resolves the GUID reference to the string ID, then the logical asset ID
+    /// to the variant for the active locale and the device profile.
+    pub fn resolve(&self, id: &AssetID) -> AssetID {
+        let id = self.guids.resolve(id);
+        self.variants.resolve(&id, self.locale(), &self.profile)
     }
 }
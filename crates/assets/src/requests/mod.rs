@@ -3,7 +3,7 @@ pub mod task;
 
 use crate::{AssetID, AssetType};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub struct AssetRequestID(usize);
 
 impl std::fmt::Display for AssetRequestID {
@@ -12,9 +12,23 @@ impl std::fmt::Display for AssetRequestID {
     }
 }
 
+/// Priority of the request (see `AssetHub::request_with_priority`).
+/// The tasks of the more urgent requests are started first,
+/// and their reads are taken by the reader before the others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    /// Assets that may be needed later, e.g. the neighbouring levels.
+    Low,
+    #[default]
+    Normal,
+    /// Assets needed right now, e.g. the ones on the screen.
+    High,
+}
+
 #[derive(Debug, Clone)]
 pub enum AssetRequestQuery {
     ByID(AssetID),
+    ByIDs(Vec<AssetID>),
     ByTag(String),
     ByTags(Vec<String>),
     ByType(AssetType),
@@ -30,12 +44,9 @@ pub enum AssetRequest {
     LoadNoDeps(AssetRequestQuery),
     Free(AssetRequestQuery),
     FreeNoDeps(AssetRequestQuery),
-}
-
-impl Default for AssetRequestID {
-    fn default() -> Self {
-        AssetRequestID(0)
-    }
+    /// Reads the data of the read and the loaded assets again and replaces
+    /// the loaded ones in place (see `hot_reload` module). Dependencies are not reloaded.
+    Reload(AssetRequestQuery),
 }
 
 impl AssetRequestID {
@@ -1,9 +1,9 @@
 use crate::registry::{AssetRegistry, AssetState, RegistryError};
 use crate::requests::task::{AssetTaskID, TaskCommand};
-use crate::requests::{AssetRequest, AssetRequestID, AssetRequestQuery};
-use crate::AssetID;
-use log::{debug, error, info};
-use std::collections::HashSet;
+use crate::requests::{AssetRequest, AssetRequestID, AssetRequestQuery, Priority};
+use crate::{AssetID, AssetTag};
+use log::debug;
+use std::collections::{HashMap, HashSet};
 use thiserror::Error;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -19,16 +19,43 @@ pub(crate) struct Task {
     pub command: TaskCommand,
     dependencies: HashSet<AssetTaskID>,
     state: TaskState,
+    // Task of another request doing the same job.
+    // The task is never peeked, it's finished together with the owner.
+    owner: Option<AssetTaskID>,
 }
 
 #[derive(Debug, Clone)]
-enum RequestPromise {
-    Pending(AssetRequestID, AssetRequest),
-    Unwrapped(AssetRequestID, Vec<Task>),
+struct RequestPromise {
+    rid: AssetRequestID,
+    request: AssetRequest,
+    priority: Priority,
+    // None until the request is unwrapped
+    tasks: Option<Vec<Task>>,
+}
+
+impl RequestPromise {
+    // Frees, reloads and enumerations change the states the other requests
+    // were unwrapped against, so they run alone
+    fn is_barrier(&self) -> bool {
+        matches!(
+            self.request,
+            AssetRequest::Enumerate
+                | AssetRequest::Free(_)
+                | AssetRequest::FreeNoDeps(_)
+                | AssetRequest::Reload(_)
+        )
+    }
 }
 
 pub(crate) struct Scheduler {
     promises: Vec<RequestPromise>,
+    // Task doing each job. The same jobs of the other requests wait for it.
+    jobs: HashMap<TaskCommand, AssetTaskID>,
+    // Tasks in progress whose requests have already finished (e.g. failed).
+    // Their results are still needed by the tasks waiting for them.
+    orphans: HashSet<AssetTaskID>,
+    // Jobs already started that more urgent requests have joined since
+    boosted: Vec<AssetTaskID>,
     peekable: bool,
 }
 
@@ -40,14 +67,15 @@ pub(crate) enum TaskFinishedError {
     UnknownTask(AssetTaskID, AssetRequestID),
     #[error("Task {0} failed for command {1:?}: {2}")]
     TaskFailed(AssetTaskID, Option<TaskCommand>, anyhow::Error),
-    #[error("Finish of non-unwrapped request {0}")]
-    NonUnwrappedRequest(AssetRequestID),
+    #[error("Asset {asset} failed because its dependency {dependency} failed: {reason}")]
+    DependencyFailed {
+        asset: AssetID,
+        dependency: AssetID,
+        reason: Box<TaskFinishedError>,
+    },
 }
 
-pub(crate) enum TaskDoneResult {
-    Ok,
-    RequestFinished(AssetRequestID, Result<(), TaskFinishedError>),
-}
+pub(crate) type FinishedRequest = (AssetRequestID, Result<(), TaskFinishedError>);
 
 #[derive(Debug, Clone, Error)]
 pub(crate) enum PeekError {
@@ -69,17 +97,77 @@ impl Scheduler {
     pub fn new() -> Self {
         Scheduler {
             promises: Vec::new(),
+            jobs: HashMap::new(),
+            orphans: HashSet::new(),
+            boosted: Vec::new(),
             peekable: false,
         }
     }
 
-    pub fn request(&mut self, request: AssetRequest) -> AssetRequestID {
+    pub fn request(&mut self, request: AssetRequest, priority: Priority) -> AssetRequestID {
         let rid = AssetRequestID::new();
-        self.promises.push(RequestPromise::Pending(rid, request));
+        self.promises.push(RequestPromise {
+            rid,
+            request,
+            priority,
+            tasks: None,
+        });
         self.peekable = true;
         rid
     }
 
+    /// Priority of the job: the highest one of the requests waiting for it.
+    pub fn job_priority(&self, task_id: AssetTaskID) -> Priority {
+        self.promises
+            .iter()
+            .filter(|p| {
+                p.rid == task_id.as_request()
+                    || p.tasks.iter().flatten().any(|t| t.owner == Some(task_id))
+            })
+            .map(|p| p.priority)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Started jobs whose priority was raised by the requests joining them.
+    pub fn take_boosted(&mut self) -> Vec<AssetTaskID> {
+        std::mem::take(&mut self.boosted)
+    }
+
+    /// Removes the request. The jobs it shares with the other requests go on,
+    /// the ones of its own are not started. Returns the reads already sent
+    /// that nobody else waits for, so the reader can drop them: their results
+    /// still arrive, with the error if dropped, and must be discarded.
+    /// `None` if the request is unknown (e.g. already finished).
+    pub fn cancel(&mut self, rid: AssetRequestID) -> Option<Vec<AssetTaskID>> {
+        let index = self.promises.iter().position(|p| p.rid == rid)?;
+        let tasks = self.promises[index].tasks.clone().unwrap_or_default();
+        let waited: HashSet<AssetTaskID> = self
+            .promises
+            .iter()
+            .filter_map(|p| p.tasks.as_ref())
+            .flatten()
+            .filter_map(|t| t.owner)
+            .collect();
+
+        let dropped: Vec<AssetTaskID> = tasks
+            .iter()
+            .filter(|t| {
+                matches!(t.command, TaskCommand::Read(_))
+                    && t.state == TaskState::Processing
+                    && t.owner.is_none()
+                    && !waited.contains(&t.id)
+            })
+            .map(|t| t.id)
+            .collect();
+
+        self.remove_request(index);
+        // The next requests of the asset must not wait for the dropped reads
+        self.jobs.retain(|_, owner| !dropped.contains(owner));
+        self.peekable = true;
+        Some(dropped)
+    }
+
     fn collect_tasks_for_asset(
         rid: AssetRequestID,
         aid: AssetID,
@@ -108,7 +196,7 @@ impl Scheduler {
             for dep in &header.dependencies {
                 let deps = Self::collect_tasks_for_asset(
                     rid,
-                    dep.clone(),
+                    registry.resolve(dep),
                     registry,
                     true,
                     stack,
@@ -131,23 +219,6 @@ impl Scheduler {
         Ok(tasks)
     }
 
-    fn print_task_tree(tasks: &Vec<Task>, task: &Task, depth: usize) {
-        println!(
-            "{} Task ID: {}, Command: {:?}, State: {:?}",
-            " ".repeat(depth),
-            task.id,
-            task.command,
-            task.state
-        );
-        for dep in task.dependencies.iter() {
-            if let Some(dep_task) = tasks.iter().find(|t| &t.id == dep) {
-                Self::print_task_tree(tasks, &dep_task, depth + 2);
-            } else {
-                error!("Dependency task {:?} not found in task list", dep);
-            }
-        }
-    }
-
     fn collect_tasks_for_query(
         rid: AssetRequestID,
         query: AssetRequestQuery,
@@ -161,29 +232,36 @@ impl Scheduler {
         ) -> Result<Vec<Task>, PeekError>,
     ) -> Result<Vec<Task>, PeekError> {
         let ids = match query {
-            AssetRequestQuery::ByID(id) => vec![id],
-            AssetRequestQuery::ByTag(tag) => registry
-                .keys()
-                .filter(|id| {
-                    if let Ok(header) = registry.get_header(id) {
-                        header.tags.contains(&tag)
-                    } else {
-                        false
-                    }
-                })
-                .cloned()
-                .collect(),
-            AssetRequestQuery::ByTags(tags) => registry
-                .keys()
-                .filter(|id| {
-                    if let Ok(header) = registry.get_header(id) {
-                        tags.iter().all(|tag| header.tags.contains(tag))
-                    } else {
-                        false
-                    }
-                })
-                .cloned()
-                .collect(),
+            AssetRequestQuery::ByID(id) => vec![registry.resolve(&id)],
+            AssetRequestQuery::ByIDs(ids) => ids.iter().map(|id| registry.resolve(id)).collect(),
+            AssetRequestQuery::ByTag(tag) => {
+                let tag = AssetTag::from(tag);
+                registry
+                    .keys()
+                    .filter(|id| {
+                        if let Ok(header) = registry.get_header(id) {
+                            header.tags.contains(&tag)
+                        } else {
+                            false
+                        }
+                    })
+                    .cloned()
+                    .collect()
+            }
+            AssetRequestQuery::ByTags(tags) => {
+                let tags: Vec<AssetTag> = tags.into_iter().map(AssetTag::from).collect();
+                registry
+                    .keys()
+                    .filter(|id| {
+                        if let Ok(header) = registry.get_header(id) {
+                            tags.iter().all(|tag| header.tags.contains(tag))
+                        } else {
+                            false
+                        }
+                    })
+                    .cloned()
+                    .collect()
+            }
             AssetRequestQuery::All => registry.keys().cloned().collect(),
             AssetRequestQuery::ByType(asset_type) => registry
                 .keys()
@@ -230,21 +308,13 @@ impl Scheduler {
             let mut new_dependencies = HashSet::new();
             for dependency in &task.dependencies {
                 // Find the merged task for the dependency.
-                let old_command = all_tasks
-                    .iter()
-                    .find(|t| t.id == dependency.clone())
-                    .unwrap();
+                let old_command = all_tasks.iter().find(|t| t.id == *dependency).unwrap();
                 // Replace the dependency with the merged task.
-                new_dependencies.insert(task_map.get(&old_command.command).unwrap().id.clone());
+                new_dependencies.insert(task_map.get(&old_command.command).unwrap().id);
             }
             task.dependencies = new_dependencies;
         }
 
-        // let all_tasks= all_tasks.into_iter().collect();
-        // for task in merged_map.values() {
-        //     Self::print_task_tree(&all_tasks, &task, 0);
-        // }
-
         Ok(merged_map.values().cloned().collect())
     }
 
@@ -261,6 +331,7 @@ impl Scheduler {
                 command: TaskCommand::Read(aid),
                 dependencies,
                 state: TaskState::Pending,
+                owner: None,
             }]),
             _ => Ok(vec![]),
         }
@@ -278,61 +349,70 @@ impl Scheduler {
                 let load_tid = AssetTaskID::new(rid);
                 Ok(vec![
                     Task {
-                        id: load_tid.clone(),
+                        id: load_tid,
                         command: TaskCommand::Read(aid.clone()),
                         dependencies,
                         state: TaskState::Pending,
+                        owner: None,
                     },
                     Task {
                         id: AssetTaskID::new(rid),
                         command: TaskCommand::Load(aid),
                         dependencies: vec![load_tid].into_iter().collect(),
                         state: TaskState::Pending,
+                        owner: None,
                     },
                 ])
             }
-            AssetState::Loaded(_, _) => Ok(vec![Task {
+            // Already read, e.g. by another request. Reading it again
+            // would be a waste, and would drop the state of a loaded asset.
+            AssetState::Read(_) => Ok(vec![Task {
                 id: AssetTaskID::new(rid),
-                command: TaskCommand::Read(aid),
+                command: TaskCommand::Load(aid),
                 dependencies,
                 state: TaskState::Pending,
+                owner: None,
             }]),
-            _ => Ok(vec![]),
+            AssetState::Loaded(_, _) => Ok(vec![]),
         }
     }
 
-    fn load_constructor_no_dep(
+    fn free_constructor(
         rid: AssetRequestID,
         registry: &AssetRegistry,
         aid: AssetID,
-        _dependencies: HashSet<AssetTaskID>,
+        dependencies: HashSet<AssetTaskID>,
     ) -> Result<Vec<Task>, PeekError> {
-        // If the asset is already loaded, no need to load it again.
         match registry.get_state(&aid)? {
             AssetState::Loaded(_, _) => Ok(vec![Task {
                 id: AssetTaskID::new(rid),
-                command: TaskCommand::Read(aid),
-                dependencies: HashSet::new(),
+                command: TaskCommand::Free(aid),
+                dependencies,
                 state: TaskState::Pending,
+                owner: None,
             }]),
             _ => Ok(vec![]),
         }
     }
 
-    fn free_constructor(
+    fn reload_constructor(
         rid: AssetRequestID,
         registry: &AssetRegistry,
         aid: AssetID,
         dependencies: HashSet<AssetTaskID>,
     ) -> Result<Vec<Task>, PeekError> {
+        // The assets not read yet get the new data anyway.
+        // The procedural ones have nothing to read
         match registry.get_state(&aid)? {
-            AssetState::Loaded(_, _) => Ok(vec![Task {
+            AssetState::Empty => Ok(vec![]),
+            _ if registry.get_procedural(&aid).is_some() => Ok(vec![]),
+            _ => Ok(vec![Task {
                 id: AssetTaskID::new(rid),
-                command: TaskCommand::Free(aid),
+                command: TaskCommand::Reload(aid),
                 dependencies,
                 state: TaskState::Pending,
+                owner: None,
             }]),
-            _ => Ok(vec![]),
         }
     }
 
@@ -347,6 +427,7 @@ impl Scheduler {
                 command: TaskCommand::Enumerate,
                 dependencies: HashSet::new(),
                 state: TaskState::Pending,
+                owner: None,
             }]),
             AssetRequest::Read(query) => {
                 Self::collect_tasks_for_query(rid, query, registry, true, &Self::read_constructor)
@@ -357,19 +438,52 @@ impl Scheduler {
             AssetRequest::Load(query) => {
                 Self::collect_tasks_for_query(rid, query, registry, true, &Self::load_constructor)
             }
-            AssetRequest::LoadNoDeps(query) => Self::collect_tasks_for_query(
-                rid,
-                query,
-                registry,
-                false,
-                &Self::load_constructor_no_dep,
-            ),
+            AssetRequest::LoadNoDeps(query) => {
+                Self::collect_tasks_for_query(rid, query, registry, false, &Self::load_constructor)
+            }
             AssetRequest::Free(query) => {
                 Self::collect_tasks_for_query(rid, query, registry, true, &Self::free_constructor)
             }
             AssetRequest::FreeNoDeps(query) => {
                 Self::collect_tasks_for_query(rid, query, registry, false, &Self::free_constructor)
             }
+            AssetRequest::Reload(query) => Self::collect_tasks_for_query(
+                rid,
+                query,
+                registry,
+                false,
+                &Self::reload_constructor,
+            ),
+        }
+    }
+
+    // Number of the requests at the front that run concurrently.
+    // The consecutive reads and loads run together, sharing the jobs
+    // for the same assets. A barrier runs only when it's the first one.
+    fn window(&self) -> usize {
+        match self.promises.iter().position(|p| p.is_barrier()) {
+            Some(0) => 1,
+            Some(index) => index,
+            None => self.promises.len(),
+        }
+    }
+
+    // Links the tasks to the ones of the other requests doing the same jobs.
+    fn share_jobs(&mut self, tasks: &mut [Task], priority: Priority) {
+        for task in tasks {
+            match self.jobs.get(&task.command).copied() {
+                Some(owner) => {
+                    debug!("Task {} waits for the same job of {}", task.id, owner);
+                    task.owner = Some(owner);
+                    task.state = TaskState::Processing;
+                    if priority > self.job_priority(owner) {
+                        self.boosted.push(owner);
+                    }
+                }
+                None => {
+                    self.jobs.insert(task.command.clone(), task.id);
+                }
+            }
         }
     }
 
@@ -378,131 +492,422 @@ impl Scheduler {
             return PeekResult::NoPendingTasks;
         }
 
-        // Processing the first promise. If it is still pending, we need to unwrap it into tasks.
-        let to_unwrap = match self.promises.first() {
-            None => {
-                debug!("No pending requests");
-                self.peekable = false;
-                return PeekResult::NoPendingTasks;
+        // Unwrap all the requests that can run now, the urgent ones first
+        let window = self.window();
+        let mut order: Vec<usize> = (0..window).collect();
+        order.sort_by_key(|index| std::cmp::Reverse(self.promises[*index].priority));
+        for index in order.iter().copied() {
+            let promise = &self.promises[index];
+            if promise.tasks.is_some() {
+                continue;
             }
-            Some(RequestPromise::Pending(rid, request)) => Some((rid.clone(), request.clone())),
-            _ => None,
-        };
-        if let Some((rid, request)) = to_unwrap {
-            match Self::unwrap(rid.clone(), request.clone(), registry) {
-                Ok(tasks) if !tasks.is_empty() => {
-                    // Replace the pending promise with the unwrapped tasks.
-                    self.promises.remove(0);
-                    self.promises
-                        .insert(0, RequestPromise::Unwrapped(rid.clone(), tasks.clone()));
+
+            let (rid, request, priority) = (promise.rid, promise.request.clone(), promise.priority);
+            match Self::unwrap(rid, request.clone(), registry) {
+                Ok(mut tasks) if !tasks.is_empty() => {
+                    self.share_jobs(&mut tasks, priority);
                     debug!("Unwrapped request {} ({:?}) into {:?}", rid, request, tasks);
+                    self.promises[index].tasks = Some(tasks);
                 }
-                Ok(_) => return PeekResult::EmptyUnwrap(AssetTaskID::new(rid.clone())),
+                // Nothing to do (e.g. everything is already loaded)
+                Ok(_) => return PeekResult::EmptyUnwrap(AssetTaskID::new(rid)),
                 Err(e) => {
                     // The request will be removed in the next call to task_finished.
                     // Hoping upper layers will handle the error appropriately.
-                    return PeekResult::UnwrapFailed(AssetTaskID::new(rid.clone()), e);
+                    return PeekResult::UnwrapFailed(AssetTaskID::new(rid), e);
                 }
             };
         }
 
         // Select any task that is pending and has no dependencies.
-        let (rid, tasks) = match &mut self.promises[0] {
-            RequestPromise::Unwrapped(rid, tasks) => (rid.clone(), tasks),
-            _ => unreachable!(),
-        };
-        if let Some(pos) = tasks
-            .iter()
-            .position(|t| t.state == TaskState::Pending && t.dependencies.is_empty())
-        {
-            // Mark the task as processing and return it.
-            let task = &mut tasks[pos];
-            task.state = TaskState::Processing;
-
-            debug!("Peeking task {} from request {}", task.id, rid);
-            PeekResult::Peeked(task.clone())
-        } else {
-            // No pending tasks available right now.
-            // Hoping some will appear after some tasks finish.
-            self.peekable = false;
-            PeekResult::NoPendingTasks
+        for index in order {
+            let promise = &mut self.promises[index];
+            let Some(tasks) = &mut promise.tasks else {
+                continue;
+            };
+            if let Some(task) = tasks
+                .iter_mut()
+                .find(|t| t.state == TaskState::Pending && t.dependencies.is_empty())
+            {
+                // Mark the task as processing and return it.
+                task.state = TaskState::Processing;
+
+                debug!("Peeking task {} from request {}", task.id, promise.rid);
+                return PeekResult::Peeked(task.clone());
+            }
         }
+
+        // No pending tasks available right now.
+        // Hoping some will appear after some tasks finish.
+        self.peekable = false;
+        PeekResult::NoPendingTasks
     }
 
+    /// Marks the task finished, together with the tasks of the other requests
+    /// waiting for the same job. Returns the requests finished by that.
     pub fn task_finished(
         &mut self,
         task_id: AssetTaskID,
         result: anyhow::Result<()>,
-    ) -> TaskDoneResult {
-        let rid = task_id.as_request();
+    ) -> Vec<FinishedRequest> {
         self.peekable = true;
 
+        self.jobs.retain(|_, owner| *owner != task_id);
+        let waiting: Vec<AssetTaskID> = self
+            .promises
+            .iter()
+            .filter_map(|p| p.tasks.as_ref())
+            .flatten()
+            .filter(|t| t.owner == Some(task_id))
+            .map(|t| t.id)
+            .collect();
+
+        // The error is not cloneable, so the waiting tasks get its message
+        let shared = result.as_ref().map_err(|e| format!("{:#}", e)).copied();
+
+        let mut finished = Vec::new();
+        if !self.orphans.remove(&task_id) {
+            self.complete(task_id, result, &mut finished);
+        }
+        for id in waiting {
+            let result = shared.clone().map_err(|e| anyhow::anyhow!(e));
+            self.complete(id, result, &mut finished);
+        }
+        finished
+    }
+
+    fn complete(
+        &mut self,
+        task_id: AssetTaskID,
+        result: anyhow::Result<()>,
+        finished: &mut Vec<FinishedRequest>,
+    ) {
+        let rid = task_id.as_request();
+
         // Find the request index.
-        let request_index = match self.promises.iter().position(|p| match p {
-            RequestPromise::Pending(other, _) | RequestPromise::Unwrapped(other, _) => {
-                rid.0 == other.0
+        let Some(request_index) = self.promises.iter().position(|p| p.rid == rid) else {
+            finished.push((rid, Err(TaskFinishedError::UnknownRequest(task_id, rid))));
+            return;
+        };
+
+        // Empty or failed unwrap finishes the request right away
+        let Some(tasks) = &mut self.promises[request_index].tasks else {
+            let result = result.map_err(|e| TaskFinishedError::TaskFailed(task_id, None, e));
+            self.remove_request(request_index);
+            finished.push((rid, result));
+            return;
+        };
+
+        let Some(task_index) = tasks.iter().position(|t| t.id == task_id) else {
+            finished.push((rid, Err(TaskFinishedError::UnknownTask(task_id, rid))));
+            return;
+        };
+
+        let result = match result {
+            Ok(()) => {
+                // Mark the task done and remove it from dependencies of other tasks.
+                tasks[task_index].state = TaskState::Done;
+                for task in tasks.iter_mut() {
+                    task.dependencies.remove(&task_id);
+                }
+
+                if tasks.iter().any(|t| t.state != TaskState::Done) {
+                    // There's still some tasks pending.
+                    return;
+                }
+                Ok(())
             }
-        }) {
-            Some(index) => index,
-            None => {
-                return TaskDoneResult::RequestFinished(
-                    rid,
-                    Err(TaskFinishedError::UnknownRequest(task_id, rid)),
-                );
+            Err(e) => {
+                let command = tasks[task_index].command.clone();
+                let error = TaskFinishedError::TaskFailed(task_id, Some(command), e);
+                Err(Self::dependency_chain(tasks, task_index, error))
             }
         };
 
-        let mut command = None;
-        if !result.is_err() {
-            // Mark the task done and remove it from dependencies of other tasks.
-            match &mut self.promises[request_index] {
-                RequestPromise::Unwrapped(_, tasks) => {
-                    let task_index = match tasks.iter().position(|t| t.id == task_id) {
-                        Some(index) => index,
-                        None => {
-                            return TaskDoneResult::RequestFinished(
-                                rid,
-                                Err(TaskFinishedError::UnknownTask(task_id, rid)),
-                            );
-                        }
+        // If all tasks are done or one has failed, remove the request.
+        self.remove_request(request_index);
+        finished.push((rid, result));
+    }
+
+    // Wraps the error of the failed task into the errors of the assets
+    // depending on it, up to the requested one.
+    fn dependency_chain(
+        tasks: &[Task],
+        failed_index: usize,
+        mut error: TaskFinishedError,
+    ) -> TaskFinishedError {
+        let mut current = &tasks[failed_index];
+        let mut visited = HashSet::new();
+        loop {
+            // The tasks also depend on the dependencies of their dependencies,
+            // so the nearest dependent is the one not depending on the others
+            let dependents: Vec<&Task> = tasks
+                .iter()
+                .filter(|t| t.dependencies.contains(&current.id) && !visited.contains(&t.id))
+                .collect();
+            let Some(dependent) = dependents.iter().copied().find(|t| {
+                !dependents
+                    .iter()
+                    .any(|other| t.dependencies.contains(&other.id))
+            }) else {
+                break;
+            };
+            visited.insert(dependent.id);
+            if let (Some(asset), Some(dependency)) =
+                (dependent.command.asset(), current.command.asset())
+            {
+                // Reading and loading of the same asset are a single step
+                if asset != dependency {
+                    error = TaskFinishedError::DependencyFailed {
+                        asset: asset.clone(),
+                        dependency: dependency.clone(),
+                        reason: Box::new(error),
                     };
+                }
+            }
+            current = dependent;
+        }
+        error
+    }
 
-                    tasks[task_index].state = TaskState::Done;
-                    command = Some(tasks[task_index].command.clone());
-                    let completed_task_id = tasks[task_index].id;
-                    for task in tasks.iter_mut() {
-                        task.dependencies.remove(&completed_task_id);
-                    }
+    // Removes the finished request. The jobs of its unfinished tasks
+    // are handed over to the other requests waiting for them.
+    fn remove_request(&mut self, index: usize) {
+        let promise = self.promises.remove(index);
+        for task in promise.tasks.into_iter().flatten() {
+            if task.state == TaskState::Done || task.owner.is_some() {
+                continue;
+            }
+            if self.jobs.get(&task.command) != Some(&task.id) {
+                continue;
+            }
 
-                    if tasks.iter().any(|t| t.state != TaskState::Done) {
-                        // There's still some tasks pending, return Ok.
-                        return TaskDoneResult::Ok;
+            if task.state == TaskState::Processing {
+                // Already sent, the result will arrive anyway
+                self.orphans.insert(task.id);
+                continue;
+            }
+
+            // Not started yet, the first waiting task takes over the job
+            let mut successor = None;
+            for other in self
+                .promises
+                .iter_mut()
+                .filter_map(|p| p.tasks.as_mut())
+                .flatten()
+                .filter(|t| t.owner == Some(task.id))
+            {
+                match successor {
+                    None => {
+                        other.owner = None;
+                        other.state = TaskState::Pending;
+                        successor = Some(other.id);
                     }
+                    Some(id) => other.owner = Some(id),
                 }
-                _ => {
-                    // This should never happen,
-                    // as we unwrap requests before processing tasks.
-                }
+            }
+            match successor {
+                Some(id) => self.jobs.insert(task.command, id),
+                None => self.jobs.remove(&task.command),
             };
-        } else {
-            // If the task failed, find the command for error reporting.
-            match &mut self.promises[request_index] {
-                RequestPromise::Unwrapped(_, tasks) => {
-                    match tasks.iter().position(|t| t.id == task_id) {
-                        Some(index) => {
-                            command = Some(tasks[index].command.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AssetHeader;
+    use std::collections::VecDeque;
+
+    fn registry(assets: &[(&str, &[&str])]) -> AssetRegistry {
+        let mut registry = AssetRegistry::new();
+        let headers = assets
+            .iter()
+            .map(|(id, deps)| AssetHeader {
+                id: (*id).into(),
+                dependencies: deps.iter().map(|dep| (*dep).into()).collect(),
+                ..Default::default()
+            })
+            .collect();
+        registry.enumerate(headers, Default::default(), Default::default());
+        registry
+    }
+
+    fn load(id: &str) -> AssetRequest {
+        AssetRequest::Load(AssetRequestQuery::ByID(id.into()))
+    }
+
+    fn read(id: &str) -> TaskCommand {
+        TaskCommand::Read(id.into())
+    }
+
+    // Runs the requests as the hub does, finishing the started tasks in order.
+    // Returns the commands started and the requests finished
+    fn run(
+        scheduler: &mut Scheduler,
+        registry: &AssetRegistry,
+        failing: Option<TaskCommand>,
+    ) -> (Vec<TaskCommand>, Vec<FinishedRequest>) {
+        let mut started = Vec::new();
+        let mut running = VecDeque::new();
+        let mut finished = Vec::new();
+        loop {
+            match scheduler.peek(registry) {
+                PeekResult::Peeked(task) => {
+                    started.push(task.command.clone());
+                    running.push_back(task);
+                }
+                PeekResult::NoPendingTasks => {
+                    let Some(task) = running.pop_front() else {
+                        break;
+                    };
+                    let result = match failing {
+                        Some(ref command) if *command == task.command => {
+                            Err(anyhow::anyhow!("Broken data"))
                         }
-                        None => {}
-                    }
+                        _ => Ok(()),
+                    };
+                    finished.extend(scheduler.task_finished(task.id, result));
                 }
-                _ => {}
+                other => panic!("Unexpected peek result {:?}", other),
             }
         }
+        (started, finished)
+    }
+
+    fn result(finished: &[FinishedRequest], rid: AssetRequestID) -> &Result<(), TaskFinishedError> {
+        let mut results = finished.iter().filter(|(id, _)| *id == rid);
+        let (_, result) = results.next().expect("Request is not finished");
+        assert!(results.next().is_none(), "Request is finished twice");
+        result
+    }
+
+    #[test]
+    fn shared_dependency_is_read_once() {
+        let registry = registry(&[("a", &["c"]), ("b", &["c"]), ("c", &[])]);
+        let mut scheduler = Scheduler::new();
+        let first = scheduler.request(load("a"), Priority::Normal);
+        let second = scheduler.request(load("b"), Priority::Normal);
+
+        let (started, finished) = run(&mut scheduler, &registry, None);
+        assert_eq!(started.first(), Some(&read("c")));
+        for command in [read("c"), TaskCommand::Load("c".into())] {
+            assert_eq!(started.iter().filter(|c| **c == command).count(), 1);
+        }
+        assert_eq!(started.len(), 6);
+        assert!(result(&finished, first).is_ok());
+        assert!(result(&finished, second).is_ok());
+        assert!(scheduler.jobs.is_empty());
+    }
+
+    #[test]
+    fn failed_dependency_is_reported_up_the_chain() {
+        let registry = registry(&[("a", &["b"]), ("b", &["c"]), ("c", &[])]);
+        let mut scheduler = Scheduler::new();
+        let first = scheduler.request(load("a"), Priority::Normal);
+        // Waits for the same read of c
+        let second = scheduler.request(load("b"), Priority::Normal);
+
+        let (started, finished) = run(&mut scheduler, &registry, Some(read("c")));
+        assert_eq!(started, vec![read("c")]);
+
+        let Err(TaskFinishedError::DependencyFailed {
+            asset,
+            dependency,
+            reason,
+        }) = result(&finished, first)
+        else {
+            panic!("Unexpected result {:?}", result(&finished, first));
+        };
+        assert_eq!((asset.as_str(), dependency.as_str()), ("a", "b"));
+        let TaskFinishedError::DependencyFailed {
+            asset,
+            dependency,
+            reason,
+        } = reason.as_ref()
+        else {
+            panic!("Unexpected reason {:?}", reason);
+        };
+        assert_eq!((asset.as_str(), dependency.as_str()), ("b", "c"));
+        assert!(matches!(
+            reason.as_ref(),
+            TaskFinishedError::TaskFailed(_, Some(TaskCommand::Read(id)), _) if id.as_str() == "c"
+        ));
+
+        let Err(TaskFinishedError::DependencyFailed {
+            asset, dependency, ..
+        }) = result(&finished, second)
+        else {
+            panic!("Unexpected result {:?}", result(&finished, second));
+        };
+        assert_eq!((asset.as_str(), dependency.as_str()), ("b", "c"));
+        assert!(scheduler.promises.is_empty());
+    }
+
+    #[test]
+    fn cancelled_read_is_orphaned() {
+        let registry = registry(&[("a", &[])]);
+        let mut scheduler = Scheduler::new();
+        let cancelled = scheduler.request(load("a"), Priority::Normal);
+        let PeekResult::Peeked(dropped) = scheduler.peek(&registry) else {
+            panic!("Read of a is not started");
+        };
+
+        // Nobody else waits for the read, so the reader may drop it
+        assert_eq!(scheduler.cancel(cancelled), Some(vec![dropped.id]));
+        assert!(scheduler.cancel(cancelled).is_none());
+        assert!(scheduler.orphans.contains(&dropped.id));
+
+        // The next request does not wait for the dropped read
+        let next = scheduler.request(load("a"), Priority::Normal);
+        let PeekResult::Peeked(task) = scheduler.peek(&registry) else {
+            panic!("Read of a is not started again");
+        };
+        assert_eq!(task.command, read("a"));
+        assert_ne!(task.id, dropped.id);
 
-        let result = result.map_err(|e| TaskFinishedError::TaskFailed(task_id, command, e));
+        // The result of the dropped read is discarded
+        let finished = scheduler.task_finished(dropped.id, Err(anyhow::anyhow!("Dropped")));
+        assert!(finished.is_empty());
+        assert!(scheduler.orphans.is_empty());
 
-        // If all tasks are done or failed, remove the request and return RequestFinished.
-        self.promises.remove(request_index);
-        TaskDoneResult::RequestFinished(rid, result)
+        finish_read_then_load(&mut scheduler, &registry, task, next);
+    }
+
+    #[test]
+    fn orphaned_read_finishes_the_waiting_request() {
+        let registry = registry(&[("a", &[])]);
+        let mut scheduler = Scheduler::new();
+        let cancelled = scheduler.request(load("a"), Priority::Normal);
+        let waiting = scheduler.request(load("a"), Priority::Normal);
+        let PeekResult::Peeked(task) = scheduler.peek(&registry) else {
+            panic!("Read of a is not started");
+        };
+        assert!(matches!(
+            scheduler.peek(&registry),
+            PeekResult::NoPendingTasks
+        ));
+
+        // The other request waits for the read, so it goes on
+        assert_eq!(scheduler.cancel(cancelled), Some(vec![]));
+        assert!(scheduler.orphans.contains(&task.id));
+
+        finish_read_then_load(&mut scheduler, &registry, task, waiting);
+        assert!(scheduler.orphans.is_empty());
+    }
+
+    // Finishes the read, then the load of the request
+    fn finish_read_then_load(
+        scheduler: &mut Scheduler,
+        registry: &AssetRegistry,
+        read: Task,
+        rid: AssetRequestID,
+    ) {
+        assert!(scheduler.task_finished(read.id, Ok(())).is_empty());
+        let (started, finished) = run(scheduler, registry, None);
+        assert_eq!(started, vec![TaskCommand::Load("a".into())]);
+        assert!(result(&finished, rid).is_ok());
+        assert_eq!(finished.len(), 1);
     }
 }
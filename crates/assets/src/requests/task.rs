@@ -7,6 +7,20 @@ pub(crate) enum TaskCommand {
     Read(AssetID),
     Load(AssetID),
     Free(AssetID),
+    Reload(AssetID),
+}
+
+impl TaskCommand {
+    /// The asset the command operates on.
+    pub fn asset(&self) -> Option<&AssetID> {
+        match self {
+            TaskCommand::Enumerate => None,
+            TaskCommand::Read(aid)
+            | TaskCommand::Load(aid)
+            | TaskCommand::Free(aid)
+            | TaskCommand::Reload(aid) => Some(aid),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -0,0 +1,253 @@
+//! Asset handles in the world snapshots.
+//!
+//! The handles stored in the components (`Asset`, `TypedAsset`) are live
+//! references to the loaded assets and can't be serialized. The snapshot stores
+//! the IDs of the assets instead (see `AssetRef`), the GUID references
+//! (see `AssetGuid::to_id`) when known, so the snapshots survive the renames.
+//!
+//! Saving: the `SnapshotSaver` turns the handles into the references and
+//! collects the referenced assets into `SnapshotAssets`, stored alongside
+//! the components. Restoring: the `SnapshotRestore` requests the load of all
+//! of them, and once the request finishes, the references are resolved into
+//! the handles got from the hub. Each restored handle is the clone of the one
+//! the hub holds, so the reference counts after the restore are the same as
+//! if the components were created from scratch: the assets freed while the
+//! snapshot was on disk are loaded again, and the restored components keep
+//! them from being freed until they are despawned.
+//!
+//! The components holding the handles implement `SnapshotAdapter`.
+
+use crate::hub::{AssetHub, GetAssetError};
+use crate::requests::{AssetRequest, AssetRequestID, AssetRequestQuery};
+use crate::{Asset, AssetCastable, AssetID, TypedAsset};
+use log::debug;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use thiserror::Error;
+
+#[derive(Debug, Error, Clone)]
+pub enum SnapshotError {
+    #[error("Asset handle is not loaded by the hub")]
+    UnknownHandle,
+    #[error("Assets of the snapshot are not loaded yet")]
+    NotReady,
+    #[error("Failed to load the assets of the snapshot: {0}")]
+    LoadFailed(String),
+    #[error("Failed to restore the asset handle: {0}")]
+    Asset(#[from] GetAssetError),
+}
+
+/// Asset handle as stored in the snapshot.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(transparent)]
+pub struct AssetRef(AssetID);
+
+impl AssetRef {
+    pub fn id(&self) -> &AssetID {
+        &self.0
+    }
+}
+
+/// Assets referenced by the snapshot, loaded before its components are restored.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct SnapshotAssets {
+    pub assets: Vec<AssetID>,
+}
+
+/// Turns the asset handles into the references while the snapshot is saved.
+pub struct SnapshotSaver {
+    // Address of the loaded asset (see `Asset::address`) to its reference
+    refs: HashMap<usize, AssetRef>,
+    saved: BTreeSet<AssetID>,
+}
+
+impl SnapshotSaver {
+    /// Takes the IDs of the assets currently loaded by the hub.
+    /// The handles got from the hub later are not known to the saver.
+    pub fn new(hub: &AssetHub) -> Self {
+        let refs = hub
+            .loaded_assets()
+            .into_iter()
+            .map(|(id, asset)| {
+                let id = match hub.guid(&id) {
+                    Some(guid) => guid.to_id(),
+                    None => id,
+                };
+                (asset.address(), AssetRef(id))
+            })
+            .collect();
+        SnapshotSaver {
+            refs,
+            saved: BTreeSet::new(),
+        }
+    }
+
+    pub fn save(&mut self, asset: &Asset) -> Result<AssetRef, SnapshotError> {
+        let asset_ref = self
+            .refs
+            .get(&asset.address())
+            .ok_or(SnapshotError::UnknownHandle)?
+            .clone();
+        self.saved.insert(asset_ref.0.clone());
+        Ok(asset_ref)
+    }
+
+    pub fn save_typed<T: AssetCastable>(
+        &mut self,
+        asset: &TypedAsset<T>,
+    ) -> Result<AssetRef, SnapshotError> {
+        self.save(asset.asset())
+    }
+
+    /// Assets referenced by the saved handles, sorted by the ID.
+    pub fn finish(self) -> SnapshotAssets {
+        SnapshotAssets {
+            assets: self.saved.into_iter().collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RestoreState {
+    Pending,
+    Loading(AssetRequestID),
+    Ready,
+    Failed(String),
+}
+
+/// Loads the assets of the snapshot and resolves the references into the handles.
+/// Forward the `AssetHubEvent::RequestFinished` events to `request_finished`.
+pub struct SnapshotRestore {
+    assets: SnapshotAssets,
+    state: RestoreState,
+}
+
+impl SnapshotRestore {
+    pub fn new(assets: SnapshotAssets) -> Self {
+        SnapshotRestore {
+            assets,
+            state: RestoreState::Pending,
+        }
+    }
+
+    /// Requests the load of the assets of the snapshot (with the dependencies).
+    /// The assets loaded already are not loaded again.
+    pub fn request(&mut self, hub: &mut AssetHub) -> AssetRequestID {
+        debug!("Restoring {} snapshot assets", self.assets.assets.len());
+        let rid = hub.request(AssetRequest::Load(AssetRequestQuery::ByIDs(
+            self.assets.assets.clone(),
+        )));
+        self.state = RestoreState::Loading(rid);
+        rid
+    }
+
+    /// Returns true if the request was the one of this restore.
+    pub fn request_finished(&mut self, rid: AssetRequestID, result: &anyhow::Result<()>) -> bool {
+        if self.state != RestoreState::Loading(rid) {
+            return false;
+        }
+        self.state = match result {
+            Ok(()) => RestoreState::Ready,
+            Err(err) => RestoreState::Failed(err.to_string()),
+        };
+        true
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.state == RestoreState::Ready
+    }
+
+    /// Handle of the referenced asset.
+    /// The references of the GUIDs resolve to the current string IDs.
+    pub fn restore(&self, hub: &AssetHub, asset_ref: &AssetRef) -> Result<Asset, SnapshotError> {
+        match &self.state {
+            RestoreState::Ready => Ok(hub.get(asset_ref.0.clone())?),
+            RestoreState::Failed(error) => Err(SnapshotError::LoadFailed(error.clone())),
+            RestoreState::Pending | RestoreState::Loading(_) => Err(SnapshotError::NotReady),
+        }
+    }
+
+    pub fn restore_typed<T: AssetCastable>(
+        &self,
+        hub: &AssetHub,
+        asset_ref: &AssetRef,
+    ) -> Result<TypedAsset<T>, SnapshotError> {
+        Ok(TypedAsset::new(self.restore(hub, asset_ref)?))
+    }
+}
+
+/// Serialized form of the component holding the asset handles.
+/// ```
+/// use dawn_assets::hub::AssetHub;
+/// use dawn_assets::snapshot::{AssetRef, SnapshotAdapter, SnapshotError, SnapshotRestore, SnapshotSaver};
+/// use dawn_assets::{AssetCastable, TypedAsset};
+///
+/// struct Mesh;
+/// impl AssetCastable for Mesh {}
+///
+/// struct ObjectMesh {
+///     mesh: TypedAsset<Mesh>,
+///     visible: bool,
+/// }
+///
+/// impl SnapshotAdapter for ObjectMesh {
+///     type Stored = (AssetRef, bool);
+///
+///     fn save(&self, saver: &mut SnapshotSaver) -> Result<Self::Stored, SnapshotError> {
+///         Ok((self.mesh.save(saver)?, self.visible))
+///     }
+///
+///     fn restore(
+///         (mesh, visible): Self::Stored,
+///         restore: &SnapshotRestore,
+///         hub: &AssetHub,
+///     ) -> Result<Self, SnapshotError> {
+///         let mesh = TypedAsset::restore(mesh, restore, hub)?;
+///         Ok(ObjectMesh { mesh, visible })
+///     }
+/// }
+/// ```
+pub trait SnapshotAdapter: Sized {
+    type Stored: Serialize + DeserializeOwned;
+
+    fn save(&self, saver: &mut SnapshotSaver) -> Result<Self::Stored, SnapshotError>;
+
+    fn restore(
+        stored: Self::Stored,
+        restore: &SnapshotRestore,
+        hub: &AssetHub,
+    ) -> Result<Self, SnapshotError>;
+}
+
+impl SnapshotAdapter for Asset {
+    type Stored = AssetRef;
+
+    fn save(&self, saver: &mut SnapshotSaver) -> Result<AssetRef, SnapshotError> {
+        saver.save(self)
+    }
+
+    fn restore(
+        stored: AssetRef,
+        restore: &SnapshotRestore,
+        hub: &AssetHub,
+    ) -> Result<Self, SnapshotError> {
+        restore.restore(hub, &stored)
+    }
+}
+
+impl<T: AssetCastable> SnapshotAdapter for TypedAsset<T> {
+    type Stored = AssetRef;
+
+    fn save(&self, saver: &mut SnapshotSaver) -> Result<AssetRef, SnapshotError> {
+        saver.save_typed(self)
+    }
+
+    fn restore(
+        stored: AssetRef,
+        restore: &SnapshotRestore,
+        hub: &AssetHub,
+    ) -> Result<Self, SnapshotError> {
+        restore.restore_typed(hub, &stored)
+    }
+}
@@ -0,0 +1,313 @@
+use crate::hub::{AssetHub, AssetHubEvent};
+use crate::requests::{AssetRequest, AssetRequestID, AssetRequestQuery};
+use crate::AssetID;
+use dawn_ecs::events::TickEvent;
+use evenio::component::Component;
+use evenio::event::{GlobalEvent, Receiver, Sender};
+use evenio::fetch::Single;
+use evenio::handler::IntoHandler;
+use evenio::prelude::World;
+use glam::{IVec3, Vec3};
+use log::{debug, warn};
+use std::collections::HashMap;
+
+/// Identifier of a streaming cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CellID(usize);
+
+impl std::fmt::Display for CellID {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cell({})", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellState {
+    Unloaded,
+    Loading,
+    Loaded,
+    /// `CellUnloading` was sent, the assets are freed on the next tick.
+    Unloading,
+    Freeing,
+    /// Loading of the bundle failed. The cell is not loaded again
+    /// until the focus leaves the unload radius.
+    Failed,
+}
+
+/// Events sent by the `WorldStreamer` to the ECS world.
+/// The streamer does not spawn anything by itself: the handlers of these events
+/// spawn and despawn the entities of the cells.
+#[derive(GlobalEvent)]
+pub enum StreamingEvent {
+    /// The bundle of the cell is loaded, its entities can be spawned.
+    CellLoaded(CellID),
+    /// The cell is going to be unloaded. Its entities must be despawned
+    /// and the assets of the bundle released in this tick.
+    CellUnloading(CellID),
+    /// The assets of the cell not used by the other cells are freed.
+    CellUnloaded(CellID),
+    /// Loading of the bundle failed.
+    CellFailed(CellID, String),
+}
+
+/// Sets the point the cells are streamed around, usually the camera position.
+#[derive(GlobalEvent)]
+pub struct StreamingFocusEvent(pub Vec3);
+
+#[derive(Debug, Clone)]
+pub struct StreamingConfig {
+    /// Cells closer than that to the focus are loaded.
+    pub load_radius: f32,
+    /// Loaded cells farther than that from the focus are unloaded.
+    /// Should be larger than `load_radius`, so the cells on the border
+    /// are not reloaded every time the focus moves back and forth.
+    pub unload_radius: f32,
+    /// Maximal number of the cells loading at once.
+    /// The hub processes the requests in order, so the larger queue
+    /// delays the cells that became the nearest in the meantime.
+    pub max_loading: usize,
+    /// Maximal number of the loaded (and loading) cells.
+    /// The nearest cells are loaded first.
+    pub max_loaded: usize,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        StreamingConfig {
+            load_radius: 100.0,
+            unload_radius: 150.0,
+            max_loading: 2,
+            max_loaded: 32,
+        }
+    }
+}
+
+struct Cell {
+    min: Vec3,
+    max: Vec3,
+    bundle: Vec<AssetID>,
+    state: CellState,
+    request: Option<AssetRequestID>,
+}
+
+impl Cell {
+    fn distance(&self, point: Vec3) -> f32 {
+        point.distance(point.clamp(self.min, self.max))
+    }
+}
+
+/// Streams the world partitioned into cells: loads the asset bundles of the cells
+/// near the focus and unloads the distant ones, reporting the changes
+/// with `StreamingEvent` events.
+/// Assets shared by several cells are kept loaded while any of them is active.
+#[derive(Component)]
+pub struct WorldStreamer {
+    config: StreamingConfig,
+    cells: Vec<Cell>,
+    focus: Option<Vec3>,
+    // Number of the active cells referencing the asset
+    holders: HashMap<AssetID, usize>,
+}
+
+impl WorldStreamer {
+    pub fn new(config: StreamingConfig) -> Self {
+        WorldStreamer {
+            config,
+            cells: Vec::new(),
+            focus: None,
+            holders: HashMap::new(),
+        }
+    }
+
+    /// Adds the cell covering the given box.
+    pub fn add_cell(&mut self, min: Vec3, max: Vec3, bundle: Vec<AssetID>) -> CellID {
+        let id = CellID(self.cells.len());
+        self.cells.push(Cell {
+            min: min.min(max),
+            max: min.max(max),
+            bundle,
+            state: CellState::Unloaded,
+            request: None,
+        });
+        id
+    }
+
+    /// Adds the cell of the regular grid with the given cell size.
+    /// Cell `(0, 0, 0)` spans from the origin to `(size, size, size)`.
+    pub fn add_grid_cell(&mut self, size: f32, coord: IVec3, bundle: Vec<AssetID>) -> CellID {
+        let min = coord.as_vec3() * size;
+        self.add_cell(min, min + Vec3::splat(size), bundle)
+    }
+
+    pub fn state(&self, id: CellID) -> Option<CellState> {
+        self.cells.get(id.0).map(|cell| cell.state)
+    }
+
+    pub fn set_focus(&mut self, focus: Vec3) {
+        self.focus = Some(focus);
+    }
+
+    /// Moves the streamer into the ECS world.
+    /// The cells are updated on each main loop tick, the focus is set
+    /// by the `StreamingFocusEvent` events.
+    /// Requires the `AssetHub` attached to the same world.
+    pub fn attach_to_ecs(self, world: &mut World) {
+        let entity = world.spawn();
+        world.insert(entity, self);
+        world.add_handler(Self::focus_handler);
+        world.add_handler(Self::hub_handler);
+        world.add_handler(Self::tick_handler.low());
+    }
+
+    fn focus_handler(r: Receiver<StreamingFocusEvent>, mut streamer: Single<&mut WorldStreamer>) {
+        streamer.set_focus(r.event.0);
+    }
+
+    fn hub_handler(
+        r: Receiver<AssetHubEvent>,
+        mut streamer: Single<&mut WorldStreamer>,
+        mut sender: Sender<StreamingEvent>,
+    ) {
+        if let AssetHubEvent::RequestFinished(rid, result) = r.event {
+            let error = result.as_ref().err().map(|err| err.to_string());
+            if let Some(event) = streamer.request_finished(*rid, error) {
+                sender.send(event);
+            }
+        }
+    }
+
+    fn tick_handler(
+        _: Receiver<TickEvent>,
+        mut streamer: Single<&mut WorldStreamer>,
+        mut hub: Single<&mut AssetHub>,
+        mut sender: Sender<StreamingEvent>,
+    ) {
+        for event in streamer.update(&mut hub) {
+            sender.send(event);
+        }
+    }
+
+    fn request_finished(
+        &mut self,
+        rid: AssetRequestID,
+        error: Option<String>,
+    ) -> Option<StreamingEvent> {
+        let index = self.cells.iter().position(|c| c.request == Some(rid))?;
+        let id = CellID(index);
+        let cell = &mut self.cells[index];
+        cell.request = None;
+
+        match (cell.state, error) {
+            (CellState::Loading, None) => {
+                debug!("{} loaded", id);
+                cell.state = CellState::Loaded;
+                Some(StreamingEvent::CellLoaded(id))
+            }
+            (CellState::Loading, Some(error)) => {
+                warn!("Failed to load {}: {}", id, error);
+                cell.state = CellState::Failed;
+                Some(StreamingEvent::CellFailed(id, error))
+            }
+            (_, error) => {
+                if let Some(error) = error {
+                    warn!("Failed to free {}: {}", id, error);
+                }
+                debug!("{} unloaded", id);
+                cell.state = CellState::Unloaded;
+                Some(StreamingEvent::CellUnloaded(id))
+            }
+        }
+    }
+
+    fn update(&mut self, hub: &mut AssetHub) -> Vec<StreamingEvent> {
+        let mut events = Vec::new();
+        let Some(focus) = self.focus else {
+            return events;
+        };
+
+        // Free the cells despawned since the last tick.
+        // The assets still used by the other cells stay loaded
+        for index in 0..self.cells.len() {
+            if self.cells[index].state != CellState::Unloading {
+                continue;
+            }
+            let mut unused = Vec::new();
+            for id in &self.cells[index].bundle {
+                let holders = self.holders.get_mut(id).unwrap();
+                *holders -= 1;
+                if *holders == 0 {
+                    self.holders.remove(id);
+                    unused.push(id.clone());
+                }
+            }
+
+            let cell = &mut self.cells[index];
+            if unused.is_empty() {
+                cell.state = CellState::Unloaded;
+                events.push(StreamingEvent::CellUnloaded(CellID(index)));
+            } else {
+                cell.state = CellState::Freeing;
+                cell.request =
+                    Some(hub.request(AssetRequest::Free(AssetRequestQuery::ByIDs(unused))));
+            }
+        }
+
+        // Hysteresis: the cells are unloaded only beyond the unload radius
+        for (index, cell) in self.cells.iter_mut().enumerate() {
+            if cell.distance(focus) <= self.config.unload_radius {
+                continue;
+            }
+            match cell.state {
+                CellState::Loaded => {
+                    cell.state = CellState::Unloading;
+                    events.push(StreamingEvent::CellUnloading(CellID(index)));
+                }
+                // Nothing is spawned, free the partially loaded bundle right away
+                CellState::Failed => cell.state = CellState::Unloading,
+                _ => {}
+            }
+        }
+
+        // Load the nearest cells within the budgets
+        let mut loading = 0;
+        let mut active = 0;
+        for cell in &self.cells {
+            match cell.state {
+                CellState::Loading => {
+                    loading += 1;
+                    active += 1;
+                }
+                CellState::Loaded | CellState::Failed => active += 1,
+                _ => {}
+            }
+        }
+        let mut candidates: Vec<(usize, f32)> = self
+            .cells
+            .iter()
+            .enumerate()
+            .filter(|(_, cell)| cell.state == CellState::Unloaded)
+            .map(|(index, cell)| (index, cell.distance(focus)))
+            .filter(|(_, distance)| *distance <= self.config.load_radius)
+            .collect();
+        candidates.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        for (index, _) in candidates {
+            if loading >= self.config.max_loading || active >= self.config.max_loaded {
+                break;
+            }
+            let cell = &mut self.cells[index];
+            for id in &cell.bundle {
+                *self.holders.entry(id.clone()).or_default() += 1;
+            }
+            debug!("Loading {}", CellID(index));
+            cell.state = CellState::Loading;
+            cell.request = Some(hub.request(AssetRequest::Load(AssetRequestQuery::ByIDs(
+                cell.bundle.clone(),
+            ))));
+            loading += 1;
+            active += 1;
+        }
+
+        events
+    }
+}
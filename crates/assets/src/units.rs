@@ -0,0 +1,335 @@
+//! Coordinate system and unit conventions of the engine.
+//! The content is converted into them once, at the import (see the `conventions`
+//! of the dacgen mesh assets), so the runtime never deals with the mixed axes.
+
+use crate::ir::mesh::{IRIndexType, IRMesh, IRMeshBounds, IRMeshVertex, IRSubMesh, IRTopology};
+use crate::AssetID;
+use glam::{Mat3, Mat4, Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::mem::offset_of;
+
+// Bounds of the sane content size in meters. Anything out of them is likely
+// imported in the wrong unit (e.g. the centimeters taken as the meters).
+const MIN_CONTENT_SIZE: f32 = 1e-3;
+const MAX_CONTENT_SIZE: f32 = 1e4;
+// Share of the triangle area winding against the normals that is reported
+const FLIPPED_WINDING_SHARE: f32 = 0.5;
+
+/// Direction along one of the coordinate axes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Axis {
+    #[serde(rename = "+X")]
+    PosX,
+    #[serde(rename = "-X")]
+    NegX,
+    #[serde(rename = "+Y")]
+    PosY,
+    #[serde(rename = "-Y")]
+    NegY,
+    #[serde(rename = "+Z")]
+    PosZ,
+    #[serde(rename = "-Z")]
+    NegZ,
+}
+
+impl Axis {
+    pub fn vector(self) -> Vec3 {
+        match self {
+            Axis::PosX => Vec3::X,
+            Axis::NegX => Vec3::NEG_X,
+            Axis::PosY => Vec3::Y,
+            Axis::NegY => Vec3::NEG_Y,
+            Axis::PosZ => Vec3::Z,
+            Axis::NegZ => Vec3::NEG_Z,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Handedness {
+    Right,
+    Left,
+}
+
+/// Axes of the coordinate system. `forward` is the direction the front
+/// of the model faces, the right axis follows from the handedness.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CoordinateSystem {
+    pub up: Axis,
+    pub forward: Axis,
+    pub handedness: Handedness,
+}
+
+impl CoordinateSystem {
+    /// Coordinate system of the engine: Y up, the models face +Z, right-handed.
+    /// The same as the glTF one, so the glTF content is taken as is.
+    pub const ENGINE: CoordinateSystem =
+        CoordinateSystem::new(Axis::PosY, Axis::PosZ, Handedness::Right);
+
+    pub const fn new(up: Axis, forward: Axis, handedness: Handedness) -> Self {
+        CoordinateSystem {
+            up,
+            forward,
+            handedness,
+        }
+    }
+
+    /// The up and the forward axes must not lie on the same line.
+    pub fn is_valid(&self) -> bool {
+        self.up.vector().dot(self.forward.vector()) == 0.0
+    }
+
+    /// Direction to the right of the model.
+    pub fn right(&self) -> Vec3 {
+        let (up, forward) = (self.up.vector(), self.forward.vector());
+        match self.handedness {
+            Handedness::Right => forward.cross(up),
+            Handedness::Left => up.cross(forward),
+        }
+    }
+
+    // Columns are the right, up and forward directions
+    fn basis(&self) -> Mat3 {
+        Mat3::from_cols(self.right(), self.up.vector(), self.forward.vector())
+    }
+
+    /// Matrix moving the positions and the directions from this system into the target one.
+    /// Mirrors the content if the handedness differs (see `flips_winding`).
+    pub fn conversion_to(&self, target: &CoordinateSystem) -> Mat3 {
+        // The bases are orthonormal, so the inverse is the transpose
+        target.basis() * self.basis().transpose()
+    }
+
+    /// The conversion into the target system mirrors the content,
+    /// so the winding of its triangles must be reversed.
+    pub fn flips_winding(&self, target: &CoordinateSystem) -> bool {
+        self.handedness != target.handedness
+    }
+}
+
+impl Default for CoordinateSystem {
+    fn default() -> Self {
+        CoordinateSystem::ENGINE
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+pub enum LengthUnit {
+    Millimeter,
+    Centimeter,
+    #[default]
+    Meter,
+    Kilometer,
+    Inch,
+    Foot,
+    /// Size of the unit in meters.
+    Custom(f32),
+}
+
+impl LengthUnit {
+    /// Size of the unit in meters, the unit of the engine.
+    pub fn meters(self) -> f32 {
+        match self {
+            LengthUnit::Millimeter => 0.001,
+            LengthUnit::Centimeter => 0.01,
+            LengthUnit::Meter => 1.0,
+            LengthUnit::Kilometer => 1000.0,
+            LengthUnit::Inch => 0.0254,
+            LengthUnit::Foot => 0.3048,
+            LengthUnit::Custom(meters) => meters,
+        }
+    }
+}
+
+/// Axes and the unit the content was authored in.
+/// In the TOML: `{ up = "+Z", forward = "-Y", handedness = "Right", unit = "Centimeter" }`,
+/// the omitted fields are taken from the engine conventions.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Conventions {
+    #[serde(flatten)]
+    pub system: CoordinateSystem,
+    pub unit: LengthUnit,
+}
+
+impl Conventions {
+    /// Conventions of the engine: Y up, +Z forward, right-handed, meters.
+    pub const ENGINE: Conventions = Conventions {
+        system: CoordinateSystem::ENGINE,
+        unit: LengthUnit::Meter,
+    };
+
+    /// Conventions of the glTF files, by the specification.
+    pub const GLTF: Conventions = Conventions::ENGINE;
+
+    pub fn new(system: CoordinateSystem, unit: LengthUnit) -> Self {
+        Conventions { system, unit }
+    }
+
+    /// Matrix converting the content into the engine conventions.
+    pub fn to_engine(&self) -> Mat4 {
+        Mat4::from_mat3(self.system.conversion_to(&CoordinateSystem::ENGINE))
+            * Mat4::from_scale(Vec3::splat(self.unit.meters()))
+    }
+
+    /// The conversion into the engine conventions mirrors the content.
+    pub fn flips_winding(&self) -> bool {
+        self.system.flips_winding(&CoordinateSystem::ENGINE)
+    }
+
+    pub fn is_engine(&self) -> bool {
+        *self == Conventions::ENGINE
+    }
+}
+
+/// Content that likely does not follow the engine conventions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConventionIssue {
+    /// Most of the triangle area winds against the vertex normals:
+    /// the content is mirrored, e.g. a left-handed source imported as a right-handed one.
+    FlippedWinding { share: f32 },
+    /// The largest extent of the content in meters is implausible,
+    /// it was likely authored in the other unit.
+    Size { extent: f32 },
+}
+
+impl Display for ConventionIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConventionIssue::FlippedWinding { share } => write!(
+                f,
+                "{:.0}% of the triangle area winds against the normals, check the handedness",
+                share * 100.0
+            ),
+            ConventionIssue::Size { extent } => {
+                write!(f, "content is {} m large, check the unit", extent)
+            }
+        }
+    }
+}
+
+/// Checks the mesh against the engine conventions. The check is heuristic:
+/// it cannot tell the up axis, use the `axes_gizmo` next to the content for that.
+pub fn check_mesh(mesh: &IRMesh) -> Vec<ConventionIssue> {
+    let mut issues = Vec::new();
+
+    let extent = (mesh.bounds.max() - mesh.bounds.min()).max_element();
+    let empty = mesh
+        .submesh
+        .iter()
+        .all(|submesh| submesh.vertices.is_empty());
+    if !empty && !(MIN_CONTENT_SIZE..=MAX_CONTENT_SIZE).contains(&extent) {
+        issues.push(ConventionIssue::Size { extent });
+    }
+
+    let (mut total, mut flipped) = (0.0, 0.0);
+    for submesh in &mesh.submesh {
+        if submesh.topology != IRTopology::Triangles {
+            continue;
+        }
+        let vertices = decode_vertices(&submesh.vertices);
+        let indices = decode_indices(&submesh.indices, &mesh.index_type);
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| vertices[i]);
+            // Counter-clockwise triangles face the normals
+            let face = (b.0 - a.0).cross(c.0 - a.0);
+            let area = face.length();
+            total += area;
+            if face.dot(a.1 + b.1 + c.1) < 0.0 {
+                flipped += area;
+            }
+        }
+    }
+    if total > 0.0 && flipped / total > FLIPPED_WINDING_SHARE {
+        issues.push(ConventionIssue::FlippedWinding {
+            share: flipped / total,
+        });
+    }
+
+    issues
+}
+
+// Positions and normals of the vertices
+fn decode_vertices(bytes: &[u8]) -> Vec<(Vec3, Vec3)> {
+    let float = |vertex: &[u8], offset: usize| {
+        f32::from_ne_bytes(vertex[offset..offset + 4].try_into().unwrap())
+    };
+    let vec3 = |vertex: &[u8], offset: usize| {
+        Vec3::new(
+            float(vertex, offset),
+            float(vertex, offset + 4),
+            float(vertex, offset + 8),
+        )
+    };
+    bytes
+        .chunks_exact(size_of::<IRMeshVertex>())
+        .map(|vertex| {
+            (
+                vec3(vertex, offset_of!(IRMeshVertex, position)),
+                vec3(vertex, offset_of!(IRMeshVertex, normal)),
+            )
+        })
+        .collect()
+}
+
+fn decode_indices(bytes: &[u8], index_type: &IRIndexType) -> Vec<usize> {
+    match index_type {
+        IRIndexType::U16 => bytes
+            .chunks_exact(2)
+            .map(|i| u16::from_le_bytes([i[0], i[1]]) as usize)
+            .collect(),
+        IRIndexType::U32 => bytes
+            .chunks_exact(4)
+            .map(|i| u32::from_le_bytes([i[0], i[1], i[2], i[3]]) as usize)
+            .collect(),
+    }
+}
+
+/// Lines along the right, up and forward axes of the engine, `length` meters long.
+/// Each axis is a separate submesh with its material, so they can be told apart.
+/// Render it next to the imported content (e.g. created with `AssetHub::create_procedural`)
+/// to see if the content stands up and faces forward.
+pub fn axes_gizmo(length: f32, materials: [Option<AssetID>; 3]) -> IRMesh {
+    let system = CoordinateSystem::ENGINE;
+    let axes = [system.right(), system.up.vector(), system.forward.vector()];
+    let submesh = axes
+        .into_iter()
+        .zip(materials)
+        .map(|(axis, material)| {
+            let mut vertices = Vec::new();
+            for (position, u) in [(Vec3::ZERO, 0.0), (axis * length, 1.0)] {
+                let vertex = IRMeshVertex::new(position, axis, Vec2::new(u, 0.0));
+                vertices.extend_from_slice(vertex.into_bytes());
+            }
+            let indices = [0u16, 1].iter().flat_map(|i| i.to_le_bytes()).collect();
+            IRSubMesh {
+                vertices,
+                indices,
+                material,
+                bounds: IRMeshBounds {
+                    min: Vec3::ZERO.min(axis * length).to_array(),
+                    max: Vec3::ZERO.max(axis * length).to_array(),
+                },
+                topology: IRTopology::Lines,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let (min, max) = submesh
+        .iter()
+        .fold((Vec3::ZERO, Vec3::ZERO), |(min, max), submesh| {
+            (min.min(submesh.bounds.min()), max.max(submesh.bounds.max()))
+        });
+    IRMesh {
+        submesh,
+        bounds: IRMeshBounds {
+            min: min.to_array(),
+            max: max.to_array(),
+        },
+        index_type: IRIndexType::U16,
+        static_batching: false,
+        bvh: None,
+    }
+}
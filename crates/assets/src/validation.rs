@@ -0,0 +1,665 @@
+//! Sanity checks of the IR content, so the corrupted or hand-edited containers
+//! are rejected with the precise reason instead of failing deep inside the
+//! factories. Enabled in the hub by `AssetHub::set_validation`, can also be used
+//! directly on the IRs read from the container:
+//!
+//! ```
+//! use dawn_assets::ir::texture::{IRPixelFormat, IRTexture, IRTextureType};
+//! use dawn_assets::ir::IRAsset;
+//! use dawn_assets::validation::{ValidationError, ValidationRules};
+//!
+//! let texture = IRTexture {
+//!     data: vec![0; 3 * 4],
+//!     texture_type: IRTextureType::Texture2D { width: 3, height: 1 },
+//!     pixel_format: IRPixelFormat::R8G8B8A8,
+//!     ..Default::default()
+//! };
+//! let ir = IRAsset::Texture(texture);
+//! assert_eq!(ir.validate(&ValidationRules::default()), Ok(()));
+//!
+//! let rules = ValidationRules {
+//!     power_of_two_textures: true,
+//!     ..Default::default()
+//! };
+//! assert_eq!(
+//!     ir.validate(&rules),
+//!     Err(ValidationError::NotPowerOfTwo([3, 1, 1]))
+//! );
+//! ```
+
+use crate::ir::audio::IRAudio;
+use crate::ir::mesh::{IRIndexType, IRMesh, IRMeshBvh, IRMeshVertex, IRTopology};
+use crate::ir::shader::{IRShader, IRShaderSourceKind};
+use crate::ir::texture::{IRPixelFormat, IRTexture, IRTextureType};
+use crate::ir::IRAsset;
+use std::ops::RangeInclusive;
+use thiserror::Error;
+
+/// What the IRs are checked against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationRules {
+    /// Reject the textures with any dimension not a power of two
+    /// (e.g. for the targets without the NPOT support).
+    pub power_of_two_textures: bool,
+    /// Sample rates of the audio considered sane.
+    pub sample_rates: RangeInclusive<u32>,
+    /// Maximal number of the audio channels.
+    pub max_channels: u8,
+}
+
+impl Default for ValidationRules {
+    fn default() -> Self {
+        ValidationRules {
+            power_of_two_textures: false,
+            sample_rates: 8000..=192000,
+            max_channels: 8,
+        }
+    }
+}
+
+/// The first problem found in the IR.
+/// Submeshes are referenced by their index in the mesh.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    #[error("Texture type is unknown")]
+    UnknownTextureType,
+    #[error("Pixel format is unknown")]
+    UnknownPixelFormat,
+    #[error("Texture has zero size ({0:?})")]
+    EmptyTexture([u32; 3]),
+    #[error("Texture size {0:?} is not a power of two")]
+    NotPowerOfTwo([u32; 3]),
+    #[error("Texture data has {actual} bytes, {expected} expected for {texture_type:?} of {pixel_format:?}")]
+    TextureDataSize {
+        texture_type: IRTextureType,
+        pixel_format: IRPixelFormat,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("Vertex data of submesh {submesh} has {bytes} bytes, not a multiple of the vertex size {vertex}")]
+    VertexDataSize {
+        submesh: usize,
+        bytes: usize,
+        vertex: usize,
+    },
+    #[error("Index data of submesh {submesh} has {bytes} bytes, not a multiple of the {index_type:?} size")]
+    IndexDataSize {
+        submesh: usize,
+        bytes: usize,
+        index_type: IRIndexType,
+    },
+    #[error("Index {index} at {position} of submesh {submesh} is out of {vertices} vertices")]
+    IndexOutOfBounds {
+        submesh: usize,
+        position: usize,
+        index: usize,
+        vertices: usize,
+    },
+    #[error("Submesh {submesh} has {indices} indices, not whole {topology:?}")]
+    IncompletePrimitive {
+        submesh: usize,
+        indices: usize,
+        topology: IRTopology,
+    },
+    #[error("Node {0} of the mesh BVH refers out of the hierarchy or of its triangles")]
+    InvalidBvhNode(usize),
+    #[error("Shader has no sources")]
+    NoShaderSources,
+    #[error("Shader source of the {0:?} stage is empty")]
+    EmptyShaderSource(IRShaderSourceKind),
+    #[error("Shader has no {0:?} stage")]
+    MissingShaderStage(IRShaderSourceKind),
+    #[error("Compute shader is mixed with the {0:?} stage")]
+    MixedComputeShader(IRShaderSourceKind),
+    #[error("Sample rate {rate} Hz is out of {min}..={max} Hz")]
+    SampleRate { rate: u32, min: u32, max: u32 },
+    #[error("Audio has {0} channels, 1..={1} supported")]
+    Channels(u8, u8),
+    #[error("Audio data has {actual} samples, {expected} expected")]
+    AudioDataSize { expected: usize, actual: usize },
+    #[error("Audio chunk {chunk} starts at frame {start}, {expected} expected")]
+    AudioChunkGap {
+        chunk: usize,
+        start: usize,
+        expected: usize,
+    },
+}
+
+impl IRAsset {
+    /// Checks the content of the IR. The types without the checks are always valid.
+    pub fn validate(&self, rules: &ValidationRules) -> Result<(), ValidationError> {
+        match self {
+            IRAsset::Texture(texture) => validate_texture(texture, rules),
+            IRAsset::Mesh(mesh) => validate_mesh(mesh),
+            IRAsset::Shader(shader) => validate_shader(shader),
+            IRAsset::Audio(audio) => validate_audio(audio, rules),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn validate_texture(texture: &IRTexture, rules: &ValidationRules) -> Result<(), ValidationError> {
+    if texture.texture_type == IRTextureType::Unknown {
+        return Err(ValidationError::UnknownTextureType);
+    }
+    let extent = texture.texture_type.extent();
+    if extent.contains(&0) {
+        return Err(ValidationError::EmptyTexture(extent));
+    }
+    if rules.power_of_two_textures && !extent.iter().all(|d| d.is_power_of_two()) {
+        return Err(ValidationError::NotPowerOfTwo(extent));
+    }
+
+    let pixel = texture
+        .pixel_format
+        .bytes_per_pixel()
+        .ok_or(ValidationError::UnknownPixelFormat)?;
+    // Multisample textures are rendered to, not uploaded
+    if matches!(
+        texture.texture_type,
+        IRTextureType::Texture2DMultisample { .. }
+            | IRTextureType::Texture2DMultisampleArray { .. }
+    ) {
+        return Ok(());
+    }
+    // The size overflowing is never matched by the data
+    let expected = texture
+        .texture_type
+        .texels()
+        .and_then(|texels| texels.checked_mul(pixel));
+    if expected != Some(texture.data.len()) {
+        return Err(ValidationError::TextureDataSize {
+            texture_type: texture.texture_type,
+            pixel_format: texture.pixel_format,
+            expected: expected.unwrap_or(usize::MAX),
+            actual: texture.data.len(),
+        });
+    }
+    Ok(())
+}
+
+fn validate_mesh(mesh: &IRMesh) -> Result<(), ValidationError> {
+    let vertex = size_of::<IRMeshVertex>();
+    let index = match mesh.index_type {
+        IRIndexType::U16 => 2,
+        IRIndexType::U32 => 4,
+    };
+
+    for (i, submesh) in mesh.submesh.iter().enumerate() {
+        if submesh.vertices.len() % vertex != 0 {
+            return Err(ValidationError::VertexDataSize {
+                submesh: i,
+                bytes: submesh.vertices.len(),
+                vertex,
+            });
+        }
+        if submesh.indices.len() % index != 0 {
+            return Err(ValidationError::IndexDataSize {
+                submesh: i,
+                bytes: submesh.indices.len(),
+                index_type: mesh.index_type.clone(),
+            });
+        }
+
+        let indices = submesh.indices.len() / index;
+        let per_primitive = match submesh.topology {
+            IRTopology::Points => 1,
+            IRTopology::Lines => 2,
+            IRTopology::Triangles => 3,
+        };
+        if indices % per_primitive != 0 {
+            return Err(ValidationError::IncompletePrimitive {
+                submesh: i,
+                indices,
+                topology: submesh.topology.clone(),
+            });
+        }
+
+        let vertices = submesh.vertices.len() / vertex;
+        for (position, bytes) in submesh.indices.chunks_exact(index).enumerate() {
+            let value = match mesh.index_type {
+                IRIndexType::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as usize,
+                IRIndexType::U32 => {
+                    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+                }
+            };
+            if value >= vertices {
+                return Err(ValidationError::IndexOutOfBounds {
+                    submesh: i,
+                    position,
+                    index: value,
+                    vertices,
+                });
+            }
+        }
+    }
+
+    if let Some(bvh) = &mesh.bvh {
+        validate_bvh(bvh)?;
+    }
+    Ok(())
+}
+
+// The picking walks the hierarchy without the checks
+fn validate_bvh(bvh: &IRMeshBvh) -> Result<(), ValidationError> {
+    // Each node but the root is a child of exactly one inner node before it
+    let mut parents = vec![0u8; bvh.nodes.len()];
+    for (index, node) in bvh.nodes.iter().enumerate() {
+        if node.count == 0 {
+            let right = node.offset as usize;
+            if right <= index + 1 || right >= bvh.nodes.len() {
+                return Err(ValidationError::InvalidBvhNode(index));
+            }
+            for child in [index + 1, right] {
+                parents[child] = parents[child].saturating_add(1);
+            }
+        } else {
+            let end = (node.offset as usize).checked_add(node.count as usize);
+            if end.is_none_or(|end| end > bvh.triangles.len()) {
+                return Err(ValidationError::InvalidBvhNode(index));
+            }
+        }
+    }
+    match parents.iter().skip(1).position(|parents| *parents != 1) {
+        Some(index) => Err(ValidationError::InvalidBvhNode(index + 1)),
+        None => Ok(()),
+    }
+}
+
+fn validate_shader(shader: &IRShader) -> Result<(), ValidationError> {
+    if shader.sources.is_empty() {
+        return Err(ValidationError::NoShaderSources);
+    }
+    if let Some((kind, _)) = shader.sources.iter().find(|(_, source)| source.is_empty()) {
+        return Err(ValidationError::EmptyShaderSource(*kind));
+    }
+
+    // Either the compute program or the graphics one with both the mandatory stages
+    if shader.sources.contains_key(&IRShaderSourceKind::Compute) {
+        return match shader
+            .sources
+            .keys()
+            .find(|kind| **kind != IRShaderSourceKind::Compute)
+        {
+            Some(kind) => Err(ValidationError::MixedComputeShader(*kind)),
+            None => Ok(()),
+        };
+    }
+    for stage in [IRShaderSourceKind::Vertex, IRShaderSourceKind::Fragment] {
+        if !shader.sources.contains_key(&stage) {
+            return Err(ValidationError::MissingShaderStage(stage));
+        }
+    }
+    Ok(())
+}
+
+fn validate_audio(audio: &IRAudio, rules: &ValidationRules) -> Result<(), ValidationError> {
+    if !rules.sample_rates.contains(&audio.sample_rate) {
+        return Err(ValidationError::SampleRate {
+            rate: audio.sample_rate,
+            min: *rules.sample_rates.start(),
+            max: *rules.sample_rates.end(),
+        });
+    }
+    if audio.channels == 0 || audio.channels > rules.max_channels {
+        return Err(ValidationError::Channels(
+            audio.channels,
+            rules.max_channels,
+        ));
+    }
+
+    if audio.is_encoded() {
+        // The length is counted when building, the stream is checked while decoding
+        return Ok(());
+    }
+    if audio.is_packed() {
+        // The seek table must cover the whole clip without gaps
+        let mut expected = 0;
+        for (i, chunk) in audio.chunks.iter().enumerate() {
+            if chunk.start != expected {
+                return Err(ValidationError::AudioChunkGap {
+                    chunk: i,
+                    start: chunk.start,
+                    expected,
+                });
+            }
+            expected = expected.saturating_add(chunk.frames);
+        }
+        if expected != audio.length {
+            return Err(ValidationError::AudioDataSize {
+                expected: audio.length.saturating_mul(audio.channels as usize),
+                actual: expected.saturating_mul(audio.channels as usize),
+            });
+        }
+    } else {
+        let expected = audio.length.saturating_mul(audio.channels as usize);
+        if audio.data.len() != expected {
+            return Err(ValidationError::AudioDataSize {
+                expected,
+                actual: audio.data.len(),
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::audio::IRAudioChunk;
+    use crate::ir::mesh::{IRMeshBounds, IRSubMesh};
+    use glam::Vec3;
+
+    fn texture(texture_type: IRTextureType, pixel_format: IRPixelFormat, bytes: usize) -> IRAsset {
+        IRAsset::Texture(IRTexture {
+            data: vec![0; bytes],
+            texture_type,
+            pixel_format,
+            ..Default::default()
+        })
+    }
+
+    fn validate(ir: IRAsset) -> Result<(), ValidationError> {
+        ir.validate(&ValidationRules::default())
+    }
+
+    #[test]
+    fn textures() {
+        let rgba = IRPixelFormat::R8G8B8A8;
+        let square = IRTextureType::Texture2D {
+            width: 4,
+            height: 4,
+        };
+        assert_eq!(validate(texture(square, rgba, 64)), Ok(()));
+        assert_eq!(
+            validate(texture(IRTextureType::Unknown, rgba, 0)),
+            Err(ValidationError::UnknownTextureType)
+        );
+        assert_eq!(
+            validate(texture(square, IRPixelFormat::Unknown, 64)),
+            Err(ValidationError::UnknownPixelFormat)
+        );
+        assert_eq!(
+            validate(texture(
+                IRTextureType::Texture2D {
+                    width: 4,
+                    height: 0
+                },
+                rgba,
+                0
+            )),
+            Err(ValidationError::EmptyTexture([4, 0, 1]))
+        );
+        assert_eq!(
+            validate(texture(square, rgba, 60)),
+            Err(ValidationError::TextureDataSize {
+                texture_type: square,
+                pixel_format: rgba,
+                expected: 64,
+                actual: 60,
+            })
+        );
+
+        let npot = IRTextureType::Texture2D {
+            width: 3,
+            height: 4,
+        };
+        let rules = ValidationRules {
+            power_of_two_textures: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            texture(npot, rgba, 48).validate(&rules),
+            Err(ValidationError::NotPowerOfTwo([3, 4, 1]))
+        );
+    }
+
+    #[test]
+    fn overflowing_texture_size() {
+        let huge = IRTextureType::TextureCubeArray {
+            size: u32::MAX,
+            layers: u32::MAX,
+        };
+        assert_eq!(huge.texels(), None);
+        assert_eq!(
+            validate(texture(huge, IRPixelFormat::R8G8B8A8, 16)),
+            Err(ValidationError::TextureDataSize {
+                texture_type: huge,
+                pixel_format: IRPixelFormat::R8G8B8A8,
+                expected: usize::MAX,
+                actual: 16,
+            })
+        );
+
+        // The texels fit, the bytes do not
+        let wide = IRTextureType::Texture2D {
+            width: u32::MAX,
+            height: u32::MAX,
+        };
+        assert!(wide.texels().is_some());
+        assert!(matches!(
+            validate(texture(wide, IRPixelFormat::R8G8B8A8, 16)),
+            Err(ValidationError::TextureDataSize { .. })
+        ));
+    }
+
+    fn mesh(index_type: IRIndexType, submesh: IRSubMesh) -> IRAsset {
+        IRAsset::Mesh(IRMesh {
+            submesh: vec![submesh],
+            bounds: IRMeshBounds {
+                min: [0.0; 3],
+                max: [0.0; 3],
+            },
+            index_type,
+            static_batching: false,
+            bvh: None,
+        })
+    }
+
+    fn triangles(vertices: usize, indices: &[u16]) -> IRSubMesh {
+        IRSubMesh {
+            vertices: vec![0; vertices * size_of::<IRMeshVertex>()],
+            indices: indices.iter().flat_map(|i| i.to_le_bytes()).collect(),
+            topology: IRTopology::Triangles,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn meshes() {
+        let vertex = size_of::<IRMeshVertex>();
+        assert_eq!(
+            validate(mesh(IRIndexType::U16, triangles(3, &[0, 1, 2]))),
+            Ok(())
+        );
+
+        let mut partial_vertex = triangles(3, &[0, 1, 2]);
+        partial_vertex.vertices.pop();
+        assert_eq!(
+            validate(mesh(IRIndexType::U16, partial_vertex)),
+            Err(ValidationError::VertexDataSize {
+                submesh: 0,
+                bytes: 3 * vertex - 1,
+                vertex,
+            })
+        );
+
+        // Three u16 indices are not whole u32 ones
+        assert_eq!(
+            validate(mesh(IRIndexType::U32, triangles(3, &[0, 1, 2]))),
+            Err(ValidationError::IndexDataSize {
+                submesh: 0,
+                bytes: 6,
+                index_type: IRIndexType::U32,
+            })
+        );
+        assert_eq!(
+            validate(mesh(IRIndexType::U16, triangles(3, &[0, 1, 3]))),
+            Err(ValidationError::IndexOutOfBounds {
+                submesh: 0,
+                position: 2,
+                index: 3,
+                vertices: 3,
+            })
+        );
+        assert_eq!(
+            validate(mesh(IRIndexType::U16, triangles(3, &[0, 1]))),
+            Err(ValidationError::IncompletePrimitive {
+                submesh: 0,
+                indices: 2,
+                topology: IRTopology::Triangles,
+            })
+        );
+    }
+
+    #[test]
+    fn mesh_bvh() {
+        let strip = (0..20)
+            .map(|i| {
+                let offset = Vec3::X * i as f32;
+                [Vec3::ZERO, Vec3::X, Vec3::Y].map(|v| v + offset)
+            })
+            .collect();
+        let bvh = IRMeshBvh::build(strip);
+        let with_bvh = |bvh: &IRMeshBvh| {
+            let IRAsset::Mesh(mut mesh) = mesh(IRIndexType::U16, triangles(3, &[0, 1, 2])) else {
+                unreachable!();
+            };
+            mesh.bvh = Some(bvh.clone());
+            validate(IRAsset::Mesh(mesh))
+        };
+        assert_eq!(with_bvh(&bvh), Ok(()));
+
+        // The cycle
+        let mut corrupted = bvh.clone();
+        corrupted.nodes[0].offset = 0;
+        assert_eq!(
+            with_bvh(&corrupted),
+            Err(ValidationError::InvalidBvhNode(0))
+        );
+
+        // The leaf out of the triangles
+        let mut corrupted = bvh.clone();
+        let leaf = corrupted.nodes.iter().position(|n| n.count > 0).unwrap();
+        corrupted.nodes[leaf].offset = 19;
+        assert_eq!(
+            with_bvh(&corrupted),
+            Err(ValidationError::InvalidBvhNode(leaf))
+        );
+
+        // The node shared by two parents, the last one unreachable
+        let mut corrupted = bvh.clone();
+        let last = corrupted.nodes.len() - 1;
+        let parent = corrupted
+            .nodes
+            .iter()
+            .position(|n| n.count == 0 && n.offset as usize == last)
+            .unwrap();
+        corrupted.nodes[parent].offset -= 1;
+        assert!(matches!(
+            with_bvh(&corrupted),
+            Err(ValidationError::InvalidBvhNode(_))
+        ));
+    }
+
+    fn shader(stages: &[(IRShaderSourceKind, &str)]) -> IRAsset {
+        IRAsset::Shader(IRShader {
+            sources: stages
+                .iter()
+                .map(|(kind, source)| (*kind, source.as_bytes().to_vec()))
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn shaders() {
+        use IRShaderSourceKind::{Compute, Fragment, Vertex};
+        assert_eq!(validate(shader(&[(Vertex, "v"), (Fragment, "f")])), Ok(()));
+        assert_eq!(validate(shader(&[(Compute, "c")])), Ok(()));
+        assert_eq!(validate(shader(&[])), Err(ValidationError::NoShaderSources));
+        assert_eq!(
+            validate(shader(&[(Vertex, "v"), (Fragment, "")])),
+            Err(ValidationError::EmptyShaderSource(Fragment))
+        );
+        assert_eq!(
+            validate(shader(&[(Vertex, "v")])),
+            Err(ValidationError::MissingShaderStage(Fragment))
+        );
+        assert_eq!(
+            validate(shader(&[(Compute, "c"), (Vertex, "v")])),
+            Err(ValidationError::MixedComputeShader(Vertex))
+        );
+    }
+
+    fn chunk(start: usize, frames: usize) -> IRAudioChunk {
+        IRAudioChunk {
+            start,
+            frames,
+            data: vec![],
+        }
+    }
+
+    #[test]
+    fn audio() {
+        let clip = |length: usize, samples: usize| IRAudio {
+            data: vec![0.0; samples],
+            length,
+            ..Default::default()
+        };
+        assert_eq!(validate(IRAsset::Audio(clip(4, 8))), Ok(()));
+        assert_eq!(
+            validate(IRAsset::Audio(clip(4, 7))),
+            Err(ValidationError::AudioDataSize {
+                expected: 8,
+                actual: 7,
+            })
+        );
+        assert_eq!(
+            validate(IRAsset::Audio(IRAudio {
+                sample_rate: 1000,
+                ..clip(4, 8)
+            })),
+            Err(ValidationError::SampleRate {
+                rate: 1000,
+                min: 8000,
+                max: 192000,
+            })
+        );
+        assert_eq!(
+            validate(IRAsset::Audio(IRAudio {
+                channels: 0,
+                ..clip(4, 8)
+            })),
+            Err(ValidationError::Channels(0, 8))
+        );
+
+        let packed = |chunks: Vec<IRAudioChunk>| {
+            IRAsset::Audio(IRAudio {
+                chunks,
+                ..clip(10, 0)
+            })
+        };
+        assert_eq!(validate(packed(vec![chunk(0, 4), chunk(4, 6)])), Ok(()));
+        assert_eq!(
+            validate(packed(vec![chunk(0, 4), chunk(5, 5)])),
+            Err(ValidationError::AudioChunkGap {
+                chunk: 1,
+                start: 5,
+                expected: 4,
+            })
+        );
+        assert_eq!(
+            validate(packed(vec![chunk(0, 4)])),
+            Err(ValidationError::AudioDataSize {
+                expected: 20,
+                actual: 8,
+            })
+        );
+        // The corrupted frame counts do not overflow
+        assert!(matches!(
+            validate(packed(vec![chunk(0, usize::MAX), chunk(usize::MAX, 4)])),
+            Err(ValidationError::AudioDataSize { .. })
+        ));
+    }
+}
@@ -0,0 +1,160 @@
+use crate::AssetID;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Locale-specific variants of one logical asset,
+/// e.g. the same dialogue recorded in several languages.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct LocaleVariants {
+    /// Variant used when there's no variant for the active locale.
+    pub default: AssetID,
+    /// Locale name (e.g. `en`, `de`) to the ID of the variant.
+    pub locales: HashMap<String, AssetID>,
+}
+
+/// Quality tier of the asset variant, e.g. the resolution of the texture.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default,
+)]
+pub enum QualityTier {
+    Low,
+    Medium,
+    #[default]
+    High,
+}
+
+impl QualityTier {
+    pub fn name(&self) -> &'static str {
+        match self {
+            QualityTier::Low => "low",
+            QualityTier::Medium => "medium",
+            QualityTier::High => "high",
+        }
+    }
+}
+
+impl std::fmt::Display for QualityTier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Quality or platform specific variant of one logical asset.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct QualityVariant {
+    pub id: AssetID,
+    pub tier: QualityTier,
+    /// Platform feature the variant requires, e.g. the texture
+    /// compression format (`astc`, `bc`). `None` if it works everywhere.
+    pub requires: Option<String>,
+}
+
+/// Capabilities of the device the assets are loaded on.
+/// Used to select the quality variants.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DeviceProfile {
+    /// The highest quality tier the device should load.
+    pub quality: QualityTier,
+    /// Platform features supported by the device.
+    pub features: HashSet<String>,
+}
+
+impl DeviceProfile {
+    fn supports(&self, variant: &QualityVariant) -> bool {
+        match &variant.requires {
+            Some(feature) => self.features.contains(feature),
+            None => true,
+        }
+    }
+}
+
+/// Variants of the logical assets.
+/// Logical IDs are not the real assets, they are resolved to one of the
+/// variants when the asset is requested or retrieved from the hub.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct AssetVariants {
+    pub locales: HashMap<AssetID, LocaleVariants>,
+    pub quality: HashMap<AssetID, Vec<QualityVariant>>,
+}
+
+impl AssetVariants {
+    pub fn is_empty(&self) -> bool {
+        self.locales.is_empty() && self.quality.is_empty()
+    }
+
+    /// Resolves the logical ID to the variant matching the locale,
+    /// then to the quality variant matching the device profile.
+    /// IDs that have no variants are returned as is.
+    /// ```
+    /// use dawn_assets::variants::{AssetVariants, DeviceProfile, LocaleVariants};
+    ///
+    /// let mut variants = AssetVariants::default();
+    /// variants.locales.insert(
+    ///     "dialogue".into(),
+    ///     LocaleVariants {
+    ///         default: "dialogue_en".into(),
+    ///         locales: [("de".to_string(), "dialogue_de".into())].into(),
+    ///     },
+    /// );
+    ///
+    /// let profile = DeviceProfile::default();
+    /// let resolve = |id: &str, locale| variants.resolve(&id.into(), locale, &profile);
+    /// assert_eq!(resolve("dialogue", Some("de")), "dialogue_de".into());
+    /// assert_eq!(resolve("dialogue", Some("fr")), "dialogue_en".into());
+    /// assert_eq!(resolve("dialogue", None), "dialogue_en".into());
+    /// assert_eq!(resolve("music", Some("de")), "music".into());
+    /// ```
+    pub fn resolve(&self, id: &AssetID, locale: Option<&str>, profile: &DeviceProfile) -> AssetID {
+        let id = match self.locales.get(id) {
+            Some(variants) => locale
+                .and_then(|locale| variants.locales.get(locale))
+                .unwrap_or(&variants.default),
+            None => id,
+        };
+
+        match self.quality.get(id) {
+            Some(variants) => Self::select_quality(variants, profile)
+                .map(|variant| variant.id.clone())
+                .unwrap_or_else(|| id.clone()),
+            None => id.clone(),
+        }
+    }
+
+    /// Selects the best supported variant not exceeding the profile quality.
+    /// If all the supported variants exceed it, the lowest one is selected.
+    /// Platform-specific variants are preferred over the generic ones of the same tier.
+    /// ```
+    /// use dawn_assets::variants::{AssetVariants, DeviceProfile, QualityTier, QualityVariant};
+    ///
+    /// let variant = |id: &str, tier, requires: Option<&str>| QualityVariant {
+    ///     id: id.into(),
+    ///     tier,
+    ///     requires: requires.map(|r| r.to_string()),
+    /// };
+    /// let variants = vec![
+    ///     variant("wood@low", QualityTier::Low, None),
+    ///     variant("wood@high", QualityTier::High, None),
+    ///     variant("wood@high_astc", QualityTier::High, Some("astc")),
+    /// ];
+    ///
+    /// let mut profile = DeviceProfile::default();
+    /// let select = |profile: &DeviceProfile| AssetVariants::select_quality(&variants, profile).unwrap().id.clone();
+    /// assert_eq!(select(&profile), "wood@high".into());
+    /// profile.features.insert("astc".to_string());
+    /// assert_eq!(select(&profile), "wood@high_astc".into());
+    /// profile.quality = QualityTier::Medium;
+    /// assert_eq!(select(&profile), "wood@low".into());
+    /// ```
+    pub fn select_quality<'a>(
+        variants: &'a [QualityVariant],
+        profile: &DeviceProfile,
+    ) -> Option<&'a QualityVariant> {
+        let key = |v: &&QualityVariant| (v.tier, v.requires.is_some());
+        let supported = variants.iter().filter(|v| profile.supports(v));
+        supported
+            .clone()
+            .filter(|v| v.tier <= profile.quality)
+            .max_by_key(key)
+            .or_else(|| supported.min_by_key(|v| v.tier))
+    }
+}
@@ -0,0 +1,20 @@
+// The block-size-* features are additive, so the crates of the build may enable
+// several of them. Only one is used (see `BLOCK_SIZE`), tell which one.
+fn main() {
+    let enabled: Vec<&str> = [
+        (cfg!(feature = "block-size-1024"), "1024"),
+        (cfg!(feature = "block-size-256"), "256"),
+        (cfg!(feature = "block-size-128"), "128"),
+    ]
+    .into_iter()
+    .filter(|(enabled, _)| *enabled)
+    .map(|(_, size)| size)
+    .collect();
+    if enabled.len() > 1 {
+        println!(
+            "cargo:warning=Several block-size-* features are enabled ({}), using {} samples",
+            enabled.join(", "),
+            enabled[0]
+        );
+    }
+}
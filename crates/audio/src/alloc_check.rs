@@ -0,0 +1,78 @@
+//! Debug check that nothing allocates on the audio thread.
+//! With the `alloc-check` feature, the crate installs the global allocator
+//! aborting the process (with a backtrace) on any allocation made inside
+//! the render callback of the player. Without the feature, the guard is a no-op.
+//! Note that the deallocations are not checked, and that most loggers allocate,
+//! so the warnings logged from the audio thread are reported too.
+
+#[cfg(feature = "alloc-check")]
+mod checked {
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+    use std::io::Write;
+
+    thread_local! {
+        pub(super) static GUARDED: Cell<bool> = const { Cell::new(false) };
+    }
+
+    pub struct CheckedAllocator;
+
+    impl CheckedAllocator {
+        fn check(&self) {
+            // Not guarded while reporting, the backtrace allocates itself
+            if GUARDED.try_with(|guarded| guarded.replace(false)) == Ok(true) {
+                let backtrace = std::backtrace::Backtrace::force_capture();
+                let _ = writeln!(
+                    std::io::stderr(),
+                    "Allocation on the audio thread:\n{}",
+                    backtrace
+                );
+                std::process::abort();
+            }
+        }
+    }
+
+    unsafe impl GlobalAlloc for CheckedAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            self.check();
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+
+        unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+            self.check();
+            unsafe { System.alloc_zeroed(layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            self.check();
+            unsafe { System.realloc(ptr, layout, new_size) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CheckedAllocator = CheckedAllocator;
+}
+
+/// Marks the scope where no allocation is allowed.
+pub(crate) struct NoAllocGuard;
+
+impl NoAllocGuard {
+    #[inline(always)]
+    pub fn new() -> Self {
+        #[cfg(feature = "alloc-check")]
+        checked::GUARDED.with(|guarded| guarded.set(true));
+        NoAllocGuard
+    }
+}
+
+impl Drop for NoAllocGuard {
+    #[inline(always)]
+    fn drop(&mut self) {
+        #[cfg(feature = "alloc-check")]
+        checked::GUARDED.with(|guarded| guarded.set(false));
+    }
+}
@@ -1,14 +1,18 @@
 use crate::SampleRate;
+use anyhow::anyhow;
 use dawn_assets::factory::{BasicFactory, FactoryBinding};
 use dawn_assets::ir::audio::IRAudio;
+use dawn_assets::ir::captions::IRCaptions;
+use dawn_assets::ir::music::IRMusic;
 use dawn_assets::ir::notes::IRNotes;
 use dawn_assets::ir::IRAsset;
-use dawn_assets::{AssetCastable, AssetMemoryUsage, AssetType};
+use dawn_assets::{Asset, AssetCastable, AssetID, AssetMemoryUsage, AssetType, TypedAsset};
 use dawn_ecs::events::TickEvent;
 use evenio::component::Component;
 use evenio::event::Receiver;
 use evenio::fetch::Single;
 use evenio::world::World;
+use std::collections::HashMap;
 use std::time::Duration;
 
 #[derive(Debug)]
@@ -18,6 +22,8 @@ impl AssetCastable for AudioAsset {}
 
 #[derive(Component)]
 pub struct AudioAssetFactory {
+    // Target rate for the resampling TODO in `process_events`.
+    #[allow(dead_code)]
     sample_rate: SampleRate,
     basic_factory: BasicFactory<AudioAsset>,
 }
@@ -72,6 +78,12 @@ pub struct NotesAssetFactory {
     basic_factory: BasicFactory<NotesAsset>,
 }
 
+impl Default for NotesAssetFactory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NotesAssetFactory {
     pub fn new() -> Self {
         NotesAssetFactory {
@@ -109,3 +121,153 @@ impl NotesAssetFactory {
         world.add_handler(handler);
     }
 }
+
+/// Music asset with the resolved stems.
+#[derive(Debug)]
+pub struct MusicAsset {
+    pub ir: IRMusic,
+    // Stems of the layers, indexed by the section and the layer
+    stems: Vec<Vec<TypedAsset<AudioAsset>>>,
+}
+
+impl AssetCastable for MusicAsset {}
+
+impl MusicAsset {
+    pub(crate) fn from_ir(ir: IRMusic, deps: &HashMap<AssetID, Asset>) -> anyhow::Result<Self> {
+        let mut stems = Vec::with_capacity(ir.sections.len());
+        for section in &ir.sections {
+            let mut layers = Vec::with_capacity(section.layers.len());
+            for layer in &section.layers {
+                let stem = deps
+                    .get(&layer.stem)
+                    .ok_or_else(|| anyhow!("Stem {} not found", layer.stem))?;
+                let stem = TypedAsset::<AudioAsset>::new(stem.clone());
+                // The stems are mixed sample-accurately on the audio thread
+                let clip = &stem.cast().0;
+                if clip.is_packed() || clip.is_encoded() {
+                    return Err(anyhow!("Stem {} is not decoded", layer.stem));
+                }
+                layers.push(stem);
+            }
+            stems.push(layers);
+        }
+
+        Ok(MusicAsset { ir, stems })
+    }
+
+    #[inline(always)]
+    pub(crate) fn stem(&self, section: usize, layer: usize) -> &IRAudio {
+        &self.stems[section][layer].cast().0
+    }
+
+    /// Length of the section in samples: the length of its longest stem.
+    pub(crate) fn section_length(&self, section: usize) -> usize {
+        self.stems[section]
+            .iter()
+            .map(|stem| stem.cast().0.length)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+#[derive(Component)]
+pub struct MusicAssetFactory {
+    basic_factory: BasicFactory<MusicAsset>,
+}
+
+impl Default for MusicAssetFactory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MusicAssetFactory {
+    pub fn new() -> Self {
+        MusicAssetFactory {
+            basic_factory: BasicFactory::new(),
+        }
+    }
+
+    pub fn bind(&mut self, binding: FactoryBinding) {
+        assert_eq!(binding.asset_type(), AssetType::Music);
+        self.basic_factory.bind(binding);
+    }
+
+    pub fn process_events(&mut self) {
+        self.basic_factory.process_events(
+            |message| {
+                if let IRAsset::Music(data) = message.ir {
+                    let size = data.memory_usage();
+                    let music = MusicAsset::from_ir(data, &message.dependencies)?;
+                    Ok((music, AssetMemoryUsage::new(size, 0)))
+                } else {
+                    Err(anyhow::anyhow!("Expected music metadata"))
+                }
+            },
+            |_| {
+                // The stems are released with the asset
+            },
+            Duration::ZERO,
+        );
+    }
+
+    pub fn attach_to_ecs(&mut self, world: &mut World) {
+        fn handler(_: Receiver<TickEvent>, mut factory: Single<&mut MusicAssetFactory>) {
+            factory.process_events();
+        }
+
+        world.add_handler(handler);
+    }
+}
+
+#[derive(Debug)]
+pub struct CaptionsAsset(pub IRCaptions);
+
+impl AssetCastable for CaptionsAsset {}
+
+#[derive(Component)]
+pub struct CaptionsAssetFactory {
+    basic_factory: BasicFactory<CaptionsAsset>,
+}
+
+impl Default for CaptionsAssetFactory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CaptionsAssetFactory {
+    pub fn new() -> Self {
+        CaptionsAssetFactory {
+            basic_factory: BasicFactory::new(),
+        }
+    }
+
+    pub fn bind(&mut self, binding: FactoryBinding) {
+        assert_eq!(binding.asset_type(), AssetType::Captions);
+        self.basic_factory.bind(binding);
+    }
+
+    pub fn process_events(&mut self) {
+        self.basic_factory.process_events(
+            |message| {
+                if let IRAsset::Captions(data) = message.ir {
+                    let size = data.memory_usage();
+                    Ok((CaptionsAsset(data), AssetMemoryUsage::new(size, 0)))
+                } else {
+                    Err(anyhow::anyhow!("Expected captions metadata"))
+                }
+            },
+            |_| {},
+            Duration::ZERO,
+        );
+    }
+
+    pub fn attach_to_ecs(&mut self, world: &mut World) {
+        fn handler(_: Receiver<TickEvent>, mut factory: Single<&mut CaptionsAssetFactory>) {
+            factory.process_events();
+        }
+
+        world.add_handler(handler);
+    }
+}
@@ -1,4 +1,6 @@
 use crate::sample::{MappedInterleavedBuffer, Sample};
+use std::sync::atomic::{AtomicU64, AtomicUsize};
+use std::sync::Arc;
 
 pub mod backend_impl {
     pub type PlayerBackendConfig = crate::cpal::PlayerConfig;
@@ -9,7 +11,48 @@ pub mod backend_impl {
 use crate::{ChannelsCount, SampleRate, SamplesCount};
 pub use backend_impl::*;
 
-#[allow(dead_code)]
+/// Why the output stream was moved to another device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceChangeReason {
+    /// The played device was disconnected.
+    Lost,
+    /// The default device changed, or the selected one was connected.
+    Changed,
+    /// Another device was selected by the user.
+    Selected,
+}
+
+pub(crate) struct DeviceSwitch {
+    pub previous: Option<String>,
+    pub reason: DeviceChangeReason,
+    /// Name of the new device, or the error if the stream could not be opened.
+    pub result: Result<String, PlayerBackendError>,
+}
+
+/// Counters of the stream shared with the audio thread.
+pub(crate) struct BackendStats {
+    /// Number of underruns since the start
+    pub underruns: AtomicUsize,
+    /// Number of blocks filled by repeating the previous one since the start
+    pub panic_fills: AtomicUsize,
+    /// Current size of the device buffer in samples
+    pub buffer_size: AtomicUsize,
+    /// Time from the callback to the playback of its first sample
+    /// reported by the device, in nanoseconds
+    pub output_latency: AtomicU64,
+}
+
+impl BackendStats {
+    pub fn new(buffer_size: SamplesCount) -> Self {
+        BackendStats {
+            underruns: AtomicUsize::new(0),
+            panic_fills: AtomicUsize::new(0),
+            buffer_size: AtomicUsize::new(buffer_size),
+            output_latency: AtomicU64::new(0),
+        }
+    }
+}
+
 pub(crate) struct InternalBackendConfig {
     /// Backend-specific configuration
     pub backend_specific: PlayerBackendConfig,
@@ -34,4 +77,25 @@ where
         F: FnMut(&mut MappedInterleavedBuffer<f32>) + Send + 'static;
 
     fn close(&mut self) -> Result<(), PlayerBackendError>;
+
+    /// Name of the device the stream is played on.
+    fn device(&self) -> Option<String>;
+
+    /// Names of the connected output devices.
+    fn output_devices(&self) -> Vec<String>;
+
+    /// Selects the output device by name, or the default one with `None`.
+    /// The stream is moved on the next `poll_device` call.
+    fn select_device(&mut self, name: Option<String>);
+
+    /// Checks the connected devices and moves the stream if the played
+    /// device is lost or another one should be used. The renderer
+    /// keeps its state between the streams.
+    fn poll_device(&mut self) -> Option<DeviceSwitch>;
+
+    fn stats(&self) -> Arc<BackendStats>;
+
+    /// Checks the underruns since the last call, reports them and grows
+    /// the device buffer if they keep happening.
+    fn poll_underruns(&mut self);
 }
@@ -0,0 +1,184 @@
+use crate::{SampleRate, SamplesCount};
+use evenio::event::GlobalEvent;
+
+/// Tempo of the musical clock.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tempo {
+    /// Number of beats per minute.
+    pub bpm: f64,
+    /// Number of beats in one bar (the numerator of the time signature).
+    pub beats_per_bar: usize,
+}
+
+impl Default for Tempo {
+    fn default() -> Self {
+        Tempo {
+            bpm: 120.0,
+            beats_per_bar: 4,
+        }
+    }
+}
+
+impl Tempo {
+    pub fn new(bpm: f64, beats_per_bar: usize) -> Self {
+        Tempo { bpm, beats_per_bar }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.bpm.is_finite() && self.bpm > 0.0 && self.beats_per_bar > 0
+    }
+}
+
+/// Event sent to the ECS on each beat rendered by the audio thread.
+#[derive(GlobalEvent, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeatEvent {
+    /// Number of the bar since the start of the player.
+    pub bar: usize,
+    /// Number of the beat within the bar. Zero is the downbeat.
+    pub beat: usize,
+    /// Sample of the audio stream the beat falls on, see `player::AudioClock`.
+    pub sample: SamplesCount,
+}
+
+/// Musical clock derived from the sample clock of the sink.
+/// Tempo changes keep the beat position continuous: the beats after the change
+/// are counted from the position at the sample the change is applied at.
+pub(crate) struct BeatClock {
+    sample_rate: SampleRate,
+    tempo: Tempo,
+    samples_per_beat: f64,
+    // Sample and the (possibly fractional) beat the tempo was set at
+    origin_sample: SamplesCount,
+    origin_beat: f64,
+}
+
+impl BeatClock {
+    pub fn new(sample_rate: SampleRate, tempo: Tempo) -> Self {
+        BeatClock {
+            sample_rate,
+            tempo,
+            samples_per_beat: Self::samples_per_beat(sample_rate, &tempo),
+            origin_sample: 0,
+            origin_beat: 0.0,
+        }
+    }
+
+    fn samples_per_beat(sample_rate: SampleRate, tempo: &Tempo) -> f64 {
+        sample_rate as f64 * 60.0 / tempo.bpm
+    }
+
+    /// Changes the tempo starting from the given sample.
+    /// Bars are counted by the new `beats_per_bar` from the start of the stream,
+    /// so the time signature should be changed on a downbeat.
+    pub fn set_tempo(&mut self, sample: SamplesCount, tempo: Tempo) {
+        self.origin_beat = self.beat_at(sample);
+        self.origin_sample = sample;
+        self.tempo = tempo;
+        self.samples_per_beat = Self::samples_per_beat(self.sample_rate, &tempo);
+    }
+
+    fn beat_at(&self, sample: SamplesCount) -> f64 {
+        self.origin_beat + (sample as f64 - self.origin_sample as f64) / self.samples_per_beat
+    }
+
+    fn sample_of(&self, beat: usize) -> SamplesCount {
+        let offset = (beat as f64 - self.origin_beat) * self.samples_per_beat;
+        (self.origin_sample as f64 + offset).round() as SamplesCount
+    }
+
+    /// Returns the first sample at or after the given one falling on a beat
+    /// divisible by `multiple` (1 for any beat, `beats_per_bar` for the bars).
+    fn next_multiple(&self, sample: SamplesCount, multiple: usize) -> SamplesCount {
+        let beat = self.beat_at(sample).ceil() as usize;
+        let mut beat = beat.div_ceil(multiple) * multiple;
+        // The beat could be rounded to the sample before the given one
+        if self.sample_of(beat) < sample {
+            beat += multiple;
+        }
+        self.sample_of(beat)
+    }
+
+    /// Returns the first sample at or after the given one falling on a beat.
+    pub fn next_beat(&self, sample: SamplesCount) -> SamplesCount {
+        self.next_multiple(sample, 1)
+    }
+
+    /// Returns the first sample at or after the given one falling on a downbeat.
+    pub fn next_bar(&self, sample: SamplesCount) -> SamplesCount {
+        self.next_multiple(sample, self.tempo.beats_per_bar)
+    }
+
+    /// Calls `f` for each beat falling in the `start..end` samples range.
+    pub fn beats_in<F: FnMut(BeatEvent)>(&self, start: SamplesCount, end: SamplesCount, mut f: F) {
+        // Start from the previous beat, since the rounding may put it into the range
+        let mut beat = (self.beat_at(start).ceil() as usize).saturating_sub(1);
+        loop {
+            let sample = self.sample_of(beat);
+            if sample >= end {
+                break;
+            }
+            if sample >= start {
+                f(BeatEvent {
+                    bar: beat / self.tempo.beats_per_bar,
+                    beat: beat % self.tempo.beats_per_bar,
+                    sample,
+                });
+            }
+            beat += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::beat::{BeatClock, BeatEvent, Tempo};
+
+    fn collect(clock: &BeatClock, start: usize, end: usize) -> Vec<BeatEvent> {
+        let mut beats = Vec::new();
+        clock.beats_in(start, end, |beat| beats.push(beat));
+        beats
+    }
+
+    #[test]
+    fn beats_and_bars() {
+        // 2 beats per second, 3 beats per bar
+        let clock = BeatClock::new(48000, Tempo::new(120.0, 3));
+        assert_eq!(clock.next_beat(0), 0);
+        assert_eq!(clock.next_beat(1), 24000);
+        assert_eq!(clock.next_beat(24000), 24000);
+        assert_eq!(clock.next_bar(1), 72000);
+        assert_eq!(clock.next_bar(72001), 144000);
+
+        // Each beat is reported once, however the range is split
+        let mut split = Vec::new();
+        for start in (0..150000).step_by(512) {
+            split.extend(collect(&clock, start, start + 512));
+        }
+        let whole = collect(&clock, 0, 150000 / 512 * 512 + 512);
+        assert_eq!(split, whole);
+        assert_eq!(whole.len(), 7);
+        assert_eq!(
+            whole[4],
+            BeatEvent {
+                bar: 1,
+                beat: 1,
+                sample: 96000
+            }
+        );
+    }
+
+    #[test]
+    fn tempo_change_keeps_position() {
+        let mut clock = BeatClock::new(48000, Tempo::new(120.0, 4));
+        // Half of the second beat, then twice as fast
+        clock.set_tempo(36000, Tempo::new(240.0, 4));
+        assert_eq!(clock.next_beat(36000), 42000);
+        assert_eq!(clock.next_bar(36000), 66000);
+
+        let beats = collect(&clock, 36000, 54001);
+        assert_eq!(beats.len(), 2);
+        assert_eq!(beats[0].beat, 2);
+        assert_eq!(beats[0].sample, 42000);
+        assert_eq!(beats[1].sample, 54000);
+    }
+}
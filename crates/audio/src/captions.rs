@@ -0,0 +1,247 @@
+use crate::assets::{AudioAsset, CaptionsAsset};
+use crate::player::AudioClock;
+use crate::SamplesCount;
+use dawn_assets::hub::AssetHub;
+use dawn_assets::TypedAsset;
+use dawn_ecs::events::TickEvent;
+use evenio::component::Component;
+use evenio::event::{GlobalEvent, Receiver, Sender};
+use evenio::fetch::Single;
+use evenio::world::World;
+use log::warn;
+
+/// Identifies the caption track started by the `CaptionPlayer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CaptionTrackId(usize);
+
+/// Sent when the caption should appear on the screen.
+#[derive(GlobalEvent, Debug, Clone, PartialEq)]
+pub struct CaptionShow {
+    pub track: CaptionTrackId,
+    /// Index of the entry in the track. Identifies the caption in the `CaptionHide`.
+    pub index: usize,
+    pub speaker: Option<String>,
+    pub text: String,
+}
+
+/// Sent when the caption should disappear from the screen.
+#[derive(GlobalEvent, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptionHide {
+    pub track: CaptionTrackId,
+    pub index: usize,
+}
+
+#[derive(Debug, PartialEq)]
+enum CaptionChange {
+    Show(CaptionShow),
+    Hide(CaptionHide),
+}
+
+struct CaptionTrack {
+    id: CaptionTrackId,
+    captions: TypedAsset<CaptionsAsset>,
+    // Sample of the audio stream the clip starts at
+    start: SamplesCount,
+    // Next entry to be shown
+    next: usize,
+    shown: Vec<usize>,
+    stopped: bool,
+}
+
+/// Shows the captions of the playing clips. The captions follow the
+/// `AudioClock`, not the main loop, so they stay in sync with the sound
+/// heard even when the frames are dropped.
+/// The events are sent on the ticks, so they lag behind the audio by up to a tick.
+#[derive(Component)]
+pub struct CaptionPlayer {
+    clock: AudioClock,
+    tracks: Vec<CaptionTrack>,
+    next_id: usize,
+}
+
+impl CaptionPlayer {
+    /// Takes the clock of the player the clips are played by, see `Player::clock`.
+    pub fn new(clock: AudioClock) -> Self {
+        CaptionPlayer {
+            clock,
+            tracks: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Starts the captions at the sample of the audio stream the clip starts
+    /// playing at. Use `AudioClock::earliest` with the `AudioEvent::at` of the clip.
+    pub fn play(
+        &mut self,
+        captions: TypedAsset<CaptionsAsset>,
+        start: SamplesCount,
+    ) -> CaptionTrackId {
+        let id = CaptionTrackId(self.next_id);
+        self.next_id += 1;
+        self.tracks.push(CaptionTrack {
+            id,
+            captions,
+            start,
+            next: 0,
+            shown: Vec::new(),
+            stopped: false,
+        });
+        id
+    }
+
+    /// Starts the captions of the clip, if it has any.
+    /// The captions are the dependency of the clip, so they are loaded with it.
+    pub fn play_clip(
+        &mut self,
+        hub: &AssetHub,
+        clip: &TypedAsset<AudioAsset>,
+        start: SamplesCount,
+    ) -> Option<CaptionTrackId> {
+        let id = clip.cast().0.captions.clone()?;
+        match hub.get_typed::<CaptionsAsset>(id.clone()) {
+            Ok(captions) => Some(self.play(captions, start)),
+            Err(err) => {
+                warn!("Cannot get the captions {}: {:?}", id, err);
+                None
+            }
+        }
+    }
+
+    /// Stops the track, the shown captions are hidden on the next tick.
+    pub fn stop(&mut self, id: CaptionTrackId) {
+        if let Some(track) = self.tracks.iter_mut().find(|track| track.id == id) {
+            track.stopped = true;
+        }
+    }
+
+    fn update(&mut self, sample: SamplesCount, mut f: impl FnMut(CaptionChange)) {
+        let sample_rate = self.clock.sample_rate() as f32;
+        self.tracks.retain_mut(|track| {
+            let entries = &track.captions.cast().0.entries;
+            if track.stopped {
+                for index in &track.shown {
+                    f(CaptionChange::Hide(CaptionHide {
+                        track: track.id,
+                        index: *index,
+                    }));
+                }
+                return false;
+            }
+            if sample < track.start {
+                return true;
+            }
+            let time = (sample - track.start) as f32 / sample_rate;
+
+            track.shown.retain(|index| {
+                let visible = entries[*index].end > time;
+                if !visible {
+                    f(CaptionChange::Hide(CaptionHide {
+                        track: track.id,
+                        index: *index,
+                    }));
+                }
+                visible
+            });
+
+            while let Some(entry) = entries.get(track.next) {
+                if entry.start > time {
+                    break;
+                }
+                // Skip the entries passed before the tick, e.g. when started late
+                if entry.end > time {
+                    f(CaptionChange::Show(CaptionShow {
+                        track: track.id,
+                        index: track.next,
+                        speaker: entry.speaker.clone(),
+                        text: entry.text.clone(),
+                    }));
+                    track.shown.push(track.next);
+                }
+                track.next += 1;
+            }
+
+            track.next < entries.len() || !track.shown.is_empty()
+        });
+    }
+
+    pub fn attach_to_ecs(self, world: &mut World) {
+        let entity = world.spawn();
+        world.insert(entity, self);
+
+        fn handler(
+            _: Receiver<TickEvent>,
+            mut player: Single<&mut CaptionPlayer>,
+            mut sender: Sender<(CaptionShow, CaptionHide)>,
+        ) {
+            let sample = player.clock.samples();
+            player.update(sample, |change| match change {
+                CaptionChange::Show(show) => sender.send(show),
+                CaptionChange::Hide(hide) => sender.send(hide),
+            });
+        }
+
+        world.add_handler(handler);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dawn_assets::ir::captions::{IRCaption, IRCaptions};
+    use dawn_assets::Asset;
+    use std::any::TypeId;
+    use std::ptr::NonNull;
+
+    fn captions(entries: &[(f32, f32)]) -> TypedAsset<CaptionsAsset> {
+        let entries = entries
+            .iter()
+            .enumerate()
+            .map(|(i, (start, end))| IRCaption {
+                start: *start,
+                end: *end,
+                speaker: None,
+                text: i.to_string(),
+            })
+            .collect();
+        let ptr = Box::into_raw(Box::new(CaptionsAsset(IRCaptions { entries })));
+        TypedAsset::new(Asset::new(
+            TypeId::of::<CaptionsAsset>(),
+            NonNull::new(ptr as *mut ()).unwrap(),
+        ))
+    }
+
+    // Indices of the shown (+) and the hidden (-) entries
+    fn update(player: &mut CaptionPlayer, sample: SamplesCount) -> Vec<String> {
+        let mut changes = vec![];
+        player.update(sample, |change| match change {
+            CaptionChange::Show(show) => changes.push(format!("+{}", show.index)),
+            CaptionChange::Hide(hide) => changes.push(format!("-{}", hide.index)),
+        });
+        changes
+    }
+
+    #[test]
+    fn follows_clock() {
+        let mut player = CaptionPlayer::new(AudioClock::new(1000));
+        player.play(captions(&[(0.0, 1.0), (0.5, 2.0), (3.0, 4.0)]), 1000);
+
+        // Not started yet
+        assert_eq!(update(&mut player, 500), Vec::<String>::new());
+        assert_eq!(update(&mut player, 1000), vec!["+0"]);
+        assert_eq!(update(&mut player, 1600), vec!["+1"]);
+        assert_eq!(update(&mut player, 2000), vec!["-0"]);
+        // The third entry is entirely passed between the ticks
+        assert_eq!(update(&mut player, 5500), vec!["-1"]);
+        assert!(player.tracks.is_empty());
+    }
+
+    #[test]
+    fn stop_hides_shown() {
+        let mut player = CaptionPlayer::new(AudioClock::new(1000));
+        let id = player.play(captions(&[(0.0, 10.0), (5.0, 6.0)]), 0);
+        assert_eq!(update(&mut player, 100), vec!["+0"]);
+        player.stop(id);
+        assert_eq!(update(&mut player, 200), vec!["-0"]);
+        assert!(player.tracks.is_empty());
+    }
+}
@@ -1,4 +1,10 @@
-use crate::backend::{InternalBackendConfig, PlayerBackendTrait};
+mod recovery;
+
+use crate::backend::{
+    BackendStats, DeviceChangeReason, DeviceSwitch, InternalBackendConfig, PlayerBackendTrait,
+};
+use crate::cpal::recovery::Recovery;
+use crate::entities::sinks::MAX_OUTPUT_LEN;
 use crate::sample::{MappedInterleavedBuffer, Sample, SampleCode};
 use crate::{ChannelsCount, SampleRate, SamplesCount};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
@@ -6,8 +12,13 @@ use cpal::SizedSample;
 use log::{debug, info, warn};
 use std::fmt::{Debug, Display, Formatter};
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+// Most variants are named after the cpal error they wrap.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     NotSupportedStreamParameters(SampleRate, ChannelsCount, SamplesCount, cpal::SampleFormat),
     FetchConfigFailed(cpal::SupportedStreamConfigsError),
@@ -17,6 +28,7 @@ pub enum Error {
     AlreadyClosed,
     PausedStreamError(cpal::PauseStreamError),
     DefaultHostNotFound,
+    DeviceNotFound(String),
 }
 
 impl Display for Error {
@@ -40,6 +52,7 @@ impl Display for Error {
             Error::AlreadyClosed => write!(f, "Stream is already closed"),
             Error::BuildStreamError(err) => write!(f, "Failed to build stream: {}", err),
             Error::DefaultHostNotFound => write!(f, "Default host not found"),
+            Error::DeviceNotFound(name) => write!(f, "Output device not found: {}", name),
             Error::FetchConfigFailed(err) => write!(f, "Failed to fetch config: {}", err),
         }
     }
@@ -48,12 +61,58 @@ impl Display for Error {
 impl std::error::Error for Error {}
 
 #[derive(Debug)]
-pub struct PlayerConfig {}
+pub struct PlayerConfig {
+    /// Name of the output device (see `Player::output_devices`).
+    /// If not set or not connected, the default output device is used.
+    pub device: Option<String>,
+    /// How often the connected devices are checked for changes
+    /// (e.g. the headphones became the default device).
+    /// The loss of the played device is detected immediately.
+    pub device_poll_interval: Duration,
+    /// The device buffer is grown up to this latency if the underruns keep happening.
+    pub max_latency: Duration,
+    /// Number of underruns within 5 seconds that grows the buffer.
+    pub underrun_threshold: usize,
+    /// Fills the block after an underrun with the previous one faded out,
+    /// instead of rendering it late. Hides the short glitches.
+    pub panic_fill: bool,
+}
+
+/// Window the underruns are counted in.
+const UNDERRUN_WINDOW: Duration = Duration::from_secs(5);
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        PlayerConfig {
+            device: None,
+            device_poll_interval: Duration::from_secs(1),
+            max_latency: Duration::from_millis(30),
+            underrun_threshold: 3,
+            panic_fill: true,
+        }
+    }
+}
+
+type RenderFn = Box<dyn FnMut(&mut MappedInterleavedBuffer<f32>) + Send>;
 
 pub(crate) struct Player<S> {
-    device: cpal::Device,
-    stream_config: cpal::StreamConfig,
+    cfg: InternalBackendConfig,
+    device: Option<cpal::Device>,
+    device_name: Option<String>,
     stream: Option<cpal::Stream>,
+    // Shared between the streams, so the renderer outlives the switch of the device.
+    render_fn: Option<Arc<Mutex<RenderFn>>>,
+    // Set by the stream when its device is disconnected.
+    lost: Arc<AtomicBool>,
+    // Device the stream could not be opened on. Not retried until it changes.
+    rejected: Option<String>,
+    selection_changed: bool,
+    last_poll: Instant,
+    stats: Arc<BackendStats>,
+    max_buffer_size: usize,
+    // Start of the window the underruns are counted in, and the count at its start.
+    window_start: Instant,
+    window_underruns: usize,
     keep_s: PhantomData<S>,
 }
 
@@ -75,104 +134,120 @@ where
     }
 }
 
-impl<S> PlayerBackendTrait<S> for Player<S>
+/// Picks the stream config of the device matching the requested parameters.
+fn select_config<S>(
+    device: &cpal::Device,
+    cfg: &InternalBackendConfig,
+) -> Result<cpal::StreamConfig, Error>
 where
-    S: Sample + SizedSample + Send,
+    S: Sample,
 {
-    fn new(cfg: InternalBackendConfig) -> Result<Self, Error>
-    where
-        Self: Sized,
-    {
-        let host = cpal::default_host();
-        let device = host
-            .default_output_device()
-            .ok_or(Error::DefaultHostNotFound)?;
-
-        let supported_configs = device
-            .supported_output_configs()
-            .map_err(Error::FetchConfigFailed)?;
-
-        let mut selected_config: Option<cpal::StreamConfig> = None;
-        let required_sample_format = sample_code_to_cpal_format::<S>();
-
-        #[cfg(target_os = "macos")]
-        // In some reason I can't explain, on macOS the buffer size is some
-        // random value bigger than the requested one, so we limit it to
-        // 80% of the requested size. In the other case, the upper level code
-        // will panic.
-        let max_buf_size = (cfg.buffer_size as f32 * 0.8) as usize;
-        #[cfg(not(target_os = "macos"))]
-        let max_buf_size = cfg.buffer_size as usize;
-
-        for config in supported_configs {
-            // Log the supported config details
-            debug!(
-                "Supported config: sample_format: {:?}, sample_rate: {}-{}, channels: {}, buffer_size: {:?}",
-                config.sample_format(),
-                config.min_sample_rate().0,
-                config.max_sample_rate().0,
-                config.channels(),
-                config.buffer_size());
-
-            let sample_format_ok = config.sample_format() == required_sample_format;
-            let sample_rate_ok = config.min_sample_rate()
-                <= cpal::SampleRate(cfg.sample_rate as u32)
-                && cpal::SampleRate(cfg.sample_rate as u32) <= config.max_sample_rate();
-            let channels_ok = config.channels() == cfg.channels as u16;
-            let buffer_size_ok = match config.buffer_size() {
-                cpal::SupportedBufferSize::Range { min, max } => {
-                    max_buf_size >= *min as usize && max_buf_size <= *max as usize
-                }
-                cpal::SupportedBufferSize::Unknown => continue,
-            };
+    let supported_configs = device
+        .supported_output_configs()
+        .map_err(Error::FetchConfigFailed)?;
 
-            debug!(
-                "Checking config: sample_format_ok: {}, sample_rate_ok: {}, channels_ok: {}, buffer_size_ok: {}",
-                sample_format_ok, sample_rate_ok, channels_ok, buffer_size_ok
-            );
+    let required_sample_format = sample_code_to_cpal_format::<S>();
+
+    #[cfg(target_os = "macos")]
+    // In some reason I can't explain, on macOS the buffer size is some
+    // random value bigger than the requested one, so we limit it to
+    // 80% of the requested size. In the other case, the upper level code
+    // will panic.
+    let max_buf_size = (cfg.buffer_size as f32 * 0.8) as usize;
+    #[cfg(not(target_os = "macos"))]
+    let max_buf_size = cfg.buffer_size;
 
-            if sample_format_ok && sample_rate_ok && channels_ok && buffer_size_ok {
-                selected_config = Some(cpal::StreamConfig {
-                    channels: config.channels(),
-                    sample_rate: cpal::SampleRate(cfg.sample_rate as u32),
-                    buffer_size: cpal::BufferSize::Fixed(max_buf_size as u32),
-                });
-                break;
+    for config in supported_configs {
+        // Log the supported config details
+        debug!(
+            "Supported config: sample_format: {:?}, sample_rate: {}-{}, channels: {}, buffer_size: {:?}",
+            config.sample_format(),
+            config.min_sample_rate().0,
+            config.max_sample_rate().0,
+            config.channels(),
+            config.buffer_size());
+
+        let sample_format_ok = config.sample_format() == required_sample_format;
+        let sample_rate_ok = config.min_sample_rate() <= cpal::SampleRate(cfg.sample_rate as u32)
+            && cpal::SampleRate(cfg.sample_rate as u32) <= config.max_sample_rate();
+        let channels_ok = config.channels() == cfg.channels as u16;
+        let buffer_size_ok = match config.buffer_size() {
+            cpal::SupportedBufferSize::Range { min, max } => {
+                max_buf_size >= *min as usize && max_buf_size <= *max as usize
             }
-        }
+            cpal::SupportedBufferSize::Unknown => continue,
+        };
 
-        Ok(Player::<S> {
-            device,
-            stream_config: selected_config.ok_or(Error::NotSupportedStreamParameters(
-                cfg.sample_rate,
-                cfg.channels,
-                cfg.buffer_size,
-                required_sample_format,
-            ))?,
-            stream: None,
+        debug!(
+            "Checking config: sample_format_ok: {}, sample_rate_ok: {}, channels_ok: {}, buffer_size_ok: {}",
+            sample_format_ok, sample_rate_ok, channels_ok, buffer_size_ok
+        );
 
-            keep_s: Default::default(),
-        })
+        if sample_format_ok && sample_rate_ok && channels_ok && buffer_size_ok {
+            return Ok(cpal::StreamConfig {
+                channels: config.channels(),
+                sample_rate: cpal::SampleRate(cfg.sample_rate as u32),
+                buffer_size: cpal::BufferSize::Fixed(max_buf_size as u32),
+            });
+        }
     }
 
-    fn open<F>(&mut self, mut raw_fn: F) -> Result<(), Error>
-    where
-        F: FnMut(&mut MappedInterleavedBuffer<f32>) + Send + 'static,
-    {
-        if self.stream.is_some() {
-            return Err(Error::AlreadyOpened);
+    Err(Error::NotSupportedStreamParameters(
+        cfg.sample_rate,
+        cfg.channels,
+        cfg.buffer_size,
+        required_sample_format,
+    ))
+}
+
+impl<S> Player<S>
+where
+    S: Sample + SizedSample + Send,
+{
+    /// The selected device if it's connected, the default one otherwise.
+    fn find_device(&self) -> Option<cpal::Device> {
+        let host = cpal::default_host();
+        if let Some(name) = &self.cfg.backend_specific.device {
+            let selected = host.output_devices().ok().and_then(|mut devices| {
+                devices.find(|device| device.name().is_ok_and(|n| n == *name))
+            });
+            if selected.is_some() {
+                return selected;
+            }
+            debug!(
+                "Output device {} is not connected, using the default one",
+                name
+            );
         }
+        host.default_output_device()
+    }
 
-        info!("Opening stream");
-        let err_fn = |err| eprintln!("Error building output sound stream: {}", err);
-        let interleaved_samples_count = match self.stream_config.buffer_size {
+    fn build_stream(&self, device: &cpal::Device) -> Result<cpal::Stream, Error> {
+        let stream_config = select_config::<S>(device, &self.cfg)?;
+
+        let lost = Arc::clone(&self.lost);
+        let err_fn = move |err| {
+            eprintln!("Error building output sound stream: {}", err);
+            if let cpal::StreamError::DeviceNotAvailable = err {
+                lost.store(true, Ordering::Release);
+            }
+        };
+        let interleaved_samples_count = match stream_config.buffer_size {
             cpal::BufferSize::Fixed(size) => size as usize,
             cpal::BufferSize::Default => {
                 panic!("Default buffer size is not supported in this context")
             }
         };
-        let samples_count = interleaved_samples_count * self.stream_config.channels as usize;
+        let samples_count = interleaved_samples_count * stream_config.channels as usize;
 
+        let render_fn = Arc::clone(self.render_fn.as_ref().unwrap());
+        let stats = Arc::clone(&self.stats);
+        let mut recovery = Recovery::new(
+            Arc::clone(&self.stats),
+            self.cfg.sample_rate,
+            self.cfg.backend_specific.panic_fill,
+            self.max_buffer_size,
+        );
         let f = match S::code() {
             SampleCode::I8 => {
                 todo!()
@@ -193,21 +268,38 @@ where
                 todo!()
             }
             SampleCode::F32 => {
-                move |data: &mut [f32], _: &cpal::OutputCallbackInfo| match MappedInterleavedBuffer::<
-                    f32,
-                >::new(
-                    data
-                ) {
-                    Some(mut mapped_buffer) => {
-                        raw_fn(&mut mapped_buffer);
+                move |data: &mut [f32], info: &cpal::OutputCallbackInfo| {
+                    if recovery.begin(data, Some(info.timestamp().callback)) {
+                        recovery.fill(data);
+                        return;
                     }
-                    None => {
-                        warn!(
+
+                    let timestamp = info.timestamp();
+                    let latency = timestamp
+                        .playback
+                        .duration_since(&timestamp.callback)
+                        .unwrap_or_default();
+                    stats
+                        .output_latency
+                        .store(latency.as_nanos() as u64, Ordering::Relaxed);
+
+                    let start = Instant::now();
+                    match MappedInterleavedBuffer::<f32>::new(data) {
+                        Some(mut mapped_buffer) => {
+                            // Only the stream being switched to can wait here,
+                            // the old one is dropped before
+                            (render_fn.lock().unwrap())(&mut mapped_buffer);
+                        }
+                        None => {
+                            warn!(
                                 "Failed to create interleaved sample buffer: expected {} samples, got {}",
                                 samples_count,
                                 data.len()
                             );
+                            return;
+                        }
                     }
+                    recovery.rendered(data, start.elapsed());
                 }
             }
             SampleCode::F64 => {
@@ -215,25 +307,225 @@ where
             }
         };
 
-        let stream = self
-            .device
-            .build_output_stream(&self.stream_config, f, err_fn, None)
+        let stream = device
+            .build_output_stream(&stream_config, f, err_fn, None)
             .map_err(Error::BuildStreamError)?;
 
         stream.play().map_err(Error::StartStreamError)?;
-        self.stream = Some(stream);
+        Ok(stream)
+    }
+
+    /// Moves the renderer to the stream on the device.
+    fn switch(&mut self, device: Option<cpal::Device>, reason: DeviceChangeReason) -> DeviceSwitch {
+        let previous = self.device_name.take();
+        self.device = None;
+        // Closing the old stream first, so the device is not opened twice
+        self.stream = None;
+        self.lost.store(false, Ordering::Release);
+
+        let result = match device {
+            None => Err(Error::DefaultHostNotFound),
+            Some(device) => {
+                let name = device.name().unwrap_or_default();
+                info!("Opening stream on {} ({:?})", name, reason);
+                match self.build_stream(&device) {
+                    Ok(stream) => {
+                        self.stream = Some(stream);
+                        self.device = Some(device);
+                        self.device_name = Some(name.clone());
+                        self.rejected = None;
+                        Ok(name)
+                    }
+                    Err(err) => {
+                        warn!("Failed to open stream on {}: {}", name, err);
+                        self.rejected = Some(name);
+                        Err(err)
+                    }
+                }
+            }
+        };
+
+        DeviceSwitch {
+            previous,
+            reason,
+            result,
+        }
+    }
+}
+
+impl<S> PlayerBackendTrait<S> for Player<S>
+where
+    S: Sample + SizedSample + Send,
+{
+    fn new(cfg: InternalBackendConfig) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        let max_buffer_size =
+            ((cfg.sample_rate as f64 * cfg.backend_specific.max_latency.as_secs_f64()) as usize)
+                .min(MAX_OUTPUT_LEN)
+                .max(cfg.buffer_size);
+        let mut player = Player::<S> {
+            stats: Arc::new(BackendStats::new(cfg.buffer_size)),
+            max_buffer_size,
+            window_start: Instant::now(),
+            window_underruns: 0,
+            cfg,
+            device: None,
+            device_name: None,
+            stream: None,
+            render_fn: None,
+            lost: Arc::new(AtomicBool::new(false)),
+            rejected: None,
+            selection_changed: false,
+            last_poll: Instant::now(),
+            keep_s: Default::default(),
+        };
+
+        // Check the device is usable before the renderer is attached
+        let device = player.find_device().ok_or(Error::DefaultHostNotFound)?;
+        select_config::<S>(&device, &player.cfg)?;
+        player.device_name = Some(device.name().unwrap_or_default());
+        Ok(player)
+    }
+
+    fn open<F>(&mut self, raw_fn: F) -> Result<(), Error>
+    where
+        F: FnMut(&mut MappedInterleavedBuffer<f32>) + Send + 'static,
+    {
+        if self.render_fn.is_some() {
+            return Err(Error::AlreadyOpened);
+        }
+
+        info!("Opening stream");
+        self.render_fn = Some(Arc::new(Mutex::new(Box::new(raw_fn))));
+        let device = self.find_device().ok_or(Error::DefaultHostNotFound)?;
+        self.device_name = Some(device.name().unwrap_or_default());
+        self.stream = Some(self.build_stream(&device)?);
+        self.device = Some(device);
+        self.last_poll = Instant::now();
+        self.window_start = Instant::now();
 
         Ok(())
     }
 
     fn close(&mut self) -> Result<(), Error> {
-        match self.stream {
-            Some(ref stream) => {
-                stream.pause().map_err(Error::PausedStreamError)?;
-                self.stream = None;
-                Ok(())
+        if self.render_fn.take().is_none() {
+            return Err(Error::AlreadyClosed);
+        }
+        match self.stream.take() {
+            Some(stream) => stream.pause().map_err(Error::PausedStreamError),
+            // The device was lost and no other one was found
+            None => Ok(()),
+        }
+    }
+
+    fn device(&self) -> Option<String> {
+        self.device_name.clone()
+    }
+
+    fn output_devices(&self) -> Vec<String> {
+        match cpal::default_host().output_devices() {
+            Ok(devices) => devices.filter_map(|device| device.name().ok()).collect(),
+            Err(err) => {
+                warn!("Failed to enumerate output devices: {}", err);
+                vec![]
+            }
+        }
+    }
+
+    fn select_device(&mut self, name: Option<String>) {
+        self.cfg.backend_specific.device = name;
+        self.selection_changed = true;
+    }
+
+    fn poll_device(&mut self) -> Option<DeviceSwitch> {
+        // Nothing to move until the stream is opened
+        self.render_fn.as_ref()?;
+
+        let lost = self.lost.swap(false, Ordering::AcqRel);
+        let reason = if lost {
+            DeviceChangeReason::Lost
+        } else if self.selection_changed {
+            DeviceChangeReason::Selected
+        } else if self.last_poll.elapsed() >= self.cfg.backend_specific.device_poll_interval {
+            DeviceChangeReason::Changed
+        } else {
+            return None;
+        };
+        self.selection_changed = false;
+        self.last_poll = Instant::now();
+
+        let device = self.find_device();
+        if !lost {
+            let name = device.as_ref().and_then(|device| device.name().ok());
+            if name == self.device_name || (name.is_some() && name == self.rejected) {
+                return None;
+            }
+        }
+        Some(self.switch(device, reason))
+    }
+
+    fn stats(&self) -> Arc<BackendStats> {
+        Arc::clone(&self.stats)
+    }
+
+    fn poll_underruns(&mut self) {
+        if self.window_start.elapsed() < UNDERRUN_WINDOW {
+            return;
+        }
+        let total = self.stats.underruns.load(Ordering::Relaxed);
+        let count = total - self.window_underruns;
+        self.window_start = Instant::now();
+        self.window_underruns = total;
+        if count == 0 {
+            return;
+        }
+
+        let buffer_size = self.cfg.buffer_size;
+        warn!(
+            "{} underruns in the last {:?} ({} total, {} panic fills, buffer of {} samples)",
+            count,
+            UNDERRUN_WINDOW,
+            total,
+            self.stats.panic_fills.load(Ordering::Relaxed),
+            buffer_size
+        );
+        if count < self.cfg.backend_specific.underrun_threshold {
+            return;
+        }
+        if buffer_size >= self.max_buffer_size {
+            warn!("The buffer is already at the max latency, not growing it");
+            return;
+        }
+
+        let Some(device) = self.device.take() else {
+            return;
+        };
+        let grown = (buffer_size * 2).min(self.max_buffer_size);
+        warn!(
+            "Growing the buffer from {} to {} samples",
+            buffer_size, grown
+        );
+        self.stream = None;
+        self.cfg.buffer_size = grown;
+        let stream = self.build_stream(&device).or_else(|err| {
+            warn!("Failed to grow the buffer: {}", err);
+            // Not trying it again
+            self.cfg.buffer_size = buffer_size;
+            self.max_buffer_size = buffer_size;
+            self.build_stream(&device)
+        });
+        match stream {
+            Ok(stream) => {
+                self.stream = Some(stream);
+                self.device = Some(device);
+                self.stats
+                    .buffer_size
+                    .store(self.cfg.buffer_size, Ordering::Relaxed);
             }
-            None => Err(Error::AlreadyClosed),
+            // Moved to another device on the next poll
+            Err(_) => self.lost.store(true, Ordering::Release),
         }
     }
 }
@@ -0,0 +1,150 @@
+use crate::backend::BackendStats;
+use crate::{SampleRate, CHANNELS_COUNT};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Gain applied to the repeated block on each panic fill.
+const FILL_FADE: f32 = 0.5;
+
+/// Tracks the underruns of the stream on the audio thread and hides them.
+/// The underrun is counted when the render takes longer than the audio it
+/// rendered, or when the device calls back later than expected.
+/// With the panic fill enabled, the block after a late render is not rendered,
+/// but filled with the last rendered block faded out, so the stream catches up
+/// without a gap of silence. The rendering resumes with a fade in.
+pub(super) struct Recovery {
+    stats: Arc<BackendStats>,
+    sample_rate: SampleRate,
+    panic_fill: bool,
+    last_block: Vec<f32>,
+    gain: f32,
+    fill_next: bool,
+    last_callback: Option<cpal::StreamInstant>,
+}
+
+impl Recovery {
+    pub fn new(
+        stats: Arc<BackendStats>,
+        sample_rate: SampleRate,
+        panic_fill: bool,
+        capacity: usize,
+    ) -> Self {
+        Recovery {
+            stats,
+            sample_rate,
+            panic_fill,
+            last_block: Vec::with_capacity(capacity * CHANNELS_COUNT),
+            gain: 1.0,
+            fill_next: false,
+            last_callback: None,
+        }
+    }
+
+    fn budget(&self, data: &[f32]) -> Duration {
+        let frames = data.len() / CHANNELS_COUNT;
+        Duration::from_secs_f64(frames as f64 / self.sample_rate as f64)
+    }
+
+    /// Called at the start of the callback.
+    /// Returns `true` if the block should be filled instead of rendered.
+    pub fn begin(&mut self, data: &[f32], callback: Option<cpal::StreamInstant>) -> bool {
+        // The late render is already counted, the gap after it is expected
+        let budget = self.budget(data);
+        if let (Some(last), Some(callback), false) = (self.last_callback, callback, self.fill_next)
+        {
+            if callback
+                .duration_since(&last)
+                .is_some_and(|gap| gap > budget * 3 / 2)
+            {
+                self.stats.underruns.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.last_callback = callback;
+        std::mem::take(&mut self.fill_next) && self.panic_fill
+    }
+
+    /// Repeats the last rendered block, fading it out.
+    pub fn fill(&mut self, data: &mut [f32]) {
+        self.stats.panic_fills.fetch_add(1, Ordering::Relaxed);
+        let len = self.last_block.len().min(data.len());
+        data[..len].copy_from_slice(&self.last_block[..len]);
+        data[len..].fill(0.0);
+
+        let from = self.gain;
+        self.gain *= FILL_FADE;
+        ramp(data, from, self.gain);
+    }
+
+    /// Called after the block is rendered in `elapsed` time.
+    pub fn rendered(&mut self, data: &mut [f32], elapsed: Duration) {
+        if self.gain < 1.0 {
+            ramp(data, self.gain, 1.0);
+            self.gain = 1.0;
+        }
+        self.last_block.clear();
+        self.last_block.extend_from_slice(data);
+
+        if elapsed > self.budget(data) {
+            self.stats.underruns.fetch_add(1, Ordering::Relaxed);
+            self.fill_next = true;
+        }
+    }
+}
+
+/// Multiplies the interleaved samples by the gain going linearly from `from` to `to`.
+fn ramp(data: &mut [f32], from: f32, to: f32) {
+    let frames = (data.len() / CHANNELS_COUNT).max(1) as f32;
+    for (i, frame) in data.chunks_exact_mut(CHANNELS_COUNT).enumerate() {
+        let gain = from + (to - from) * (i + 1) as f32 / frames;
+        for sample in frame {
+            *sample *= gain;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recovery(panic_fill: bool) -> (Recovery, Arc<BackendStats>) {
+        let stats = Arc::new(BackendStats::new(4));
+        (Recovery::new(Arc::clone(&stats), 4, panic_fill, 4), stats)
+    }
+
+    #[test]
+    fn late_render_is_filled() {
+        let (mut recovery, stats) = recovery(true);
+        let mut data = [1.0; 4 * CHANNELS_COUNT];
+
+        assert!(!recovery.begin(&data, None));
+        recovery.rendered(&mut data, Duration::from_secs(2));
+        assert_eq!(stats.underruns.load(Ordering::Relaxed), 1);
+
+        // The last block is repeated, fading out to the half
+        assert!(recovery.begin(&data, None));
+        let mut filled = [0.0; 4 * CHANNELS_COUNT];
+        recovery.fill(&mut filled);
+        assert_eq!(stats.panic_fills.load(Ordering::Relaxed), 1);
+        assert_eq!(filled[0], 0.875);
+        assert_eq!(filled[filled.len() - 1], 0.5);
+
+        // Then faded in back
+        assert!(!recovery.begin(&data, None));
+        let mut data = [1.0; 4 * CHANNELS_COUNT];
+        recovery.rendered(&mut data, Duration::ZERO);
+        assert_eq!(data[0], 0.625);
+        assert_eq!(data[data.len() - 1], 1.0);
+    }
+
+    #[test]
+    fn no_fill_when_disabled() {
+        let (mut recovery, stats) = recovery(false);
+        let mut data = [1.0; 4 * CHANNELS_COUNT];
+
+        recovery.rendered(&mut data, Duration::from_secs(2));
+        assert!(!recovery.begin(&data, None));
+        assert_eq!(stats.underruns.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.panic_fills.load(Ordering::Relaxed), 0);
+    }
+}
@@ -105,7 +105,35 @@ pub unsafe fn sve_block_m4(input: &PlanarBlock<f32>, output: &mut PlanarBlock<f3
 #[cfg(target_arch = "x86_64")]
 #[target_feature(enable = "avx")]
 pub unsafe fn avx_block_m32(input: &PlanarBlock<f32>, output: &mut PlanarBlock<f32>) {
-    todo!();
+    // Only the float operations, which are in AVX already
+    use core::arch::x86_64::*;
+
+    for channel in 0..CHANNELS_COUNT {
+        let input_ptr = input.samples.get_unchecked(channel).as_ptr();
+        let output_ptr = output.samples.get_unchecked_mut(channel).as_mut_ptr();
+
+        // 128 floats / 8 = 16 vectors
+        // Unroll x4: 16 / 4 = 4 iterations
+        let mut i = 0;
+        while i < BLOCK_SIZE {
+            let in0 = _mm256_load_ps(input_ptr.add(i));
+            let in1 = _mm256_load_ps(input_ptr.add(i + 8));
+            let in2 = _mm256_load_ps(input_ptr.add(i + 16));
+            let in3 = _mm256_load_ps(input_ptr.add(i + 24));
+
+            let out0 = _mm256_load_ps(output_ptr.add(i));
+            let out1 = _mm256_load_ps(output_ptr.add(i + 8));
+            let out2 = _mm256_load_ps(output_ptr.add(i + 16));
+            let out3 = _mm256_load_ps(output_ptr.add(i + 24));
+
+            _mm256_store_ps(output_ptr.add(i), _mm256_add_ps(in0, out0));
+            _mm256_store_ps(output_ptr.add(i + 8), _mm256_add_ps(in1, out1));
+            _mm256_store_ps(output_ptr.add(i + 16), _mm256_add_ps(in2, out2));
+            _mm256_store_ps(output_ptr.add(i + 24), _mm256_add_ps(in3, out3));
+
+            i += 32;
+        }
+    }
 }
 
 #[inline(never)]
@@ -136,7 +164,7 @@ pub unsafe fn sse42_block_m32(input: &PlanarBlock<f32>, output: &mut PlanarBlock
 
 #[inline(always)]
 pub fn fallback(input: &PlanarBlock<f32>, output: &mut PlanarBlock<f32>) {
-    for channel in 0..CHANNELS_COUNT as usize {
+    for channel in 0..CHANNELS_COUNT {
         for i in 0..BLOCK_SIZE {
             output.samples[channel][i] += input.samples[channel][i];
         }
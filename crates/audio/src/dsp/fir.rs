@@ -1 +1,85 @@
+// Dot product of the FIR taps with the delay line.
+// The lanes are summed in a different order than in the fallback,
+// so the results differ by the rounding errors.
 
+#[inline(never)]
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+pub unsafe fn avx512_dot(a: &[f32], b: &[f32]) -> f32 {
+    use core::arch::x86_64::*;
+
+    let len = a.len().min(b.len());
+    let mut acc = _mm512_setzero_ps();
+    let mut i = 0;
+    while i + 16 <= len {
+        let x = _mm512_loadu_ps(a.as_ptr().add(i));
+        let y = _mm512_loadu_ps(b.as_ptr().add(i));
+        acc = _mm512_add_ps(acc, _mm512_mul_ps(x, y));
+        i += 16;
+    }
+    _mm512_reduce_add_ps(acc) + fallback(&a[i..len], &b[i..len])
+}
+
+#[inline(never)]
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx")]
+pub unsafe fn avx_dot(a: &[f32], b: &[f32]) -> f32 {
+    use core::arch::x86_64::*;
+
+    let len = a.len().min(b.len());
+    let mut acc = _mm256_setzero_ps();
+    let mut i = 0;
+    while i + 8 <= len {
+        let x = _mm256_loadu_ps(a.as_ptr().add(i));
+        let y = _mm256_loadu_ps(b.as_ptr().add(i));
+        acc = _mm256_add_ps(acc, _mm256_mul_ps(x, y));
+        i += 8;
+    }
+    let sum = _mm_add_ps(_mm256_castps256_ps128(acc), _mm256_extractf128_ps::<1>(acc));
+    let sum = _mm_hadd_ps(sum, sum);
+    let sum = _mm_hadd_ps(sum, sum);
+    _mm_cvtss_f32(sum) + fallback(&a[i..len], &b[i..len])
+}
+
+#[inline(never)]
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.2")]
+pub unsafe fn sse42_dot(a: &[f32], b: &[f32]) -> f32 {
+    use core::arch::x86_64::*;
+
+    let len = a.len().min(b.len());
+    let mut acc = _mm_setzero_ps();
+    let mut i = 0;
+    while i + 4 <= len {
+        let x = _mm_loadu_ps(a.as_ptr().add(i));
+        let y = _mm_loadu_ps(b.as_ptr().add(i));
+        acc = _mm_add_ps(acc, _mm_mul_ps(x, y));
+        i += 4;
+    }
+    let sum = _mm_hadd_ps(acc, acc);
+    let sum = _mm_hadd_ps(sum, sum);
+    _mm_cvtss_f32(sum) + fallback(&a[i..len], &b[i..len])
+}
+
+#[inline(never)]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+#[target_feature(enable = "neon")]
+pub unsafe fn neon_dot(a: &[f32], b: &[f32]) -> f32 {
+    use core::arch::aarch64::*;
+
+    let len = a.len().min(b.len());
+    let mut acc = vdupq_n_f32(0.0);
+    let mut i = 0;
+    while i + 4 <= len {
+        let x = vld1q_f32(a.as_ptr().add(i));
+        let y = vld1q_f32(b.as_ptr().add(i));
+        acc = vaddq_f32(acc, vmulq_f32(x, y));
+        i += 4;
+    }
+    vaddvq_f32(acc) + fallback(&a[i..len], &b[i..len])
+}
+
+#[inline(always)]
+pub fn fallback(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
@@ -0,0 +1,105 @@
+use crate::sample::{PlanarBlock, LEFT_CHANNEL, RIGHT_CHANNEL};
+use crate::BLOCK_SIZE;
+
+// Only the multiplications, so there is no AVX2 variant: AVX has them already.
+
+#[inline(never)]
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+// The block MUST be aligned to 64 bytes
+pub unsafe fn avx512_block_m32(block: &mut PlanarBlock<f32>, left: f32, right: f32) {
+    use core::arch::x86_64::*;
+
+    for (channel, gain) in [(LEFT_CHANNEL, left), (RIGHT_CHANNEL, right)] {
+        let mul = _mm512_set1_ps(gain);
+        let ptr = block.samples.get_unchecked_mut(channel).as_mut_ptr();
+
+        let mut i = 0;
+        while i < BLOCK_SIZE {
+            let a = _mm512_load_ps(ptr.add(i));
+            let b = _mm512_load_ps(ptr.add(i + 16));
+            _mm512_store_ps(ptr.add(i), _mm512_mul_ps(a, mul));
+            _mm512_store_ps(ptr.add(i + 16), _mm512_mul_ps(b, mul));
+
+            i += 32;
+        }
+    }
+}
+
+#[inline(never)]
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx")]
+pub unsafe fn avx_block_m32(block: &mut PlanarBlock<f32>, left: f32, right: f32) {
+    use core::arch::x86_64::*;
+
+    for (channel, gain) in [(LEFT_CHANNEL, left), (RIGHT_CHANNEL, right)] {
+        let mul = _mm256_set1_ps(gain);
+        let ptr = block.samples.get_unchecked_mut(channel).as_mut_ptr();
+
+        let mut i = 0;
+        while i < BLOCK_SIZE {
+            let a = _mm256_load_ps(ptr.add(i));
+            let b = _mm256_load_ps(ptr.add(i + 8));
+            let c = _mm256_load_ps(ptr.add(i + 16));
+            let d = _mm256_load_ps(ptr.add(i + 24));
+            _mm256_store_ps(ptr.add(i), _mm256_mul_ps(a, mul));
+            _mm256_store_ps(ptr.add(i + 8), _mm256_mul_ps(b, mul));
+            _mm256_store_ps(ptr.add(i + 16), _mm256_mul_ps(c, mul));
+            _mm256_store_ps(ptr.add(i + 24), _mm256_mul_ps(d, mul));
+
+            i += 32;
+        }
+    }
+}
+
+#[inline(never)]
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.2")]
+pub unsafe fn sse42_block_m32(block: &mut PlanarBlock<f32>, left: f32, right: f32) {
+    use core::arch::x86_64::*;
+
+    for (channel, gain) in [(LEFT_CHANNEL, left), (RIGHT_CHANNEL, right)] {
+        let mul = _mm_set1_ps(gain);
+        let ptr = block.samples.get_unchecked_mut(channel).as_mut_ptr();
+
+        let mut i = 0;
+        while i < BLOCK_SIZE {
+            let a = _mm_load_ps(ptr.add(i));
+            let b = _mm_load_ps(ptr.add(i + 4));
+            _mm_store_ps(ptr.add(i), _mm_mul_ps(a, mul));
+            _mm_store_ps(ptr.add(i + 4), _mm_mul_ps(b, mul));
+
+            i += 8;
+        }
+    }
+}
+
+#[inline(never)]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+#[target_feature(enable = "neon")]
+pub unsafe fn neon_block_m4(block: &mut PlanarBlock<f32>, left: f32, right: f32) {
+    use core::arch::aarch64::*;
+
+    for (channel, gain) in [(LEFT_CHANNEL, left), (RIGHT_CHANNEL, right)] {
+        let mul = vdupq_n_f32(gain);
+        let ptr = block.samples[channel].as_mut_ptr();
+
+        let mut i = 0;
+        while i < BLOCK_SIZE {
+            vst1q_f32(ptr.add(i), vmulq_f32(vld1q_f32(ptr.add(i)), mul));
+            i += 4;
+        }
+    }
+}
+
+#[inline(always)]
+pub fn fallback(block: &mut PlanarBlock<f32>, left: f32, right: f32) {
+    // Separating the channels into two loops gives a little bit
+    // better performance due to better cache locality
+    for sample in block.samples[LEFT_CHANNEL].iter_mut() {
+        *sample *= left;
+    }
+    for sample in block.samples[RIGHT_CHANNEL].iter_mut() {
+        *sample *= right;
+    }
+}
@@ -1,12 +1,12 @@
-use crate::sample::{InterleavedBlock, PlanarBlock, LEFT_CHANNEL, RIGHT_CHANNEL};
+use crate::sample::{InterleavedBlock, PlanarBlock};
 use crate::{SamplesCount, BLOCK_SIZE, CHANNELS_COUNT};
 
 mod add;
 mod addm;
 mod copy_into_interleaved;
 mod fir;
+mod gain_pan;
 mod soft_clip;
-mod soft_limit;
 #[cfg(test)]
 mod tests;
 
@@ -70,6 +70,12 @@ mod feature_flag_impl {
 
 use feature_flag_impl::FeatureFlag;
 
+// The aligned loads of the SIMD variants rely on it.
+// Each channel of the block must start at the multiple of 64 bytes too
+const _: () = assert!(std::mem::align_of::<PlanarBlock<f32>>() >= 64);
+const _: () = assert!((BLOCK_SIZE * std::mem::size_of::<f32>()).is_multiple_of(64));
+const _: () = assert!(std::mem::align_of::<InterleavedBlock<f32>>() >= 64);
+
 #[cfg(target_arch = "x86_64")]
 mod features {
     use crate::dsp::FeatureFlag;
@@ -150,16 +156,16 @@ macro_rules! call_accelerated(
 
 impl PlanarBlock<f32> {
     #[inline(always)]
+    // Only the tests call it while the clip copy in `ActorsSource` is commented out.
+    #[allow(dead_code)]
     pub(crate) fn copy_from_planar_vec(
         &mut self,
         input: &[Vec<f32>; CHANNELS_COUNT],
         start: SamplesCount,
         size: SamplesCount,
     ) {
-        for channel in 0..CHANNELS_COUNT {
-            let src = &input[channel][start..start + size];
-            let dst = &mut self.samples[channel];
-            dst.copy_from_slice(src);
+        for (dst, src) in self.samples.iter_mut().zip(input) {
+            dst.copy_from_slice(&src[start..start + size]);
         }
     }
 
@@ -214,7 +220,7 @@ impl PlanarBlock<f32> {
         );
 
         use copy_into_interleaved::*;
-        // accelerated!("aarch64", 4, ARM_HAS_NEON, neon_block_m4);
+        accelerated!("aarch64", 4, ARM_HAS_NEON, neon_block_m4);
         // accelerated!("aarch64", 4, ARM_HAS_SVE, sve_block_m4);
         accelerated!("x86_64", 32, X86_HAS_AVX512, avx512_block_m32);
         accelerated!("x86_64", 32, X86_HAS_AVX2, avx2_block_m32);
@@ -271,7 +277,7 @@ impl PlanarBlock<f32> {
         use addm::*;
         accelerated!("aarch64", 4, ARM_HAS_NEON, neon_block_m4);
         accelerated!("aarch64", 4, ARM_HAS_SVE, sve_block_m4);
-        // accelerated!("x86_64", 32, X86_HAS_AVX512, avx512_block_m32); TODO: Sometimes causes a crash
+        accelerated!("x86_64", 32, X86_HAS_AVX512, avx512_block_m32);
         accelerated!("x86_64", 32, X86_HAS_AVX2, avx2_block_m32);
         accelerated!("x86_64", 32, X86_HAS_AVX, avx_block_m32);
         accelerated!("x86_64", 32, X86_HAS_SSE42, sse42_block_m32);
@@ -280,6 +286,7 @@ impl PlanarBlock<f32> {
         fallback(input, self, k);
     }
 
+    /// y = tanh(1.5 * x), approximated with a rational function.
     #[inline(always)]
     pub(crate) fn soft_clip(&mut self) {
         macro_rules! accelerated(
@@ -294,31 +301,65 @@ impl PlanarBlock<f32> {
             }
         );
 
-        // use soft_clip::*;
-        // accelerated!("aarch64", 4, ARM_HAS_NEON, neon_block_m4);
-        // accelerated!("aarch64", 4, ARM_HAS_SVE, sve_block_m4);
-        // accelerated!("x86_64", 32, X86_HAS_AVX512, avx512_block_m32);
-        // accelerated!("x86_64", 32, X86_HAS_AVX2, avx2_block_m32);
-        // accelerated!("x86_64", 32, X86_HAS_AVX, avx_block_m32);
-        // accelerated!("x86_64", 32, X86_HAS_SSE42, sse42_block_m32);
-        //
-        // // Fallback to the basic implementation if no SIMD is available
-        // fallback(self);
+        use soft_clip::*;
+        accelerated!("aarch64", 4, ARM_HAS_NEON, neon_block_m4);
+        accelerated!("x86_64", 32, X86_HAS_AVX512, avx512_block_m32);
+        accelerated!("x86_64", 32, X86_HAS_AVX, avx_block_m32);
+        accelerated!("x86_64", 32, X86_HAS_SSE42, sse42_block_m32);
+
+        // Fallback to the basic implementation if no SIMD is available
+        fallback(self);
     }
 
     #[inline(always)]
     pub(crate) fn gain_pan(&mut self, gain: f32, pan: f32) {
-        // TODO: Implement SIMD acceleration for gain and pan
-        // Separating the channels into two loops gives a little bit
-        // better performance due to better cache locality
         let left_gain = gain * (1.0 - pan).sqrt();
-        for i in 0..BLOCK_SIZE {
-            self.samples[LEFT_CHANNEL][i] = (self.samples[LEFT_CHANNEL][i] * left_gain);
-        }
-
         let right_gain = gain * (1.0 + pan).sqrt();
-        for i in 0..BLOCK_SIZE {
-            self.samples[RIGHT_CHANNEL][i] = (self.samples[RIGHT_CHANNEL][i] * right_gain);
+        macro_rules! accelerated(
+            ($arch:expr, $align:expr, $condvar:ident, $func:expr) => {
+                call_accelerated!(
+                    $arch,
+                    $align,
+                    $condvar,
+                    $func,
+                    self,
+                    left_gain,
+                    right_gain
+                );
+            }
+        );
+
+        use gain_pan::*;
+        accelerated!("aarch64", 4, ARM_HAS_NEON, neon_block_m4);
+        accelerated!("x86_64", 32, X86_HAS_AVX512, avx512_block_m32);
+        accelerated!("x86_64", 32, X86_HAS_AVX, avx_block_m32);
+        accelerated!("x86_64", 32, X86_HAS_SSE42, sse42_block_m32);
+
+        // Fallback to the basic implementation if SIMD is not available
+        fallback(self, left_gain, right_gain);
+    }
+}
+
+/// Dot product of the slices, up to the length of the shorter one.
+#[inline(always)]
+pub(crate) fn dot(a: &[f32], b: &[f32]) -> f32 {
+    use fir::*;
+    #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+    if ARM_HAS_NEON.get() {
+        return unsafe { neon_dot(a, b) };
+    }
+    #[cfg(target_arch = "x86_64")]
+    {
+        if X86_HAS_AVX512.get() {
+            return unsafe { avx512_dot(a, b) };
+        }
+        if X86_HAS_AVX.get() {
+            return unsafe { avx_dot(a, b) };
+        }
+        if X86_HAS_SSE42.get() {
+            return unsafe { sse42_dot(a, b) };
         }
     }
+
+    fallback(a, b)
 }
@@ -1,27 +1,133 @@
-// #[inline(always)]
-// pub unsafe fn soft_clip_avx(x: __m256) -> __m256 {
-//     // y = tanh(1.5 * x)
-//     let gain = _mm256_set1_ps(1.5);
-//     let scaled = _mm256_mul_ps(x, gain);
-//     tanh_ps_avx(scaled)
-// }
-
-// tanh(x) ≈ x * (27 + x^2) / (27 + 9x^2)
-// Быстрое приближение, приемлемое качество
-//
-// #[inline(always)]
-// unsafe fn tanh_ps_avx(x: __m256) -> __m256 {
-//     let x2 = _mm256_mul_ps(x, x);
-//     let num = _mm256_add_ps(_mm256_set1_ps(27.0), x2);
-//     let denom = _mm256_add_ps(_mm256_set1_ps(27.0), _mm256_mul_ps(x2, _mm256_set1_ps(9.0)));
-//     _mm256_mul_ps(x, _mm256_div_ps(num, denom))
-// }
-
-fn fallback(input: &mut [f32]) {
-    for sample in input.iter_mut() {
-        // y = tanh(1.5 * x)
-        let gain = 1.5;
-        let scaled = *sample * gain;
-        *sample = scaled.tanh();
+use crate::sample::PlanarBlock;
+use crate::{BLOCK_SIZE, CHANNELS_COUNT};
+
+// y = tanh(1.5 * x), with tanh(x) ≈ x * (27 + x^2) / (27 + 9x^2).
+// The approximation reaches 1 at x = 3, so the input is clamped there.
+// All the variants do the same operations in the same order,
+// so the results are bit-exact with the fallback.
+const GAIN: f32 = 1.5;
+const LIMIT: f32 = 3.0;
+
+#[inline(never)]
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx512f")]
+// The block MUST be aligned to 64 bytes
+pub unsafe fn avx512_block_m32(block: &mut PlanarBlock<f32>) {
+    use core::arch::x86_64::*;
+
+    let gain = _mm512_set1_ps(GAIN);
+    let min = _mm512_set1_ps(-LIMIT);
+    let max = _mm512_set1_ps(LIMIT);
+    let c27 = _mm512_set1_ps(27.0);
+    let c9 = _mm512_set1_ps(9.0);
+    for channel in 0..CHANNELS_COUNT {
+        let ptr = block.samples.get_unchecked_mut(channel).as_mut_ptr();
+
+        let mut i = 0;
+        while i < BLOCK_SIZE {
+            let x = _mm512_mul_ps(_mm512_load_ps(ptr.add(i)), gain);
+            let x = _mm512_min_ps(_mm512_max_ps(x, min), max);
+            let x2 = _mm512_mul_ps(x, x);
+            let num = _mm512_add_ps(c27, x2);
+            let den = _mm512_add_ps(c27, _mm512_mul_ps(x2, c9));
+            _mm512_store_ps(ptr.add(i), _mm512_mul_ps(x, _mm512_div_ps(num, den)));
+
+            i += 16;
+        }
+    }
+}
+
+#[inline(never)]
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx")]
+pub unsafe fn avx_block_m32(block: &mut PlanarBlock<f32>) {
+    use core::arch::x86_64::*;
+
+    let gain = _mm256_set1_ps(GAIN);
+    let min = _mm256_set1_ps(-LIMIT);
+    let max = _mm256_set1_ps(LIMIT);
+    let c27 = _mm256_set1_ps(27.0);
+    let c9 = _mm256_set1_ps(9.0);
+    for channel in 0..CHANNELS_COUNT {
+        let ptr = block.samples.get_unchecked_mut(channel).as_mut_ptr();
+
+        let mut i = 0;
+        while i < BLOCK_SIZE {
+            let x = _mm256_mul_ps(_mm256_load_ps(ptr.add(i)), gain);
+            let x = _mm256_min_ps(_mm256_max_ps(x, min), max);
+            let x2 = _mm256_mul_ps(x, x);
+            let num = _mm256_add_ps(c27, x2);
+            let den = _mm256_add_ps(c27, _mm256_mul_ps(x2, c9));
+            _mm256_store_ps(ptr.add(i), _mm256_mul_ps(x, _mm256_div_ps(num, den)));
+
+            i += 8;
+        }
+    }
+}
+
+#[inline(never)]
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.2")]
+pub unsafe fn sse42_block_m32(block: &mut PlanarBlock<f32>) {
+    use core::arch::x86_64::*;
+
+    let gain = _mm_set1_ps(GAIN);
+    let min = _mm_set1_ps(-LIMIT);
+    let max = _mm_set1_ps(LIMIT);
+    let c27 = _mm_set1_ps(27.0);
+    let c9 = _mm_set1_ps(9.0);
+    for channel in 0..CHANNELS_COUNT {
+        let ptr = block.samples.get_unchecked_mut(channel).as_mut_ptr();
+
+        let mut i = 0;
+        while i < BLOCK_SIZE {
+            let x = _mm_mul_ps(_mm_load_ps(ptr.add(i)), gain);
+            let x = _mm_min_ps(_mm_max_ps(x, min), max);
+            let x2 = _mm_mul_ps(x, x);
+            let num = _mm_add_ps(c27, x2);
+            let den = _mm_add_ps(c27, _mm_mul_ps(x2, c9));
+            _mm_store_ps(ptr.add(i), _mm_mul_ps(x, _mm_div_ps(num, den)));
+
+            i += 4;
+        }
+    }
+}
+
+#[inline(never)]
+#[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+#[target_feature(enable = "neon")]
+pub unsafe fn neon_block_m4(block: &mut PlanarBlock<f32>) {
+    use core::arch::aarch64::*;
+
+    let gain = vdupq_n_f32(GAIN);
+    let min = vdupq_n_f32(-LIMIT);
+    let max = vdupq_n_f32(LIMIT);
+    let c27 = vdupq_n_f32(27.0);
+    let c9 = vdupq_n_f32(9.0);
+    for channel in 0..CHANNELS_COUNT {
+        let ptr = block.samples[channel].as_mut_ptr();
+
+        let mut i = 0;
+        while i < BLOCK_SIZE {
+            let x = vmulq_f32(vld1q_f32(ptr.add(i)), gain);
+            let x = vminq_f32(vmaxq_f32(x, min), max);
+            let x2 = vmulq_f32(x, x);
+            let num = vaddq_f32(c27, x2);
+            let den = vaddq_f32(c27, vmulq_f32(x2, c9));
+            vst1q_f32(ptr.add(i), vmulq_f32(x, vdivq_f32(num, den)));
+
+            i += 4;
+        }
+    }
+}
+
+#[inline(always)]
+pub fn fallback(block: &mut PlanarBlock<f32>) {
+    for channel in 0..CHANNELS_COUNT {
+        for sample in block.samples[channel].iter_mut() {
+            let x = (*sample * GAIN).clamp(-LIMIT, LIMIT);
+            let x2 = x * x;
+            *sample = x * ((27.0 + x2) / (27.0 + x2 * 9.0));
+        }
     }
 }
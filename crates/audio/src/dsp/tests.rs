@@ -1,208 +1,475 @@
-#[cfg(test)]
-mod tests {
-    extern crate test;
-    use crate::dsp::{detect_features, disable_all_features};
-    use crate::sample::{InterleavedBlock, PlanarBlock};
-    use crate::{BLOCK_SIZE, CHANNELS_COUNT};
-    use std::panic;
-    use test::Bencher;
-
-    #[test]
-    fn copy_from_planar_vec_full_test() {
+// The blocks are compared sample by sample, indexing both sides.
+#![allow(clippy::needless_range_loop)]
+
+extern crate test;
+use crate::dsp::{detect_features, disable_all_features};
+use crate::sample::{InterleavedBlock, PlanarBlock, LEFT_CHANNEL, RIGHT_CHANNEL};
+use crate::{BLOCK_SIZE, CHANNELS_COUNT};
+use std::panic;
+use test::Bencher;
+
+#[test]
+fn copy_from_planar_vec_full_test() {
+    let mut block = PlanarBlock::<f32>::default();
+    let input: [Vec<f32>; CHANNELS_COUNT] = [
+        (0..BLOCK_SIZE).map(|i| i as f32).collect(),
+        (0..BLOCK_SIZE).map(|i| i as f32 + 1.0).collect(),
+    ];
+
+    block.copy_from_planar_vec(&input, 0, BLOCK_SIZE);
+
+    for channel in 0..CHANNELS_COUNT {
+        for i in 0..BLOCK_SIZE {
+            assert_eq!(block.samples[channel][i], input[channel][i]);
+        }
+    }
+}
+
+#[test]
+fn copy_from_planar_vec_fail_if_not_end_of_block() {
+    let result = panic::catch_unwind(|| {
         let mut block = PlanarBlock::<f32>::default();
         let input: [Vec<f32>; CHANNELS_COUNT] = [
             (0..BLOCK_SIZE).map(|i| i as f32).collect(),
             (0..BLOCK_SIZE).map(|i| i as f32 + 1.0).collect(),
         ];
 
-        block.copy_from_planar_vec(&input, 0, BLOCK_SIZE);
+        // Panic if we try to copy more than the block size
+        block.copy_from_planar_vec(&input, 0, BLOCK_SIZE + 1);
+    });
 
-        for channel in 0..CHANNELS_COUNT {
-            for i in 0..BLOCK_SIZE {
-                assert_eq!(block.samples[channel][i], input[channel][i]);
-            }
+    assert!(
+        result.is_err(),
+        "Expected panic when copying more than block size"
+    );
+}
+
+#[test]
+fn copy_from_test() {
+    let mut block1 = PlanarBlock::<f32>::default();
+    let mut block2 = PlanarBlock::<f32>::default();
+
+    for channel in 0..CHANNELS_COUNT {
+        for i in 0..BLOCK_SIZE {
+            block1.samples[channel][i] = i as f32 + channel as f32;
         }
     }
 
-    #[test]
-    fn copy_from_planar_vec_fail_if_not_end_of_block() {
-        let result = panic::catch_unwind(|| {
-            let mut block = PlanarBlock::<f32>::default();
-            let input: [Vec<f32>; CHANNELS_COUNT] = [
-                (0..BLOCK_SIZE).map(|i| i as f32).collect(),
-                (0..BLOCK_SIZE).map(|i| i as f32 + 1.0).collect(),
-            ];
+    block2.copy_from(&block1);
 
-            // Panic if we try to copy more than the block size
-            block.copy_from_planar_vec(&input, 0, BLOCK_SIZE + 1);
-        });
-
-        assert!(
-            result.is_err(),
-            "Expected panic when copying more than block size"
-        );
+    for channel in 0..CHANNELS_COUNT {
+        for i in 0..BLOCK_SIZE {
+            assert_eq!(block2.samples[channel][i], i as f32 + channel as f32);
+        }
     }
+}
 
-    #[test]
-    fn copy_from_test() {
-        let mut block1 = PlanarBlock::<f32>::default();
-        let mut block2 = PlanarBlock::<f32>::default();
+fn copy_into_interleaved_test() {
+    let mut block = PlanarBlock::<f32>::default();
+    let mut interleaved = InterleavedBlock::<f32>::default();
 
-        for channel in 0..CHANNELS_COUNT {
-            for i in 0..BLOCK_SIZE {
-                block1.samples[channel][i] = i as f32 + channel as f32;
-            }
+    for channel in 0..CHANNELS_COUNT {
+        for i in 0..BLOCK_SIZE {
+            block.samples[channel][i] = i as f32 + channel as f32 * 100.0;
         }
+    }
 
-        block2.copy_from(&block1);
+    block.copy_into_interleaved(&mut interleaved);
 
+    for i in 0..BLOCK_SIZE {
         for channel in 0..CHANNELS_COUNT {
-            for i in 0..BLOCK_SIZE {
-                assert_eq!(block2.samples[channel][i], i as f32 + channel as f32);
-            }
+            let expected = i as f32 + channel as f32 * 100.0;
+            let actual = interleaved.samples[i].channels[channel];
+            assert_eq!(
+                actual, expected,
+                "Mismatch at sample {}, channel {}",
+                i, channel
+            );
         }
     }
+}
 
-    fn copy_into_interleaved_test() {
-        let mut block = PlanarBlock::<f32>::default();
-        let mut interleaved = InterleavedBlock::<f32>::default();
+#[test]
+fn copy_into_interleaved_test_simd() {
+    detect_features();
+    copy_into_interleaved_test();
+}
 
-        for channel in 0..CHANNELS_COUNT {
-            for i in 0..BLOCK_SIZE {
-                block.samples[channel][i] = i as f32 + channel as f32 * 100.0;
-            }
+#[test]
+fn copy_into_interleaved_test_fallback() {
+    disable_all_features();
+    copy_into_interleaved_test();
+}
+
+#[bench]
+fn copy_into_interleaved_bench(b: &mut Bencher) {
+    detect_features();
+
+    let mut planar = PlanarBlock::<f32>::default();
+    let mut interleaved: InterleavedBlock<f32> = InterleavedBlock::default();
+
+    for channel in 0..CHANNELS_COUNT {
+        for i in 0..BLOCK_SIZE {
+            planar.samples[channel][i] = i as f32 + channel as f32;
         }
+    }
+
+    b.iter(|| {
+        planar.copy_into_interleaved(&mut interleaved);
+    });
+}
 
-        block.copy_into_interleaved(&mut interleaved);
+fn add_test() {
+    let mut block1 = PlanarBlock::<f32>::default();
+    let mut block2 = PlanarBlock::<f32>::default();
 
+    for channel in 0..CHANNELS_COUNT {
         for i in 0..BLOCK_SIZE {
-            for channel in 0..CHANNELS_COUNT {
-                let expected = i as f32 + channel as f32 * 100.0;
-                let actual = interleaved.samples[i].channels[channel];
-                assert_eq!(
-                    actual, expected,
-                    "Mismatch at sample {}, channel {}",
-                    i, channel
-                );
-            }
+            block1.samples[channel][i] = i as f32 + channel as f32;
+            block2.samples[channel][i] = (i as f32 + channel as f32) * 2.0;
         }
     }
 
-    #[test]
-    fn copy_into_interleaved_test_simd() {
-        detect_features();
-        copy_into_interleaved_test();
+    block1.add(&block2);
+
+    for channel in 0..CHANNELS_COUNT {
+        for i in 0..BLOCK_SIZE {
+            assert_eq!(
+                block1.samples[channel][i],
+                (i as f32 + channel as f32) * 3.0
+            );
+        }
     }
+}
+
+#[test]
+fn add_test_simd() {
+    detect_features();
+    add_test();
+}
+
+#[test]
+fn add_test_fallback() {
+    disable_all_features();
+    add_test();
+}
+
+#[bench]
+fn add_bench(b: &mut Bencher) {
+    detect_features();
 
-    #[test]
-    fn copy_into_interleaved_test_fallback() {
-        disable_all_features();
-        copy_into_interleaved_test();
+    let mut block1 = PlanarBlock::<f32>::default();
+    let mut block2 = PlanarBlock::<f32>::default();
+
+    for channel in 0..CHANNELS_COUNT {
+        for i in 0..BLOCK_SIZE {
+            block1.samples[channel][i] = i as f32 + channel as f32;
+            block2.samples[channel][i] = (i as f32 + channel as f32) * 2.0;
+        }
     }
 
-    #[bench]
-    fn copy_into_interleaved_bench(b: &mut Bencher) {
-        detect_features();
+    b.iter(|| {
+        block1.add(&block2);
+    });
+}
 
-        let mut planar = PlanarBlock::<f32>::default();
-        let mut interleaved: InterleavedBlock<f32> = InterleavedBlock::default();
+fn addm_test() {
+    let mut block1 = PlanarBlock::<f32>::default();
+    let mut block2 = PlanarBlock::<f32>::default();
 
-        for channel in 0..CHANNELS_COUNT {
-            for i in 0..BLOCK_SIZE {
-                planar.samples[channel][i] = i as f32 + channel as f32;
-            }
+    for channel in 0..CHANNELS_COUNT {
+        for i in 0..BLOCK_SIZE {
+            block1.samples[channel][i] = i as f32 + channel as f32;
+            block2.samples[channel][i] = (i as f32 + channel as f32) * 3.0;
         }
+    }
 
-        b.iter(|| {
-            planar.copy_into_interleaved(&mut interleaved);
-        });
+    block1.addm(&block2, 0.5);
+    for channel in 0..CHANNELS_COUNT {
+        for i in 0..BLOCK_SIZE {
+            assert_eq!(
+                block1.samples[channel][i],
+                (i as f32 + channel as f32) + (i as f32 + channel as f32) * 3.0 * 0.5
+            );
+        }
     }
+}
 
-    fn add_test() {
-        let mut block1 = PlanarBlock::<f32>::default();
-        let mut block2 = PlanarBlock::<f32>::default();
+#[test]
+fn addm_test_simd() {
+    detect_features();
+    addm_test();
+}
 
-        for channel in 0..CHANNELS_COUNT {
-            for i in 0..BLOCK_SIZE {
-                block1.samples[channel][i] = i as f32 + channel as f32;
-                block2.samples[channel][i] = (i as f32 + channel as f32) * 2.0;
-            }
-        }
+#[test]
+fn addm_test_fallback() {
+    disable_all_features();
+    addm_test();
+}
 
-        block1.add(&block2);
+/// Sweeps the range of the signal, with the edge values at the start.
+fn sweep_block(seed: usize) -> PlanarBlock<f32> {
+    const EDGES: [f32; 8] = [0.0, -0.0, 1.0, -1.0, 2.0, -2.0, 1e-30, -1e30];
 
-        for channel in 0..CHANNELS_COUNT {
-            for i in 0..BLOCK_SIZE {
-                assert_eq!(
-                    block1.samples[channel][i],
-                    (i as f32 + channel as f32) * 3.0
-                );
-            }
+    let mut block = PlanarBlock::<f32>::default();
+    for channel in 0..CHANNELS_COUNT {
+        for i in 0..BLOCK_SIZE {
+            block.samples[channel][i] = match EDGES.get(i) {
+                Some(edge) if seed == 0 => *edge,
+                _ => {
+                    let t = (i + seed * BLOCK_SIZE + channel * 7) as f32;
+                    (t * 0.37).sin() * (seed as f32 + 1.0) * 0.75
+                }
+            };
         }
     }
+    block
+}
 
-    #[test]
-    fn add_test_simd() {
-        detect_features();
-        add_test();
+fn assert_same(actual: &PlanarBlock<f32>, expected: &PlanarBlock<f32>, variant: &str) {
+    for channel in 0..CHANNELS_COUNT {
+        for i in 0..BLOCK_SIZE {
+            assert_eq!(
+                actual.samples[channel][i].to_bits(),
+                expected.samples[channel][i].to_bits(),
+                "{}: mismatch at sample {}, channel {}",
+                variant,
+                i,
+                channel
+            );
+        }
     }
+}
 
-    #[test]
-    fn add_test_fallback() {
-        disable_all_features();
-        add_test();
+/// Calls every variant supported by the CPU directly,
+/// so the result does not depend on the global feature flags.
+macro_rules! for_each_variant(
+    ($module:ident, $block:ident, $call:expr) => {{
+        let mut variants: Vec<(&str, PlanarBlock<f32>)> = Vec::new();
+        #[cfg(target_arch = "x86_64")]
+        {
+            use std::arch::is_x86_feature_detected;
+            use crate::dsp::$module::*;
+            let mut $block = PlanarBlock::<f32>::default();
+            if is_x86_feature_detected!("avx512f") {
+                $call(avx512_block_m32, &mut $block);
+                variants.push(("avx512", $block));
+            }
+            let mut $block = PlanarBlock::<f32>::default();
+            if is_x86_feature_detected!("avx") {
+                $call(avx_block_m32, &mut $block);
+                variants.push(("avx", $block));
+            }
+            let mut $block = PlanarBlock::<f32>::default();
+            if is_x86_feature_detected!("sse4.2") {
+                $call(sse42_block_m32, &mut $block);
+                variants.push(("sse42", $block));
+            }
+        }
+        #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+        {
+            use std::arch::is_aarch64_feature_detected;
+            use crate::dsp::$module::*;
+            let mut $block = PlanarBlock::<f32>::default();
+            if is_aarch64_feature_detected!("neon") {
+                $call(neon_block_m4, &mut $block);
+                variants.push(("neon", $block));
+            }
+        }
+        variants
+    }}
+);
+
+#[test]
+fn gain_pan_equivalence_test() {
+    use crate::dsp::gain_pan::fallback;
+
+    for seed in 0..4 {
+        for (left, right) in [(1.0, 1.0), (0.0, 1.41), (0.5, -0.25), (1e20, 3.3)] {
+            let mut expected = sweep_block(seed);
+            fallback(&mut expected, left, right);
+
+            type Variant = unsafe fn(&mut PlanarBlock<f32>, f32, f32);
+            let call = |f: Variant, block: &mut PlanarBlock<f32>| {
+                block.copy_from(&sweep_block(seed));
+                unsafe { f(block, left, right) }
+            };
+            let variants = for_each_variant!(gain_pan, block, call);
+            for (variant, actual) in &variants {
+                assert_same(actual, &expected, variant);
+            }
+        }
     }
+}
 
-    #[bench]
-    fn add_bench(b: &mut Bencher) {
-        detect_features();
+#[test]
+fn soft_clip_equivalence_test() {
+    use crate::dsp::soft_clip::fallback;
 
-        let mut block1 = PlanarBlock::<f32>::default();
-        let mut block2 = PlanarBlock::<f32>::default();
+    for seed in 0..8 {
+        let mut expected = sweep_block(seed);
+        fallback(&mut expected);
 
-        for channel in 0..CHANNELS_COUNT {
-            for i in 0..BLOCK_SIZE {
-                block1.samples[channel][i] = i as f32 + channel as f32;
-                block2.samples[channel][i] = (i as f32 + channel as f32) * 2.0;
+        let variants = for_each_variant!(
+            soft_clip,
+            block,
+            |f: unsafe fn(&mut PlanarBlock<f32>), block: &mut PlanarBlock<f32>| {
+                block.copy_from(&sweep_block(seed));
+                unsafe { f(block) }
             }
+        );
+        for (variant, actual) in &variants {
+            assert_same(actual, &expected, variant);
         }
-
-        b.iter(|| {
-            block1.add(&block2);
-        });
     }
+}
 
-    fn addm_test() {
-        let mut block1 = PlanarBlock::<f32>::default();
-        let mut block2 = PlanarBlock::<f32>::default();
+#[test]
+fn soft_clip_approximation_test() {
+    let mut block = sweep_block(3);
+    let input = sweep_block(3);
+    block.soft_clip();
 
-        for channel in 0..CHANNELS_COUNT {
-            for i in 0..BLOCK_SIZE {
-                block1.samples[channel][i] = i as f32 + channel as f32;
-                block2.samples[channel][i] = (i as f32 + channel as f32) * 3.0;
-            }
+    for channel in 0..CHANNELS_COUNT {
+        for i in 0..BLOCK_SIZE {
+            let expected = (input.samples[channel][i] * 1.5).tanh();
+            let actual = block.samples[channel][i];
+            // Reaches 1 at the clamped input, up to the rounding
+            assert!(actual.abs() <= 1.0 + f32::EPSILON);
+            assert!(
+                (actual - expected).abs() < 0.025,
+                "{} is too far from {}",
+                actual,
+                expected
+            );
         }
+    }
+}
 
-        block1.addm(&block2, 0.5);
-        for channel in 0..CHANNELS_COUNT {
-            for i in 0..BLOCK_SIZE {
-                assert_eq!(
-                    block1.samples[channel][i],
-                    (i as f32 + channel as f32) + (i as f32 + channel as f32) * 3.0 * 0.5
-                );
+#[test]
+fn dot_equivalence_test() {
+    use crate::dsp::fir::fallback;
+
+    // Lengths not multiple of the vector width cover the scalar remainder
+    for len in [0, 1, 3, 4, 7, 15, 16, 17, 31, 32, 33, 64, 100] {
+        let a: Vec<f32> = (0..len).map(|i| (i as f32 * 0.13).cos()).collect();
+        let b: Vec<f32> = (0..len).map(|i| (i as f32 * 0.71).sin()).collect();
+        let expected = fallback(&a, &b);
+
+        let mut variants: Vec<(&str, f32)> = Vec::new();
+        #[cfg(target_arch = "x86_64")]
+        {
+            use crate::dsp::fir::*;
+            use std::arch::is_x86_feature_detected;
+            if is_x86_feature_detected!("avx512f") {
+                variants.push(("avx512", unsafe { avx512_dot(&a, &b) }));
+            }
+            if is_x86_feature_detected!("avx") {
+                variants.push(("avx", unsafe { avx_dot(&a, &b) }));
+            }
+            if is_x86_feature_detected!("sse4.2") {
+                variants.push(("sse42", unsafe { sse42_dot(&a, &b) }));
             }
         }
+        #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+        {
+            use crate::dsp::fir::*;
+            use std::arch::is_aarch64_feature_detected;
+            if is_aarch64_feature_detected!("neon") {
+                variants.push(("neon", unsafe { neon_dot(&a, &b) }));
+            }
+        }
+
+        for (variant, actual) in variants {
+            // The lanes are summed in a different order
+            assert!(
+                (actual - expected).abs() <= 1e-5 * (len as f32 + 1.0),
+                "{}: {} != {} for length {}",
+                variant,
+                actual,
+                expected,
+                len
+            );
+        }
     }
+}
 
-    #[test]
-    fn addm_test_simd() {
-        detect_features();
-        addm_test();
+fn gain_pan_test() {
+    let mut block = sweep_block(1);
+    let input = sweep_block(1);
+    block.gain_pan(0.5, 0.5);
+
+    let left = 0.5 * 0.5f32.sqrt();
+    let right = 0.5 * 1.5f32.sqrt();
+    for i in 0..BLOCK_SIZE {
+        assert_eq!(
+            block.samples[LEFT_CHANNEL][i],
+            input.samples[LEFT_CHANNEL][i] * left
+        );
+        assert_eq!(
+            block.samples[RIGHT_CHANNEL][i],
+            input.samples[RIGHT_CHANNEL][i] * right
+        );
     }
+}
+
+#[test]
+fn gain_pan_test_simd() {
+    detect_features();
+    gain_pan_test();
+}
+
+#[test]
+fn gain_pan_test_fallback() {
+    disable_all_features();
+    gain_pan_test();
+}
+
+#[test]
+fn dispatch_feature_combinations_test() {
+    use crate::dsp::features;
+
+    // Every combination of the flags, the dispatch must pick
+    // the best enabled variant, all giving the same result
+    let mut expected = sweep_block(2);
+    crate::dsp::soft_clip::fallback(&mut expected);
+
+    detect_features();
+    #[cfg(target_arch = "x86_64")]
+    let flags = [
+        &features::X86_HAS_AVX512,
+        &features::X86_HAS_AVX2,
+        &features::X86_HAS_AVX,
+        &features::X86_HAS_SSE42,
+    ];
+    #[cfg(any(target_arch = "aarch64", target_arch = "arm64ec"))]
+    let flags = [&features::ARM_HAS_NEON];
+    let supported: Vec<bool> = flags.iter().map(|flag| flag.get()).collect();
+
+    for mask in 0..1usize << flags.len() {
+        for (i, flag) in flags.iter().enumerate() {
+            // Never enable the flag the CPU does not support
+            flag.set(supported[i] && mask & (1 << i) != 0);
+        }
+
+        let mut block = sweep_block(2);
+        block.soft_clip();
+        assert_same(&block, &expected, &format!("mask {:b}", mask));
+
+        let mut block = sweep_block(2);
+        block.gain_pan(0.8, -0.3);
+        let mut expected_gain = sweep_block(2);
+        crate::dsp::gain_pan::fallback(
+            &mut expected_gain,
+            0.8 * 1.3f32.sqrt(),
+            0.8 * 0.7f32.sqrt(),
+        );
+        assert_same(&block, &expected_gain, &format!("mask {:b}", mask));
 
-    #[test]
-    fn addm_test_fallback() {
-        disable_all_features();
-        addm_test();
+        let mut block = sweep_block(2);
+        block.addm(&sweep_block(3), 0.5);
+        let mut expected_addm = sweep_block(2);
+        crate::dsp::addm::fallback(&sweep_block(3), &mut expected_addm, 0.5);
+        assert_same(&block, &expected_addm, &format!("mask {:b}", mask));
     }
 }
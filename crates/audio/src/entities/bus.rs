@@ -67,10 +67,8 @@ where
         targets
     }
 
-    fn dispatch(&mut self, event: &AudioEventType) {
-        match event {
-            _ => {}
-        }
+    fn dispatch(&mut self, _event: &AudioEventType) {
+        {}
     }
 
     #[inline(always)]
@@ -122,7 +120,7 @@ mod tests {
         let source = TestSource::new();
         let mut bus = Bus::new(effect, source, None, None);
 
-        for i in 0..10 {
+        for _i in 0..10 {
             bus.frame_start();
             let info = BlockInfo::new(0, 44_100);
             let output = bus.render(&info);
@@ -144,7 +142,7 @@ mod tests {
         let source = TestSource::new();
         let mut bus = Bus::new(effect, source, Some(0.5), None);
 
-        for i in 0..10 {
+        for _i in 0..10 {
             bus.frame_start();
             let info = BlockInfo::new(0, 44_100);
             let output = bus.render(&info);
@@ -13,6 +13,12 @@ impl Effect for BypassEffect {
     }
 }
 
+impl Default for BypassEffect {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl BypassEffect {
     pub fn new() -> Self {
         BypassEffect {}
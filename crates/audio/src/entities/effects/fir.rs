@@ -1,7 +1,7 @@
+use crate::dsp::dot;
 use crate::entities::events::{AudioEventTarget, AudioEventTargetId, AudioEventType};
 use crate::entities::{BlockInfo, Effect};
 use crate::sample::{PlanarBlock, LEFT_CHANNEL, RIGHT_CHANNEL};
-use crate::BLOCK_SIZE;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum FirFilterEffectEvent {
@@ -12,8 +12,11 @@ pub struct FirFilterEffect<const N: usize> {
     id: AudioEventTargetId,
     bypass: bool,
 
-    coeffs: [f32; N],
-    buffer: [f32; N],
+    /// Reversed, so the convolution is a dot product with the history.
+    taps: [f32; N],
+    /// The last N samples, written twice, so any window of N samples
+    /// ending at the current one is contiguous.
+    history: Box<[f32]>,
     pos: usize,
 }
 
@@ -31,7 +34,7 @@ impl<const N: usize> FirFilterEffect<N> {
         let norm_cutoff = f_c / sample_rate;
         let mut coeffs = [0.0; N];
 
-        for i in 0..N {
+        for (i, coeff) in coeffs.iter_mut().enumerate() {
             let n = i as f32 - (N as f32 - 1.0) / 2.0;
             let sinc = if n == 0.0 {
                 1.0
@@ -43,24 +46,25 @@ impl<const N: usize> FirFilterEffect<N> {
             let window =
                 0.54 - 0.46 * ((2.0 * std::f32::consts::PI * i as f32) / (N as f32 - 1.0)).cos();
 
-            coeffs[i] = 2.0 * norm_cutoff * sinc * window;
+            *coeff = 2.0 * norm_cutoff * sinc * window;
         }
 
         // Normalize coefficients
         let sum: f32 = coeffs.iter().sum();
-        for i in 0..N {
-            coeffs[i] /= sum;
+        for coeff in coeffs.iter_mut() {
+            *coeff /= sum;
         }
 
         FirFilterEffect::new(coeffs)
     }
 
-    pub fn new(coeffs: [f32; N]) -> Self {
+    pub fn new(mut coeffs: [f32; N]) -> Self {
+        coeffs.reverse();
         Self {
             id: AudioEventTargetId::new(),
             bypass: false,
-            coeffs,
-            buffer: [0.0; N],
+            taps: coeffs,
+            history: vec![0.0; N * 2].into_boxed_slice(),
             pos: 0,
         }
     }
@@ -101,16 +105,14 @@ impl<const N: usize> Effect for FirFilterEffect<N> {
         info: &BlockInfo,
     ) {
         // TODO: Support of multi-channel processing
-        for i in 0..BLOCK_SIZE {
+        for i in 0..info.len() {
             let sample = input.samples[LEFT_CHANNEL][i];
 
-            // TODO: Add support for SIMD processing
-            self.buffer[self.pos] = sample;
-            let mut acc = 0.0;
-            for i in 0..N {
-                let index = (self.pos + N - i) % N;
-                acc += self.buffer[index] * self.coeffs[i];
-            }
+            self.history[self.pos] = sample;
+            self.history[self.pos + N] = sample;
+            // The oldest sample first, matching the reversed taps
+            let window = &self.history[self.pos + 1..self.pos + 1 + N];
+            let acc = dot(&self.taps, window);
             self.pos = (self.pos + 1) % N;
 
             output.samples[LEFT_CHANNEL][i] = acc;
@@ -118,3 +120,31 @@ impl<const N: usize> Effect for FirFilterEffect<N> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::testkit::*;
+    use crate::BLOCK_SIZE;
+
+    fn low_pass() -> FirFilterEffect<64> {
+        FirFilterEffect::new_from_design(4000.0, SAMPLE_RATE as f32)
+    }
+
+    #[test]
+    fn low_pass_response() {
+        let [response, _] = render(&mut low_pass(), &impulse(4096), &[BLOCK_SIZE]);
+        assert_magnitude_response(&response, |frequency| match frequency {
+            f if f < 1500.0 => Some(-0.1..=0.1),
+            f if f > 7000.0 => Some(f32::NEG_INFINITY..=-45.0),
+            _ => None,
+        });
+    }
+
+    #[test]
+    fn low_pass_is_block_independent() {
+        let signal = noise(BLOCK_SIZE * 4, 0.5, 7);
+        assert_block_independent(low_pass, &signal, 0.0);
+        assert_simd_matches(low_pass, &signal, 1e-5);
+    }
+}
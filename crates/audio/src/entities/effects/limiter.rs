@@ -0,0 +1,423 @@
+use crate::entities::events::{AudioEventTarget, AudioEventTargetId, AudioEventType};
+use crate::entities::{BlockInfo, Effect};
+use crate::sample::PlanarBlock;
+use crate::{SampleRate, SamplesCount, CHANNELS_COUNT};
+use dawn_util::profile::MonitorSample;
+use evenio::event::GlobalEvent;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Oversampling factor of the true-peak estimation.
+const OVERSAMPLING: usize = 4;
+/// Taps of each phase of the interpolator. The detector delays the signal by the half of it.
+const PHASE_TAPS: usize = 12;
+/// How long before the peak the gain starts going down.
+const LOOKAHEAD: Duration = Duration::from_millis(2);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LimiterEffectEvent {
+    Bypass(bool),
+    /// Highest true-peak level of the output in dBTP.
+    SetCeiling(f32),
+    /// Time the gain takes to recover after the peak.
+    SetRelease(Duration),
+}
+
+/// Gain reduction of the limiter, sent every tick while the limiter is rendered
+/// (see `Player::monitor_limiter`).
+#[derive(GlobalEvent, Debug, Clone)]
+pub struct LimiterMonitorEvent {
+    /// Gain reduction in dB (positive) over the rendered blocks.
+    pub gain_reduction: MonitorSample<f32>,
+    /// Highest true-peak level of the input in dBTP.
+    pub true_peak: f32,
+}
+
+struct MeterState {
+    // The non-negative floats are ordered as their bits,
+    // so they are updated with the integer atomics
+    min_reduction: AtomicU32,
+    max_reduction: AtomicU32,
+    peak: AtomicU32,
+    // In the thousandths of dB
+    reduction_sum: AtomicU64,
+    blocks: AtomicU64,
+}
+
+/// Shared handle to the meter of the limiter, written by the audio thread.
+#[derive(Clone)]
+pub struct LimiterMeter(Arc<MeterState>);
+
+impl LimiterMeter {
+    fn new() -> Self {
+        LimiterMeter(Arc::new(MeterState {
+            min_reduction: AtomicU32::new(f32::MAX.to_bits()),
+            max_reduction: AtomicU32::new(0),
+            peak: AtomicU32::new(0),
+            reduction_sum: AtomicU64::new(0),
+            blocks: AtomicU64::new(0),
+        }))
+    }
+
+    fn record(&self, min_reduction: f32, max_reduction: f32, average: f32, peak: f32) {
+        let state = &self.0;
+        state
+            .min_reduction
+            .fetch_min(min_reduction.to_bits(), Ordering::Relaxed);
+        state
+            .max_reduction
+            .fetch_max(max_reduction.to_bits(), Ordering::Relaxed);
+        state.peak.fetch_max(peak.to_bits(), Ordering::Relaxed);
+        state
+            .reduction_sum
+            .fetch_add((average * 1000.0) as u64, Ordering::Relaxed);
+        state.blocks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Takes the values recorded since the last call.
+    /// `None` if nothing was rendered.
+    pub fn take(&self) -> Option<LimiterMonitorEvent> {
+        let state = &self.0;
+        let blocks = state.blocks.swap(0, Ordering::Relaxed);
+        let sum = state.reduction_sum.swap(0, Ordering::Relaxed);
+        let min = state
+            .min_reduction
+            .swap(f32::MAX.to_bits(), Ordering::Relaxed);
+        let max = state.max_reduction.swap(0, Ordering::Relaxed);
+        let peak = state.peak.swap(0, Ordering::Relaxed);
+        if blocks == 0 {
+            return None;
+        }
+
+        Some(LimiterMonitorEvent {
+            gain_reduction: MonitorSample::new(
+                f32::from_bits(min),
+                sum as f32 / 1000.0 / blocks as f32,
+                f32::from_bits(max),
+            ),
+            true_peak: 20.0 * f32::from_bits(peak).max(f32::MIN_POSITIVE).log10(),
+        })
+    }
+}
+
+fn dispatch_limiter(ptr: *mut u8, event: &AudioEventType) {
+    let limiter: &mut LimiterEffect = unsafe { &mut *(ptr as *mut LimiterEffect) };
+    limiter.dispatch(event);
+}
+
+/// Lookahead brickwall limiter for the master bus.
+/// The peaks between the samples are estimated by oversampling, and the gain
+/// is lowered smoothly before them, so the output stays under the ceiling
+/// however many voices are mixed in. Delays the signal by `latency` samples.
+pub struct LimiterEffect {
+    id: AudioEventTargetId,
+    bypass: bool,
+    sample_rate: SampleRate,
+    ceiling: f32,
+    release: f32,
+    meter: LimiterMeter,
+
+    // Interpolator taps, for each phase between the samples
+    phases: [[f32; PHASE_TAPS]; OVERSAMPLING - 1],
+    history: [[f32; PHASE_TAPS]; CHANNELS_COUNT],
+    // Peak between the previous sample and the center of the history
+    previous_peak: f32,
+
+    lookahead: usize,
+    // Sliding minimum of the gain over the lookahead: (index, gain)
+    hold: VecDeque<(usize, f32)>,
+    released: f32,
+    // Moving average of the released gain over the lookahead
+    window: Box<[f32]>,
+    window_sum: f64,
+    delay: [Box<[f32]>; CHANNELS_COUNT],
+    index: usize,
+}
+
+impl LimiterEffect {
+    /// Creates the limiter with the ceiling in dBTP (e.g. -1.0).
+    pub fn new(sample_rate: SampleRate, ceiling: f32, release: Duration) -> Self {
+        let lookahead = ((LOOKAHEAD.as_secs_f32() * sample_rate as f32) as usize).max(1);
+        let latency = PHASE_TAPS / 2 + lookahead - 1;
+
+        let mut limiter = LimiterEffect {
+            id: AudioEventTargetId::new(),
+            bypass: false,
+            sample_rate,
+            ceiling: 1.0,
+            release: 0.0,
+            meter: LimiterMeter::new(),
+            phases: Self::design_phases(),
+            history: [[0.0; PHASE_TAPS]; CHANNELS_COUNT],
+            previous_peak: 0.0,
+            lookahead,
+            hold: VecDeque::with_capacity(lookahead + 1),
+            released: 1.0,
+            window: vec![1.0; lookahead].into_boxed_slice(),
+            window_sum: lookahead as f64,
+            delay: std::array::from_fn(|_| vec![0.0; latency].into_boxed_slice()),
+            index: 0,
+        };
+        limiter.set_ceiling(ceiling);
+        limiter.set_release(release);
+        limiter
+    }
+
+    /// Hann windowed sinc, interpolating at the fractions of the sample
+    /// after the center of the history.
+    fn design_phases() -> [[f32; PHASE_TAPS]; OVERSAMPLING - 1] {
+        let center = (PHASE_TAPS / 2 - 1) as f32;
+        let half = (PHASE_TAPS / 2) as f32;
+        std::array::from_fn(|phase| {
+            let fraction = (phase + 1) as f32 / OVERSAMPLING as f32;
+            let mut taps: [f32; PHASE_TAPS] = std::array::from_fn(|k| {
+                let t = k as f32 - center - fraction;
+                let sinc = (std::f32::consts::PI * t).sin() / (std::f32::consts::PI * t);
+                let window = 0.5 + 0.5 * (std::f32::consts::PI * t / half).cos();
+                sinc * window
+            });
+            // Unity gain for DC
+            let sum: f32 = taps.iter().sum();
+            taps.iter_mut().for_each(|tap| *tap /= sum);
+            taps
+        })
+    }
+
+    fn set_ceiling(&mut self, ceiling: f32) {
+        self.ceiling = 10.0f32.powf(ceiling.min(0.0) / 20.0);
+    }
+
+    fn set_release(&mut self, release: Duration) {
+        let samples = release.as_secs_f32() * self.sample_rate as f32;
+        self.release = 1.0 - (-1.0 / samples.max(1.0)).exp();
+    }
+
+    pub fn get_id(&self) -> AudioEventTargetId {
+        self.id
+    }
+
+    /// Delay of the output in samples.
+    pub fn latency(&self) -> SamplesCount {
+        self.delay[0].len()
+    }
+
+    /// Meter to pass to `Player::monitor_limiter`.
+    pub fn meter(&self) -> LimiterMeter {
+        self.meter.clone()
+    }
+
+    fn create_event_target(&self) -> AudioEventTarget {
+        AudioEventTarget::new(dispatch_limiter, self.id, self)
+    }
+
+    /// Pushes the frame into the detector, returns the true peak
+    /// of the sample in the center of the history.
+    fn detect(&mut self, frame: [f32; CHANNELS_COUNT]) -> f32 {
+        let mut center = 0.0f32;
+        let mut next = 0.0f32;
+        for (history, sample) in self.history.iter_mut().zip(frame) {
+            history.copy_within(1.., 0);
+            history[PHASE_TAPS - 1] = sample;
+
+            center = center.max(history[PHASE_TAPS / 2 - 1].abs());
+            for taps in &self.phases {
+                let value: f32 = taps.iter().zip(history.iter()).map(|(t, h)| t * h).sum();
+                next = next.max(value.abs());
+            }
+        }
+
+        // The peaks on the both sides of the sample
+        let peak = center.max(next).max(self.previous_peak);
+        self.previous_peak = next;
+        peak
+    }
+
+    /// Gain applied to the sample leaving the lookahead.
+    fn gain(&mut self, peak: f32) -> f32 {
+        let target = if peak > self.ceiling {
+            self.ceiling / peak
+        } else {
+            1.0
+        };
+
+        let index = self.index;
+        while self.hold.back().is_some_and(|(_, gain)| *gain >= target) {
+            self.hold.pop_back();
+        }
+        self.hold.push_back((index, target));
+        while self
+            .hold
+            .front()
+            .is_some_and(|(i, _)| *i + self.lookahead <= index)
+        {
+            self.hold.pop_front();
+        }
+        let held = self.hold.front().map_or(1.0, |(_, gain)| *gain);
+
+        // Instant attack, the lookahead smooths it
+        self.released = if held < self.released {
+            held
+        } else {
+            self.released + (held - self.released) * self.release
+        };
+
+        // The average of the window is not above any gain held in it,
+        // including the one of the sample leaving the lookahead
+        let slot = index % self.lookahead;
+        self.window_sum += (self.released - self.window[slot]) as f64;
+        self.window[slot] = self.released;
+        (self.window_sum / self.lookahead as f64) as f32
+    }
+}
+
+impl Effect for LimiterEffect {
+    fn get_targets(&self) -> Vec<AudioEventTarget> {
+        vec![self.create_event_target()]
+    }
+
+    fn dispatch(&mut self, event: &AudioEventType) {
+        match event {
+            AudioEventType::Limiter(LimiterEffectEvent::Bypass(bypass)) => self.bypass = *bypass,
+            AudioEventType::Limiter(LimiterEffectEvent::SetCeiling(ceiling)) => {
+                self.set_ceiling(*ceiling)
+            }
+            AudioEventType::Limiter(LimiterEffectEvent::SetRelease(release)) => {
+                self.set_release(*release)
+            }
+            _ => {}
+        }
+    }
+
+    fn bypass(&self) -> bool {
+        self.bypass
+    }
+
+    #[allow(clippy::needless_range_loop)]
+    fn render(
+        &mut self,
+        input: &PlanarBlock<f32>,
+        output: &mut PlanarBlock<f32>,
+        info: &BlockInfo,
+    ) {
+        let mut min_gain = 1.0f32;
+        let mut max_gain = 0.0f32;
+        let mut gain_sum = 0.0f32;
+        let mut max_peak = 0.0f32;
+        let len = self.delay[0].len();
+
+        for i in 0..info.len() {
+            let frame: [f32; CHANNELS_COUNT] = std::array::from_fn(|c| input.samples[c][i]);
+            let peak = self.detect(frame);
+            let gain = self.gain(peak);
+
+            let slot = self.index % len;
+            for channel in 0..CHANNELS_COUNT {
+                let delayed = std::mem::replace(&mut self.delay[channel][slot], frame[channel]);
+                // Catches the rounding errors of the gain
+                output.samples[channel][i] = (delayed * gain).clamp(-self.ceiling, self.ceiling);
+            }
+            self.index += 1;
+
+            min_gain = min_gain.min(gain);
+            max_gain = max_gain.max(gain);
+            gain_sum += gain;
+            max_peak = max_peak.max(peak);
+        }
+
+        if info.len() > 0 {
+            let db = |gain: f32| -20.0 * gain.max(f32::MIN_POSITIVE).log10();
+            self.meter.record(
+                db(max_gain).max(0.0),
+                db(min_gain).max(0.0),
+                db(gain_sum / info.len() as f32).max(0.0),
+                max_peak,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BLOCK_SIZE;
+
+    fn render(limiter: &mut LimiterEffect, signal: impl Fn(usize) -> f32, blocks: usize) -> f32 {
+        let mut input = PlanarBlock::default();
+        let mut output = PlanarBlock::default();
+        let mut max = 0.0f32;
+        for block in 0..blocks {
+            for channel in 0..CHANNELS_COUNT {
+                for i in 0..BLOCK_SIZE {
+                    input.samples[channel][i] = signal(block * BLOCK_SIZE + i);
+                }
+            }
+            limiter.render(
+                &input,
+                &mut output,
+                &BlockInfo::new(block * BLOCK_SIZE, 48000),
+            );
+            for channel in 0..CHANNELS_COUNT {
+                for sample in output.samples[channel].iter() {
+                    max = max.max(sample.abs());
+                }
+            }
+        }
+        max
+    }
+
+    #[test]
+    fn limits_stacked_voices() {
+        let mut limiter = LimiterEffect::new(48000, -1.0, Duration::from_millis(50));
+        let meter = limiter.meter();
+
+        // Eight voices in phase, with the peaks between the samples
+        let signal = |i: usize| 8.0 * (i as f32 * std::f32::consts::FRAC_PI_2 + 0.785).sin();
+        let max = render(&mut limiter, signal, 8);
+        assert!(
+            max <= 10.0f32.powf(-1.0 / 20.0),
+            "{} is above the ceiling",
+            max
+        );
+
+        let event = meter.take().unwrap();
+        assert!(event.gain_reduction.max() > 18.0);
+        // The true peak is above all the samples of the signal (8 * sin(45°))
+        assert!(event.true_peak > 20.0 * (8.0f32 * 0.71).log10());
+        assert!(meter.take().is_none());
+    }
+
+    #[test]
+    fn passes_quiet_signal() {
+        let mut limiter = LimiterEffect::new(48000, -1.0, Duration::from_millis(50));
+        let latency = limiter.latency();
+
+        let signal = |i: usize| 0.25 * (i as f32 * 0.01).sin();
+        let mut input = PlanarBlock::default();
+        let mut output = PlanarBlock::default();
+        for channel in 0..CHANNELS_COUNT {
+            for i in 0..BLOCK_SIZE {
+                input.samples[channel][i] = signal(i);
+            }
+        }
+        limiter.render(&input, &mut output, &BlockInfo::new(0, 48000));
+
+        for i in latency..BLOCK_SIZE {
+            assert_eq!(output.samples[0][i], signal(i - latency));
+        }
+        assert_eq!(limiter.meter().take().unwrap().gain_reduction.max(), 0.0);
+    }
+
+    #[test]
+    fn limiting_is_block_independent() {
+        use crate::entities::testkit::*;
+
+        let make = || LimiterEffect::new(SAMPLE_RATE, -1.0, Duration::from_millis(50));
+        let signal = chirp(50.0, 2000.0, BLOCK_SIZE * 8, 4.0);
+        let [output, _] = render(&mut make(), &signal, &[BLOCK_SIZE]);
+        assert_eq!(boundary_artifacts(&output, 4.0), Vec::<usize>::new());
+        assert_block_independent(make, &signal, 0.0);
+        assert_block_independent(make, &noise(BLOCK_SIZE * 4, 2.0, 3), 0.0);
+    }
+}
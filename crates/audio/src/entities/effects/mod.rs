@@ -1,10 +1,16 @@
 pub mod bypass;
 pub mod fir;
-pub mod freeverb;
+pub mod limiter;
 pub mod multiplexer;
+pub mod reverb;
 pub mod soft_clip;
 
 #[cfg(test)]
+pub(crate) use test::*;
+
+// Shared fixture for the effect tests, not every part of it is used yet.
+#[cfg(test)]
+#[allow(dead_code)]
 mod test {
     use crate::entities::events::{AudioEventTarget, AudioEventTargetId, AudioEventType};
     use crate::entities::{BlockInfo, Effect};
@@ -92,7 +98,7 @@ mod test {
             &mut self,
             input: &PlanarBlock<f32>,
             output: &mut PlanarBlock<f32>,
-            info: &BlockInfo,
+            _info: &BlockInfo,
         ) {
             for channel in 0..CHANNELS_COUNT {
                 for index in 0..BLOCK_SIZE {
@@ -103,6 +109,3 @@ mod test {
         }
     }
 }
-
-#[cfg(test)]
-pub(crate) use test::*;
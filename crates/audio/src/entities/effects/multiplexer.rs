@@ -1,5 +1,6 @@
 use crate::entities::events::{AudioEventTarget, AudioEventTargetId, AudioEventType};
-use crate::entities::{Effect, NodeCell};
+use crate::entities::{BlockInfo, Effect, NodeCell};
+use crate::pool::BlockPool;
 use crate::sample::PlanarBlock;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -8,13 +9,44 @@ pub enum MultiplexerEffectEvent {
     SetDryWet(usize, f32),
 }
 
+/// Renders the effect into the block, or copies the input if the effect is bypassed.
+#[inline(always)]
+fn render_effect<E: Effect>(
+    effect: &NodeCell<E>,
+    input: &PlanarBlock<f32>,
+    block: &mut PlanarBlock<f32>,
+    info: &BlockInfo,
+) {
+    let effect = effect.as_mut();
+    if effect.bypass() {
+        block.copy_from(input);
+    } else {
+        effect.render(input, block, info);
+    }
+}
+
+/// Adds one of the `count` parallel effects to the output, mixed with the dry input.
+/// The effects are averaged, so the unity gain is kept.
+#[inline(always)]
+fn mix(
+    output: &mut PlanarBlock<f32>,
+    input: &PlanarBlock<f32>,
+    block: &PlanarBlock<f32>,
+    wet: f32,
+    count: usize,
+) {
+    let k = 1.0 / count as f32;
+    output.addm(input, (1.0 - wet) * k);
+    output.addm(block, wet * k);
+}
+
 /// Multiplexer for 1 effect (with the same type)
 pub struct Multiplexer1Effect<T1: Effect> {
     id: AudioEventTargetId,
     bypass: bool,
     effect1: NodeCell<T1>,
     wet1: f32,
-    output: PlanarBlock<f32>,
+    scratch: BlockPool<1>,
 }
 
 /// Multiplexer for 2 effects (with different types)
@@ -25,7 +57,7 @@ pub struct Multiplexer2Effect<T1: Effect, T2: Effect> {
     effect2: NodeCell<T2>,
     wet1: f32,
     wet2: f32,
-    output: PlanarBlock<f32>,
+    scratch: BlockPool<2>,
 }
 
 /// Multiplexer for 3 effects (with different types)
@@ -38,7 +70,7 @@ pub struct Multiplexer3Effect<T1: Effect, T2: Effect, T3: Effect> {
     wet1: f32,
     wet2: f32,
     wet3: f32,
-    output: PlanarBlock<f32>,
+    scratch: BlockPool<3>,
 }
 
 /// Multiplexer for 4 effects (with different types)
@@ -53,17 +85,19 @@ pub struct Multiplexer4Effect<T1: Effect, T2: Effect, T3: Effect, T4: Effect> {
     wet2: f32,
     wet3: f32,
     wet4: f32,
-    output: PlanarBlock<f32>,
+    scratch: BlockPool<4>,
 }
 
 /// Multiplexer for N effects, where N is a compile-time constant
-/// Note that all effects must have the same type `T`
+/// Note that all effects must have the same type `T`.
+/// The effects are applied to the same input in parallel, each mixed
+/// with the dry input by its wet level, and averaged.
 pub struct MultiplexerEffect<T: Effect, const N: usize> {
     bypass: bool,
     id: AudioEventTargetId,
     effects: [NodeCell<T>; N],
     wet: [f32; N],
-    output: PlanarBlock<f32>,
+    scratch: BlockPool<N>,
 }
 
 fn dispatch_multiplexer<T: Effect, const N: usize>(ptr: *mut u8, event: &AudioEventType) {
@@ -80,7 +114,7 @@ impl<T: Effect, const N: usize> MultiplexerEffect<T, N> {
             id: AudioEventTargetId::new(),
             effects: effects_refs,
             wet: [1.0; N],
-            output: PlanarBlock::default(),
+            scratch: BlockPool::new(),
         }
     }
 
@@ -103,10 +137,10 @@ impl<T: Effect, const N: usize> Effect for MultiplexerEffect<T, N> {
             AudioEventType::MuxEffect(MultiplexerEffectEvent::Bypass(bypass)) => {
                 self.bypass = *bypass;
             }
-            AudioEventType::MuxEffect(MultiplexerEffectEvent::SetDryWet(index, wet)) => {
-                if *index < N {
-                    self.wet[*index] = wet.clamp(0.0, 1.0);
-                }
+            AudioEventType::MuxEffect(MultiplexerEffectEvent::SetDryWet(index, wet))
+                if *index < N =>
+            {
+                self.wet[*index] = wet.clamp(0.0, 1.0);
             }
             _ => {}
         }
@@ -120,9 +154,16 @@ impl<T: Effect, const N: usize> Effect for MultiplexerEffect<T, N> {
         &mut self,
         input: &PlanarBlock<f32>,
         output: &mut PlanarBlock<f32>,
-        info: &crate::entities::BlockInfo,
+        info: &BlockInfo,
     ) {
-        todo!()
+        let blocks = self.scratch.take::<N>();
+        for (effect, block) in self.effects.iter().zip(blocks.iter_mut()) {
+            render_effect(effect, input, block, info);
+        }
+        output.silence();
+        for (wet, block) in self.wet.iter().zip(blocks.iter()) {
+            mix(output, input, block, *wet, N);
+        }
     }
 }
 
@@ -139,7 +180,7 @@ impl<T1: Effect> Multiplexer1Effect<T1> {
             bypass: false,
             effect1: NodeCell::new(effect1),
             wet1: 1.0,
-            output: PlanarBlock::default(),
+            scratch: BlockPool::new(),
         }
     }
 
@@ -177,9 +218,12 @@ impl<T1: Effect> Effect for Multiplexer1Effect<T1> {
         &mut self,
         input: &PlanarBlock<f32>,
         output: &mut PlanarBlock<f32>,
-        info: &crate::entities::BlockInfo,
+        info: &BlockInfo,
     ) {
-        todo!()
+        let [block1] = self.scratch.take::<1>();
+        render_effect(&self.effect1, input, block1, info);
+        output.silence();
+        mix(output, input, block1, self.wet1, 1);
     }
 }
 
@@ -198,7 +242,7 @@ impl<T1: Effect, T2: Effect> Multiplexer2Effect<T1, T2> {
             effect2: NodeCell::new(effect2),
             wet1: 1.0,
             wet2: 1.0,
-            output: PlanarBlock::default(),
+            scratch: BlockPool::new(),
         }
     }
 
@@ -239,9 +283,14 @@ impl<T1: Effect, T2: Effect> Effect for Multiplexer2Effect<T1, T2> {
         &mut self,
         input: &PlanarBlock<f32>,
         output: &mut PlanarBlock<f32>,
-        info: &crate::entities::BlockInfo,
+        info: &BlockInfo,
     ) {
-        todo!()
+        let [block1, block2] = self.scratch.take::<2>();
+        render_effect(&self.effect1, input, block1, info);
+        render_effect(&self.effect2, input, block2, info);
+        output.silence();
+        mix(output, input, block1, self.wet1, 2);
+        mix(output, input, block2, self.wet2, 2);
     }
 }
 
@@ -262,7 +311,7 @@ impl<T1: Effect, T2: Effect, T3: Effect> Multiplexer3Effect<T1, T2, T3> {
             wet1: 1.0,
             wet2: 1.0,
             wet3: 1.0,
-            output: PlanarBlock::default(),
+            scratch: BlockPool::new(),
         }
     }
 
@@ -306,9 +355,16 @@ impl<T1: Effect, T2: Effect, T3: Effect> Effect for Multiplexer3Effect<T1, T2, T
         &mut self,
         input: &PlanarBlock<f32>,
         output: &mut PlanarBlock<f32>,
-        info: &crate::entities::BlockInfo,
+        info: &BlockInfo,
     ) {
-        todo!()
+        let [block1, block2, block3] = self.scratch.take::<3>();
+        render_effect(&self.effect1, input, block1, info);
+        render_effect(&self.effect2, input, block2, info);
+        render_effect(&self.effect3, input, block3, info);
+        output.silence();
+        mix(output, input, block1, self.wet1, 3);
+        mix(output, input, block2, self.wet2, 3);
+        mix(output, input, block3, self.wet3, 3);
     }
 }
 
@@ -334,7 +390,7 @@ impl<T1: Effect, T2: Effect, T3: Effect, T4: Effect> Multiplexer4Effect<T1, T2,
             wet2: 1.0,
             wet3: 1.0,
             wet4: 1.0,
-            output: PlanarBlock::default(),
+            scratch: BlockPool::new(),
         }
     }
 
@@ -381,8 +437,61 @@ impl<T1: Effect, T2: Effect, T3: Effect, T4: Effect> Effect for Multiplexer4Effe
         &mut self,
         input: &PlanarBlock<f32>,
         output: &mut PlanarBlock<f32>,
-        info: &crate::entities::BlockInfo,
+        info: &BlockInfo,
     ) {
-        todo!()
+        let [block1, block2, block3, block4] = self.scratch.take::<4>();
+        render_effect(&self.effect1, input, block1, info);
+        render_effect(&self.effect2, input, block2, info);
+        render_effect(&self.effect3, input, block3, info);
+        render_effect(&self.effect4, input, block4, info);
+        output.silence();
+        mix(output, input, block1, self.wet1, 4);
+        mix(output, input, block2, self.wet2, 4);
+        mix(output, input, block3, self.wet3, 4);
+        mix(output, input, block4, self.wet4, 4);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::detect_features;
+    use crate::entities::effects::{TestEffect, TestEffectEvent, TestEffectFunction};
+    use crate::{BLOCK_SIZE, CHANNELS_COUNT};
+
+    #[test]
+    fn test_multiplexer2_mix() {
+        detect_features();
+
+        let mut multiplexer = Multiplexer2Effect::new(
+            TestEffect::new(TestEffectFunction::Constant(1.0)),
+            TestEffect::new(TestEffectFunction::Constant(3.0)),
+        );
+        multiplexer.dispatch(&AudioEventType::MuxEffect(
+            MultiplexerEffectEvent::SetDryWet(1, 0.5),
+        ));
+
+        let mut input = PlanarBlock::default();
+        for channel in 0..CHANNELS_COUNT {
+            input.samples[channel] = [2.0; BLOCK_SIZE];
+        }
+        let mut output = PlanarBlock::default();
+        let info = BlockInfo::new(0, 44_100);
+
+        // (1.0 + (2.0 + 3.0) / 2) / 2
+        multiplexer.render(&input, &mut output, &info);
+        for channel in 0..CHANNELS_COUNT {
+            assert!(output.samples[channel].iter().all(|s| *s == 1.75));
+        }
+
+        // Bypassed effect passes the input: (1.0 + 2.0) / 2
+        multiplexer
+            .effect2
+            .as_mut()
+            .dispatch(&AudioEventType::TestEffect(TestEffectEvent::Bypass(true)));
+        multiplexer.render(&input, &mut output, &info);
+        for channel in 0..CHANNELS_COUNT {
+            assert!(output.samples[channel].iter().all(|s| *s == 1.5));
+        }
     }
 }
@@ -0,0 +1,418 @@
+use crate::entities::events::{AudioEventTarget, AudioEventTargetId, AudioEventType};
+use crate::entities::{BlockInfo, Effect};
+use crate::sample::{PlanarBlock, LEFT_CHANNEL, RIGHT_CHANNEL};
+use crate::{SampleRate, BLOCK_SIZE, CHANNELS_COUNT};
+
+// Constants of the original Freeverb, the delays are in samples at 44.1 kHz
+const TUNING_SAMPLE_RATE: usize = 44100;
+const COMB_DELAYS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_DELAYS: [usize; 4] = [556, 441, 341, 225];
+/// Added to the delays of the right channel, so the channels are decorrelated.
+const STEREO_SPREAD: usize = 23;
+const ALLPASS_FEEDBACK: f32 = 0.5;
+const FIXED_GAIN: f32 = 0.015;
+const SCALE_WET: f32 = 3.0;
+const SCALE_DRY: f32 = 2.0;
+const SCALE_DAMP: f32 = 0.4;
+const SCALE_ROOM: f32 = 0.28;
+const OFFSET_ROOM: f32 = 0.7;
+// Flushes the decaying tail to zero before it becomes denormal
+const ANTI_DENORMAL: f32 = 1e-18;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BuiltInTuning {
+    TuningA,
+    TuningB,
+    TuningC,
+}
+
+impl BuiltInTuning {
+    fn apply(&self, reverb: &mut ReverbEffect) {
+        match self {
+            BuiltInTuning::TuningA => {
+                reverb.room_size = 0.5;
+                reverb.damping = 0.5;
+                reverb.wet_level = 0.33;
+                reverb.dry_level = 0.4;
+                reverb.width = 1.0;
+                reverb.freeze_mode = false;
+            }
+            BuiltInTuning::TuningB => {
+                reverb.room_size = 0.7;
+                reverb.damping = 0.3;
+                reverb.wet_level = 0.5;
+                reverb.dry_level = 0.5;
+                reverb.width = 0.8;
+                reverb.freeze_mode = true;
+            }
+            BuiltInTuning::TuningC => {
+                reverb.room_size = 0.9;
+                reverb.damping = 0.2;
+                reverb.wet_level = 0.7;
+                reverb.dry_level = 0.3;
+                reverb.width = 0.6;
+                reverb.freeze_mode = false;
+            }
+        }
+        reverb.update();
+    }
+}
+
+/// All the levels are in 0..=1, the values out of the range are clamped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReverbEffectEvent {
+    Bypass(bool),
+    SetRoomSize(f32),
+    SetDamping(f32),
+    SetWetLevel(f32),
+    SetDryLevel(f32),
+    /// Stereo width of the reverberation, zero for mono.
+    SetWidth(f32),
+    /// Holds the current reverberation infinitely, ignoring the input.
+    SetFreezeMode(bool),
+    SetBuiltInTuning(BuiltInTuning),
+}
+
+// Delay lines are processed in the runs up to their end, so each run reads
+// and writes the contiguous slices without wrapping the index per sample
+fn runs(pos: usize, delay: usize, len: usize) -> impl Iterator<Item = (usize, usize, usize)> {
+    let mut done = 0;
+    let mut pos = pos;
+    std::iter::from_fn(move || {
+        if done == len {
+            return None;
+        }
+        let run = (len - done).min(delay - pos);
+        let item = (done, pos, run);
+        done += run;
+        pos = (pos + run) % delay;
+        Some(item)
+    })
+}
+
+/// Feedback comb filter with the lowpass in the loop (the damping).
+struct Comb {
+    buffer: Box<[f32]>,
+    pos: usize,
+    filter: f32,
+}
+
+impl Comb {
+    fn new(delay: usize) -> Self {
+        Comb {
+            buffer: vec![0.0; delay].into_boxed_slice(),
+            pos: 0,
+            filter: 0.0,
+        }
+    }
+
+    /// Adds the response to the `input` into the `output`.
+    fn process(&mut self, input: &[f32], output: &mut [f32], feedback: f32, damping: f32) {
+        let delay = self.buffer.len();
+        for (start, pos, run) in runs(self.pos, delay, input.len()) {
+            let input = &input[start..start + run];
+            let output = &mut output[start..start + run];
+            let buffer = &mut self.buffer[pos..pos + run];
+            for ((x, y), delayed) in input.iter().zip(output).zip(buffer) {
+                let out = *delayed;
+                self.filter = out * (1.0 - damping) + self.filter * damping;
+                self.filter = (self.filter + ANTI_DENORMAL) - ANTI_DENORMAL;
+                *delayed = x + self.filter * feedback;
+                *y += out;
+            }
+        }
+        self.pos = (self.pos + input.len()) % delay;
+    }
+}
+
+/// Schroeder allpass diffusing the output of the combs.
+struct Allpass {
+    buffer: Box<[f32]>,
+    pos: usize,
+}
+
+impl Allpass {
+    fn new(delay: usize) -> Self {
+        Allpass {
+            buffer: vec![0.0; delay].into_boxed_slice(),
+            pos: 0,
+        }
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        let delay = self.buffer.len();
+        for (start, pos, run) in runs(self.pos, delay, samples.len()) {
+            let samples = &mut samples[start..start + run];
+            let buffer = &mut self.buffer[pos..pos + run];
+            for (x, delayed) in samples.iter_mut().zip(buffer) {
+                let out = *delayed;
+                *delayed = *x + out * ALLPASS_FEEDBACK;
+                *x = out - *x;
+            }
+        }
+        self.pos = (self.pos + samples.len()) % delay;
+    }
+}
+
+/// Filters of one channel.
+struct Tank {
+    combs: Vec<Comb>,
+    allpasses: Vec<Allpass>,
+}
+
+impl Tank {
+    fn new(sample_rate: SampleRate, spread: usize) -> Self {
+        let scale = |delay: usize| ((delay + spread) * sample_rate / TUNING_SAMPLE_RATE).max(1);
+        Tank {
+            combs: COMB_DELAYS.iter().map(|d| Comb::new(scale(*d))).collect(),
+            allpasses: ALLPASS_DELAYS
+                .iter()
+                .map(|d| Allpass::new(scale(*d)))
+                .collect(),
+        }
+    }
+}
+
+fn dispatch_reverb(ptr: *mut u8, event: &AudioEventType) {
+    let reverb: &mut ReverbEffect = unsafe { &mut *(ptr as *mut ReverbEffect) };
+    reverb.dispatch(event);
+}
+
+/// Freeverb: eight parallel combs followed by four allpasses per channel,
+/// fed with the mono sum of the input. The delays are scaled to the sample rate.
+/// The block is processed filter by filter over the contiguous runs of
+/// the delay lines, and the mixing loops over the whole block.
+pub struct ReverbEffect {
+    id: AudioEventTargetId,
+    bypass: bool,
+
+    room_size: f32,
+    damping: f32,
+    wet_level: f32,
+    dry_level: f32,
+    width: f32,
+    freeze_mode: bool,
+
+    // Derived from the parameters above by `update`
+    feedback: f32,
+    damp: f32,
+    input_gain: f32,
+    wet1: f32,
+    wet2: f32,
+    dry: f32,
+
+    tanks: [Tank; CHANNELS_COUNT],
+    mono: Box<[f32; BLOCK_SIZE]>,
+    wet: Box<PlanarBlock<f32>>,
+}
+
+impl ReverbEffect {
+    pub fn new(sample_rate: SampleRate, tuning: BuiltInTuning) -> Self {
+        let mut reverb = ReverbEffect {
+            id: AudioEventTargetId::new(),
+            bypass: false,
+            room_size: 0.0,
+            damping: 0.0,
+            wet_level: 0.0,
+            dry_level: 0.0,
+            width: 0.0,
+            freeze_mode: false,
+            feedback: 0.0,
+            damp: 0.0,
+            input_gain: 0.0,
+            wet1: 0.0,
+            wet2: 0.0,
+            dry: 0.0,
+            tanks: std::array::from_fn(|channel| Tank::new(sample_rate, channel * STEREO_SPREAD)),
+            mono: Box::new([0.0; BLOCK_SIZE]),
+            wet: Box::default(),
+        };
+        tuning.apply(&mut reverb);
+        reverb
+    }
+
+    pub fn get_id(&self) -> AudioEventTargetId {
+        self.id
+    }
+
+    fn create_event_target(&self) -> AudioEventTarget {
+        AudioEventTarget::new(dispatch_reverb, self.id, self)
+    }
+
+    fn update(&mut self) {
+        let wet = self.wet_level * SCALE_WET;
+        self.wet1 = wet * (self.width / 2.0 + 0.5);
+        self.wet2 = wet * ((1.0 - self.width) / 2.0);
+        self.dry = self.dry_level * SCALE_DRY;
+        if self.freeze_mode {
+            self.feedback = 1.0;
+            self.damp = 0.0;
+            self.input_gain = 0.0;
+        } else {
+            self.feedback = self.room_size * SCALE_ROOM + OFFSET_ROOM;
+            self.damp = self.damping * SCALE_DAMP;
+            self.input_gain = FIXED_GAIN;
+        }
+    }
+}
+
+impl Effect for ReverbEffect {
+    fn get_targets(&self) -> Vec<AudioEventTarget> {
+        vec![self.create_event_target()]
+    }
+
+    fn dispatch(&mut self, event: &AudioEventType) {
+        let AudioEventType::Reverb(event) = event else {
+            // Ignore other events
+            return;
+        };
+        match event {
+            ReverbEffectEvent::Bypass(bypass) => self.bypass = *bypass,
+            ReverbEffectEvent::SetRoomSize(size) => self.room_size = size.clamp(0.0, 1.0),
+            ReverbEffectEvent::SetDamping(damping) => self.damping = damping.clamp(0.0, 1.0),
+            ReverbEffectEvent::SetWetLevel(level) => self.wet_level = level.clamp(0.0, 1.0),
+            ReverbEffectEvent::SetDryLevel(level) => self.dry_level = level.clamp(0.0, 1.0),
+            ReverbEffectEvent::SetWidth(width) => self.width = width.clamp(0.0, 1.0),
+            ReverbEffectEvent::SetFreezeMode(freeze) => self.freeze_mode = *freeze,
+            ReverbEffectEvent::SetBuiltInTuning(tuning) => tuning.apply(self),
+        }
+        self.update();
+    }
+
+    fn bypass(&self) -> bool {
+        self.bypass
+    }
+
+    fn render(
+        &mut self,
+        input: &PlanarBlock<f32>,
+        output: &mut PlanarBlock<f32>,
+        info: &BlockInfo,
+    ) {
+        let len = info.len();
+        let (left, right) = (
+            &input.samples[LEFT_CHANNEL][..len],
+            &input.samples[RIGHT_CHANNEL][..len],
+        );
+        let mono = &mut self.mono[..len];
+        for ((m, l), r) in mono.iter_mut().zip(left).zip(right) {
+            *m = (l + r) * self.input_gain;
+        }
+
+        for (tank, wet) in self.tanks.iter_mut().zip(self.wet.samples.iter_mut()) {
+            let wet = &mut wet[..len];
+            wet.fill(0.0);
+            for comb in tank.combs.iter_mut() {
+                comb.process(mono, wet, self.feedback, self.damp);
+            }
+            for allpass in tank.allpasses.iter_mut() {
+                allpass.process(wet);
+            }
+        }
+
+        // Each channel gets the other one by the inverse of the width
+        let (wet_left, wet_right) = (
+            &self.wet.samples[LEFT_CHANNEL][..len],
+            &self.wet.samples[RIGHT_CHANNEL][..len],
+        );
+        let (wet1, wet2, dry) = (self.wet1, self.wet2, self.dry);
+        for (channel, (own, other)) in [
+            (LEFT_CHANNEL, (wet_left, wet_right)),
+            (RIGHT_CHANNEL, (wet_right, wet_left)),
+        ] {
+            let dry_input = &input.samples[channel][..len];
+            let out = &mut output.samples[channel][..len];
+            for (((y, x), a), b) in out.iter_mut().zip(dry_input).zip(own).zip(other) {
+                *y = a * wet1 + b * wet2 + x * dry;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::testkit::*;
+    use crate::BLOCK_SIZE;
+
+    fn reverb() -> ReverbEffect {
+        ReverbEffect::new(SAMPLE_RATE, BuiltInTuning::TuningA)
+    }
+
+    fn energy(signal: &[f32]) -> f32 {
+        signal.iter().map(|x| x * x).sum()
+    }
+
+    #[test]
+    fn dry_signal_without_wet() {
+        let mut reverb = reverb();
+        reverb.dispatch(&AudioEventType::Reverb(ReverbEffectEvent::SetWetLevel(0.0)));
+        reverb.dispatch(&AudioEventType::Reverb(ReverbEffectEvent::SetDryLevel(0.5)));
+
+        let signal = noise(BLOCK_SIZE * 4, 0.5, 1);
+        let [left, right] = render(&mut reverb, &signal, &[BLOCK_SIZE]);
+        assert_eq!(left, signal);
+        assert_eq!(right, signal);
+    }
+
+    #[test]
+    fn tail_grows_with_room_size() {
+        let tail = |room_size: f32| {
+            let mut reverb = reverb();
+            reverb.dispatch(&AudioEventType::Reverb(ReverbEffectEvent::SetDryLevel(0.0)));
+            reverb.dispatch(&AudioEventType::Reverb(ReverbEffectEvent::SetRoomSize(
+                room_size,
+            )));
+            let [left, _] = render(&mut reverb, &impulse(SAMPLE_RATE * 2), &[BLOCK_SIZE]);
+            // Energy of the second half a second and of the last one
+            (
+                energy(&left[SAMPLE_RATE / 2..SAMPLE_RATE]),
+                energy(&left[SAMPLE_RATE..]),
+            )
+        };
+
+        let (small_early, small_late) = tail(0.2);
+        let (large_early, large_late) = tail(0.9);
+        assert!(small_late < small_early);
+        assert!(large_late < large_early);
+        assert!(large_late > small_late * 10.0);
+    }
+
+    #[test]
+    fn stereo_width() {
+        let correlation = |width: f32| {
+            let mut reverb = reverb();
+            reverb.dispatch(&AudioEventType::Reverb(ReverbEffectEvent::SetDryLevel(0.0)));
+            reverb.dispatch(&AudioEventType::Reverb(ReverbEffectEvent::SetWidth(width)));
+            let [left, right] = render(&mut reverb, &impulse(SAMPLE_RATE), &[BLOCK_SIZE]);
+            let dot: f32 = left.iter().zip(&right).map(|(l, r)| l * r).sum();
+            dot / (energy(&left) * energy(&right)).sqrt()
+        };
+
+        assert!((correlation(0.0) - 1.0).abs() < 1e-4);
+        assert!(correlation(1.0) < 0.5);
+    }
+
+    #[test]
+    fn freeze_holds_tail() {
+        let mut reverb = reverb();
+        reverb.dispatch(&AudioEventType::Reverb(ReverbEffectEvent::SetDryLevel(0.0)));
+        render(&mut reverb, &noise(SAMPLE_RATE / 4, 0.5, 2), &[BLOCK_SIZE]);
+        reverb.dispatch(&AudioEventType::Reverb(ReverbEffectEvent::SetFreezeMode(
+            true,
+        )));
+
+        // Frozen tail neither decays nor takes the input
+        let [left, _] = render(&mut reverb, &noise(SAMPLE_RATE * 2, 0.5, 3), &[BLOCK_SIZE]);
+        let early = energy(&left[..SAMPLE_RATE / 2]);
+        let late = energy(&left[SAMPLE_RATE * 3 / 2..]);
+        assert!(early > 0.0);
+        assert!((late / early - 1.0).abs() < 0.1, "{} vs {}", late, early);
+    }
+
+    #[test]
+    fn no_boundary_artifacts() {
+        let signal = chirp(50.0, 5000.0, BLOCK_SIZE * 16, 0.5);
+        assert_block_independent(reverb, &signal, 0.0);
+    }
+}
@@ -1,7 +1,6 @@
 use crate::entities::events::{AudioEventTarget, AudioEventTargetId, AudioEventType};
 use crate::entities::{BlockInfo, Effect};
 use crate::sample::PlanarBlock;
-use crate::{BLOCK_SIZE, CHANNELS_COUNT};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum SoftClipEffectEvent {
@@ -69,18 +68,38 @@ impl Effect for SoftClipEffect {
         &mut self,
         input: &PlanarBlock<f32>,
         output: &mut PlanarBlock<f32>,
-        info: &BlockInfo,
+        _info: &BlockInfo,
     ) {
-        for channel in 0..CHANNELS_COUNT {
-            for i in 0..BLOCK_SIZE {
-                // TODO: SIMD optimization for soft clipping
-                let sample = input.samples[channel][i];
+        // Apply soft clipping using (approximated) tanh function
+        output.copy_from(input);
+        output.soft_clip();
+    }
+}
 
-                // Apply soft clipping using tanh function
-                let gain = 1.5;
-                let scaled = sample * gain;
-                output.samples[channel][i] = scaled.tanh();
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entities::testkit::*;
+    use crate::BLOCK_SIZE;
+
+    fn soft_clip() -> SoftClipEffect {
+        SoftClipEffect::new(0.5, 1.0)
+    }
+
+    #[test]
+    fn distortion_grows_with_level() {
+        let quiet = render(&mut soft_clip(), &sine(16, 4096, 0.05), &[BLOCK_SIZE]);
+        let loud = render(&mut soft_clip(), &sine(16, 4096, 2.0), &[BLOCK_SIZE]);
+        assert!(thd(&quiet[0], 16) < 0.01);
+        assert!(thd(&loud[0], 16) > 0.2);
+    }
+
+    #[test]
+    fn no_boundary_artifacts() {
+        let signal = chirp(50.0, 5000.0, BLOCK_SIZE * 16, 1.5);
+        let [output, _] = render(&mut soft_clip(), &signal, &[BLOCK_SIZE]);
+        assert_eq!(boundary_artifacts(&output, 4.0), Vec::<usize>::new());
+        assert_block_independent(soft_clip, &signal, 0.0);
+        assert_simd_matches(soft_clip, &signal, 0.0);
     }
 }
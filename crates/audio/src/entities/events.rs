@@ -1,22 +1,70 @@
+use crate::beat::BeatClock;
 use crate::entities::bus::BusEvent;
 use crate::entities::effects::fir::FirFilterEffectEvent;
-use crate::entities::effects::freeverb::FreeverbEffectEvent;
+use crate::entities::effects::limiter::LimiterEffectEvent;
 use crate::entities::effects::multiplexer::MultiplexerEffectEvent;
+use crate::entities::effects::reverb::ReverbEffectEvent;
 use crate::entities::effects::soft_clip::SoftClipEffectEvent;
 use crate::entities::sources::actor::ActorsSourceEvent;
 use crate::entities::sources::multiplexer::MultiplexerSourceEvent;
+use crate::entities::sources::music::MusicSourceEvent;
 use crate::entities::sources::waveform::WaveformSourceEvent;
+use crate::{SampleRate, SamplesCount};
 use evenio::prelude::GlobalEvent;
 
+/// Point of the audio stream the event is applied at.
+/// Samples and seconds are counted from the start of the player, see `player::AudioClock`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioTime {
+    Sample(SamplesCount),
+    Seconds(f64),
+    /// The first beat of the musical clock after the event is received
+    /// by the audio thread, see `beat::Tempo`.
+    NextBeat,
+    /// Same as `NextBeat`, but the first beat of the bar.
+    NextBar,
+}
+
+impl AudioTime {
+    /// Converts the time into the sample of the stream.
+    /// `now` is the first sample not rendered yet.
+    pub(crate) fn resolve(
+        &self,
+        sample_rate: SampleRate,
+        now: SamplesCount,
+        beats: &BeatClock,
+    ) -> SamplesCount {
+        match *self {
+            AudioTime::Sample(sample) => sample,
+            AudioTime::Seconds(seconds) => (seconds * sample_rate as f64).round() as SamplesCount,
+            AudioTime::NextBeat => beats.next_beat(now),
+            AudioTime::NextBar => beats.next_bar(now),
+        }
+    }
+}
+
 #[derive(GlobalEvent, Debug, Clone)]
 pub struct AudioEvent {
     target_id: AudioEventTargetId,
     event: AudioEventType,
+    time: Option<AudioTime>,
 }
 
 impl AudioEvent {
     pub fn new(target_id: AudioEventTargetId, event: AudioEventType) -> Self {
-        AudioEvent { target_id, event }
+        AudioEvent {
+            target_id,
+            event,
+            time: None,
+        }
+    }
+
+    /// Schedules the event to the exact sample of the audio stream.
+    /// Without the time the event is applied at the start of the next audio block.
+    /// Events scheduled to the already rendered samples are applied as soon as possible.
+    pub fn at(mut self, time: AudioTime) -> Self {
+        self.time = Some(time);
+        self
     }
 
     #[inline(always)]
@@ -24,6 +72,11 @@ impl AudioEvent {
         self.target_id
     }
 
+    #[inline(always)]
+    pub fn get_time(&self) -> Option<AudioTime> {
+        self.time
+    }
+
     #[inline(always)]
     pub fn get_event(&self) -> &AudioEventType {
         &self.event
@@ -43,12 +96,14 @@ pub enum AudioEventType {
     MuxSource(MultiplexerSourceEvent),
     Waveform(WaveformSourceEvent),
     Actors(ActorsSourceEvent),
+    Music(MusicSourceEvent),
 
     // Effects events
     MuxEffect(MultiplexerEffectEvent),
     FirFilter(FirFilterEffectEvent),
-    Freeverb(FreeverbEffectEvent),
+    Reverb(ReverbEffectEvent),
     SoftClip(SoftClipEffectEvent),
+    Limiter(LimiterEffectEvent),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
@@ -75,13 +130,29 @@ impl AudioEventTargetId {
 
 pub(crate) type EventDispatcher = fn(*mut u8, &AudioEventType);
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug)]
 pub struct AudioEventTarget {
     dispatcher: EventDispatcher,
     id: AudioEventTargetId,
     ptr: *mut u8,
 }
 
+// The dispatcher is determined by the target node, so only the node is compared.
+impl PartialEq for AudioEventTarget {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.ptr == other.ptr
+    }
+}
+
+impl Eq for AudioEventTarget {}
+
+impl std::hash::Hash for AudioEventTarget {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+        self.ptr.hash(state);
+    }
+}
+
 unsafe impl Send for AudioEventTarget {}
 unsafe impl Sync for AudioEventTarget {}
 
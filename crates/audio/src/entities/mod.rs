@@ -1,6 +1,6 @@
 use crate::entities::events::{AudioEventTarget, AudioEventTargetId, AudioEventType};
 use crate::sample::PlanarBlock;
-use crate::{SampleRate, SamplesCount};
+use crate::{SampleRate, SamplesCount, BLOCK_SIZE};
 use std::cell::UnsafeCell;
 
 pub mod bus;
@@ -8,6 +8,8 @@ pub mod effects;
 pub mod events;
 pub mod sinks;
 pub mod sources;
+#[cfg(test)]
+pub(crate) mod testkit;
 
 #[repr(C)]
 #[derive(Debug)]
@@ -29,6 +31,8 @@ impl<T> NodeCell<T> {
         unsafe { &*self.node.get() }
     }
 
+    // Nodes are only rendered and dispatched from the audio thread, one at a time.
+    #[allow(clippy::mut_from_ref)]
     pub(crate) fn as_mut(&self) -> &mut T {
         unsafe { &mut *self.node.get() }
     }
@@ -51,14 +55,27 @@ pub trait Effect {
 pub struct BlockInfo {
     sample_index: SamplesCount,
     sample_rate: SampleRate,
+    len: SamplesCount,
 }
 
 #[allow(unused)]
 impl BlockInfo {
     pub(crate) fn new(sample_index: SamplesCount, sample_rate: SampleRate) -> Self {
+        Self::partial(sample_index, sample_rate, BLOCK_SIZE)
+    }
+
+    /// Block split by a scheduled event: only the first `len` samples are used.
+    /// The nodes keeping the state between the blocks (delay lines,
+    /// playback positions) must advance it by `len` samples only.
+    pub(crate) fn partial(
+        sample_index: SamplesCount,
+        sample_rate: SampleRate,
+        len: SamplesCount,
+    ) -> Self {
         BlockInfo {
             sample_index,
             sample_rate,
+            len,
         }
     }
 
@@ -70,6 +87,11 @@ impl BlockInfo {
         self.sample_rate
     }
 
+    #[inline(always)]
+    fn len(&self) -> SamplesCount {
+        self.len
+    }
+
     #[inline(always)]
     fn time(&self, i: SamplesCount) -> f32 {
         (self.sample_index as f32 + i as f32) / self.sample_rate as f32
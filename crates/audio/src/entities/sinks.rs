@@ -1,11 +1,25 @@
+use crate::beat::{BeatClock, BeatEvent, Tempo};
 use crate::entities::events::{AudioEvent, AudioEventTarget};
 use crate::entities::{BlockInfo, Source};
 use crate::sample::{InterleavedBlock, InterleavedSample, MappedInterleavedBuffer};
 use crate::{SampleRate, BLOCK_SIZE};
+use crossbeam_queue::ArrayQueue;
 use ringbuf::traits::{Consumer, Observer, Producer, SplitRef};
+use std::collections::VecDeque;
+use std::sync::Arc;
 
 const ROUTER_CAPACITY: usize = 64;
-const RING_BUFFER_CAPACITY: usize = 2048;
+// Holds at least a few blocks for the large block sizes
+const RING_BUFFER_CAPACITY: usize = if BLOCK_SIZE * 4 > 2048 {
+    BLOCK_SIZE * 4
+} else {
+    2048
+};
+/// Largest output rendered in one call. The ring buffer holds
+/// the output and the rest of the last rendered block.
+pub(crate) const MAX_OUTPUT_LEN: usize = RING_BUFFER_CAPACITY - BLOCK_SIZE;
+// Preallocated to avoid allocations on the audio thread
+const SCHEDULED_CAPACITY: usize = 1024;
 
 /// Wraps a master source and allows interleaved
 /// buffered rendering and event dispatching.
@@ -15,6 +29,11 @@ pub struct InterleavedSink<T: Source> {
     sample_rate: SampleRate,
     ring_buf: ringbuf::StaticRb<InterleavedSample<f32>, RING_BUFFER_CAPACITY>,
     processed: usize,
+    // Timestamped events sorted by the sample they are applied at
+    scheduled: VecDeque<(usize, AudioEvent)>,
+    // Musical clock, driven by the rendered samples
+    beats: BeatClock,
+    beats_queue: Option<Arc<ArrayQueue<BeatEvent>>>,
 }
 
 unsafe impl<T: Source> Send for InterleavedSink<T> {}
@@ -38,9 +57,80 @@ impl<T: Source> InterleavedSink<T> {
             sample_rate,
             ring_buf: ringbuf::StaticRb::default(),
             processed: 0,
+            scheduled: VecDeque::with_capacity(SCHEDULED_CAPACITY),
+            beats: BeatClock::new(sample_rate, Tempo::default()),
+            beats_queue: None,
         }
     }
 
+    /// Sets the queue the beats of the musical clock are reported to.
+    pub(crate) fn set_beats_queue(&mut self, queue: Arc<ArrayQueue<BeatEvent>>) {
+        self.beats_queue = Some(queue);
+    }
+
+    /// Changes the tempo of the musical clock starting from the next rendered sample.
+    pub(crate) fn set_tempo(&mut self, tempo: Tempo) {
+        self.beats.set_tempo(self.processed, tempo);
+    }
+
+    /// Dispatches the event, or schedules it if it has a time
+    /// past the already rendered samples.
+    pub(crate) fn push(&mut self, event: AudioEvent) {
+        let sample = match event.get_time() {
+            Some(time) => time.resolve(self.sample_rate, self.processed, &self.beats),
+            None => return self.dispatch(&event),
+        };
+        if sample <= self.processed {
+            return self.dispatch(&event);
+        }
+
+        // Growing the queue would allocate on the audio thread,
+        // applying the event early is the lesser evil
+        if self.scheduled.len() == self.scheduled.capacity() {
+            return self.dispatch(&event);
+        }
+
+        // Events with the same time are applied in the order they were pushed
+        let index = self.scheduled.partition_point(|(s, _)| *s <= sample);
+        self.scheduled.insert(index, (sample, event));
+    }
+
+    /// Renders the next block. The block is split at the samples the scheduled
+    /// events are applied at, so the events take effect mid-block.
+    fn render_block(&mut self) {
+        let block_end = self.processed + BLOCK_SIZE;
+        let mut start = self.processed;
+        while start < block_end {
+            while let Some((_, event)) = self.scheduled.pop_front_if(|(s, _)| *s <= start) {
+                self.dispatch(&event);
+            }
+            let end = match self.scheduled.front() {
+                Some((sample, _)) => (*sample).min(block_end),
+                None => block_end,
+            };
+
+            let info = BlockInfo::partial(start, self.sample_rate, end - start);
+            self.source.frame_start();
+            let rendered = self.source.render(&info);
+
+            let mut interleaved_block = InterleavedBlock::default();
+            rendered.copy_into_interleaved(&mut interleaved_block);
+
+            // Put the rendered part into the ring buffer
+            self.ring_buf
+                .push_slice(&interleaved_block.samples[..end - start]);
+            start = end;
+        }
+
+        if let Some(queue) = &self.beats_queue {
+            self.beats.beats_in(self.processed, block_end, |beat| {
+                // Consumer is not keeping up, it will miss the beat anyway
+                let _ = queue.push(beat);
+            });
+        }
+        self.processed = block_end;
+    }
+
     pub(crate) fn dispatch(&self, b: &AudioEvent) {
         let index = b.get_target_id().as_usize();
 
@@ -73,16 +163,7 @@ impl<T: Source> InterleavedSink<T> {
 
         // If theres not enough samples in the ring buffer, we need to fill it
         while self.ring_buf.split_ref().0.occupied_len() < output.len {
-            let info = BlockInfo::new(self.processed, self.sample_rate);
-            self.source.frame_start();
-            let rendered = self.source.render(&info);
-            self.processed += BLOCK_SIZE;
-
-            let mut interleaved_block = InterleavedBlock::default();
-            rendered.copy_into_interleaved(&mut interleaved_block);
-
-            // Put the rendered block into the ring buffer
-            self.ring_buf.push_slice(&interleaved_block.samples);
+            self.render_block();
         }
 
         // Now we can read the samples from the ring buffer
@@ -98,7 +179,8 @@ mod tests {
     use crate::dsp::detect_features;
     use crate::entities::bus::Bus;
     use crate::entities::effects::bypass::BypassEffect;
-    use crate::entities::sources::TestSource;
+    use crate::entities::events::{AudioEventType, AudioTime};
+    use crate::entities::sources::{TestSource, TestSourceEvent};
 
     #[test]
     fn sink_test() {
@@ -113,7 +195,7 @@ mod tests {
         let mut mapped_output = MappedInterleavedBuffer::new(&mut output).unwrap();
 
         let mut processed = 0;
-        for i in 0..100 {
+        for _i in 0..100 {
             sink.render(&mut mapped_output);
             // First 33 samples should be 1.0, 2.0, ..., 33.0
             // Data starts from 1 every 255 samples
@@ -132,6 +214,108 @@ mod tests {
         }
     }
 
+    #[test]
+    fn scheduled_event_applies_mid_block() {
+        detect_features();
+
+        // Bus keeps the source on the heap, so the router can address it
+        let source = TestSource::new();
+        let target = source.get_id();
+        let bus = Bus::new(BypassEffect::new(), source, None, None);
+        let mut sink = InterleavedSink::new(bus, 44100);
+
+        // Both in the second block
+        let first = BLOCK_SIZE + 100;
+        let second = BLOCK_SIZE + 300;
+        sink.push(
+            AudioEvent::new(
+                target,
+                AudioEventType::TestSource(TestSourceEvent::SetMultiplier(2.0)),
+            )
+            .at(AudioTime::Sample(first)),
+        );
+        sink.push(
+            AudioEvent::new(
+                target,
+                AudioEventType::TestSource(TestSourceEvent::SetMultiplier(3.0)),
+            )
+            .at(AudioTime::Seconds(second as f64 / 44100.0)),
+        );
+
+        let mut output: [f32; 64 * 2] = [0.0; 64 * 2];
+        let mut mapped_output = MappedInterleavedBuffer::new(&mut output).unwrap();
+
+        let mut processed = 0;
+        for _i in 0..32 {
+            sink.render(&mut mapped_output);
+            for i in 0..64 {
+                let sample = processed + i;
+                let mul = if sample >= second {
+                    3.0
+                } else if sample >= first {
+                    2.0
+                } else {
+                    1.0
+                };
+                let expected = ((sample % BLOCK_SIZE) + 1) as f32 * mul;
+                let actual = mapped_output.samples[i].channels[0];
+                assert_eq!(actual, expected, "Mismatch at sample {}", sample);
+            }
+            processed += 64;
+        }
+    }
+
+    #[test]
+    fn event_on_next_beat() {
+        detect_features();
+
+        let source = TestSource::new();
+        let target = source.get_id();
+        let bus = Bus::new(BypassEffect::new(), source, None, None);
+        let mut sink = InterleavedSink::new(bus, 44100);
+        let beats = Arc::new(ArrayQueue::new(16));
+        sink.set_beats_queue(beats.clone());
+        // One beat per 700 samples (with the default block size),
+        // the first beat after the first block
+        let beat = BLOCK_SIZE + 188;
+        sink.set_tempo(Tempo::new(44100.0 * 60.0 / beat as f64, 2));
+
+        let mut output: [f32; 64 * 2] = [0.0; 64 * 2];
+        let mut mapped_output = MappedInterleavedBuffer::new(&mut output).unwrap();
+        // The first block is rendered, the event must wait for the second beat
+        sink.render(&mut mapped_output);
+        sink.push(
+            AudioEvent::new(
+                target,
+                AudioEventType::TestSource(TestSourceEvent::SetMultiplier(2.0)),
+            )
+            .at(AudioTime::NextBeat),
+        );
+
+        let mut processed = 64;
+        // The whole blocks before the fourth beat
+        let blocks = beat * 3 / BLOCK_SIZE;
+        for _i in 0..(blocks * BLOCK_SIZE / 64 - 1) {
+            sink.render(&mut mapped_output);
+            for i in 0..64 {
+                let sample = processed + i;
+                let mul = if sample >= beat { 2.0 } else { 1.0 };
+                let expected = ((sample % BLOCK_SIZE) + 1) as f32 * mul;
+                let actual = mapped_output.samples[i].channels[0];
+                assert_eq!(actual, expected, "Mismatch at sample {}", sample);
+            }
+            processed += 64;
+        }
+
+        // The blocks are rendered as a whole, so no further beats are reported
+        let beats: Vec<_> = std::iter::from_fn(|| beats.pop()).collect();
+        let expected = [(0, 0, 0), (0, 1, beat), (1, 0, beat * 2)];
+        assert_eq!(beats.len(), expected.len());
+        for (beat, (bar, index, sample)) in beats.iter().zip(expected) {
+            assert_eq!((beat.bar, beat.beat, beat.sample), (bar, index, sample));
+        }
+    }
+
     #[bench]
     fn bench_sink(b: &mut test::Bencher) {
         detect_features();
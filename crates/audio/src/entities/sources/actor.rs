@@ -1,17 +1,24 @@
 use crate::assets::AudioAsset;
 use crate::entities::{AudioEventTarget, AudioEventTargetId, AudioEventType, BlockInfo, Source};
 use crate::sample::PlanarBlock;
-use crate::{SamplesCount, BLOCK_SIZE};
-use dawn_assets::{Asset, TypedAsset};
+use crate::CHANNELS_COUNT;
+use crossbeam_queue::ArrayQueue;
+use dawn_assets::TypedAsset;
 use glam::Vec3;
-use std::cmp::min;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 const MAX_ACTORS: usize = 1024;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ActorID(usize);
 
+impl Default for ActorID {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ActorID {
     const EMPTY: ActorID = ActorID(0);
 
@@ -27,8 +34,11 @@ impl ActorID {
 pub enum ActorsSourceEvent {
     AddActor {
         id: Option<ActorID>,
-        pos: Vec3,
+        /// Position of the actor. Actors without it are not attenuated by the distance.
+        pos: Option<Vec3>,
         gain: f32,
+        /// Playback speed, changes the pitch of the clip.
+        pitch: f32,
         clip: TypedAsset<AudioAsset>,
     },
     RemoveActor(ActorID),
@@ -47,19 +57,28 @@ pub enum ActorsSourceEvent {
 
 struct Voice {
     id: ActorID,
-    position: Vec3,
+    position: Option<Vec3>,
     gain: f32,
-    playback_position: SamplesCount,
+    pitch: f32,
+    // In the frames of the clip, fractional because of the pitch
+    playback_position: f64,
     clip: Option<TypedAsset<AudioAsset>>,
 }
 
 impl Voice {
-    pub fn new(id: ActorID, position: Vec3, gain: f32, clip: TypedAsset<AudioAsset>) -> Self {
+    pub fn new(
+        id: ActorID,
+        position: Option<Vec3>,
+        gain: f32,
+        pitch: f32,
+        clip: TypedAsset<AudioAsset>,
+    ) -> Self {
         Voice {
             id,
             position,
             gain,
-            playback_position: 0,
+            pitch,
+            playback_position: 0.0,
             clip: Some(clip),
         }
     }
@@ -69,9 +88,10 @@ impl Default for Voice {
     fn default() -> Self {
         Voice {
             id: ActorID::EMPTY,
-            position: Vec3::ZERO,
-            playback_position: 0,
+            position: None,
+            playback_position: 0.0,
             gain: 1.0,
+            pitch: 1.0,
             clip: None,
         }
     }
@@ -135,6 +155,8 @@ pub struct ActorsSource {
     output: PlanarBlock<f32>,
     gain_func: DistanceGainFunction,
     lpf_func: DistanceLPFFunction,
+    // Actors finished playing their clips
+    finished: Arc<ArrayQueue<ActorID>>,
 }
 
 fn dispatch_actors(ptr: *mut u8, event: &AudioEventType) {
@@ -144,19 +166,19 @@ fn dispatch_actors(ptr: *mut u8, event: &AudioEventType) {
 
 impl ActorsSource {
     pub fn new(gain_func: DistanceGainFunction, lpf_func: DistanceLPFFunction) -> Self {
-        let mut voices = unsafe { std::mem::zeroed::<[Voice; MAX_ACTORS]>() };
-        for voice in voices.iter_mut() {
-            *voice = Voice::default();
-        }
+        let voices = std::array::from_fn(|_| Voice::default());
         ActorsSource {
             id: AudioEventTargetId::new(),
             cached: false,
             listener_position: Vec3::ZERO,
             gain_func,
             lpf_func,
-            id_map: HashMap::new(),
+            // Twice the actors, so the removed entries are always
+            // cleaned up in place instead of growing the map on the audio thread
+            id_map: HashMap::with_capacity(MAX_ACTORS * 2),
             voices,
             output: Default::default(),
+            finished: Arc::new(ArrayQueue::new(MAX_ACTORS)),
         }
     }
 
@@ -164,6 +186,12 @@ impl ActorsSource {
         self.id
     }
 
+    /// Queue the IDs of the actors that finished playing are pushed to.
+    /// Such actors are removed automatically.
+    pub fn finished_queue(&self) -> Arc<ArrayQueue<ActorID>> {
+        self.finished.clone()
+    }
+
     fn create_event_target(&self) -> AudioEventTarget {
         AudioEventTarget::new(dispatch_actors, self.id, self)
     }
@@ -180,21 +208,21 @@ impl Source for ActorsSource {
                 id,
                 pos,
                 gain,
+                pitch,
                 clip,
             }) => {
+                let ir = &clip.cast().0;
+                if ir.is_packed() || ir.is_encoded() {
+                    log::warn!("Packed and encoded clips are played by the StreamSource");
+                    return;
+                }
                 // Find free slot
                 if let Some(index) = self.voices.iter_mut().position(|v| v.id == ActorID::EMPTY) {
                     let actor_id = id.unwrap_or_else(ActorID::new);
-                    self.voices[index] = Voice::new(actor_id, *pos, *gain, clip.clone());
+                    self.voices[index] = Voice::new(actor_id, *pos, *gain, *pitch, clip.clone());
                     // TODO: What if the actor already exists?
                     self.id_map.insert(actor_id, index);
                     self.cached = false;
-                    log::debug!(
-                        "Added actor: {:?} at position: {:?} with gain: {}",
-                        actor_id,
-                        pos,
-                        gain
-                    );
                 } else {
                     log::warn!("No free voice slot available for new actor");
                 }
@@ -204,14 +232,13 @@ impl Source for ActorsSource {
                     self.voices[index] = Voice::default();
                     self.id_map.remove(id);
                     self.cached = false;
-                    log::debug!("Removed actor: {:?}", id);
                 } else {
                     log::warn!("Attempted to remove non-existent actor: {:?}", id);
                 }
             }
             AudioEventType::Actors(ActorsSourceEvent::ChangeActorPosition { id, pos }) => {
                 if let Some(&index) = self.id_map.get(id) {
-                    self.voices[index].position = *pos;
+                    self.voices[index].position = Some(*pos);
                     self.cached = false;
                 } else {
                     log::warn!(
@@ -265,26 +292,42 @@ impl Source for ActorsSource {
             if let Some(clip) = actor.clip.as_ref() {
                 // TODO: Implement SIMD processing for performance
 
-                let distance = (actor.position - self.listener_position).length();
-                let gain = self.gain_func.gain(distance) * actor.gain;
-                let lpf_cutoff = self.lpf_func.cutoff(distance);
-
-                // Copy audio data from the clip to the output
-                let clip = clip.cast();
-                let to_copy = min(BLOCK_SIZE, clip.0.length - actor.playback_position);
-
-                let mut block = PlanarBlock::default();
-                // TODO: Implement copy
-                // block.copy_from_planar_vec(&clip.0.data, actor.playback_position, to_copy);
-                self.output.addm(&block, actor.gain * gain);
+                let gain = match actor.position {
+                    Some(position) => {
+                        let distance = (position - self.listener_position).length();
+                        let _lpf_cutoff = self.lpf_func.cutoff(distance);
+                        self.gain_func.gain(distance) * actor.gain
+                    }
+                    None => actor.gain,
+                };
+
+                // Mix the clip into the output, interpolating between the frames
+                let clip = &clip.cast().0;
+                let channels = clip.channels as usize;
+                for i in 0..info.len() {
+                    let frame = actor.playback_position as usize;
+                    if frame >= clip.length {
+                        break;
+                    }
+                    let next = (frame + 1).min(clip.length - 1);
+                    let t = (actor.playback_position - frame as f64) as f32;
+                    for channel in 0..CHANNELS_COUNT {
+                        // Mono clips are played in both channels
+                        let a = clip.data[frame * channels + channel % channels];
+                        let b = clip.data[next * channels + channel % channels];
+                        self.output.samples[channel][i] += (a + (b - a) * t) * gain;
+                    }
+                    actor.playback_position += actor.pitch as f64;
+                }
 
                 // TODO: Implement low-pass filtering based on lpf_cutoff
                 // TODO: Implement panning based on actor position
-                actor.playback_position += to_copy;
 
                 // Check if the playback is finished
-                if actor.playback_position == clip.0.length {
-                    log::debug!("Actor {:?} finished playing clip", actor.id);
+                if actor.playback_position >= clip.length as f64 {
+                    self.id_map.remove(&actor.id);
+                    // Nobody listens, if the queue is full
+                    let _ = self.finished.push(actor.id);
                     actor.id = ActorID::EMPTY; // Reset voice if clip is finished
                     actor.clip = None; // Drop the clip
                 }
@@ -295,3 +338,70 @@ impl Source for ActorsSource {
         &self.output
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dsp::detect_features;
+    use crate::BLOCK_SIZE;
+    use dawn_assets::ir::audio::IRAudio;
+    use dawn_assets::Asset;
+    use std::any::TypeId;
+    use std::ptr::NonNull;
+
+    fn clip(length: usize) -> TypedAsset<AudioAsset> {
+        let audio = AudioAsset(IRAudio {
+            data: (0..length).map(|i| i as f32).collect(),
+            sample_rate: 44100,
+            channels: 1,
+            length,
+            chunks: vec![],
+            encoded: None,
+            captions: None,
+        });
+        let ptr = Box::into_raw(Box::new(audio));
+        TypedAsset::new(Asset::new(
+            TypeId::of::<AudioAsset>(),
+            NonNull::new(ptr as *mut ()).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn actor_plays_clip_and_finishes() {
+        detect_features();
+
+        let mut source = ActorsSource::new(
+            DistanceGainFunction::Constant(1.0),
+            DistanceLPFFunction::Constant(0.0),
+        );
+        let finished = source.finished_queue();
+        let id = ActorID::new();
+        source.dispatch(&AudioEventType::Actors(ActorsSourceEvent::AddActor {
+            id: Some(id),
+            pos: None,
+            gain: 0.5,
+            pitch: 2.0,
+            clip: clip(BLOCK_SIZE * 5),
+        }));
+
+        // Twice as fast, every second frame
+        let mut position = 0;
+        for _ in 0..2 {
+            source.frame_start();
+            let output = source.render(&BlockInfo::new(position, 44100));
+            for i in 0..BLOCK_SIZE {
+                let expected = ((position + i) * 2) as f32 * 0.5;
+                assert_eq!(output.samples[0][i], expected);
+                assert_eq!(output.samples[1][i], expected);
+            }
+            position += BLOCK_SIZE;
+            assert_eq!(finished.pop(), None);
+        }
+
+        source.frame_start();
+        let output = source.render(&BlockInfo::new(position, 44100));
+        assert_eq!(output.samples[0][BLOCK_SIZE / 2], 0.0);
+        assert_eq!(finished.pop(), Some(id));
+        assert!(source.id_map.is_empty());
+    }
+}
@@ -1,7 +1,12 @@
 pub mod actor;
-pub mod notes;
 pub mod multiplexer;
+pub mod music;
+pub mod notes;
+pub mod stream;
 pub mod waveform;
+
+#[cfg(test)]
+pub(crate) use test::*;
 #[cfg(test)]
 mod test {
     use crate::entities::events::{AudioEventTarget, AudioEventTargetId, AudioEventType};
@@ -46,11 +51,13 @@ mod test {
             AudioEventTarget::new(dispatch_test_source, self.id, self)
         }
 
-        fn generate_test_signal(output: &mut PlanarBlock<f32>, mul: f32) {
-            // Fill the output block with a simple test signal, e.g., a sequence of numbers
+        fn generate_test_signal(output: &mut PlanarBlock<f32>, mul: f32, info: &BlockInfo) {
+            // Fill the output block with a simple test signal, e.g., a sequence of numbers.
+            // The sequence restarts every BLOCK_SIZE samples of the stream
             for i in 0..BLOCK_SIZE {
+                let value = ((info.sample_index() + i) % BLOCK_SIZE + 1) as f32 * mul;
                 for channel in 0..output.samples.len() {
-                    output.samples[channel][i] = (i + 1) as f32 * mul; // Fill with 1, 2, 3, ...
+                    output.samples[channel][i] = value; // Fill with 1, 2, 3, ...
                 }
             }
         }
@@ -62,13 +69,10 @@ mod test {
         }
 
         fn dispatch(&mut self, event: &AudioEventType) {
-            match event {
-                AudioEventType::TestSource(TestSourceEvent::SetMultiplier(mul)) => {
-                    self.mul = *mul;
-                    // Here you might want to update the output based on the new multiplier
-                    // For simplicity, we won't modify the output in this example
-                }
-                _ => {}
+            if let AudioEventType::TestSource(TestSourceEvent::SetMultiplier(mul)) = event {
+                self.mul = *mul;
+                // Here you might want to update the output based on the new multiplier
+                // For simplicity, we won't modify the output in this example
             }
         }
 
@@ -77,13 +81,13 @@ mod test {
             self.cached = false;
         }
 
-        fn render(&mut self, _info: &BlockInfo) -> &PlanarBlock<f32> {
+        fn render(&mut self, info: &BlockInfo) -> &PlanarBlock<f32> {
             if self.cached {
                 return &self.output;
             }
 
             self.cached = true;
-            TestSource::generate_test_signal(&mut self.output, self.mul);
+            TestSource::generate_test_signal(&mut self.output, self.mul, info);
             &self.output
         }
     }
@@ -121,6 +125,3 @@ mod test {
         }
     }
 }
-
-#[cfg(test)]
-pub(crate) use test::*;
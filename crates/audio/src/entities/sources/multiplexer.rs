@@ -90,10 +90,8 @@ impl<T: Source, const N: usize> Source for MultiplexerSource<T, N> {
             .collect()
     }
 
-    fn dispatch(&mut self, event: &AudioEventType) {
-        match event {
-            _ => {}
-        }
+    fn dispatch(&mut self, _event: &AudioEventType) {
+        {}
     }
 
     #[inline(always)]
@@ -155,10 +153,8 @@ impl<T1: Source> Source for Multiplexer1Source<T1> {
         targets
     }
 
-    fn dispatch(&mut self, event: &AudioEventType) {
-        match event {
-            _ => {}
-        }
+    fn dispatch(&mut self, _event: &AudioEventType) {
+        {}
     }
 
     #[inline(always)]
@@ -215,10 +211,8 @@ impl<T1: Source, T2: Source> Source for Multiplexer2Source<T1, T2> {
         targets
     }
 
-    fn dispatch(&mut self, event: &AudioEventType) {
-        match event {
-            _ => {}
-        }
+    fn dispatch(&mut self, _event: &AudioEventType) {
+        {}
     }
 
     #[inline(always)]
@@ -280,10 +274,8 @@ impl<T1: Source, T2: Source, T3: Source> Source for Multiplexer3Source<T1, T2, T
         targets
     }
 
-    fn dispatch(&mut self, event: &AudioEventType) {
-        match event {
-            _ => {}
-        }
+    fn dispatch(&mut self, _event: &AudioEventType) {
+        {}
     }
 
     #[inline(always)]
@@ -353,10 +345,8 @@ impl<T1: Source, T2: Source, T3: Source, T4: Source> Source for Multiplexer4Sour
         targets
     }
 
-    fn dispatch(&mut self, event: &AudioEventType) {
-        match event {
-            _ => {}
-        }
+    fn dispatch(&mut self, _event: &AudioEventType) {
+        {}
     }
 
     #[inline(always)]
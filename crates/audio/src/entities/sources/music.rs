@@ -0,0 +1,424 @@
+use crate::assets::MusicAsset;
+use crate::beat::Tempo;
+use crate::entities::events::{AudioEvent, AudioTime};
+use crate::entities::{AudioEventTarget, AudioEventTargetId, AudioEventType, BlockInfo, Source};
+use crate::sample::PlanarBlock;
+use crate::{SamplesCount, CHANNELS_COUNT};
+use dawn_assets::ir::music::IRMusicQuantize;
+use dawn_assets::TypedAsset;
+
+const MAX_LAYERS: usize = 16;
+
+#[derive(Debug, Clone)]
+pub enum MusicSourceEvent {
+    /// Starts the section from the beginning, replacing the current music.
+    Play {
+        music: TypedAsset<MusicAsset>,
+        section: usize,
+    },
+    /// Fades out the music in the given number of seconds.
+    Stop { fade: f32 },
+    /// Changes the intensity, the layers follow it in `IRMusic::intensity_fade` seconds.
+    SetIntensity(f32),
+    /// Starts the section crossfading it with the current one.
+    TransitionTo { section: usize, crossfade: f32 },
+}
+
+/// Section being played. All the layers share the playback position.
+struct SectionVoice {
+    section: usize,
+    position: SamplesCount,
+    length: SamplesCount,
+    looped: bool,
+    // Crossfade gain and its change per second
+    gain: f32,
+    gain_rate: f32,
+    volumes: [f32; MAX_LAYERS],
+}
+
+impl SectionVoice {
+    fn new(music: &MusicAsset, section: usize, intensity: f32, gain: f32, gain_rate: f32) -> Self {
+        let mut volumes = [0.0; MAX_LAYERS];
+        for (volume, layer) in volumes.iter_mut().zip(&music.ir.sections[section].layers) {
+            *volume = layer.volume(intensity);
+        }
+        SectionVoice {
+            section,
+            position: 0,
+            length: music.section_length(section),
+            looped: music.ir.sections[section].looped,
+            gain,
+            gain_rate,
+            volumes,
+        }
+    }
+
+    fn fade(duration: f32) -> f32 {
+        if duration > 0.0 {
+            1.0 / duration
+        } else {
+            f32::INFINITY
+        }
+    }
+
+    /// Mixes the section into the output. Returns false when the voice is finished.
+    fn render(
+        &mut self,
+        music: &MusicAsset,
+        intensity: f32,
+        output: &mut PlanarBlock<f32>,
+        info: &BlockInfo,
+    ) -> bool {
+        let sample_rate = info.sample_rate() as f32;
+        let gain_step = self.gain_rate / sample_rate;
+        let volume_step = SectionVoice::fade(music.ir.intensity_fade) / sample_rate;
+        let layers = &music.ir.sections[self.section].layers;
+
+        for i in 0..info.len() {
+            if self.position >= self.length {
+                if !self.looped || self.length == 0 {
+                    return false;
+                }
+                self.position = 0;
+            }
+            self.gain = (self.gain + gain_step).clamp(0.0, 1.0);
+            if self.gain == 0.0 && self.gain_rate < 0.0 {
+                return false;
+            }
+
+            for (index, layer) in layers.iter().enumerate().take(MAX_LAYERS) {
+                let target = layer.volume(intensity);
+                let volume = &mut self.volumes[index];
+                if *volume < target {
+                    *volume = (*volume + volume_step).min(target);
+                } else {
+                    *volume = (*volume - volume_step).max(target);
+                }
+
+                let stem = music.stem(self.section, index);
+                if self.position >= stem.length || *volume == 0.0 {
+                    continue;
+                }
+                let channels = stem.channels as usize;
+                let frame = &stem.data[self.position * channels..(self.position + 1) * channels];
+                let k = *volume * self.gain;
+                for channel in 0..CHANNELS_COUNT {
+                    // Mono stems are played in both channels
+                    output.samples[channel][i] += frame[channel % channels] * k;
+                }
+            }
+            self.position += 1;
+        }
+
+        true
+    }
+}
+
+/// Plays the adaptive music: the layers of the current section follow
+/// the intensity, the sections are switched with the crossfades.
+/// Use `MusicController` to build the events.
+pub struct MusicSource {
+    id: AudioEventTargetId,
+    cached: bool,
+    music: Option<TypedAsset<MusicAsset>>,
+    intensity: f32,
+    current: Option<SectionVoice>,
+    // Previous section fading out
+    fading: Option<SectionVoice>,
+    output: PlanarBlock<f32>,
+}
+
+fn dispatch_music(ptr: *mut u8, event: &AudioEventType) {
+    let music: &mut MusicSource = unsafe { &mut *(ptr as *mut MusicSource) };
+    music.dispatch(event);
+}
+
+impl Default for MusicSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MusicSource {
+    pub fn new() -> Self {
+        MusicSource {
+            id: AudioEventTargetId::new(),
+            cached: false,
+            music: None,
+            intensity: 0.0,
+            current: None,
+            fading: None,
+            output: PlanarBlock::default(),
+        }
+    }
+
+    pub fn get_id(&self) -> AudioEventTargetId {
+        self.id
+    }
+
+    fn create_event_target(&self) -> AudioEventTarget {
+        AudioEventTarget::new(dispatch_music, self.id, self)
+    }
+}
+
+impl Source for MusicSource {
+    fn get_targets(&self) -> Vec<AudioEventTarget> {
+        vec![self.create_event_target()]
+    }
+
+    fn dispatch(&mut self, event: &AudioEventType) {
+        match event {
+            AudioEventType::Music(MusicSourceEvent::Play { music, section }) => {
+                self.current = Some(SectionVoice::new(
+                    music.cast(),
+                    *section,
+                    self.intensity,
+                    1.0,
+                    0.0,
+                ));
+                self.fading = None;
+                self.music = Some(music.clone());
+            }
+            AudioEventType::Music(MusicSourceEvent::Stop { fade }) => {
+                self.fading = self.current.take().map(|mut voice| {
+                    voice.gain_rate = -SectionVoice::fade(*fade);
+                    voice
+                });
+            }
+            AudioEventType::Music(MusicSourceEvent::SetIntensity(intensity)) => {
+                self.intensity = *intensity;
+            }
+            AudioEventType::Music(MusicSourceEvent::TransitionTo { section, crossfade }) => {
+                let Some(music) = &self.music else {
+                    log::warn!("Transition requested with no music playing");
+                    return;
+                };
+                let rate = SectionVoice::fade(*crossfade);
+                self.fading = self.current.take().map(|mut voice| {
+                    voice.gain_rate = -rate;
+                    voice
+                });
+                self.current = Some(SectionVoice::new(
+                    music.cast(),
+                    *section,
+                    self.intensity,
+                    0.0,
+                    rate,
+                ));
+            }
+            _ => {}
+        }
+        self.cached = false;
+    }
+
+    fn frame_start(&mut self) {
+        self.cached = false;
+    }
+
+    fn render(&mut self, info: &BlockInfo) -> &PlanarBlock<f32> {
+        if self.cached {
+            return &self.output;
+        }
+
+        self.output.silence();
+        if let Some(music) = &self.music {
+            let music = music.cast();
+            for slot in [&mut self.current, &mut self.fading] {
+                if let Some(voice) = slot {
+                    if !voice.render(music, self.intensity, &mut self.output, info) {
+                        *slot = None;
+                    }
+                }
+            }
+        }
+
+        self.cached = true;
+        &self.output
+    }
+}
+
+/// Builds the events of the `MusicSource` from the gameplay code.
+/// The transitions are scheduled on the beats of the player's musical clock,
+/// so set its tempo to `MusicController::tempo` before playing.
+pub struct MusicController {
+    target: AudioEventTargetId,
+    music: TypedAsset<MusicAsset>,
+    section: Option<usize>,
+}
+
+impl MusicController {
+    pub fn new(target: AudioEventTargetId, music: TypedAsset<MusicAsset>) -> Self {
+        MusicController {
+            target,
+            music,
+            section: None,
+        }
+    }
+
+    pub fn tempo(&self) -> Tempo {
+        let ir = &self.music.cast().ir;
+        Tempo::new(ir.bpm as f64, ir.beats_per_bar as usize)
+    }
+
+    /// Name of the section being played.
+    pub fn section(&self) -> Option<&str> {
+        let section = self.section?;
+        Some(self.music.cast().ir.sections[section].name.as_str())
+    }
+
+    fn event(&self, event: MusicSourceEvent) -> AudioEvent {
+        AudioEvent::new(self.target, AudioEventType::Music(event))
+    }
+
+    /// Starts the section on the next bar.
+    /// Returns `None` if the music has no such section.
+    pub fn play(&mut self, section: &str) -> Option<AudioEvent> {
+        let index = self.music.cast().ir.section(section)?;
+        self.section = Some(index);
+        let event = self.event(MusicSourceEvent::Play {
+            music: self.music.clone(),
+            section: index,
+        });
+        Some(event.at(AudioTime::NextBar))
+    }
+
+    pub fn stop(&mut self, fade: f32) -> AudioEvent {
+        self.section = None;
+        self.event(MusicSourceEvent::Stop { fade })
+    }
+
+    pub fn set_intensity(&self, intensity: f32) -> AudioEvent {
+        self.event(MusicSourceEvent::SetIntensity(intensity))
+    }
+
+    /// Switches to the section following the transition rules of the music.
+    /// Returns `None` if the music has no such section or it's already played.
+    pub fn transition_to(&mut self, section: &str) -> Option<AudioEvent> {
+        let ir = &self.music.cast().ir;
+        let index = ir.section(section)?;
+        if self.section == Some(index) {
+            return None;
+        }
+
+        let rule = ir.transition(self.section(), section);
+        self.section = Some(index);
+        let event = self.event(MusicSourceEvent::TransitionTo {
+            section: index,
+            crossfade: rule.crossfade,
+        });
+        Some(match rule.quantize {
+            IRMusicQuantize::Immediate => event,
+            IRMusicQuantize::Beat => event.at(AudioTime::NextBeat),
+            IRMusicQuantize::Bar => event.at(AudioTime::NextBar),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::AudioAsset;
+    use crate::dsp::detect_features;
+    use crate::BLOCK_SIZE;
+    use dawn_assets::ir::audio::IRAudio;
+    use dawn_assets::ir::music::{IRMusic, IRMusicLayer, IRMusicSection, IRMusicTransition};
+    use dawn_assets::{Asset, AssetCastable, AssetID};
+    use std::any::TypeId;
+    use std::collections::HashMap;
+    use std::ptr::NonNull;
+
+    fn asset<T: AssetCastable + 'static>(value: T) -> Asset {
+        let ptr = Box::into_raw(Box::new(value));
+        Asset::new(TypeId::of::<T>(), NonNull::new(ptr as *mut ()).unwrap())
+    }
+
+    // Mono stem of the constant value
+    fn stem(value: f32) -> Asset {
+        asset(AudioAsset(IRAudio {
+            data: vec![value; 4096],
+            sample_rate: 44100,
+            channels: 1,
+            length: 4096,
+            chunks: vec![],
+            encoded: None,
+            captions: None,
+        }))
+    }
+
+    fn layer(stem: &str, fade_in_start: f32, fade_in_end: f32) -> IRMusicLayer {
+        IRMusicLayer {
+            stem: stem.into(),
+            fade_in_start,
+            fade_in_end,
+        }
+    }
+
+    fn music() -> TypedAsset<MusicAsset> {
+        let ir = IRMusic {
+            sections: vec![
+                IRMusicSection {
+                    name: "explore".to_string(),
+                    layers: vec![layer("pads", 0.0, 0.0), layer("drums", 0.5, 1.0)],
+                    looped: true,
+                },
+                IRMusicSection {
+                    name: "combat".to_string(),
+                    layers: vec![layer("brass", 0.0, 0.0)],
+                    looped: true,
+                },
+            ],
+            transitions: vec![IRMusicTransition {
+                from: Some("explore".to_string()),
+                to: Some("combat".to_string()),
+                quantize: IRMusicQuantize::Beat,
+                crossfade: 0.0,
+            }],
+            intensity_fade: 0.0,
+            ..Default::default()
+        };
+        let deps: HashMap<AssetID, Asset> = [
+            ("pads".into(), stem(1.0)),
+            ("drums".into(), stem(10.0)),
+            ("brass".into(), stem(100.0)),
+        ]
+        .into_iter()
+        .collect();
+
+        TypedAsset::new(asset(MusicAsset::from_ir(ir, &deps).unwrap()))
+    }
+
+    fn render(source: &mut MusicSource) -> (f32, f32) {
+        source.frame_start();
+        let output = source.render(&BlockInfo::new(0, 44100));
+        (output.samples[0][0], output.samples[1][BLOCK_SIZE - 1])
+    }
+
+    #[test]
+    fn layers_and_transitions() {
+        detect_features();
+
+        let mut source = MusicSource::new();
+        let mut controller = MusicController::new(source.get_id(), music());
+        assert_eq!(controller.tempo(), Tempo::new(120.0, 4));
+        assert!(controller.play("missing").is_none());
+
+        let play = controller.play("explore").unwrap();
+        assert_eq!(play.get_time(), Some(AudioTime::NextBar));
+        source.dispatch(play.get_event());
+        assert_eq!(render(&mut source), (1.0, 1.0));
+
+        // Half of the drums
+        source.dispatch(controller.set_intensity(0.75).get_event());
+        assert_eq!(render(&mut source), (6.0, 6.0));
+
+        let transition = controller.transition_to("combat").unwrap();
+        assert_eq!(transition.get_time(), Some(AudioTime::NextBeat));
+        assert_eq!(controller.section(), Some("combat"));
+        assert!(controller.transition_to("combat").is_none());
+        source.dispatch(transition.get_event());
+        assert_eq!(render(&mut source), (100.0, 100.0));
+
+        source.dispatch(controller.stop(0.0).get_event());
+        assert_eq!(render(&mut source), (0.0, 0.0));
+    }
+}
@@ -48,13 +48,6 @@ impl Default for Note {
 }
 
 impl Note {
-    pub(crate) fn new(name: NoteName, octave: u8) -> Self {
-        if octave < 0 || octave > 8 {
-            panic!("Octave must be between 0 and 8");
-        }
-        Note { name, octave }
-    }
-
     pub(crate) fn frequency(&self) -> f32 {
         let base_frequency = match self.name {
             NoteName::C => 261.63,
@@ -74,10 +67,10 @@ impl Note {
     }
 
     pub fn from_midi(midi_note: u8) -> Self {
-        if midi_note < 21 || midi_note > 108 {
+        if !(21..=108).contains(&midi_note) {
             panic!("MIDI note must be between 21 and 108");
         }
-        let octave = (midi_note / 12) as u8;
+        let octave = midi_note / 12;
         let note_index = midi_note % 12;
         let name = match note_index {
             0 => NoteName::C,
@@ -104,6 +97,13 @@ struct Voice {
     playing: bool,
 }
 
+/// Audio graph driven by a [`NotesPlayer`]: one waveform voice per bus, mixed
+/// and sent through the master filter and clipper.
+pub type NotesBus<const VOICES_COUNT: usize> = Bus<
+    Multiplexer2Effect<FirFilterEffect<32>, SoftClipEffect>,
+    MultiplexerSource<Bus<BypassEffect, WaveformSource>, VOICES_COUNT>,
+>;
+
 pub struct NotesPlayer<const VOICES_COUNT: usize> {
     voices: [Voice; VOICES_COUNT],
     // Currently processing event index
@@ -112,16 +112,10 @@ pub struct NotesPlayer<const VOICES_COUNT: usize> {
 }
 
 impl<const VOICES_COUNT: usize> NotesPlayer<VOICES_COUNT> {
-    pub fn new<'a>(
+    pub fn new(
         midi: TypedAsset<NotesAsset>,
         sample_rate: SampleRate,
-    ) -> (
-        NotesPlayer<VOICES_COUNT>,
-        Bus<
-            Multiplexer2Effect<FirFilterEffect<32>, SoftClipEffect>,
-            MultiplexerSource<Bus<BypassEffect, WaveformSource>, VOICES_COUNT>,
-        >,
-    ) {
+    ) -> (NotesPlayer<VOICES_COUNT>, NotesBus<VOICES_COUNT>) {
         let mut voices: [Voice; VOICES_COUNT] = unsafe { std::mem::zeroed() };
         let mut busses: [_; VOICES_COUNT] = unsafe { std::mem::zeroed() };
         let mut rng = tinyrand::Wyrand::default();
@@ -213,19 +207,18 @@ impl<const VOICES_COUNT: usize> NotesPlayer<VOICES_COUNT> {
         while self.index < events.len() {
             match &events[self.index] {
                 IRNoteEvent::NoteOn {
-                    channel,
+                    channel: _,
                     note,
-                    velocity,
+                    velocity: _,
                 } => {
                     self.play_note(player, *note);
                 }
-                IRNoteEvent::NoteOff { channel, note } => {
+                IRNoteEvent::NoteOff { channel: _, note } => {
                     self.stop_note(player, *note);
                 }
                 IRNoteEvent::Idle { ms } => {
                     sleep(Duration::from_micros((*ms * 1000.0) as u64));
                 }
-                _ => {}
             }
             self.index += 1;
         }
@@ -0,0 +1,512 @@
+use crate::assets::AudioAsset;
+use crate::entities::events::{AudioEventTarget, AudioEventTargetId};
+use crate::entities::{BlockInfo, Source};
+use crate::sample::PlanarBlock;
+use crate::{BLOCK_SIZE, CHANNELS_COUNT};
+use dawn_assets::ir::audio::AudioStreamDecoder;
+use dawn_assets::TypedAsset;
+use dawn_ecs::events::TickEvent;
+use evenio::component::Component;
+use evenio::entity::EntityId;
+use evenio::event::Receiver;
+use evenio::fetch::Fetcher;
+use evenio::world::World;
+use log::{error, info};
+use ringbuf::traits::{Consumer, Observer, Producer, Split};
+use ringbuf::{HeapCons, HeapProd, HeapRb};
+use std::io::Cursor;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{Builder, JoinHandle};
+use std::time::Duration;
+
+type Frame = [f32; CHANNELS_COUNT];
+
+/// Plays the clip decoded ahead of the playback by the `StreamFeeder`,
+/// so the long clips do not have to be decoded into the memory at once.
+/// The packed clips (see `IRAudio::pack`) are decoded chunk by chunk,
+/// the seeking and the looping decode only the chunk of the new position.
+/// The encoded clips (Ogg Vorbis or FLAC, see `IREncodedAudio`) are decoded
+/// block by block, seeking back restarts the decoding from the beginning.
+/// The source plays silence while the feeder falls behind.
+pub struct StreamSource {
+    id: AudioEventTargetId,
+    cached: bool,
+    consumer: HeapCons<Frame>,
+    flush: Arc<AtomicBool>,
+    frames: [Frame; BLOCK_SIZE],
+    output: PlanarBlock<f32>,
+}
+
+/// Decodes the clip of the `StreamSource` outside the audio thread.
+/// Attach it to the ECS to feed the source every tick,
+/// or move it to the `StreamDecodeThread`.
+#[derive(Component)]
+pub struct StreamFeeder {
+    producer: HeapProd<Frame>,
+    // Set by the feeder to discard the buffered frames, cleared by the source
+    flush: Arc<AtomicBool>,
+    clip: Option<TypedAsset<AudioAsset>>,
+    looping: bool,
+    // Next frame to be decoded
+    position: usize,
+    // Decoded chunk of the packed clip or block of the encoded one
+    chunk: Vec<f32>,
+    chunk_index: Option<usize>,
+    decoder: Option<AudioStreamDecoder<Cursor<EncodedClip>>>,
+    // First frame of the decoded block of the encoded clip
+    block_start: usize,
+}
+
+// Encoded data of the clip, owned by the decoder
+struct EncodedClip(TypedAsset<AudioAsset>);
+
+impl AsRef<[u8]> for EncodedClip {
+    fn as_ref(&self) -> &[u8] {
+        self.0.cast().0.encoded.as_ref().map_or(&[], |e| &e.data)
+    }
+}
+
+impl StreamSource {
+    /// The capacity is the number of the frames decoded ahead.
+    /// It must cover the time between the feeds with a margin.
+    pub fn new(capacity: usize) -> (StreamSource, StreamFeeder) {
+        let (producer, consumer) = HeapRb::<Frame>::new(capacity.max(BLOCK_SIZE)).split();
+        let flush = Arc::new(AtomicBool::new(false));
+        (
+            StreamSource {
+                id: AudioEventTargetId::new(),
+                cached: false,
+                consumer,
+                flush: Arc::clone(&flush),
+                frames: [[0.0; CHANNELS_COUNT]; BLOCK_SIZE],
+                output: PlanarBlock::default(),
+            },
+            StreamFeeder {
+                producer,
+                flush,
+                clip: None,
+                looping: false,
+                position: 0,
+                chunk: Vec::new(),
+                chunk_index: None,
+                decoder: None,
+                block_start: 0,
+            },
+        )
+    }
+
+    pub fn get_id(&self) -> AudioEventTargetId {
+        self.id
+    }
+}
+
+impl Source for StreamSource {
+    fn get_targets(&self) -> Vec<AudioEventTarget> {
+        // Controlled by the feeder
+        vec![]
+    }
+
+    #[inline(always)]
+    fn frame_start(&mut self) {
+        self.cached = false;
+    }
+
+    fn render(&mut self, info: &BlockInfo) -> &PlanarBlock<f32> {
+        if self.cached {
+            return &self.output;
+        }
+
+        if self.flush.load(Ordering::Acquire) {
+            self.consumer.clear();
+            self.flush.store(false, Ordering::Release);
+        }
+
+        let len = info.len();
+        let popped = self.consumer.pop_slice(&mut self.frames[..len]);
+        for (i, frame) in self.frames[..popped].iter().enumerate() {
+            for (channel, sample) in frame.iter().enumerate() {
+                self.output.samples[channel][i] = *sample;
+            }
+        }
+        for channel in 0..CHANNELS_COUNT {
+            self.output.samples[channel][popped..].fill(0.0);
+        }
+
+        self.cached = true;
+        &self.output
+    }
+}
+
+impl StreamFeeder {
+    /// Starts the clip from the beginning, replacing the current one.
+    pub fn play(&mut self, clip: TypedAsset<AudioAsset>, looping: bool) {
+        self.clip = Some(clip);
+        self.looping = looping;
+        self.chunk_index = None;
+        self.decoder = None;
+        self.seek(0);
+    }
+
+    /// Continues the playback from the frame of the clip.
+    pub fn seek(&mut self, frame: usize) {
+        self.position = frame;
+        self.flush.store(true, Ordering::Release);
+    }
+
+    pub fn stop(&mut self) {
+        self.clip = None;
+        self.flush.store(true, Ordering::Release);
+    }
+
+    /// Whether the clip is still being decoded.
+    /// The last decoded frames may still be buffered.
+    pub fn is_decoding(&self) -> bool {
+        self.clip.is_some()
+    }
+
+    /// Decodes the clip until the buffer of the source is full.
+    pub fn feed(&mut self) -> anyhow::Result<()> {
+        // The frames pushed before the source flushed the buffer would be lost
+        if self.flush.load(Ordering::Acquire) {
+            return Ok(());
+        }
+
+        while let Some(asset) = &self.clip {
+            if self.producer.is_full() {
+                break;
+            }
+
+            let clip = &asset.cast().0;
+            if self.position >= clip.length {
+                if self.looping && clip.length > 0 {
+                    self.position = 0;
+                    continue;
+                }
+                self.clip = None;
+                break;
+            }
+
+            // Samples from the position to the end of the chunk (or of the clip)
+            let channels = clip.channels.max(1) as usize;
+            let samples = if let Some(encoded) = &clip.encoded {
+                // The blocks are decoded in order, seeking back restarts the decoder
+                if self.decoder.is_none() || self.position < self.block_start {
+                    let reader = Cursor::new(EncodedClip(asset.clone()));
+                    self.decoder = Some(AudioStreamDecoder::new(encoded.codec, reader)?);
+                    self.block_start = 0;
+                    self.chunk.clear();
+                }
+                let decoder = self.decoder.as_mut().unwrap();
+                while self.position >= self.block_start + self.chunk.len() / channels {
+                    self.block_start += self.chunk.len() / channels;
+                    if !decoder.decode_block(&mut self.chunk)? {
+                        return Err(anyhow::anyhow!(
+                            "Stream ended at frame {}, {} expected",
+                            self.block_start,
+                            clip.length
+                        ));
+                    }
+                }
+                &self.chunk[(self.position - self.block_start) * channels..]
+            } else if clip.is_packed() {
+                let index = clip.chunk_at(self.position).ok_or_else(|| {
+                    anyhow::anyhow!("Frame {} is not in any chunk", self.position)
+                })?;
+                if self.chunk_index != Some(index) {
+                    clip.decode_chunk(index, &mut self.chunk)?;
+                    self.chunk_index = Some(index);
+                }
+                let offset = self.position - clip.chunks[index].start;
+                &self.chunk[offset * channels..]
+            } else {
+                &clip.data[self.position * channels..clip.length * channels]
+            };
+
+            let pushed = self
+                .producer
+                .push_iter(samples.chunks_exact(channels).map(|frame| {
+                    // Mono clips are played in both channels
+                    std::array::from_fn(|channel| frame[channel % channels])
+                }));
+            self.position += pushed;
+        }
+
+        Ok(())
+    }
+
+    /// Spawns the entity with the feeder, fed every tick.
+    /// Returns the entity to control the playback through.
+    pub fn attach_to_ecs(self, world: &mut World) -> EntityId {
+        let entity = world.spawn();
+        world.insert(entity, self);
+        world.add_handler(
+            move |_: Receiver<TickEvent>, mut feeders: Fetcher<&mut StreamFeeder>| {
+                if let Ok(feeder) = feeders.get_mut(entity) {
+                    if let Err(err) = feeder.feed() {
+                        error!("Failed to decode the stream: {}", err);
+                        feeder.stop();
+                    }
+                }
+            },
+        );
+        entity
+    }
+}
+
+/// Feeds the `StreamSource` from its own thread, so decoding the long
+/// encoded clips does not take the time of the ticks.
+/// The playback is controlled through the `feeder`,
+/// dropping the component stops the thread.
+#[derive(Component)]
+pub struct StreamDecodeThread {
+    feeder: Arc<Mutex<StreamFeeder>>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl StreamDecodeThread {
+    /// The buffer of the source is topped up every `interval`,
+    /// the capacity of the source must cover it with a margin.
+    pub fn spawn(feeder: StreamFeeder, interval: Duration) -> std::io::Result<Self> {
+        let feeder = Arc::new(Mutex::new(feeder));
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = Builder::new().name("audio-stream".to_string()).spawn({
+            let feeder = Arc::clone(&feeder);
+            let stop = Arc::clone(&stop);
+            move || {
+                info!("Stream decode thread started");
+                while !stop.load(Ordering::Acquire) {
+                    let mut feeder = feeder.lock().unwrap();
+                    if let Err(err) = feeder.feed() {
+                        error!("Failed to decode the stream: {}", err);
+                        feeder.stop();
+                    }
+                    drop(feeder);
+                    std::thread::sleep(interval);
+                }
+                info!("Stream decode thread stopped");
+            }
+        })?;
+
+        Ok(StreamDecodeThread {
+            feeder,
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    /// Blocks the decoding while the guard is held.
+    pub fn feeder(&self) -> MutexGuard<'_, StreamFeeder> {
+        self.feeder.lock().unwrap()
+    }
+
+    /// Spawns the entity with the thread.
+    /// Returns the entity to control the playback through.
+    pub fn attach_to_ecs(self, world: &mut World) -> EntityId {
+        let entity = world.spawn();
+        world.insert(entity, self);
+        entity
+    }
+}
+
+impl Drop for StreamDecodeThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dawn_assets::ir::audio::{IRAudio, IRAudioCodec, IREncodedAudio};
+    use dawn_assets::Asset;
+    use std::any::TypeId;
+    use std::ptr::NonNull;
+
+    // Stereo clip, the left channel is the frame index, the right one is negated
+    fn clip(length: usize, chunk_frames: Option<usize>) -> TypedAsset<AudioAsset> {
+        let mut ir = IRAudio {
+            data: (0..length).flat_map(|i| [i as f32, -(i as f32)]).collect(),
+            sample_rate: 44100,
+            channels: 2,
+            length,
+            chunks: vec![],
+            encoded: None,
+            captions: None,
+        };
+        if let Some(chunk_frames) = chunk_frames {
+            ir.pack(chunk_frames).unwrap();
+            assert_eq!(ir.chunks.len(), length.div_ceil(chunk_frames));
+        }
+        asset(ir)
+    }
+
+    fn crc8(bytes: &[u8]) -> u8 {
+        bytes.iter().fold(0, |crc, byte| {
+            (0..8).fold(crc ^ byte, |crc, _| {
+                (crc << 1) ^ if crc & 0x80 != 0 { 0x07 } else { 0 }
+            })
+        })
+    }
+
+    fn crc16(bytes: &[u8]) -> u16 {
+        bytes.iter().fold(0, |crc, byte| {
+            (0..8).fold(crc ^ (*byte as u16) << 8, |crc, _| {
+                (crc << 1) ^ if crc & 0x8000 != 0 { 0x8005 } else { 0 }
+            })
+        })
+    }
+
+    // Same as `clip`, but encoded into 16-bit FLAC of the verbatim frames
+    // of 64 samples, so the samples are scaled by 1/32768
+    fn flac_clip(length: usize) -> TypedAsset<AudioAsset> {
+        const BLOCK: usize = 64;
+        let mut data = b"fLaC\x80\0\0\x22".to_vec();
+        data.extend_from_slice(&(BLOCK as u16).to_be_bytes());
+        data.extend_from_slice(&(BLOCK as u16).to_be_bytes());
+        data.extend_from_slice(&[0; 6]);
+        let info = (44100u64 << 44) | (1 << 41) | (15 << 36) | length as u64;
+        data.extend_from_slice(&info.to_be_bytes());
+        data.extend_from_slice(&[0; 16]);
+
+        for (index, start) in (0..length).step_by(BLOCK).enumerate() {
+            let frames = BLOCK.min(length - start);
+            let mut frame = vec![0xFF, 0xF8, 0x60, 0x18, index as u8, frames as u8 - 1];
+            frame.push(crc8(&frame));
+            for sign in [1, -1] {
+                // Verbatim subframe
+                frame.push(0x02);
+                for i in start..start + frames {
+                    frame.extend_from_slice(&(sign * i as i16).to_be_bytes());
+                }
+            }
+            frame.extend_from_slice(&crc16(&frame).to_be_bytes());
+            data.extend_from_slice(&frame);
+        }
+
+        asset(IRAudio {
+            length,
+            encoded: Some(IREncodedAudio {
+                codec: IRAudioCodec::Flac,
+                data,
+            }),
+            ..Default::default()
+        })
+    }
+
+    fn asset(ir: IRAudio) -> TypedAsset<AudioAsset> {
+        let ptr = Box::into_raw(Box::new(AudioAsset(ir)));
+        TypedAsset::new(Asset::new(
+            TypeId::of::<AudioAsset>(),
+            NonNull::new(ptr as *mut ()).unwrap(),
+        ))
+    }
+
+    fn render(source: &mut StreamSource) -> Vec<f32> {
+        source.frame_start();
+        let output = source.render(&BlockInfo::new(0, 44100));
+        assert_eq!(output.samples[0].map(|s| -s), output.samples[1]);
+        output.samples[0].to_vec()
+    }
+
+    #[test]
+    fn packed_clip_seek_and_loop() {
+        let length = BLOCK_SIZE * 3 + 100;
+        let (mut source, mut feeder) = StreamSource::new(BLOCK_SIZE * 2);
+        feeder.play(clip(length, Some(1000)), true);
+
+        // Nothing is decoded until the source flushes the buffer
+        feeder.feed().unwrap();
+        assert_eq!(render(&mut source), vec![0.0; BLOCK_SIZE]);
+
+        feeder.feed().unwrap();
+        let expected = (0..BLOCK_SIZE).map(|i| i as f32).collect::<Vec<_>>();
+        assert_eq!(render(&mut source), expected);
+
+        // Seeking right before the end, the clip wraps around
+        feeder.seek(length - 10);
+        render(&mut source);
+        feeder.feed().unwrap();
+        let expected = (length - 10..length)
+            .chain(0..BLOCK_SIZE - 10)
+            .map(|i| i as f32)
+            .collect::<Vec<_>>();
+        assert_eq!(render(&mut source), expected);
+    }
+
+    #[test]
+    fn unpacked_clip_ends() {
+        let (mut source, mut feeder) = StreamSource::new(BLOCK_SIZE * 4);
+        feeder.play(clip(BLOCK_SIZE + 5, None), false);
+        render(&mut source);
+        feeder.feed().unwrap();
+        assert!(!feeder.is_decoding());
+
+        render(&mut source);
+        let tail = render(&mut source);
+        let expected = (BLOCK_SIZE..BLOCK_SIZE + 5)
+            .map(|i| i as f32)
+            .chain(std::iter::repeat_n(0.0, BLOCK_SIZE - 5))
+            .collect::<Vec<_>>();
+        assert_eq!(tail, expected);
+    }
+
+    fn scaled(frames: impl Iterator<Item = usize>) -> Vec<f32> {
+        frames.map(|i| i as f32 / 32768.0).collect()
+    }
+
+    #[test]
+    fn encoded_clip_seek_back_and_loop() {
+        let length = BLOCK_SIZE * 2 + 100;
+        let (mut source, mut feeder) = StreamSource::new(BLOCK_SIZE);
+        feeder.play(flac_clip(length), true);
+        render(&mut source);
+        feeder.feed().unwrap();
+        assert_eq!(render(&mut source), scaled(0..BLOCK_SIZE));
+
+        // Wraps around to the beginning, restarting the decoder
+        feeder.seek(length - 10);
+        render(&mut source);
+        feeder.feed().unwrap();
+        assert_eq!(
+            render(&mut source),
+            scaled((length - 10..length).chain(0..BLOCK_SIZE - 10))
+        );
+
+        // Seeking back decodes from the beginning again
+        feeder.seek(BLOCK_SIZE + 5);
+        render(&mut source);
+        feeder.feed().unwrap();
+        feeder.seek(5);
+        render(&mut source);
+        feeder.feed().unwrap();
+        assert_eq!(render(&mut source), scaled(5..BLOCK_SIZE + 5));
+    }
+
+    #[test]
+    fn decode_thread_feeds_source() {
+        let (mut source, feeder) = StreamSource::new(BLOCK_SIZE);
+        let thread = StreamDecodeThread::spawn(feeder, Duration::from_millis(1)).unwrap();
+        thread.feeder().play(flac_clip(BLOCK_SIZE / 2), false);
+
+        // The thread decodes the clip once the source has flushed the buffer
+        render(&mut source);
+        for _ in 0..1000 {
+            if !thread.feeder().is_decoding() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(!thread.feeder().is_decoding());
+
+        let expected = scaled(0..BLOCK_SIZE / 2)
+            .into_iter()
+            .chain(std::iter::repeat_n(0.0, BLOCK_SIZE / 2))
+            .collect::<Vec<_>>();
+        assert_eq!(render(&mut source), expected);
+    }
+}
@@ -0,0 +1,308 @@
+//! Numerical checks of the DSP entities: the test signals, rendering them
+//! through the effects block by block, and the analysis of the output
+//! (magnitude response, harmonic distortion, artifacts at the block boundaries).
+//!
+//! All the signals are mono, rendered in every channel at `SAMPLE_RATE`.
+
+use crate::dsp::{detect_features, disable_all_features};
+use crate::entities::{BlockInfo, Effect};
+use crate::sample::PlanarBlock;
+use crate::{SampleRate, BLOCK_SIZE, CHANNELS_COUNT};
+use std::f64::consts::PI;
+use std::ops::RangeInclusive;
+use tinyrand::{Rand, Seeded, Wyrand};
+
+pub(crate) const SAMPLE_RATE: SampleRate = 48000;
+
+/// Unit impulse followed by the zeros. The output is the impulse response.
+pub(crate) fn impulse(len: usize) -> Vec<f32> {
+    let mut signal = vec![0.0; len];
+    signal[0] = 1.0;
+    signal
+}
+
+/// Sine of exactly `cycles` periods in `len` samples: all its energy is in the
+/// bin `cycles` of the `len`-point spectrum, so no window is needed.
+pub(crate) fn sine(cycles: usize, len: usize, amplitude: f32) -> Vec<f32> {
+    (0..len)
+        .map(|i| amplitude * (2.0 * PI * (cycles * i) as f64 / len as f64).sin() as f32)
+        .collect()
+}
+
+/// Linear sweep from `from` to `to` Hz.
+pub(crate) fn chirp(from: f32, to: f32, len: usize, amplitude: f32) -> Vec<f32> {
+    let duration = len as f64 / SAMPLE_RATE as f64;
+    let rate = (to - from) as f64 / duration;
+    (0..len)
+        .map(|i| {
+            let t = i as f64 / SAMPLE_RATE as f64;
+            let phase = 2.0 * PI * (from as f64 * t + rate * t * t / 2.0);
+            amplitude * phase.sin() as f32
+        })
+        .collect()
+}
+
+/// Uniform white noise, the same for the same seed.
+pub(crate) fn noise(len: usize, amplitude: f32, seed: u64) -> Vec<f32> {
+    let mut rng = Wyrand::seed(seed);
+    (0..len)
+        .map(|_| amplitude * (rng.next_u32() as f32 / u32::MAX as f32 * 2.0 - 1.0))
+        .collect()
+}
+
+/// Renders the signal through the effect, the blocks are `lengths` samples
+/// long in turn (see `BlockInfo::partial`). `&[BLOCK_SIZE]` renders
+/// the full blocks, as the player does.
+pub(crate) fn render<E: Effect + ?Sized>(
+    effect: &mut E,
+    signal: &[f32],
+    lengths: &[usize],
+) -> [Vec<f32>; CHANNELS_COUNT] {
+    assert!(lengths.iter().all(|len| (1..=BLOCK_SIZE).contains(len)));
+
+    let mut output: [Vec<f32>; CHANNELS_COUNT] = Default::default();
+    let mut input_block = PlanarBlock::default();
+    let mut output_block = PlanarBlock::default();
+    let mut start = 0;
+    for len in lengths.iter().cycle() {
+        if start >= signal.len() {
+            break;
+        }
+        let len = (*len).min(signal.len() - start);
+        input_block.silence();
+        for channel in 0..CHANNELS_COUNT {
+            input_block.samples[channel][..len].copy_from_slice(&signal[start..start + len]);
+        }
+        effect.render(
+            &input_block,
+            &mut output_block,
+            &BlockInfo::partial(start, SAMPLE_RATE, len),
+        );
+        for (channel, output) in output.iter_mut().enumerate() {
+            output.extend_from_slice(&output_block.samples[channel][..len]);
+        }
+        start += len;
+    }
+    output
+}
+
+/// Asserts the output does not depend on how the signal is split into the blocks,
+/// i.e. the effect keeps its state right at the block boundaries.
+pub(crate) fn assert_block_independent<E: Effect>(
+    make: impl Fn() -> E,
+    signal: &[f32],
+    tolerance: f32,
+) {
+    let expected = render(&mut make(), signal, &[BLOCK_SIZE]);
+    for lengths in [
+        &[1][..],
+        &[BLOCK_SIZE / 3, 7, BLOCK_SIZE],
+        &[BLOCK_SIZE - 1, BLOCK_SIZE / 2 + 1],
+    ] {
+        let actual = render(&mut make(), signal, lengths);
+        assert_close(
+            &actual,
+            &expected,
+            tolerance,
+            &format!("blocks {:?}", lengths),
+        );
+    }
+}
+
+/// Asserts the SIMD variants of the DSP functions used by the effect
+/// give the same output as the fallback ones.
+pub(crate) fn assert_simd_matches<E: Effect>(make: impl Fn() -> E, signal: &[f32], tolerance: f32) {
+    disable_all_features();
+    let expected = render(&mut make(), signal, &[BLOCK_SIZE]);
+    detect_features();
+    let actual = render(&mut make(), signal, &[BLOCK_SIZE]);
+    assert_close(&actual, &expected, tolerance, "SIMD");
+}
+
+fn assert_close(
+    actual: &[Vec<f32>; CHANNELS_COUNT],
+    expected: &[Vec<f32>; CHANNELS_COUNT],
+    tolerance: f32,
+    what: &str,
+) {
+    for channel in 0..CHANNELS_COUNT {
+        for (i, (a, e)) in actual[channel].iter().zip(&expected[channel]).enumerate() {
+            assert!(
+                (a - e).abs() <= tolerance,
+                "{}: {} != {} at sample {}, channel {}",
+                what,
+                a,
+                e,
+                i,
+                channel
+            );
+        }
+    }
+}
+
+/// Magnitudes of the spectrum bins up to the Nyquist frequency.
+/// The signal is zero-padded to the power of two. Not scaled, so the spectrum
+/// of the impulse response is the magnitude response of the effect.
+pub(crate) struct Spectrum {
+    magnitudes: Vec<f32>,
+    bin_width: f32,
+}
+
+impl Spectrum {
+    pub(crate) fn new(signal: &[f32]) -> Self {
+        let len = signal.len().next_power_of_two();
+        let mut re: Vec<f64> = signal.iter().map(|s| *s as f64).collect();
+        re.resize(len, 0.0);
+        let mut im = vec![0.0; len];
+        fft(&mut re, &mut im);
+
+        Spectrum {
+            magnitudes: (0..=len / 2).map(|k| re[k].hypot(im[k]) as f32).collect(),
+            bin_width: SAMPLE_RATE as f32 / len as f32,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.magnitudes.len()
+    }
+
+    pub(crate) fn frequency(&self, bin: usize) -> f32 {
+        bin as f32 * self.bin_width
+    }
+
+    pub(crate) fn magnitude(&self, bin: usize) -> f32 {
+        self.magnitudes[bin]
+    }
+
+    pub(crate) fn db(&self, bin: usize) -> f32 {
+        20.0 * self.magnitudes[bin].max(f32::MIN_POSITIVE).log10()
+    }
+}
+
+// In-place iterative radix-2 FFT, the length is the power of two
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    debug_assert!(n.is_power_of_two() && im.len() == n);
+
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+    while size <= n {
+        let angle = -2.0 * PI / size as f64;
+        for start in (0..n).step_by(size) {
+            for k in 0..size / 2 {
+                let (w_im, w_re) = (angle * k as f64).sin_cos();
+                let (a, b) = (start + k, start + k + size / 2);
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        size <<= 1;
+    }
+}
+
+/// Asserts the magnitude response, in dB, is in the range `expected` returns
+/// for each frequency. `None` skips the frequency, e.g. in the transition band.
+pub(crate) fn assert_magnitude_response(
+    impulse_response: &[f32],
+    expected: impl Fn(f32) -> Option<RangeInclusive<f32>>,
+) {
+    let spectrum = Spectrum::new(impulse_response);
+    for bin in 0..spectrum.len() {
+        let frequency = spectrum.frequency(bin);
+        if let Some(range) = expected(frequency) {
+            let db = spectrum.db(bin);
+            assert!(
+                range.contains(&db),
+                "{:.1} dB at {:.0} Hz is out of {:?}",
+                db,
+                frequency,
+                range
+            );
+        }
+    }
+}
+
+/// Total harmonic distortion of the output of `sine(cycles, len, ..)`:
+/// RMS of the harmonics relative to the fundamental.
+/// The length must be the power of two, so the harmonics fall into the bins.
+pub(crate) fn thd(output: &[f32], cycles: usize) -> f32 {
+    assert!(output.len().is_power_of_two());
+    let spectrum = Spectrum::new(output);
+    let harmonics: f32 = (2..)
+        .map(|harmonic| harmonic * cycles)
+        .take_while(|bin| *bin < spectrum.len())
+        .map(|bin| spectrum.magnitude(bin).powi(2))
+        .sum();
+    harmonics.sqrt() / spectrum.magnitude(cycles)
+}
+
+/// Block boundaries where the output jumps: the second difference there is more
+/// than `factor` times larger than the ones of the nearby samples.
+/// Meaningful for the smooth signals, e.g. `sine` and `chirp`.
+pub(crate) fn boundary_artifacts(output: &[f32], factor: f32) -> Vec<usize> {
+    const NEARBY: usize = 16;
+
+    let second_difference = |i: usize| (output[i + 1] - 2.0 * output[i] + output[i - 1]).abs();
+    (BLOCK_SIZE..output.len().saturating_sub(NEARBY + 1))
+        .step_by(BLOCK_SIZE)
+        .filter(|boundary| {
+            // The jump between the blocks shows in the samples on both sides
+            let jump = second_difference(boundary - 1).max(second_difference(*boundary));
+            let nearby = (boundary - NEARBY..boundary - 1)
+                .chain(boundary + 1..boundary + NEARBY)
+                .map(second_difference)
+                .fold(0.0f32, f32::max);
+            jump > factor * nearby + f32::EPSILON
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn thd_of_square_wave() {
+        let square: Vec<f32> = sine(8, 4096, 1.0).iter().map(|s| s.signum()).collect();
+        // sqrt(π² / 8 - 1) of the ideal square wave
+        assert!((thd(&square, 8) - 0.483).abs() < 0.01);
+        assert!(thd(&sine(8, 4096, 1.0), 8) < 1e-5);
+    }
+
+    #[test]
+    fn magnitude_response_of_delay() {
+        let mut delayed = impulse(256);
+        delayed.rotate_right(5);
+        assert_magnitude_response(&delayed, |_| Some(-0.01..=0.01));
+    }
+
+    #[test]
+    fn detects_boundary_artifacts() {
+        let mut signal = chirp(100.0, 2000.0, BLOCK_SIZE * 8, 0.5);
+        assert!(boundary_artifacts(&signal, 4.0).is_empty());
+
+        // Offset of the state changed at the block start
+        for sample in &mut signal[BLOCK_SIZE * 3..] {
+            *sample += 0.05;
+        }
+        assert_eq!(boundary_artifacts(&signal, 4.0), vec![BLOCK_SIZE * 3]);
+    }
+}
@@ -1,14 +1,29 @@
-#![feature(test)]
-#![feature(maybe_uninit_slice)]
+#![cfg_attr(test, feature(test))]
 
+mod alloc_check;
 pub mod assets;
 pub mod backend;
+pub mod beat;
+pub mod captions;
 mod cpal;
 pub mod dsp;
 pub mod entities;
+pub mod oneshot;
 pub mod player;
+mod pool;
+pub mod reverb_zones;
 mod sample;
 
+/// Version and the enabled features of the crate.
+pub fn build_info() -> dawn_util::build_info::CrateBuildInfo {
+    dawn_util::crate_build_info![
+        "alloc-check",
+        "block-size-128",
+        "block-size-256",
+        "block-size-1024"
+    ]
+}
+
 pub type SamplesCount = usize;
 pub type SampleRate = usize;
 pub type ChannelsCount = usize;
@@ -22,6 +37,20 @@ pub type SampleType = f32;
 /// This is a stereo sound device, so it has 2 channels.
 const CHANNELS_COUNT: ChannelsCount = 2;
 
-/// Hardcoded size of the audio block.
+/// Size of the audio block.
 /// This is the number of samples processed in one block.
-const BLOCK_SIZE: SamplesCount = 512;
+/// Selected with the `block-size-*` features (512 by default): the smaller
+/// blocks lower the latency, the larger ones spend less CPU per sample.
+/// The features are additive: if several crates of the build enable different
+/// sizes, the first enabled one in the order `block-size-1024`, `block-size-256`,
+/// `block-size-128` is used, and the build script warns about the conflict.
+/// The default 512 is only used when none of them is enabled.
+pub const BLOCK_SIZE: SamplesCount = if cfg!(feature = "block-size-1024") {
+    1024
+} else if cfg!(feature = "block-size-256") {
+    256
+} else if cfg!(feature = "block-size-128") {
+    128
+} else {
+    512
+};
@@ -0,0 +1,152 @@
+use crate::assets::AudioAsset;
+use crate::entities::events::{AudioEvent, AudioEventTargetId, AudioEventType};
+use crate::entities::sources::actor::{ActorID, ActorsSource, ActorsSourceEvent};
+use crossbeam_queue::ArrayQueue;
+use dawn_assets::TypedAsset;
+use dawn_ecs::events::TickEvent;
+use evenio::component::Component;
+use evenio::entity::EntityId;
+use evenio::event::{Despawn, GlobalEvent, Insert, Receiver, Sender, Spawn};
+use evenio::fetch::Single;
+use evenio::handler::IntoHandler;
+use evenio::world::World;
+use glam::Vec3;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tinyrand::{Rand, Wyrand};
+
+#[derive(Debug, Clone)]
+pub struct PlaySoundOptions {
+    /// Position of the sound. Sounds without it are not attenuated by the distance.
+    pub position: Option<Vec3>,
+    /// The volume is picked randomly from the range.
+    pub volume: (f32, f32),
+    /// The playback speed is picked randomly from the range.
+    pub pitch: (f32, f32),
+}
+
+impl Default for PlaySoundOptions {
+    fn default() -> Self {
+        PlaySoundOptions {
+            position: None,
+            volume: (1.0, 1.0),
+            pitch: (1.0, 1.0),
+        }
+    }
+}
+
+/// Plays the clip once. The sound gets its own entity with the `PlayingSound`
+/// component, which is despawned when the playback completes.
+/// Despawning the entity earlier stops the sound.
+#[derive(GlobalEvent)]
+pub struct PlaySound {
+    pub clip: TypedAsset<AudioAsset>,
+    pub options: PlaySoundOptions,
+}
+
+/// Shortcut for sending the `PlaySound` event:
+/// `sender.send(play_sound(clip, PlaySoundOptions::default()))`.
+pub fn play_sound(clip: TypedAsset<AudioAsset>, options: PlaySoundOptions) -> PlaySound {
+    PlaySound { clip, options }
+}
+
+/// Component of the entity of the sound being played.
+#[derive(Component, Debug)]
+#[component(immutable)]
+pub struct PlayingSound(pub ActorID);
+
+/// Sent when the sound finished playing, right before its entity is despawned.
+#[derive(GlobalEvent)]
+pub struct SoundFinished(pub EntityId);
+
+/// Manages the fire-and-forget sounds played by the `ActorsSource`.
+#[derive(Component)]
+pub struct OneShots {
+    target: AudioEventTargetId,
+    finished: Arc<ArrayQueue<ActorID>>,
+    playing: HashMap<ActorID, EntityId>,
+    rng: Wyrand,
+}
+
+impl OneShots {
+    /// Must be created before the source is moved into the player.
+    pub fn new(actors: &ActorsSource) -> Self {
+        OneShots {
+            target: actors.get_id(),
+            finished: actors.finished_queue(),
+            playing: HashMap::new(),
+            rng: Wyrand::default(),
+        }
+    }
+
+    fn random(&mut self, range: (f32, f32)) -> f32 {
+        let t = self.rng.next_u32() as f32 / u32::MAX as f32;
+        range.0 + (range.1 - range.0) * t
+    }
+
+    /// Handles the `PlaySound` events and cleans up the finished sounds on each tick.
+    /// Requires the `Player` attached to the same world.
+    /// This function moves the component into the ECS world.
+    pub fn attach_to_ecs(self, world: &mut World) {
+        let entity = world.spawn();
+        world.insert(entity, self);
+
+        world.add_handler(Self::play_handler);
+        world.add_handler(Self::despawn_handler);
+        world.add_handler(Self::tick_handler.low());
+    }
+
+    fn play_handler(
+        r: Receiver<PlaySound>,
+        mut one_shots: Single<&mut OneShots>,
+        mut sender: Sender<(Spawn, Insert<PlayingSound>, AudioEvent)>,
+    ) {
+        let options = &r.event.options;
+        let gain = one_shots.random(options.volume);
+        let pitch = one_shots.random(options.pitch);
+
+        let id = ActorID::new();
+        let entity = sender.spawn();
+        sender.insert(entity, PlayingSound(id));
+        one_shots.playing.insert(id, entity);
+        sender.send(AudioEvent::new(
+            one_shots.target,
+            AudioEventType::Actors(ActorsSourceEvent::AddActor {
+                id: Some(id),
+                pos: options.position,
+                gain,
+                pitch,
+                clip: r.event.clip.clone(),
+            }),
+        ));
+    }
+
+    fn despawn_handler(
+        r: Receiver<Despawn, &PlayingSound>,
+        mut one_shots: Single<&mut OneShots>,
+        mut sender: Sender<AudioEvent>,
+    ) {
+        // Still playing, so it's despawned by the user
+        let id = r.query.0;
+        if one_shots.playing.remove(&id).is_some() {
+            sender.send(AudioEvent::new(
+                one_shots.target,
+                AudioEventType::Actors(ActorsSourceEvent::RemoveActor(id)),
+            ));
+        }
+    }
+
+    fn tick_handler(
+        _: Receiver<TickEvent>,
+        mut one_shots: Single<&mut OneShots>,
+        mut sender: Sender<(Despawn, SoundFinished)>,
+    ) {
+        while let Some(id) = one_shots.finished.pop() {
+            // The actors added directly are not tracked
+            if let Some(entity) = one_shots.playing.remove(&id) {
+                sender.send(SoundFinished(entity));
+                sender.despawn(entity);
+            }
+        }
+    }
+}
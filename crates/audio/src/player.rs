@@ -1,14 +1,18 @@
+use crate::alloc_check::NoAllocGuard;
 use crate::backend::{
-    InternalBackendConfig, PlayerBackend, PlayerBackendConfig, PlayerBackendError,
-    PlayerBackendTrait,
+    BackendStats, DeviceChangeReason, InternalBackendConfig, PlayerBackend, PlayerBackendConfig,
+    PlayerBackendError, PlayerBackendTrait,
 };
+use crate::beat::{BeatEvent, Tempo};
 use crate::dsp::detect_features;
+use crate::entities::effects::limiter::{LimiterMeter, LimiterMonitorEvent};
 use crate::entities::events::AudioEvent;
 use crate::entities::sinks::InterleavedSink;
 use crate::entities::Source;
 use crate::sample::MappedInterleavedBuffer;
 use crate::{ChannelsCount, SampleRate, SampleType, SamplesCount, BLOCK_SIZE, CHANNELS_COUNT};
 use crossbeam_queue::ArrayQueue;
+use dawn_ecs::av_sync::AudioSyncEvent;
 use dawn_ecs::events::TickEvent;
 use dawn_util::profile::{Counter, MonitorSample, Stopwatch};
 use evenio::component::Component;
@@ -18,11 +22,14 @@ use evenio::handler::IntoHandler;
 use evenio::world::World;
 use log::{info, warn};
 use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 const EVENTS_QUEUE_CAPACITY: usize = 1024;
 const MONITOR_QUEUE_CAPACITY: usize = 32;
+const TEMPO_QUEUE_CAPACITY: usize = 16;
+const BEATS_QUEUE_CAPACITY: usize = 64;
 
 /// Event sent every second with profiling data about the audio player.
 #[derive(GlobalEvent)]
@@ -40,14 +47,40 @@ pub struct PlayerMonitorEvent {
     /// Average renderer load in percent
     pub load: MonitorSample<f32>,
 
+    /// Underruns of the device buffer since the start
+    pub underruns: usize,
+    /// Blocks filled by repeating the previous one after an underrun, since the start
+    pub panic_fills: usize,
+
     // Player parameters
     pub sample_rate: SampleRate,
     pub channels: ChannelsCount,
     pub block_size: SamplesCount,
+    /// Size of the device buffer, grown on repeated underruns
+    pub buffer_size: SamplesCount,
+}
+
+/// Sent when the output stream is moved to another device
+/// (e.g. the headphones were plugged in). The audio graph keeps playing
+/// from where it was, only the samples buffered by the old device are lost.
+#[derive(GlobalEvent, Debug, Clone)]
+pub struct AudioDeviceEvent {
+    pub previous: Option<String>,
+    /// Name of the device the stream is played on now, or the error
+    /// if it could not be opened. Until another device is connected,
+    /// nothing is played.
+    pub current: Result<String, PlayerBackendError>,
+    pub reason: DeviceChangeReason,
 }
 
+/// Selects the output device of the player by name (see `Player::output_devices`).
+/// `None` follows the default output device.
+#[derive(GlobalEvent, Debug, Clone)]
+pub struct AudioDeviceRequest(pub Option<String>);
+
 trait PlayerMonitorTrait {
     fn set_queue(&mut self, _queue: Arc<ArrayQueue<PlayerMonitorEvent>>) {}
+    fn set_backend_stats(&mut self, _stats: Arc<BackendStats>) {}
     fn events_start(&mut self) {}
     fn events_end(&mut self, _processed: usize) {}
     fn renderer_start(&mut self) {}
@@ -56,6 +89,7 @@ trait PlayerMonitorTrait {
 
 struct PlayerMonitor {
     queue: Option<Arc<ArrayQueue<PlayerMonitorEvent>>>,
+    backend_stats: Option<Arc<BackendStats>>,
     last_update: Instant,
     sample_rate: SampleRate,
     renderer_time: Stopwatch,
@@ -68,6 +102,7 @@ impl PlayerMonitor {
     fn new(sample_rate: SampleRate) -> Self {
         PlayerMonitor {
             queue: None,
+            backend_stats: None,
             last_update: Instant::now(),
             sample_rate,
             renderer_time: Stopwatch::new(0.5),
@@ -83,6 +118,10 @@ impl PlayerMonitorTrait for PlayerMonitor {
         self.queue = Some(queue);
     }
 
+    fn set_backend_stats(&mut self, stats: Arc<BackendStats>) {
+        self.backend_stats = Some(stats);
+    }
+
     fn events_start(&mut self) {
         self.renderer_tps.count(1);
         self.events.start();
@@ -129,15 +168,26 @@ impl PlayerMonitorTrait for PlayerMonitor {
                     total_time_max.as_secs_f32() / allowed_time,
                 );
 
+                let (underruns, panic_fills, buffer_size) = match &self.backend_stats {
+                    Some(stats) => (
+                        stats.underruns.load(Ordering::Relaxed),
+                        stats.panic_fills.load(Ordering::Relaxed),
+                        stats.buffer_size.load(Ordering::Relaxed),
+                    ),
+                    None => (0, 0, BLOCK_SIZE),
+                };
                 let frame = PlayerMonitorEvent {
                     render: renderer_time,
                     render_tps,
                     events: events_time,
                     events_tps: self.events_tps.get(),
                     load,
+                    underruns,
+                    panic_fills,
                     sample_rate: self.sample_rate,
                     channels: CHANNELS_COUNT,
                     block_size: BLOCK_SIZE,
+                    buffer_size,
                 };
 
                 // Send the monitoring frame to the queue
@@ -151,6 +201,101 @@ impl PlayerMonitorTrait for PlayerMonitor {
 
 struct DummyPlayerMonitor;
 
+// Sample of the last rendered block and the instant it is heard at.
+// Written by the audio thread only, so the readers retry while
+// the sequence is odd or changes under them
+struct SyncPoint {
+    epoch: Instant,
+    sequence: AtomicUsize,
+    samples: AtomicUsize,
+    // Since the epoch
+    nanos: AtomicU64,
+}
+
+/// Position of the audio stream played by the player.
+/// Use it to schedule the events from the main loop with `AudioEvent::at`.
+/// Can be cloned and shared between threads.
+#[derive(Clone)]
+pub struct AudioClock {
+    samples: Arc<AtomicUsize>,
+    sample_rate: SampleRate,
+    sync: Arc<SyncPoint>,
+}
+
+impl AudioClock {
+    pub(crate) fn new(sample_rate: SampleRate) -> Self {
+        AudioClock {
+            samples: Arc::new(AtomicUsize::new(0)),
+            sample_rate,
+            sync: Arc::new(SyncPoint {
+                epoch: Instant::now(),
+                sequence: AtomicUsize::new(0),
+                samples: AtomicUsize::new(0),
+                nanos: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    // Called by the audio thread before rendering the block starting at the sample
+    fn mark(&self, samples: SamplesCount, heard: Instant) {
+        let sync = &self.sync;
+        let nanos = heard.saturating_duration_since(sync.epoch).as_nanos() as u64;
+        let sequence = sync.sequence.load(Ordering::Relaxed);
+        sync.sequence.store(sequence + 1, Ordering::Relaxed);
+        std::sync::atomic::fence(Ordering::Release);
+        sync.samples.store(samples, Ordering::Relaxed);
+        sync.nanos.store(nanos, Ordering::Relaxed);
+        sync.sequence.store(sequence + 2, Ordering::Release);
+    }
+
+    /// The sample of the last rendered block and the instant it is heard at,
+    /// i.e. the time of the device callback plus the output latency reported by the device.
+    /// `None` until the first block is rendered.
+    pub fn sync_point(&self) -> Option<(SamplesCount, Instant)> {
+        let sync = &self.sync;
+        loop {
+            let sequence = sync.sequence.load(Ordering::Acquire);
+            if sequence == 0 {
+                return None;
+            }
+            if sequence % 2 == 1 {
+                std::hint::spin_loop();
+                continue;
+            }
+            let samples = sync.samples.load(Ordering::Relaxed);
+            let nanos = sync.nanos.load(Ordering::Relaxed);
+            std::sync::atomic::fence(Ordering::Acquire);
+            if sync.sequence.load(Ordering::Relaxed) == sequence {
+                return Some((samples, sync.epoch + Duration::from_nanos(nanos)));
+            }
+        }
+    }
+
+    /// Number of samples passed to the backend since the start of the player.
+    #[inline(always)]
+    pub fn samples(&self) -> SamplesCount {
+        self.samples.load(Ordering::Acquire)
+    }
+
+    /// Same as `samples`, but in seconds.
+    #[inline(always)]
+    pub fn seconds(&self) -> f64 {
+        self.samples() as f64 / self.sample_rate as f64
+    }
+
+    #[inline(always)]
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    /// The earliest sample the event pushed right now is guaranteed
+    /// to be applied at exactly. The audio ahead of it may already be rendered.
+    #[inline(always)]
+    pub fn earliest(&self) -> SamplesCount {
+        self.samples() + 2 * BLOCK_SIZE
+    }
+}
+
 impl PlayerMonitorTrait for DummyPlayerMonitor {}
 
 /// The audio player is a component that handles audio output and processing.
@@ -164,12 +309,24 @@ pub struct Player {
     events: Arc<ArrayQueue<AudioEvent>>,
     // Queue for transferring monitor frames to the main thread.
     monitor_queue: Arc<ArrayQueue<PlayerMonitorEvent>>,
+    // Number of the samples played, updated by the audio thread.
+    clock: AudioClock,
+    // Tempo changes of the musical clock.
+    tempo: Arc<ArrayQueue<Tempo>>,
+    // Beats of the musical clock reported by the audio thread.
+    beats: Arc<ArrayQueue<BeatEvent>>,
+    // Meter of the master bus limiter, if any.
+    limiter: Option<LimiterMeter>,
+    // The last sync point sent to the ECS.
+    synced: Option<SamplesCount>,
 }
 
 impl Drop for Player {
     fn drop(&mut self) {
         info!("Dropping Player");
-        self.backend.close().unwrap();
+        if let Err(err) = self.backend.close() {
+            warn!("Failed to close the audio backend: {}", err);
+        }
     }
 }
 
@@ -178,6 +335,7 @@ pub enum PlayerError {
     InvalidSampleRate(SampleRate),
     InvalidChannels(ChannelsCount),
     InvalidBufferSize(SamplesCount),
+    InvalidTempo,
     FailedToStartBackend(PlayerBackendError),
     FailedToCreateBackend(PlayerBackendError),
 }
@@ -194,6 +352,9 @@ impl Display for PlayerError {
             PlayerError::InvalidBufferSize(size) => {
                 write!(f, "Invalid buffer size: {}", size)
             }
+            PlayerError::InvalidTempo => {
+                write!(f, "Invalid tempo")
+            }
             PlayerError::FailedToStartBackend(err) => {
                 write!(f, "Failed to start backend: {}", err)
             }
@@ -246,7 +407,9 @@ impl Player {
         }
 
         // Setup monitor
-        let monitor_queue = Arc::new(ArrayQueue::<PlayerMonitorEvent>::new(MONITOR_QUEUE_CAPACITY));
+        let monitor_queue = Arc::new(ArrayQueue::<PlayerMonitorEvent>::new(
+            MONITOR_QUEUE_CAPACITY,
+        ));
         monitor.set_queue(Arc::clone(&monitor_queue));
 
         // Should not be here, since DSP processing is not required
@@ -262,23 +425,41 @@ impl Player {
         };
         let events_queue = Arc::new(ArrayQueue::<AudioEvent>::new(EVENTS_QUEUE_CAPACITY));
         let events_queue_clone = Arc::clone(&events_queue);
+        let clock = AudioClock::new(sample_rate);
+        let samples = Arc::clone(&clock.samples);
+        let sync_clock = clock.clone();
+        let tempo_queue = Arc::new(ArrayQueue::<Tempo>::new(TEMPO_QUEUE_CAPACITY));
+        let tempo_queue_clone = Arc::clone(&tempo_queue);
+        let beats_queue = Arc::new(ArrayQueue::<BeatEvent>::new(BEATS_QUEUE_CAPACITY));
+        sink.set_beats_queue(Arc::clone(&beats_queue));
         let mut backend = PlayerBackend::<SampleType>::new(backend_config)
             .map_err(PlayerError::FailedToCreateBackend)?;
+        let stats = backend.stats();
+        monitor.set_backend_stats(Arc::clone(&stats));
         backend
             .open(move |output: &mut MappedInterleavedBuffer<f32>| {
+                let _guard = NoAllocGuard::new();
                 // Process events from the queue
                 monitor.events_start();
+                // Tempo is changed first, so the events scheduled
+                // on the next beat use the new one
+                while let Some(tempo) = tempo_queue_clone.pop() {
+                    sink.set_tempo(tempo);
+                }
                 let mut processed_events = 0;
                 while let Some(event) = events_queue_clone.pop() {
-                    // Process the event
-                    sink.dispatch(&event);
+                    // Process the event or schedule it to the exact sample
+                    sink.push(event);
                     processed_events += 1;
                 }
                 monitor.events_end(processed_events);
 
                 // Render the audio output
                 monitor.renderer_start();
+                let latency = Duration::from_nanos(stats.output_latency.load(Ordering::Relaxed));
+                sync_clock.mark(samples.load(Ordering::Relaxed), Instant::now() + latency);
                 sink.render(output);
+                samples.fetch_add(output.len, Ordering::Release);
                 monitor.renderer_end();
             })
             .map_err(PlayerError::FailedToStartBackend)?;
@@ -287,11 +468,57 @@ impl Player {
             backend,
             events: events_queue,
             monitor_queue,
+            clock,
+            tempo: tempo_queue,
+            beats: beats_queue,
+            limiter: None,
+            synced: None,
         })
     }
 
+    /// Returns the clock of the played audio stream.
+    pub fn clock(&self) -> AudioClock {
+        self.clock.clone()
+    }
+
+    /// Changes the tempo of the musical clock. The beat position is kept,
+    /// only the beats after the next rendered sample are affected.
+    /// The clock starts at 120 BPM with 4 beats per bar.
+    pub fn set_tempo(&self, tempo: Tempo) -> Result<(), PlayerError> {
+        if !tempo.is_valid() {
+            return Err(PlayerError::InvalidTempo);
+        }
+        self.tempo.force_push(tempo);
+        Ok(())
+    }
+
+    /// Reports the gain reduction of the limiter (see `LimiterEffect::meter`)
+    /// as `LimiterMonitorEvent` events on each tick, once the player is attached to the ECS.
+    pub fn monitor_limiter(&mut self, meter: LimiterMeter) {
+        self.limiter = Some(meter);
+    }
+
+    /// Name of the device the audio is played on.
+    /// `None` if the device was lost and no other one is connected.
+    pub fn device(&self) -> Option<String> {
+        self.backend.device()
+    }
+
+    /// Names of the connected output devices.
+    pub fn output_devices(&self) -> Vec<String> {
+        self.backend.output_devices()
+    }
+
+    /// Moves the audio to the device with the name, or to the default one with `None`.
+    /// If the device is not connected, the default one is used until it is.
+    /// The switch is reported with the `AudioDeviceEvent` once the player is attached to the ECS.
+    pub fn select_device(&mut self, name: Option<String>) {
+        self.backend.select_device(name);
+    }
+
     /// Transfers the audio event to the sink for processing.
-    /// The event will be processed at the start of the next audio block.
+    /// The event will be processed at the start of the next audio block,
+    /// or at the exact sample if it has a time (see `AudioEvent::at`).
     /// Usually you want not to use this method directly.
     /// Instead, you should use the `AudioEvent` events in the ECS
     pub fn push_event(&self, event: &AudioEvent) {
@@ -302,6 +529,12 @@ impl Player {
     /// of type `AudioEvent` and pass them to the sink for processing.
     /// Also, if you enabled profiling, it will send profiling data
     /// as `PlayerMonitorEvent` events to the ECS every second.
+    /// The beats of the musical clock are sent as `BeatEvent` events.
+    /// The gain reduction of the monitored limiter is sent as `LimiterMonitorEvent` events.
+    /// The output devices are checked on each tick, and the switches of the device
+    /// are sent as `AudioDeviceEvent` events. Send the `AudioDeviceRequest` to select the device.
+    /// The new sync points of the clock are sent as `AudioSyncEvent` events
+    /// (see `dawn_ecs::av_sync::AvSyncClock`).
     /// This function moves the player into the ECS world.
     pub fn attach_to_ecs(self, world: &mut World) {
         // Setup the audio player entity in the ECS
@@ -316,17 +549,62 @@ impl Player {
         fn tick_handler(
             _: Receiver<TickEvent>,
             player: Single<&Player>,
-            mut sender: Sender<PlayerMonitorEvent>,
+            mut sender: Sender<(PlayerMonitorEvent, BeatEvent, LimiterMonitorEvent)>,
         ) {
             // Check if there's any monitor frame to process.
             // If so, push them to the ECS
             while let Some(frame) = player.0.monitor_queue.pop() {
                 sender.send(frame);
             }
+            while let Some(beat) = player.0.beats.pop() {
+                sender.send(beat);
+            }
+            if let Some(event) = player.0.limiter.as_ref().and_then(LimiterMeter::take) {
+                sender.send(event);
+            }
+        }
+
+        fn device_handler(
+            _: Receiver<TickEvent>,
+            player: Single<&mut Player>,
+            mut sender: Sender<AudioDeviceEvent>,
+        ) {
+            player.0.backend.poll_underruns();
+            if let Some(switch) = player.0.backend.poll_device() {
+                sender.send(AudioDeviceEvent {
+                    previous: switch.previous,
+                    current: switch.result,
+                    reason: switch.reason,
+                });
+            }
+        }
+
+        fn sync_handler(
+            _: Receiver<TickEvent>,
+            player: Single<&mut Player>,
+            mut sender: Sender<AudioSyncEvent>,
+        ) {
+            let Some((samples, at)) = player.0.clock.sync_point() else {
+                return;
+            };
+            if player.0.synced != Some(samples) {
+                player.0.synced = Some(samples);
+                sender.send(AudioSyncEvent {
+                    position: samples as f64 / player.0.clock.sample_rate as f64,
+                    at,
+                });
+            }
+        }
+
+        fn device_request_handler(r: Receiver<AudioDeviceRequest>, player: Single<&mut Player>) {
+            player.0.select_device(r.event.0.clone());
         }
 
         // Setup the audio events handler (from the ECS)
         world.add_handler(audio_events_handler.low());
+        world.add_handler(device_request_handler);
+        world.add_handler(device_handler.low());
+        world.add_handler(sync_handler.low());
         // Setup transfer of monitor frames to the ECS
         world.add_handler(tick_handler.low());
     }
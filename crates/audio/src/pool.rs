@@ -0,0 +1,28 @@
+use crate::sample::PlanarBlock;
+
+/// Upper bound of the blocks in one pool, so a typo in the count
+/// does not allocate megabytes per node.
+const MAX_POOL_BLOCKS: usize = 16;
+
+/// Scratch blocks of the audio node, allocated once when the node is created,
+/// so the render does not allocate on the audio thread.
+/// The number of blocks taken is checked against the capacity at compile time.
+pub(crate) struct BlockPool<const N: usize> {
+    blocks: Box<[PlanarBlock<f32>; N]>,
+}
+
+impl<const N: usize> BlockPool<N> {
+    pub fn new() -> Self {
+        const { assert!(N <= MAX_POOL_BLOCKS, "Block pool is too large") };
+        BlockPool {
+            blocks: Box::new(std::array::from_fn(|_| PlanarBlock::default())),
+        }
+    }
+
+    /// Borrows the first `K` blocks. The contents are left from the previous use.
+    #[inline(always)]
+    pub fn take<const K: usize>(&mut self) -> &mut [PlanarBlock<f32>; K] {
+        const { assert!(K <= N, "Taking more blocks than the pool has") };
+        (&mut self.blocks[..K]).try_into().unwrap()
+    }
+}
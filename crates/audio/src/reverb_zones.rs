@@ -0,0 +1,300 @@
+use crate::entities::effects::multiplexer::MultiplexerEffectEvent;
+use crate::entities::events::{AudioEvent, AudioEventTargetId, AudioEventType};
+use dawn_ecs::events::TickEvent;
+use evenio::component::Component;
+use evenio::event::{Receiver, Sender};
+use evenio::fetch::{Fetcher, Single};
+use evenio::world::World;
+use glam::Vec3;
+
+// Changes of the send level smaller than that are not sent to the audio thread
+const LEVEL_EPSILON: f32 = 1e-3;
+
+/// Reverb the zone sends to: one of the parallel effects of the multiplexer
+/// effect (e.g. `MultiplexerEffect` of the `ReverbEffect`s tuned for the environments,
+/// or of the FIR filters with their impulse responses), mixed in by its dry/wet level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReverbSend {
+    /// Target of the multiplexer effect (see `MultiplexerEffect::get_id`)
+    pub multiplexer: AudioEventTargetId,
+    /// Index of the effect in the multiplexer
+    pub index: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ZoneVolume {
+    Sphere {
+        center: Vec3,
+        radius: f32,
+    },
+    /// Axis-aligned box
+    Box {
+        center: Vec3,
+        half_extents: Vec3,
+    },
+}
+
+impl ZoneVolume {
+    /// Distance from the point to the volume, zero inside it.
+    pub fn distance(&self, point: Vec3) -> f32 {
+        match self {
+            ZoneVolume::Sphere { center, radius } => {
+                (point.distance(*center) - radius.max(0.0)).max(0.0)
+            }
+            ZoneVolume::Box {
+                center,
+                half_extents,
+            } => ((point - *center).abs() - half_extents.max(Vec3::ZERO))
+                .max(Vec3::ZERO)
+                .length(),
+        }
+    }
+}
+
+/// Environment of the volume, e.g. the cave or the hall.
+/// The reverb of the zone is fully mixed in while the listener is inside
+/// the volume, and fades out over the blend distance outside it.
+#[derive(Component, Debug, Clone)]
+pub struct ReverbZone {
+    pub volume: ZoneVolume,
+    pub send: ReverbSend,
+    /// Send level inside the volume, from 0.0 to 1.0
+    pub level: f32,
+    /// Distance outside the volume the reverb fades out over
+    pub blend_distance: f32,
+}
+
+impl ReverbZone {
+    /// Send level of the zone heard at the position.
+    pub fn weight(&self, position: Vec3) -> f32 {
+        let distance = self.volume.distance(position);
+        let fade = if distance <= 0.0 {
+            1.0
+        } else if self.blend_distance > 0.0 {
+            (1.0 - distance / self.blend_distance).max(0.0)
+        } else {
+            0.0
+        };
+        fade * self.level.clamp(0.0, 1.0)
+    }
+}
+
+/// Position the reverb zones are heard at, usually of the camera entity.
+/// Only the first listener is used.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct AudioListener {
+    pub position: Vec3,
+}
+
+/// Send levels of the zones at the position. Zones with the same send take
+/// the loudest level, and if the overlapping zones sum over the unity,
+/// they are scaled down to it, so the wet signal keeps its loudness.
+pub fn zone_levels<'a>(
+    position: Vec3,
+    zones: impl IntoIterator<Item = &'a ReverbZone>,
+) -> Vec<(ReverbSend, f32)> {
+    let mut levels: Vec<(ReverbSend, f32)> = Vec::new();
+    for zone in zones {
+        let weight = zone.weight(position);
+        match levels.iter_mut().find(|(send, _)| *send == zone.send) {
+            Some((_, level)) => *level = level.max(weight),
+            None => levels.push((zone.send, weight)),
+        }
+    }
+
+    let total: f32 = levels.iter().map(|(_, level)| level).sum();
+    if total > 1.0 {
+        for (_, level) in &mut levels {
+            *level /= total;
+        }
+    }
+    levels
+}
+
+/// Crossfades the sends of the reverb zones (see `ReverbZone`) around
+/// the `AudioListener` on each tick, so the environments sound distinct
+/// without the gameplay code setting the effect parameters.
+/// The sends are silenced as soon as a zone refers to them, so the effects of the
+/// multiplexer start fully dry instead of its default full wet level.
+#[derive(Component, Debug)]
+pub struct ReverbZones {
+    // Send levels applied so far, the sends of the removed zones fade to zero
+    sends: Vec<(ReverbSend, f32)>,
+    crossfade: f32,
+}
+
+impl ReverbZones {
+    /// The levels change from zero to full in the `crossfade` seconds at most,
+    /// so the teleports and the zone changes are not heard as clicks.
+    pub fn new(crossfade: f32) -> Self {
+        ReverbZones {
+            sends: Vec::new(),
+            crossfade: crossfade.max(0.0),
+        }
+    }
+
+    /// Current send level, `None` if no zone has used the send.
+    pub fn level(&self, send: ReverbSend) -> Option<f32> {
+        self.sends
+            .iter()
+            .find(|(known, _)| *known == send)
+            .map(|(_, level)| *level)
+    }
+
+    // Moves the levels towards the targets, reports the changed ones
+    fn step(
+        &mut self,
+        targets: &[(ReverbSend, f32)],
+        delta: f32,
+        mut set: impl FnMut(ReverbSend, f32),
+    ) {
+        for (send, _) in targets {
+            if self.level(*send).is_none() {
+                // Starts silent, the first change is sent below
+                self.sends.push((*send, -1.0));
+            }
+        }
+
+        let max_step = if self.crossfade > 0.0 {
+            delta / self.crossfade
+        } else {
+            f32::INFINITY
+        };
+        for (send, level) in &mut self.sends {
+            let target = targets
+                .iter()
+                .find(|(known, _)| known == send)
+                .map_or(0.0, |(_, target)| *target);
+            let current = level.max(0.0);
+            let next = current + (target - current).clamp(-max_step, max_step);
+            if (next - *level).abs() > LEVEL_EPSILON || (next == target && next != *level) {
+                *level = next;
+                set(*send, next);
+            }
+        }
+    }
+
+    pub fn attach_to_ecs(self, world: &mut World) {
+        let entity = world.spawn();
+        world.insert(entity, self);
+
+        fn zones_handler(
+            r: Receiver<TickEvent>,
+            mut zones: Single<&mut ReverbZones>,
+            listeners: Fetcher<&AudioListener>,
+            volumes: Fetcher<&ReverbZone>,
+            mut sender: Sender<AudioEvent>,
+        ) {
+            let Some(listener) = listeners.iter().next() else {
+                return;
+            };
+            let targets = zone_levels(listener.position, volumes.iter());
+            zones.step(&targets, r.event.delta, |send, level| {
+                sender.send(AudioEvent::new(
+                    send.multiplexer,
+                    AudioEventType::MuxEffect(MultiplexerEffectEvent::SetDryWet(send.index, level)),
+                ));
+            });
+        }
+
+        world.add_handler(zones_handler);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zone(index: usize, volume: ZoneVolume, blend_distance: f32) -> ReverbZone {
+        ReverbZone {
+            volume,
+            send: ReverbSend {
+                multiplexer: AudioEventTargetId::default(),
+                index,
+            },
+            level: 1.0,
+            blend_distance,
+        }
+    }
+
+    #[test]
+    fn zones_fade_over_the_blend_distance() {
+        let hall = zone(
+            0,
+            ZoneVolume::Box {
+                center: Vec3::ZERO,
+                half_extents: Vec3::new(10.0, 5.0, 10.0),
+            },
+            4.0,
+        );
+        assert_eq!(hall.weight(Vec3::new(9.0, 0.0, 0.0)), 1.0);
+        assert_eq!(hall.weight(Vec3::new(12.0, 0.0, 0.0)), 0.5);
+        assert_eq!(hall.weight(Vec3::new(20.0, 0.0, 0.0)), 0.0);
+
+        let cave = zone(
+            1,
+            ZoneVolume::Sphere {
+                center: Vec3::ZERO,
+                radius: 2.0,
+            },
+            0.0,
+        );
+        assert_eq!(cave.weight(Vec3::new(0.0, 2.0, 0.0)), 1.0);
+        assert_eq!(cave.weight(Vec3::new(0.0, 2.1, 0.0)), 0.0);
+    }
+
+    #[test]
+    fn overlapping_zones_keep_the_unity() {
+        let sphere = |center: Vec3| ZoneVolume::Sphere {
+            center,
+            radius: 1.0,
+        };
+        let zones = [
+            zone(0, sphere(Vec3::ZERO), 2.0),
+            zone(0, sphere(Vec3::new(0.5, 0.0, 0.0)), 2.0),
+            zone(1, sphere(Vec3::new(2.0, 0.0, 0.0)), 2.0),
+        ];
+
+        // Inside both zones of the first send and blended with the second one
+        let levels = zone_levels(Vec3::new(0.5, 0.0, 0.0), &zones);
+        assert_eq!(levels.len(), 2);
+        assert!((levels[0].1 - 1.0 / 1.75).abs() < 1e-6);
+        assert!((levels[1].1 - 0.75 / 1.75).abs() < 1e-6);
+
+        let levels = zone_levels(Vec3::new(-5.0, 0.0, 0.0), &zones);
+        assert!(levels.iter().all(|(_, level)| *level == 0.0));
+    }
+
+    #[test]
+    fn levels_crossfade() {
+        let send = |index| ReverbSend {
+            multiplexer: AudioEventTargetId::default(),
+            index,
+        };
+        let mut zones = ReverbZones::new(0.5);
+        let mut sent = Vec::new();
+
+        zones.step(&[(send(0), 1.0)], 0.25, |send, level| {
+            sent.push((send.index, level))
+        });
+        assert_eq!(sent, [(0, 0.5)]);
+        zones.step(&[(send(0), 1.0)], 0.25, |send, level| {
+            sent.push((send.index, level))
+        });
+        assert_eq!(sent[1], (0, 1.0));
+
+        // Nothing changes, nothing is sent
+        zones.step(&[(send(0), 1.0)], 0.25, |send, level| {
+            sent.push((send.index, level))
+        });
+        assert_eq!(sent.len(), 2);
+
+        // Moving to the other zone fades the first one out
+        sent.clear();
+        zones.step(&[(send(1), 1.0)], 0.25, |send, level| {
+            sent.push((send.index, level))
+        });
+        assert_eq!(sent, [(0, 0.5), (1, 0.5)]);
+        assert_eq!(zones.level(send(0)), Some(0.5));
+    }
+}
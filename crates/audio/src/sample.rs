@@ -145,7 +145,7 @@ pub(crate) struct InterleavedSample<S>
 where
     S: Sample,
 {
-    pub channels: [S; CHANNELS_COUNT as usize],
+    pub channels: [S; CHANNELS_COUNT],
 }
 
 pub const LEFT_CHANNEL: usize = 0;
@@ -157,8 +157,9 @@ pub const RIGHT_CHANNEL: usize = 1;
 /// For example: r.0, r.1, r.2, ..., l.0, l.1, l.2, ...
 /// Used in audio processing chains - for example, in generators.
 /// The Amount of samples in the buffer is equal to `BLOCK_SIZE`.
+// Aligned for the AVX512 loads, each channel starts at the multiple of 64 bytes too
 #[repr(C)]
-#[repr(align(32))]
+#[repr(align(64))]
 #[derive(Debug)]
 pub struct PlanarBlock<S>
 where
@@ -179,7 +180,7 @@ where
 }
 
 #[repr(C)]
-#[repr(align(32))]
+#[repr(align(64))]
 #[derive(Debug)]
 pub(crate) struct InterleavedBlock<S>
 where
@@ -195,7 +196,7 @@ where
     fn default() -> Self {
         Self {
             samples: [InterleavedSample {
-                channels: [S::zero_value(); CHANNELS_COUNT as usize],
+                channels: [S::zero_value(); CHANNELS_COUNT],
             }; BLOCK_SIZE],
         }
     }
@@ -217,7 +218,7 @@ where
 {
     pub fn new(raw: &'a mut [S]) -> Option<Self> {
         // Check that the length of the raw slice is a multiple of CHANNELS_COUNT
-        if raw.len() % CHANNELS_COUNT != 0 {
+        if !raw.len().is_multiple_of(CHANNELS_COUNT) {
             return None; // Invalid length for interleaved samples
         }
 
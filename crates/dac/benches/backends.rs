@@ -0,0 +1,199 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use dawn_assets::ir::audio::IRAudio;
+use dawn_assets::ir::mesh::{IRIndexType, IRMesh, IRMeshBounds, IRSubMesh, IRTopology};
+use dawn_assets::ir::texture::{IRPixelFormat, IRTexture, IRTextureType};
+use dawn_assets::ir::IRAsset;
+use dawn_dac::compression_backend::{compress, decompress};
+use dawn_dac::serialize_backend::{deserialize, serialize};
+use dawn_dac::CompressionLevel;
+
+// Approximate sizes of the serialized assets
+const SIZES: [(&str, usize); 3] = [("16K", 16 << 10), ("256K", 256 << 10), ("4M", 4 << 20)];
+
+const LEVELS: [(&str, CompressionLevel); 3] = [
+    ("fast", CompressionLevel::Fast),
+    ("default", CompressionLevel::Default),
+    ("best", CompressionLevel::Best),
+];
+
+// Deterministic noise, so the runs are comparable
+struct Lcg(u32);
+
+impl Lcg {
+    fn next(&mut self) -> u32 {
+        self.0 = self.0.wrapping_mul(1664525).wrapping_add(1013904223);
+        self.0 >> 8
+    }
+}
+
+// Gradient with some noise: compresses like the real photos, not like the flat colors
+fn texture(size: usize) -> IRAsset {
+    let side = ((size / 4) as f32).sqrt() as usize;
+    let mut rng = Lcg(1);
+    let mut data = Vec::with_capacity(side * side * 4);
+    for y in 0..side {
+        for x in 0..side {
+            let noise = (rng.next() % 16) as u8;
+            data.extend_from_slice(&[
+                (x * 255 / side) as u8 ^ noise,
+                (y * 255 / side) as u8 ^ noise,
+                ((x + y) * 127 / side) as u8,
+                255,
+            ]);
+        }
+    }
+
+    IRAsset::Texture(IRTexture {
+        data,
+        texture_type: IRTextureType::Texture2D {
+            width: side as u32,
+            height: side as u32,
+        },
+        pixel_format: IRPixelFormat::R8G8B8A8,
+        ..Default::default()
+    })
+}
+
+// Noisy height map grid, 32 bytes per vertex and 24 bytes of indices per quad
+fn mesh(size: usize) -> IRAsset {
+    let side = ((size / 56) as f32).sqrt().max(2.0) as usize;
+    let mut rng = Lcg(2);
+    let mut vertices = Vec::with_capacity(side * side * 32);
+    for y in 0..side {
+        for x in 0..side {
+            let height = (rng.next() % 1000) as f32 / 1000.0;
+            let position = [x as f32, height, y as f32];
+            let normal = [0.0, 1.0, 0.0];
+            let tex_coord = [x as f32 / side as f32, y as f32 / side as f32];
+            for attribute in position.into_iter().chain(normal).chain(tex_coord) {
+                vertices.extend_from_slice(&attribute.to_le_bytes());
+            }
+        }
+    }
+
+    let mut indices = Vec::with_capacity((side - 1) * (side - 1) * 24);
+    for y in 0..side - 1 {
+        for x in 0..side - 1 {
+            let i = (y * side + x) as u32;
+            let quad = [
+                i,
+                i + 1,
+                i + side as u32,
+                i + 1,
+                i + side as u32 + 1,
+                i + side as u32,
+            ];
+            for index in quad {
+                indices.extend_from_slice(&index.to_le_bytes());
+            }
+        }
+    }
+
+    let bounds = IRMeshBounds {
+        min: [0.0, 0.0, 0.0],
+        max: [side as f32, 1.0, side as f32],
+    };
+    IRAsset::Mesh(IRMesh {
+        submesh: vec![IRSubMesh {
+            vertices,
+            indices,
+            material: None,
+            bounds: bounds.clone(),
+            topology: IRTopology::Triangles,
+        }],
+        bounds,
+        index_type: IRIndexType::U32,
+        static_batching: false,
+        bvh: None,
+    })
+}
+
+// Stereo chord with a bit of noise
+fn audio(size: usize) -> IRAsset {
+    let length = size / 4 / 2;
+    let mut rng = Lcg(3);
+    let mut data = Vec::with_capacity(length * 2);
+    for i in 0..length {
+        let t = i as f32 / 44100.0;
+        let sample = (t * 440.0 * std::f32::consts::TAU).sin() * 0.5
+            + (t * 660.0 * std::f32::consts::TAU).sin() * 0.25
+            + (rng.next() % 100) as f32 / 10000.0;
+        data.push(sample);
+        data.push(sample * 0.8);
+    }
+
+    IRAsset::Audio(IRAudio {
+        data,
+        sample_rate: 44100,
+        channels: 2,
+        length,
+        chunks: vec![],
+        encoded: None,
+        captions: None,
+    })
+}
+
+fn assets() -> Vec<(String, IRAsset)> {
+    let mut assets = Vec::new();
+    for (size_name, size) in SIZES {
+        for (kind, generator) in [
+            ("texture", texture as fn(usize) -> IRAsset),
+            ("mesh", mesh),
+            ("audio", audio),
+        ] {
+            assets.push((format!("{}/{}", kind, size_name), generator(size)));
+        }
+    }
+    assets
+}
+
+fn serialization(c: &mut Criterion) {
+    let mut group = c.benchmark_group("serialize");
+    for (name, asset) in assets() {
+        let raw = serialize(&asset).unwrap();
+        group.throughput(Throughput::Bytes(raw.len() as u64));
+        group.bench_with_input(BenchmarkId::new("encode", &name), &asset, |b, asset| {
+            b.iter(|| serialize(black_box(asset)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("decode", &name), &raw, |b, raw| {
+            b.iter(|| deserialize::<IRAsset>(black_box(raw)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn compression(c: &mut Criterion) {
+    // One group per compression backend, so the new ones can be compared side by side
+    let mut group = c.benchmark_group("brotli");
+    // The best level takes seconds on the large assets
+    group.sample_size(10);
+    for (name, asset) in assets() {
+        let raw = serialize(&asset).unwrap();
+        group.throughput(Throughput::Bytes(raw.len() as u64));
+        for (level_name, level) in LEVELS {
+            let compressed = compress(&raw, level.clone()).unwrap();
+            println!(
+                "{}/{}: {} -> {} bytes ({:.1}%)",
+                name,
+                level_name,
+                raw.len(),
+                compressed.len(),
+                compressed.len() as f64 / raw.len() as f64 * 100.0
+            );
+
+            let id = format!("{}/{}", name, level_name);
+            group.bench_with_input(BenchmarkId::new("compress", &id), &raw, |b, raw| {
+                b.iter(|| compress(black_box(raw), level.clone()).unwrap())
+            });
+            group.bench_with_input(
+                BenchmarkId::new("decompress", &id),
+                &compressed,
+                |b, compressed| b.iter(|| decompress(black_box(compressed)).unwrap()),
+            );
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, serialization, compression);
+criterion_main!(benches);
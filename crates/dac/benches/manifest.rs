@@ -0,0 +1,90 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use dawn_assets::ir::notes::IRNotes;
+use dawn_assets::ir::IRAsset;
+use dawn_assets::{AssetHeader, AssetID, AssetType};
+use dawn_dac::builder::ContainerBuilder;
+use dawn_dac::reader::read_manifest;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+const TAGS: [&str; 4] = ["ui", "level", "shared", "debug"];
+
+// Typical dependency graph: every asset depends on a couple of the previous ones
+// and shares the tags with many others
+fn headers(count: usize) -> Vec<AssetHeader> {
+    (0..count)
+        .map(|i| AssetHeader {
+            id: format!("assets/object_{}", i).into(),
+            asset_type: AssetType::Notes,
+            dependencies: (1..=3)
+                .filter(|d| *d <= i)
+                .map(|d| AssetID::from(format!("assets/object_{}", i - d)))
+                .collect(),
+            tags: [TAGS[i % TAGS.len()], TAGS[(i / 2) % TAGS.len()]]
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn container(count: usize) -> Vec<u8> {
+    let mut builder = ContainerBuilder::new();
+    for header in headers(count) {
+        builder = builder.add_asset(header, IRAsset::Notes(IRNotes { events: vec![] }));
+    }
+
+    let mut data = Vec::new();
+    builder.write(&mut data).unwrap();
+    data
+}
+
+fn manifest(c: &mut Criterion) {
+    let mut group = c.benchmark_group("manifest");
+    for count in [100, 1000, 10000] {
+        let data = container(count);
+        group.bench_with_input(BenchmarkId::new("parse", count), &data, |b, data| {
+            b.iter(|| read_manifest(&mut Cursor::new(data)).unwrap())
+        });
+
+        let manifest = read_manifest(&mut Cursor::new(&data)).unwrap();
+        let index: HashMap<AssetID, &AssetHeader> = manifest
+            .headers
+            .iter()
+            .map(|header| (header.id.clone(), header))
+            .collect();
+        let ids: Vec<AssetID> = manifest.headers.iter().map(|h| h.id.clone()).collect();
+        group.bench_with_input(BenchmarkId::new("lookup", count), &ids, |b, ids| {
+            b.iter(|| {
+                // Resolve the direct dependencies of every asset, like the loader does
+                let mut resolved = 0;
+                for id in ids {
+                    let header = index[id];
+                    for dependency in header.dependencies.iter() {
+                        resolved += index.contains_key(dependency) as usize;
+                    }
+                }
+                black_box(resolved)
+            })
+        });
+
+        group.bench_with_input(
+            BenchmarkId::new("by_tag", count),
+            &manifest,
+            |b, manifest| {
+                b.iter(|| {
+                    manifest
+                        .headers
+                        .iter()
+                        .filter(|header| header.tags.iter().any(|tag| tag == "shared"))
+                        .count()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, manifest);
+criterion_main!(benches);
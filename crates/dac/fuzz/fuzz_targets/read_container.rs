@@ -0,0 +1,26 @@
+#![no_main]
+
+// Run with `cargo fuzz run read_container` from the `crates/dac` directory.
+// Reading any input must either succeed or return a ContainerError,
+// but never panic or allocate unbounded memory.
+
+use dawn_dac::reader::{read_asset, read_manifest, verify_container};
+use dawn_dac::signing::{TrustConfig, TrustPolicy};
+use libfuzzer_sys::fuzz_target;
+use std::io::Cursor;
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = Cursor::new(data);
+
+    let trust = TrustConfig {
+        trusted_keys: vec![],
+        policy: TrustPolicy::Warn,
+    };
+    let _ = verify_container(&mut reader, &trust);
+
+    if let Ok(manifest) = read_manifest(&mut reader) {
+        for header in manifest.headers {
+            let _ = read_asset(&mut reader, header.id);
+        }
+    }
+});
@@ -0,0 +1,303 @@
+use crate::encryption::{decrypt_payload, EncryptionKey};
+use crate::reader::{
+    decode_asset, parse_manifest, segment_length, segment_range, AssetLocation, ContainerIndex,
+    ReadLimits,
+};
+use crate::{ContainerError, Manifest, CHUNKS_MAGIC, DAC_MAGIC, MANIFEST_MAGIC, TOC_MAGIC};
+use dawn_assets::ir::IRAsset;
+use dawn_assets::AssetID;
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+
+// Async counterparts of the functions in `reader`. Only the I/O is async,
+// the segments are parsed and validated by the same code as in the sync reader.
+// Decompressing and deserializing the payload runs on the calling task,
+// read the large assets with `spawn_blocking` if that's a concern.
+
+async fn read_segments<R: AsyncRead + AsyncSeek + Unpin>(
+    reader: &mut R,
+) -> Result<HashMap<u8, (usize, usize)>, ContainerError> {
+    let size = reader.seek(SeekFrom::End(0)).await?;
+    reader.seek(SeekFrom::Start(0)).await?;
+
+    let mut magic = [0u8; 3];
+    reader.read_exact(&mut magic).await?;
+    if &magic != DAC_MAGIC {
+        return Err(ContainerError::InvalidMagic);
+    }
+
+    let mut segments = HashMap::new();
+    loop {
+        let mut segment_magic = [0u8; 1];
+        if let Err(e) = reader.read_exact(&mut segment_magic).await {
+            if e.kind() == std::io::ErrorKind::UnexpectedEof {
+                break; // End of a file
+            } else {
+                return Err(ContainerError::IOError(e));
+            }
+        }
+
+        let mut length_bytes = [0u8; 4];
+        reader.read_exact(&mut length_bytes).await?;
+
+        let offset = reader.stream_position().await? as usize;
+        let length = segment_length(segment_magic[0], length_bytes, offset, size)?;
+        segments.insert(segment_magic[0], (offset, length));
+
+        reader
+            .seek(SeekFrom::Start(offset as u64 + length as u64))
+            .await?;
+    }
+
+    Ok(segments)
+}
+
+async fn segment_bytes<R: AsyncRead + AsyncSeek + Unpin>(
+    reader: &mut R,
+    segments: &HashMap<u8, (usize, usize)>,
+    magic: u8,
+    limits: &ReadLimits,
+) -> Result<Vec<u8>, ContainerError> {
+    let (offset, length) = segment_range(segments, magic, limits)?;
+    reader.seek(SeekFrom::Start(offset as u64)).await?;
+    let mut segment_bytes = vec![0u8; length];
+    reader.read_exact(&mut segment_bytes).await?;
+    Ok(segment_bytes)
+}
+
+async fn read_index<R: AsyncRead + AsyncSeek + Unpin>(
+    reader: &mut R,
+    limits: &ReadLimits,
+) -> Result<ContainerIndex, ContainerError> {
+    let segments = read_segments(reader).await?;
+    let toc = segment_bytes(reader, &segments, TOC_MAGIC, limits).await?;
+    let chunks = if segments.contains_key(&CHUNKS_MAGIC) {
+        Some(segment_bytes(reader, &segments, CHUNKS_MAGIC, limits).await?)
+    } else {
+        None
+    };
+    ContainerIndex::parse(&segments, &toc, chunks.as_deref(), limits)
+}
+
+async fn read_location<R: AsyncRead + AsyncSeek + Unpin>(
+    reader: &mut R,
+    id: &AssetID,
+    location: &AssetLocation,
+) -> Result<Vec<u8>, ContainerError> {
+    let mut data_bytes = Vec::with_capacity(location.length);
+    for (offset, length) in &location.regions {
+        let start = data_bytes.len();
+        data_bytes.resize(start + length, 0);
+        reader.seek(SeekFrom::Start(*offset)).await?;
+        reader.read_exact(&mut data_bytes[start..]).await?;
+    }
+    location.check(id, &data_bytes)?;
+    Ok(data_bytes)
+}
+
+/// Async version of `reader::read_manifest`.
+pub async fn read_manifest<R: AsyncRead + AsyncSeek + Unpin>(
+    reader: &mut R,
+) -> Result<Manifest, ContainerError> {
+    read_manifest_with_limits(reader, &ReadLimits::default()).await
+}
+
+pub async fn read_manifest_with_limits<R: AsyncRead + AsyncSeek + Unpin>(
+    reader: &mut R,
+    limits: &ReadLimits,
+) -> Result<Manifest, ContainerError> {
+    let segments = read_segments(reader).await?;
+    let bytes = segment_bytes(reader, &segments, MANIFEST_MAGIC, limits).await?;
+    parse_manifest(&bytes, limits)
+}
+
+/// Async version of `reader::read_asset`.
+pub async fn read_asset<R: AsyncRead + AsyncSeek + Unpin>(
+    reader: &mut R,
+    id: AssetID,
+) -> Result<IRAsset, ContainerError> {
+    read_asset_with_limits(reader, id, &ReadLimits::default()).await
+}
+
+pub async fn read_asset_with_limits<R: AsyncRead + AsyncSeek + Unpin>(
+    reader: &mut R,
+    id: AssetID,
+    limits: &ReadLimits,
+) -> Result<IRAsset, ContainerError> {
+    let index = read_index(reader, limits).await?;
+    let location = index.locate(&id, limits)?;
+    let data_bytes = read_location(reader, &id, &location).await?;
+    let data_bytes = decrypt_payload(&id, data_bytes, location.encryption, None)?;
+    decode_asset(id, data_bytes, location.compression, limits)
+}
+
+/// Async version of `reader::Container`, e.g. for the asset servers.
+/// The control segments are parsed once, when the container is opened.
+///
+/// ```
+/// use dawn_assets::ir::notes::IRNotes;
+/// use dawn_assets::ir::IRAsset;
+/// use dawn_assets::AssetHeader;
+/// use dawn_dac::async_reader::AsyncContainer;
+/// use dawn_dac::builder::ContainerBuilder;
+/// use std::io::Cursor;
+///
+/// let mut data = Vec::new();
+/// ContainerBuilder::new()
+///     .add_asset(
+///         AssetHeader {
+///             id: "theme".into(),
+///             ..Default::default()
+///         },
+///         IRAsset::Notes(IRNotes { events: vec![] }),
+///     )
+///     .write(&mut data)
+///     .unwrap();
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// runtime.block_on(async {
+///     let mut container = AsyncContainer::open(Cursor::new(data)).await.unwrap();
+///     assert_eq!(container.manifest().headers.len(), 1);
+///     assert!(container.read_asset("theme".into()).await.is_ok());
+/// });
+/// ```
+pub struct AsyncContainer<R: AsyncRead + AsyncSeek + Unpin> {
+    reader: R,
+    index: ContainerIndex,
+    manifest: Manifest,
+    limits: ReadLimits,
+    key: Option<EncryptionKey>,
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncContainer<R> {
+    /// Opens the container without the key, see `reader::open`.
+    pub async fn open(reader: R) -> Result<Self, ContainerError> {
+        Self::open_with_limits(reader, None, ReadLimits::default()).await
+    }
+
+    /// Opens the container with the key decrypting the encrypted assets.
+    pub async fn open_with_key(reader: R, key: EncryptionKey) -> Result<Self, ContainerError> {
+        Self::open_with_limits(reader, Some(key), ReadLimits::default()).await
+    }
+
+    pub async fn open_with_limits(
+        mut reader: R,
+        key: Option<EncryptionKey>,
+        limits: ReadLimits,
+    ) -> Result<Self, ContainerError> {
+        let manifest = read_manifest_with_limits(&mut reader, &limits).await?;
+        let index = read_index(&mut reader, &limits).await?;
+        Ok(AsyncContainer {
+            reader,
+            index,
+            manifest,
+            limits,
+            key,
+        })
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    pub async fn read_asset(&mut self, id: AssetID) -> Result<IRAsset, ContainerError> {
+        let location = self.index.locate(&id, &self.limits)?;
+        let data_bytes = read_location(&mut self.reader, &id, &location).await?;
+        let data_bytes = decrypt_payload(&id, data_bytes, location.encryption, self.key.as_ref())?;
+        decode_asset(id, data_bytes, location.compression, &self.limits)
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::async_reader::{read_asset, read_manifest, AsyncContainer};
+    use crate::builder::ContainerBuilder;
+    use crate::reader;
+    use crate::serialize_backend::serialize;
+    use crate::{CompressionLevel, ContainerError};
+    use dawn_assets::ir::notes::{IRNoteEvent, IRNotes};
+    use dawn_assets::ir::IRAsset;
+    use dawn_assets::AssetHeader;
+    use std::io::Cursor;
+
+    fn container(compression: CompressionLevel, blocks: bool) -> Vec<u8> {
+        let events = (0..512)
+            .map(|i| IRNoteEvent::NoteOn {
+                channel: 0,
+                note: (i % 16) as u8,
+                velocity: 100,
+            })
+            .collect::<Vec<_>>();
+        let mut builder = ContainerBuilder::new().compression(compression);
+        if blocks {
+            builder = builder.compression_blocks(1024);
+        }
+        let mut data = Vec::new();
+        builder
+            .add_asset(
+                AssetHeader {
+                    id: "a".into(),
+                    ..Default::default()
+                },
+                IRAsset::Notes(IRNotes {
+                    events: events.clone(),
+                }),
+            )
+            .add_asset(
+                AssetHeader {
+                    id: "b".into(),
+                    ..Default::default()
+                },
+                IRAsset::Notes(IRNotes { events }),
+            )
+            .write(&mut data)
+            .unwrap();
+        data
+    }
+
+    #[tokio::test]
+    async fn async_reader_matches_sync_reader() {
+        for (compression, blocks) in [
+            (CompressionLevel::None, false),
+            (CompressionLevel::Fast, false),
+            (CompressionLevel::Fast, true),
+        ] {
+            let data = container(compression, blocks);
+            let mut sync = Cursor::new(data.as_slice());
+            let mut reader = Cursor::new(data.as_slice());
+
+            let manifest = read_manifest(&mut reader).await.unwrap();
+            let expected = reader::read_manifest(&mut sync).unwrap();
+            assert_eq!(manifest.headers.len(), expected.headers.len());
+            for id in ["a", "b"] {
+                let read = read_asset(&mut reader, id.into()).await.unwrap();
+                let expected = reader::read_asset(&mut sync, id.into()).unwrap();
+                assert_eq!(serialize(&read).unwrap(), serialize(&expected).unwrap());
+            }
+
+            let mut container = AsyncContainer::open(reader).await.unwrap();
+            assert_eq!(container.manifest().headers.len(), 2);
+            assert!(matches!(
+                container.read_asset("missing".into()).await,
+                Err(ContainerError::AssetNotFound(_))
+            ));
+            let read = container.read_asset("a".into()).await.unwrap();
+            let expected = reader::read_asset(&mut sync, "a".into()).unwrap();
+            assert_eq!(serialize(&read).unwrap(), serialize(&expected).unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn async_reader_rejects_truncated_container() {
+        let data = container(CompressionLevel::Fast, false);
+        let truncated = &data[..data.len() / 2];
+        assert!(read_manifest(&mut Cursor::new(truncated)).await.is_err());
+        assert!(AsyncContainer::open(Cursor::new(truncated)).await.is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::compression_backend::{compress, compress_blocks};
+use crate::serialize_backend::serialize;
+use crate::writer::{write_container, BinaryAsset, ContainerOptions, ContainerWriter};
+use crate::{
+    ChecksumAlgorithm, CompressionLevel, CompressionMode, ContainerError, Manifest, ReadMode,
+};
+use dawn_assets::guid::AssetGuids;
+use dawn_assets::ir::IRAsset;
+use dawn_assets::variants::AssetVariants;
+use dawn_assets::AssetHeader;
+use std::collections::HashSet;
+use std::io::{Seek, Write};
+use std::time::SystemTime;
+
+/// Builds the container from the IR assets in memory,
+/// without the TOML files and the directory layout required by dacgen.
+/// Useful for the tests and the editor tools.
+///
+/// ```
+/// use dawn_assets::ir::notes::IRNotes;
+/// use dawn_assets::ir::IRAsset;
+/// use dawn_assets::{AssetHeader, AssetType};
+/// use dawn_dac::builder::ContainerBuilder;
+/// use dawn_dac::reader::{read_asset, read_manifest};
+/// use std::io::Cursor;
+///
+/// let header = AssetHeader {
+///     id: "readme".into(),
+///     asset_type: AssetType::Notes,
+///     ..Default::default()
+/// };
+///
+/// let mut data = Vec::new();
+/// ContainerBuilder::new()
+///     .author("Me")
+///     .add_asset(header, IRAsset::Notes(IRNotes { events: vec![] }))
+///     .write(&mut data)
+///     .unwrap();
+///
+/// let manifest = read_manifest(&mut Cursor::new(&data)).unwrap();
+/// assert_eq!(manifest.author.as_deref(), Some("Me"));
+/// assert!(read_asset(&mut Cursor::new(&data), "readme".into()).is_ok());
+/// ```
+pub struct ContainerBuilder {
+    manifest: Manifest,
+    compression: CompressionLevel,
+    compression_blocks: Option<usize>,
+    options: ContainerOptions,
+    assets: Vec<(AssetHeader, IRAsset)>,
+}
+
+impl Default for ContainerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContainerBuilder {
+    pub fn new() -> Self {
+        ContainerBuilder {
+            manifest: Manifest {
+                author: None,
+                description: None,
+                version: None,
+                license: None,
+                tool: "dawn-dac-builder".to_string(),
+                tool_version: env!("CARGO_PKG_VERSION").to_string(),
+                created: SystemTime::now(),
+                read_mode: ReadMode::Flat,
+                checksum_algorithm: ChecksumAlgorithm::Blake3,
+                headers: vec![],
+                variants: AssetVariants::default(),
+                guids: AssetGuids::default(),
+                encrypted: vec![],
+            },
+            compression: CompressionLevel::None,
+            compression_blocks: None,
+            options: ContainerOptions::default(),
+            assets: vec![],
+        }
+    }
+
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.manifest.author = Some(author.into());
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.manifest.description = Some(description.into());
+        self
+    }
+
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.manifest.version = Some(version.into());
+        self
+    }
+
+    pub fn license(mut self, license: impl Into<String>) -> Self {
+        self.manifest.license = Some(license.into());
+        self
+    }
+
+    pub fn variants(mut self, variants: AssetVariants) -> Self {
+        self.manifest.variants = variants;
+        self
+    }
+
+    pub fn guids(mut self, guids: AssetGuids) -> Self {
+        self.manifest.guids = guids;
+        self
+    }
+
+    /// Compression of the asset payloads. No compression by default.
+    pub fn compression(mut self, level: CompressionLevel) -> Self {
+        self.compression = level;
+        self
+    }
+
+    /// Payloads larger than `block_size` are compressed in the independent blocks
+    /// (see `compression_backend::compress_blocks`).
+    pub fn compression_blocks(mut self, block_size: usize) -> Self {
+        self.compression_blocks = Some(block_size);
+        self
+    }
+
+    pub fn options(mut self, options: ContainerOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn add_asset(mut self, header: AssetHeader, ir: IRAsset) -> Self {
+        self.assets.push((header, ir));
+        self
+    }
+
+    fn to_binary(
+        header: AssetHeader,
+        ir: &IRAsset,
+        compression: &CompressionLevel,
+        blocks: Option<usize>,
+    ) -> Result<BinaryAsset, ContainerError> {
+        let raw = serialize(ir).map_err(ContainerError::SerializationError)?;
+        let compressed = match (compression, blocks) {
+            (CompressionLevel::None, _) => None,
+            (level, Some(block_size)) if raw.len() > block_size => {
+                let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+                Some((
+                    compress_blocks(&raw, level.clone(), block_size, threads)
+                        .map_err(ContainerError::CompressionError)?,
+                    CompressionMode::BrotliBlocks,
+                ))
+            }
+            (level, _) => Some((
+                compress(&raw, level.clone()).map_err(ContainerError::CompressionError)?,
+                CompressionMode::Brotli,
+            )),
+        };
+
+        // Keep the compressed payload only if it's actually smaller
+        Ok(match compressed {
+            Some((compressed, compression)) if compressed.len() < raw.len() => BinaryAsset {
+                raw: compressed,
+                header,
+                compression,
+            },
+            _ => BinaryAsset {
+                raw,
+                header,
+                compression: CompressionMode::None,
+            },
+        })
+    }
+
+    pub fn write<W: Write>(self, writer: &mut W) -> Result<(), ContainerError> {
+        let mut ids = HashSet::new();
+        let mut binaries = Vec::with_capacity(self.assets.len());
+        for (header, ir) in self.assets {
+            if !ids.insert(header.id.clone()) {
+                return Err(ContainerError::DuplicateAsset(header.id));
+            }
+            binaries.push(Self::to_binary(
+                header,
+                &ir,
+                &self.compression,
+                self.compression_blocks,
+            )?);
+        }
+
+        let mut manifest = self.manifest;
+        manifest.headers = binaries.iter().map(|b| b.header.clone()).collect();
+        write_container(writer, manifest, binaries, &self.options)
+    }
+
+    /// Same as `write`, but serializes, compresses and writes the assets one by one,
+    /// so only one payload is kept in memory at a time. See `ContainerWriter`.
+    pub fn write_streaming<W: Write + Seek>(self, writer: &mut W) -> Result<(), ContainerError> {
+        let mut container = ContainerWriter::new(writer, self.options)?;
+        for (header, ir) in self.assets {
+            container.add(Self::to_binary(
+                header,
+                &ir,
+                &self.compression,
+                self.compression_blocks,
+            )?)?;
+        }
+
+        let mut manifest = self.manifest;
+        manifest.headers = container.headers().to_vec();
+        container.finish(&manifest)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+// Content-defined chunking (FastCDC).
+// Splits the data into variable-sized chunks, where the boundaries depend only on
+// the local content. So the same sub-blob shared by several assets produces the same
+// chunks, even if it's placed at different offsets, allowing storing it only once.
+//
+// See "FastCDC: a Fast and Efficient Content-Defined Chunking Approach
+// for Data Deduplication" (Xia et al., 2016).
+
+const fn splitmix64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    (state, z ^ (z >> 31))
+}
+
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state = 0x44_41_43; // "DAC"
+    let mut i = 0;
+    while i < 256 {
+        let (next, value) = splitmix64(state);
+        state = next;
+        table[i] = value;
+        i += 1;
+    }
+    table
+}
+
+// Must never change, otherwise chunk boundaries will differ between the tool versions
+static GEAR: [u64; 256] = gear_table();
+
+/// Parameters of the content-defined chunking.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChunkingParams {
+    /// Minimal size of the chunk in bytes. Payloads smaller than that are never chunked.
+    pub min_size: usize,
+    /// Desired average size of the chunk in bytes. Must be a power of two.
+    pub avg_size: usize,
+    /// Maximal size of the chunk in bytes.
+    pub max_size: usize,
+}
+
+impl Default for ChunkingParams {
+    fn default() -> Self {
+        ChunkingParams {
+            min_size: 16 * 1024,
+            avg_size: 64 * 1024,
+            max_size: 256 * 1024,
+        }
+    }
+}
+
+impl ChunkingParams {
+    fn masks(&self) -> (u64, u64) {
+        let bits = self.avg_size.max(4).ilog2();
+        // Use the top bits of the hash - they depend on the last 64 bytes.
+        // A stricter mask is used before the average size and a looser one after it,
+        // this normalizes the chunk size distribution.
+        let strict = !0u64 << (64 - (bits + 1));
+        let loose = !0u64 << (64 - (bits - 1));
+        (strict, loose)
+    }
+
+    /// Finds the length of the first chunk of the data.
+    fn cut(&self, data: &[u8], strict: u64, loose: u64) -> usize {
+        let len = data.len();
+        if len <= self.min_size {
+            return len;
+        }
+
+        let len = len.min(self.max_size);
+        let normal = self.avg_size.min(len);
+
+        let mut hash = 0u64;
+        let mut i = self.min_size;
+        while i < normal {
+            hash = (hash << 1).wrapping_add(GEAR[data[i] as usize]);
+            if hash & strict == 0 {
+                return i + 1;
+            }
+            i += 1;
+        }
+        while i < len {
+            hash = (hash << 1).wrapping_add(GEAR[data[i] as usize]);
+            if hash & loose == 0 {
+                return i + 1;
+            }
+            i += 1;
+        }
+
+        len
+    }
+
+    /// Splits the data into the content-defined chunks.
+    /// Returns the ranges of the chunks, covering the whole data.
+    pub fn split(&self, data: &[u8]) -> Vec<Range<usize>> {
+        let (strict, loose) = self.masks();
+
+        let mut chunks = Vec::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let length = self.cut(&data[offset..], strict, loose);
+            chunks.push(offset..offset + length);
+            offset += length;
+        }
+
+        chunks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chunking::ChunkingParams;
+    use std::collections::HashSet;
+
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                let (next, value) = super::splitmix64(state);
+                state = next;
+                value as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chunks_cover_data_and_respect_bounds() {
+        let params = ChunkingParams {
+            min_size: 256,
+            avg_size: 1024,
+            max_size: 4096,
+        };
+        let data = noise(100_000, 1);
+        let chunks = params.split(&data);
+
+        assert_eq!(chunks.first().unwrap().start, 0);
+        assert_eq!(chunks.last().unwrap().end, data.len());
+        for pair in chunks.windows(2) {
+            assert_eq!(pair[0].end, pair[1].start);
+        }
+        for chunk in &chunks[..chunks.len() - 1] {
+            assert!(chunk.len() >= params.min_size && chunk.len() <= params.max_size);
+        }
+    }
+
+    #[test]
+    fn shifted_content_shares_chunks() {
+        let params = ChunkingParams {
+            min_size: 256,
+            avg_size: 1024,
+            max_size: 4096,
+        };
+        let shared = noise(50_000, 2);
+        let mut a = noise(777, 3);
+        a.extend_from_slice(&shared);
+        let mut b = noise(1234, 4);
+        b.extend_from_slice(&shared);
+
+        let a_chunks: HashSet<&[u8]> = params.split(&a).into_iter().map(|r| &a[r]).collect();
+        let b_chunks: HashSet<&[u8]> = params.split(&b).into_iter().map(|r| &b[r]).collect();
+
+        // Everything except a couple of chunks near the start must be shared
+        let common = a_chunks.intersection(&b_chunks).count();
+        assert!(common + 4 >= a_chunks.len());
+    }
+}
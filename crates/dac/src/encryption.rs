@@ -0,0 +1,144 @@
+use crate::ContainerError;
+use dawn_assets::{AssetHeader, AssetID, AssetTag};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+/// Cipher the asset payload is encrypted with.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum EncryptionMode {
+    #[default]
+    None,
+    /// The payload is prefixed with the random nonce and followed by the tag.
+    /// The asset ID is authenticated too, so the payloads can't be swapped.
+    ChaCha20Poly1305,
+}
+
+/// 256-bit key the assets are encrypted with. The same key decrypts them,
+/// so it must be shipped with the application in some form.
+#[derive(Clone, PartialEq, Eq)]
+pub struct EncryptionKey([u8; 32]);
+
+impl Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print the key itself
+        write!(f, "EncryptionKey(..)")
+    }
+}
+
+impl EncryptionKey {
+    pub fn from_bytes(bytes: [u8; 32]) -> EncryptionKey {
+        EncryptionKey(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// Generates a new random key. Store it securely,
+    /// the encrypted assets can't be read without it.
+    pub fn generate() -> Result<EncryptionKey, ContainerError> {
+        let mut bytes = [0u8; 32];
+        SystemRandom::new()
+            .fill(&mut bytes)
+            .map_err(|_| ContainerError::EncryptionError("Failed to generate key".to_string()))?;
+        Ok(EncryptionKey(bytes))
+    }
+
+    fn cipher(&self) -> LessSafeKey {
+        // The key length always matches the cipher
+        LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, &self.0).unwrap())
+    }
+
+    pub(crate) fn encrypt(
+        &self,
+        mode: EncryptionMode,
+        id: &AssetID,
+        data: &[u8],
+    ) -> Result<Vec<u8>, ContainerError> {
+        match mode {
+            EncryptionMode::None => Ok(data.to_vec()),
+            EncryptionMode::ChaCha20Poly1305 => {
+                let mut nonce = [0u8; NONCE_LEN];
+                SystemRandom::new().fill(&mut nonce).map_err(|_| {
+                    ContainerError::EncryptionError("Failed to generate nonce".to_string())
+                })?;
+
+                let mut in_out = data.to_vec();
+                self.cipher()
+                    .seal_in_place_append_tag(
+                        Nonce::assume_unique_for_key(nonce),
+                        Aad::from(id.as_str().as_bytes()),
+                        &mut in_out,
+                    )
+                    .map_err(|_| {
+                        ContainerError::EncryptionError(format!("Failed to encrypt {}", id))
+                    })?;
+
+                let mut sealed = nonce.to_vec();
+                sealed.append(&mut in_out);
+                Ok(sealed)
+            }
+        }
+    }
+
+    /// Fails with `ContainerError::DecryptionFailed` if the key is wrong
+    /// or the payload was tampered with.
+    pub(crate) fn decrypt(
+        &self,
+        mode: EncryptionMode,
+        id: &AssetID,
+        mut data: Vec<u8>,
+    ) -> Result<Vec<u8>, ContainerError> {
+        match mode {
+            EncryptionMode::None => Ok(data),
+            EncryptionMode::ChaCha20Poly1305 => {
+                if data.len() < NONCE_LEN {
+                    return Err(ContainerError::DecryptionFailed(id.clone()));
+                }
+                let mut sealed = data.split_off(NONCE_LEN);
+                let nonce = Nonce::try_assume_unique_for_key(&data)
+                    .map_err(|_| ContainerError::DecryptionFailed(id.clone()))?;
+                let length = self
+                    .cipher()
+                    .open_in_place(nonce, Aad::from(id.as_str().as_bytes()), &mut sealed)
+                    .map_err(|_| ContainerError::DecryptionFailed(id.clone()))?
+                    .len();
+                sealed.truncate(length);
+                Ok(sealed)
+            }
+        }
+    }
+}
+
+/// Decrypts the payload read from the container.
+pub(crate) fn decrypt_payload(
+    id: &AssetID,
+    data: Vec<u8>,
+    mode: EncryptionMode,
+    key: Option<&EncryptionKey>,
+) -> Result<Vec<u8>, ContainerError> {
+    match (mode, key) {
+        (EncryptionMode::None, _) => Ok(data),
+        (_, Some(key)) => key.decrypt(mode, id, data),
+        (_, None) => Err(ContainerError::EncryptionKeyRequired(id.clone())),
+    }
+}
+
+/// Which assets the writer encrypts, and how.
+#[derive(Debug, Clone)]
+pub struct EncryptionOptions {
+    pub mode: EncryptionMode,
+    pub key: EncryptionKey,
+    /// Assets having any of the tags are encrypted, e.g. the licensed content.
+    /// Empty encrypts all the assets.
+    pub tags: Vec<AssetTag>,
+}
+
+impl EncryptionOptions {
+    pub fn selects(&self, header: &AssetHeader) -> bool {
+        self.mode != EncryptionMode::None
+            && (self.tags.is_empty() || header.tags.iter().any(|tag| self.tags.contains(tag)))
+    }
+}
@@ -0,0 +1,296 @@
+use dawn_assets::variants::AssetVariants;
+use dawn_assets::{AssetHeader, AssetID};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
+
+/// Chain of the assets depending on each other, the first one is repeated at the end.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub struct DependencyCycle(pub Vec<AssetID>);
+
+impl Display for DependencyCycle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (i, id) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " -> ")?;
+            }
+            write!(f, "{}", id.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    New,
+    InProgress,
+    Done,
+}
+
+/// Dependencies of the assets of the container, both ways.
+/// The logical assets (see `AssetVariants`) are the nodes too, depending on
+/// all their variants, so the cycles through the variants are found as well.
+/// Dependencies missing in the container are the nodes without the dependencies.
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    ids: Vec<AssetID>,
+    indices: HashMap<AssetID, usize>,
+    dependencies: Vec<Vec<usize>>,
+    dependents: Vec<Vec<usize>>,
+}
+
+impl DependencyGraph {
+    pub fn new(headers: &[AssetHeader], variants: &AssetVariants) -> Self {
+        let mut graph = DependencyGraph::default();
+        for header in headers {
+            for dependency in &header.dependencies {
+                graph.add_edge(&header.id, dependency);
+            }
+            graph.node(&header.id);
+        }
+
+        let mut logical: Vec<(&AssetID, Vec<&AssetID>)> = Vec::new();
+        for (id, locales) in &variants.locales {
+            let mut ids: Vec<&AssetID> = locales.locales.values().collect();
+            ids.push(&locales.default);
+            logical.push((id, ids));
+        }
+        for (id, quality) in &variants.quality {
+            logical.push((id, quality.iter().map(|variant| &variant.id).collect()));
+        }
+        // The maps are unordered, keep the graph the same for the same manifest
+        logical.sort_by(|a, b| a.0.cmp(b.0));
+        for (id, mut ids) in logical {
+            ids.sort();
+            for variant in ids {
+                graph.add_edge(id, variant);
+            }
+        }
+        graph
+    }
+
+    fn node(&mut self, id: &AssetID) -> usize {
+        if let Some(index) = self.indices.get(id) {
+            return *index;
+        }
+        let index = self.ids.len();
+        self.ids.push(id.clone());
+        self.indices.insert(id.clone(), index);
+        self.dependencies.push(Vec::new());
+        self.dependents.push(Vec::new());
+        index
+    }
+
+    fn add_edge(&mut self, from: &AssetID, to: &AssetID) {
+        let from = self.node(from);
+        let to = self.node(to);
+        if !self.dependencies[from].contains(&to) {
+            self.dependencies[from].push(to);
+            self.dependents[to].push(from);
+        }
+    }
+
+    fn ids(&self, indices: &[usize]) -> Vec<&AssetID> {
+        indices.iter().map(|index| &self.ids[*index]).collect()
+    }
+
+    pub fn contains(&self, id: &AssetID) -> bool {
+        self.indices.contains_key(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Direct dependencies of the asset.
+    pub fn dependencies_of(&self, id: &AssetID) -> Vec<&AssetID> {
+        self.indices
+            .get(id)
+            .map(|index| self.ids(&self.dependencies[*index]))
+            .unwrap_or_default()
+    }
+
+    /// Assets depending on the asset directly.
+    pub fn dependents_of(&self, id: &AssetID) -> Vec<&AssetID> {
+        self.indices
+            .get(id)
+            .map(|index| self.ids(&self.dependents[*index]))
+            .unwrap_or_default()
+    }
+
+    /// Assets depending on the asset directly or through the other assets,
+    /// e.g. the ones to rebuild when it changes.
+    pub fn all_dependents_of(&self, id: &AssetID) -> Vec<&AssetID> {
+        let Some(start) = self.indices.get(id) else {
+            return Vec::new();
+        };
+        let mut visited = vec![false; self.ids.len()];
+        visited[*start] = true;
+        let mut stack = vec![*start];
+        let mut result = Vec::new();
+        while let Some(index) = stack.pop() {
+            for dependent in &self.dependents[index] {
+                if !visited[*dependent] {
+                    visited[*dependent] = true;
+                    result.push(*dependent);
+                    stack.push(*dependent);
+                }
+            }
+        }
+        self.ids(&result)
+    }
+
+    /// Any dependency cycle, including the indirect ones and the assets
+    /// depending on themselves. `None` if the graph is acyclic.
+    pub fn find_cycle(&self) -> Option<DependencyCycle> {
+        let mut visits = vec![Visit::New; self.ids.len()];
+        for root in 0..self.ids.len() {
+            if visits[root] != Visit::New {
+                continue;
+            }
+            // Depth-first, the path is the nodes in progress with the next dependency
+            let mut path: Vec<(usize, usize)> = vec![(root, 0)];
+            visits[root] = Visit::InProgress;
+            while let Some((index, next)) = path.last_mut() {
+                let index = *index;
+                let Some(dependency) = self.dependencies[index].get(*next).copied() else {
+                    visits[index] = Visit::Done;
+                    path.pop();
+                    continue;
+                };
+                *next += 1;
+                match visits[dependency] {
+                    Visit::New => {
+                        visits[dependency] = Visit::InProgress;
+                        path.push((dependency, 0));
+                    }
+                    Visit::InProgress => {
+                        let start = path.iter().position(|(i, _)| *i == dependency).unwrap();
+                        let mut cycle: Vec<AssetID> = path[start..]
+                            .iter()
+                            .map(|(i, _)| self.ids[*i].clone())
+                            .collect();
+                        cycle.push(self.ids[dependency].clone());
+                        return Some(DependencyCycle(cycle));
+                    }
+                    Visit::Done => {}
+                }
+            }
+        }
+        None
+    }
+
+    /// All the assets, each one after its dependencies, e.g. the order to load them in.
+    /// The order is the same for the same manifest.
+    pub fn topological_order(&self) -> Result<Vec<&AssetID>, DependencyCycle> {
+        let mut remaining: Vec<usize> = self.dependencies.iter().map(Vec::len).collect();
+        let mut ready: Vec<usize> = (0..self.ids.len())
+            .filter(|index| remaining[*index] == 0)
+            .rev()
+            .collect();
+        let mut order = Vec::with_capacity(self.ids.len());
+        while let Some(index) = ready.pop() {
+            order.push(index);
+            for dependent in self.dependents[index].iter().rev() {
+                remaining[*dependent] -= 1;
+                if remaining[*dependent] == 0 {
+                    ready.push(*dependent);
+                }
+            }
+        }
+
+        if order.len() < self.ids.len() {
+            return Err(self.find_cycle().unwrap());
+        }
+        Ok(self.ids(&order))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dawn_assets::variants::LocaleVariants;
+
+    fn header(id: &str, dependencies: &[&str]) -> AssetHeader {
+        AssetHeader {
+            id: id.into(),
+            dependencies: dependencies.iter().map(|id| AssetID::from(*id)).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn strings(ids: Vec<&AssetID>) -> Vec<&str> {
+        ids.into_iter().map(|id| id.as_str()).collect()
+    }
+
+    #[test]
+    fn orders_dependencies_first() {
+        let headers = [
+            header("scene", &["mesh", "material"]),
+            header("mesh", &["material"]),
+            header("material", &["texture", "shader"]),
+            header("texture", &[]),
+            header("shader", &[]),
+        ];
+        let graph = DependencyGraph::new(&headers, &AssetVariants::default());
+
+        let order = strings(graph.topological_order().unwrap());
+        assert_eq!(order.len(), 5);
+        let position = |id| order.iter().position(|known| *known == id).unwrap();
+        for header in &headers {
+            for dependency in &header.dependencies {
+                assert!(position(dependency.as_str()) < position(header.id.as_str()));
+            }
+        }
+
+        assert_eq!(
+            strings(graph.dependents_of(&"material".into())),
+            ["scene", "mesh"]
+        );
+        let mut all = strings(graph.all_dependents_of(&"texture".into()));
+        all.sort();
+        assert_eq!(all, ["material", "mesh", "scene"]);
+        assert!(graph.find_cycle().is_none());
+    }
+
+    #[test]
+    fn finds_indirect_cycles() {
+        let headers = [
+            header("a", &["b"]),
+            header("b", &["c"]),
+            header("c", &["a"]),
+            header("d", &["a"]),
+        ];
+        let graph = DependencyGraph::new(&headers, &AssetVariants::default());
+        let cycle = graph.topological_order().unwrap_err();
+        assert_eq!(cycle.to_string(), "a -> b -> c -> a");
+
+        let graph = DependencyGraph::new(&[header("a", &["a"])], &AssetVariants::default());
+        assert_eq!(graph.find_cycle().unwrap().to_string(), "a -> a");
+    }
+
+    #[test]
+    fn follows_variants() {
+        let headers = [
+            header("dialogue_en", &["level"]),
+            header("level", &["dialogue"]),
+        ];
+        let mut variants = AssetVariants::default();
+        variants.locales.insert(
+            "dialogue".into(),
+            LocaleVariants {
+                default: "dialogue_en".into(),
+                locales: HashMap::new(),
+            },
+        );
+        let graph = DependencyGraph::new(&headers, &variants);
+        assert_eq!(
+            graph.find_cycle().unwrap().to_string(),
+            "dialogue_en -> level -> dialogue -> dialogue_en"
+        );
+    }
+}
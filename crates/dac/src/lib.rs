@@ -1,3 +1,8 @@
+use crate::encryption::EncryptionMode;
+use crate::graph::DependencyGraph;
+use crate::signing::Verification;
+use dawn_assets::guid::AssetGuids;
+use dawn_assets::variants::AssetVariants;
 use dawn_assets::{AssetHeader, AssetID};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -5,33 +10,62 @@ use std::fmt::Display;
 use std::time::SystemTime;
 use thiserror::Error;
 
+#[cfg(feature = "async")]
+pub mod async_reader;
+pub mod builder;
+pub mod chunking;
+pub mod encryption;
+pub mod graph;
+pub mod prefetch;
 pub mod reader;
+pub mod signing;
 pub mod writer;
 
+/// Version and the enabled features of the crate.
+pub fn build_info() -> dawn_util::build_info::CrateBuildInfo {
+    dawn_util::crate_build_info!["async", "bench"]
+}
+
 // DAC file format (Dawn Asset Container):
 // - 3 bytes: "DAC" magic
-// - Repeated segments:
+// - Repeated segments, in any order. The data segment usually goes last,
+//   or first if the container was written by the streaming writer:
 //   - 1 byte: segment type magic
 //   - 4 bytes: segment length (u32 little-endian)
 //   - N bytes: segment data
 //
 // Segment types:
 // - 0x0: TOC (Table of contents) segment
-//   - Serialized TOC structure (HashMap<AssetID, Record>)
+//   - Serialized TOC structure (HashMap<AssetID, Record>).
+//     Records of the encrypted assets carry the cipher, the payload
+//     is compressed first and then encrypted. Each record carries the SHA-256
+//     digest of the payload as stored, checked by the readers
 // - 0x1: Manifest segment
 //   - Serialized Manifest structure
 // - 0x2: Data segment
-//   - Concatenated raw asset data
+//   - Concatenated raw asset data and shared chunks
+// - 0x3: Chunk index segment (optional)
+//   - Serialized ChunkIndex structure. Assets listed in the index are stored
+//     as a sequence of (possibly shared) chunks instead of a contiguous blob
+// - 0x4: Signature segment (optional)
+//   - Serialized SignatureRecord: Ed25519 public key and the signature
+//     of the TOC, manifest and chunk index segments. The data segment is
+//     covered through the digests of the payloads in the TOC
 
 pub(crate) const DAC_MAGIC: &[u8; 3] = b"DAC";
 pub(crate) const TOC_MAGIC: u8 = 0x0;
 pub(crate) const MANIFEST_MAGIC: u8 = 0x1;
 pub(crate) const DATA_MAGIC: u8 = 0x2;
+pub(crate) const CHUNKS_MAGIC: u8 = 0x3;
+pub(crate) const SIGNATURE_MAGIC: u8 = 0x4;
 
 #[derive(Serialize, Deserialize, Clone, Copy)]
 pub enum CompressionMode {
     None,
     Brotli,
+    /// Independently compressed blocks of the same uncompressed size,
+    /// see `compression_backend::compress_blocks`.
+    BrotliBlocks,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -39,11 +73,31 @@ pub(crate) struct Record {
     offset: u32,
     length: u32,
     compression: CompressionMode,
+    encryption: EncryptionMode,
+    // SHA-256 of the payload as stored in the data segment (reassembled from
+    // the chunks, compressed and encrypted)
+    digest: [u8; 32],
 }
 
+// Spelled like the TOC segment of the container layout above.
 #[derive(Serialize, Deserialize)]
+#[allow(clippy::upper_case_acronyms)]
 pub(crate) struct TOC(HashMap<AssetID, Record>);
 
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ChunkRecord {
+    offset: u32,
+    length: u32,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub(crate) struct ChunkIndex {
+    // All the unique chunks. Offsets are relative to the start of the data segment
+    chunks: Vec<ChunkRecord>,
+    // Indices of the chunks the asset consists of, in order
+    assets: HashMap<AssetID, Vec<u32>>,
+}
+
 #[derive(Error, Debug)]
 pub enum ContainerError {
     #[error("Compression error: {0}")]
@@ -62,6 +116,36 @@ pub enum ContainerError {
     AssetNotFound(AssetID),
     #[error("Deserialization error: {0}")]
     DeserializationError(anyhow::Error),
+    #[error("Duplicate asset: {0}")]
+    DuplicateAsset(AssetID),
+    #[error("Invalid chunk reference: {0}")]
+    InvalidChunk(u32),
+    #[error("Segment {0:#x} exceeds the container size")]
+    SegmentOutOfBounds(u8),
+    #[error("Record of asset {0} points outside of the data segment")]
+    InvalidRecord(AssetID),
+    #[error("Segment {0:#x} is too large: {1} bytes")]
+    SegmentTooLarge(u8, usize),
+    #[error("Too many assets: {0}")]
+    TooManyAssets(usize),
+    #[error("Asset {0} exceeds the decompressed size limit")]
+    DecompressedTooLarge(AssetID),
+    #[error("Asset {0} exceeds the compression ratio limit")]
+    CompressionRatioExceeded(AssetID),
+    #[error("Signing error: {0}")]
+    SigningError(String),
+    #[error("Untrusted container: {0}")]
+    UntrustedContainer(Verification),
+    #[error("Container can't be appended to, its data segment is not the first one")]
+    NotAppendable,
+    #[error("Encryption error: {0}")]
+    EncryptionError(String),
+    #[error("Asset {0} is encrypted, open the container with the key")]
+    EncryptionKeyRequired(AssetID),
+    #[error("Failed to decrypt asset {0}: wrong key or tampered payload")]
+    DecryptionFailed(AssetID),
+    #[error("Payload of asset {0} doesn't match its digest: corrupted or tampered container")]
+    DigestMismatch(AssetID),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Hash)]
@@ -95,7 +179,7 @@ pub enum CompressionLevel {
     Best,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Manifest {
     // File information
     pub author: Option<String>,
@@ -110,27 +194,51 @@ pub struct Manifest {
     pub read_mode: ReadMode,
     pub checksum_algorithm: ChecksumAlgorithm,
     pub headers: Vec<AssetHeader>,
+    /// Variants of the logical assets. Pass them to the hub
+    /// alongside the headers when enumerating.
+    pub variants: AssetVariants,
+    /// Stable GUIDs of the assets, mapped to their current IDs.
+    /// Passed to the hub the same way as the variants.
+    pub guids: AssetGuids,
+    /// Assets with the encrypted payloads, sorted.
+    /// Filled by the writer, whatever the given manifest says.
+    pub encrypted: Vec<AssetID>,
 }
 
 impl Manifest {
+    /// Dependencies of the assets, with the topological ordering,
+    /// the reverse dependencies and the cycle detection.
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        DependencyGraph::new(&self.headers, &self.variants)
+    }
+
+    /// Walks the dependencies of the asset depth-first, downward.
+    /// The shared dependencies are visited once per path leading to them,
+    /// the dependency cycles are not followed.
     pub fn tree(&self, id: AssetID, callback: &impl Fn(&AssetID, &AssetHeader, usize)) {
-        pub fn tree_inner(
-            manifest: &Manifest,
-            depth: usize,
-            id: &AssetID,
+        fn tree_inner<'a>(
+            headers: &HashMap<&AssetID, &'a AssetHeader>,
+            path: &mut Vec<&'a AssetID>,
+            header: &'a AssetHeader,
             callback: &impl Fn(&AssetID, &AssetHeader, usize),
         ) {
-            let asset = manifest.headers.iter().find(|h| h.id == id.clone());
-            if let Some(asset) = asset {
-                callback(id, asset, depth);
-
-                for dep in &asset.dependencies {
-                    tree_inner(manifest, depth + 1, dep, callback);
+            callback(&header.id, header, path.len());
+            path.push(&header.id);
+            for dep in &header.dependencies {
+                if let Some(dep) = headers.get(dep) {
+                    if !path.contains(&&dep.id) {
+                        tree_inner(headers, path, dep, callback);
+                    }
                 }
             }
+            path.pop();
         }
 
-        tree_inner(self, 0, &id, callback)
+        let headers: HashMap<&AssetID, &AssetHeader> =
+            self.headers.iter().map(|h| (&h.id, h)).collect();
+        if let Some(header) = headers.get(&id) {
+            tree_inner(&headers, &mut Vec::new(), header, callback)
+        }
     }
 }
 
@@ -150,7 +258,9 @@ pub mod serialize_backend {
     }
 }
 
+// `any()`/`all()` pick the serialization backend, swap them to switch to TOML.
 #[cfg(all())]
+#[allow(clippy::non_minimal_cfg)]
 pub mod serialize_backend {
     use bincode;
     use serde::de::DeserializeOwned;
@@ -161,8 +271,22 @@ pub mod serialize_backend {
         Ok(data)
     }
 
+    // Lengths of the containers inside the serialized data are not trusted,
+    // bincode allocates them before reading. Cap the total claimed size
+    const MAX_DECODE_SIZE: usize = 1 << 30;
+
     pub fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> anyhow::Result<T> {
-        let (object, _) = bincode::serde::decode_from_slice(bytes, bincode::config::standard())?;
+        let config = bincode::config::standard().with_limit::<MAX_DECODE_SIZE>();
+        let (object, _) = bincode::serde::decode_from_slice(bytes, config)?;
+        Ok(object)
+    }
+
+    /// Deserializes the object read from the stream, without buffering it as a whole.
+    pub fn deserialize_from<T: DeserializeOwned, R: std::io::Read>(
+        reader: &mut R,
+    ) -> anyhow::Result<T> {
+        let config = bincode::config::standard().with_limit::<MAX_DECODE_SIZE>();
+        let object = bincode::serde::decode_from_std_read(reader, config)?;
         Ok(object)
     }
 }
@@ -178,38 +302,54 @@ pub mod compression_backend {
         writer::CompressorWriter,
         BrotliEncoderParams, StandardAlloc,
     };
-    use std::io::{Read, Write};
-    use std::sync::Arc;
+    use std::io::{Read, Take, Write};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    fn to_params(level: CompressionLevel) -> BrotliEncoderParams {
+        let mut p = BrotliEncoderParams::default();
+        match level {
+            CompressionLevel::Fast => {
+                p.quality = 3;
+                p.lgwin = 20;
+            }
+            CompressionLevel::Default => {
+                p.quality = 6;
+                p.lgwin = 22;
+            }
+            CompressionLevel::Best => {
+                p.quality = 11;
+                p.lgwin = 22;
+            }
+            CompressionLevel::None => {
+                unreachable!()
+            }
+        }
+
+        p
+    }
+
+    fn available_threads() -> usize {
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    }
 
     pub fn compress(data: &[u8], level: CompressionLevel) -> anyhow::Result<Vec<u8>> {
+        compress_with_threads(data, level, available_threads())
+    }
+
+    /// Same as `compress`, but the large data is compressed on at most `threads` threads.
+    pub fn compress_with_threads(
+        data: &[u8],
+        level: CompressionLevel,
+        threads: usize,
+    ) -> anyhow::Result<Vec<u8>> {
         // Why bother compressing if the level is None?
         if matches!(level, CompressionLevel::None) {
             return Ok(data.to_vec());
         }
 
-        fn to_params(level: CompressionLevel) -> BrotliEncoderParams {
-            let mut p = BrotliEncoderParams::default();
-            match level {
-                CompressionLevel::Fast => {
-                    p.quality = 3;
-                    p.lgwin = 20;
-                }
-                CompressionLevel::Default => {
-                    p.quality = 6;
-                    p.lgwin = 22;
-                }
-                CompressionLevel::Best => {
-                    p.quality = 11;
-                    p.lgwin = 22;
-                }
-                CompressionLevel::None => {
-                    unreachable!()
-                }
-            }
-
-            p
-        }
-
         struct ArcSlice(Arc<[u8]>);
         impl SliceWrapper<u8> for ArcSlice {
             #[inline]
@@ -223,7 +363,7 @@ pub mod compression_backend {
 
         // Do not use multi-threading if the data is too small.
         // It's faster to use a single thread.
-        if data.len() <= THRESHOLD {
+        if data.len() <= THRESHOLD || threads <= 1 {
             let mut w = CompressorWriter::with_params(Vec::new(), 64 * 1024, &params);
             w.write_all(data)?;
             return Ok(w.into_inner());
@@ -234,11 +374,6 @@ pub mod compression_backend {
         let owned_buf: Arc<[u8]> = Arc::from(data); // одна аллокация и копия входа
         let mut owned_input = Owned::new(ArcSlice(owned_buf)); // Owned<SliceW>
 
-        // Select threads count
-        let threads = std::thread::available_parallelism()
-            .map(|n| n.get())
-            .unwrap_or(1)
-            .max(2);
         let mut out =
             vec![
                 0u8;
@@ -271,4 +406,180 @@ pub mod compression_backend {
         reader.read_to_end(&mut decompressed)?;
         Ok(decompressed)
     }
+
+    /// Decompresses the data, producing at most `limit` bytes.
+    /// Returns `None` if the decompressed data exceeds the limit.
+    pub fn decompress_bounded(data: &[u8], limit: usize) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut decompressed = Vec::new();
+        let reader = brotli::Decompressor::new(data, 4096);
+        reader
+            .take(limit as u64 + 1)
+            .read_to_end(&mut decompressed)?;
+        if decompressed.len() > limit {
+            return Ok(None);
+        }
+        Ok(Some(decompressed))
+    }
+
+    /// Splits the data into the blocks of `block_size` bytes and compresses them
+    /// independently on at most `threads` threads. Compresses a bit worse than
+    /// `compress`, since the blocks share no history, but each block can be
+    /// decompressed on its own.
+    ///
+    /// Layout (all the numbers are u32 little-endian):
+    /// - uncompressed size of the block, the last one may be shorter
+    /// - number of the blocks
+    /// - compressed length of each block
+    /// - the blocks, each one is a complete Brotli stream
+    pub fn compress_blocks(
+        data: &[u8],
+        level: CompressionLevel,
+        block_size: usize,
+        threads: usize,
+    ) -> anyhow::Result<Vec<u8>> {
+        anyhow::ensure!(
+            !matches!(level, CompressionLevel::None),
+            "Blocks are always compressed"
+        );
+        let params = to_params(level);
+        let blocks: Vec<&[u8]> = data.chunks(block_size.max(1)).collect();
+
+        // Each worker takes the next block until none is left
+        let next = AtomicUsize::new(0);
+        let compressed: Mutex<Vec<Option<Vec<u8>>>> = Mutex::new(vec![None; blocks.len()]);
+        std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads.clamp(1, blocks.len().max(1)))
+                .map(|_| {
+                    scope.spawn(|| -> anyhow::Result<()> {
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            let Some(block) = blocks.get(index) else {
+                                return Ok(());
+                            };
+                            let mut w =
+                                CompressorWriter::with_params(Vec::new(), 64 * 1024, &params);
+                            w.write_all(block)?;
+                            compressed.lock().unwrap()[index] = Some(w.into_inner());
+                        }
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .try_for_each(|worker| worker.join().unwrap())
+        })?;
+
+        let compressed: Vec<Vec<u8>> = compressed
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect();
+        let mut out = Vec::new();
+        out.extend_from_slice(&u32::try_from(block_size.max(1))?.to_le_bytes());
+        out.extend_from_slice(&u32::try_from(compressed.len())?.to_le_bytes());
+        for block in &compressed {
+            out.extend_from_slice(&u32::try_from(block.len())?.to_le_bytes());
+        }
+        for block in compressed {
+            out.extend_from_slice(&block);
+        }
+        Ok(out)
+    }
+
+    fn read_u32<R: Read>(reader: &mut R) -> std::io::Result<u32> {
+        let mut bytes = [0u8; 4];
+        reader.read_exact(&mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    // Block size and the compressed lengths of the blocks.
+    // The table must fit into the payload, so the corrupted count is not allocated
+    fn read_block_table<R: Read>(
+        reader: &mut R,
+        payload_length: usize,
+    ) -> std::io::Result<(usize, Vec<usize>)> {
+        let block_size = read_u32(reader)? as usize;
+        let count = read_u32(reader)? as usize;
+        if count.saturating_mul(4).saturating_add(8) > payload_length {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Block table exceeds the payload",
+            ));
+        }
+        let lengths = (0..count)
+            .map(|_| read_u32(reader).map(|length| length as usize))
+            .collect::<std::io::Result<_>>()?;
+        Ok((block_size, lengths))
+    }
+
+    /// Decompresses the data made with `compress_blocks`, producing at most `limit` bytes.
+    /// Returns `None` if the decompressed data exceeds the limit.
+    pub fn decompress_blocks_bounded(data: &[u8], limit: usize) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut table = data;
+        let (block_size, lengths) = read_block_table(&mut table, data.len())?;
+        let mut rest = table;
+        let mut decompressed = Vec::new();
+        for (i, length) in lengths.iter().enumerate() {
+            anyhow::ensure!(*length <= rest.len(), "Block {} exceeds the payload", i);
+            let (block, next) = rest.split_at(*length);
+            rest = next;
+
+            let Some(block) = decompress_bounded(block, limit - decompressed.len())? else {
+                return Ok(None);
+            };
+            // Every block but the last one is full, so the blocks can be found by the offset
+            anyhow::ensure!(
+                block.len() == block_size || (i + 1 == lengths.len() && block.len() <= block_size),
+                "Block {} has invalid size {}",
+                i,
+                block.len()
+            );
+            decompressed.extend_from_slice(&block);
+        }
+        Ok(Some(decompressed))
+    }
+
+    /// Decompresses the data made with `compress_blocks` as it is read.
+    pub struct BlocksDecompressor<R: Read> {
+        // Decompressor of the current block, `None` after the last one
+        block: Option<brotli::Decompressor<Take<R>>>,
+        lengths: std::vec::IntoIter<usize>,
+    }
+
+    impl<R: Read> BlocksDecompressor<R> {
+        /// Reads the block table. `payload_length` is the size of the compressed data.
+        pub fn new(mut reader: R, payload_length: usize) -> std::io::Result<Self> {
+            let (_, lengths) = read_block_table(&mut reader, payload_length)?;
+            let mut lengths = lengths.into_iter();
+            Ok(BlocksDecompressor {
+                block: lengths
+                    .next()
+                    .map(|length| brotli::Decompressor::new(reader.take(length as u64), 4096)),
+                lengths,
+            })
+        }
+    }
+
+    impl<R: Read> Read for BlocksDecompressor<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if buf.is_empty() {
+                return Ok(0);
+            }
+            while let Some(block) = &mut self.block {
+                let read = block.read(buf)?;
+                if read > 0 {
+                    return Ok(read);
+                }
+                // Skip the bytes after the end of the stream, if any
+                let mut rest = self.block.take().unwrap().into_inner();
+                std::io::copy(&mut rest, &mut std::io::sink())?;
+                if let Some(length) = self.lengths.next() {
+                    let reader = rest.into_inner().take(length as u64);
+                    self.block = Some(brotli::Decompressor::new(reader, 4096));
+                }
+            }
+            Ok(0)
+        }
+    }
 }
@@ -0,0 +1,304 @@
+use crate::encryption::{decrypt_payload, EncryptionKey, EncryptionMode};
+use crate::reader::{
+    decode_asset, read_location, read_manifest_with_limits, AssetLocation, ContainerIndex,
+    ReadLimits,
+};
+use crate::{CompressionMode, ContainerError, Manifest};
+use dawn_assets::ir::IRAsset;
+use dawn_assets::{AssetID, ReadTiming};
+use log::debug;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{Read, Seek};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+struct Job {
+    id: AssetID,
+    location: AssetLocation,
+    // Requested by the reader, not just hinted
+    urgent: bool,
+}
+
+// Payload as it's stored in the container
+struct StoredPayload {
+    data: Vec<u8>,
+    compression: CompressionMode,
+    encryption: EncryptionMode,
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<Job>,
+    queued: HashSet<AssetID>,
+    ready: HashMap<AssetID, Result<StoredPayload, ContainerError>>,
+    ready_size: usize,
+    stop: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+/// Container reader for the long-living consumers, like the asset reader thread.
+/// Parses the control segments once and reads the payloads on a background IO thread.
+/// Assets hinted by `prefetch` are read ahead, so the decompression
+/// of one asset overlaps with the reading of the next ones.
+///
+/// ```
+/// use dawn_assets::ir::notes::IRNotes;
+/// use dawn_assets::ir::IRAsset;
+/// use dawn_assets::AssetHeader;
+/// use dawn_dac::builder::ContainerBuilder;
+/// use dawn_dac::prefetch::PrefetchReader;
+/// use dawn_dac::reader::ReadLimits;
+/// use std::io::Cursor;
+///
+/// let mut data = Vec::new();
+/// ContainerBuilder::new()
+///     .add_asset(
+///         AssetHeader {
+///             id: "song".into(),
+///             ..Default::default()
+///         },
+///         IRAsset::Notes(IRNotes { events: vec![] }),
+///     )
+///     .write(&mut data)
+///     .unwrap();
+///
+/// let reader = PrefetchReader::new(Cursor::new(data), ReadLimits::default(), 1 << 20).unwrap();
+/// reader.prefetch(&["song".into()]);
+/// let (ir, _timing) = reader.read("song".into()).unwrap();
+/// assert!(matches!(ir, IRAsset::Notes(_)));
+/// ```
+pub struct PrefetchReader {
+    index: ContainerIndex,
+    manifest: Manifest,
+    limits: ReadLimits,
+    key: Option<EncryptionKey>,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PrefetchReader {
+    /// `budget` limits the size of the payloads read ahead but not yet consumed.
+    /// Requested reads are never blocked by it.
+    pub fn new<R: Read + Seek + Send + 'static>(
+        mut reader: R,
+        limits: ReadLimits,
+        budget: usize,
+    ) -> Result<Self, ContainerError> {
+        let manifest = read_manifest_with_limits(&mut reader, &limits)?;
+        let index = ContainerIndex::read(&mut reader, &limits)?;
+
+        let shared = Arc::new(Shared::default());
+        let thread = std::thread::Builder::new()
+            .name("dac-prefetch".to_string())
+            .spawn({
+                let shared = shared.clone();
+                move || io_thread(reader, &shared, budget)
+            })?;
+
+        Ok(PrefetchReader {
+            index,
+            manifest,
+            limits,
+            key: None,
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Decrypts the encrypted assets with the key (see `reader::open_with_key`).
+    pub fn with_key(mut self, key: EncryptionKey) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Hints that the assets will be read soon. The payloads are read
+    /// in the given order. Unknown assets are ignored, `read` reports them.
+    pub fn prefetch(&self, ids: &[AssetID]) {
+        let mut state = self.shared.state.lock().unwrap();
+        for id in ids {
+            if state.queued.contains(id) || state.ready.contains_key(id) {
+                continue;
+            }
+            if let Ok(location) = self.index.locate(id, &self.limits) {
+                state.queued.insert(id.clone());
+                state.queue.push_back(Job {
+                    id: id.clone(),
+                    location,
+                    urgent: false,
+                });
+            }
+        }
+        self.shared.changed.notify_all();
+    }
+
+    /// Reads the asset, waiting for its payload if it's not prefetched yet.
+    pub fn read(&self, id: AssetID) -> Result<(IRAsset, ReadTiming), ContainerError> {
+        let start = Instant::now();
+        let payload = {
+            let mut state = self.shared.state.lock().unwrap();
+            if !state.ready.contains_key(&id) {
+                // Move the hinted asset to the front of the queue, or queue it there
+                let job = match state.queue.iter().position(|job| job.id == id) {
+                    Some(position) => state.queue.remove(position).unwrap(),
+                    None => {
+                        let location = self.index.locate(&id, &self.limits)?;
+                        state.queued.insert(id.clone());
+                        Job {
+                            id: id.clone(),
+                            location,
+                            urgent: true,
+                        }
+                    }
+                };
+                state.queue.push_front(Job {
+                    urgent: true,
+                    ..job
+                });
+                self.shared.changed.notify_all();
+
+                while !state.ready.contains_key(&id) {
+                    state = self.shared.changed.wait(state).unwrap();
+                }
+            }
+
+            let payload = state.ready.remove(&id).unwrap()?;
+            state.ready_size -= payload.data.len();
+            self.shared.changed.notify_all();
+            payload
+        };
+        let io_wait = start.elapsed();
+
+        let start = Instant::now();
+        let data_bytes = decrypt_payload(&id, payload.data, payload.encryption, self.key.as_ref())?;
+        let asset = decode_asset(id, data_bytes, payload.compression, &self.limits)?;
+        Ok((
+            asset,
+            ReadTiming {
+                io_wait,
+                decode: start.elapsed(),
+            },
+        ))
+    }
+}
+
+impl Drop for PrefetchReader {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().stop = true;
+        self.shared.changed.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn io_thread<R: Read + Seek>(mut reader: R, shared: &Shared, budget: usize) {
+    loop {
+        let job = {
+            let mut state = shared.state.lock().unwrap();
+            loop {
+                if state.stop {
+                    return;
+                }
+                // Hinted assets wait until the consumer catches up
+                let runnable = match state.queue.front() {
+                    Some(job) => job.urgent || state.ready_size < budget,
+                    None => false,
+                };
+                if runnable {
+                    break state.queue.pop_front().unwrap();
+                }
+                state = shared.changed.wait(state).unwrap();
+            }
+        };
+
+        let result = read_location(&mut reader, &job.id, &job.location).map(|data| StoredPayload {
+            data,
+            compression: job.location.compression,
+            encryption: job.location.encryption,
+        });
+        if let Err(err) = &result {
+            debug!("Failed to read asset {}: {}", job.id, err);
+        }
+
+        let mut state = shared.state.lock().unwrap();
+        state.queued.remove(&job.id);
+        if let Ok(payload) = &result {
+            state.ready_size += payload.data.len();
+        }
+        state.ready.insert(job.id, result);
+        shared.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::ContainerBuilder;
+    use crate::chunking::ChunkingParams;
+    use crate::prefetch::PrefetchReader;
+    use crate::reader::{read_asset, ReadLimits};
+    use crate::serialize_backend::serialize;
+    use crate::writer::ContainerOptions;
+    use crate::{CompressionLevel, ContainerError};
+    use dawn_assets::ir::custom::{CustomAssetTag, IRCustom};
+    use dawn_assets::ir::IRAsset;
+    use dawn_assets::{AssetHeader, AssetID};
+    use std::io::Cursor;
+
+    #[test]
+    fn reads_prefetched_and_requested_assets() {
+        let tag = CustomAssetTag::new("test", "blob").unwrap();
+        let mut builder = ContainerBuilder::new()
+            .compression(CompressionLevel::Fast)
+            .options(ContainerOptions {
+                chunking: Some(ChunkingParams {
+                    min_size: 64,
+                    avg_size: 256,
+                    max_size: 1024,
+                }),
+                ..Default::default()
+            });
+        let ids: Vec<AssetID> = (0..16).map(|i| format!("blob_{}", i).into()).collect();
+        for (i, id) in ids.iter().enumerate() {
+            let header = AssetHeader {
+                id: id.clone(),
+                ..Default::default()
+            };
+            let data = (0..4096).map(|j| ((i * j) % 251) as u8).collect();
+            let ir = IRAsset::Custom(IRCustom {
+                tag: tag.clone(),
+                data,
+            });
+            builder = builder.add_asset(header, ir);
+        }
+        let mut data = Vec::new();
+        builder.write(&mut data).unwrap();
+
+        // Tiny budget: the hints must not block the requested reads
+        let reader =
+            PrefetchReader::new(Cursor::new(data.clone()), ReadLimits::default(), 1).unwrap();
+        assert_eq!(reader.manifest().headers.len(), ids.len());
+
+        reader.prefetch(&ids[..8]);
+        // Out of the hinted order, and not hinted at all
+        for id in ids.iter().rev() {
+            let (ir, _) = reader.read(id.clone()).unwrap();
+            let expected = read_asset(&mut Cursor::new(&data), id.clone()).unwrap();
+            assert_eq!(serialize(&ir).unwrap(), serialize(&expected).unwrap());
+        }
+
+        assert!(matches!(
+            reader.read("missing".into()),
+            Err(ContainerError::AssetNotFound(_))
+        ));
+    }
+}
@@ -1,21 +1,34 @@
-use crate::compression_backend::decompress;
-use crate::serialize_backend::deserialize;
+use crate::compression_backend::{
+    decompress_blocks_bounded, decompress_bounded, BlocksDecompressor,
+};
+use crate::encryption::{decrypt_payload, EncryptionKey, EncryptionMode};
+use crate::serialize_backend::{deserialize, deserialize_from};
+use crate::signing::{signed_message, SignatureRecord, TrustConfig, TrustPolicy, Verification};
 use crate::{
-    CompressionMode, ContainerError, Manifest, DAC_MAGIC, DATA_MAGIC, MANIFEST_MAGIC, TOC,
-    TOC_MAGIC,
+    ChunkIndex, CompressionMode, ContainerError, Manifest, CHUNKS_MAGIC, DAC_MAGIC, DATA_MAGIC,
+    MANIFEST_MAGIC, SIGNATURE_MAGIC, TOC, TOC_MAGIC,
 };
 use dawn_assets::ir::IRAsset;
 use dawn_assets::AssetID;
+use log::warn;
+use memmap2::Mmap;
+use ring::digest::{Context, SHA256};
 use serde::de::DeserializeOwned;
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::io::{Read, Seek, SeekFrom};
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
 
 /// Read segments from a DAC file
 /// Returns a map of segment magic to segment offset in the file and length
 /// The actual segment data can be read by seeking to the offset and reading the length
-fn read_segments<R: Read + Seek>(
+pub(crate) fn read_segments<R: Read + Seek>(
     reader: &mut R,
 ) -> Result<HashMap<u8, (usize, usize)>, ContainerError> {
+    // Segment lengths are not trusted, they must fit into the stream
+    let size = reader.seek(SeekFrom::End(0))?;
+
     // To be sure, seek to the start
     reader.seek(SeekFrom::Start(0))?;
 
@@ -41,10 +54,10 @@ fn read_segments<R: Read + Seek>(
         // Read segment length
         let mut length_bytes = [0u8; 4];
         reader.read_exact(&mut length_bytes)?;
-        let length = u32::from_le_bytes(length_bytes) as usize;
 
         // Record the offset of the segment data
         let offset = reader.stream_position()? as usize;
+        let length = segment_length(segment_magic[0], length_bytes, offset, size)?;
         segments.insert(segment_magic[0], (offset, length));
 
         // Skip segment data
@@ -54,59 +67,1289 @@ fn read_segments<R: Read + Seek>(
     Ok(segments)
 }
 
-fn segment_to_object<R: Read + Seek, T: DeserializeOwned>(
-    reader: &mut R,
+/// Limits protecting the reader from the malformed or malicious containers.
+/// Exceeding any of them makes the reader return an error
+/// instead of allocating the memory.
+#[derive(Debug, Clone)]
+pub struct ReadLimits {
+    /// Maximal size of the segment loaded into memory.
+    /// Applies to all the segments except the data one, which is never loaded as a whole.
+    pub max_segment_size: usize,
+    /// Maximal number of the assets in the TOC and the manifest.
+    pub max_asset_count: usize,
+    /// Maximal size of the asset payload after decompression.
+    pub max_decompressed_size: usize,
+    /// Maximal ratio of the decompressed to the compressed payload size.
+    pub max_compression_ratio: usize,
+    /// Payloads decompressing into at most that many bytes are not checked
+    /// for the ratio. Blank textures and similar data compress extremely well.
+    pub ratio_check_threshold: usize,
+}
+
+impl Default for ReadLimits {
+    fn default() -> Self {
+        ReadLimits {
+            max_segment_size: 64 * 1024 * 1024,
+            max_asset_count: 100_000,
+            max_decompressed_size: 1024 * 1024 * 1024,
+            max_compression_ratio: 1024,
+            ratio_check_threshold: 16 * 1024 * 1024,
+        }
+    }
+}
+
+/// Validates the length of the segment starting at the `offset`
+/// against the size of the stream.
+pub(crate) fn segment_length(
+    magic: u8,
+    length_bytes: [u8; 4],
+    offset: usize,
+    size: u64,
+) -> Result<usize, ContainerError> {
+    let length = u32::from_le_bytes(length_bytes) as usize;
+    if offset as u64 + length as u64 > size {
+        return Err(ContainerError::SegmentOutOfBounds(magic));
+    }
+    Ok(length)
+}
+
+/// Locates the segment, checking it can be loaded into memory.
+pub(crate) fn segment_range(
     segments: &HashMap<u8, (usize, usize)>,
     magic: u8,
-) -> Result<T, ContainerError> {
-    let (offset, length) = segments
+    limits: &ReadLimits,
+) -> Result<(usize, usize), ContainerError> {
+    let (offset, length) = *segments
         .get(&magic)
         .ok_or(ContainerError::SegmentNotFound)?;
+    if length > limits.max_segment_size {
+        return Err(ContainerError::SegmentTooLarge(magic, length));
+    }
+    Ok((offset, length))
+}
 
-    reader.seek(SeekFrom::Start(*offset as u64))?;
-    let mut segment_bytes = vec![0u8; *length];
+fn segment_bytes<R: Read + Seek>(
+    reader: &mut R,
+    segments: &HashMap<u8, (usize, usize)>,
+    magic: u8,
+    limits: &ReadLimits,
+) -> Result<Vec<u8>, ContainerError> {
+    let (offset, length) = segment_range(segments, magic, limits)?;
+    reader.seek(SeekFrom::Start(offset as u64))?;
+    let mut segment_bytes = vec![0u8; length];
     reader.read_exact(&mut segment_bytes)?;
+    Ok(segment_bytes)
+}
 
-    let object: T =
-        deserialize(&segment_bytes).map_err(|e| ContainerError::DeserializationError(e))?;
+fn segment_to_object<R: Read + Seek, T: DeserializeOwned>(
+    reader: &mut R,
+    segments: &HashMap<u8, (usize, usize)>,
+    magic: u8,
+    limits: &ReadLimits,
+) -> Result<T, ContainerError> {
+    let segment_bytes = segment_bytes(reader, segments, magic, limits)?;
+    let object: T = deserialize(&segment_bytes).map_err(ContainerError::DeserializationError)?;
     Ok(object)
 }
 
 pub fn read_manifest<R: Read + Seek>(reader: &mut R) -> Result<Manifest, ContainerError> {
+    read_manifest_with_limits(reader, &ReadLimits::default())
+}
+
+pub fn read_manifest_with_limits<R: Read + Seek>(
+    reader: &mut R,
+    limits: &ReadLimits,
+) -> Result<Manifest, ContainerError> {
     let segments = read_segments(reader)?;
-    Ok(segment_to_object(reader, &segments, MANIFEST_MAGIC)?)
+    let bytes = segment_bytes(reader, &segments, MANIFEST_MAGIC, limits)?;
+    parse_manifest(&bytes, limits)
 }
 
-pub fn read_asset<R: Read + Seek>(reader: &mut R, id: AssetID) -> Result<IRAsset, ContainerError> {
-    // Locate and read the TOC
+/// Deserializes the manifest segment.
+pub(crate) fn parse_manifest(
+    bytes: &[u8],
+    limits: &ReadLimits,
+) -> Result<Manifest, ContainerError> {
+    let manifest: Manifest = deserialize(bytes).map_err(ContainerError::DeserializationError)?;
+    if manifest.headers.len() > limits.max_asset_count {
+        return Err(ContainerError::TooManyAssets(manifest.headers.len()));
+    }
+    Ok(manifest)
+}
+
+/// Checks the container signature against the trusted keys.
+/// With `TrustPolicy::Reject` anything except a valid signature made by one
+/// of the trusted keys is an error. With `TrustPolicy::Warn` the problem
+/// is only logged, and the result is returned to the caller.
+/// Call it once before handing the container to the asset hub.
+/// The payloads are not read here: the signed TOC holds their digests, and
+/// the readers reject the payloads not matching them with
+/// `ContainerError::DigestMismatch`.
+pub fn verify_container<R: Read + Seek>(
+    reader: &mut R,
+    trust: &TrustConfig,
+) -> Result<Verification, ContainerError> {
     let segments = read_segments(reader)?;
-    let toc = segment_to_object::<R, TOC>(reader, &segments, TOC_MAGIC)?;
-
-    // Locate the asset in the TOC
-    let record = toc
-        .0
-        .get(&id)
-        .ok_or(ContainerError::AssetNotFound(id.clone()))?;
-    let (data_offset, _) = segments
-        .get(&DATA_MAGIC)
-        .ok_or(ContainerError::SegmentNotFound)?;
-    let data_offset = data_offset + record.offset as usize;
+    let limits = ReadLimits::default();
 
-    // Read the asset data
-    let mut data_bytes = vec![0u8; record.length as usize];
-    reader.seek(SeekFrom::Start(data_offset as u64))?;
-    reader.read_exact(&mut data_bytes)?;
+    let verification = if segments.contains_key(&SIGNATURE_MAGIC) {
+        let signature =
+            segment_to_object::<R, SignatureRecord>(reader, &segments, SIGNATURE_MAGIC, &limits)?;
 
-    // Decompress if needed
-    let decompressed = match record.compression {
-        CompressionMode::None => data_bytes,
-        CompressionMode::Brotli => {
-            decompress(&data_bytes).map_err(|e| ContainerError::CompressionError(e))?
+        // Must match the order used by the writer
+        let mut signed = Vec::new();
+        for magic in [TOC_MAGIC, MANIFEST_MAGIC, CHUNKS_MAGIC] {
+            if segments.contains_key(&magic) {
+                signed.push((magic, segment_bytes(reader, &segments, magic, &limits)?));
+            }
         }
+        let message = signed_message(signed.iter().map(|(m, raw)| (*m, raw.as_slice())));
+        signature.verify(&message, trust)
+    } else {
+        Verification::Unsigned
     };
 
+    match (verification, trust.policy) {
+        (Verification::Trusted, _) => Ok(verification),
+        (_, TrustPolicy::Reject) => Err(ContainerError::UntrustedContainer(verification)),
+        (_, TrustPolicy::Warn) => {
+            warn!("{}", verification);
+            Ok(verification)
+        }
+    }
+}
+
+pub fn read_asset<R: Read + Seek>(reader: &mut R, id: AssetID) -> Result<IRAsset, ContainerError> {
+    read_asset_with_limits(reader, id, &ReadLimits::default())
+}
+
+pub fn read_asset_with_limits<R: Read + Seek>(
+    reader: &mut R,
+    id: AssetID,
+    limits: &ReadLimits,
+) -> Result<IRAsset, ContainerError> {
+    let index = ContainerIndex::read(reader, limits)?;
+    let location = index.locate(&id, limits)?;
+    let data_bytes = read_location(reader, &id, &location)?;
+    let data_bytes = decrypt_payload(&id, data_bytes, location.encryption, None)?;
+    decode_asset(id, data_bytes, location.compression, limits)
+}
+
+/// Container opened for reading the assets one by one.
+/// The control segments are parsed once, when the container is opened.
+pub struct Container<R: Read + Seek> {
+    reader: R,
+    index: ContainerIndex,
+    manifest: Manifest,
+    limits: ReadLimits,
+    key: Option<EncryptionKey>,
+}
+
+/// Opens the container without the key, the encrypted assets
+/// fail to read with `ContainerError::EncryptionKeyRequired`.
+pub fn open<R: Read + Seek>(reader: R) -> Result<Container<R>, ContainerError> {
+    open_with_limits(reader, None, ReadLimits::default())
+}
+
+/// Opens the container with the key decrypting the encrypted assets
+/// (see `writer::ContainerOptions::encryption`).
+///
+/// ```
+/// use dawn_assets::ir::notes::IRNotes;
+/// use dawn_assets::ir::IRAsset;
+/// use dawn_assets::AssetHeader;
+/// use dawn_dac::builder::ContainerBuilder;
+/// use dawn_dac::encryption::{EncryptionKey, EncryptionMode, EncryptionOptions};
+/// use dawn_dac::reader::{open, open_with_key};
+/// use dawn_dac::writer::ContainerOptions;
+/// use dawn_dac::ContainerError;
+/// use std::io::Cursor;
+///
+/// let key = EncryptionKey::generate().unwrap();
+/// let mut data = Vec::new();
+/// ContainerBuilder::new()
+///     .options(ContainerOptions {
+///         encryption: Some(EncryptionOptions {
+///             mode: EncryptionMode::ChaCha20Poly1305,
+///             key: key.clone(),
+///             tags: vec![],
+///         }),
+///         ..Default::default()
+///     })
+///     .add_asset(
+///         AssetHeader {
+///             id: "theme".into(),
+///             ..Default::default()
+///         },
+///         IRAsset::Notes(IRNotes { events: vec![] }),
+///     )
+///     .write(&mut data)
+///     .unwrap();
+///
+/// let mut container = open_with_key(Cursor::new(&data), key).unwrap();
+/// assert_eq!(container.manifest().encrypted, vec!["theme".into()]);
+/// assert!(container.read_asset("theme".into()).is_ok());
+///
+/// let mut container = open(Cursor::new(&data)).unwrap();
+/// assert!(matches!(
+///     container.read_asset("theme".into()),
+///     Err(ContainerError::EncryptionKeyRequired(_))
+/// ));
+/// ```
+pub fn open_with_key<R: Read + Seek>(
+    reader: R,
+    key: EncryptionKey,
+) -> Result<Container<R>, ContainerError> {
+    open_with_limits(reader, Some(key), ReadLimits::default())
+}
+
+pub fn open_with_limits<R: Read + Seek>(
+    mut reader: R,
+    key: Option<EncryptionKey>,
+    limits: ReadLimits,
+) -> Result<Container<R>, ContainerError> {
+    let manifest = read_manifest_with_limits(&mut reader, &limits)?;
+    let index = ContainerIndex::read(&mut reader, &limits)?;
+    Ok(Container {
+        reader,
+        index,
+        manifest,
+        limits,
+        key,
+    })
+}
+
+impl<R: Read + Seek> Container<R> {
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    pub fn read_asset(&mut self, id: AssetID) -> Result<IRAsset, ContainerError> {
+        let location = self.index.locate(&id, &self.limits)?;
+        let data_bytes = read_location(&mut self.reader, &id, &location)?;
+        let data_bytes = decrypt_payload(&id, data_bytes, location.encryption, self.key.as_ref())?;
+        decode_asset(id, data_bytes, location.compression, &self.limits)
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+/// Container read from memory, usually the memory-mapped file (see `open_mapped`).
+/// The payloads are not copied out of the container: the uncompressed ones are
+/// deserialized right from the mapped memory, and can be borrowed with `payload`,
+/// so loading the asset doesn't need the memory for its payload twice.
+/// The pages of the file are loaded by the OS as they are accessed
+/// and can be evicted under the memory pressure, unlike the heap memory.
+pub struct MappedContainer<B: AsRef<[u8]> = Mmap> {
+    bytes: B,
+    index: ContainerIndex,
+    manifest: Manifest,
+    limits: ReadLimits,
+    key: Option<EncryptionKey>,
+}
+
+/// Maps the container file into memory, see `MappedContainer`.
+/// The file must not be modified while it's mapped, e.g. replace it
+/// with the new one (the mapping keeps the old one) instead of rewriting it.
+/// Truncating the mapped file kills the process on some platforms.
+pub fn open_mapped(path: impl AsRef<Path>) -> Result<MappedContainer, ContainerError> {
+    open_mapped_with_limits(path, None, ReadLimits::default())
+}
+
+pub fn open_mapped_with_limits(
+    path: impl AsRef<Path>,
+    key: Option<EncryptionKey>,
+    limits: ReadLimits,
+) -> Result<MappedContainer, ContainerError> {
+    let file = File::open(path)?;
+    // SAFETY: The mapping is read only. The file is not modified by the engine
+    // while it's mapped, see the requirements of `open_mapped`.
+    let map = unsafe { Mmap::map(&file)? };
+    MappedContainer::from_bytes(map, key, limits)
+}
+
+impl<B: AsRef<[u8]>> MappedContainer<B> {
+    /// Reads the container from the bytes already in memory.
+    pub fn from_bytes(
+        bytes: B,
+        key: Option<EncryptionKey>,
+        limits: ReadLimits,
+    ) -> Result<Self, ContainerError> {
+        let mut cursor = Cursor::new(bytes.as_ref());
+        let manifest = read_manifest_with_limits(&mut cursor, &limits)?;
+        let index = ContainerIndex::read(&mut cursor, &limits)?;
+        Ok(MappedContainer {
+            bytes,
+            index,
+            manifest,
+            limits,
+            key,
+        })
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    /// Serialized payload of the asset, decompressed and decrypted.
+    /// Borrowed from the container if the payload is stored as is:
+    /// uncompressed, not encrypted and in one piece (the chunks adjacent in the file).
+    pub fn payload(&self, id: &AssetID) -> Result<Cow<'_, [u8]>, ContainerError> {
+        let location = self.index.locate(id, &self.limits)?;
+        let bytes = self.bytes.as_ref();
+        let region = |(offset, length): (u64, usize)| {
+            bytes
+                .get(offset as usize..offset as usize + length)
+                .ok_or_else(|| ContainerError::InvalidRecord(id.clone()))
+        };
+
+        let contiguous = location
+            .regions
+            .windows(2)
+            .all(|pair| pair[0].0 + pair[0].1 as u64 == pair[1].0);
+        let stored = match location.regions.first() {
+            Some((offset, _)) if contiguous => Cow::Borrowed(region((*offset, location.length))?),
+            Some(_) => {
+                let mut data = Vec::with_capacity(location.length);
+                for part in &location.regions {
+                    data.extend_from_slice(region(*part)?);
+                }
+                Cow::Owned(data)
+            }
+            None => Cow::Borrowed(&[][..]),
+        };
+        location.check(id, &stored)?;
+
+        let stored = match location.encryption {
+            EncryptionMode::None => stored,
+            _ => Cow::Owned(decrypt_payload(
+                id,
+                stored.into_owned(),
+                location.encryption,
+                self.key.as_ref(),
+            )?),
+        };
+        match location.compression {
+            CompressionMode::None => Ok(stored),
+            compression => {
+                let decompressed = decompress_payload(id, &stored, compression, &self.limits)?;
+                Ok(Cow::Owned(decompressed.into_owned()))
+            }
+        }
+    }
+
+    pub fn read_asset(&self, id: AssetID) -> Result<IRAsset, ContainerError> {
+        let payload = self.payload(&id)?;
+        deserialize(&payload).map_err(ContainerError::DeserializationError)
+    }
+
+    /// Returns the underlying bytes.
+    pub fn into_inner(self) -> B {
+        self.bytes
+    }
+}
+
+/// Opens the serialized payload of the asset for the streaming read.
+/// Unlike `read_asset`, the payload is read from the container and decompressed
+/// as it is consumed, so the large assets are never held in memory
+/// in the compressed and the decompressed form at once.
+/// Pass `&mut reader` to keep using the reader afterward.
+/// The encrypted assets can't be streamed, since the payload is authenticated
+/// as a whole. Read them with `open_with_key`.
+/// The stored payload is read once more beforehand to check its digest.
+pub fn read_asset_stream<R: Read + Seek>(
+    reader: R,
+    id: AssetID,
+) -> Result<AssetStream<R>, ContainerError> {
+    read_asset_stream_with_limits(reader, id, &ReadLimits::default())
+}
+
+pub fn read_asset_stream_with_limits<R: Read + Seek>(
+    mut reader: R,
+    id: AssetID,
+    limits: &ReadLimits,
+) -> Result<AssetStream<R>, ContainerError> {
+    let index = ContainerIndex::read(&mut reader, limits)?;
+    let location = index.locate(&id, limits)?;
+    if location.encryption != EncryptionMode::None {
+        return Err(ContainerError::EncryptionKeyRequired(id));
+    }
+
+    // Hashed in pieces, the payload is not held in memory
+    let mut regions = RegionsReader {
+        reader,
+        regions: location.regions.iter().rev().copied().collect(),
+        remaining: 0,
+    };
+    let mut context = Context::new(&SHA256);
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = regions.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        context.update(&buffer[..read]);
+    }
+    if context.finish().as_ref() != location.digest {
+        return Err(ContainerError::DigestMismatch(id));
+    }
+
+    let regions = RegionsReader {
+        reader: regions.reader,
+        regions: location.regions.into_iter().rev().collect(),
+        remaining: 0,
+    };
+    let (payload, limit, by_ratio) = match location.compression {
+        CompressionMode::None => (Payload::Plain(regions), location.length, false),
+        CompressionMode::Brotli | CompressionMode::BrotliBlocks => {
+            // Same guards against the decompression bombs as in `decode_asset`
+            let by_ratio = (location.length.saturating_mul(limits.max_compression_ratio))
+                .max(limits.ratio_check_threshold);
+            let limit = by_ratio.min(limits.max_decompressed_size);
+            let payload = match location.compression {
+                CompressionMode::BrotliBlocks => Payload::BrotliBlocks(Box::new(
+                    BlocksDecompressor::new(regions, location.length)?,
+                )),
+                _ => Payload::Brotli(Box::new(brotli::Decompressor::new(regions, 4096))),
+            };
+            (payload, limit, limit == by_ratio)
+        }
+    };
+
+    Ok(AssetStream {
+        id,
+        payload,
+        compression: location.compression,
+        produced: 0,
+        limit,
+        by_ratio,
+        exceeded: false,
+    })
+}
+
+// Reads the payload parts one after another, seeking only between them
+struct RegionsReader<R> {
+    reader: R,
+    // Parts left to read, the next one is last
+    regions: Vec<(u64, usize)>,
+    // Bytes left in the current part
+    remaining: usize,
+}
+
+impl<R: Read + Seek> Read for RegionsReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.remaining == 0 {
+            let Some((offset, length)) = self.regions.pop() else {
+                return Ok(0);
+            };
+            self.reader.seek(SeekFrom::Start(offset))?;
+            self.remaining = length;
+        }
+
+        let size = buf.len().min(self.remaining);
+        let read = self.reader.read(&mut buf[..size])?;
+        if read == 0 && size > 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= read;
+        Ok(read)
+    }
+}
+
+enum Payload<R: Read + Seek> {
+    Plain(RegionsReader<R>),
+    Brotli(Box<brotli::Decompressor<RegionsReader<R>>>),
+    BrotliBlocks(Box<BlocksDecompressor<RegionsReader<R>>>),
+}
+
+/// Decompressed serialized payload of the asset, read from the container
+/// in pieces (see `read_asset_stream`). Exceeding the `ReadLimits` while
+/// reading fails with the error wrapping the `ContainerError`.
+pub struct AssetStream<R: Read + Seek> {
+    id: AssetID,
+    payload: Payload<R>,
+    compression: CompressionMode,
+    produced: usize,
+    limit: usize,
+    // The limit comes from the compression ratio rather than from the size
+    by_ratio: bool,
+    exceeded: bool,
+}
+
+impl<R: Read + Seek> AssetStream<R> {
+    pub fn id(&self) -> &AssetID {
+        &self.id
+    }
+
+    pub fn compression(&self) -> CompressionMode {
+        self.compression
+    }
+
+    /// Bytes of the payload read so far.
+    pub fn position(&self) -> usize {
+        self.produced
+    }
+
+    fn limit_error(&self) -> ContainerError {
+        if self.by_ratio {
+            ContainerError::CompressionRatioExceeded(self.id.clone())
+        } else {
+            ContainerError::DecompressedTooLarge(self.id.clone())
+        }
+    }
+
+    /// Deserializes the asset from the rest of the stream.
+    pub fn decode(mut self) -> Result<IRAsset, ContainerError> {
+        match deserialize_from(&mut self) {
+            Ok(asset) => Ok(asset),
+            Err(_) if self.exceeded => Err(self.limit_error()),
+            Err(e) => Err(ContainerError::DeserializationError(e)),
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for AssetStream<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.exceeded {
+            return Err(std::io::Error::other(self.limit_error()));
+        }
+
+        // One byte over the limit tells the payload of exactly that size from the larger one
+        let allowed = (self.limit - self.produced).saturating_add(1);
+        let size = buf.len().min(allowed);
+        let read = match &mut self.payload {
+            Payload::Plain(reader) => reader.read(&mut buf[..size])?,
+            Payload::Brotli(reader) => reader.read(&mut buf[..size])?,
+            Payload::BrotliBlocks(reader) => reader.read(&mut buf[..size])?,
+        };
+        self.produced += read;
+        if self.produced > self.limit {
+            self.exceeded = true;
+            return Err(std::io::Error::other(self.limit_error()));
+        }
+        Ok(read)
+    }
+}
+
+/// Parsed control segments required to locate the asset payloads.
+pub(crate) struct ContainerIndex {
+    pub(crate) toc: TOC,
+    pub(crate) chunks: Option<ChunkIndex>,
+    pub(crate) data_offset: usize,
+    pub(crate) data_length: usize,
+}
+
+/// Validated position of the asset payload in the container.
+pub(crate) struct AssetLocation {
+    /// Offsets in the container and lengths of the payload parts, in order
+    pub(crate) regions: Vec<(u64, usize)>,
+    pub(crate) length: usize,
+    pub(crate) compression: CompressionMode,
+    pub(crate) encryption: EncryptionMode,
+    /// SHA-256 of the stored payload
+    pub(crate) digest: [u8; 32],
+}
+
+impl AssetLocation {
+    /// Checks the payload read from the regions against the digest from the TOC.
+    pub(crate) fn check(&self, id: &AssetID, stored: &[u8]) -> Result<(), ContainerError> {
+        let mut context = Context::new(&SHA256);
+        context.update(stored);
+        if context.finish().as_ref() != self.digest {
+            return Err(ContainerError::DigestMismatch(id.clone()));
+        }
+        Ok(())
+    }
+}
+
+impl ContainerIndex {
+    pub(crate) fn read<R: Read + Seek>(
+        reader: &mut R,
+        limits: &ReadLimits,
+    ) -> Result<Self, ContainerError> {
+        // Locate and read the TOC
+        let segments = read_segments(reader)?;
+        let toc = segment_bytes(reader, &segments, TOC_MAGIC, limits)?;
+
+        // Chunk index is optional
+        let chunks = if segments.contains_key(&CHUNKS_MAGIC) {
+            Some(segment_bytes(reader, &segments, CHUNKS_MAGIC, limits)?)
+        } else {
+            None
+        };
+
+        Self::parse(&segments, &toc, chunks.as_deref(), limits)
+    }
+
+    /// Builds the index from the raw TOC and chunk index segments.
+    pub(crate) fn parse(
+        segments: &HashMap<u8, (usize, usize)>,
+        toc: &[u8],
+        chunks: Option<&[u8]>,
+        limits: &ReadLimits,
+    ) -> Result<Self, ContainerError> {
+        let toc: TOC = deserialize(toc).map_err(ContainerError::DeserializationError)?;
+        if toc.0.len() > limits.max_asset_count {
+            return Err(ContainerError::TooManyAssets(toc.0.len()));
+        }
+
+        let (data_offset, data_length) = *segments
+            .get(&DATA_MAGIC)
+            .ok_or(ContainerError::SegmentNotFound)?;
+
+        let chunks = chunks
+            .map(deserialize::<ChunkIndex>)
+            .transpose()
+            .map_err(ContainerError::DeserializationError)?;
+
+        Ok(ContainerIndex {
+            toc,
+            chunks,
+            data_offset,
+            data_length,
+        })
+    }
+
+    pub(crate) fn locate(
+        &self,
+        id: &AssetID,
+        limits: &ReadLimits,
+    ) -> Result<AssetLocation, ContainerError> {
+        // Locate the asset in the TOC
+        let record = self
+            .toc
+            .0
+            .get(id)
+            .ok_or(ContainerError::AssetNotFound(id.clone()))?;
+        if record.length as usize > limits.max_decompressed_size {
+            return Err(ContainerError::DecompressedTooLarge(id.clone()));
+        }
+        let in_data =
+            |offset: u32, length: u32| offset as usize + length as usize <= self.data_length;
+        let region = |offset: u32, length: u32| {
+            ((self.data_offset + offset as usize) as u64, length as usize)
+        };
+
+        // If the asset is chunked, it's reassembled from the chunks
+        let chunked = self
+            .chunks
+            .as_ref()
+            .and_then(|index| index.assets.get(id).map(|ids| (index, ids)));
+        let regions = match chunked {
+            Some((index, chunk_ids)) => {
+                // Validate the chunks before allocating anything
+                let mut regions = Vec::with_capacity(chunk_ids.len());
+                let mut total = 0usize;
+                for chunk_id in chunk_ids {
+                    let chunk = index
+                        .chunks
+                        .get(*chunk_id as usize)
+                        .filter(|chunk| in_data(chunk.offset, chunk.length))
+                        .ok_or(ContainerError::InvalidChunk(*chunk_id))?;
+                    total += chunk.length as usize;
+                    regions.push(region(chunk.offset, chunk.length));
+                }
+                if total != record.length as usize {
+                    return Err(ContainerError::InvalidRecord(id.clone()));
+                }
+                regions
+            }
+            None => {
+                if !in_data(record.offset, record.length) {
+                    return Err(ContainerError::InvalidRecord(id.clone()));
+                }
+                vec![region(record.offset, record.length)]
+            }
+        };
+
+        Ok(AssetLocation {
+            regions,
+            length: record.length as usize,
+            compression: record.compression,
+            encryption: record.encryption,
+            digest: record.digest,
+        })
+    }
+}
+
+/// Reads the (possibly compressed) payload of the asset, checking its digest.
+pub(crate) fn read_location<R: Read + Seek>(
+    reader: &mut R,
+    id: &AssetID,
+    location: &AssetLocation,
+) -> Result<Vec<u8>, ContainerError> {
+    let mut data_bytes = Vec::with_capacity(location.length);
+    for (offset, length) in &location.regions {
+        let start = data_bytes.len();
+        data_bytes.resize(start + length, 0);
+        reader.seek(SeekFrom::Start(*offset))?;
+        reader.read_exact(&mut data_bytes[start..])?;
+    }
+    location.check(id, &data_bytes)?;
+    Ok(data_bytes)
+}
+
+/// Decompresses and deserializes the payload of the asset.
+pub(crate) fn decode_asset(
+    id: AssetID,
+    data_bytes: Vec<u8>,
+    compression: CompressionMode,
+    limits: &ReadLimits,
+) -> Result<IRAsset, ContainerError> {
+    let decompressed = decompress_payload(&id, &data_bytes, compression, limits)?;
+
     // Deserialize the asset
     let asset: IRAsset =
-        deserialize(&decompressed).map_err(|e| ContainerError::DeserializationError(e))?;
+        deserialize(&decompressed).map_err(ContainerError::DeserializationError)?;
     Ok(asset)
 }
+
+/// Decompresses the payload of the asset, if it's compressed.
+fn decompress_payload<'a>(
+    id: &AssetID,
+    data_bytes: &'a [u8],
+    compression: CompressionMode,
+    limits: &ReadLimits,
+) -> Result<Cow<'a, [u8]>, ContainerError> {
+    match compression {
+        CompressionMode::None => Ok(Cow::Borrowed(data_bytes)),
+        CompressionMode::Brotli | CompressionMode::BrotliBlocks => {
+            // Guard against the decompression bombs
+            let by_ratio = (data_bytes
+                .len()
+                .saturating_mul(limits.max_compression_ratio))
+            .max(limits.ratio_check_threshold);
+            let limit = by_ratio.min(limits.max_decompressed_size);
+            let decompressed = match compression {
+                CompressionMode::BrotliBlocks => decompress_blocks_bounded(data_bytes, limit),
+                _ => decompress_bounded(data_bytes, limit),
+            };
+            match decompressed.map_err(ContainerError::CompressionError)? {
+                Some(decompressed) => Ok(Cow::Owned(decompressed)),
+                None if limit == by_ratio => {
+                    Err(ContainerError::CompressionRatioExceeded(id.clone()))
+                }
+                None => Err(ContainerError::DecompressedTooLarge(id.clone())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::ContainerBuilder;
+    use crate::chunking::ChunkingParams;
+    use crate::encryption::{EncryptionKey, EncryptionMode, EncryptionOptions};
+    use crate::prefetch::PrefetchReader;
+    use crate::reader::{
+        open_mapped, open_with_key, read_asset, read_asset_stream, read_asset_stream_with_limits,
+        read_asset_with_limits, read_manifest, read_manifest_with_limits, MappedContainer,
+        ReadLimits,
+    };
+    use crate::serialize_backend::serialize;
+    use crate::writer::{BinaryAsset, ContainerOptions, ContainerWriter};
+    use crate::{
+        ChecksumAlgorithm, CompressionLevel, CompressionMode, ContainerError, Manifest, ReadMode,
+    };
+    use dawn_assets::ir::custom::{CustomAssetTag, IRCustom};
+    use dawn_assets::ir::notes::{IRNoteEvent, IRNotes};
+    use dawn_assets::ir::IRAsset;
+    use dawn_assets::{AssetHeader, AssetID, AssetTag, AssetType};
+    use proptest::collection::{hash_map, vec};
+    use proptest::prelude::*;
+    use std::borrow::Cow;
+    use std::io::{Cursor, Read};
+    use std::time::SystemTime;
+
+    fn ir_strategy() -> impl Strategy<Value = IRAsset> {
+        let event = prop_oneof![
+            (any::<u8>(), any::<u8>(), any::<u8>()).prop_map(|(channel, note, velocity)| {
+                IRNoteEvent::NoteOn {
+                    channel,
+                    note,
+                    velocity,
+                }
+            }),
+            (any::<u8>(), any::<u8>())
+                .prop_map(|(channel, note)| IRNoteEvent::NoteOff { channel, note }),
+            (0.0f32..1000.0).prop_map(|ms| IRNoteEvent::Idle { ms }),
+        ];
+        let tag = CustomAssetTag::new("test", "blob").unwrap();
+
+        prop_oneof![
+            vec(event, 0..32).prop_map(|events| IRAsset::Notes(IRNotes { events })),
+            // Repeated pattern makes the chunking and the compression do something
+            (vec(any::<u8>(), 0..64), 0usize..2048).prop_map(move |(pattern, repeat)| {
+                let data = pattern.repeat(repeat);
+                IRAsset::Custom(IRCustom {
+                    tag: tag.clone(),
+                    data,
+                })
+            }),
+        ]
+    }
+
+    fn container_strategy() -> impl Strategy<Value = (Vec<(AssetHeader, IRAsset)>, Vec<u8>)> {
+        let assets = hash_map(
+            "[a-z_]{1,16}",
+            (ir_strategy(), vec("[a-z]{1,8}", 0..3)),
+            0..8,
+        );
+        let flags = (any::<bool>(), any::<bool>(), any::<bool>(), any::<bool>());
+        (assets, flags).prop_map(|(assets, (compress, blocks, chunk, streaming))| {
+            let assets: Vec<(AssetHeader, IRAsset)> = assets
+                .into_iter()
+                .map(|(id, (ir, tags))| {
+                    let header = AssetHeader {
+                        id: AssetID::from(id),
+                        asset_type: AssetType::Notes,
+                        tags: tags.into_iter().map(AssetTag::from).collect(),
+                        ..Default::default()
+                    };
+                    (header, ir)
+                })
+                .collect();
+
+            let options = ContainerOptions {
+                chunking: chunk.then_some(ChunkingParams {
+                    min_size: 64,
+                    avg_size: 256,
+                    max_size: 1024,
+                }),
+                ..Default::default()
+            };
+            let compression = if compress {
+                CompressionLevel::Fast
+            } else {
+                CompressionLevel::None
+            };
+
+            let mut builder = ContainerBuilder::new()
+                .compression(compression)
+                .options(options);
+            if blocks {
+                builder = builder.compression_blocks(1024);
+            }
+            for (header, ir) in &assets {
+                builder = builder.add_asset(header.clone(), ir.clone());
+            }
+            let mut data = Vec::new();
+            if streaming {
+                let mut cursor = Cursor::new(&mut data);
+                builder.write_streaming(&mut cursor).unwrap();
+            } else {
+                builder.write(&mut data).unwrap();
+            }
+            (assets, data)
+        })
+    }
+
+    #[test]
+    fn limits_are_enforced() {
+        let header = AssetHeader {
+            id: "bomb".into(),
+            ..Default::default()
+        };
+        let ir = IRAsset::Custom(IRCustom {
+            tag: CustomAssetTag::new("test", "blob").unwrap(),
+            data: vec![0u8; 4 * 1024 * 1024],
+        });
+        let mut data = Vec::new();
+        ContainerBuilder::new()
+            .compression(CompressionLevel::Fast)
+            .add_asset(header, ir)
+            .write(&mut data)
+            .unwrap();
+
+        let read = |limits: &ReadLimits| {
+            read_asset_with_limits(&mut Cursor::new(&data), "bomb".into(), limits)
+        };
+        assert!(read(&ReadLimits::default()).is_ok());
+        assert!(matches!(
+            read(&ReadLimits {
+                max_compression_ratio: 16,
+                ratio_check_threshold: 0,
+                ..Default::default()
+            }),
+            Err(ContainerError::CompressionRatioExceeded(id)) if id == "bomb".into()
+        ));
+        assert!(matches!(
+            read(&ReadLimits {
+                max_decompressed_size: 1024 * 1024,
+                ..Default::default()
+            }),
+            Err(ContainerError::DecompressedTooLarge(id)) if id == "bomb".into()
+        ));
+
+        // The stream stops at the limit instead of decompressing the whole payload
+        let stream = |limits: &ReadLimits| {
+            read_asset_stream_with_limits(Cursor::new(&data), "bomb".into(), limits).unwrap()
+        };
+        assert!(stream(&ReadLimits::default()).decode().is_ok());
+        let mut limited = stream(&ReadLimits {
+            max_decompressed_size: 1024 * 1024,
+            ..Default::default()
+        });
+        let mut sink = Vec::new();
+        assert!(limited.read_to_end(&mut sink).is_err());
+        assert!(sink.len() <= 1024 * 1024 + 1);
+        assert!(matches!(
+            stream(&ReadLimits {
+                max_compression_ratio: 16,
+                ratio_check_threshold: 0,
+                ..Default::default()
+            })
+            .decode(),
+            Err(ContainerError::CompressionRatioExceeded(id)) if id == "bomb".into()
+        ));
+
+        let limits = ReadLimits {
+            max_asset_count: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            read_manifest_with_limits(&mut Cursor::new(&data), &limits),
+            Err(ContainerError::TooManyAssets(1))
+        ));
+        let limits = ReadLimits {
+            max_segment_size: 4,
+            ..Default::default()
+        };
+        assert!(matches!(
+            read_manifest_with_limits(&mut Cursor::new(&data), &limits),
+            Err(ContainerError::SegmentTooLarge(_, _))
+        ));
+    }
+
+    #[test]
+    fn block_compression_round_trip() {
+        let header = AssetHeader {
+            id: "large".into(),
+            ..Default::default()
+        };
+        let ir = IRAsset::Custom(IRCustom {
+            tag: CustomAssetTag::new("test", "blob").unwrap(),
+            data: (0..256 * 1024).map(|i| (i % 251) as u8).collect(),
+        });
+        let mut data = Vec::new();
+        ContainerBuilder::new()
+            .compression(CompressionLevel::Fast)
+            .compression_blocks(64 * 1024)
+            .add_asset(header, ir.clone())
+            .write(&mut data)
+            .unwrap();
+
+        let read = read_asset(&mut Cursor::new(&data), "large".into()).unwrap();
+        assert_eq!(serialize(&read).unwrap(), serialize(&ir).unwrap());
+
+        let stream = read_asset_stream(Cursor::new(&data), "large".into()).unwrap();
+        assert!(matches!(
+            stream.compression(),
+            CompressionMode::BrotliBlocks
+        ));
+        let streamed = stream.decode().unwrap();
+        assert_eq!(serialize(&streamed).unwrap(), serialize(&ir).unwrap());
+
+        // The limits apply to the blocks as a whole
+        let limits = ReadLimits {
+            max_decompressed_size: 100 * 1024,
+            ..Default::default()
+        };
+        assert!(matches!(
+            read_asset_with_limits(&mut Cursor::new(&data), "large".into(), &limits),
+            Err(ContainerError::DecompressedTooLarge(_))
+        ));
+        assert!(matches!(
+            read_asset_stream_with_limits(Cursor::new(&data), "large".into(), &limits)
+                .unwrap()
+                .decode(),
+            Err(ContainerError::DecompressedTooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn appended_container_keeps_payloads() {
+        let blob = |id: &str, data: Vec<u8>| {
+            let ir = IRAsset::Custom(IRCustom {
+                tag: CustomAssetTag::new("test", "blob").unwrap(),
+                data,
+            });
+            BinaryAsset {
+                raw: serialize(&ir).unwrap(),
+                header: AssetHeader {
+                    id: id.into(),
+                    ..Default::default()
+                },
+                compression: CompressionMode::None,
+            }
+        };
+        let options = ContainerOptions {
+            chunking: Some(ChunkingParams {
+                min_size: 64,
+                avg_size: 256,
+                max_size: 1024,
+            }),
+            ..Default::default()
+        };
+        let finish = |writer: ContainerWriter<&mut Cursor<Vec<u8>>>| {
+            let manifest = Manifest {
+                author: None,
+                description: None,
+                version: None,
+                license: None,
+                tool: "append-test".to_string(),
+                tool_version: "0.1.0".to_string(),
+                created: SystemTime::UNIX_EPOCH,
+                read_mode: ReadMode::Flat,
+                checksum_algorithm: ChecksumAlgorithm::Blake3,
+                headers: writer.headers().to_vec(),
+                variants: Default::default(),
+                guids: Default::default(),
+                encrypted: vec![],
+            };
+            let cursor = writer.finish(&manifest).unwrap();
+            let end = cursor.position() as usize;
+            cursor.get_mut().truncate(end);
+        };
+        let payload = |data: &Cursor<Vec<u8>>, id: &str| {
+            let ir = read_asset(&mut Cursor::new(data.get_ref()), id.into()).unwrap();
+            serialize(&ir).unwrap()
+        };
+
+        let large = (0..8192u32)
+            .map(|i| (i * 7 % 251) as u8)
+            .collect::<Vec<_>>();
+        let mut data = Cursor::new(Vec::new());
+        let mut writer = ContainerWriter::new(&mut data, options.clone()).unwrap();
+        writer.add(blob("large", large.clone())).unwrap();
+        writer.add(blob("small", vec![1, 2, 3])).unwrap();
+        writer.add(blob("dropped", vec![4, 5, 6])).unwrap();
+        finish(writer);
+        let size = data.get_ref().len();
+
+        let mut writer = ContainerWriter::append(&mut data, options).unwrap();
+        assert!(writer.keep(&blob("large", vec![]).header).unwrap());
+        assert!(!writer.keep(&blob("new", vec![]).header).unwrap());
+        writer.add(blob("small", vec![7, 8])).unwrap();
+        writer.add(blob("new", vec![9])).unwrap();
+        assert!(matches!(
+            writer.keep(&blob("small", vec![]).header),
+            Err(ContainerError::DuplicateAsset(_))
+        ));
+        finish(writer);
+
+        // The kept payload is not written again
+        assert!(data.get_ref().len() < size + 256);
+        let manifest = read_manifest(&mut Cursor::new(data.get_ref())).unwrap();
+        assert_eq!(manifest.headers.len(), 3);
+        assert_eq!(payload(&data, "large"), blob("large", large).raw);
+        assert_eq!(payload(&data, "small"), blob("small", vec![7, 8]).raw);
+        assert_eq!(payload(&data, "new"), blob("new", vec![9]).raw);
+        assert!(matches!(
+            read_asset(&mut Cursor::new(data.get_ref()), "dropped".into()),
+            Err(ContainerError::AssetNotFound(_))
+        ));
+
+        // The data segment of the in-memory writer goes last
+        let mut data = Vec::new();
+        ContainerBuilder::new().write(&mut data).unwrap();
+        assert!(matches!(
+            ContainerWriter::append(Cursor::new(data), ContainerOptions::default()),
+            Err(ContainerError::NotAppendable)
+        ));
+    }
+
+    #[test]
+    fn encrypts_selected_assets() {
+        let key = EncryptionKey::generate().unwrap();
+        let secret = b"licensed melody".repeat(64);
+        let blob = |id: &str, tags: &[&str]| {
+            let header = AssetHeader {
+                id: id.into(),
+                tags: tags.iter().map(|tag| AssetTag::from(*tag)).collect(),
+                ..Default::default()
+            };
+            let ir = IRAsset::Custom(IRCustom {
+                tag: CustomAssetTag::new("test", "blob").unwrap(),
+                data: secret.clone(),
+            });
+            (header, ir)
+        };
+        let (licensed, licensed_ir) = blob("licensed", &["licensed"]);
+        let (free, free_ir) = blob("free", &[]);
+
+        let mut data = Vec::new();
+        ContainerBuilder::new()
+            .options(ContainerOptions {
+                chunking: Some(ChunkingParams {
+                    min_size: 64,
+                    avg_size: 256,
+                    max_size: 1024,
+                }),
+                encryption: Some(EncryptionOptions {
+                    mode: EncryptionMode::ChaCha20Poly1305,
+                    key: key.clone(),
+                    tags: vec!["licensed".into()],
+                }),
+                ..Default::default()
+            })
+            .add_asset(licensed, licensed_ir.clone())
+            .add_asset(free, free_ir)
+            .write(&mut data)
+            .unwrap();
+
+        let manifest = read_manifest(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(manifest.encrypted, vec!["licensed".into()]);
+        // Only the free asset is stored as is
+        let occurrences = data
+            .windows(secret.len())
+            .filter(|window| *window == secret.as_slice())
+            .count();
+        assert_eq!(occurrences, 1);
+
+        let mut container = open_with_key(Cursor::new(&data), key.clone()).unwrap();
+        let read = container.read_asset("licensed".into()).unwrap();
+        assert_eq!(serialize(&read).unwrap(), serialize(&licensed_ir).unwrap());
+        assert!(container.read_asset("free".into()).is_ok());
+        let prefetch = PrefetchReader::new(Cursor::new(data.clone()), ReadLimits::default(), 0)
+            .unwrap()
+            .with_key(key);
+        assert!(prefetch.read("licensed".into()).is_ok());
+
+        assert!(read_asset(&mut Cursor::new(&data), "free".into()).is_ok());
+        assert!(matches!(
+            read_asset(&mut Cursor::new(&data), "licensed".into()),
+            Err(ContainerError::EncryptionKeyRequired(_))
+        ));
+        assert!(matches!(
+            read_asset_stream(Cursor::new(&data), "licensed".into()),
+            Err(ContainerError::EncryptionKeyRequired(_))
+        ));
+        let other_key = EncryptionKey::generate().unwrap();
+        let mut container = open_with_key(Cursor::new(&data), other_key).unwrap();
+        assert!(matches!(
+            container.read_asset("licensed".into()),
+            Err(ContainerError::DecryptionFailed(_))
+        ));
+    }
+
+    #[test]
+    fn mapped_container_borrows_payloads() {
+        let tag = CustomAssetTag::new("test", "blob").unwrap();
+        let custom = |id: &str, fill: u8| {
+            (
+                AssetHeader {
+                    id: id.into(),
+                    ..Default::default()
+                },
+                IRAsset::Custom(IRCustom {
+                    tag: tag.clone(),
+                    data: vec![fill; 64 * 1024],
+                }),
+            )
+        };
+        let write = |compression: CompressionLevel, path: &std::path::Path| {
+            let (header, ir) = custom("level", 7);
+            let mut data = Vec::new();
+            ContainerBuilder::new()
+                .compression(compression)
+                .add_asset(header, ir)
+                .write(&mut data)
+                .unwrap();
+            std::fs::write(path, data).unwrap();
+        };
+        let (_, ir) = custom("level", 7);
+        let dir = std::env::temp_dir().join(format!("dac_mapped_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("plain.dac");
+        write(CompressionLevel::None, &path);
+        let container = open_mapped(&path).unwrap();
+        let payload = container.payload(&"level".into()).unwrap();
+        assert!(matches!(payload, Cow::Borrowed(_)));
+        assert_eq!(payload.as_ref(), serialize(&ir).unwrap().as_slice());
+        let read = container.read_asset("level".into()).unwrap();
+        assert_eq!(serialize(&read).unwrap(), serialize(&ir).unwrap());
+        assert!(matches!(
+            container.read_asset("missing".into()),
+            Err(ContainerError::AssetNotFound(_))
+        ));
+
+        let path = dir.join("compressed.dac");
+        write(CompressionLevel::Fast, &path);
+        let container = open_mapped(&path).unwrap();
+        let payload = container.payload(&"level".into()).unwrap();
+        assert!(matches!(payload, Cow::Owned(_)));
+        assert_eq!(payload.as_ref(), serialize(&ir).unwrap().as_slice());
+
+        drop(container);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn write_read_round_trip((assets, data) in container_strategy()) {
+            let manifest = read_manifest(&mut Cursor::new(&data)).unwrap();
+            prop_assert_eq!(manifest.headers.len(), assets.len());
+            let mapped =
+                MappedContainer::from_bytes(data.as_slice(), None, ReadLimits::default()).unwrap();
+
+            for (header, ir) in &assets {
+                prop_assert!(manifest.headers.contains(header));
+                let read = read_asset(&mut Cursor::new(&data), header.id.clone()).unwrap();
+                prop_assert_eq!(serialize(&read).unwrap(), serialize(ir).unwrap());
+                let payload = mapped.payload(&header.id).unwrap();
+                prop_assert_eq!(payload.into_owned(), serialize(ir).unwrap());
+
+                // Streamed in small pieces, as the large assets are
+                let mut stream = read_asset_stream(Cursor::new(&data), header.id.clone()).unwrap();
+                let mut payload = Vec::new();
+                let mut piece = [0u8; 97];
+                loop {
+                    let read = stream.read(&mut piece).unwrap();
+                    if read == 0 {
+                        break;
+                    }
+                    payload.extend_from_slice(&piece[..read]);
+                }
+                prop_assert_eq!(payload, serialize(ir).unwrap());
+                let streamed = read_asset_stream(Cursor::new(&data), header.id.clone())
+                    .unwrap()
+                    .decode()
+                    .unwrap();
+                prop_assert_eq!(serialize(&streamed).unwrap(), serialize(ir).unwrap());
+            }
+        }
+
+        #[test]
+        fn corrupted_container_does_not_panic(
+            (assets, mut data) in container_strategy(),
+            flips in vec((any::<prop::sample::Index>(), any::<u8>()), 1..16),
+            truncate in any::<prop::sample::Index>(),
+        ) {
+            for (index, value) in flips {
+                let at = index.index(data.len());
+                data[at] ^= value;
+            }
+            data.truncate(truncate.index(data.len() + 1));
+
+            // Any result is fine, as long as it's not a panic
+            let _ = read_manifest(&mut Cursor::new(&data));
+            let mapped = MappedContainer::from_bytes(data.as_slice(), None, ReadLimits::default());
+            for (header, _) in &assets {
+                if let Ok(mapped) = &mapped {
+                    let _ = mapped.read_asset(header.id.clone());
+                }
+                let _ = read_asset(&mut Cursor::new(&data), header.id.clone());
+                if let Ok(stream) = read_asset_stream(Cursor::new(&data), header.id.clone()) {
+                    let _ = stream.decode();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,277 @@
+use crate::ContainerError;
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Ed25519 public key used to verify the container signature.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PublicKey([u8; 32]);
+
+impl PublicKey {
+    pub fn from_bytes(bytes: [u8; 32]) -> PublicKey {
+        PublicKey(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    pub fn hex_string(&self) -> String {
+        self.0.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+/// Ed25519 private key used by the writer to sign the container.
+#[derive(Clone)]
+pub struct SigningKey(Arc<Ed25519KeyPair>);
+
+impl Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Never print the private part
+        write!(f, "SigningKey({})", self.public_key().hex_string())
+    }
+}
+
+impl SigningKey {
+    /// Generates a new key pair and returns it in the PKCS#8 v2 form.
+    /// Store it securely, it's the only way to get the private key back.
+    pub fn generate_pkcs8() -> Result<Vec<u8>, ContainerError> {
+        let document = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map_err(|_| ContainerError::SigningError("Failed to generate key".to_string()))?;
+        Ok(document.as_ref().to_vec())
+    }
+
+    /// Loads the key pair from the PKCS#8 v2 document.
+    pub fn from_pkcs8(document: &[u8]) -> Result<SigningKey, ContainerError> {
+        let pair = Ed25519KeyPair::from_pkcs8(document)
+            .map_err(|e| ContainerError::SigningError(e.to_string()))?;
+        Ok(SigningKey(Arc::new(pair)))
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        let mut bytes = [0u8; 32];
+        bytes.copy_from_slice(self.0.public_key().as_ref());
+        PublicKey(bytes)
+    }
+
+    pub(crate) fn sign(&self, message: &[u8]) -> SignatureRecord {
+        SignatureRecord {
+            public_key: self.public_key(),
+            signature: self.0.sign(message).as_ref().to_vec(),
+        }
+    }
+}
+
+/// What to do when the container signature cannot be trusted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TrustPolicy {
+    /// Refuse to read the container.
+    #[default]
+    Reject,
+    /// Log a warning and continue reading.
+    Warn,
+}
+
+/// Configuration of the container signature verification.
+#[derive(Debug, Clone, Default)]
+pub struct TrustConfig {
+    /// Keys the container must be signed with.
+    pub trusted_keys: Vec<PublicKey>,
+    pub policy: TrustPolicy,
+}
+
+/// Result of the container signature verification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verification {
+    /// Signature is valid and made by one of the trusted keys.
+    Trusted,
+    /// The container has no signature.
+    Unsigned,
+    /// Signature is valid, but the key is not trusted.
+    UntrustedKey(PublicKey),
+    /// Signature doesn't match the content. The container was tampered with.
+    InvalidSignature,
+}
+
+impl std::fmt::Display for Verification {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verification::Trusted => write!(f, "Trusted"),
+            Verification::Unsigned => write!(f, "Container is not signed"),
+            Verification::UntrustedKey(key) => {
+                write!(
+                    f,
+                    "Container is signed by untrusted key {}",
+                    key.hex_string()
+                )
+            }
+            Verification::InvalidSignature => write!(f, "Container signature is invalid"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct SignatureRecord {
+    public_key: PublicKey,
+    #[serde(with = "serde_bytes")]
+    signature: Vec<u8>,
+}
+
+impl SignatureRecord {
+    pub(crate) fn verify(&self, message: &[u8], trust: &TrustConfig) -> Verification {
+        let key = UnparsedPublicKey::new(&ED25519, self.public_key.as_bytes());
+        if key.verify(message, &self.signature).is_err() {
+            Verification::InvalidSignature
+        } else if trust.trusted_keys.contains(&self.public_key) {
+            Verification::Trusted
+        } else {
+            Verification::UntrustedKey(self.public_key)
+        }
+    }
+}
+
+/// Builds the message to sign from the control segments.
+/// Each present segment contributes its magic, length and content.
+pub(crate) fn signed_message<'a>(segments: impl Iterator<Item = (u8, &'a [u8])>) -> Vec<u8> {
+    let mut message = Vec::new();
+    for (magic, raw) in segments {
+        message.push(magic);
+        message.extend_from_slice(&(raw.len() as u32).to_le_bytes());
+        message.extend_from_slice(raw);
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::ContainerBuilder;
+    use crate::prefetch::PrefetchReader;
+    use crate::reader::{
+        open, read_asset, read_asset_stream, verify_container, MappedContainer, ReadLimits,
+    };
+    use crate::signing::{SigningKey, TrustConfig, TrustPolicy, Verification};
+    use crate::writer::{write_container, ContainerOptions};
+    use crate::{ChecksumAlgorithm, ContainerError, Manifest, ReadMode};
+    use dawn_assets::ir::custom::{CustomAssetTag, IRCustom};
+    use dawn_assets::ir::IRAsset;
+    use dawn_assets::AssetHeader;
+    use std::io::Cursor;
+    use std::time::SystemTime;
+
+    fn signed_container(key: &SigningKey) -> Vec<u8> {
+        let manifest = Manifest {
+            author: None,
+            description: None,
+            version: None,
+            license: None,
+            tool: "signing-test".to_string(),
+            tool_version: "0.1.0".to_string(),
+            created: SystemTime::UNIX_EPOCH,
+            read_mode: ReadMode::Flat,
+            checksum_algorithm: ChecksumAlgorithm::Blake3,
+            headers: vec![],
+            variants: Default::default(),
+            guids: Default::default(),
+            encrypted: vec![],
+        };
+        let options = ContainerOptions {
+            signing_key: Some(key.clone()),
+            ..Default::default()
+        };
+
+        let mut data = Vec::new();
+        write_container(&mut data, manifest, vec![], &options).unwrap();
+        data
+    }
+
+    #[test]
+    fn detects_tampering_and_untrusted_keys() {
+        let key = SigningKey::from_pkcs8(&SigningKey::generate_pkcs8().unwrap()).unwrap();
+        let data = signed_container(&key);
+        let trust = TrustConfig {
+            trusted_keys: vec![key.public_key()],
+            policy: TrustPolicy::Reject,
+        };
+
+        let result = verify_container(&mut Cursor::new(&data), &trust).unwrap();
+        assert_eq!(result, Verification::Trusted);
+
+        // Not trusting anyone
+        let result = verify_container(&mut Cursor::new(&data), &TrustConfig::default());
+        assert!(matches!(
+            result,
+            Err(ContainerError::UntrustedContainer(
+                Verification::UntrustedKey(_)
+            ))
+        ));
+
+        // Patch the tool name in the manifest
+        let mut tampered = data.clone();
+        let at = tampered
+            .windows(12)
+            .position(|w| w == b"signing-test")
+            .unwrap();
+        tampered[at] = b'S';
+        let warn = TrustConfig {
+            policy: TrustPolicy::Warn,
+            ..trust
+        };
+        let result = verify_container(&mut Cursor::new(&tampered), &warn).unwrap();
+        assert_eq!(result, Verification::InvalidSignature);
+    }
+
+    #[test]
+    fn rejects_tampered_payloads() {
+        let key = SigningKey::from_pkcs8(&SigningKey::generate_pkcs8().unwrap()).unwrap();
+        let tag = CustomAssetTag::new("test", "blob").unwrap();
+        let mut data = Vec::new();
+        ContainerBuilder::new()
+            .options(ContainerOptions {
+                signing_key: Some(key.clone()),
+                ..Default::default()
+            })
+            .add_asset(
+                AssetHeader {
+                    id: "blob".into(),
+                    ..Default::default()
+                },
+                IRAsset::Custom(IRCustom {
+                    tag,
+                    data: b"payload of the signed asset".to_vec(),
+                }),
+            )
+            .write(&mut data)
+            .unwrap();
+        let trust = TrustConfig {
+            trusted_keys: vec![key.public_key()],
+            policy: TrustPolicy::Reject,
+        };
+
+        // Patch the payload in the data segment, the control segments are intact
+        let mut tampered = data.clone();
+        let at = tampered.windows(7).position(|w| w == b"payload").unwrap();
+        tampered[at] = b'P';
+        let result = verify_container(&mut Cursor::new(&tampered), &trust).unwrap();
+        assert_eq!(result, Verification::Trusted);
+
+        let rejected = |result: Result<IRAsset, ContainerError>| {
+            matches!(result, Err(ContainerError::DigestMismatch(_)))
+        };
+        assert!(read_asset(&mut Cursor::new(&data), "blob".into()).is_ok());
+        assert!(rejected(read_asset(
+            &mut Cursor::new(&tampered),
+            "blob".into()
+        )));
+        let mut container = open(Cursor::new(&tampered)).unwrap();
+        assert!(rejected(container.read_asset("blob".into())));
+        let mapped = MappedContainer::from_bytes(&tampered, None, ReadLimits::default()).unwrap();
+        assert!(rejected(mapped.read_asset("blob".into())));
+        let stream = read_asset_stream(Cursor::new(&tampered), "blob".into());
+        assert!(matches!(stream, Err(ContainerError::DigestMismatch(_))));
+        let prefetch =
+            PrefetchReader::new(Cursor::new(tampered), ReadLimits::default(), 1 << 20).unwrap();
+        assert!(rejected(prefetch.read("blob".into()).map(|(ir, _)| ir)));
+    }
+}
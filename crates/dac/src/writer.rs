@@ -1,13 +1,19 @@
+use crate::chunking::ChunkingParams;
+use crate::encryption::{EncryptionMode, EncryptionOptions};
+use crate::reader::{read_segments, ContainerIndex, ReadLimits};
 use crate::serialize_backend::serialize;
+use crate::signing::{signed_message, SigningKey};
 use crate::{
-    CompressionMode, ContainerError, Manifest, Record, DAC_MAGIC, DATA_MAGIC, MANIFEST_MAGIC, TOC,
-    TOC_MAGIC,
+    ChunkIndex, ChunkRecord, CompressionMode, ContainerError, Manifest, Record, CHUNKS_MAGIC,
+    DAC_MAGIC, DATA_MAGIC, MANIFEST_MAGIC, SIGNATURE_MAGIC, TOC, TOC_MAGIC,
 };
-use dawn_assets::AssetHeader;
+use dawn_assets::{AssetHeader, AssetID};
 use dawn_util::profile::Measure;
+use log::debug;
+use ring::digest::{digest, SHA256};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::io::Write;
+use std::io::{Read, Seek, SeekFrom, Write};
 
 struct Segment {
     magic: u8,
@@ -33,17 +39,22 @@ fn write_container_from_segments<W: Write>(
 
     // Write segments
     for segment in segments {
-        // Write segment magic
-        writer.write_all(segment.magic.to_le_bytes().as_slice())?;
+        write_segment(writer, &segment)?;
+    }
 
-        // Write segment length
-        let length = segment.raw.len() as u32;
-        writer.write_all(length.to_le_bytes().as_slice())?;
+    Ok(())
+}
 
-        // Write segment data
-        writer.write_all(segment.raw.as_slice())?;
-    }
+fn write_segment<W: Write>(writer: &mut W, segment: &Segment) -> Result<(), ContainerError> {
+    // Write segment magic
+    writer.write_all(segment.magic.to_le_bytes().as_slice())?;
+
+    // Write segment length
+    let length = length_u32(segment.raw.len())?;
+    writer.write_all(length.to_le_bytes().as_slice())?;
 
+    // Write segment data
+    writer.write_all(segment.raw.as_slice())?;
     Ok(())
 }
 
@@ -53,7 +64,7 @@ fn write_container_from_segments<W: Write>(
 pub fn write_data_segment<W: Write>(
     writer: &mut W,
     total_len: u32,
-    binaries: Vec<BinaryAsset>,
+    blobs: Vec<&[u8]>,
 ) -> Result<(), ContainerError> {
     let _measure = Measure::new("Write DAC data segment".to_string());
 
@@ -64,54 +75,422 @@ pub fn write_data_segment<W: Write>(
     writer.write_all(total_len.to_le_bytes().as_slice())?;
 
     // Write concatenated binary data
-    for binary in binaries {
-        writer.write_all(binary.raw.as_slice())?;
+    for blob in blobs {
+        writer.write_all(blob)?;
     }
 
     Ok(())
 }
 
-pub fn write_container<W: Write>(
-    writer: &mut W,
-    manifest: Manifest,
-    binaries: Vec<BinaryAsset>,
-) -> Result<(), ContainerError> {
-    let _measure = Measure::new("Write DAC container".to_string());
+/// Options of the container layout.
+#[derive(Debug, Clone, Default)]
+pub struct ContainerOptions {
+    /// If set, asset payloads are split into content-defined chunks,
+    /// and the chunks shared between assets are stored only once.
+    /// Works best with uncompressed payloads, since compression
+    /// usually hides the similarity of the data.
+    pub chunking: Option<ChunkingParams>,
+    /// If set, the control segments (TOC, manifest and chunk index) are signed
+    /// with the key, so the reader can detect tampered containers. The payloads
+    /// are covered by their digests in the TOC.
+    pub signing_key: Option<SigningKey>,
+    /// If set, the payloads of the selected assets are encrypted after the compression.
+    /// Encrypted payloads are never chunked, since they share no data.
+    pub encryption: Option<EncryptionOptions>,
+}
 
-    // Create TOC (Table of contents)
-    // All the offsets are relative to the start of the data segment
-    let mut toc = TOC(HashMap::new());
-    let mut offset = 0u32;
-    for binary in &binaries {
-        toc.0.insert(
-            binary.header.id.clone(),
-            Record {
-                offset,
-                length: binary.raw.len() as u32,
-                compression: binary.compression,
-            },
-        );
+/// Encrypts the payload if the options select the asset.
+fn encrypt(
+    binary: BinaryAsset,
+    options: &ContainerOptions,
+) -> Result<(BinaryAsset, EncryptionMode), ContainerError> {
+    match &options.encryption {
+        Some(encryption) if encryption.selects(&binary.header) => {
+            let raw = encryption
+                .key
+                .encrypt(encryption.mode, &binary.header.id, &binary.raw)?;
+            Ok((BinaryAsset { raw, ..binary }, encryption.mode))
+        }
+        _ => Ok((binary, EncryptionMode::None)),
+    }
+}
+
+fn length_u32(length: usize) -> Result<u32, ContainerError> {
+    u32::try_from(length).map_err(|_| ContainerError::SizeOverflow)
+}
+
+/// Places the payloads into the data segment and builds the TOC and the chunk index.
+/// All the offsets are relative to the start of the data segment.
+struct Layout {
+    toc: TOC,
+    index: ChunkIndex,
+    // Chunks are identified by the digest, so the written payloads can be dropped
+    known_chunks: HashMap<[u8; 32], u32>,
+    offset: u32,
+}
+
+impl Layout {
+    fn new() -> Self {
+        Layout {
+            toc: TOC(HashMap::new()),
+            index: ChunkIndex::default(),
+            known_chunks: HashMap::new(),
+            offset: 0,
+        }
+    }
 
-        offset = offset
-            .checked_add(binary.raw.len() as u32)
+    fn advance(&mut self, length: u32) -> Result<u32, ContainerError> {
+        let offset = self.offset;
+        self.offset = offset
+            .checked_add(length)
             .ok_or(ContainerError::SizeOverflow)?;
+        Ok(offset)
     }
 
-    // Serialize and write control segments
-    write_container_from_segments(
-        writer,
-        vec![
+    /// Returns the parts of the payload that must be appended to the data segment.
+    fn place<'a>(
+        &mut self,
+        binary: &'a BinaryAsset,
+        encryption: EncryptionMode,
+        chunking: Option<&ChunkingParams>,
+    ) -> Result<Vec<&'a [u8]>, ContainerError> {
+        let id = &binary.header.id;
+        if self.toc.0.contains_key(id) {
+            return Err(ContainerError::DuplicateAsset(id.clone()));
+        }
+
+        let length = length_u32(binary.raw.len())?;
+        let payload_digest: [u8; 32] = digest(&SHA256, &binary.raw).as_ref().try_into().unwrap();
+        let chunks = match chunking {
+            Some(params)
+                if binary.raw.len() > params.min_size && encryption == EncryptionMode::None =>
+            {
+                params.split(&binary.raw)
+            }
+            _ => vec![],
+        };
+
+        let mut blobs = Vec::new();
+        if chunks.len() > 1 {
+            let mut ids = Vec::with_capacity(chunks.len());
+            for range in chunks {
+                let chunk = &binary.raw[range];
+                let digest: [u8; 32] = digest(&SHA256, chunk).as_ref().try_into().unwrap();
+                let chunk_id = match self.known_chunks.get(&digest) {
+                    Some(chunk_id) => *chunk_id,
+                    None => {
+                        let chunk_id = length_u32(self.index.chunks.len())?;
+                        let length = length_u32(chunk.len())?;
+                        let offset = self.advance(length)?;
+                        self.index.chunks.push(ChunkRecord { offset, length });
+                        self.known_chunks.insert(digest, chunk_id);
+                        blobs.push(chunk);
+                        chunk_id
+                    }
+                };
+                ids.push(chunk_id);
+            }
+            self.index.assets.insert(id.clone(), ids);
+
+            // The record describes the reassembled payload
+            self.toc.0.insert(
+                id.clone(),
+                Record {
+                    offset: 0,
+                    length,
+                    compression: binary.compression,
+                    encryption,
+                    digest: payload_digest,
+                },
+            );
+        } else {
+            let offset = self.advance(length)?;
+            self.toc.0.insert(
+                id.clone(),
+                Record {
+                    offset,
+                    length,
+                    compression: binary.compression,
+                    encryption,
+                    digest: payload_digest,
+                },
+            );
+            blobs.push(binary.raw.as_slice());
+        }
+
+        Ok(blobs)
+    }
+
+    /// Serializes the TOC, manifest, chunk index and the signature segments.
+    fn control_segments(
+        self,
+        manifest: &Manifest,
+        options: &ContainerOptions,
+    ) -> Result<Vec<Segment>, ContainerError> {
+        if options.chunking.is_some() {
+            debug!(
+                "Chunked {} assets into {} unique chunks",
+                self.index.assets.len(),
+                self.index.chunks.len()
+            );
+        }
+
+        let mut manifest = manifest.clone();
+        manifest.encrypted = self
+            .toc
+            .0
+            .iter()
+            .filter(|(_, record)| record.encryption != EncryptionMode::None)
+            .map(|(id, _)| id.clone())
+            .collect();
+        manifest.encrypted.sort();
+
+        let mut segments = vec![
             Segment {
                 magic: TOC_MAGIC,
-                raw: serialize(&toc).map_err(|e| ContainerError::SerializationError(e))?,
+                raw: serialize(&self.toc).map_err(ContainerError::SerializationError)?,
             },
             Segment {
                 magic: MANIFEST_MAGIC,
-                raw: serialize(&manifest).map_err(|e| ContainerError::SerializationError(e))?,
+                raw: serialize(&manifest).map_err(ContainerError::SerializationError)?,
             },
-        ],
-    )?;
+        ];
+        if !self.index.assets.is_empty() {
+            segments.push(Segment {
+                magic: CHUNKS_MAGIC,
+                raw: serialize(&self.index).map_err(ContainerError::SerializationError)?,
+            });
+        }
+        if let Some(key) = &options.signing_key {
+            let message = signed_message(segments.iter().map(|s| (s.magic, s.raw.as_slice())));
+            let signature = key.sign(&message);
+            debug!(
+                "Signed container with key {}",
+                key.public_key().hex_string()
+            );
+            segments.push(Segment {
+                magic: SIGNATURE_MAGIC,
+                raw: serialize(&signature).map_err(ContainerError::SerializationError)?,
+            });
+        }
+
+        Ok(segments)
+    }
+}
+
+/// Writes the container with all the payloads in memory.
+/// See `ContainerWriter` for writing the assets as they are produced.
+pub fn write_container<W: Write>(
+    writer: &mut W,
+    manifest: Manifest,
+    binaries: Vec<BinaryAsset>,
+    options: &ContainerOptions,
+) -> Result<(), ContainerError> {
+    let _measure = Measure::new("Write DAC container".to_string());
+
+    let binaries = binaries
+        .into_iter()
+        .map(|binary| encrypt(binary, options))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut layout = Layout::new();
+    let mut blobs: Vec<&[u8]> = Vec::new();
+    for (binary, encryption) in &binaries {
+        blobs.extend(layout.place(binary, *encryption, options.chunking.as_ref())?);
+    }
+
+    // Serialize and write control segments
+    let total_len = layout.offset;
+    let segments = layout.control_segments(&manifest, options)?;
+    write_container_from_segments(writer, segments)?;
+
     // Write data segment
-    write_data_segment(writer, offset, binaries)?;
+    write_data_segment(writer, total_len, blobs)?;
     Ok(())
 }
+
+/// Streaming container writer. Writes each asset's payload as soon as it's added,
+/// so only the assets in flight are kept in memory.
+///
+/// The data segment goes first and its length is patched when the writer is finished,
+/// followed by the TOC, manifest and the other control segments.
+///
+/// ```
+/// use dawn_assets::{AssetHeader, AssetType};
+/// use dawn_dac::reader::{read_asset, read_manifest};
+/// use dawn_dac::writer::{BinaryAsset, ContainerOptions, ContainerWriter};
+/// use dawn_dac::{ChecksumAlgorithm, CompressionMode, Manifest, ReadMode};
+/// use std::io::Cursor;
+/// use std::time::SystemTime;
+///
+/// let mut data = Cursor::new(Vec::new());
+/// let mut writer = ContainerWriter::new(&mut data, ContainerOptions::default()).unwrap();
+/// writer
+///     .add(BinaryAsset {
+///         raw: vec![1, 2, 3],
+///         header: AssetHeader {
+///             id: "blob".into(),
+///             asset_type: AssetType::Notes,
+///             ..Default::default()
+///         },
+///         compression: CompressionMode::None,
+///     })
+///     .unwrap();
+///
+/// let manifest = Manifest {
+///     author: None,
+///     description: None,
+///     version: None,
+///     license: None,
+///     tool: "example".to_string(),
+///     tool_version: "0.1.0".to_string(),
+///     created: SystemTime::now(),
+///     read_mode: ReadMode::Flat,
+///     checksum_algorithm: ChecksumAlgorithm::Blake3,
+///     headers: writer.headers().to_vec(),
+///     variants: Default::default(),
+///     guids: Default::default(),
+///     encrypted: vec![],
+/// };
+/// writer.finish(&manifest).unwrap();
+///
+/// let manifest = read_manifest(&mut data).unwrap();
+/// assert_eq!(manifest.headers.len(), 1);
+/// ```
+pub struct ContainerWriter<W: Write + Seek> {
+    writer: W,
+    options: ContainerOptions,
+    layout: Layout,
+    headers: Vec<AssetHeader>,
+    // Position of the data segment length
+    length_position: u64,
+    // Payloads of the reopened container, not kept yet
+    previous: TOC,
+    previous_chunks: HashMap<AssetID, Vec<u32>>,
+}
+
+impl<W: Write + Seek> ContainerWriter<W> {
+    pub fn new(mut writer: W, options: ContainerOptions) -> Result<Self, ContainerError> {
+        writer.write_all(DAC_MAGIC)?;
+        writer.write_all(DATA_MAGIC.to_le_bytes().as_slice())?;
+        let length_position = writer.stream_position()?;
+        // Patched in `finish`
+        writer.write_all(0u32.to_le_bytes().as_slice())?;
+
+        Ok(ContainerWriter {
+            writer,
+            options,
+            layout: Layout::new(),
+            headers: Vec::new(),
+            length_position,
+            previous: TOC(HashMap::new()),
+            previous_chunks: HashMap::new(),
+        })
+    }
+
+    /// Appends the payload to the data segment. The payload is dropped right after.
+    pub fn add(&mut self, binary: BinaryAsset) -> Result<(), ContainerError> {
+        let (binary, encryption) = encrypt(binary, &self.options)?;
+        let chunking = self.options.chunking.as_ref();
+        for blob in self.layout.place(&binary, encryption, chunking)? {
+            self.writer.write_all(blob)?;
+        }
+        self.headers.push(binary.header);
+        Ok(())
+    }
+
+    /// Keeps the payload the reopened container already has for the asset
+    /// (see `ContainerWriter::append`) instead of writing it again.
+    /// Returns `false` if there's no such payload, then the asset must be added.
+    pub fn keep(&mut self, header: &AssetHeader) -> Result<bool, ContainerError> {
+        let id = &header.id;
+        if self.layout.toc.0.contains_key(id) {
+            return Err(ContainerError::DuplicateAsset(id.clone()));
+        }
+        let Some(record) = self.previous.0.remove(id) else {
+            return Ok(false);
+        };
+
+        if let Some(chunks) = self.previous_chunks.remove(id) {
+            self.layout.index.assets.insert(id.clone(), chunks);
+        }
+        self.layout.toc.0.insert(id.clone(), record);
+        self.headers.push(header.clone());
+        Ok(true)
+    }
+
+    /// Headers of the added assets, in the order they were added.
+    pub fn headers(&self) -> &[AssetHeader] {
+        &self.headers
+    }
+
+    /// Patches the data segment length and writes the control segments.
+    /// Returns the underlying writer, positioned at the end of the container.
+    pub fn finish(mut self, manifest: &Manifest) -> Result<W, ContainerError> {
+        let _measure = Measure::new("Finish DAC container".to_string());
+
+        let end = self.writer.stream_position()?;
+        self.writer.seek(SeekFrom::Start(self.length_position))?;
+        self.writer
+            .write_all(self.layout.offset.to_le_bytes().as_slice())?;
+        self.writer.seek(SeekFrom::Start(end))?;
+
+        for segment in self.layout.control_segments(manifest, &self.options)? {
+            write_segment(&mut self.writer, &segment)?;
+        }
+
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Read + Write + Seek> ContainerWriter<W> {
+    /// Reopens the container written by the `ContainerWriter` to update it in place.
+    /// The assets are either kept (see `ContainerWriter::keep`) or added again,
+    /// the ones neither kept nor added are dropped from the container.
+    ///
+    /// New payloads are appended to the data segment and the control segments
+    /// are written anew after it. Payloads of the replaced and the dropped assets
+    /// stay in the data segment as the dead space until the container is rebuilt.
+    /// The updated container may be shorter than the old one, so truncate
+    /// the file to the position of the writer returned by `finish`.
+    pub fn append(mut writer: W, options: ContainerOptions) -> Result<Self, ContainerError> {
+        let segments = read_segments(&mut writer)?;
+        let (data_offset, data_length) = *segments
+            .get(&DATA_MAGIC)
+            .ok_or(ContainerError::SegmentNotFound)?;
+        // The control segments are overwritten, so nothing but them may follow the data
+        if segments.values().any(|(offset, _)| *offset < data_offset) {
+            return Err(ContainerError::NotAppendable);
+        }
+
+        let index = ContainerIndex::read(&mut writer, &ReadLimits::default())?;
+        let mut layout = Layout::new();
+        layout.offset = length_u32(data_length)?;
+        // Chunk IDs of the kept assets must stay valid, so all the chunks are kept.
+        // New payloads are not deduplicated against them
+        let previous_chunks = match index.chunks {
+            Some(chunks) => {
+                layout.index.chunks = chunks.chunks;
+                chunks.assets
+            }
+            None => HashMap::new(),
+        };
+        debug!(
+            "Reopened container with {} assets, {} bytes of data",
+            index.toc.0.len(),
+            data_length
+        );
+
+        writer.seek(SeekFrom::Start((data_offset + data_length) as u64))?;
+        Ok(ContainerWriter {
+            writer,
+            options,
+            layout,
+            headers: Vec::new(),
+            length_position: (data_offset - size_of::<u32>()) as u64,
+            previous: index.toc,
+            previous_chunks,
+        })
+    }
+}
@@ -0,0 +1,464 @@
+//! End-to-end benchmark of the asset pipeline. Generates a fake asset tree,
+//! builds the container from it with the cold, the warm and the partially
+//! invalidated cache, then reads every asset back. The timings, the cache
+//! hits and the sizes are written to the JSON report:
+//!
+//! ```sh
+//! cargo bench -p dawn-dacgen --features bench --bench pipeline
+//! ```
+//!
+//! Configured with the environment variables:
+//! - `DAWN_BENCH_TEXTURES`, `DAWN_BENCH_TEXTURE_SIZE`: number and side of the RGBA textures.
+//! - `DAWN_BENCH_MESHES`, `DAWN_BENCH_MESH_GRID`: number and side (in vertices) of the grid meshes.
+//! - `DAWN_BENCH_AUDIO`, `DAWN_BENCH_AUDIO_SECONDS`: number and duration of the stereo clips.
+//!   Every second clip is packed into the chunks.
+//! - `DAWN_BENCH_INVALIDATE`: percent of the assets changed before the incremental build.
+//! - `DAWN_BENCH_REPORT`: path of the report, `target/tmp/pipeline-report.json` by default.
+//! - `DAWN_BENCH_BASELINE`: report of the previous run. The bench fails if any timing
+//!   grew by more than `DAWN_BENCH_TOLERANCE` percent (20 by default).
+
+use dawn_dac::reader::{read_asset, read_manifest};
+use dawn_dac::{ChecksumAlgorithm, CompressionLevel, ReadMode};
+use dawn_dacgen::config::WriteConfig;
+use dawn_dacgen::write_from_directory;
+use serde_json::{json, Value};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+// Deterministic noise, so the runs are comparable
+struct Lcg(u32);
+
+impl Lcg {
+    fn next(&mut self) -> u32 {
+        self.0 = self.0.wrapping_mul(1664525).wrapping_add(1013904223);
+        self.0 >> 8
+    }
+}
+
+fn env_or(name: &str, default: usize) -> usize {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+struct Config {
+    textures: usize,
+    texture_size: u32,
+    meshes: usize,
+    mesh_grid: usize,
+    audio: usize,
+    audio_seconds: usize,
+    invalidate: usize,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        Config {
+            textures: env_or("DAWN_BENCH_TEXTURES", 32),
+            texture_size: env_or("DAWN_BENCH_TEXTURE_SIZE", 256) as u32,
+            meshes: env_or("DAWN_BENCH_MESHES", 16),
+            mesh_grid: env_or("DAWN_BENCH_MESH_GRID", 64).max(2),
+            audio: env_or("DAWN_BENCH_AUDIO", 8),
+            audio_seconds: env_or("DAWN_BENCH_AUDIO_SECONDS", 2),
+            invalidate: env_or("DAWN_BENCH_INVALIDATE", 10).min(100),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "textures": self.textures,
+            "texture_size": self.texture_size,
+            "meshes": self.meshes,
+            "mesh_grid": self.mesh_grid,
+            "audio": self.audio,
+            "audio_seconds": self.audio_seconds,
+            "invalidate": self.invalidate,
+        })
+    }
+}
+
+// Counts the cache hits and misses reported by dacgen
+struct CacheCounter {
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+static CACHE: CacheCounter = CacheCounter {
+    hits: AtomicUsize::new(0),
+    misses: AtomicUsize::new(0),
+};
+
+impl log::Log for CacheCounter {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.target() == "dawn_dacgen::cache" || metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record) {
+        if record.target() == "dawn_dacgen::cache" {
+            let message = record.args().to_string();
+            if message.starts_with("Cache hit") {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+            } else if message.starts_with("Cache miss") {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+            }
+        } else if record.level() <= log::Level::Warn {
+            eprintln!("{}: {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn write_toml(path: &Path, content: &str) {
+    std::fs::write(path, content).unwrap();
+}
+
+// Gradient with some noise: compresses like the real photos, not like the flat colors
+fn generate_texture(dir: &Path, index: usize, size: u32) {
+    let mut rng = Lcg(index as u32 + 1);
+    let image = image::RgbaImage::from_fn(size, size, |x, y| {
+        let noise = (rng.next() % 16) as u8;
+        image::Rgba([
+            (x * 255 / size) as u8 ^ noise,
+            (y * 255 / size) as u8 ^ noise,
+            ((x + y) * 127 / size) as u8,
+            255,
+        ])
+    });
+    let png = dir.join(format!("texture_{index}.png"));
+    image.save(&png).unwrap();
+    write_toml(
+        &dir.join(format!("texture_{index}.toml")),
+        &format!(
+            "[header]\nasset_type = \"Texture\"\n\n\
+             [properties.Texture]\nsources = [{{ File = {:?} }}]\npixel_format = \"R8G8B8A8\"\n",
+            png
+        ),
+    );
+}
+
+// Noisy height map grid with the external buffer
+fn generate_mesh(dir: &Path, index: usize, grid: usize) {
+    let mut rng = Lcg(index as u32 + 1000);
+    let mut buffer = Vec::new();
+    let (mut min, mut max) = ([f32::MAX; 3], [f32::MIN; 3]);
+    for z in 0..grid {
+        for x in 0..grid {
+            let position = [x as f32, (rng.next() % 256) as f32 / 256.0, z as f32];
+            for i in 0..3 {
+                min[i] = min[i].min(position[i]);
+                max[i] = max[i].max(position[i]);
+            }
+            position.iter().for_each(|v| buffer.extend(v.to_le_bytes()));
+        }
+    }
+    for _ in 0..grid * grid {
+        [0.0f32, 1.0, 0.0]
+            .iter()
+            .for_each(|v| buffer.extend(v.to_le_bytes()));
+    }
+    for z in 0..grid {
+        for x in 0..grid {
+            let uv = [x as f32 / grid as f32, z as f32 / grid as f32];
+            uv.iter().for_each(|v| buffer.extend(v.to_le_bytes()));
+        }
+    }
+    let mut indices = 0;
+    for z in 0..grid - 1 {
+        for x in 0..grid - 1 {
+            let i = (z * grid + x) as u32;
+            let quad = [
+                i,
+                i + grid as u32,
+                i + 1,
+                i + 1,
+                i + grid as u32,
+                i + grid as u32 + 1,
+            ];
+            quad.iter().for_each(|v| buffer.extend(v.to_le_bytes()));
+            indices += quad.len();
+        }
+    }
+
+    let vertices = grid * grid;
+    let bin = format!("mesh_{index}.bin");
+    std::fs::write(dir.join(&bin), &buffer).unwrap();
+    let gltf = json!({
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "mesh": 0 }],
+        "meshes": [{ "primitives": [{
+            "attributes": { "POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2 },
+            "indices": 3,
+        }]}],
+        "buffers": [{ "uri": bin, "byteLength": buffer.len() }],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": vertices * 12 },
+            { "buffer": 0, "byteOffset": vertices * 12, "byteLength": vertices * 12 },
+            { "buffer": 0, "byteOffset": vertices * 24, "byteLength": vertices * 8 },
+            { "buffer": 0, "byteOffset": vertices * 32, "byteLength": indices * 4 },
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": vertices, "type": "VEC3",
+              "min": min, "max": max },
+            { "bufferView": 1, "componentType": 5126, "count": vertices, "type": "VEC3" },
+            { "bufferView": 2, "componentType": 5126, "count": vertices, "type": "VEC2" },
+            { "bufferView": 3, "componentType": 5125, "count": indices, "type": "SCALAR" },
+        ],
+    });
+    let path = dir.join(format!("mesh_{index}.gltf"));
+    std::fs::write(&path, gltf.to_string()).unwrap();
+    write_toml(
+        &dir.join(format!("mesh_{index}.toml")),
+        &format!(
+            "[header]\nasset_type = \"Mesh\"\n\n\
+             [properties.Mesh]\nsource = {{ File = {:?} }}\ngen_material = false\n",
+            path
+        ),
+    );
+}
+
+// Stereo 16-bit WAV with the detuned sines
+fn generate_audio(dir: &Path, index: usize, seconds: usize) {
+    const SAMPLE_RATE: usize = 44100;
+    let frames = SAMPLE_RATE * seconds;
+    let mut data = Vec::with_capacity(frames * 4);
+    for i in 0..frames {
+        let t = i as f32 / SAMPLE_RATE as f32;
+        for frequency in [220.0 + index as f32, 330.0 + index as f32] {
+            let sample = (t * frequency * std::f32::consts::TAU).sin() * 0.5;
+            data.extend(((sample * i16::MAX as f32) as i16).to_le_bytes());
+        }
+    }
+
+    let mut wav = Vec::with_capacity(44 + data.len());
+    wav.extend(b"RIFF");
+    wav.extend((36 + data.len() as u32).to_le_bytes());
+    wav.extend(b"WAVEfmt ");
+    wav.extend(16u32.to_le_bytes());
+    wav.extend(1u16.to_le_bytes()); // PCM
+    wav.extend(2u16.to_le_bytes());
+    wav.extend((SAMPLE_RATE as u32).to_le_bytes());
+    wav.extend((SAMPLE_RATE as u32 * 4).to_le_bytes());
+    wav.extend(4u16.to_le_bytes());
+    wav.extend(16u16.to_le_bytes());
+    wav.extend(b"data");
+    wav.extend((data.len() as u32).to_le_bytes());
+    wav.extend(data);
+
+    let path = dir.join(format!("audio_{index}.wav"));
+    std::fs::write(&path, wav).unwrap();
+    let chunks = if index % 2 == 1 {
+        "chunk_duration = 500\n"
+    } else {
+        ""
+    };
+    write_toml(
+        &dir.join(format!("audio_{index}.toml")),
+        &format!(
+            "[header]\nasset_type = \"Audio\"\n\n\
+             [properties.Audio]\nsample_rate = {SAMPLE_RATE}\nchannels = 2\n\
+             source = {{ File = {:?} }}\n{chunks}",
+            path
+        ),
+    );
+}
+
+// Changes the metadata of every n-th asset, so its cache entry is invalidated
+fn invalidate(dir: &Path, percent: usize) -> usize {
+    let mut tomls = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect::<Vec<_>>();
+    tomls.sort();
+
+    let count = tomls.len() * percent / 100;
+    for path in tomls.iter().take(count) {
+        let content = std::fs::read_to_string(path).unwrap();
+        let content = content.replace("[header]\n", "[header]\ntags = [\"invalidated\"]\n");
+        std::fs::write(path, content).unwrap();
+    }
+    count
+}
+
+struct Build {
+    time: Duration,
+    hits: usize,
+    misses: usize,
+    size: u64,
+}
+
+fn build(sources: &Path, cache_dir: &Path, output: &Path) -> Build {
+    CACHE.hits.store(0, Ordering::Relaxed);
+    CACHE.misses.store(0, Ordering::Relaxed);
+
+    let start = Instant::now();
+    let mut writer = BufWriter::new(File::create(output).unwrap());
+    write_from_directory(
+        &mut writer,
+        sources.to_path_buf(),
+        WriteConfig {
+            read_mode: ReadMode::Flat,
+            checksum_algorithm: ChecksumAlgorithm::Blake3,
+            compression_level: CompressionLevel::Default,
+            cache_dir: cache_dir.to_path_buf(),
+            author: None,
+            description: Some("Pipeline benchmark".to_string()),
+            version: None,
+            license: None,
+            converters: Default::default(),
+            chunking: None,
+            signing_key: None,
+            encryption: None,
+            downscale: Default::default(),
+            conventions: Default::default(),
+            threads: None,
+            compression_threads: None,
+            compression_block_size: None,
+            guid_lock: None,
+            bundles: vec![],
+        },
+    )
+    .unwrap();
+    writer.flush().unwrap();
+    drop(writer);
+
+    Build {
+        time: start.elapsed(),
+        hits: CACHE.hits.load(Ordering::Relaxed),
+        misses: CACHE.misses.load(Ordering::Relaxed),
+        size: std::fs::metadata(output).unwrap().len(),
+    }
+}
+
+impl Build {
+    fn to_json(&self) -> Value {
+        let total = self.hits + self.misses;
+        json!({
+            "seconds": self.time.as_secs_f64(),
+            "cache_hits": self.hits,
+            "cache_misses": self.misses,
+            "cache_hit_rate": if total == 0 { 0.0 } else { self.hits as f64 / total as f64 },
+            "container_bytes": self.size,
+        })
+    }
+}
+
+// Reads and decodes every asset, like the hub does on the full load
+fn load(output: &Path) -> (Duration, usize) {
+    let start = Instant::now();
+    let mut reader = BufReader::new(File::open(output).unwrap());
+    let manifest = read_manifest(&mut reader).unwrap();
+    for header in &manifest.headers {
+        read_asset(&mut reader, header.id.clone()).unwrap();
+    }
+    (start.elapsed(), manifest.headers.len())
+}
+
+// Timings of the report compared with the baseline
+const TIMINGS: [&str; 4] = [
+    "/cold_build/seconds",
+    "/warm_build/seconds",
+    "/incremental_build/seconds",
+    "/load/seconds",
+];
+
+fn compare(report: &Value, baseline: &Path, tolerance: f64) -> bool {
+    let baseline: Value = serde_json::from_reader(File::open(baseline).unwrap()).unwrap();
+    if baseline.get("config") != report.get("config") {
+        eprintln!("Baseline was made with the different config, not comparing");
+        return true;
+    }
+
+    let mut passed = true;
+    for pointer in TIMINGS {
+        let (Some(old), Some(new)) = (
+            baseline.pointer(pointer).and_then(Value::as_f64),
+            report.pointer(pointer).and_then(Value::as_f64),
+        ) else {
+            continue;
+        };
+        let change = (new / old - 1.0) * 100.0;
+        println!("{pointer}: {old:.3}s -> {new:.3}s ({change:+.1}%)");
+        if change > tolerance {
+            eprintln!("{pointer} regressed by {change:.1}%");
+            passed = false;
+        }
+    }
+    passed
+}
+
+fn main() -> ExitCode {
+    log::set_logger(&CACHE).unwrap();
+    log::set_max_level(log::LevelFilter::Debug);
+
+    let config = Config::from_env();
+    let root = std::env::temp_dir().join(format!("dawn-pipeline-bench-{}", std::process::id()));
+    let sources = root.join("sources");
+    let cache_dir = root.join("cache");
+    std::fs::create_dir_all(&sources).unwrap();
+    std::fs::create_dir_all(&cache_dir).unwrap();
+    let output = root.join("assets.dac");
+
+    let start = Instant::now();
+    for i in 0..config.textures {
+        generate_texture(&sources, i, config.texture_size);
+    }
+    for i in 0..config.meshes {
+        generate_mesh(&sources, i, config.mesh_grid);
+    }
+    for i in 0..config.audio {
+        generate_audio(&sources, i, config.audio_seconds);
+    }
+    let sources_bytes = std::fs::read_dir(&sources)
+        .unwrap()
+        .map(|entry| entry.unwrap().metadata().unwrap().len())
+        .sum::<u64>();
+    let generate = start.elapsed();
+
+    let cold = build(&sources, &cache_dir, &output);
+    let warm = build(&sources, &cache_dir, &output);
+    let invalidated = invalidate(&sources, config.invalidate);
+    let incremental = build(&sources, &cache_dir, &output);
+    let (load_time, assets) = load(&output);
+    let mut incremental = incremental.to_json();
+    incremental["invalidated"] = json!(invalidated);
+
+    let report = json!({
+        "config": config.to_json(),
+        "generate": {
+            "seconds": generate.as_secs_f64(),
+            "source_bytes": sources_bytes,
+        },
+        "cold_build": cold.to_json(),
+        "warm_build": warm.to_json(),
+        "incremental_build": incremental,
+        "load": {
+            "seconds": load_time.as_secs_f64(),
+            "assets": assets,
+        },
+    });
+    std::fs::remove_dir_all(&root).unwrap();
+
+    let path = std::env::var_os("DAWN_BENCH_REPORT")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(env!("CARGO_TARGET_TMPDIR")).join("pipeline-report.json"));
+    std::fs::write(&path, serde_json::to_string_pretty(&report).unwrap()).unwrap();
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    println!("Report written to {}", path.display());
+
+    if let Some(baseline) = std::env::var_os("DAWN_BENCH_BASELINE") {
+        let tolerance = env_or("DAWN_BENCH_TOLERANCE", 20) as f64;
+        if !compare(&report, Path::new(&baseline), tolerance) {
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
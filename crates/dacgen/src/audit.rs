@@ -0,0 +1,252 @@
+use crate::WriterError;
+use dawn_assets::guid::AssetGuid;
+use dawn_assets::AssetID;
+use dawn_dac::reader::read_manifest;
+use dawn_dac::Manifest;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+/// Where and how the references to the assets are looked for.
+#[derive(Debug, Clone)]
+pub struct AuditConfig {
+    /// Files or directories (scanned recursively) of the code, the scenes
+    /// and the scripts referring to the assets.
+    pub roots: Vec<PathBuf>,
+    /// Extensions of the scanned files, e.g. `rs` or `ron`. Empty scans all the files.
+    pub extensions: Vec<String>,
+    /// Text right before the string literal that makes it the asset reference,
+    /// e.g. `AssetID::from(`. Such literals, and the GUID references
+    /// (see `AssetGuid::to_id`), are reported as missing if the
+    /// container has no such asset. Any other literal equal to the ID of
+    /// the asset counts as its reference as well.
+    pub markers: Vec<String>,
+    /// Assets never reported as orphaned, e.g. the ones loaded by type or all at once.
+    pub keep: Vec<AssetID>,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        AuditConfig {
+            roots: Vec::new(),
+            extensions: vec!["rs".to_string()],
+            markers: vec!["AssetID::from(".to_string(), "AssetID::new(".to_string()],
+            keep: Vec::new(),
+        }
+    }
+}
+
+/// Asset ID found in the scanned file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetReference {
+    pub id: AssetID,
+    pub file: PathBuf,
+    /// Line number, starting from 1
+    pub line: usize,
+    /// The literal follows one of the `AuditConfig::markers`
+    pub marked: bool,
+}
+
+/// Result of cross-checking the references against the container manifest.
+#[derive(Debug, Clone, Default)]
+pub struct AuditReport {
+    /// Marked references to the assets the container does not have.
+    pub missing: Vec<AssetReference>,
+    /// Assets neither referenced nor required by the referenced ones,
+    /// sorted by the ID. Candidates for pruning.
+    pub orphaned: Vec<AssetID>,
+    /// Assets referenced directly or through the dependencies and the variants.
+    pub used: usize,
+    pub scanned_files: usize,
+}
+
+impl AuditReport {
+    pub fn is_clean(&self) -> bool {
+        self.missing.is_empty() && self.orphaned.is_empty()
+    }
+}
+
+impl Display for AuditReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Scanned {} files, {} assets used, {} missing, {} orphaned",
+            self.scanned_files,
+            self.used,
+            self.missing.len(),
+            self.orphaned.len()
+        )?;
+        for reference in &self.missing {
+            writeln!(
+                f,
+                "Missing: {} at {}:{}",
+                reference.id.as_str(),
+                reference.file.display(),
+                reference.line
+            )?;
+        }
+        for id in &self.orphaned {
+            writeln!(f, "Orphaned: {}", id.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+// String literals of the line with the text before each of them
+fn literals(line: &str) -> Vec<(&str, &str)> {
+    let mut result = Vec::new();
+    let mut start = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match (start, c) {
+            (Some(_), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(open), '"') if !escaped => {
+                result.push((&line[..open], &line[open + 1..i]));
+                start = None;
+            }
+            (None, '"') => start = Some(i),
+            _ => {}
+        }
+        escaped = false;
+    }
+    result
+}
+
+fn scan_file(
+    path: &Path,
+    config: &AuditConfig,
+    references: &mut Vec<AssetReference>,
+) -> Result<(), WriterError> {
+    // Binary files are not the references sources
+    let Ok(text) = std::fs::read_to_string(path) else {
+        return Ok(());
+    };
+    for (number, line) in text.lines().enumerate() {
+        for (before, literal) in literals(line) {
+            if literal.is_empty() {
+                continue;
+            }
+            let before = before.trim_end();
+            references.push(AssetReference {
+                id: AssetID::from(literal),
+                file: path.to_path_buf(),
+                line: number + 1,
+                marked: config
+                    .markers
+                    .iter()
+                    .any(|marker| before.ends_with(marker.trim_end())),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Collects the string literals of the files under the roots.
+/// Most of them are not the asset IDs, `audit` tells them apart by the manifest.
+pub fn scan_references(config: &AuditConfig) -> Result<(Vec<AssetReference>, usize), WriterError> {
+    let mut references = Vec::new();
+    let mut files = 0;
+    for root in &config.roots {
+        for entry in walkdir::WalkDir::new(root) {
+            let entry = entry.map_err(std::io::Error::from)?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let matches = config.extensions.is_empty()
+                || entry.path().extension().is_some_and(|extension| {
+                    config
+                        .extensions
+                        .iter()
+                        .any(|allowed| extension.eq_ignore_ascii_case(allowed.as_str()))
+                });
+            if matches {
+                files += 1;
+                scan_file(entry.path(), config, &mut references)?;
+            }
+        }
+    }
+    Ok((references, files))
+}
+
+/// Cross-checks the references against the manifest. The referenced assets keep
+/// their dependencies used, and the referenced logical assets keep all their variants.
+pub fn audit(manifest: &Manifest, references: &[AssetReference], keep: &[AssetID]) -> AuditReport {
+    let headers: HashMap<&AssetID, _> = manifest
+        .headers
+        .iter()
+        .map(|header| (&header.id, header))
+        .collect();
+    let variants = |id: &AssetID| -> Vec<AssetID> {
+        let mut result = Vec::new();
+        if let Some(locales) = manifest.variants.locales.get(id) {
+            result.push(locales.default.clone());
+            result.extend(locales.locales.values().cloned());
+        }
+        if let Some(quality) = manifest.variants.quality.get(id) {
+            result.extend(quality.iter().map(|variant| variant.id.clone()));
+        }
+        result
+    };
+    let known = |id: &AssetID| {
+        headers.contains_key(id)
+            || manifest.variants.locales.contains_key(id)
+            || manifest.variants.quality.contains_key(id)
+    };
+
+    let mut report = AuditReport::default();
+    let mut stack = Vec::new();
+    for reference in references {
+        // The references by the GUID count as the references to the current ID
+        let id = manifest.guids.resolve(&reference.id);
+        if known(&id) {
+            stack.push(id);
+        } else if (reference.marked || AssetGuid::from_id(&reference.id).is_some())
+            && !report
+                .missing
+                .iter()
+                .any(|missing| missing.id == reference.id)
+        {
+            report.missing.push(reference.clone());
+        }
+    }
+    stack.extend(keep.iter().cloned());
+
+    let mut used = HashSet::new();
+    while let Some(id) = stack.pop() {
+        if !used.insert(id.clone()) {
+            continue;
+        }
+        if let Some(header) = headers.get(&id) {
+            stack.extend(header.dependencies.iter().cloned());
+        }
+        stack.extend(variants(&id));
+    }
+
+    report.used = used.iter().filter(|id| headers.contains_key(id)).count();
+    report.orphaned = manifest
+        .headers
+        .iter()
+        .filter(|header| !used.contains(&header.id))
+        .map(|header| header.id.clone())
+        .collect();
+    report.orphaned.sort();
+    report
+}
+
+/// Dry run of the pruning: scans the roots for the references and
+/// reports the broken ones and the never used assets of the container,
+/// without loading or changing anything.
+pub fn audit_container<R: Read + Seek>(
+    reader: &mut R,
+    config: &AuditConfig,
+) -> Result<AuditReport, WriterError> {
+    let manifest = read_manifest(reader)?;
+    let (references, scanned_files) = scan_references(config)?;
+    let mut report = audit(&manifest, &references, &config.keep);
+    report.scanned_files = scanned_files;
+    Ok(report)
+}
@@ -0,0 +1,198 @@
+use crate::config::BundleRule;
+use crate::report::BuildReport;
+use crate::{UserAssetFile, WriterError};
+use dawn_assets::guid::AssetGuids;
+use dawn_assets::variants::AssetVariants;
+use dawn_assets::{AssetHeader, AssetID};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+/// Bundle of the assets that don't go anywhere else.
+pub const BASE_BUNDLE: &str = "base";
+
+/// Bundles the user assets are split into. The first one is the base bundle.
+///
+/// The bundles are named by the paths, e.g. `levels/level1`: the assets of the
+/// bundle may depend on the ones of the same bundle, of the base one,
+/// and of the bundles it's nested into (`levels`).
+pub(crate) struct Bundles {
+    pub(crate) names: Vec<String>,
+    // Index of the bundle of each user asset
+    of: Vec<usize>,
+}
+
+fn normalize(name: &str) -> String {
+    let name = name.trim_matches('/');
+    if name.is_empty() {
+        BASE_BUNDLE.to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+impl Bundles {
+    /// Everything goes into one container.
+    pub(crate) fn single(user_assets: &[UserAssetFile]) -> Self {
+        Bundles {
+            names: vec![BASE_BUNDLE.to_string()],
+            of: vec![0; user_assets.len()],
+        }
+    }
+
+    /// Splits by the `bundle` in the header of the user asset,
+    /// or by the first rule matching its tags.
+    pub(crate) fn split(user_assets: &[UserAssetFile], rules: &[BundleRule]) -> Self {
+        let assigned = user_assets
+            .iter()
+            .map(|user_asset| {
+                let header = &user_asset.asset.header;
+                let by_tag = || {
+                    rules
+                        .iter()
+                        .find(|rule| header.tags.contains(&rule.tag))
+                        .map(|rule| rule.bundle.as_str())
+                };
+                header
+                    .bundle
+                    .as_deref()
+                    .or_else(by_tag)
+                    .map_or(BASE_BUNDLE.to_string(), normalize)
+            })
+            .collect::<Vec<_>>();
+
+        let mut names = vec![BASE_BUNDLE.to_string()];
+        names.extend(
+            assigned
+                .iter()
+                .filter(|name| *name != BASE_BUNDLE)
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .cloned(),
+        );
+        let of = assigned
+            .iter()
+            .map(|name| names.iter().position(|n| n == name).unwrap())
+            .collect();
+        Bundles { names, of }
+    }
+
+    /// Bundle of the user asset.
+    pub(crate) fn of(&self, user_asset: usize) -> usize {
+        self.of[user_asset]
+    }
+
+    /// File the bundle is written to, named after the last part of its path.
+    pub(crate) fn file_name(&self, bundle: usize) -> String {
+        let name = &self.names[bundle];
+        format!("{}.dac", name.rsplit('/').next().unwrap_or(name))
+    }
+
+    // Assets of the `from` bundle can depend on the ones of the `to` bundle
+    fn visible(&self, from: usize, to: usize) -> bool {
+        let (from, to) = (&self.names[from], &self.names[to]);
+        to == BASE_BUNDLE
+            || from == to
+            || from
+                .strip_prefix(to.as_str())
+                .is_some_and(|rest| rest.starts_with('/'))
+    }
+
+    /// Assigns the written assets and the logical ones to the bundles,
+    /// checking nothing depends on the assets it's not installed with.
+    /// All the variants of the logical asset must be in one bundle.
+    pub(crate) fn assign(
+        &self,
+        headers: &[Vec<AssetHeader>],
+        variants: &AssetVariants,
+        sources: &HashMap<AssetID, PathBuf>,
+    ) -> Result<HashMap<AssetID, usize>, WriterError> {
+        let mut report = BuildReport::default();
+        let mut fail = |id: &AssetID, error: WriterError| {
+            let path = sources.get(id).map_or(Path::new(""), PathBuf::as_path);
+            report.add(path, id.clone(), error);
+        };
+
+        let mut assigned = HashMap::new();
+        for (bundle, headers) in headers.iter().enumerate() {
+            for header in headers {
+                assigned.insert(header.id.clone(), bundle);
+            }
+        }
+
+        let locales = variants.locales.iter().map(|(of, locales)| {
+            let ids = std::iter::once(&locales.default).chain(locales.locales.values());
+            (of, ids.cloned().collect::<Vec<_>>())
+        });
+        let quality = (variants.quality.iter())
+            .map(|(of, variants)| (of, variants.iter().map(|v| v.id.clone()).collect()));
+        for (of, ids) in locales.chain(quality) {
+            let bundles = ids
+                .iter()
+                .filter_map(|id| assigned.get(id).copied())
+                .collect::<BTreeSet<_>>();
+            match bundles.first() {
+                Some(bundle) if bundles.len() == 1 => {
+                    assigned.insert(of.clone(), *bundle);
+                }
+                Some(_) => fail(of, WriterError::SplitVariants(of.clone())),
+                None => {}
+            }
+        }
+
+        for (bundle, headers) in headers.iter().enumerate() {
+            for header in headers {
+                for dep in &header.dependencies {
+                    match assigned.get(dep) {
+                        Some(to) if !self.visible(bundle, *to) => fail(
+                            &header.id,
+                            WriterError::CrossBundleDependency(
+                                header.id.clone(),
+                                dep.clone(),
+                                self.names[*to].clone(),
+                            ),
+                        ),
+                        // The missing ones are reported by the sanity check
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        report.into_result()?;
+        Ok(assigned)
+    }
+}
+
+/// Variants of the logical assets assigned to the bundle.
+pub(crate) fn bundle_variants(
+    variants: &AssetVariants,
+    assigned: &HashMap<AssetID, usize>,
+    bundle: usize,
+) -> AssetVariants {
+    let in_bundle = |of: &AssetID| assigned.get(of) == Some(&bundle);
+    AssetVariants {
+        locales: (variants.locales.iter())
+            .filter(|(of, _)| in_bundle(of))
+            .map(|(of, locales)| (of.clone(), locales.clone()))
+            .collect(),
+        quality: (variants.quality.iter())
+            .filter(|(of, _)| in_bundle(of))
+            .map(|(of, variants)| (of.clone(), variants.clone()))
+            .collect(),
+    }
+}
+
+/// GUIDs of the assets assigned to the bundle.
+pub(crate) fn bundle_guids(
+    guids: &AssetGuids,
+    assigned: &HashMap<AssetID, usize>,
+    bundle: usize,
+) -> AssetGuids {
+    let mut result = AssetGuids::default();
+    for (guid, id) in guids.iter() {
+        if assigned.get(id) == Some(&bundle) {
+            result.insert(guid, id.clone());
+        }
+    }
+    result
+}
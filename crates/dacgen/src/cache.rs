@@ -1,18 +1,24 @@
 use crate::deep_hash::DeepHasher;
-use crate::{UserAssetFile, WriteConfig, WriterError};
-use dawn_assets::AssetChecksum;
+use crate::ir::normalize_name;
+use crate::{UserAssetFile, UserAssetIndex, WriteConfig, WriterError};
+use dawn_assets::{AssetChecksum, AssetID};
 use dawn_dac::serialize_backend::deserialize;
 use dawn_dac::writer::BinaryAsset;
 use dawn_dac::ChecksumAlgorithm;
 use dawn_util::profile::Measure;
 use log::debug;
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Mutex;
 
 pub struct Cache {
     cache_dir: PathBuf,
     cwd: PathBuf,
     write_config: WriteConfig,
     checksum_algorithm: ChecksumAlgorithm,
+    // Hashes of the assets read by the bakes, including their own inputs.
+    // The same meshes are usually read by several bakes
+    inputs: Mutex<HashMap<AssetID, AssetChecksum>>,
 }
 
 impl Cache {
@@ -27,10 +33,66 @@ impl Cache {
             cwd,
             write_config,
             checksum_algorithm,
+            inputs: Mutex::new(HashMap::new()),
         }
     }
 
-    fn get_fn(&self, asset: &UserAssetFile) -> Result<PathBuf, WriterError> {
+    // Hash of the input asset and all the assets it reads in turn
+    fn input_hash(
+        &self,
+        id: &AssetID,
+        index: &UserAssetIndex,
+        stack: &mut Vec<AssetID>,
+    ) -> Result<AssetChecksum, WriterError> {
+        if let Some(hash) = self.inputs.lock().unwrap().get(id) {
+            return Ok(*hash);
+        }
+
+        let reader = stack.last().cloned().unwrap_or_else(|| id.clone());
+        if stack.contains(id) {
+            return Err(WriterError::CircleDependency(reader, id.clone()));
+        }
+        let asset = index
+            .get(id)
+            .ok_or_else(|| WriterError::InputMissing(reader, id.clone()))?;
+
+        let mut hasher = DeepHasher::new(self.checksum_algorithm);
+        hasher
+            .update_object(*asset, self.cache_dir.clone(), self.cwd.clone())
+            .map_err(WriterError::HashError)?;
+        stack.push(id.clone());
+        self.update_inputs(&mut hasher, asset, index, stack)?;
+        stack.pop();
+
+        let hash = hasher.finalize();
+        self.inputs.lock().unwrap().insert(id.clone(), hash);
+        Ok(hash)
+    }
+
+    fn update_inputs(
+        &self,
+        hasher: &mut DeepHasher,
+        asset: &UserAssetFile,
+        index: &UserAssetIndex,
+        stack: &mut Vec<AssetID>,
+    ) -> Result<(), WriterError> {
+        for input in asset.inputs(&self.write_config.converters) {
+            let hash = self.input_hash(&input, index, stack)?;
+            hasher
+                .update_object(&input, self.cache_dir.clone(), self.cwd.clone())
+                .map_err(WriterError::HashError)?;
+            hasher
+                .update_object(&hash.hex_string(), self.cache_dir.clone(), self.cwd.clone())
+                .map_err(WriterError::HashError)?;
+        }
+        Ok(())
+    }
+
+    fn get_fn(
+        &self,
+        asset: &UserAssetFile,
+        index: &UserAssetIndex,
+    ) -> Result<PathBuf, WriterError> {
         let _measure = Measure::new(format!("Calculated deep hash of {}", asset.path.display()));
 
         let mut hasher = DeepHasher::new(self.checksum_algorithm);
@@ -40,6 +102,9 @@ impl Cache {
         hasher
             .update_object(asset, self.cache_dir.clone(), self.cwd.clone())
             .map_err(WriterError::HashError)?;
+        // The bakes are stale once any asset they read has changed
+        let mut stack = vec![normalize_name(asset.path.clone())];
+        self.update_inputs(&mut hasher, asset, index, &mut stack)?;
 
         let hash = hasher.finalize().hex_string();
 
@@ -50,10 +115,10 @@ impl Cache {
         Ok(self.cache_dir.join(filename))
     }
 
-    pub fn get(&self, asset: &UserAssetFile) -> Option<Vec<BinaryAsset>> {
+    pub fn get(&self, asset: &UserAssetFile, index: &UserAssetIndex) -> Option<Vec<BinaryAsset>> {
         let _measure = Measure::new(format!("Cache get {:?} computed", asset.path));
 
-        let cache_path = self.get_fn(asset).ok()?;
+        let cache_path = self.get_fn(asset, index).ok()?;
         if cache_path.exists() {
             debug!("Cache hit for asset {:?} at {:?}", asset.path, cache_path);
             // Read the cached binaries
@@ -76,11 +141,12 @@ impl Cache {
     pub fn insert(
         &self,
         asset: &UserAssetFile,
+        index: &UserAssetIndex,
         binaries: &Vec<BinaryAsset>,
     ) -> Result<(), WriterError> {
         let _measure = Measure::new(format!("Cache insert {:?} computed", asset.path));
 
-        let cache_path = self.get_fn(asset)?;
+        let cache_path = self.get_fn(asset, index)?;
 
         // Ensure the cache directory exists
         if let Some(parent) = cache_path.parent() {
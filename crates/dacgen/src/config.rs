@@ -1,18 +1,92 @@
-use crate::deep_hash::{DeepHash, DeepHashCtx};
+use crate::deep_hash::{with_std, DeepHash, DeepHashCtx};
+use crate::plugin::ConverterRegistry;
+use dawn_assets::units::Conventions;
+use dawn_assets::variants::QualityTier;
+use dawn_dac::chunking::ChunkingParams;
+use dawn_dac::encryption::EncryptionOptions;
+use dawn_dac::signing::SigningKey;
 use dawn_dac::{ChecksumAlgorithm, CompressionLevel, ReadMode};
 use std::hash::{Hash, Hasher};
 use std::path::PathBuf;
 
+/// Rule generating a lower quality variant of the asset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DownscaleRule {
+    pub tier: QualityTier,
+    /// Scale of the dimensions relative to the source.
+    pub scale: f32,
+}
+
+/// Per-type rules generating the quality variants from one source.
+/// The source itself becomes the `High` tier variant.
+/// Assets declaring the quality variant in the TOML manually are left intact.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DownscaleRules {
+    pub textures: Vec<DownscaleRule>,
+}
+
+/// Packs the assets with the tag into the bundle, unless they
+/// name the bundle in their TOML (see `write_bundles`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleRule {
+    pub tag: String,
+    pub bundle: String,
+}
+
+/// Per-format conventions of the imported meshes, for the exporters
+/// writing them wrong or not at all. `None` follows the format: the glTF
+/// specification, or the units and the axes declared in the FBX file.
+/// Overridden by the `conventions` of the mesh asset in its TOML.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ImportConventions {
+    pub gltf: Option<Conventions>,
+    pub fbx: Option<Conventions>,
+}
+
 #[derive(Debug, Clone)]
 pub struct WriteConfig {
     pub read_mode: ReadMode,
     pub checksum_algorithm: ChecksumAlgorithm,
     pub compression_level: CompressionLevel,
+    /// Directory of the converted assets and the downloaded sources,
+    /// usually resolved with `dawn_paths::Paths::cache_dir`.
     pub cache_dir: PathBuf,
     pub author: Option<String>,
     pub description: Option<String>,
     pub version: Option<String>,
     pub license: Option<String>,
+    /// Converters for the custom asset types (see `plugin` module).
+    pub converters: ConverterRegistry,
+    /// Enables content-defined chunking of the asset payloads,
+    /// so the data shared between assets is stored only once.
+    pub chunking: Option<ChunkingParams>,
+    /// Ed25519 key to sign the container with.
+    /// Load it with `SigningKey::from_pkcs8`.
+    pub signing_key: Option<SigningKey>,
+    /// Encrypts the payloads of the selected assets, e.g. the licensed content.
+    /// The application reads them with the same key (see `reader::open_with_key`).
+    pub encryption: Option<EncryptionOptions>,
+    /// Rules generating the quality variants of the assets.
+    pub downscale: DownscaleRules,
+    /// Conventions the meshes are converted from into the engine ones.
+    pub conventions: ImportConventions,
+    /// Number of the threads converting the assets.
+    /// Defaults to the number of the logical cores.
+    pub threads: Option<usize>,
+    /// Number of the threads compressing one large asset, on top of the converting ones.
+    /// Defaults to the number of the logical cores.
+    pub compression_threads: Option<usize>,
+    /// Payloads larger than that are compressed in the independent blocks of that size,
+    /// in parallel (see `compression_backend::compress_blocks`). `None` compresses
+    /// each payload as one stream.
+    pub compression_block_size: Option<usize>,
+    /// Lock file keeping the GUIDs of the assets between the packs, so the
+    /// references by the GUID survive the renames. Usually next to the sources
+    /// and kept in the version control. `None` packs the assets without the GUIDs.
+    pub guid_lock: Option<PathBuf>,
+    /// Rules splitting the assets into the bundles by the tags, the first
+    /// matching one applies. Only `write_bundles` splits the assets.
+    pub bundles: Vec<BundleRule>,
 }
 
 impl DeepHash for ChecksumAlgorithm {
@@ -36,17 +110,55 @@ impl DeepHash for CompressionLevel {
     }
 }
 
+impl DeepHash for DownscaleRule {
+    fn deep_hash<T: Hasher>(&self, state: &mut T, ctx: &mut DeepHashCtx) -> anyhow::Result<()> {
+        with_std(&self.tier, state);
+        self.scale.deep_hash(state, ctx)?;
+        Ok(())
+    }
+}
+
+impl DeepHash for DownscaleRules {
+    fn deep_hash<T: Hasher>(&self, state: &mut T, ctx: &mut DeepHashCtx) -> anyhow::Result<()> {
+        self.textures.deep_hash(state, ctx)?;
+        Ok(())
+    }
+}
+
+impl DeepHash for Conventions {
+    fn deep_hash<T: Hasher>(&self, state: &mut T, ctx: &mut DeepHashCtx) -> anyhow::Result<()> {
+        with_std(&self.system, state);
+        self.unit.meters().deep_hash(state, ctx)?;
+        Ok(())
+    }
+}
+
+impl DeepHash for ImportConventions {
+    fn deep_hash<T: Hasher>(&self, state: &mut T, ctx: &mut DeepHashCtx) -> anyhow::Result<()> {
+        self.gltf.deep_hash(state, ctx)?;
+        self.fbx.deep_hash(state, ctx)?;
+        Ok(())
+    }
+}
+
 impl DeepHash for WriteConfig {
     fn deep_hash<T: Hasher>(&self, state: &mut T, ctx: &mut DeepHashCtx) -> anyhow::Result<()> {
         self.read_mode.deep_hash(state, ctx)?;
         self.checksum_algorithm.deep_hash(state, ctx)?;
         self.compression_level.deep_hash(state, ctx)?;
+        self.compression_block_size.hash(state);
         // Do not hash the cache dir path contents, only the path itself
         self.cache_dir.hash(state);
         self.author.deep_hash(state, ctx)?;
         self.description.deep_hash(state, ctx)?;
         self.version.deep_hash(state, ctx)?;
         self.license.deep_hash(state, ctx)?;
+        self.converters.deep_hash(state, ctx)?;
+        self.downscale.deep_hash(state, ctx)?;
+        self.conventions.deep_hash(state, ctx)?;
+        // Chunking, signing and encryption are applied by the container writer,
+        // not to the cached binaries.
+        // The thread counts and the GUIDs do not affect the output
         Ok(())
     }
 }
@@ -185,7 +185,7 @@ impl DeepHash for f64 {
     }
 }
 
-impl<'a, T: DeepHash> DeepHash for &'a T {
+impl<T: DeepHash> DeepHash for &T {
     fn deep_hash<H: Hasher>(&self, state: &mut H, ctx: &mut DeepHashCtx) -> anyhow::Result<()> {
         (*self).deep_hash(state, ctx)
     }
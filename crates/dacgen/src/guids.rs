@@ -0,0 +1,157 @@
+use crate::deep_hash::DeepHasher;
+use crate::ir::normalize_name;
+use crate::{UserAssetFile, WriteConfig, WriterError};
+use dawn_assets::guid::{AssetGuid, AssetGuids};
+use dawn_assets::{AssetHeader, AssetID};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+use std::time::SystemTime;
+
+const LOCK_COMMENT: &str =
+    "# GUIDs of the assets, generated by dacgen. Keep it in the version control.\n\n";
+
+/// Contents of the GUID lock file (see `WriteConfig::guid_lock`).
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct GuidLock {
+    /// Hash of the contents of each user asset, by its ID.
+    /// The asset of the same contents under the new ID is the renamed one.
+    #[serde(default)]
+    sources: BTreeMap<String, String>,
+    #[serde(default)]
+    guids: BTreeMap<String, AssetGuid>,
+}
+
+impl GuidLock {
+    fn read(path: &Path) -> Result<GuidLock, WriterError> {
+        if !path.exists() {
+            info!("GUID lock {} not found, creating it", path.display());
+            return Ok(GuidLock::default());
+        }
+        let text = std::fs::read_to_string(path)?;
+        toml::from_str(&text).map_err(|e| WriterError::DeserializationError(path.to_path_buf(), e))
+    }
+
+    fn write(&self, path: &Path) -> Result<(), WriterError> {
+        let text = toml::to_string(self)
+            .map_err(|e| WriterError::SerializationError(anyhow::anyhow!(e)))?;
+        std::fs::write(path, format!("{}{}", LOCK_COMMENT, text))?;
+        Ok(())
+    }
+}
+
+// Random enough for the assets of one project: the time, the process and the ID
+fn generate(id: &AssetID, taken: &HashSet<AssetGuid>) -> AssetGuid {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let mut salt = 0u64;
+    loop {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(id.as_str().as_bytes());
+        hasher.update(&nanos.to_le_bytes());
+        hasher.update(&std::process::id().to_le_bytes());
+        hasher.update(&salt.to_le_bytes());
+        let bytes: [u8; 16] = hasher.finalize().as_bytes()[..16].try_into().unwrap();
+        let guid = AssetGuid::from_u128(u128::from_le_bytes(bytes));
+        if !taken.contains(&guid) {
+            return guid;
+        }
+        salt += 1;
+    }
+}
+
+// The asset IDs generated from the user asset start with its ID,
+// e.g. the meshes of the glTF scene or the quality variants
+fn derived_suffix<'a>(id: &'a str, base: &str) -> Option<&'a str> {
+    let suffix = id.strip_prefix(base)?;
+    (suffix.is_empty() || suffix.starts_with(['_', '@'])).then_some(suffix)
+}
+
+/// Assigns the GUIDs to the packed assets: the ones from the lock file,
+/// the ones of the renamed assets, and the new ones for the rest.
+/// The lock file is updated to the current assets.
+pub(crate) fn assign_guids(
+    lock_path: &Path,
+    user_assets: &[UserAssetFile],
+    headers: &[AssetHeader],
+    config: &WriteConfig,
+    cwd: &Path,
+) -> Result<AssetGuids, WriterError> {
+    let lock = GuidLock::read(lock_path)?;
+
+    let mut sources = BTreeMap::new();
+    for asset in user_assets {
+        let mut hasher = DeepHasher::new(config.checksum_algorithm);
+        hasher
+            .update_object(asset, config.cache_dir.clone(), cwd.to_path_buf())
+            .map_err(WriterError::HashError)?;
+        let id = normalize_name(asset.path.clone());
+        sources.insert(id.as_str().to_string(), hasher.finalize().hex_string());
+    }
+
+    // Renamed only if the contents match exactly one removed and one new asset
+    let removed = |hash: &String| {
+        lock.sources
+            .iter()
+            .filter(|(id, known)| *known == hash && !sources.contains_key(*id))
+            .map(|(id, _)| id.as_str())
+            .collect::<Vec<_>>()
+    };
+    let added = |hash: &String| {
+        sources
+            .iter()
+            .filter(|(id, known)| *known == hash && !lock.sources.contains_key(*id))
+            .count()
+    };
+    let mut renames = Vec::new();
+    for (id, hash) in &sources {
+        if lock.sources.contains_key(id) {
+            continue;
+        }
+        match removed(hash).as_slice() {
+            [from] if added(hash) == 1 => {
+                info!("Asset {} is renamed to {}, keeping its GUIDs", from, id);
+                renames.push((from.to_string(), id.as_str()));
+            }
+            [] => {}
+            _ => warn!(
+                "Asset {} matches several removed assets, new GUIDs are assigned",
+                id
+            ),
+        }
+    }
+
+    let mut taken: HashSet<AssetGuid> = lock.guids.values().copied().collect();
+    let mut guids = AssetGuids::default();
+    let mut assigned = BTreeMap::new();
+    for header in headers {
+        let id = header.id.as_str();
+        let renamed = || {
+            renames.iter().find_map(|(from, to)| {
+                let suffix = derived_suffix(id, to)?;
+                lock.guids.get(&format!("{}{}", from, suffix)).copied()
+            })
+        };
+        let guid = match lock.guids.get(id).copied().or_else(renamed) {
+            Some(guid) => guid,
+            None => {
+                let guid = generate(&header.id, &taken);
+                debug!("Assigned GUID {} to {}", guid, id);
+                taken.insert(guid);
+                guid
+            }
+        };
+        guids.insert(guid, header.id.clone());
+        assigned.insert(id.to_string(), guid);
+    }
+
+    GuidLock {
+        sources,
+        guids: assigned,
+    }
+    .write(lock_path)?;
+    Ok(guids)
+}
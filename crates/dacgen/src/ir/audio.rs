@@ -1,13 +1,181 @@
 use crate::ir::PartialIR;
 use crate::user::UserAudioAsset;
 use crate::UserAssetFile;
+use dawn_assets::ir::audio::{IRAudio, IRAudioCodec, IREncodedAudio};
+use dawn_assets::ir::IRAsset;
 use std::path::Path;
 
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+struct WavFormat {
+    format: u16,
+    channels: u16,
+    sample_rate: u32,
+    bits_per_sample: u16,
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Decodes the RIFF WAVE file into the interleaved F32 samples.
+fn decode_wav(bytes: &[u8]) -> anyhow::Result<(WavFormat, Vec<f32>)> {
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(anyhow::anyhow!("Not a RIFF WAVE file"));
+    }
+
+    let mut format = None;
+    let mut data = None;
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let size = u32_at(bytes, offset + 4) as usize;
+        let body = &bytes[offset + 8..(offset + 8 + size).min(bytes.len())];
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let mut tag = u16_at(body, 0);
+                // The actual format is the first two bytes of the sub-format GUID
+                if tag == WAVE_FORMAT_EXTENSIBLE && body.len() >= 26 {
+                    tag = u16_at(body, 24);
+                }
+                format = Some(WavFormat {
+                    format: tag,
+                    channels: u16_at(body, 2),
+                    sample_rate: u32_at(body, 4),
+                    bits_per_sample: u16_at(body, 14),
+                });
+            }
+            b"data" => data = Some(body),
+            _ => {}
+        }
+        // Chunks are padded to the even size
+        offset += 8 + size + (size & 1);
+    }
+
+    let format = format.ok_or_else(|| anyhow::anyhow!("Missing fmt chunk"))?;
+    let data = data.ok_or_else(|| anyhow::anyhow!("Missing data chunk"))?;
+    let samples = match (format.format, format.bits_per_sample) {
+        (WAVE_FORMAT_PCM, 8) => data.iter().map(|s| (*s as f32 - 128.0) / 128.0).collect(),
+        (WAVE_FORMAT_PCM, 16) => data
+            .chunks_exact(2)
+            .map(|s| i16::from_le_bytes([s[0], s[1]]) as f32 / 32768.0)
+            .collect(),
+        (WAVE_FORMAT_PCM, 24) => data
+            .chunks_exact(3)
+            .map(|s| (i32::from_le_bytes([0, s[0], s[1], s[2]]) >> 8) as f32 / 8388608.0)
+            .collect(),
+        (WAVE_FORMAT_PCM, 32) => data
+            .chunks_exact(4)
+            .map(|s| i32::from_le_bytes(s.try_into().unwrap()) as f32 / 2147483648.0)
+            .collect(),
+        (WAVE_FORMAT_IEEE_FLOAT, 32) => data
+            .chunks_exact(4)
+            .map(|s| f32::from_le_bytes(s.try_into().unwrap()))
+            .collect(),
+        (tag, bits) => {
+            return Err(anyhow::anyhow!(
+                "Unsupported WAVE format {} with {} bits per sample",
+                tag,
+                bits
+            ))
+        }
+    };
+    Ok((format, samples))
+}
+
+/// Codec of the Ogg Vorbis or FLAC file, `None` for anything else.
+fn detect_codec(bytes: &[u8]) -> Option<IRAudioCodec> {
+    if bytes.starts_with(b"OggS") {
+        Some(IRAudioCodec::Vorbis)
+    } else if bytes.starts_with(b"fLaC") {
+        Some(IRAudioCodec::Flac)
+    } else {
+        None
+    }
+}
+
+/// Decodes the whole encoded file, returning the sample rate,
+/// the number of channels and the interleaved samples.
+fn decode_encoded(codec: IRAudioCodec, bytes: &[u8]) -> anyhow::Result<(u32, u16, Vec<f32>)> {
+    let encoded = IREncodedAudio {
+        codec,
+        data: bytes.to_vec(),
+    };
+    let mut decoder = encoded.decoder()?;
+    let mut samples = Vec::new();
+    let mut block = Vec::new();
+    while decoder.decode_block(&mut block)? {
+        samples.extend_from_slice(&block);
+    }
+    Ok((decoder.sample_rate(), decoder.channels() as u16, samples))
+}
+
 pub fn convert_audio(
     file: &UserAssetFile,
     cache_dir: &Path,
     cwd: &Path,
     user: &UserAudioAsset,
 ) -> anyhow::Result<Vec<PartialIR>> {
-    todo!()
+    let bytes = user.source.read(cache_dir, cwd)?;
+    let codec = detect_codec(&bytes);
+    let (sample_rate, channels, data) = match codec {
+        Some(codec) => decode_encoded(codec, &bytes)?,
+        None => {
+            let (format, data) = decode_wav(&bytes)?;
+            (format.sample_rate, format.channels, data)
+        }
+    };
+    if sample_rate != user.sample_rate || channels != user.channels as u16 {
+        return Err(anyhow::anyhow!(
+            "Audio is {} Hz with {} channels, expected {} Hz with {} channels",
+            sample_rate,
+            channels,
+            user.sample_rate,
+            user.channels
+        ));
+    }
+
+    let mut ir = IRAudio {
+        length: data.len() / user.channels.max(1) as usize,
+        data,
+        sample_rate: user.sample_rate,
+        channels: user.channels,
+        chunks: vec![],
+        encoded: None,
+        captions: user.captions.clone(),
+    };
+    if user.streamed {
+        let Some(codec) = codec else {
+            return Err(anyhow::anyhow!(
+                "Only the Ogg Vorbis and FLAC sources can be streamed"
+            ));
+        };
+        if user.chunk_duration.is_some() {
+            return Err(anyhow::anyhow!("Streamed audio cannot be packed"));
+        }
+        // The samples were decoded only to check the stream and to count the frames
+        ir.data = vec![];
+        ir.encoded = Some(IREncodedAudio { codec, data: bytes });
+    } else if let Some(duration) = user.chunk_duration {
+        let frames = user.sample_rate as u64 * duration as u64 / 1000;
+        ir.pack(frames as usize)?;
+    }
+
+    // The captions are loaded with the clip
+    let mut header = file.asset.header.clone();
+    if let Some(captions) = &user.captions {
+        header.dependencies.insert(captions.clone());
+    }
+
+    Ok(vec![PartialIR::new_from_path(
+        IRAsset::Audio(ir),
+        header,
+        file.path.clone(),
+    )])
 }
@@ -0,0 +1,126 @@
+use crate::ir::PartialIR;
+use crate::user::UserCaptionsAsset;
+use crate::UserAssetFile;
+use anyhow::{anyhow, bail};
+use dawn_assets::ir::captions::{IRCaption, IRCaptions};
+use dawn_assets::ir::IRAsset;
+use std::path::Path;
+
+/// Parses `[hh:]mm:ss.mmm` (WebVTT) or `hh:mm:ss,mmm` (SubRip) into seconds.
+fn parse_timestamp(str: &str) -> anyhow::Result<f32> {
+    let invalid = || anyhow!("Invalid timestamp '{}'", str);
+    let (clock, millis) = str.split_once(['.', ',']).ok_or_else(invalid)?;
+    let millis: u32 = millis.parse().map_err(|_| invalid())?;
+
+    let mut seconds = 0u32;
+    let parts = clock.split(':').collect::<Vec<_>>();
+    if !(2..=3).contains(&parts.len()) {
+        return Err(invalid());
+    }
+    for part in parts {
+        seconds = seconds * 60 + part.parse::<u32>().map_err(|_| invalid())?;
+    }
+    Ok(seconds as f32 + millis as f32 / 1000.0)
+}
+
+/// Splits the speaker of the voice span off and strips the rest of the markup.
+fn parse_text(lines: &[&str]) -> (Option<String>, String) {
+    let mut speaker = None;
+    let mut text = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            text.push('\n');
+        }
+        let mut rest = *line;
+        while let Some(start) = rest.find(['<', '{']) {
+            text.push_str(&rest[..start]);
+            let close = if rest[start..].starts_with('<') {
+                '>'
+            } else {
+                '}'
+            };
+            let Some(end) = rest[start..].find(close) else {
+                // Not a tag, keep as is
+                text.push_str(&rest[start..]);
+                rest = "";
+                break;
+            };
+            let tag = &rest[start + 1..start + end];
+            // <v Name> or <v.class Name>
+            if let Some(voice) = tag.strip_prefix('v').filter(|v| v.starts_with([' ', '.'])) {
+                if let Some((_, name)) = voice.split_once(' ') {
+                    speaker.get_or_insert_with(|| name.trim().to_string());
+                }
+            }
+            rest = &rest[start + end + 1..];
+        }
+        text.push_str(rest);
+    }
+
+    let text = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", "\u{a0}")
+        .replace("&amp;", "&");
+    (speaker, text.trim().to_string())
+}
+
+/// Parses the SubRip or the WebVTT captions. Both are the blocks separated
+/// by the empty lines: the optional identifier, the timing and the text.
+fn parse_captions(source: &str) -> anyhow::Result<IRCaptions> {
+    let source = source.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+    let mut entries = Vec::new();
+    for block in source.split("\n\n") {
+        let lines = block
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .collect::<Vec<_>>();
+        // The WebVTT header and the comment, style and region blocks
+        let Some(timing) = lines.iter().position(|line| line.contains("-->")) else {
+            continue;
+        };
+        if timing > 1 {
+            bail!("Unexpected lines before the timing '{}'", lines[timing]);
+        }
+
+        let (start, end) = lines[timing].split_once("-->").unwrap();
+        // WebVTT cue settings follow the end time
+        let end = end.split_whitespace().next().unwrap_or_default();
+        let start = parse_timestamp(start.trim())?;
+        let end = parse_timestamp(end)?;
+        if end < start {
+            bail!("Caption ends before it starts: {}", lines[timing]);
+        }
+
+        let (speaker, text) = parse_text(&lines[timing + 1..]);
+        entries.push(IRCaption {
+            start,
+            end,
+            speaker,
+            text,
+        });
+    }
+
+    entries.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Ok(IRCaptions { entries })
+}
+
+pub fn convert_captions(
+    file: &UserAssetFile,
+    cache_dir: &Path,
+    cwd: &Path,
+    user: &UserCaptionsAsset,
+) -> anyhow::Result<Vec<PartialIR>> {
+    let bytes = user.source.read(cache_dir, cwd)?;
+    let source = String::from_utf8(bytes)?;
+    let captions = parse_captions(&source)?;
+    if captions.entries.is_empty() {
+        bail!("Captions have no entries");
+    }
+
+    Ok(vec![PartialIR::new_from_path(
+        IRAsset::Captions(captions),
+        file.asset.header.clone(),
+        file.path.clone(),
+    )])
+}
@@ -0,0 +1,61 @@
+use crate::ir::{normalize_name, PartialIR};
+use crate::plugin::{ConverterError, ConverterInput, ConverterRegistry};
+use crate::user::UserCustomAsset;
+use crate::{UserAssetFile, UserAssetIndex};
+use dawn_assets::ir::IRAsset;
+use dawn_assets::AssetType;
+use std::collections::HashMap;
+use std::path::Path;
+
+pub fn convert_custom(
+    file: &UserAssetFile,
+    cache_dir: &Path,
+    cwd: &Path,
+    user: &UserCustomAsset,
+    converters: &ConverterRegistry,
+    index: &UserAssetIndex,
+) -> anyhow::Result<Vec<PartialIR>> {
+    let converter = converters.get(&user.asset_type)?;
+
+    let mut sources = Vec::with_capacity(user.sources.len());
+    for source in user.sources.iter() {
+        sources.push(source.read(cache_dir, cwd)?);
+    }
+
+    let mut inputs = HashMap::new();
+    for input in file.inputs(converters) {
+        let asset = index
+            .get(&input)
+            .ok_or_else(|| ConverterError::InputNotFound(input.clone()))?;
+        let mut sources = vec![];
+        for source in asset.asset.properties.sources() {
+            sources.push(source.read(cache_dir, cwd)?);
+        }
+        inputs.insert(input, sources);
+    }
+
+    let id = normalize_name(file.path.clone());
+    let converted = converter.convert(ConverterInput {
+        id: id.clone(),
+        sources,
+        params: &user.params,
+        inputs,
+    })?;
+
+    if converted.len() > 1 && converted.iter().any(|c| c.id.is_none()) {
+        Err(ConverterError::AmbiguousID(user.asset_type.clone()))?;
+    }
+
+    Ok(converted
+        .into_iter()
+        .map(|c| {
+            let mut header = file.asset.header.clone();
+            header.dependencies.extend(c.dependencies);
+            // Custom payloads are routed to the factory registered for their tag
+            if let IRAsset::Custom(custom) = &c.ir {
+                header.asset_type = AssetType::Custom(custom.tag.id());
+            }
+            PartialIR::new_from_id(c.ir, header, c.id.unwrap_or(id.clone()))
+        })
+        .collect())
+}
@@ -0,0 +1,932 @@
+//! Import of the binary FBX files (7.x) into the mesh and the material IRs,
+//! so the content exported from the other engines and the DCC tools can be
+//! packed without the conversion to glTF first.
+//!
+//! Imported:
+//!  - The geometry of the mesh models, triangulated, with the normals and
+//!    the first UV set. Placed by the model hierarchy (translation, pre-rotation,
+//!    rotation and scaling), converted to meters and to the Y-up axis.
+//!  - The materials: the diffuse color, the opacity and the shininess
+//!    (converted to the roughness). The textures are not imported.
+//!  - The Unity `.meta` file next to the source: the `globalScale`
+//!    and the `useFileScale` of the model importer.
+//!
+//! The animations are skipped, since there is no animation IR yet.
+//! The ASCII FBX files are not supported, re-export them as binary.
+
+use crate::ir::PartialIR;
+use crate::user::UserAssetHeader;
+use dawn_assets::ir::material::IRMaterial;
+use dawn_assets::ir::mesh::{IRMeshBounds, IRMeshVertex, IRSubMesh, IRTopology};
+use dawn_assets::ir::IRAsset;
+use dawn_assets::units::Conventions;
+use dawn_assets::{AssetID, AssetType};
+use flate2::read::ZlibDecoder;
+use glam::{EulerRot, Mat3, Mat4, Quat, Vec2, Vec3};
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use thiserror::Error;
+
+const MAGIC: &[u8] = b"Kaydara FBX Binary  \0";
+// The exporters nest the nodes a few levels deep,
+// the deeper files are malformed and would overflow the stack
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Error)]
+pub enum FbxError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not a binary FBX file (ASCII FBX is not supported)")]
+    NotBinary,
+    #[error("Unexpected end of the file at {0}")]
+    Truncated(usize),
+    #[error("Node at {0} ends before its contents")]
+    InvalidNodeEnd(usize),
+    #[error("Nodes are nested deeper than {MAX_DEPTH} at {0}")]
+    TooDeep(usize),
+    #[error("Unknown property type '{0}' at {1}")]
+    UnknownProperty(char, usize),
+    #[error("Failed to decompress the array at {0}: {1}")]
+    Decompress(usize, std::io::Error),
+    #[error("File has no Objects section")]
+    NoObjects,
+    #[error("Geometry {0} has no {1}")]
+    MissingGeometryData(String, &'static str),
+    #[error("Geometry {0} refers to the missing vertex {1}")]
+    InvalidIndex(String, i32),
+}
+
+#[derive(Debug)]
+enum Property {
+    Bool(bool),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    String(String),
+    Raw,
+    Bools(Vec<bool>),
+    I32s(Vec<i32>),
+    I64s(Vec<i64>),
+    F32s(Vec<f32>),
+    F64s(Vec<f64>),
+}
+
+impl Property {
+    fn as_i64(&self) -> Option<i64> {
+        match self {
+            Property::Bool(v) => Some(*v as i64),
+            Property::I16(v) => Some(*v as i64),
+            Property::I32(v) => Some(*v as i64),
+            Property::I64(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Property::F32(v) => Some(*v as f64),
+            Property::F64(v) => Some(*v),
+            other => other.as_i64().map(|v| v as f64),
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Property::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn as_f64s(&self) -> Option<Vec<f64>> {
+        match self {
+            Property::F64s(v) => Some(v.clone()),
+            Property::F32s(v) => Some(v.iter().map(|v| *v as f64).collect()),
+            _ => None,
+        }
+    }
+
+    fn as_i32s(&self) -> Option<Vec<i32>> {
+        match self {
+            Property::I32s(v) => Some(v.clone()),
+            Property::I64s(v) => Some(v.iter().map(|v| *v as i32).collect()),
+            Property::Bools(v) => Some(v.iter().map(|v| *v as i32).collect()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Node {
+    name: String,
+    properties: Vec<Property>,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn child(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Node> + 'a {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    fn property(&self, index: usize) -> Option<&Property> {
+        self.properties.get(index)
+    }
+
+    fn id(&self) -> Option<i64> {
+        self.property(0)?.as_i64()
+    }
+
+    // The object names are stored as "Name\0\x01Class"
+    fn object_name(&self) -> String {
+        let name = self.property(1).and_then(Property::as_str).unwrap_or("");
+        name.split("\0\x01").next().unwrap_or("").to_string()
+    }
+
+    // Values of the "P" records in the "Properties70", by the property name
+    fn properties70(&self) -> HashMap<&str, &Node> {
+        self.child("Properties70")
+            .map(|p| {
+                p.children_named("P")
+                    .filter_map(|p| Some((p.property(0)?.as_str()?, p)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn p_scalar(properties: &HashMap<&str, &Node>, name: &str) -> Option<f64> {
+    properties.get(name)?.property(4)?.as_f64()
+}
+
+fn p_vec3(properties: &HashMap<&str, &Node>, name: &str) -> Option<Vec3> {
+    let p = properties.get(name)?;
+    let component = |i| p.property(i).and_then(Property::as_f64).map(|v| v as f32);
+    Some(Vec3::new(component(4)?, component(5)?, component(6)?))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+    // Since 7.5 the offsets and the counts of the node records are 64-bit
+    wide: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, count: usize) -> Result<&'a [u8], FbxError> {
+        let end = self
+            .position
+            .checked_add(count)
+            .ok_or(FbxError::Truncated(self.position))?;
+        let bytes = self
+            .data
+            .get(self.position..end)
+            .ok_or(FbxError::Truncated(self.position))?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], FbxError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, FbxError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, FbxError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn offset(&mut self) -> Result<u64, FbxError> {
+        if self.wide {
+            Ok(u64::from_le_bytes(self.array()?))
+        } else {
+            Ok(self.u32()? as u64)
+        }
+    }
+
+    fn property(&mut self) -> Result<Property, FbxError> {
+        let at = self.position;
+        Ok(match self.u8()? {
+            b'C' => Property::Bool(self.u8()? != 0),
+            b'Y' => Property::I16(i16::from_le_bytes(self.array()?)),
+            b'I' => Property::I32(i32::from_le_bytes(self.array()?)),
+            b'L' => Property::I64(i64::from_le_bytes(self.array()?)),
+            b'F' => Property::F32(f32::from_le_bytes(self.array()?)),
+            b'D' => Property::F64(f64::from_le_bytes(self.array()?)),
+            b'S' => {
+                let length = self.u32()? as usize;
+                Property::String(String::from_utf8_lossy(self.bytes(length)?).into_owned())
+            }
+            b'R' => {
+                let length = self.u32()? as usize;
+                self.bytes(length)?;
+                Property::Raw
+            }
+            b'b' => Property::Bools(self.elements(1, at)?.iter().map(|b| *b != 0).collect()),
+            b'i' => Property::I32s(
+                self.elements(4, at)?
+                    .chunks_exact(4)
+                    .map(|c| i32::from_le_bytes(c.try_into().unwrap()))
+                    .collect(),
+            ),
+            b'l' => Property::I64s(
+                self.elements(8, at)?
+                    .chunks_exact(8)
+                    .map(|c| i64::from_le_bytes(c.try_into().unwrap()))
+                    .collect(),
+            ),
+            b'f' => Property::F32s(
+                self.elements(4, at)?
+                    .chunks_exact(4)
+                    .map(|c| f32::from_le_bytes(c.try_into().unwrap()))
+                    .collect(),
+            ),
+            b'd' => Property::F64s(
+                self.elements(8, at)?
+                    .chunks_exact(8)
+                    .map(|c| f64::from_le_bytes(c.try_into().unwrap()))
+                    .collect(),
+            ),
+            other => return Err(FbxError::UnknownProperty(other as char, at)),
+        })
+    }
+
+    // Raw bytes of the array property, decompressed if needed
+    fn elements(&mut self, size: usize, at: usize) -> Result<Vec<u8>, FbxError> {
+        let length = self.u32()? as usize;
+        let encoding = self.u32()?;
+        let compressed_length = self.u32()? as usize;
+        let bytes = self.bytes(compressed_length)?;
+        if encoding == 0 {
+            return Ok(bytes.to_vec());
+        }
+
+        // The declared length is not trusted for the allocation
+        let expected = length.checked_mul(size).ok_or(FbxError::Truncated(at))?;
+        let mut decompressed = Vec::new();
+        ZlibDecoder::new(bytes)
+            .read_to_end(&mut decompressed)
+            .map_err(|e| FbxError::Decompress(at, e))?;
+        if decompressed.len() < expected {
+            return Err(FbxError::Truncated(at));
+        }
+        Ok(decompressed)
+    }
+
+    // Returns `None` on the null record ending the list of the nodes
+    fn node(&mut self, depth: usize) -> Result<Option<Node>, FbxError> {
+        let start = self.position;
+        if depth > MAX_DEPTH {
+            return Err(FbxError::TooDeep(start));
+        }
+        let end = usize::try_from(self.offset()?).map_err(|_| FbxError::Truncated(start))?;
+        let count = self.offset()?;
+        let _properties_length = self.offset()?;
+        let name_length = self.u8()? as usize;
+        if end == 0 {
+            return Ok(None);
+        }
+
+        let name = String::from_utf8_lossy(self.bytes(name_length)?).into_owned();
+        let properties = (0..count)
+            .map(|_| self.property())
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut children = Vec::new();
+        while self.position < end {
+            match self.node(depth + 1)? {
+                Some(child) => children.push(child),
+                None => break,
+            }
+        }
+        if end > self.data.len() {
+            return Err(FbxError::Truncated(self.position));
+        }
+        // Otherwise the reader would move back and read the same nodes again
+        if end < self.position {
+            return Err(FbxError::InvalidNodeEnd(start));
+        }
+        self.position = end;
+
+        Ok(Some(Node {
+            name,
+            properties,
+            children,
+        }))
+    }
+}
+
+fn parse(data: &[u8]) -> Result<Vec<Node>, FbxError> {
+    if !data.starts_with(MAGIC) || data.len() < 27 {
+        return Err(FbxError::NotBinary);
+    }
+    let version = u32::from_le_bytes(data[23..27].try_into().unwrap());
+    debug!("FBX version {}", version);
+
+    let mut reader = Reader {
+        data,
+        position: 27,
+        wide: version >= 7500,
+    };
+    let mut nodes = Vec::new();
+    while reader.position < data.len() {
+        match reader.node(0)? {
+            Some(node) => nodes.push(node),
+            // The footer follows
+            None => break,
+        }
+    }
+    Ok(nodes)
+}
+
+/// Model importer settings of the Unity `.meta` file.
+struct UnityMeta {
+    global_scale: f32,
+    use_file_scale: bool,
+}
+
+impl Default for UnityMeta {
+    fn default() -> Self {
+        UnityMeta {
+            global_scale: 1.0,
+            use_file_scale: true,
+        }
+    }
+}
+
+impl UnityMeta {
+    // The .meta files are YAML, but only the two scalar keys are needed
+    fn load(source: &Path) -> Self {
+        let mut meta = UnityMeta::default();
+        let path = meta_path(source);
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            return meta;
+        };
+        debug!("Using Unity meta file {}", path.display());
+        for line in text.lines() {
+            let Some((key, value)) = line.trim().split_once(':') else {
+                continue;
+            };
+            match key.trim() {
+                "globalScale" => {
+                    meta.global_scale = value.trim().parse().unwrap_or(meta.global_scale)
+                }
+                "useFileScale" => meta.use_file_scale = value.trim() != "0",
+                _ => {}
+            }
+        }
+        meta
+    }
+}
+
+/// Unity keeps the import settings of `model.fbx` in `model.fbx.meta`.
+pub fn meta_path(source: &Path) -> std::path::PathBuf {
+    let mut path = source.as_os_str().to_owned();
+    path.push(".meta");
+    path.into()
+}
+
+struct Model {
+    name: String,
+    local: Mat4,
+    parent: Option<i64>,
+    materials: Vec<i64>,
+}
+
+fn model_local(node: &Node) -> Mat4 {
+    let p = node.properties70();
+    let translation = p_vec3(&p, "Lcl Translation").unwrap_or(Vec3::ZERO);
+    let euler = |v: Vec3| {
+        let v = v * (std::f32::consts::PI / 180.0);
+        // The default XYZ order rotates around X first
+        Quat::from_euler(EulerRot::ZYX, v.z, v.y, v.x)
+    };
+    let pre_rotation = euler(p_vec3(&p, "PreRotation").unwrap_or(Vec3::ZERO));
+    let rotation = euler(p_vec3(&p, "Lcl Rotation").unwrap_or(Vec3::ZERO));
+    let scale = p_vec3(&p, "Lcl Scaling").unwrap_or(Vec3::ONE);
+    Mat4::from_scale_rotation_translation(scale, pre_rotation * rotation, translation)
+}
+
+fn world_transform(models: &HashMap<i64, Model>, id: i64) -> Mat4 {
+    let mut transform = Mat4::IDENTITY;
+    let mut current = Some(id);
+    // Guards against the cycles in the broken files
+    let mut depth = 0;
+    while let Some(model) = current.and_then(|id| models.get(&id)) {
+        transform = model.local * transform;
+        current = model.parent;
+        depth += 1;
+        if depth > models.len() {
+            warn!("Model hierarchy of {} has a cycle", model.name);
+            break;
+        }
+    }
+    transform
+}
+
+fn convert_material(node: &Node) -> IRMaterial {
+    let p = node.properties70();
+    let diffuse = p_vec3(&p, "DiffuseColor")
+        .or_else(|| p_vec3(&p, "Diffuse"))
+        .unwrap_or(Vec3::ONE);
+    let factor = p_scalar(&p, "DiffuseFactor").unwrap_or(1.0) as f32;
+    let opacity = p_scalar(&p, "Opacity")
+        .or_else(|| p_scalar(&p, "TransparencyFactor").map(|t| 1.0 - t))
+        .unwrap_or(1.0) as f32;
+    // Blinn-Phong exponent to the roughness
+    let shininess = p_scalar(&p, "Shininess")
+        .or_else(|| p_scalar(&p, "ShininessExponent"))
+        .unwrap_or(20.0) as f32;
+    let roughness = (2.0 / (shininess.max(0.0) + 2.0)).sqrt();
+
+    let color = diffuse * factor;
+    IRMaterial {
+        base_color_factor: [color.x, color.y, color.z, opacity.clamp(0.0, 1.0)],
+        metallic_factor: 0.0,
+        roughness_factor: roughness,
+        ..Default::default()
+    }
+}
+
+// Per polygon vertex data of the geometry: normals, UVs
+struct Layer {
+    mapping: String,
+    direct: bool,
+    values: Vec<f64>,
+    indices: Vec<i32>,
+    stride: usize,
+}
+
+impl Layer {
+    fn load(
+        geometry: &Node,
+        element: &str,
+        values: &str,
+        indices: &str,
+        stride: usize,
+    ) -> Option<Self> {
+        let node = geometry.child(element)?;
+        let string = |name| {
+            node.child(name)
+                .and_then(|n| n.property(0))
+                .and_then(Property::as_str)
+                .unwrap_or("")
+                .to_string()
+        };
+        Some(Layer {
+            mapping: string("MappingInformationType"),
+            direct: string("ReferenceInformationType") == "Direct",
+            values: node.child(values)?.property(0)?.as_f64s()?,
+            indices: node
+                .child(indices)
+                .and_then(|n| n.property(0))
+                .and_then(Property::as_i32s)
+                .unwrap_or_default(),
+            stride,
+        })
+    }
+
+    fn get(&self, polygon_vertex: usize, control_point: usize, polygon: usize) -> Option<&[f64]> {
+        let index = match self.mapping.as_str() {
+            "ByPolygonVertex" => polygon_vertex,
+            "ByVertex" | "ByVertice" | "ByControlPoint" => control_point,
+            "ByPolygon" => polygon,
+            _ => 0,
+        };
+        let index = if self.direct {
+            index
+        } else {
+            usize::try_from(*self.indices.get(index)?).ok()?
+        };
+        self.values
+            .get(index * self.stride..(index + 1) * self.stride)
+    }
+}
+
+// Vertices and indices of the submesh being built, deduplicated by the bits
+struct SubMeshBuilder {
+    vertices: Vec<u8>,
+    indices: Vec<u8>,
+    lookup: HashMap<[u32; 8], u32>,
+    min: Vec3,
+    max: Vec3,
+}
+
+impl Default for SubMeshBuilder {
+    fn default() -> Self {
+        SubMeshBuilder {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            lookup: HashMap::new(),
+            min: Vec3::splat(f32::MAX),
+            max: Vec3::splat(f32::MIN),
+        }
+    }
+}
+
+impl SubMeshBuilder {
+    fn push(&mut self, position: Vec3, normal: Vec3, tex_coord: Vec2) {
+        let key = [
+            position.x,
+            position.y,
+            position.z,
+            normal.x,
+            normal.y,
+            normal.z,
+            tex_coord.x,
+            tex_coord.y,
+        ]
+        .map(f32::to_bits);
+        let count = self.lookup.len() as u32;
+        let index = *self.lookup.entry(key).or_insert_with(|| {
+            self.vertices
+                .extend_from_slice(IRMeshVertex::new(position, normal, tex_coord).into_bytes());
+            count
+        });
+        self.min = self.min.min(position);
+        self.max = self.max.max(position);
+        self.indices.extend_from_slice(&index.to_le_bytes());
+    }
+
+    fn build(self, material: Option<AssetID>) -> IRSubMesh {
+        IRSubMesh {
+            vertices: self.vertices,
+            indices: self.indices,
+            material,
+            bounds: IRMeshBounds {
+                min: self.min.to_array(),
+                max: self.max.to_array(),
+            },
+            topology: IRTopology::Triangles,
+        }
+    }
+}
+
+fn convert_geometry(
+    geometry: &Node,
+    transform: Mat4,
+    slots: &[Option<AssetID>],
+) -> Result<Vec<IRSubMesh>, FbxError> {
+    let name = geometry.object_name();
+    let missing = |what| FbxError::MissingGeometryData(name.clone(), what);
+    let positions = geometry
+        .child("Vertices")
+        .and_then(|n| n.property(0))
+        .and_then(Property::as_f64s)
+        .ok_or_else(|| missing("vertices"))?;
+    let polygon_indices = geometry
+        .child("PolygonVertexIndex")
+        .and_then(|n| n.property(0))
+        .and_then(Property::as_i32s)
+        .ok_or_else(|| missing("polygons"))?;
+
+    let normals = Layer::load(geometry, "LayerElementNormal", "Normals", "NormalsIndex", 3);
+    let uvs = Layer::load(geometry, "LayerElementUV", "UV", "UVIndex", 2);
+    let material_layer = geometry.child("LayerElementMaterial");
+    let by_polygon = material_layer
+        .and_then(|n| n.child("MappingInformationType"))
+        .and_then(|n| n.property(0))
+        .and_then(Property::as_str)
+        == Some("ByPolygon");
+    let polygon_materials = material_layer
+        .and_then(|n| n.child("Materials"))
+        .and_then(|n| n.property(0))
+        .and_then(Property::as_i32s)
+        .unwrap_or_default();
+
+    let normal_matrix = Mat3::from_mat4(transform).inverse().transpose();
+    let mut builders: Vec<SubMeshBuilder> = Vec::new();
+    builders.resize_with(slots.len().max(1), Default::default);
+
+    let mut polygon = Vec::new();
+    let mut polygon_index = 0;
+    for (polygon_vertex, index) in polygon_indices.iter().enumerate() {
+        // The last index of the polygon is stored as the bitwise negation
+        let control_point = if *index < 0 { !*index } else { *index } as usize;
+        let position = positions
+            .get(control_point * 3..control_point * 3 + 3)
+            .ok_or(FbxError::InvalidIndex(name.clone(), *index))?;
+        polygon.push((polygon_vertex, control_point, position));
+        if *index >= 0 {
+            continue;
+        }
+        // The mirroring transform turns the polygons inside out
+        if transform.determinant() < 0.0 {
+            polygon.reverse();
+        }
+
+        let to_vec3 = |v: &[f64]| Vec3::new(v[0] as f32, v[1] as f32, v[2] as f32);
+        let points = polygon
+            .iter()
+            .map(|(_, _, p)| transform.transform_point3(to_vec3(p)))
+            .collect::<Vec<_>>();
+        // Newell's method, for the files without the normals
+        let face_normal = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .fold(Vec3::ZERO, |n, (a, b)| {
+                n + Vec3::new(
+                    (a.y - b.y) * (a.z + b.z),
+                    (a.z - b.z) * (a.x + b.x),
+                    (a.x - b.x) * (a.y + b.y),
+                )
+            })
+            .normalize_or_zero();
+
+        let vertices = polygon
+            .iter()
+            .zip(points)
+            .map(|((pv, cp, _), position)| {
+                let normal = normals
+                    .as_ref()
+                    .and_then(|l| l.get(*pv, *cp, polygon_index))
+                    .map(|n| (normal_matrix * to_vec3(n)).normalize_or_zero())
+                    .unwrap_or(face_normal);
+                // FBX has the V axis going up, glTF (and the IR) going down
+                let tex_coord = uvs
+                    .as_ref()
+                    .and_then(|l| l.get(*pv, *cp, polygon_index))
+                    .map(|uv| Vec2::new(uv[0] as f32, 1.0 - uv[1] as f32))
+                    .unwrap_or(Vec2::ZERO);
+                (position, normal, tex_coord)
+            })
+            .collect::<Vec<_>>();
+
+        let slot = match polygon_materials.as_slice() {
+            [] => 0,
+            materials if by_polygon => materials.get(polygon_index).copied().unwrap_or(0),
+            materials => materials[0],
+        };
+        let builder = &mut builders[(slot.max(0) as usize).min(slots.len().max(1) - 1)];
+        // Fan triangulation, fine for the convex polygons the exporters produce
+        for i in 1..vertices.len().saturating_sub(1) {
+            for (position, normal, tex_coord) in [vertices[0], vertices[i], vertices[i + 1]] {
+                builder.push(position, normal, tex_coord);
+            }
+        }
+
+        polygon.clear();
+        polygon_index += 1;
+    }
+
+    Ok(builders
+        .into_iter()
+        .enumerate()
+        .filter(|(_, builder)| !builder.indices.is_empty())
+        .map(|(slot, builder)| builder.build(slots.get(slot).cloned().flatten()))
+        .collect())
+}
+
+/// Converts the FBX file into the submeshes of the mesh `mesh_id`
+/// and the IRs of its materials. The `conventions` replace the units
+/// and the axes declared in the file.
+pub fn convert_fbx(
+    path: &Path,
+    mesh_id: &AssetID,
+    conventions: Option<Conventions>,
+) -> Result<(Vec<IRSubMesh>, Vec<PartialIR>), FbxError> {
+    let data = std::fs::read(path)?;
+    let nodes = parse(&data)?;
+    let section = |name: &str| nodes.iter().find(|n| n.name == name);
+    let objects = section("Objects").ok_or(FbxError::NoObjects)?;
+
+    // Units and axes of the file
+    let meta = UnityMeta::load(path);
+    let settings = section("GlobalSettings")
+        .map(Node::properties70)
+        .unwrap_or_default();
+    let unit_scale = p_scalar(&settings, "UnitScaleFactor").unwrap_or(1.0) as f32;
+    let mut scale = meta.global_scale;
+    if meta.use_file_scale {
+        // Centimeters by default
+        scale *= unit_scale / 100.0;
+    }
+    let up_axis = p_scalar(&settings, "UpAxis").unwrap_or(1.0) as i32;
+    let up_sign = p_scalar(&settings, "UpAxisSign").unwrap_or(1.0) as f32;
+    let axes = match up_axis {
+        0 => Mat4::from_rotation_z(std::f32::consts::FRAC_PI_2 * up_sign),
+        2 => Mat4::from_rotation_x(-std::f32::consts::FRAC_PI_2 * up_sign),
+        _ if up_sign < 0.0 => Mat4::from_rotation_x(std::f32::consts::PI),
+        _ => Mat4::IDENTITY,
+    };
+    let root = match conventions {
+        // Replaces the axes and the unit declared in the file
+        Some(conventions) => {
+            conventions.to_engine() * Mat4::from_scale(Vec3::splat(meta.global_scale))
+        }
+        None => axes * Mat4::from_scale(Vec3::splat(scale)),
+    };
+
+    if objects.child("AnimationStack").is_some() {
+        warn!(
+            "Animations of {} are skipped, they are not supported yet",
+            path.display()
+        );
+    }
+
+    let mut models = HashMap::new();
+    let mut geometries = HashMap::new();
+    let mut materials = HashMap::new();
+    for node in &objects.children {
+        let Some(id) = node.id() else {
+            continue;
+        };
+        match node.name.as_str() {
+            "Model" => {
+                models.insert(
+                    id,
+                    Model {
+                        name: node.object_name(),
+                        local: model_local(node),
+                        parent: None,
+                        materials: Vec::new(),
+                    },
+                );
+            }
+            "Geometry" => {
+                geometries.insert(id, node);
+            }
+            "Material" => {
+                materials.insert(id, node);
+            }
+            _ => {}
+        }
+    }
+
+    // Object-object connections: child, parent
+    let mut geometry_models = Vec::new();
+    let connections = section("Connections").map(|c| c.children_named("C"));
+    for connection in connections.into_iter().flatten() {
+        let kind = connection.property(0).and_then(Property::as_str);
+        let child = connection.property(1).and_then(Property::as_i64);
+        let parent = connection.property(2).and_then(Property::as_i64);
+        let (Some("OO"), Some(child), Some(parent)) = (kind, child, parent) else {
+            continue;
+        };
+        if geometries.contains_key(&child) && models.contains_key(&parent) {
+            geometry_models.push((child, parent));
+        } else if materials.contains_key(&child) {
+            // The order of the connections is the order of the material slots
+            if let Some(model) = models.get_mut(&parent) {
+                model.materials.push(child);
+            }
+        } else if models.contains_key(&parent) {
+            if let Some(model) = models.get_mut(&child) {
+                model.parent = Some(parent);
+            }
+        }
+    }
+
+    let mut irs = Vec::new();
+    let mut material_ids = HashMap::new();
+    let mut submesh = Vec::new();
+    for (geometry, model) in geometry_models {
+        let slots = models[&model]
+            .materials
+            .iter()
+            .map(|material| {
+                if let Some(id) = material_ids.get(material) {
+                    return Some(Clone::clone(id));
+                }
+                let node = materials.get(material)?;
+                let name = node.object_name();
+                let id = AssetID::new(if name.is_empty() {
+                    format!("{}_{}_material", mesh_id.as_str(), material)
+                } else {
+                    format!("{}_{}", mesh_id.as_str(), name)
+                });
+                irs.push(PartialIR {
+                    id: id.clone(),
+                    header: UserAssetHeader {
+                        asset_type: AssetType::Material,
+                        dependencies: Default::default(),
+                        tags: vec![],
+                        author: Some("Auto-generated".to_string()),
+                        license: None,
+                        locale: None,
+                        quality: None,
+                        bundle: None,
+                    },
+                    ir: IRAsset::Material(convert_material(node)),
+                });
+                material_ids.insert(*material, id.clone());
+                Some(id)
+            })
+            .collect::<Vec<_>>();
+
+        let transform = root * world_transform(&models, model);
+        submesh.extend(convert_geometry(geometries[&geometry], transform, &slots)?);
+    }
+
+    Ok((submesh, irs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A single triangle, 1 meter wide, with the compressed vertices
+    fn fixture() -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/triangle.fbx")
+    }
+
+    fn header() -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&[0x1A, 0]);
+        data.extend_from_slice(&7400u32.to_le_bytes());
+        data
+    }
+
+    // Nodes without the properties, each holding the next one
+    fn nested(depth: usize) -> Vec<u8> {
+        let mut data = header();
+        let headers_end = data.len() + 14 * depth;
+        for i in 0..depth {
+            let trailers = if i + 1 == depth { 0 } else { depth - 1 - i };
+            let end = (headers_end + 13 * trailers) as u32;
+            data.extend_from_slice(&end.to_le_bytes());
+            data.extend_from_slice(&[0; 8]);
+            data.extend_from_slice(&[1, b'N']);
+        }
+        data.resize(data.len() + 13 * depth, 0);
+        data
+    }
+
+    #[test]
+    fn converts_fixture() {
+        let (submesh, irs) =
+            convert_fbx(&fixture(), &AssetID::new("triangle".to_string()), None).unwrap();
+        assert!(irs.is_empty());
+        assert_eq!(submesh.len(), 1);
+        assert_eq!(submesh[0].indices.len(), 3 * size_of::<u32>());
+        assert_eq!(submesh[0].vertices.len(), 3 * size_of::<IRMeshVertex>());
+        // Centimeters by default
+        assert_eq!(submesh[0].bounds.min, [0.0, 0.0, 0.0]);
+        assert_eq!(submesh[0].bounds.max, [1.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn truncated_files_are_rejected() {
+        let data = std::fs::read(fixture()).unwrap();
+        // Cutting right after a top-level node loses the rest of the file silently
+        let objects_end = u32::from_le_bytes(data[27..31].try_into().unwrap()) as usize;
+        let footer = data.len() - 13;
+        for length in (0..footer).filter(|l| *l != 27 && *l != objects_end) {
+            assert!(
+                parse(&data[..length]).is_err(),
+                "truncated to {} bytes",
+                length
+            );
+        }
+        assert!(matches!(parse(&data[..20]), Err(FbxError::NotBinary)));
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        let mut data = header();
+        data.resize(data.len() + 64, 0xFF);
+        assert!(matches!(parse(&data), Err(FbxError::Truncated(_))));
+
+        // Any bytes after the header, either parsed or rejected
+        let mut state = 0x2545F491u32;
+        for _ in 0..1000 {
+            let mut data = header();
+            for _ in 0..128 {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                data.push(state as u8);
+            }
+            let _ = parse(&data);
+        }
+
+        assert!(matches!(parse(&[0xFF; 64]), Err(FbxError::NotBinary)));
+    }
+
+    #[test]
+    fn node_ending_before_its_contents_is_rejected() {
+        let mut data = header();
+        data.extend_from_slice(&30u32.to_le_bytes());
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&[1, b'N']);
+        data.resize(data.len() + 13, 0);
+        assert!(matches!(parse(&data), Err(FbxError::InvalidNodeEnd(27))));
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected() {
+        let nodes = parse(&nested(3)).unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].children[0].children[0].name, "N");
+
+        assert!(parse(&nested(MAX_DEPTH + 1)).is_ok());
+        assert!(matches!(parse(&nested(100_000)), Err(FbxError::TooDeep(_))));
+    }
+}
@@ -31,6 +31,9 @@ fn convert_texture(
                 tags: vec![],
                 author: Some("Auto-generated".to_string()),
                 license: None,
+                locale: None,
+                quality: None,
+                bundle: None,
             },
             ir: IRAsset::Texture(IRTexture {
                 data,
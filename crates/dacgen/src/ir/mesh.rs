@@ -1,10 +1,14 @@
+use crate::config::ImportConventions;
 use crate::ir::{normalize_name, PartialIR};
 use crate::user::{UserAssetHeader, UserMeshAsset};
 use crate::UserAssetFile;
 use dawn_assets::ir::material::IRMaterial;
-use dawn_assets::ir::mesh::{IRIndexType, IRMesh, IRMeshBounds, IRSubMesh, IRTopology, IRMeshVertex};
+use dawn_assets::ir::mesh::{
+    IRIndexType, IRMesh, IRMeshBounds, IRMeshBvh, IRMeshVertex, IRSubMesh, IRTopology,
+};
 use dawn_assets::ir::texture::{IRPixelFormat, IRTexture, IRTextureType};
 use dawn_assets::ir::IRAsset;
+use dawn_assets::units::{check_mesh, Conventions};
 use dawn_assets::{AssetID, AssetType};
 use dawn_util::profile::Measure;
 use glam::{Mat4, Vec3};
@@ -12,6 +16,7 @@ use gltf::buffer::Data;
 use gltf::image::Format;
 use gltf::mesh::Mode;
 use gltf::scene::Transform;
+use log::warn;
 use rayon::prelude::*;
 use std::collections::{HashMap, HashSet};
 use std::path::Path;
@@ -102,6 +107,8 @@ enum MeshError {
     },
     #[error("Cannot fit index into selected index type")]
     IndexOverflow,
+    #[error("Failed to import FBX file: {0}")]
+    Fbx(String),
 }
 
 #[derive(Clone, Debug)]
@@ -109,9 +116,6 @@ enum MaterialTextureType {
     BaseColor,
     Metallic,
     Roughness,
-    Normal,
-    Occlusion,
-    Emissive,
 }
 
 impl MaterialTextureType {
@@ -120,9 +124,6 @@ impl MaterialTextureType {
             MaterialTextureType::BaseColor => "base_color",
             MaterialTextureType::Metallic => "metallic",
             MaterialTextureType::Roughness => "roughness",
-            MaterialTextureType::Normal => "normal",
-            MaterialTextureType::Occlusion => "occlusion",
-            MaterialTextureType::Emissive => "emissive",
         }
     }
 }
@@ -143,7 +144,7 @@ fn texture_id(
             "{}_{}_{}",
             material_id.as_str(),
             texture_type.as_str(),
-            name.to_string()
+            name
         ),
     })
 }
@@ -207,6 +208,9 @@ fn process_texture(
                 tags: vec![],
                 author: Some("Auto-generated".to_string()),
                 license: None,
+                locale: None,
+                quality: None,
+                bundle: None,
             },
             ir: IRAsset::Texture(IRTexture {
                 data: data.pixels.clone(),
@@ -307,13 +311,16 @@ fn process_material(
     };
 
     irs.push(PartialIR {
-        id: AssetID::from(id.clone()),
+        id: id.clone(),
         header: UserAssetHeader {
             asset_type: AssetType::Material,
             dependencies,
             tags: vec![],
             author: Some("Auto-generated".to_string()),
             license: None,
+            locale: None,
+            quality: None,
+            bundle: None,
         },
         ir: IRAsset::Material(IRMaterial {
             base_color_factor: material.pbr_metallic_roughness().base_color_factor(),
@@ -327,7 +334,7 @@ fn process_material(
     });
 
     // TODO: Normal, occlusion and emissive textures
-    Ok((AssetID::from(id), irs))
+    Ok((id, irs))
 }
 
 fn process_primitive(
@@ -353,7 +360,7 @@ fn process_primitive(
     };
 
     let reader = primitive.reader(|buffer| Some(&ctx.buffers[buffer.index()]));
-    let indices_u32: Vec<_> = reader
+    let mut indices_u32: Vec<_> = reader
         .read_indices()
         .ok_or(MeshError::MissingIndices {
             mesh_index,
@@ -361,6 +368,12 @@ fn process_primitive(
         })?
         .into_u32()
         .collect();
+    // The mirroring transform turns the triangles inside out
+    if primitive.mode() == Mode::Triangles && transform.determinant() < 0.0 {
+        for triangle in indices_u32.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
     let mut indices = Vec::new();
     match ctx.index_type {
         IRIndexType::U16 => {
@@ -401,10 +414,7 @@ fn process_primitive(
     };
 
     let tex_coords: Vec<_> = if let Some(tex_coords_iter) = reader.read_tex_coords(0) {
-        tex_coords_iter
-            .into_f32()
-            .map(|tc| glam::Vec2::from(tc))
-            .collect()
+        tex_coords_iter.into_f32().map(glam::Vec2::from).collect()
     } else {
         Err(MeshError::MissingTexCoords {
             mesh_index,
@@ -477,12 +487,20 @@ fn convert_mesh_inner(
     cache_dir: &Path,
     cwd: &Path,
     user: &UserMeshAsset,
+    conventions: &ImportConventions,
 ) -> Result<Vec<PartialIR>, MeshError> {
     // Load the GLTF file
     let path = user
         .source
         .as_path(cache_dir, cwd)
         .map_err(|e| MeshError::NotAFile(e.to_string()))?;
+    let is_fbx = path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("fbx"));
+    if is_fbx {
+        return convert_fbx_mesh(file, &path, user, user.conventions.or(conventions.fbx));
+    }
+
     let (document, buffers, images) = {
         let _measure = Measure::new(format!("Loaded mesh file '{}'", path.display()));
         gltf::import(path.clone()).map_err(|e| MeshError::LoadError(e.to_string()))?
@@ -509,6 +527,11 @@ fn convert_mesh_inner(
     };
 
     let scene = document.scenes().next().unwrap();
+    let root = user
+        .conventions
+        .or(conventions.gltf)
+        .unwrap_or(Conventions::GLTF)
+        .to_engine();
 
     // Collect all nodes as a flat list of meshes with their transforms
     // Since IR is not hierarchical, we need to flatten the scene graph.
@@ -537,7 +560,7 @@ fn convert_mesh_inner(
 
     let mut meshes = Vec::new();
     for node in scene.nodes() {
-        collect_nodes(node, Mat4::IDENTITY, &mut meshes, ctx.clone());
+        collect_nodes(node, root, &mut meshes, ctx.clone());
     }
 
     // Process each mesh in parallel. The final order is not important.
@@ -559,36 +582,147 @@ fn convert_mesh_inner(
 
     let mut irs = Vec::new();
     let mut submesh = Vec::with_capacity(results.len());
-    let mut min_global = Vec3::splat(f32::MAX);
-    let mut max_global = Vec3::splat(f32::MIN);
     for result in results {
         irs.extend(result.irs);
-        min_global = min_global.min(result.mesh.bounds.min.into());
-        max_global = max_global.max(result.mesh.bounds.max.into());
         submesh.push(result.mesh);
     }
 
-    irs.push(PartialIR::new_from_id(
-        IRAsset::Mesh(IRMesh {
-            submesh,
-            bounds: IRMeshBounds {
-                min: min_global.to_array(),
-                max: max_global.to_array(),
-            },
-            index_type: ctx.index_type,
-        }),
+    irs.push(assemble_mesh(
+        submesh,
+        ctx.index_type,
         header,
-        mesh_id.clone(),
+        mesh_id,
+        user,
     ));
+    Ok(irs)
+}
+
+// Makes the mesh IR out of the converted submeshes
+fn assemble_mesh(
+    submesh: Vec<IRSubMesh>,
+    index_type: IRIndexType,
+    header: UserAssetHeader,
+    mesh_id: AssetID,
+    user: &UserMeshAsset,
+) -> PartialIR {
+    let mut min_global = Vec3::splat(f32::MAX);
+    let mut max_global = Vec3::splat(f32::MIN);
+    for submesh in &submesh {
+        min_global = min_global.min(submesh.bounds.min.into());
+        max_global = max_global.max(submesh.bounds.max.into());
+    }
+
+    let bvh = if user.bvh {
+        let _measure = Measure::new(format!("Built BVH of mesh {}", mesh_id));
+        Some(IRMeshBvh::build(collect_triangles(&submesh, &index_type)))
+    } else {
+        None
+    };
+
+    let mesh = IRMesh {
+        submesh,
+        bounds: IRMeshBounds {
+            min: min_global.to_array(),
+            max: max_global.to_array(),
+        },
+        index_type,
+        static_batching: user.static_batching,
+        bvh,
+    };
+    for issue in check_mesh(&mesh) {
+        warn!(
+            "Mesh {} does not seem to follow the engine conventions: {}",
+            mesh_id, issue
+        );
+    }
+    PartialIR::new_from_id(IRAsset::Mesh(mesh), header, mesh_id)
+}
+
+#[cfg(feature = "import_fbx")]
+fn convert_fbx_mesh(
+    file: &UserAssetFile,
+    path: &Path,
+    user: &UserMeshAsset,
+    conventions: Option<Conventions>,
+) -> Result<Vec<PartialIR>, MeshError> {
+    let _measure = Measure::new(format!("Imported FBX file '{}'", path.display()));
+    let mesh_id = normalize_name(file.path.clone());
+    let (submesh, mut irs) = crate::ir::fbx::convert_fbx(path, &mesh_id, conventions)
+        .map_err(|e| MeshError::Fbx(e.to_string()))?;
 
+    let mut header = file.asset.header.clone();
+    for ir in &irs {
+        header.dependencies.insert(ir.id.clone());
+    }
+    irs.push(assemble_mesh(
+        submesh,
+        IRIndexType::U32,
+        header,
+        mesh_id,
+        user,
+    ));
     Ok(irs)
 }
 
+#[cfg(not(feature = "import_fbx"))]
+fn convert_fbx_mesh(
+    _: &UserAssetFile,
+    _: &Path,
+    _: &UserMeshAsset,
+    _: Option<Conventions>,
+) -> Result<Vec<PartialIR>, MeshError> {
+    Err(MeshError::Fbx(
+        "the import_fbx feature of dawn-dacgen is not enabled".to_string(),
+    ))
+}
+
+// Positions of the triangles of all the triangle submeshes
+fn collect_triangles(submesh: &[IRSubMesh], index_type: &IRIndexType) -> Vec<[Vec3; 3]> {
+    let mut triangles = Vec::new();
+    for submesh in submesh {
+        if submesh.topology != IRTopology::Triangles {
+            continue;
+        }
+
+        let positions = submesh
+            .vertices
+            .chunks_exact(size_of::<IRMeshVertex>())
+            .map(|vertex| {
+                let float = |i: usize| {
+                    let offset = std::mem::offset_of!(IRMeshVertex, position) + i * 4;
+                    f32::from_ne_bytes(vertex[offset..offset + 4].try_into().unwrap())
+                };
+                Vec3::new(float(0), float(1), float(2))
+            })
+            .collect::<Vec<_>>();
+        let indices: Vec<usize> = match index_type {
+            IRIndexType::U16 => submesh
+                .indices
+                .chunks_exact(2)
+                .map(|i| u16::from_le_bytes([i[0], i[1]]) as usize)
+                .collect(),
+            IRIndexType::U32 => submesh
+                .indices
+                .chunks_exact(4)
+                .map(|i| u32::from_le_bytes([i[0], i[1], i[2], i[3]]) as usize)
+                .collect(),
+        };
+
+        triangles.extend(
+            indices
+                .chunks_exact(3)
+                .map(|t| [positions[t[0]], positions[t[1]], positions[t[2]]]),
+        );
+    }
+    triangles
+}
+
 pub fn convert_mesh(
     file: &UserAssetFile,
     cache_dir: &Path,
     cwd: &Path,
     user: &UserMeshAsset,
+    conventions: &ImportConventions,
 ) -> anyhow::Result<Vec<PartialIR>> {
-    convert_mesh_inner(file, cache_dir, cwd, user).map_err(|e| anyhow::anyhow!(e))
+    convert_mesh_inner(file, cache_dir, cwd, user, conventions).map_err(|e| anyhow::anyhow!(e))
 }
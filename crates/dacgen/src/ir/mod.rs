@@ -1,21 +1,32 @@
+use crate::config::WriteConfig;
 use crate::ir::audio::convert_audio;
+use crate::ir::captions::convert_captions;
+use crate::ir::settings::convert_settings;
+use crate::ir::custom::convert_custom;
 use crate::ir::font::convert_font;
 use crate::ir::material::convert_material;
 use crate::ir::mesh::convert_mesh;
+use crate::ir::music::convert_music;
 use crate::ir::shader::convert_shader;
 use crate::ir::texture::convert_texture;
 use crate::user::{UserAssetHeader, UserAssetProperties};
-use crate::{ChecksumAlgorithm, UserAssetFile, UserIRAsset};
-use anyhow::Context;
+use crate::{ChecksumAlgorithm, UserAssetFile, UserAssetIndex, UserIRAsset};
 use dawn_assets::ir::IRAsset;
-use dawn_assets::{AssetChecksum, AssetHeader, AssetID};
+use dawn_assets::variants::QualityTier;
+use dawn_assets::{AssetChecksum, AssetHeader, AssetID, AssetTag};
 use dawn_util::profile::Measure;
 use std::path::{Path, PathBuf};
 
 mod audio;
+mod captions;
+mod settings;
+mod custom;
+#[cfg(feature = "import_fbx")]
+pub(crate) mod fbx;
 mod font;
 mod material;
 mod mesh;
+mod music;
 mod shader;
 mod texture;
 
@@ -30,12 +41,17 @@ pub fn normalize_name(path: PathBuf) -> AssetID {
         .to_lowercase();
 
     // Replace whitespace with underscores and remove special characters
-    name.replace('.', "_")
-        .replace(' ', "_")
+    name.replace(['.', ' '], "_")
         .replace(|c: char| !c.is_alphanumeric() && c != '_', "")
         .into()
 }
 
+/// ID of the generated quality variant of the asset.
+/// Normalized names never contain '@', so it cannot clash with the user IDs.
+pub fn quality_variant_id(id: &AssetID, tier: QualityTier) -> AssetID {
+    format!("{}@{}", id.as_str(), tier).into()
+}
+
 #[derive(Debug)]
 pub(crate) struct PartialIR {
     id: AssetID,
@@ -56,15 +72,20 @@ impl PartialIR {
         Self { id, header, ir }
     }
 
-    pub fn convert(self, algorithm: ChecksumAlgorithm) -> anyhow::Result<UserIRAsset> {
+    pub fn convert(self, _algorithm: ChecksumAlgorithm) -> anyhow::Result<UserIRAsset> {
         Ok(UserIRAsset {
             header: AssetHeader {
                 id: self.id,
-                tags: self.header.tags.clone(),
+                tags: self
+                    .header
+                    .tags
+                    .iter()
+                    .map(|tag| AssetTag::from(tag.as_str()))
+                    .collect(),
                 author: self.header.author.clone(),
                 asset_type: self.header.asset_type,
-                checksum: AssetChecksum::default(), // TODO: Implement checksum calculation
-                dependencies: self.header.dependencies.clone(),
+                checksum: AssetChecksum::default(), // Calculated once serialized
+                dependencies: self.header.dependencies.iter().cloned().collect(),
                 license: self.header.license.clone(),
             },
             ir: self.ir,
@@ -75,10 +96,11 @@ impl PartialIR {
 impl UserAssetFile {
     pub fn convert(
         &self,
-        cache_dir: &Path,
         cwd: &Path,
-        algorithm: ChecksumAlgorithm,
+        config: &WriteConfig,
+        index: &UserAssetIndex,
     ) -> anyhow::Result<Vec<UserIRAsset>> {
+        let cache_dir = config.cache_dir.as_path();
         let _measure = Measure::new(format!(
             "Converted user asset {} to IR",
             self.path.display()
@@ -86,17 +108,28 @@ impl UserAssetFile {
 
         let irs = match &self.asset.properties {
             UserAssetProperties::Shader(shader) => convert_shader(self, cache_dir, cwd, shader),
-            UserAssetProperties::Texture(texture) => convert_texture(self, cache_dir, cwd, texture),
+            UserAssetProperties::Texture(texture) => {
+                convert_texture(self, cache_dir, cwd, texture, &config.downscale)
+            }
             UserAssetProperties::Audio(audio) => convert_audio(self, cache_dir, cwd, audio),
-            UserAssetProperties::Mesh(mesh) => convert_mesh(self, cache_dir, cwd, mesh),
+            UserAssetProperties::Mesh(mesh) => {
+                convert_mesh(self, cache_dir, cwd, mesh, &config.conventions)
+            }
             UserAssetProperties::Material(mat) => convert_material(self, cache_dir, cwd, mat),
             UserAssetProperties::Font(font) => convert_font(self, cache_dir, cwd, font),
-        }
-        .with_context(|| format!("Failed to convert asset {}", self.path.display()))?;
+            UserAssetProperties::Music(music) => convert_music(self, cache_dir, cwd, music),
+            UserAssetProperties::Captions(captions) => {
+                convert_captions(self, cache_dir, cwd, captions)
+            }
+            UserAssetProperties::Settings(settings) => convert_settings(self, settings),
+            UserAssetProperties::Custom(custom) => {
+                convert_custom(self, cache_dir, cwd, custom, &config.converters, index)
+            }
+        }?;
 
         let mut result = Vec::new();
         for ir in irs {
-            result.push(ir.convert(algorithm)?);
+            result.push(ir.convert(config.checksum_algorithm)?);
         }
 
         Ok(result)
@@ -0,0 +1,60 @@
+use crate::ir::PartialIR;
+use crate::user::UserMusicAsset;
+use crate::UserAssetFile;
+use anyhow::bail;
+use dawn_assets::ir::music::IRMusic;
+use dawn_assets::ir::IRAsset;
+use std::collections::HashSet;
+use std::path::Path;
+
+pub fn convert_music(
+    file: &UserAssetFile,
+    _cache_dir: &Path,
+    _cwd: &Path,
+    user: &UserMusicAsset,
+) -> anyhow::Result<Vec<PartialIR>> {
+    if !user.bpm.is_finite() || user.bpm <= 0.0 || user.beats_per_bar == 0 {
+        bail!(
+            "Invalid tempo: {} BPM, {} beats per bar",
+            user.bpm,
+            user.beats_per_bar
+        );
+    }
+    if user.sections.is_empty() {
+        bail!("Music has no sections");
+    }
+
+    let mut names = HashSet::new();
+    for section in &user.sections {
+        if !names.insert(section.name.as_str()) {
+            bail!("Duplicate section '{}'", section.name);
+        }
+    }
+    for transition in &user.transitions {
+        for name in [&transition.from, &transition.to].into_iter().flatten() {
+            if !names.contains(name.as_str()) {
+                bail!("Transition references unknown section '{}'", name);
+            }
+        }
+    }
+
+    // The stems are loaded before the music
+    let mut header = file.asset.header.clone();
+    for section in &user.sections {
+        for layer in &section.layers {
+            header.dependencies.insert(layer.stem.clone());
+        }
+    }
+
+    Ok(vec![PartialIR::new_from_path(
+        IRAsset::Music(IRMusic {
+            bpm: user.bpm,
+            beats_per_bar: user.beats_per_bar,
+            sections: user.sections.clone(),
+            transitions: user.transitions.clone(),
+            intensity_fade: user.intensity_fade,
+        }),
+        header,
+        file.path.clone(),
+    )])
+}
@@ -0,0 +1,107 @@
+use crate::ir::PartialIR;
+use crate::user::UserSettingsAsset;
+use crate::UserAssetFile;
+use anyhow::{anyhow, bail};
+use dawn_assets::ir::settings::{IRSettingValue, IRSettings};
+use dawn_assets::ir::IRAsset;
+use std::collections::BTreeMap;
+
+fn convert_value(key: &str, value: &toml::Value) -> anyhow::Result<IRSettingValue> {
+    Ok(match value {
+        toml::Value::Boolean(value) => IRSettingValue::Bool(*value),
+        toml::Value::Integer(value) => IRSettingValue::Int(*value),
+        toml::Value::Float(value) => IRSettingValue::Float(*value as f32),
+        toml::Value::String(value) => IRSettingValue::Text(value.clone()),
+        toml::Value::Array(values) => IRSettingValue::Vector(
+            values
+                .iter()
+                .map(|value| match value {
+                    toml::Value::Integer(value) => Ok(*value as f32),
+                    toml::Value::Float(value) => Ok(*value as f32),
+                    _ => Err(anyhow!("Array '{}' must contain only numbers", key)),
+                })
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        toml::Value::Datetime(_) => bail!("Value '{}' cannot be a date", key),
+        // Flattened by the caller
+        toml::Value::Table(_) => unreachable!(),
+    })
+}
+
+fn flatten(
+    prefix: &str,
+    table: &toml::Table,
+    values: &mut BTreeMap<String, IRSettingValue>,
+) -> anyhow::Result<()> {
+    for (name, value) in table {
+        let key = if prefix.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", prefix, name)
+        };
+        match value {
+            toml::Value::Table(table) => flatten(&key, table, values)?,
+            value => {
+                values.insert(key.clone(), convert_value(&key, value)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn convert_settings(
+    file: &UserAssetFile,
+    user: &UserSettingsAsset,
+) -> anyhow::Result<Vec<PartialIR>> {
+    let mut settings = IRSettings::default();
+    flatten("", &user.values, &mut settings.values)?;
+    if settings.values.is_empty() {
+        bail!("Settings have no values");
+    }
+
+    Ok(vec![PartialIR::new_from_path(
+        IRAsset::Settings(settings),
+        file.asset.header.clone(),
+        file.path.clone(),
+    )])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_tables_are_flattened() {
+        let table: toml::Table = toml::from_str(
+            r#"
+            fog = { density = 0.5, color = [1, 0.5, 0] }
+            bloom.enabled = true
+            shadows.resolution = 2048
+            tonemap.curve = "aces"
+            "#,
+        )
+        .unwrap();
+        let mut values = BTreeMap::new();
+        flatten("", &table, &mut values).unwrap();
+
+        assert_eq!(values.len(), 5);
+        assert_eq!(values["fog.density"], IRSettingValue::Float(0.5));
+        assert_eq!(
+            values["fog.color"],
+            IRSettingValue::Vector(vec![1.0, 0.5, 0.0])
+        );
+        assert_eq!(values["bloom.enabled"], IRSettingValue::Bool(true));
+        assert_eq!(values["shadows.resolution"], IRSettingValue::Int(2048));
+        assert_eq!(
+            values["tonemap.curve"],
+            IRSettingValue::Text("aces".to_string())
+        );
+    }
+
+    #[test]
+    fn non_numeric_arrays_are_rejected() {
+        let table: toml::Table = toml::from_str(r#"lights = ["a", "b"]"#).unwrap();
+        let err = flatten("", &table, &mut BTreeMap::new()).unwrap_err();
+        assert!(err.to_string().contains("lights"));
+    }
+}
@@ -1,4 +1,5 @@
-use crate::ir::{normalize_name, PartialIR};
+use crate::config::DownscaleRules;
+use crate::ir::{normalize_name, quality_variant_id, PartialIR};
 use crate::user::{UserAssetHeader, UserTextureAsset};
 use crate::UserAssetFile;
 use anyhow::anyhow;
@@ -6,6 +7,7 @@ use dawn_assets::ir::texture::{
     IRPixelFormat, IRTexture, IRTextureFilter, IRTextureType, IRTextureWrap,
 };
 use dawn_assets::ir::IRAsset;
+use dawn_assets::variants::QualityTier;
 use dawn_assets::AssetID;
 use image::{DynamicImage, Rgba};
 use std::path::Path;
@@ -27,7 +29,7 @@ impl Stream {
         self.data.extend_from_slice(bytes);
     }
 
-    fn to_vec(self) -> Vec<u8> {
+    fn into_vec(self) -> Vec<u8> {
         self.data
     }
 }
@@ -36,10 +38,10 @@ fn pack_texture2d(
     image: &DynamicImage,
     width: u32,
     height: u32,
-    pack: impl Fn(&mut Stream, &Rgba<u8>) -> (),
+    pack: impl Fn(&mut Stream, &Rgba<u8>),
 ) -> anyhow::Result<Vec<u8>> {
     let resized =
-        DynamicImage::resize_exact(&image, width, height, image::imageops::FilterType::Nearest);
+        DynamicImage::resize_exact(image, width, height, image::imageops::FilterType::Nearest);
     let resized = resized.to_rgba8();
 
     let mut stream = Stream::with_capacity((width * height * 4) as usize);
@@ -50,7 +52,7 @@ fn pack_texture2d(
         }
     }
 
-    Ok(stream.to_vec())
+    Ok(stream.into_vec())
 }
 
 pub(crate) struct UserTextureAssetInner<'a> {
@@ -110,8 +112,8 @@ pub fn convert_texture_from_memory(
     Ok(vec![PartialIR::new_from_id(
         IRAsset::Texture(IRTexture {
             data,
-            texture_type: user.texture_type.clone(),
-            pixel_format: user.pixel_format.clone(),
+            texture_type: user.texture_type,
+            pixel_format: user.pixel_format,
             use_mipmaps: user.use_mipmaps,
             min_filter: user.min_filter.clone(),
             mag_filter: user.mag_filter.clone(),
@@ -129,6 +131,7 @@ pub fn convert_texture(
     cache_dir: &Path,
     cwd: &Path,
     user: &UserTextureAsset,
+    downscale: &DownscaleRules,
 ) -> anyhow::Result<Vec<PartialIR>> {
     // Assume for now, that the texture is always a single image file
     if user.sources.len() != 1 {
@@ -149,19 +152,45 @@ pub fn convert_texture(
         any => any,
     };
 
-    convert_texture_from_memory(
-        normalize_name(file.path.clone()),
-        file.asset.header.clone(),
-        UserTextureAssetInner {
-            data: &img,
-            pixel_format: user.pixel_format.clone(),
-            use_mipmaps: user.use_mipmaps,
-            min_filter: user.min_filter.clone(),
-            mag_filter: user.mag_filter.clone(),
-            texture_type,
-            wrap_s: user.wrap_s.clone(),
-            wrap_t: user.wrap_t.clone(),
-            wrap_r: user.wrap_r.clone(),
-        },
-    )
+    let inner = |texture_type: IRTextureType| UserTextureAssetInner {
+        data: &img,
+        pixel_format: user.pixel_format,
+        use_mipmaps: user.use_mipmaps,
+        min_filter: user.min_filter.clone(),
+        mag_filter: user.mag_filter.clone(),
+        texture_type,
+        wrap_s: user.wrap_s.clone(),
+        wrap_t: user.wrap_t.clone(),
+        wrap_r: user.wrap_r.clone(),
+    };
+
+    let id = normalize_name(file.path.clone());
+    let header = file.asset.header.clone();
+    if downscale.textures.is_empty() || header.quality.is_some() {
+        return convert_texture_from_memory(id, header, inner(texture_type));
+    }
+
+    // The source becomes the high tier variant, the rest are downscaled from it
+    let mut irs = convert_texture_from_memory(
+        quality_variant_id(&id, QualityTier::High),
+        header.clone(),
+        inner(texture_type),
+    )?;
+    for rule in &downscale.textures {
+        let scale = |size: u32| ((size as f32 * rule.scale).round() as u32).max(1);
+        let scaled = match texture_type {
+            IRTextureType::Texture2D { width, height } => IRTextureType::Texture2D {
+                width: scale(width),
+                height: scale(height),
+            },
+            any => any,
+        };
+        irs.extend(convert_texture_from_memory(
+            quality_variant_id(&id, rule.tier),
+            header.clone(),
+            inner(scaled),
+        )?);
+    }
+
+    Ok(irs)
 }
@@ -1,31 +1,50 @@
+pub mod audit;
+mod bundles;
 mod cache;
 pub mod config;
 mod deep_hash;
+mod guids;
 mod ir;
+pub mod plugin;
+pub mod report;
 mod source;
 mod user;
 
+use crate::bundles::{bundle_guids, bundle_variants, Bundles};
 use crate::cache::Cache;
-use crate::config::WriteConfig;
-use crate::deep_hash::{DeepHash, DeepHashCtx};
-use crate::user::UserAsset;
+use crate::config::{DownscaleRules, WriteConfig};
+use crate::deep_hash::{hash_bytes, DeepHash, DeepHashCtx};
+use crate::ir::{normalize_name, quality_variant_id};
+use crate::plugin::ConverterRegistry;
+use crate::report::BuildReport;
+use crate::user::{UserAsset, UserAssetProperties};
+use dawn_assets::guid::AssetGuids;
 use dawn_assets::ir::IRAsset;
+use dawn_assets::variants::{AssetVariants, QualityTier, QualityVariant};
 use dawn_assets::{AssetChecksum, AssetHeader, AssetID};
+use dawn_dac::compression_backend::{compress_blocks, compress_with_threads};
+use dawn_dac::graph::{DependencyCycle, DependencyGraph};
+use dawn_dac::reader::read_manifest;
 use dawn_dac::serialize_backend::serialize;
-use dawn_dac::writer::{write_container, BinaryAsset};
+use dawn_dac::writer::{BinaryAsset, ContainerOptions, ContainerWriter};
 use dawn_dac::{
     ChecksumAlgorithm, CompressionLevel, CompressionMode, ContainerError, Manifest, ReadMode,
 };
 use dawn_util::profile::Measure;
-use log::{debug, info};
-use rayon::prelude::*;
+use log::{debug, info, warn};
+use std::cmp::Reverse;
+use std::collections::HashMap;
 use std::fs::File;
-use std::hash::{Hash, Hasher};
-use std::io::{Read, Write};
-use std::path::PathBuf;
+use std::hash::Hasher;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::sync::Mutex;
 use std::time::SystemTime;
 use thiserror::Error;
 
+pub use crate::bundles::BASE_BUNDLE;
+
 fn generator_tool() -> String {
     "dawn-dac".to_string() // TODO: Get from Cargo.toml
 }
@@ -34,7 +53,12 @@ fn generator_tool_version() -> String {
     "0.1.0".to_string() // TODO: Get from Cargo.toml
 }
 
-pub(crate) fn create_manifest(write_options: &WriteConfig, headers: Vec<AssetHeader>) -> Manifest {
+pub(crate) fn create_manifest(
+    write_options: &WriteConfig,
+    headers: Vec<AssetHeader>,
+    variants: AssetVariants,
+    guids: AssetGuids,
+) -> Manifest {
     Manifest {
         tool: generator_tool(),
         tool_version: generator_tool_version(),
@@ -46,6 +70,9 @@ pub(crate) fn create_manifest(write_options: &WriteConfig, headers: Vec<AssetHea
         license: write_options.license.clone(),
         version: write_options.version.clone(),
         headers,
+        variants,
+        guids,
+        encrypted: vec![],
     }
 }
 #[derive(Debug, Clone)]
@@ -62,6 +89,9 @@ impl DeepHash for UserAssetFile {
     }
 }
 
+/// User assets by their IDs. Resolves the inputs of the bakes.
+pub(crate) type UserAssetIndex<'a> = HashMap<AssetID, &'a UserAssetFile>;
+
 #[derive(Debug)]
 pub(crate) struct UserIRAsset {
     header: AssetHeader,
@@ -69,26 +99,45 @@ pub(crate) struct UserIRAsset {
 }
 
 impl UserIRAsset {
-    fn convert(&self, compression_level: CompressionLevel) -> Result<BinaryAsset, WriterError> {
-        let _measure = Measure::new(format!(
-            "Compressed {}",
-            self.header.id.clone().as_str().to_string()
-        ));
+    fn convert(&self, config: &WriteConfig) -> Result<BinaryAsset, WriterError> {
+        let _measure = Measure::new(format!("Compressed {}", self.header.id.clone().as_str()));
 
-        let serialized = serialize(&self.ir).map_err(|e| WriterError::SerializationError(e))?;
+        let serialized = serialize(&self.ir).map_err(WriterError::SerializationError)?;
+        let header = AssetHeader {
+            checksum: hash_bytes(&serialized, config.checksum_algorithm)?,
+            ..self.header.clone()
+        };
 
         // Not worth compressing such small files
         if serialized.len() > 256 {
-            let compressed =
-                dawn_dac::compression_backend::compress(&serialized, compression_level)
-                    .map_err(|e| WriterError::CompressionError(e))?;
+            let threads = config
+                .compression_threads
+                .or_else(|| std::thread::available_parallelism().ok().map(|n| n.get()))
+                .unwrap_or(1);
+            let level = config.compression_level.clone();
+            let (compressed, compression) = match config.compression_block_size {
+                Some(block_size)
+                    if serialized.len() > block_size
+                        && !matches!(level, CompressionLevel::None) =>
+                {
+                    (
+                        compress_blocks(&serialized, level, block_size, threads),
+                        CompressionMode::BrotliBlocks,
+                    )
+                }
+                _ => (
+                    compress_with_threads(&serialized, level, threads),
+                    CompressionMode::Brotli,
+                ),
+            };
+            let compressed = compressed.map_err(WriterError::CompressionError)?;
 
             // Check if the compression was effective
-            if compressed.len() != 0 && compressed.len() < serialized.len() {
+            if !compressed.is_empty() && compressed.len() < serialized.len() {
                 return Ok(BinaryAsset {
                     raw: compressed,
-                    compression: CompressionMode::Brotli,
-                    header: self.header.clone(),
+                    compression,
+                    header,
                 });
             }
         }
@@ -96,7 +145,7 @@ impl UserIRAsset {
         Ok(BinaryAsset {
             raw: serialized,
             compression: CompressionMode::None,
-            header: self.header.clone(),
+            header,
         })
     }
 }
@@ -109,22 +158,48 @@ pub enum WriterError {
     UnsupportedChecksumAlgorithm(ChecksumAlgorithm),
     #[error("Failed to parse metadata: {0}: {1}")]
     DeserializationError(PathBuf, toml::de::Error),
+    #[error("Failed to parse metadata: {0}")]
+    MetadataError(toml::de::Error),
     #[error("Hash failed: {0}")]
     HashError(anyhow::Error),
     #[error("Failed to serialize: {0}")]
     SerializationError(anyhow::Error),
     #[error("Failed to compress data: {0}")]
     CompressionError(anyhow::Error),
-    #[error("Failed to validate metadata: {0}")]
-    ConvertingToIRFailed(PathBuf, anyhow::Error),
-    #[error("Unsupported read mode: {0}")]
+    #[error("Failed to convert: {0:#}")]
+    ConversionFailed(anyhow::Error),
+    #[error("Dependency {1} of {0} is missing")]
     DependenciesMissing(AssetID, AssetID),
     #[error("Circular dependency detected: {0} -> {1}")]
     CircleDependency(AssetID, AssetID),
+    #[error("Circular dependency detected: {0}")]
+    DependencyCycle(#[from] DependencyCycle),
+    #[error("Input {1} of {0} not found")]
+    InputMissing(AssetID, AssetID),
     #[error("Non-unique ID: {0}")]
     NonUniqueID(AssetID),
+    #[error("Logical asset ID {0} clashes with a real asset")]
+    VariantIDClash(AssetID),
+    #[error("Duplicate locale '{1}' for logical asset {0}")]
+    DuplicateLocale(AssetID, String),
+    #[error("Logical asset {0} must have exactly one default variant")]
+    NoDefaultVariant(AssetID),
+    #[error("Duplicate {1} quality variant for logical asset {0}")]
+    DuplicateQualityVariant(AssetID, QualityTier),
     #[error("Container creation failed: {0}")]
     ContainerCreationFailed(#[from] ContainerError),
+    #[error("Failed to create the thread pool: {0}")]
+    ThreadPoolCreationFailed(#[from] rayon::ThreadPoolBuildError),
+    #[error("Container writer stopped")]
+    ContainerWriterStopped,
+    #[error("Build failed:\n{0}")]
+    BuildFailed(BuildReport),
+    #[error("Dependency {1} of {0} is in bundle {2}, not installed with it")]
+    CrossBundleDependency(AssetID, AssetID, String),
+    #[error("Variants of logical asset {0} are in different bundles")]
+    SplitVariants(AssetID),
+    #[error("Bundles {0} and {1} are written to the same file")]
+    BundleFileClash(String, String),
 }
 
 /// Collect files from the specified path based on the read mode
@@ -164,8 +239,9 @@ fn collect_user_assets(files: &[PathBuf]) -> Result<Vec<UserAssetFile>, WriterEr
         }
     }
 
-    // Read toml files
+    // Read toml files. The broken ones are reported all at once
     let mut user_assets = Vec::new();
+    let mut report = BuildReport::default();
     for toml_file in &toml_files {
         let mut file = File::open(toml_file)?;
         let mut content = String::new();
@@ -181,37 +257,133 @@ fn collect_user_assets(files: &[PathBuf]) -> Result<Vec<UserAssetFile>, WriterEr
             }
 
             Err(e) => {
-                Err(WriterError::DeserializationError(toml_file.clone(), e))?;
+                let id = normalize_name(toml_file.clone());
+                report.add(toml_file, id, WriterError::MetadataError(e));
             }
         }
     }
 
+    report.into_result()?;
     Ok(user_assets)
 }
 
-fn sanity_check(headers: &[AssetHeader]) -> Result<(), WriterError> {
-    // Check that all dependencies are present
-    for header in headers {
-        for dep in &header.dependencies {
-            if !headers.iter().any(|i| i.id == *dep) {
-                return Err(WriterError::DependenciesMissing(
-                    header.id.clone(),
-                    dep.clone(),
-                ));
+/// Collect the variants declared in the user assets
+/// and the quality variants generated by the downscale rules.
+fn collect_variants(
+    user_assets: &[UserAssetFile],
+    headers: &[AssetHeader],
+    downscale: &DownscaleRules,
+) -> Result<AssetVariants, WriterError> {
+    let mut variants = AssetVariants::default();
+    let mut defaults: HashMap<AssetID, usize> = HashMap::new();
+    for user_asset in user_assets {
+        let Some(locale) = &user_asset.asset.header.locale else {
+            continue;
+        };
+
+        if headers.iter().any(|h| h.id == locale.of) {
+            return Err(WriterError::VariantIDClash(locale.of.clone()));
+        }
+
+        let id = normalize_name(user_asset.path.clone());
+        let entry = variants.locales.entry(locale.of.clone()).or_default();
+        if entry
+            .locales
+            .insert(locale.locale.clone(), id.clone())
+            .is_some()
+        {
+            return Err(WriterError::DuplicateLocale(
+                locale.of.clone(),
+                locale.locale.clone(),
+            ));
+        }
+
+        let count = defaults.entry(locale.of.clone()).or_default();
+        if locale.default {
+            entry.default = id;
+            *count += 1;
+        }
+    }
+
+    for (id, count) in defaults {
+        if count != 1 {
+            return Err(WriterError::NoDefaultVariant(id));
+        }
+    }
+
+    for user_asset in user_assets {
+        let id = normalize_name(user_asset.path.clone());
+        let (of, declared) = match (
+            &user_asset.asset.header.quality,
+            &user_asset.asset.properties,
+        ) {
+            (Some(quality), _) => (
+                quality.of.clone(),
+                vec![QualityVariant {
+                    id,
+                    tier: quality.tier,
+                    requires: quality.requires.clone(),
+                }],
+            ),
+            // Must match the variants produced by the texture converter
+            (None, UserAssetProperties::Texture(_)) if !downscale.textures.is_empty() => {
+                let tiers = downscale.textures.iter().map(|rule| rule.tier);
+                let generated = std::iter::once(QualityTier::High)
+                    .chain(tiers)
+                    .map(|tier| QualityVariant {
+                        id: quality_variant_id(&id, tier),
+                        tier,
+                        requires: None,
+                    })
+                    .collect();
+                (id, generated)
+            }
+            _ => continue,
+        };
+
+        if headers.iter().any(|h| h.id == of) {
+            return Err(WriterError::VariantIDClash(of));
+        }
+
+        let entry = variants.quality.entry(of.clone()).or_default();
+        for variant in declared {
+            if entry
+                .iter()
+                .any(|v| v.tier == variant.tier && v.requires == variant.requires)
+            {
+                return Err(WriterError::DuplicateQualityVariant(of, variant.tier));
             }
+            entry.push(variant);
         }
     }
 
-    // Check that there's no circular dependencies
-    for header_a in headers {
-        for header_b in headers {
-            if header_a.id != header_b.id && header_a.dependencies.contains(&header_b.id) {
-                if header_b.dependencies.contains(&header_a.id) {
-                    return Err(WriterError::CircleDependency(
-                        header_a.id.clone(),
-                        header_b.id.clone(),
-                    ));
-                }
+    Ok(variants)
+}
+
+/// Checks the assets fit together. The failures are reported
+/// against the files of the user assets the `sources` map the assets to.
+fn sanity_check(
+    headers: &[AssetHeader],
+    variants: &AssetVariants,
+    sources: &HashMap<AssetID, PathBuf>,
+) -> Result<(), WriterError> {
+    let mut report = BuildReport::default();
+    let mut fail = |id: &AssetID, error: WriterError| {
+        let path = sources.get(id).map_or(Path::new(""), PathBuf::as_path);
+        report.add(path, id.clone(), error);
+    };
+
+    // Check that all dependencies are present. Depending on a logical asset is fine
+    for header in headers {
+        for dep in &header.dependencies {
+            if !headers.iter().any(|i| i.id == *dep)
+                && !variants.locales.contains_key(dep)
+                && !variants.quality.contains_key(dep)
+            {
+                fail(
+                    &header.id,
+                    WriterError::DependenciesMissing(header.id.clone(), dep.clone()),
+                );
             }
         }
     }
@@ -220,18 +392,349 @@ fn sanity_check(headers: &[AssetHeader]) -> Result<(), WriterError> {
     let mut ids = std::collections::HashSet::new();
     for ir in headers {
         if !ids.insert(ir.id.clone()) {
-            return Err(WriterError::NonUniqueID(ir.id.clone()));
+            fail(&ir.id, WriterError::NonUniqueID(ir.id.clone()));
         }
     }
+    report.into_result()?;
+
+    // Check that there's no circular dependencies, including the indirect ones
+    if let Some(cycle) = DependencyGraph::new(headers, variants).find_cycle() {
+        return Err(cycle.into());
+    }
 
     Ok(())
 }
 
-pub fn write_from_directory<W: Write>(
+impl UserAssetFile {
+    /// Total size of the source files. Used to start the heavy assets first.
+    /// Not yet downloaded sources are counted as empty.
+    fn estimated_cost(&self, cache_dir: &Path, cwd: &Path) -> u64 {
+        self.asset
+            .properties
+            .sources()
+            .into_iter()
+            .filter_map(|source| source.as_path(cache_dir, cwd).ok())
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+
+    /// Other assets the conversion reads (see `UserCustomAsset::inputs`).
+    /// Only the custom converters bake from the other assets.
+    fn inputs(&self, converters: &ConverterRegistry) -> Vec<AssetID> {
+        let UserAssetProperties::Custom(custom) = &self.asset.properties else {
+            return vec![];
+        };
+        let mut inputs = custom.inputs.clone();
+        if let Ok(converter) = converters.get(&custom.asset_type) {
+            for input in converter.inputs(&custom.params) {
+                if !inputs.contains(&input) {
+                    inputs.push(input);
+                }
+            }
+        }
+        inputs
+    }
+}
+
+/// Runs the jobs in the current pool, starting them in the given order.
+/// Unlike `par_iter`, that splits the input in halves, the first jobs are started first.
+/// Results are returned in the order of the jobs.
+fn run_prioritized<T, R, F>(jobs: Vec<T>, f: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    let results: Vec<Mutex<Option<R>>> = jobs.iter().map(|_| Mutex::new(None)).collect();
+    rayon::scope_fifo(|scope| {
+        for (job, slot) in jobs.into_iter().zip(&results) {
+            let f = &f;
+            scope.spawn_fifo(move |_| *slot.lock().unwrap() = Some(f(job)));
+        }
+    });
+
+    results
+        .into_iter()
+        .map(|slot| slot.into_inner().unwrap().unwrap())
+        .collect()
+}
+
+/// Binaries of the asset, collected until the last one is ready,
+/// since they are cached together.
+struct PendingAsset {
+    remaining: usize,
+    binaries: Vec<Option<BinaryAsset>>,
+}
+
+/// Converts the user assets to binaries in two flat stages: user assets to IRs
+/// and then every IR to binary, so the large assets with many IRs
+/// are spread over the whole pool. Largest jobs are started first,
+/// since they are the critical path of the build.
+/// The binaries are sent to the container writer as soon as the asset is complete.
+///
+/// The failed assets don't stop the build, their failures are collected
+/// into the `BuildReport`. Returns the files of the user assets
+/// the written assets come from.
+fn convert_user_assets(
+    user_assets: &[UserAssetFile],
+    cache: &Cache,
+    config: &WriteConfig,
+    input_dir: &Path,
+    sink: SyncSender<(usize, BinaryAsset)>,
+) -> Result<HashMap<AssetID, PathBuf>, WriterError> {
+    let sources = Mutex::new(HashMap::new());
+    let send = |i: usize, binaries: Vec<BinaryAsset>| -> Result<(), WriterError> {
+        let path = &user_assets[i].path;
+        for binary in binaries {
+            (sources.lock().unwrap()).insert(binary.header.id.clone(), path.clone());
+            sink.send((i, binary))
+                .map_err(|_| WriterError::ContainerWriterStopped)?;
+        }
+        Ok(())
+    };
+
+    let mut order = (0..user_assets.len())
+        .map(|i| {
+            let cost = user_assets[i].estimated_cost(&config.cache_dir, input_dir);
+            (i, cost)
+        })
+        .collect::<Vec<_>>();
+    order.sort_by_key(|(_, cost)| Reverse(*cost));
+
+    let index: UserAssetIndex = user_assets
+        .iter()
+        .map(|asset| (normalize_name(asset.path.clone()), asset))
+        .collect();
+
+    let converted = run_prioritized(order, |(i, _)| {
+        let user_asset = &user_assets[i];
+        if let Some(cached) = cache.get(user_asset, &index) {
+            return (i, send(i, cached).map(|_| None));
+        }
+
+        let instant = std::time::Instant::now();
+        let irs = user_asset
+            .convert(input_dir, config, &index)
+            .map_err(WriterError::ConversionFailed);
+        debug!("Converted {:?} in {:?}", user_asset.path, instant.elapsed());
+        (i, irs.map(Some))
+    });
+
+    let mut report = BuildReport::default();
+    let mut fail = |i: usize, id: AssetID, error: WriterError| match error {
+        // Not the asset's fault, the build can't go on
+        WriterError::ContainerWriterStopped => Err(error),
+        error => {
+            report.add(&user_assets[i].path, id, error);
+            Ok(())
+        }
+    };
+
+    let mut converted_irs: Vec<Option<Vec<UserIRAsset>>> =
+        user_assets.iter().map(|_| None).collect();
+    for (i, result) in converted {
+        match result {
+            Ok(irs) => converted_irs[i] = irs,
+            Err(e) => fail(i, normalize_name(user_assets[i].path.clone()), e)?,
+        }
+    }
+
+    // Queue the IRs of all assets at once
+    let mut jobs = Vec::new();
+    let mut pending = Vec::with_capacity(user_assets.len());
+    for (i, irs) in converted_irs.into_iter().enumerate() {
+        let irs = match irs {
+            Some(irs) if irs.is_empty() => {
+                if let Err(e) = cache.insert(&user_assets[i], &index, &Vec::new()) {
+                    fail(i, normalize_name(user_assets[i].path.clone()), e)?;
+                }
+                Vec::new()
+            }
+            Some(irs) => irs,
+            // Already sent from the cache
+            None => Vec::new(),
+        };
+        pending.push(Mutex::new(PendingAsset {
+            remaining: irs.len(),
+            binaries: irs.iter().map(|_| None).collect(),
+        }));
+        jobs.extend(irs.into_iter().enumerate().map(|(j, ir)| (i, j, ir)));
+    }
+    jobs.sort_by_key(|(_, _, ir)| Reverse(ir.ir.memory_usage()));
+
+    let results = run_prioritized(jobs, |(i, j, ir)| {
+        let id = ir.header.id.clone();
+        let binary = match ir.convert(config) {
+            Ok(binary) => binary,
+            // The other binaries of the asset are never completed and sent
+            Err(e) => return (i, id, Err(e)),
+        };
+        drop(ir);
+
+        let completed = {
+            let mut asset = pending[i].lock().unwrap();
+            asset.binaries[j] = Some(binary);
+            asset.remaining -= 1;
+            (asset.remaining == 0).then(|| asset.binaries.drain(..).flatten().collect())
+        };
+        let result = match completed {
+            Some(binaries) => cache
+                .insert(&user_assets[i], &index, &binaries)
+                .and_then(|_| send(i, binaries)),
+            None => Ok(()),
+        };
+        (i, id, result)
+    });
+
+    for (i, id, result) in results {
+        if let Err(e) = result {
+            fail(i, id, e)?;
+        }
+    }
+    report.into_result()?;
+    Ok(sources.into_inner().unwrap())
+}
+
+fn container_options(config: &WriteConfig) -> ContainerOptions {
+    ContainerOptions {
+        chunking: config.chunking,
+        signing_key: config.signing_key.clone(),
+        encryption: config.encryption.clone(),
+    }
+}
+
+pub fn write_from_directory<W: Write + Seek>(
     writer: &mut W,
     input_dir: PathBuf,
     config: WriteConfig,
 ) -> Result<(), WriterError> {
+    info!("Creating DAC container");
+    let container = ContainerWriter::new(writer, container_options(&config))?;
+    let open = |_: &Bundles| Ok(vec![container]);
+    write_assets(open, false, &HashMap::new(), input_dir, config)?;
+    Ok(())
+}
+
+/// Splits the assets into the bundles and writes each one into its own
+/// container in the `output_dir`: `base.dac` and the ones named after the last
+/// part of the bundle path, e.g. `level1.dac` for the assets declaring
+/// `bundle = "levels/level1"` in the header of their TOML, or selected
+/// by the `WriteConfig::bundles` rules. The rest goes to the base bundle.
+/// Meant for the DLCs and the streaming installs, the bundles are shipped
+/// and loaded separately.
+///
+/// The assets may depend on the ones of the base bundle, of their own bundle
+/// and of the bundles it's nested into (e.g. `levels`), since these are
+/// installed with it. Anything else fails the build.
+/// The generated assets (e.g. the materials of the glTF scene) go to the bundle
+/// of their user asset. All the variants of the logical asset must be in one bundle.
+///
+/// Returns the paths of the written containers, the base one first.
+/// The containers of the bundles that are gone are not removed.
+pub fn write_bundles(
+    output_dir: &Path,
+    input_dir: PathBuf,
+    config: WriteConfig,
+) -> Result<Vec<PathBuf>, WriterError> {
+    std::fs::create_dir_all(output_dir)?;
+    let mut paths = Vec::new();
+    let open = |bundles: &Bundles| {
+        let mut files: HashMap<String, &str> = HashMap::new();
+        let mut containers = Vec::with_capacity(bundles.names.len());
+        for (i, name) in bundles.names.iter().enumerate() {
+            let file_name = bundles.file_name(i);
+            if let Some(other) = files.insert(file_name.clone(), name) {
+                return Err(WriterError::BundleFileClash(
+                    other.to_string(),
+                    name.clone(),
+                ));
+            }
+            let path = output_dir.join(file_name);
+            info!(
+                "Creating DAC container {} for bundle {}",
+                path.display(),
+                name
+            );
+            let file = File::create(&path)?;
+            containers.push(ContainerWriter::new(file, container_options(&config))?);
+            paths.push(path);
+        }
+        Ok(containers)
+    };
+    write_assets(open, true, &HashMap::new(), input_dir, config.clone())?;
+    Ok(paths)
+}
+
+/// Incremental counterpart of `write_from_directory`. Reopens the container
+/// written before and writes only the assets whose payloads have changed
+/// since, comparing their checksums with the ones in the old manifest.
+/// Unchanged payloads are left in place, and with the cache the unchanged
+/// assets are not converted at all.
+///
+/// Payloads of the changed and the removed assets stay in the file as the dead
+/// space, so rebuild the container with `write_from_directory` once in a while,
+/// e.g. for the release. Kept payloads are not recompressed or rechunked
+/// after the compression, the chunking or the encryption settings change.
+/// Falls back to the full rebuild if the file is missing or can't be updated.
+pub fn update_container(
+    path: &Path,
+    input_dir: PathBuf,
+    config: WriteConfig,
+) -> Result<(), WriterError> {
+    let mut file = match File::options().read(true).write(true).open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            info!("Container {} not found, creating it", path.display());
+            let mut file = File::create(path)?;
+            return write_from_directory(&mut file, input_dir, config);
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let reopened = read_manifest(&mut file).and_then(|manifest| {
+        let container = ContainerWriter::append(&mut file, container_options(&config))?;
+        Ok((manifest, container))
+    });
+    let (manifest, container) = match reopened {
+        Ok(reopened) => reopened,
+        Err(e) => {
+            warn!("Rebuilding container {}: {}", path.display(), e);
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+            return write_from_directory(&mut file, input_dir, config);
+        }
+    };
+
+    info!("Updating DAC container {}", path.display());
+    // Assets packed before the checksums were calculated are always written
+    let previous = manifest
+        .headers
+        .into_iter()
+        .filter(|header| header.checksum != AssetChecksum::default())
+        .map(|header| (header.id, header.checksum))
+        .collect();
+    let open = |_: &Bundles| Ok(vec![container]);
+    let mut files = write_assets(open, false, &previous, input_dir, config)?;
+    let file = files.pop().expect("One container is written");
+
+    // The new control segments may be shorter than the old ones
+    let end = file.stream_position()?;
+    file.set_len(end)?;
+    Ok(())
+}
+
+/// Converts the user assets and writes them to the containers `open` returns,
+/// one per bundle (see `write_bundles`). Without the `split`, all the assets
+/// go to the single container. The assets whose checksums match
+/// the `previous` ones are kept from the reopened container.
+fn write_assets<W: Write + Seek>(
+    open: impl FnOnce(&Bundles) -> Result<Vec<ContainerWriter<W>>, WriterError>,
+    split: bool,
+    previous: &HashMap<AssetID, AssetChecksum>,
+    input_dir: PathBuf,
+    config: WriteConfig,
+) -> Result<Vec<W>, WriterError> {
     let input_files = collect_files(input_dir.clone(), config.read_mode)?;
 
     let cache = Cache::new(
@@ -241,130 +744,516 @@ pub fn write_from_directory<W: Write>(
         config.checksum_algorithm,
     );
     let user_assets = collect_user_assets(&input_files)?;
+    let bundles = if split {
+        Bundles::split(&user_assets, &config.bundles)
+    } else {
+        Bundles::single(&user_assets)
+    };
+    let mut containers = open(&bundles)?;
 
-    debug!("Converting User Assets");
-    let binaries = user_assets
-        .par_iter()
-        .map(|user_asset| {
-            if let Some(cached) = cache.get(&user_asset) {
-                Ok(cached)
-            } else {
-                let user_clone = user_asset.clone();
-
-                let instant = std::time::Instant::now();
-                let irs = user_asset
-                    .convert(
-                        config.cache_dir.as_path(),
-                        input_dir.as_path(),
-                        config.checksum_algorithm.clone(),
-                    )
-                    .map_err(|e| WriterError::ConvertingToIRFailed(user_asset.path.clone(), e))?;
-                debug!("Converted {:?} in {:?}", user_asset.path, instant.elapsed());
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(config.threads.unwrap_or(0))
+        .thread_name(|i| format!("dacgen-{}", i))
+        .build()?;
+    debug!(
+        "Converting User Assets on {} threads",
+        pool.current_num_threads()
+    );
 
-                let binaries = irs
-                    .par_iter()
-                    .map(|ir| ir.convert(config.compression_level.clone()))
-                    .collect::<Result<Vec<BinaryAsset>, WriterError>>()?;
+    // Keep only a few binaries in flight between the converters and the writer
+    let mut kept = 0;
+    let (sender, receiver) = sync_channel(pool.current_num_threads() * 2);
+    let (written, converted) = std::thread::scope(|scope| {
+        let converter = scope.spawn(|| {
+            pool.install(|| convert_user_assets(&user_assets, &cache, &config, &input_dir, sender))
+        });
 
-                cache.insert(&user_clone, &binaries)?;
-                Ok(binaries)
+        let written = receiver.iter().try_for_each(|(i, binary)| {
+            let container = &mut containers[bundles.of(i)];
+            let unchanged = previous.get(&binary.header.id) == Some(&binary.header.checksum);
+            if unchanged && container.keep(&binary.header)? {
+                kept += 1;
+                return Ok(());
             }
-        })
-        .collect::<Result<Vec<Vec<BinaryAsset>>, WriterError>>()?
-        .into_iter()
-        .flatten()
-        .collect::<Vec<BinaryAsset>>();
+            container.add(binary)
+        });
+        // Unblock the converters if the writer failed
+        drop(receiver);
+        let converted = converter.join().expect("Converter thread panicked");
+        (written, converted)
+    });
+    let sources = match (written, converted) {
+        // The converters fail to send only if the writer has failed
+        (Err(e), _) => return Err(e.into()),
+        (_, Err(e)) => return Err(e),
+        (_, Ok(sources)) => sources,
+    };
 
-    debug!("Collected {} binaries", binaries.len());
-    let headers = binaries
+    // Writing order depends on the scheduling, keep the manifest stable
+    let bundle_headers = containers
         .iter()
-        .map(|b| b.header.clone())
+        .map(|container| {
+            let mut headers = container.headers().to_vec();
+            headers.sort_by(|a, b| a.id.as_str().cmp(b.id.as_str()));
+            headers
+        })
         .collect::<Vec<_>>();
+    let headers = bundle_headers.concat();
+    debug!("Collected {} binaries", headers.len());
+    if !previous.is_empty() {
+        info!("Kept {} unchanged assets in place", kept);
+    }
 
-    sanity_check(&headers)?;
-
-    let manifest = create_manifest(&config, headers);
+    // The assets are checked together, as they are loaded
+    let variants = collect_variants(&user_assets, &headers, &config.downscale)?;
+    sanity_check(&headers, &variants, &sources)?;
+    let assigned = bundles.assign(&bundle_headers, &variants, &sources)?;
 
-    info!("Creating DAC container");
-    write_container(writer, manifest, binaries)?;
+    let guids = match &config.guid_lock {
+        Some(lock) => guids::assign_guids(lock, &user_assets, &headers, &config, &input_dir)?,
+        None => AssetGuids::default(),
+    };
 
-    Ok(())
+    let mut written = Vec::with_capacity(containers.len());
+    for (i, (container, headers)) in containers.into_iter().zip(bundle_headers).enumerate() {
+        let variants = bundle_variants(&variants, &assigned, i);
+        let guids = bundle_guids(&guids, &assigned, i);
+        let manifest = create_manifest(&config, headers, variants, guids);
+        written.push(container.finish(&manifest)?);
+    }
+    Ok(written)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{write_from_directory, WriteConfig};
-    use dawn_dac::reader::read_manifest;
+    use crate::audit::{audit, scan_references, AuditConfig};
+    use crate::config::BundleRule;
+    use crate::plugin::{AssetConverter, ConvertedAsset, ConverterInput};
+    use crate::{
+        create_manifest, run_prioritized, update_container, write_bundles, write_from_directory,
+        WriteConfig, WriterError,
+    };
+    use dawn_assets::ir::custom::{CustomAssetTag, IRCustom};
+    use dawn_assets::ir::IRAsset;
+    use dawn_assets::variants::{AssetVariants, LocaleVariants};
+    use dawn_assets::{AssetHeader, AssetID};
+    use dawn_dac::reader::{read_asset, read_manifest};
     use dawn_dac::{ChecksumAlgorithm, CompressionLevel, ReadMode};
+    use std::io::Cursor;
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// Scratch directory with the asset sources of a test.
+    /// Removed on drop, so a failed assertion does not leave it behind.
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("dacgen_{}_{}", name, std::process::id()));
+            // Left over from an aborted run
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(path.join("sources")).unwrap();
+            TestDir(path)
+        }
+
+        fn path(&self) -> &Path {
+            &self.0
+        }
+
+        fn sources(&self) -> PathBuf {
+            self.0.join("sources")
+        }
+
+        /// Writes the metadata of a custom asset with the extra `header` lines
+        /// and the `body` of its properties.
+        fn custom(&self, name: &str, header: &str, body: &str) {
+            let toml = format!(
+                "[header]\nasset_type = \"Unknown\"\n{header}\n\n[properties.Custom]\n{body}\n"
+            );
+            std::fs::write(self.sources().join(format!("{name}.toml")), toml).unwrap();
+        }
+
+        /// Writes a [`Blob`] asset converted from `name.bin` holding `data`.
+        fn blob(&self, name: &str, header: &str, data: impl AsRef<[u8]>) {
+            let body =
+                format!("type = \"blob\"\nsources = [{{ File = \"{name}.bin\" }}]\nparams = {{}}");
+            self.custom(name, header, &body);
+            std::fs::write(self.sources().join(format!("{name}.bin")), data).unwrap();
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Passes the first source through as a custom asset.
+    /// An empty source fails the conversion.
+    struct Blob;
+
+    impl AssetConverter for Blob {
+        fn convert(&self, input: ConverterInput) -> anyhow::Result<Vec<ConvertedAsset>> {
+            anyhow::ensure!(!input.sources[0].is_empty(), "empty source");
+            Ok(vec![ConvertedAsset {
+                id: None,
+                ir: IRAsset::Custom(IRCustom {
+                    tag: CustomAssetTag::new("test", "blob").unwrap(),
+                    data: input.sources[0].clone(),
+                }),
+                dependencies: Default::default(),
+            }])
+        }
+    }
+
+    /// Flat uncompressed container written on a single thread, cached in `dir`.
+    fn test_config(dir: &Path) -> WriteConfig {
+        WriteConfig {
+            read_mode: ReadMode::Flat,
+            checksum_algorithm: ChecksumAlgorithm::Blake3,
+            compression_level: CompressionLevel::None,
+            cache_dir: dir.join("cache"),
+            author: None,
+            description: None,
+            version: None,
+            license: None,
+            converters: Default::default(),
+            chunking: None,
+            signing_key: None,
+            encryption: None,
+            downscale: Default::default(),
+            conventions: Default::default(),
+            threads: Some(1),
+            compression_threads: None,
+            compression_block_size: None,
+            guid_lock: None,
+            bundles: vec![],
+        }
+    }
 
     #[test]
-    fn test() {
-        // Setup basic logging
-        struct Logger;
-        impl log::Log for Logger {
-            fn enabled(&self, metadata: &log::Metadata) -> bool {
-                metadata.level() <= log::Level::Debug
-            }
+    fn prioritized_jobs_start_in_order() {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
 
-            fn log(&self, record: &log::Record) {
-                println!("{}: {}", record.level(), record.args());
-            }
+        let started = Mutex::new(Vec::new());
+        let results = pool.install(|| {
+            run_prioritized((0..16).collect(), |i: usize| {
+                started.lock().unwrap().push(i);
+                i * 2
+            })
+        });
 
-            fn flush(&self) {}
-        }
-
-        log::set_logger(&Logger).unwrap();
-        log::set_max_level(log::LevelFilter::Debug);
-
-        // I'll deal with it later
-        #[cfg(unix)]
-        mod dirs {
-            // TODO: Do not commit me :(
-            pub const CURRENT_DIR: &str = "/home/taris/work/dawn/assets";
-            pub const OUTPUT_FILE: &str = "/tmp/cache/assets.dac";
-            pub const CACHE_DIR: &str = "/tmp/cache";
-        }
-        #[cfg(windows)]
-        mod dirs {
-            // TODO: Do not commit me :(
-            pub const CURRENT_DIR: &str = r"D:\coding\dawn\assets";
-            pub const OUTPUT_FILE: &str = r"D:\coding\cache\output.dac";
-            pub const CACHE_DIR: &str = r"D:\coding\cache\";
-        }
-
-        let file = std::fs::File::create(dirs::OUTPUT_FILE).unwrap();
-        let mut writer = std::io::BufWriter::new(file);
-        write_from_directory(
-            &mut writer,
-            dirs::CURRENT_DIR.into(),
-            WriteConfig {
-                read_mode: ReadMode::Recursive,
-                checksum_algorithm: ChecksumAlgorithm::Blake3,
-                compression_level: CompressionLevel::None,
-                cache_dir: dirs::CACHE_DIR.into(),
-                author: Some("Coestaris <vk_vm@ukr.net>".to_string()),
-                description: Some("Test assets".to_string()),
-                version: Some("0.1.0".to_string()),
-                license: Some("MIT".to_string()),
-            },
+        assert_eq!(started.into_inner().unwrap(), (0..16).collect::<Vec<_>>());
+        assert_eq!(results, (0..16).map(|i| i * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn audit_finds_missing_and_orphaned_assets() {
+        let dir = TestDir::new("audit");
+        std::fs::write(
+            dir.path().join("game.rs"),
+            "let level = AssetID::from(\"level\");\n\
+             let title = hub.get(\"title\");\n\
+             let broken = AssetID::from(\"lost\");\n\
+             println!(\"not an asset\");\n",
         )
         .unwrap();
-        drop(writer.into_inner().unwrap());
-
-        let file = std::fs::File::open(dirs::OUTPUT_FILE).unwrap();
-        let mut reader = std::io::BufReader::new(file);
-        let manifest = read_manifest(&mut reader).unwrap();
-        // println!("{:#?}", manifest);
-        // manifest.tree("sponza".into(), &|id, header, depth| {
-        //     println!(
-        //         "{}- {} (deps: {})",
-        //         "  ".repeat(depth),
-        //         id.as_str(),
-        //         header.dependencies.len()
-        //     );
-        // });
-
-        // let ir = read_asset(&mut reader, "barrel".into()).unwrap();
-        // println!("{:#?}", ir);
+        // Not scanned, the extension is not listed
+        std::fs::write(dir.path().join("notes.txt"), "AssetID::from(\"unused\")").unwrap();
+
+        let header = |id: &str, dependencies: &[&str]| {
+            let mut header = AssetHeader {
+                id: id.into(),
+                ..Default::default()
+            };
+            for dependency in dependencies {
+                header.dependencies.insert((*dependency).into());
+            }
+            header
+        };
+        let mut variants = AssetVariants::default();
+        variants.locales.insert(
+            "title".into(),
+            LocaleVariants {
+                default: "title_en".into(),
+                locales: [("uk".to_string(), "title_uk".into())].into(),
+            },
+        );
+        let manifest = create_manifest(
+            &test_config(dir.path()),
+            vec![
+                header("level", &["mesh"]),
+                header("mesh", &["texture"]),
+                header("texture", &[]),
+                header("title_en", &[]),
+                header("title_uk", &[]),
+                header("unused", &[]),
+                header("debug", &[]),
+            ],
+            variants,
+            Default::default(),
+        );
+
+        let config = AuditConfig {
+            roots: vec![dir.path().to_path_buf()],
+            ..Default::default()
+        };
+        let (references, files) = scan_references(&config).unwrap();
+        assert_eq!(files, 1);
+        let report = audit(&manifest, &references, &["debug".into()]);
+
+        assert_eq!(report.missing.len(), 1);
+        assert_eq!(report.missing[0].id, AssetID::from("lost"));
+        assert_eq!(report.missing[0].line, 3);
+        assert_eq!(report.orphaned, vec![AssetID::from("unused")]);
+        assert_eq!(report.used, 6);
+    }
+
+    #[test]
+    fn update_writes_only_changed_assets() {
+        let dir = TestDir::new("update");
+        let sources = dir.sources();
+        let rock = vec![1; 4096];
+        dir.blob("rock", "", &rock);
+        dir.blob("tree", "", [2; 4096]);
+
+        let container = dir.path().join("assets.dac");
+        let update = || {
+            let mut config = test_config(dir.path());
+            config.converters.register("blob", Blob);
+            update_container(&container, sources.clone(), config).unwrap();
+            std::fs::read(&container).unwrap()
+        };
+        let find = |data: &[u8], pattern: &[u8]| {
+            data.windows(pattern.len())
+                .position(|window| window == pattern)
+                .unwrap()
+        };
+
+        let first = update();
+        let tree = vec![3; 4096];
+        dir.blob("tree", "", &tree);
+        dir.blob("bush", "", [4; 4096]);
+        let updated = update();
+
+        // The unchanged payload stays in place, the changed one is appended
+        assert_eq!(find(&updated, &rock), find(&first, &rock));
+        assert!(find(&updated, &tree) > find(&first, &rock));
+        let manifest = read_manifest(&mut Cursor::new(&updated)).unwrap();
+        assert_eq!(manifest.headers.len(), 3);
+        let read = |id: &str| match read_asset(&mut Cursor::new(&updated), id.into()).unwrap() {
+            IRAsset::Custom(custom) => custom.data,
+            _ => unreachable!(),
+        };
+        assert_eq!(read("rock"), rock);
+        assert_eq!(read("tree"), tree);
+        assert_eq!(read("bush"), vec![4; 4096]);
+
+        // Nothing changed, nothing is written
+        assert_eq!(update().len(), updated.len());
+    }
+
+    #[test]
+    fn guids_survive_renames() {
+        let dir = TestDir::new("guids");
+        let sources = dir.sources();
+        dir.blob("rock", "", "rock");
+        dir.blob("tree", "", "tree");
+
+        let write = || {
+            let mut config = test_config(dir.path());
+            config.converters.register("blob", Blob);
+            config.guid_lock = Some(dir.path().join("assets.lock"));
+            let mut output = Cursor::new(Vec::new());
+            write_from_directory(&mut output, sources.clone(), config).unwrap();
+            output.set_position(0);
+            read_manifest(&mut output).unwrap().guids
+        };
+
+        let first = write();
+        let rock = first.guid(&"rock".into()).unwrap();
+        let tree = first.guid(&"tree".into()).unwrap();
+        assert_ne!(rock, tree);
+        assert_eq!(write(), first);
+
+        // The same contents under the new name keep the GUID
+        std::fs::rename(sources.join("rock.toml"), sources.join("stone.toml")).unwrap();
+        dir.blob("bush", "", "bush");
+        let renamed = write();
+        assert_eq!(renamed.guid(&"stone".into()), Some(rock));
+        assert_eq!(renamed.guid(&"tree".into()), Some(tree));
+        assert_eq!(renamed.guid(&"rock".into()), None);
+        let bush = renamed.guid(&"bush".into()).unwrap();
+        assert!(bush != rock && bush != tree);
+        assert_eq!(renamed.resolve(&rock.to_id()), "stone".into());
+    }
+
+    #[test]
+    fn bundles_are_written_separately() {
+        let dir = TestDir::new("bundles");
+        let sources = dir.sources();
+        let blob = |name: &str, header: &str| dir.blob(name, header, name);
+        blob("rock", "");
+        blob("levels", "bundle = \"levels\"");
+        blob(
+            "tree",
+            "bundle = \"levels/level1\"\ndependencies = [\"rock\", \"levels\"]",
+        );
+        blob("bush", "tags = [\"dlc\"]");
+
+        let output = dir.path().join("output");
+        let write = || {
+            let mut config = test_config(dir.path());
+            config.converters.register("blob", Blob);
+            config.bundles = vec![BundleRule {
+                tag: "dlc".to_string(),
+                bundle: "dlc".to_string(),
+            }];
+            write_bundles(&output, sources.clone(), config)
+        };
+
+        let paths = write().unwrap();
+        let names = ["base.dac", "dlc.dac", "levels.dac", "level1.dac"];
+        assert_eq!(paths, names.map(|name| output.join(name)));
+        let ids = |name: &str| {
+            let mut file = std::fs::File::open(output.join(name)).unwrap();
+            let manifest = read_manifest(&mut file).unwrap();
+            manifest
+                .headers
+                .into_iter()
+                .map(|header| header.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("base.dac"), vec![AssetID::from("rock")]);
+        assert_eq!(ids("dlc.dac"), vec![AssetID::from("bush")]);
+        assert_eq!(ids("levels.dac"), vec![AssetID::from("levels")]);
+        assert_eq!(ids("level1.dac"), vec![AssetID::from("tree")]);
+
+        // The base bundle is installed without the level
+        blob("rock", "dependencies = [\"tree\"]");
+        blob("tree", "bundle = \"levels/level1\"");
+        let Err(WriterError::BuildFailed(report)) = write() else {
+            panic!("Build must fail");
+        };
+        let failures = report.file(&sources.join("rock.toml"));
+        assert!(matches!(
+            &failures[0].error,
+            WriterError::CrossBundleDependency(_, _, bundle) if bundle == "levels/level1"
+        ));
+    }
+
+    #[test]
+    fn failures_are_reported_by_file() {
+        let dir = TestDir::new("report");
+        let sources = dir.sources();
+        dir.blob("rock", "", "rock");
+        dir.blob("tree", "", "");
+        dir.blob("bush", "", "");
+
+        let write = || {
+            let mut config = test_config(dir.path());
+            config.converters.register("blob", Blob);
+            write_from_directory(&mut Cursor::new(Vec::new()), sources.clone(), config)
+        };
+
+        // All the failed conversions are reported, not just the first one
+        let Err(WriterError::BuildFailed(report)) = write() else {
+            panic!("Build must fail");
+        };
+        assert_eq!(report.len(), 2);
+        let failures = report.file(&sources.join("tree.toml"));
+        assert_eq!(failures[0].id, AssetID::from("tree"));
+        assert!(matches!(
+            failures[0].error,
+            WriterError::ConversionFailed(_)
+        ));
+        assert!(report.file(&sources.join("rock.toml")).is_empty());
+        let text = report.to_string();
+        assert!(text.contains("tree.toml: error: [tree] Failed to convert: empty source"));
+
+        // Broken metadata is reported the same way
+        std::fs::write(sources.join("tree.toml"), "[header").unwrap();
+        dir.blob("bush", "", "bush");
+        let Err(WriterError::BuildFailed(report)) = write() else {
+            panic!("Build must fail");
+        };
+        assert_eq!(report.len(), 1);
+        let failures = report.file(&sources.join("tree.toml"));
+        assert!(matches!(failures[0].error, WriterError::MetadataError(_)));
+    }
+
+    #[test]
+    fn bake_is_invalidated_by_inputs() {
+        struct Bake(Arc<AtomicUsize>);
+        impl AssetConverter for Bake {
+            fn convert(&self, input: ConverterInput) -> anyhow::Result<Vec<ConvertedAsset>> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                let data = input.inputs.into_values().flatten().flatten().collect();
+                Ok(vec![ConvertedAsset {
+                    id: None,
+                    ir: IRAsset::Custom(IRCustom {
+                        tag: CustomAssetTag::new("test", "bake").unwrap(),
+                        data,
+                    }),
+                    dependencies: Default::default(),
+                }])
+            }
+
+            // The mesh is named in the params, the lights are listed in the TOML
+            fn inputs(&self, params: &toml::Table) -> Vec<AssetID> {
+                params
+                    .get("mesh")
+                    .and_then(|mesh| mesh.as_str())
+                    .map(AssetID::from)
+                    .into_iter()
+                    .collect()
+            }
+        }
+
+        let dir = TestDir::new("bake");
+        let sources = dir.sources();
+        dir.custom(
+            "lightmap",
+            "",
+            "type = \"bake\"\ninputs = [\"lights\"]\nparams = { mesh = \"room\" }",
+        );
+        dir.custom(
+            "lights",
+            "",
+            "type = \"bake\"\nparams = { mesh = \"room\" }",
+        );
+        dir.custom(
+            "room",
+            "",
+            "type = \"bake\"\nsources = [{ File = \"room.bin\" }]\nparams = { mesh = \"geometry\" }",
+        );
+        dir.custom(
+            "geometry",
+            "",
+            "type = \"bake\"\nsources = [{ File = \"geometry.bin\" }]\nparams = {}",
+        );
+        std::fs::write(sources.join("room.bin"), [1]).unwrap();
+        std::fs::write(sources.join("geometry.bin"), [2]).unwrap();
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let write = || {
+            let mut config = test_config(dir.path());
+            config.converters.register("bake", Bake(runs.clone()));
+            write_from_directory(&mut Cursor::new(Vec::new()), sources.clone(), config).unwrap();
+            runs.swap(0, Ordering::SeqCst)
+        };
+
+        assert_eq!(write(), 4);
+        assert_eq!(write(), 0);
+        // Read by the room, which is read by both the lights and the lightmap
+        std::fs::write(sources.join("geometry.bin"), [3]).unwrap();
+        assert_eq!(write(), 4);
+        std::fs::write(sources.join("room.bin"), [4]).unwrap();
+        assert_eq!(write(), 3);
+        assert_eq!(write(), 0);
     }
 }
@@ -0,0 +1,124 @@
+use crate::deep_hash::{DeepHash, DeepHashCtx};
+use dawn_assets::ir::IRAsset;
+use dawn_assets::AssetID;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Input passed to the custom converter.
+pub struct ConverterInput<'a> {
+    /// ID the asset would get if the converter produces a single IR asset.
+    /// Derived from the TOML file name.
+    pub id: AssetID,
+    /// Raw content of the sources listed in the TOML, in the same order.
+    pub sources: Vec<Vec<u8>>,
+    /// User-defined parameters from the `params` table of the TOML.
+    pub params: &'a toml::Table,
+    /// Raw content of the sources of the input assets (see `AssetConverter::inputs`),
+    /// keyed by their IDs.
+    pub inputs: HashMap<AssetID, Vec<Vec<u8>>>,
+}
+
+/// Single IR asset produced by the custom converter.
+#[derive(Debug)]
+pub struct ConvertedAsset {
+    /// ID of the produced asset. If `None`, the ID from `ConverterInput` is used.
+    /// Converters producing several assets must provide unique IDs.
+    pub id: Option<AssetID>,
+    pub ir: IRAsset,
+    /// Dependencies in addition to the ones declared in the TOML header.
+    pub dependencies: HashSet<AssetID>,
+}
+
+/// Converter of a project-specific asset format into the IR.
+/// Converters are registered by the `type` string used in the TOML:
+///
+/// ```toml
+/// [properties.Custom]
+/// type = "dialogue"
+/// sources = [{ File = "intro.json" }]
+/// params = { speaker = "narrator" }
+/// ```
+///
+/// Converters baking the data from other assets (lightmaps, probes, navmeshes)
+/// list them as the inputs, so the bake is redone when any of them changes:
+///
+/// ```toml
+/// [properties.Custom]
+/// type = "lightmap"
+/// inputs = ["sponza", "sun_lights"]
+/// ```
+pub trait AssetConverter: Send + Sync {
+    fn convert(&self, input: ConverterInput) -> anyhow::Result<Vec<ConvertedAsset>>;
+
+    /// Version of the converter. Bump it to invalidate the cached results
+    /// when the conversion logic changes.
+    fn version(&self) -> u32 {
+        0
+    }
+
+    /// Assets read by the converter in addition to the `inputs` listed in the TOML,
+    /// e.g. the scene meshes named in the params of the bake. The result is
+    /// reconverted when any of the input assets changes, not only the sources.
+    fn inputs(&self, _params: &toml::Table) -> Vec<AssetID> {
+        vec![]
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ConverterError {
+    #[error("No converter registered for asset type '{0}'")]
+    NotRegistered(String),
+    #[error("Converter for asset type '{0}' produced several assets without IDs")]
+    AmbiguousID(String),
+    #[error("Input asset {0} not found")]
+    InputNotFound(AssetID),
+}
+
+/// Collection of the custom converters, keyed by the asset type string.
+#[derive(Clone, Default)]
+pub struct ConverterRegistry(HashMap<String, Arc<dyn AssetConverter>>);
+
+impl ConverterRegistry {
+    pub fn new() -> Self {
+        ConverterRegistry(HashMap::new())
+    }
+
+    /// Registers a converter for the given asset type.
+    /// Replaces the previously registered converter for the same type.
+    pub fn register(
+        &mut self,
+        asset_type: impl Into<String>,
+        converter: impl AssetConverter + 'static,
+    ) {
+        self.0.insert(asset_type.into(), Arc::new(converter));
+    }
+
+    pub fn get(&self, asset_type: &str) -> Result<&dyn AssetConverter, ConverterError> {
+        self.0
+            .get(asset_type)
+            .map(|c| c.as_ref())
+            .ok_or_else(|| ConverterError::NotRegistered(asset_type.to_string()))
+    }
+}
+
+impl Debug for ConverterRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
+    }
+}
+
+impl DeepHash for ConverterRegistry {
+    fn deep_hash<T: Hasher>(&self, state: &mut T, _ctx: &mut DeepHashCtx) -> anyhow::Result<()> {
+        // Changing the set of converters or their versions must invalidate the cache
+        let mut keys: Vec<&String> = self.0.keys().collect();
+        keys.sort();
+        for key in keys {
+            key.hash(state);
+            self.0[key].version().hash(state);
+        }
+        Ok(())
+    }
+}
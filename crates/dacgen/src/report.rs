@@ -0,0 +1,72 @@
+use crate::WriterError;
+use dawn_assets::AssetID;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// Asset that failed to build.
+#[derive(Debug)]
+pub struct AssetFailure {
+    /// ID of the asset, or of the user asset if it failed before producing any.
+    pub id: AssetID,
+    pub error: WriterError,
+}
+
+/// Failures of the build, grouped by the metadata file of the user asset.
+/// The build goes on after the asset fails, so all the broken assets
+/// are reported at once (see `WriterError::BuildFailed`).
+///
+/// Displayed one failure per line as `path: error: [id] message`,
+/// the format the IDEs and the CI annotations pick up.
+#[derive(Debug, Default)]
+pub struct BuildReport {
+    pub failures: BTreeMap<PathBuf, Vec<AssetFailure>>,
+}
+
+impl BuildReport {
+    pub fn add(&mut self, path: &Path, id: AssetID, error: WriterError) {
+        self.failures
+            .entry(path.to_path_buf())
+            .or_default()
+            .push(AssetFailure { id, error });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Number of the failures in all the files.
+    pub fn len(&self) -> usize {
+        self.failures.values().map(Vec::len).sum()
+    }
+
+    /// Failures of the assets described by the file.
+    pub fn file(&self, path: &Path) -> &[AssetFailure] {
+        self.failures.get(path).map_or(&[], Vec::as_slice)
+    }
+
+    pub(crate) fn into_result(self) -> Result<(), WriterError> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(WriterError::BuildFailed(self))
+        }
+    }
+}
+
+impl Display for BuildReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (path, failures) in &self.failures {
+            for failure in failures {
+                writeln!(
+                    f,
+                    "{}: error: [{}] {}",
+                    path.display(),
+                    failure.id.as_str(),
+                    failure.error
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -8,40 +8,13 @@ use std::path::{Path, PathBuf};
 use thiserror::Error;
 use url::Url;
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub enum CachePolicy {
+    #[default]
     UseCache,
     Bypass,
 }
 
-impl Default for CachePolicy {
-    fn default() -> Self {
-        CachePolicy::UseCache
-    }
-}
-
-pub struct SerializedURL(Url);
-
-impl Serialize for SerializedURL {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        serializer.serialize_str(self.0.as_str())
-    }
-}
-
-impl<'de> Deserialize<'de> for SerializedURL {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
-    where
-        D: serde::Deserializer<'de>,
-    {
-        let s = String::deserialize(deserializer)?;
-        let url = Url::parse(&s).map_err(serde::de::Error::custom)?;
-        Ok(SerializedURL(url))
-    }
-}
-
 #[derive(Debug, Error)]
 pub enum SourceError {
     #[error("IO error: {0}")]
@@ -1,12 +1,50 @@
 use crate::deep_hash::{with_std, DeepHash, DeepHashCtx};
 use crate::source::SourceRef;
+use dawn_assets::ir::music::{IRMusicSection, IRMusicTransition};
 use dawn_assets::ir::shader::IRShaderSourceKind;
 use dawn_assets::ir::texture::{IRPixelFormat, IRTextureFilter, IRTextureType, IRTextureWrap};
+use dawn_assets::units::Conventions;
+use dawn_assets::variants::QualityTier;
 use dawn_assets::{AssetID, AssetType};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 use std::hash::{Hash, Hasher};
 
+/// Declares the asset as a locale variant of the logical asset:
+///
+/// ```toml
+/// [header.locale]
+/// of = "dialogue"
+/// locale = "en"
+/// default = true
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UserLocaleVariant {
+    /// ID of the logical asset. Must not clash with any real asset ID.
+    pub of: AssetID,
+    pub locale: String,
+    /// Exactly one variant of the logical asset must be the default one.
+    #[serde(default)]
+    pub default: bool,
+}
+
+/// Declares the asset as a quality or platform variant of the logical asset:
+///
+/// ```toml
+/// [header.quality]
+/// of = "wood"
+/// tier = "High"
+/// requires = "astc"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UserQualityVariant {
+    /// ID of the logical asset. Must not clash with any real asset ID.
+    pub of: AssetID,
+    pub tier: QualityTier,
+    /// Platform feature the variant requires (see `DeviceProfile`).
+    pub requires: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 pub struct UserAssetHeader {
     pub asset_type: AssetType,
@@ -16,6 +54,14 @@ pub struct UserAssetHeader {
     pub tags: Vec<String>,
     pub author: Option<String>,
     pub license: Option<String>,
+    #[serde(default)]
+    pub locale: Option<UserLocaleVariant>,
+    #[serde(default)]
+    pub quality: Option<UserQualityVariant>,
+    /// Bundle the asset is packed into by `write_bundles`, e.g. `levels/level1`.
+    /// `None` leaves it to the `WriteConfig::bundles` rules.
+    #[serde(default)]
+    pub bundle: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -63,12 +109,38 @@ pub(crate) struct UserAudioAsset {
     pub sample_rate: u32,
     pub channels: u8,
     pub source: SourceRef,
+    /// Duration of the chunks in milliseconds. If set, the audio is packed
+    /// into the independently compressed chunks, so it can be streamed
+    /// and seeked without decoding from the start (see `IRAudio::pack`).
+    #[serde(default)]
+    pub chunk_duration: Option<u32>,
+    /// Keep the Ogg Vorbis or FLAC source encoded and decode it while playing
+    /// with the `StreamSource`, for the long music tracks. Otherwise the
+    /// source is decoded into the samples when building.
+    #[serde(default)]
+    pub streamed: bool,
+    /// Captions asset of the clip, see `UserCaptionsAsset`.
+    #[serde(default)]
+    pub captions: Option<AssetID>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub(crate) struct UserMeshAsset {
     pub source: SourceRef,
     pub gen_material: bool,
+    /// Merge the submeshes sharing the material into a single draw at load.
+    /// Meant for the static level geometry.
+    #[serde(default)]
+    pub static_batching: bool,
+    /// Build the hierarchy over the triangles for the precise picking
+    /// and the collision cooking. Costs 36 bytes per triangle.
+    #[serde(default)]
+    pub bvh: bool,
+    /// Axes and the unit the source was authored in, when the file declares them
+    /// wrong or not at all, e.g. `{ up = "+Z", forward = "-Y", unit = "Centimeter" }`.
+    /// Defaults to the `conventions` of the write config for the source format.
+    #[serde(default)]
+    pub conventions: Option<Conventions>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -183,6 +255,87 @@ pub(crate) struct UserFontAsset {
     pub italic: bool,
 }
 
+fn default_intensity_fade() -> f32 {
+    1.0
+}
+
+/// Adaptive music built from the audio assets:
+///
+/// ```toml
+/// [properties.Music]
+/// bpm = 120.0
+/// beats_per_bar = 4
+///
+/// [[properties.Music.sections]]
+/// name = "explore"
+/// looped = true
+/// layers = [
+///     { stem = "explore_pads", fade_in_start = 0.0, fade_in_end = 0.0 },
+///     { stem = "explore_drums", fade_in_start = 0.3, fade_in_end = 0.6 },
+/// ]
+///
+/// [[properties.Music.transitions]]
+/// to = "combat"
+/// quantize = "Beat"
+/// crossfade = 0.25
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct UserMusicAsset {
+    pub bpm: f32,
+    pub beats_per_bar: u32,
+    pub sections: Vec<IRMusicSection>,
+    #[serde(default)]
+    pub transitions: Vec<IRMusicTransition>,
+    #[serde(default = "default_intensity_fade")]
+    pub intensity_fade: f32,
+}
+
+/// Captions of the audio clip, converted from the SubRip (`.srt`)
+/// or the WebVTT (`.vtt`) file. The format is detected by the content.
+/// The speakers are taken from the voice spans (`<v Name>`),
+/// the other markup is stripped. Referenced by the audio asset:
+///
+/// ```toml
+/// [properties.Audio]
+/// captions = "intro_captions"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UserCaptionsAsset {
+    pub source: SourceRef,
+}
+
+/// Named values, e.g. the render settings preset of the level. The nested
+/// tables are flattened into the dotted keys, the arrays must be numeric:
+///
+/// ```toml
+/// [properties.Settings.values]
+/// fog = { density = 0.02, color = [0.6, 0.7, 0.8] }
+/// bloom.intensity = 0.8
+/// shadows.resolution = 2048
+/// tonemap.curve = "aces"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct UserSettingsAsset {
+    pub values: toml::Table,
+}
+
+/// Asset converted by a converter registered in the `ConverterRegistry`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub(crate) struct UserCustomAsset {
+    #[serde(rename = "type")]
+    pub asset_type: String,
+    #[serde(default)]
+    pub sources: Vec<SourceRef>,
+    #[serde(default)]
+    pub params: toml::Table,
+    /// Other assets the converter reads, by their IDs: e.g. the meshes and
+    /// the light setup the lightmap is baked from. The cached result is
+    /// invalidated when any of them (or any of their own inputs) changes.
+    /// Unlike the header dependencies, they are not loaded at runtime.
+    #[serde(default)]
+    pub inputs: Vec<AssetID>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum UserAssetProperties {
     Shader(UserShaderAsset),
@@ -191,6 +344,10 @@ pub enum UserAssetProperties {
     Material(UserMaterialAsset),
     Mesh(UserMeshAsset),
     Font(UserFontAsset),
+    Custom(UserCustomAsset),
+    Music(UserMusicAsset),
+    Captions(UserCaptionsAsset),
+    Settings(UserSettingsAsset),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -199,6 +356,39 @@ pub(crate) struct UserAsset {
     pub properties: UserAssetProperties,
 }
 
+impl UserAssetProperties {
+    /// External files the asset is converted from.
+    pub fn sources(&self) -> Vec<&SourceRef> {
+        match self {
+            UserAssetProperties::Shader(shader) => shader
+                .sources
+                .iter()
+                .filter_map(|source| match &source.origin {
+                    ShaderOrigin::External(source) => Some(source),
+                    ShaderOrigin::Inline { .. } => None,
+                })
+                .collect(),
+            UserAssetProperties::Texture(texture) => texture.sources.iter().collect(),
+            UserAssetProperties::Audio(audio) => vec![&audio.source],
+            UserAssetProperties::Material(material) => [
+                &material.base_color_texture,
+                &material.metallic_texture,
+                &material.roughness_texture,
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+            UserAssetProperties::Mesh(mesh) => vec![&mesh.source],
+            UserAssetProperties::Font(font) => vec![&font.source],
+            UserAssetProperties::Custom(custom) => custom.sources.iter().collect(),
+            // Stems are the assets, not the files
+            UserAssetProperties::Music(_) => vec![],
+            UserAssetProperties::Captions(captions) => vec![&captions.source],
+            UserAssetProperties::Settings(_) => vec![],
+        }
+    }
+}
+
 impl DeepHash for ShaderSource {
     fn deep_hash<T: Hasher>(&self, state: &mut T, ctx: &mut DeepHashCtx) -> anyhow::Result<()> {
         with_std(&self.kind, state);
@@ -244,6 +434,9 @@ impl DeepHash for UserAudioAsset {
         self.sample_rate.deep_hash(state, ctx)?;
         self.channels.deep_hash(state, ctx)?;
         self.source.deep_hash(state, ctx)?;
+        self.chunk_duration.deep_hash(state, ctx)?;
+        self.streamed.deep_hash(state, ctx)?;
+        self.captions.deep_hash(state, ctx)?;
         Ok(())
     }
 }
@@ -264,6 +457,17 @@ impl DeepHash for UserMeshAsset {
     fn deep_hash<T: Hasher>(&self, state: &mut T, ctx: &mut DeepHashCtx) -> anyhow::Result<()> {
         self.source.deep_hash(state, ctx)?;
         self.gen_material.deep_hash(state, ctx)?;
+        self.static_batching.deep_hash(state, ctx)?;
+        self.bvh.deep_hash(state, ctx)?;
+        self.conventions.deep_hash(state, ctx)?;
+        // The Unity import settings next to the FBX source
+        #[cfg(feature = "import_fbx")]
+        if let SourceRef::File(_) = &self.source {
+            let path = self.source.as_path(&ctx.cache_dir, &ctx.cwd)?;
+            if let Ok(meta) = std::fs::read(crate::ir::fbx::meta_path(&path)) {
+                meta.hash(state);
+            }
+        }
         Ok(())
     }
 }
@@ -289,6 +493,25 @@ impl DeepHash for UserFontAsset {
     }
 }
 
+impl DeepHash for UserCustomAsset {
+    fn deep_hash<T: Hasher>(&self, state: &mut T, ctx: &mut DeepHashCtx) -> anyhow::Result<()> {
+        self.asset_type.deep_hash(state, ctx)?;
+        self.sources.deep_hash(state, ctx)?;
+        // Table is ordered, so the serialized form is stable
+        toml::to_string(&self.params)?.deep_hash(state, ctx)?;
+        // The content of the inputs is hashed by the cache, it knows the other assets
+        self.inputs.deep_hash(state, ctx)?;
+        Ok(())
+    }
+}
+
+impl DeepHash for UserMusicAsset {
+    fn deep_hash<T: Hasher>(&self, state: &mut T, ctx: &mut DeepHashCtx) -> anyhow::Result<()> {
+        toml::to_string(self)?.deep_hash(state, ctx)?;
+        Ok(())
+    }
+}
+
 impl DeepHash for UserAssetProperties {
     fn deep_hash<T: Hasher>(&self, state: &mut T, ctx: &mut DeepHashCtx) -> anyhow::Result<()> {
         match self {
@@ -316,6 +539,23 @@ impl DeepHash for UserAssetProperties {
                 5u8.deep_hash(state, ctx)?;
                 f.deep_hash(state, ctx)?;
             }
+            UserAssetProperties::Custom(c) => {
+                6u8.deep_hash(state, ctx)?;
+                c.deep_hash(state, ctx)?;
+            }
+            UserAssetProperties::Music(m) => {
+                7u8.deep_hash(state, ctx)?;
+                m.deep_hash(state, ctx)?;
+            }
+            UserAssetProperties::Captions(c) => {
+                8u8.deep_hash(state, ctx)?;
+                c.source.deep_hash(state, ctx)?;
+            }
+            UserAssetProperties::Settings(s) => {
+                9u8.deep_hash(state, ctx)?;
+                // Table is ordered, so the serialized form is stable
+                toml::to_string(&s.values)?.deep_hash(state, ctx)?;
+            }
         }
         Ok(())
     }
@@ -335,6 +575,10 @@ impl DeepHash for UserAssetHeader {
         self.tags.deep_hash(state, ctx)?;
         self.author.deep_hash(state, ctx)?;
         self.license.deep_hash(state, ctx)?;
+        // Variants are collected into the manifest directly, not cached.
+        // But declaring the quality variant disables the downscale rules
+        self.quality.is_some().deep_hash(state, ctx)?;
+        // The bundle only selects the container, the payload is the same
         Ok(())
     }
 }
@@ -0,0 +1,6 @@
+#version 330 core
+out vec4 out_color;
+
+void main() {
+    out_color = vec4(1.0);
+}
@@ -0,0 +1,295 @@
+//! Builds the tiny sample assets in `tests/fixtures` (a 1×1 texture, a short clip,
+//! a streamed FLAC clip, a minimal shader and a glTF cube) into the container, then loads them back
+//! through the `AssetHub` with the factories finalizing the IRs, the way
+//! the engine does, but headlessly and on a single thread.
+
+use dawn_assets::factory::{BasicFactory, LoadFactoryMessage};
+use dawn_assets::hub::{AssetHub, AssetHubEvent};
+use dawn_assets::ir::audio::IRAudioCodec;
+use dawn_assets::ir::mesh::IRIndexType;
+use dawn_assets::ir::IRAsset;
+use dawn_assets::reader::{BasicReader, EnumeratedAssets};
+use dawn_assets::requests::{AssetRequest, AssetRequestID, AssetRequestQuery};
+use dawn_assets::validation::ValidationRules;
+use dawn_assets::{AssetCastable, AssetID, AssetMemoryUsage, AssetType};
+use dawn_dac::reader::{open_mapped, read_asset, read_manifest};
+use dawn_dac::{ChecksumAlgorithm, CompressionLevel, ReadMode};
+use dawn_dacgen::config::WriteConfig;
+use dawn_dacgen::write_from_directory;
+use dawn_ecs::events::TickEvent;
+use evenio::component::Component;
+use evenio::event::Receiver;
+use evenio::fetch::Single;
+use evenio::world::World;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const FIXTURE_TAG: &str = "fixture";
+const POLL: Duration = Duration::from_millis(1);
+const MAX_TICKS: usize = 10_000;
+
+fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+// Builds the fixtures into `<temp>/<name>/fixtures.dac` with a fresh cache
+fn build(name: &str) -> (PathBuf, PathBuf) {
+    let dir = std::env::temp_dir().join(format!("dacgen_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let output = dir.join("fixtures.dac");
+    let mut writer = BufWriter::new(File::create(&output).unwrap());
+    write_from_directory(
+        &mut writer,
+        fixtures(),
+        WriteConfig {
+            read_mode: ReadMode::Flat,
+            checksum_algorithm: ChecksumAlgorithm::Blake3,
+            compression_level: CompressionLevel::Fast,
+            cache_dir: dir.join("cache"),
+            author: None,
+            description: Some("Pipeline fixtures".to_string()),
+            version: None,
+            license: None,
+            converters: Default::default(),
+            chunking: None,
+            signing_key: None,
+            encryption: None,
+            downscale: Default::default(),
+            conventions: Default::default(),
+            threads: None,
+            compression_threads: None,
+            compression_block_size: None,
+            guid_lock: None,
+            bundles: vec![],
+        },
+    )
+    .unwrap();
+    writer.flush().unwrap();
+    drop(writer);
+
+    (dir, output)
+}
+
+#[test]
+fn fixtures_are_built() {
+    let (dir, output) = build("fixtures_built");
+    let mut reader = BufReader::new(File::open(&output).unwrap());
+
+    let manifest = read_manifest(&mut reader).unwrap();
+    let types = manifest
+        .headers
+        .iter()
+        .map(|header| (header.id.as_str().to_string(), header.asset_type))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(types.get("pixel"), Some(&AssetType::Texture));
+    assert_eq!(types.get("beep"), Some(&AssetType::Audio));
+    assert_eq!(types.get("theme"), Some(&AssetType::Audio));
+    assert_eq!(types.get("unlit"), Some(&AssetType::Shader));
+    assert_eq!(types.get("cube"), Some(&AssetType::Mesh));
+    for header in &manifest.headers {
+        let ir = read_asset(&mut reader, header.id.clone()).unwrap();
+        assert_eq!(
+            ir.validate(&ValidationRules::default()),
+            Ok(()),
+            "{}",
+            header.id
+        );
+    }
+
+    match read_asset(&mut reader, "pixel".into()).unwrap() {
+        IRAsset::Texture(texture) => assert_eq!(texture.data.len(), 4),
+        ir => panic!("Unexpected IR of the texture: {:?}", ir.asset_type()),
+    }
+    match read_asset(&mut reader, "beep".into()).unwrap() {
+        IRAsset::Audio(audio) => {
+            assert_eq!(audio.sample_rate, 8000);
+            assert_eq!(audio.channels, 1);
+            assert_eq!(audio.length, 80);
+        }
+        ir => panic!("Unexpected IR of the audio: {:?}", ir.asset_type()),
+    }
+    match read_asset(&mut reader, "theme".into()).unwrap() {
+        IRAsset::Audio(audio) => {
+            // Kept encoded, the left channel ramps up by 100, the right one down
+            assert!(audio.data.is_empty());
+            assert_eq!(audio.length, 200);
+            let encoded = audio.encoded.unwrap();
+            assert_eq!(encoded.codec, IRAudioCodec::Flac);
+
+            let mut decoder = encoded.decoder().unwrap();
+            let (mut samples, mut block) = (Vec::new(), Vec::new());
+            while decoder.decode_block(&mut block).unwrap() {
+                samples.extend_from_slice(&block);
+            }
+            let expected = (0..200)
+                .flat_map(|i| [i as f32 * 100.0 / 32768.0, i as f32 * -100.0 / 32768.0])
+                .collect::<Vec<_>>();
+            assert_eq!(samples, expected);
+        }
+        ir => panic!("Unexpected IR of the audio: {:?}", ir.asset_type()),
+    }
+    match read_asset(&mut reader, "unlit".into()).unwrap() {
+        IRAsset::Shader(shader) => assert_eq!(shader.sources.len(), 2),
+        ir => panic!("Unexpected IR of the shader: {:?}", ir.asset_type()),
+    }
+    match read_asset(&mut reader, "cube".into()).unwrap() {
+        IRAsset::Mesh(mesh) => {
+            let indices = mesh.submesh.iter().map(|s| s.indices.len()).sum::<usize>();
+            let index_size = match mesh.index_type {
+                IRIndexType::U16 => 2,
+                IRIndexType::U32 => 4,
+            };
+            assert_eq!(indices, 36 * index_size);
+            assert_eq!(mesh.bounds.min, [-0.5; 3]);
+            assert_eq!(mesh.bounds.max, [0.5; 3]);
+        }
+        ir => panic!("Unexpected IR of the mesh: {:?}", ir.asset_type()),
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+// What the test factories turn the IRs into
+struct Finalized {
+    asset_type: AssetType,
+    ram: usize,
+}
+
+impl AssetCastable for Finalized {}
+
+fn finalize(message: LoadFactoryMessage) -> anyhow::Result<(Finalized, AssetMemoryUsage)> {
+    let asset_type = message.ir.asset_type();
+    anyhow::ensure!(
+        asset_type == message.asset_header.asset_type,
+        "{} IR of the {} asset",
+        asset_type,
+        message.asset_header.asset_type
+    );
+    let ram = message.ir.memory_usage();
+    Ok((Finalized { asset_type, ram }, AssetMemoryUsage::new(ram, 0)))
+}
+
+#[derive(Component, Default)]
+struct HubLog {
+    load: Option<AssetRequestID>,
+    free: Option<AssetRequestID>,
+    finished: HashMap<AssetRequestID, Result<(), String>>,
+    loaded: HashMap<AssetID, (AssetType, usize)>,
+    freed: usize,
+}
+
+fn record(
+    r: Receiver<AssetHubEvent>,
+    mut hub: Single<&mut AssetHub>,
+    mut log: Single<&mut HubLog>,
+) {
+    match r.event {
+        AssetHubEvent::AssetLoaded(id) => {
+            let asset = hub.get_typed::<Finalized>(id.clone()).unwrap();
+            let finalized = asset.cast();
+            log.loaded
+                .insert(id.clone(), (finalized.asset_type, finalized.ram));
+        }
+        AssetHubEvent::AssetFreed(_) => log.freed += 1,
+        AssetHubEvent::LoadFailed(id, reason) => panic!("Fixture {} is invalid: {}", id, reason),
+        AssetHubEvent::RequestFinished(rid, result) => {
+            let result = result.as_ref().map(|_| ()).map_err(|e| format!("{:#}", e));
+            log.finished.insert(*rid, result);
+            if log.load == Some(*rid) {
+                let query = AssetRequestQuery::ByTag(FIXTURE_TAG.to_string());
+                log.free = Some(hub.request(AssetRequest::Free(query)));
+            }
+        }
+        _ => {}
+    }
+}
+
+#[test]
+fn fixtures_are_loaded_by_hub() {
+    let (dir, output) = build("fixtures_loaded");
+    let container = open_mapped(&output).unwrap();
+    let count = container.manifest().headers.len();
+
+    let mut hub = AssetHub::new();
+    hub.set_validation(Some(ValidationRules::default()));
+    let mut reader = BasicReader::new();
+    reader.bind(hub.get_read_binding());
+    let mut factories = [
+        AssetType::Texture,
+        AssetType::Audio,
+        AssetType::Shader,
+        AssetType::Mesh,
+    ]
+    .map(|asset_type| {
+        let mut factory = BasicFactory::<Finalized>::new();
+        factory.bind(hub.get_factory_biding(asset_type));
+        factory
+    });
+
+    hub.request(AssetRequest::Enumerate);
+    let query = AssetRequestQuery::ByTag(FIXTURE_TAG.to_string());
+    let load = hub.request(AssetRequest::Load(query));
+
+    let mut world = World::new();
+    let entity = world.spawn();
+    world.insert(
+        entity,
+        HubLog {
+            load: Some(load),
+            ..Default::default()
+        },
+    );
+    hub.attach_to_ecs(&mut world);
+    world.add_handler(record);
+
+    let finished = |world: &World| {
+        let log = world.get::<HubLog>(entity).unwrap();
+        log.free
+            .is_some_and(|free| log.finished.contains_key(&free))
+    };
+    let mut frame = 0;
+    while !finished(&world) {
+        assert!(
+            frame < MAX_TICKS,
+            "The fixtures are not loaded and freed in time"
+        );
+        world.send(TickEvent {
+            frame,
+            delta: 0.0,
+            time: 0.0,
+            alpha: 1.0,
+        });
+        reader.process_events(
+            || {
+                let manifest = container.manifest();
+                Ok(EnumeratedAssets {
+                    headers: manifest.headers.clone(),
+                    variants: manifest.variants.clone(),
+                    guids: manifest.guids.clone(),
+                })
+            },
+            |id| Ok(container.read_asset(id)?),
+            POLL,
+        );
+        for factory in &mut factories {
+            factory.process_events(finalize, |_| {}, POLL);
+        }
+        frame += 1;
+    }
+
+    let log = world.get::<HubLog>(entity).unwrap();
+    assert_eq!(log.finished.get(&load), Some(&Ok(())));
+    assert_eq!(log.finished.get(&log.free.unwrap()), Some(&Ok(())));
+    assert_eq!(log.loaded.len(), count);
+    assert_eq!(log.loaded[&AssetID::from("pixel")].0, AssetType::Texture);
+    assert_eq!(log.loaded[&AssetID::from("cube")].0, AssetType::Mesh);
+    assert!(log.loaded.values().all(|(_, ram)| *ram > 0));
+    assert_eq!(log.freed, count);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,232 @@
+use evenio::component::Component;
+use evenio::event::{GlobalEvent, Receiver};
+use evenio::fetch::Single;
+use evenio::world::World;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Observations the audio rate is fitted over. The audio player reports one per tick,
+// so it's a couple of seconds: long enough to average the callback jitter out,
+// short enough to follow the drift.
+const WINDOW: usize = 128;
+// The rate is not estimated until the observations span that long
+const MIN_RATE_SPAN: f64 = 0.5;
+// Observations further than that from the prediction restart the fit,
+// e.g. after the device switch or the panic-filled blocks
+const DISCONTINUITY: f64 = 0.05;
+// Smoothing of the interval between the presented frames
+const INTERVAL_SMOOTHING: f64 = 0.1;
+
+/// Position of the audio stream heard at the instant. Sent by the audio player
+/// on each tick (see `Player::attach_to_ecs`).
+#[derive(GlobalEvent, Debug, Clone, Copy)]
+pub struct AudioSyncEvent {
+    /// Position in the stream in seconds, as the `AudioClock::seconds`
+    pub position: f64,
+    /// Instant the position is heard at: the time of the device callback
+    /// plus the output latency reported by the device
+    pub at: Instant,
+}
+
+/// Instant the frame was presented. Sent by the renderer on each tick
+/// (see `Renderer::attach_to_ecs`).
+#[derive(GlobalEvent, Debug, Clone, Copy)]
+pub struct FrameSyncEvent {
+    pub frame: usize,
+    /// Instant the buffers were swapped. With the vsync, that's close to the vblank
+    /// the frame is shown at.
+    pub presented: Instant,
+}
+
+/// Correlates the audio playback with the presented frames on the monotonic
+/// system clock, so the visuals can be aligned to the heard audio, e.g. the
+/// cutscenes, the videos or the music visualizers.
+/// The audio position is fitted linearly over the recent observations,
+/// which averages the jitter of the device callbacks out and estimates
+/// the drift of the audio device clock.
+///
+/// Spawned as the single entity by `attach_to_ecs`, and updated by the
+/// `AudioSyncEvent` and `FrameSyncEvent` events of the audio player and the renderer.
+#[derive(Component, Debug)]
+pub struct AvSyncClock {
+    // Instant the time of the observations is counted from
+    base: Option<Instant>,
+    // (seconds since the base, audio position)
+    audio: VecDeque<(f64, f64)>,
+    // Fitted audio position at the base and its rate
+    origin: f64,
+    rate: f64,
+    last_frame: Option<(usize, Instant)>,
+    frame_interval: Option<f64>,
+}
+
+impl Default for AvSyncClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AvSyncClock {
+    pub fn new() -> Self {
+        AvSyncClock {
+            base: None,
+            audio: VecDeque::with_capacity(WINDOW),
+            origin: 0.0,
+            rate: 1.0,
+            last_frame: None,
+            frame_interval: None,
+        }
+    }
+
+    fn since_base(&self, instant: Instant) -> f64 {
+        match self.base {
+            None => 0.0,
+            Some(base) if instant >= base => instant.duration_since(base).as_secs_f64(),
+            Some(base) => -base.duration_since(instant).as_secs_f64(),
+        }
+    }
+
+    /// Adds the observation of the audio position (see `AudioSyncEvent`).
+    pub fn observe_audio(&mut self, position: f64, at: Instant) {
+        if self.base.is_none() {
+            self.base = Some(at);
+        }
+        let time = self.since_base(at);
+
+        let continuous = self
+            .predict(time)
+            .is_none_or(|predicted| (predicted - position).abs() <= DISCONTINUITY);
+        if !continuous {
+            self.audio.clear();
+        }
+        if self.audio.len() == WINDOW {
+            self.audio.pop_front();
+        }
+        self.audio.push_back((time, position));
+        self.fit();
+    }
+
+    // Least squares line through the observations. The rate stays nominal
+    // until they span long enough to tell the drift from the jitter.
+    fn fit(&mut self) {
+        let count = self.audio.len() as f64;
+        let (first, last) = (self.audio[0].0, self.audio[self.audio.len() - 1].0);
+        let mean_time = self.audio.iter().map(|(t, _)| t).sum::<f64>() / count;
+        let mean_position = self.audio.iter().map(|(_, p)| p).sum::<f64>() / count;
+
+        self.rate = 1.0;
+        if last - first >= MIN_RATE_SPAN {
+            let (mut covariance, mut variance) = (0.0, 0.0);
+            for (time, position) in &self.audio {
+                covariance += (time - mean_time) * (position - mean_position);
+                variance += (time - mean_time) * (time - mean_time);
+            }
+            if variance > 0.0 {
+                self.rate = covariance / variance;
+            }
+        }
+        self.origin = mean_position - self.rate * mean_time;
+    }
+
+    fn predict(&self, time: f64) -> Option<f64> {
+        (!self.audio.is_empty()).then_some(self.origin + self.rate * time)
+    }
+
+    /// Adds the presented frame (see `FrameSyncEvent`).
+    pub fn observe_frame(&mut self, frame: usize, presented: Instant) {
+        if let Some((last, last_presented)) = self.last_frame {
+            if frame > last && presented > last_presented {
+                let interval =
+                    presented.duration_since(last_presented).as_secs_f64() / (frame - last) as f64;
+                self.frame_interval = Some(match self.frame_interval {
+                    None => interval,
+                    Some(smoothed) => smoothed + (interval - smoothed) * INTERVAL_SMOOTHING,
+                });
+            }
+        }
+        self.last_frame = Some((frame, presented));
+    }
+
+    /// Audio position in seconds heard at the instant.
+    /// `None` until the audio is observed.
+    pub fn audio_position_at(&self, instant: Instant) -> Option<f64> {
+        self.predict(self.since_base(instant))
+    }
+
+    /// Audio position in seconds heard right now.
+    pub fn audio_position(&self) -> Option<f64> {
+        self.audio_position_at(Instant::now())
+    }
+
+    /// Instant the audio position is heard at. It's in the past for the positions
+    /// already played. `None` until the audio is observed.
+    pub fn instant_of(&self, position: f64) -> Option<Instant> {
+        let base = self.base?;
+        if self.audio.is_empty() {
+            return None;
+        }
+        let time = (position - self.origin) / self.rate;
+        Some(if time >= 0.0 {
+            base + Duration::from_secs_f64(time)
+        } else {
+            base.checked_sub(Duration::from_secs_f64(-time))
+                .unwrap_or(base)
+        })
+    }
+
+    /// Rate of the audio clock against the system one, minus one, in parts per million.
+    /// Positive if the audio device plays faster. `None` until it's estimated.
+    pub fn drift_ppm(&self) -> Option<f64> {
+        let (first, last) = (self.audio.front()?.0, self.audio.back()?.0);
+        (last - first >= MIN_RATE_SPAN).then_some((self.rate - 1.0) * 1e6)
+    }
+
+    /// The last presented frame and its instant.
+    pub fn last_frame(&self) -> Option<(usize, Instant)> {
+        self.last_frame
+    }
+
+    /// Average interval between the presented frames.
+    pub fn frame_interval(&self) -> Option<Duration> {
+        self.frame_interval.map(Duration::from_secs_f64)
+    }
+
+    /// Predicted instant the next frame is presented at.
+    /// `None` until two frames are presented.
+    pub fn next_frame_at(&self) -> Option<Instant> {
+        let (_, presented) = self.last_frame?;
+        let interval = self.frame_interval?;
+        // The frames dropped since the last one are skipped
+        let now = Instant::now();
+        let mut next = presented + Duration::from_secs_f64(interval);
+        if next < now {
+            let behind = now.duration_since(next).as_secs_f64();
+            next += Duration::from_secs_f64((behind / interval).ceil() * interval);
+        }
+        Some(next)
+    }
+
+    /// Audio position heard when the next frame is presented: the position
+    /// the visuals rendered now should show to be in sync with the audio.
+    pub fn audio_position_at_next_frame(&self) -> Option<f64> {
+        self.audio_position_at(self.next_frame_at()?)
+    }
+
+    /// Spawns the clock in the world. The audio player and the renderer
+    /// attached to the same world keep it updated.
+    pub fn attach_to_ecs(self, world: &mut World) {
+        let entity = world.spawn();
+        world.insert(entity, self);
+
+        fn audio_handler(r: Receiver<AudioSyncEvent>, mut clock: Single<&mut AvSyncClock>) {
+            clock.observe_audio(r.event.position, r.event.at);
+        }
+
+        fn frame_handler(r: Receiver<FrameSyncEvent>, mut clock: Single<&mut AvSyncClock>) {
+            clock.observe_frame(r.event.frame, r.event.presented);
+        }
+
+        world.add_handler(audio_handler);
+        world.add_handler(frame_handler);
+    }
+}
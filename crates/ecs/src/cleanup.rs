@@ -0,0 +1,174 @@
+use crate::events::TickEvent;
+use evenio::component::Component;
+use evenio::entity::EntityId;
+use evenio::event::{Despawn, GlobalEvent, Receiver, Sender};
+use evenio::fetch::Single;
+use evenio::handler::IntoHandler;
+use evenio::world::World;
+use log::debug;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Entities despawned on the first tick, before their cost is measured.
+const INITIAL_DESPAWN_BATCH: usize = 16;
+/// Upper bound of the despawn batch, so one cheap tick can't schedule a hitch.
+const MAX_DESPAWN_BATCH: usize = 4096;
+
+type Task = Box<dyn FnOnce() + Send + Sync>;
+
+// Sent after the despawns of the tick, so they are measured
+#[derive(GlobalEvent)]
+struct CleanupStep {
+    start: Instant,
+    despawned: usize,
+}
+
+/// Tears the things down over several ticks instead of at once,
+/// e.g. when the large scene is unloaded: the despawns of the entities
+/// (dropping their components, asset handles and audio voices with them)
+/// and the deferred tasks (e.g. dropping the large buffers).
+/// Spawned as the single entity by `attach_to_ecs`, so the handlers
+/// queue the work through `Single<&mut Cleanup>`.
+///
+/// Each tick, the queued work is done until the budget is spent,
+/// the rest spills over to the next tick. The despawns go first, in batches:
+/// their cost is only known once they are done, so the batch size is adjusted
+/// to the time the previous batch took. At least one item is processed
+/// each tick, so the queue is drained even if the items exceed the budget.
+///
+/// The work is done on the main loop thread. The GL objects are deleted
+/// by the renderer thread when the renderer drops them, that's not covered.
+/// ```
+/// use dawn_ecs::cleanup::Cleanup;
+/// use std::time::Duration;
+///
+/// let mut cleanup = Cleanup::new(Duration::from_millis(2));
+/// cleanup.drop_later(vec![0u8; 1 << 20]);
+/// cleanup.defer(|| println!("Scene is unloaded"));
+/// assert_eq!(cleanup.pending(), 2);
+/// ```
+#[derive(Component)]
+pub struct Cleanup {
+    budget: Duration,
+    despawns: VecDeque<EntityId>,
+    tasks: VecDeque<Task>,
+    despawn_batch: usize,
+    // Time the last tick took, for the monitoring
+    last_time: Duration,
+}
+
+impl Cleanup {
+    pub fn new(budget: Duration) -> Self {
+        Cleanup {
+            budget,
+            despawns: VecDeque::new(),
+            tasks: VecDeque::new(),
+            despawn_batch: INITIAL_DESPAWN_BATCH,
+            last_time: Duration::ZERO,
+        }
+    }
+
+    /// Time spent on the cleanup each tick.
+    pub fn set_budget(&mut self, budget: Duration) {
+        self.budget = budget;
+    }
+
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// Despawns the entity on one of the next ticks.
+    /// The entities despawned in the meantime are skipped.
+    pub fn despawn(&mut self, entity: EntityId) {
+        self.despawns.push_back(entity);
+    }
+
+    pub fn despawn_all(&mut self, entities: impl IntoIterator<Item = EntityId>) {
+        self.despawns.extend(entities);
+    }
+
+    /// Runs the task on one of the next ticks, after the queued despawns.
+    pub fn defer(&mut self, task: impl FnOnce() + Send + Sync + 'static) {
+        self.tasks.push_back(Box::new(task));
+    }
+
+    /// Drops the value on one of the next ticks, e.g. the large buffer.
+    pub fn drop_later<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.defer(move || drop(value));
+    }
+
+    /// Number of the queued despawns and tasks.
+    pub fn pending(&self) -> usize {
+        self.despawns.len() + self.tasks.len()
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.despawns.is_empty() && self.tasks.is_empty()
+    }
+
+    /// Time the cleanup took on the last tick it had the work on.
+    pub fn last_time(&self) -> Duration {
+        self.last_time
+    }
+
+    fn step(&mut self, start: Instant, despawned: usize) {
+        let spent = start.elapsed();
+        if despawned > 0 {
+            let each = (spent / despawned as u32).max(Duration::from_nanos(1));
+            self.despawn_batch =
+                ((self.budget.as_nanos() / each.as_nanos()) as usize).clamp(1, MAX_DESPAWN_BATCH);
+        }
+
+        let mut processed = despawned;
+        while processed == 0 || start.elapsed() < self.budget {
+            let Some(task) = self.tasks.pop_front() else {
+                break;
+            };
+            task();
+            processed += 1;
+        }
+
+        self.last_time = start.elapsed();
+        if !self.is_idle() {
+            debug!(
+                "Cleanup spent {:?}, {} items left for the next tick",
+                self.last_time,
+                self.pending()
+            );
+        }
+    }
+
+    /// Spawns the cleanup in the world. The queued work is done
+    /// on each `TickEvent` after the other handlers.
+    pub fn attach_to_ecs(self, world: &mut World) {
+        let entity = world.spawn();
+        world.insert(entity, self);
+
+        fn tick_handler(
+            _: Receiver<TickEvent>,
+            mut cleanup: Single<&mut Cleanup>,
+            mut sender: Sender<(Despawn, CleanupStep)>,
+        ) {
+            if cleanup.is_idle() {
+                return;
+            }
+            let start = Instant::now();
+            let count = cleanup.despawn_batch.min(cleanup.despawns.len());
+            for entity in cleanup.despawns.drain(..count) {
+                sender.despawn(entity);
+            }
+            // The despawns are done once the handler returns, before the step
+            sender.send(CleanupStep {
+                start,
+                despawned: count,
+            });
+        }
+
+        fn step_handler(r: Receiver<CleanupStep>, mut cleanup: Single<&mut Cleanup>) {
+            cleanup.step(r.event.start, r.event.despawned);
+        }
+
+        world.add_handler(tick_handler.low());
+        world.add_handler(step_handler);
+    }
+}
@@ -11,6 +11,25 @@ pub struct TickEvent {
     pub delta: f32,
     /// The total time since the start of the main loop in milliseconds.
     pub time: f32,
+    /// Position between the last two fixed steps, from 0 to 1, to interpolate
+    /// the state updated in the fixed steps with. Always 1 without
+    /// the `FixedTimestep` (see `dawn_ecs::main_loop::FixedTimestep`).
+    pub alpha: f32,
+}
+
+/// Event sent at the fixed rate of the `FixedTimestep`, before the `TickEvent`
+/// of the same main loop iteration. Zero or several times per tick.
+/// Use it for the physics and the logic that must not depend on the tick rate.
+/// It should not be sent by the user.
+#[derive(GlobalEvent)]
+pub struct FixedTickEvent {
+    /// The number of the fixed step.
+    pub frame: usize,
+    /// The length of the step in seconds.
+    pub step: f32,
+    /// The time of the step since the first one in seconds,
+    /// derived from the number of the step.
+    pub time: f32,
 }
 
 /// This is a special Tick sent in between frames
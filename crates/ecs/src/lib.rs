@@ -1,2 +1,11 @@
+pub mod av_sync;
+pub mod cleanup;
 pub mod main_loop;
+pub mod scheduler;
 pub mod events;
+pub mod state_hash;
+
+/// Version and the enabled features of the crate.
+pub fn build_info() -> dawn_util::build_info::CrateBuildInfo {
+    dawn_util::crate_build_info![]
+}
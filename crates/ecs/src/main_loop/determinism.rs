@@ -0,0 +1,196 @@
+use crate::events::{ExitEvent, InterSyncEvent};
+use crate::main_loop::fixed_step::FixedStepDriver;
+use crate::state_hash::WorldHasher;
+use evenio::component::Component;
+use evenio::event::{GlobalEvent, Receiver};
+use evenio::fetch::Single;
+use evenio::handler::IntoHandler;
+use evenio::world::World;
+use log::info;
+use std::collections::BTreeMap;
+use std::path::Path;
+use thiserror::Error;
+
+type Input = Box<dyn FnOnce(&mut World)>;
+
+/// Input of the deterministic run: the events sent to the world
+/// before the ticks, e.g. the recorded input of the player.
+pub struct InputScript {
+    ticks: usize,
+    inputs: BTreeMap<usize, Vec<Input>>,
+}
+
+impl InputScript {
+    /// The run lasts for the given number of ticks.
+    pub fn new(ticks: usize) -> Self {
+        InputScript {
+            ticks,
+            inputs: BTreeMap::new(),
+        }
+    }
+
+    /// Runs the closure before the tick of the frame.
+    /// The closures of the same frame run in the order they were added.
+    pub fn at(mut self, frame: usize, input: impl FnOnce(&mut World) + 'static) -> Self {
+        self.inputs.entry(frame).or_default().push(Box::new(input));
+        self
+    }
+
+    /// Sends the event before the tick of the frame.
+    pub fn send<E: GlobalEvent + 'static>(self, frame: usize, event: E) -> Self {
+        self.at(frame, move |world| world.send(event))
+    }
+
+    pub fn ticks(&self) -> usize {
+        self.ticks
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DeterminismError {
+    #[error("World state diverged at frame {frame}: expected {expected:016x}, got {actual:016x}")]
+    Desync {
+        frame: usize,
+        expected: u64,
+        actual: u64,
+    },
+    #[error("Run lasted {actual} ticks, expected {expected}")]
+    Length { expected: usize, actual: usize },
+}
+
+/// Runs the main loop over the scripted input as fast as possible and returns
+/// the hash of the world after every tick. Unlike the other loops, the time
+/// in the `TickEvent` is derived from the frame number and the tick rate,
+/// not from the clock, so the handlers see the same time on every run.
+/// Stops after the last tick of the script or on the `ExitEvent`.
+///
+/// Run it twice on the freshly set up worlds and compare the hashes,
+/// or compare them with the stored ones (see `verify_determinism`).
+pub fn deterministic_loop(
+    world: &mut World,
+    tick_rate: f32,
+    script: InputScript,
+    hasher: &WorldHasher,
+) -> Vec<u64> {
+    let mut hashes = Vec::with_capacity(script.ticks);
+    run(world, tick_rate, script, hasher, |_, hash| {
+        hashes.push(hash);
+        true
+    });
+    hashes
+}
+
+/// Same as `deterministic_loop`, but compares the hashes with the expected
+/// ones as it goes. Stops on the first mismatch, leaving the world as it was
+/// at the diverged tick for the inspection.
+pub fn verify_determinism(
+    world: &mut World,
+    tick_rate: f32,
+    script: InputScript,
+    hasher: &WorldHasher,
+    expected: &[u64],
+) -> Result<(), DeterminismError> {
+    let mut result = Ok(());
+    let ticks = run(
+        world,
+        tick_rate,
+        script,
+        hasher,
+        |frame, actual| match expected.get(frame) {
+            Some(expected) if *expected != actual => {
+                result = Err(DeterminismError::Desync {
+                    frame,
+                    expected: *expected,
+                    actual,
+                });
+                false
+            }
+            Some(_) => true,
+            None => false,
+        },
+    );
+    result?;
+
+    if ticks != expected.len() {
+        return Err(DeterminismError::Length {
+            expected: expected.len(),
+            actual: ticks,
+        });
+    }
+    Ok(())
+}
+
+/// Stores the hashes, one hexadecimal per line, to be checked by `verify_determinism` later.
+pub fn write_hashes(path: &Path, hashes: &[u64]) -> std::io::Result<()> {
+    let text = hashes
+        .iter()
+        .map(|hash| format!("{hash:016x}\n"))
+        .collect::<String>();
+    std::fs::write(path, text)
+}
+
+pub fn read_hashes(path: &Path) -> std::io::Result<Vec<u64>> {
+    std::fs::read_to_string(path)?
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            u64::from_str_radix(line.trim(), 16)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+        })
+        .collect()
+}
+
+// Returns the number of the ticks run
+fn run(
+    world: &mut World,
+    tick_rate: f32,
+    mut script: InputScript,
+    hasher: &WorldHasher,
+    mut on_tick: impl FnMut(usize, u64) -> bool,
+) -> usize {
+    #[derive(Component, Debug)]
+    struct DeterministicLoopData {
+        stopped: bool,
+    }
+
+    fn stop_event_loop_handler(_: Receiver<ExitEvent>, mut d: Single<&mut DeterministicLoopData>) {
+        d.stopped = true;
+    }
+
+    let entity = world.spawn();
+    world.insert(entity, DeterministicLoopData { stopped: false });
+    let handler = world.add_handler(stop_event_loop_handler.low());
+    let fixed_step = FixedStepDriver::attach(world);
+
+    let delta = 1.0 / tick_rate;
+    let mut frame = 0;
+    while frame < script.ticks {
+        if let Some(inputs) = script.inputs.remove(&frame) {
+            for input in inputs {
+                input(world);
+            }
+        }
+
+        fixed_step.tick(world, frame, delta, frame as f32 * delta);
+        world.send(InterSyncEvent { frame: frame + 1 });
+
+        let hash = hasher.hash(world);
+        frame += 1;
+        if !on_tick(frame - 1, hash) {
+            break;
+        }
+        if world
+            .get::<DeterministicLoopData>(entity)
+            .is_some_and(|d| d.stopped)
+        {
+            info!("Stopping deterministic loop");
+            break;
+        }
+    }
+
+    // Leave the world reusable for the next run
+    world.remove_handler(handler);
+    world.despawn(entity);
+    fixed_step.detach(world);
+    frame
+}
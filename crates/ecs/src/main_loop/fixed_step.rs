@@ -0,0 +1,327 @@
+use crate::events::{FixedTickEvent, TickEvent};
+use evenio::component::Component;
+use evenio::entity::EntityId;
+use evenio::event::{GlobalEvent, Receiver};
+use evenio::fetch::{Single, TrySingle};
+use evenio::handler::HandlerId;
+use evenio::world::World;
+use log::warn;
+
+/// Fixed steps run per tick at most, unless set with `with_max_steps`.
+const DEFAULT_MAX_STEPS: usize = 8;
+
+/// Runs the logic at the fixed rate, independently of the tick rate
+/// (accumulator pattern): on each tick, the time passed is accumulated
+/// and consumed in the fixed steps, each sent as `FixedTickEvent` before
+/// the `TickEvent`. The time left over is reported as `TickEvent::alpha`,
+/// to interpolate the state between the last two steps when presenting it.
+///
+/// Spawned as the single entity by `attach_to_ecs`, before the main loop
+/// is started. The rate can be changed through `Single<&mut FixedTimestep>`.
+/// Without it, no `FixedTickEvent` is sent, and the `alpha` is always 1.
+///
+/// The steps missed after the long tick are caught up, at most `max_steps`
+/// per tick. The rest of the time is dropped, so the slow ticks can't make
+/// the next ones even slower by running more and more steps.
+#[derive(Component, Debug)]
+pub struct FixedTimestep {
+    rate: f32,
+    max_steps: usize,
+    accumulated: f64,
+    frame: usize,
+    alpha: f32,
+}
+
+// Steps to run in the current tick, see `FixedStepDriver`
+#[derive(Component, Debug, Clone, Copy)]
+struct FixedSteps {
+    first: usize,
+    count: usize,
+    step: f32,
+    alpha: f32,
+}
+
+impl FixedSteps {
+    const NONE: FixedSteps = FixedSteps {
+        first: 0,
+        count: 0,
+        step: 0.0,
+        alpha: 1.0,
+    };
+}
+
+impl FixedTimestep {
+    /// Steps per second. Non-positive rates are clamped to one step per second.
+    pub fn new(rate: f32) -> Self {
+        FixedTimestep {
+            rate: Self::clamp_rate(rate),
+            max_steps: DEFAULT_MAX_STEPS,
+            accumulated: 0.0,
+            frame: 0,
+            alpha: 1.0,
+        }
+    }
+
+    fn clamp_rate(rate: f32) -> f32 {
+        if rate > 0.0 {
+            rate
+        } else {
+            1.0
+        }
+    }
+
+    /// Fixed steps run per tick at most, 8 by default. At least one.
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps.max(1);
+        self
+    }
+
+    /// Changes the rate from the next tick. The time accumulated is kept.
+    pub fn set_rate(&mut self, rate: f32) {
+        self.rate = Self::clamp_rate(rate);
+    }
+
+    pub fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    /// Length of the step in seconds.
+    pub fn step(&self) -> f32 {
+        1.0 / self.rate
+    }
+
+    /// Number of the steps run so far.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Interpolation factor of the last tick, see `TickEvent::alpha`.
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    fn advance(&mut self, delta: f32) -> FixedSteps {
+        let step = 1.0 / self.rate as f64;
+        self.accumulated += delta.max(0.0) as f64;
+
+        let mut count = (self.accumulated / step).floor() as usize;
+        if count > self.max_steps {
+            warn!(
+                "Fixed timestep is behind by {} steps, skipping them",
+                count - self.max_steps
+            );
+            count = self.max_steps;
+            self.accumulated %= step;
+        } else {
+            self.accumulated -= count as f64 * step;
+        }
+
+        let first = self.frame;
+        self.frame += count;
+        self.alpha = (self.accumulated / step).clamp(0.0, 1.0) as f32;
+        FixedSteps {
+            first,
+            count,
+            step: step as f32,
+            alpha: self.alpha,
+        }
+    }
+
+    /// Spawns the fixed timestep in the world. The main loops pick it up
+    /// on each tick, including the `deterministic_loop`.
+    pub fn attach_to_ecs(self, world: &mut World) {
+        let entity = world.spawn();
+        world.insert(entity, self);
+    }
+}
+
+#[derive(GlobalEvent)]
+struct AdvanceFixedStep {
+    delta: f32,
+}
+
+/// Sends the fixed ticks and the tick of the main loop.
+/// The main loop can't query the world, so the optional `FixedTimestep`
+/// is advanced by the handler, writing the steps to run into its own entity.
+pub(crate) struct FixedStepDriver {
+    entity: EntityId,
+    handler: HandlerId,
+}
+
+impl FixedStepDriver {
+    pub(crate) fn attach(world: &mut World) -> Self {
+        fn advance_handler(
+            r: Receiver<AdvanceFixedStep>,
+            timestep: TrySingle<&mut FixedTimestep>,
+            mut steps: Single<&mut FixedSteps>,
+        ) {
+            **steps = match timestep.0 {
+                Ok(timestep) => timestep.advance(r.event.delta),
+                Err(_) => FixedSteps::NONE,
+            };
+        }
+
+        let entity = world.spawn();
+        world.insert(entity, FixedSteps::NONE);
+        let handler = world.add_handler(advance_handler);
+        FixedStepDriver { entity, handler }
+    }
+
+    /// Sends the fixed ticks due, then the tick itself.
+    pub(crate) fn tick(&self, world: &mut World, frame: usize, delta: f32, time: f32) {
+        world.send(AdvanceFixedStep { delta });
+        let steps = world
+            .get::<FixedSteps>(self.entity)
+            .copied()
+            .unwrap_or(FixedSteps::NONE);
+        for frame in steps.first..steps.first + steps.count {
+            world.send(FixedTickEvent {
+                frame,
+                step: steps.step,
+                time: frame as f32 * steps.step,
+            });
+        }
+
+        world.send(TickEvent {
+            frame,
+            delta,
+            time,
+            alpha: steps.alpha,
+        });
+    }
+
+    /// Leaves the world as it was before `attach`.
+    pub(crate) fn detach(self, world: &mut World) {
+        world.remove_handler(self.handler);
+        world.despawn(self.entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Four steps per second, so the lengths are exact
+    const STEP: f32 = 0.25;
+
+    fn timestep() -> FixedTimestep {
+        FixedTimestep::new(1.0 / STEP)
+    }
+
+    #[test]
+    fn steps_consume_accumulated_time() {
+        let mut timestep = timestep();
+        let steps = timestep.advance(2.5 * STEP);
+        assert_eq!((steps.first, steps.count, steps.step), (0, 2, STEP));
+        assert_eq!(steps.alpha, 0.5);
+
+        // The leftover half a step is completed
+        let steps = timestep.advance(0.5 * STEP);
+        assert_eq!((steps.first, steps.count), (2, 1));
+        assert_eq!(steps.alpha, 0.0);
+        assert_eq!(timestep.frame(), 3);
+    }
+
+    #[test]
+    fn catch_up_is_clamped() {
+        let mut timestep = timestep().with_max_steps(3);
+        let steps = timestep.advance(40.0 * STEP);
+        assert_eq!((steps.first, steps.count), (0, 3));
+        // The missed steps are dropped, not run on the next ticks
+        assert_eq!(steps.alpha, 0.0);
+        assert_eq!(timestep.advance(0.0).count, 0);
+
+        // The fraction of the step is kept
+        let steps = timestep.advance(8.5 * STEP);
+        assert_eq!((steps.first, steps.count), (3, 3));
+        assert_eq!(steps.alpha, 0.5);
+
+        let mut timestep = FixedTimestep::new(1.0);
+        assert_eq!(timestep.advance(100.0).count, DEFAULT_MAX_STEPS);
+        assert_eq!(FixedTimestep::new(1.0).with_max_steps(0).max_steps, 1);
+    }
+
+    #[test]
+    fn alpha_stays_in_range_after_long_frames() {
+        let mut timestep = timestep();
+        // Only the whole steps are dropped, the fraction is carried over
+        let mut total = 0.0;
+        for frame in [5.2, 40.4, 1000.9, 3.0] {
+            total += frame;
+            let steps = timestep.advance(frame * STEP);
+            assert!((0.0..1.0).contains(&steps.alpha), "{:?}", steps);
+            let expected = total % 1.0;
+            assert!((steps.alpha - expected).abs() < 1e-3, "{:?}", steps);
+            assert_eq!(timestep.alpha(), steps.alpha);
+        }
+    }
+
+    #[test]
+    fn zero_length_frames_keep_alpha() {
+        let mut timestep = timestep();
+        timestep.advance(0.5 * STEP);
+        for delta in [0.0, 0.0, -1.0] {
+            let steps = timestep.advance(delta);
+            assert_eq!(steps.count, 0);
+            assert_eq!(steps.alpha, 0.5);
+        }
+        assert_eq!(timestep.frame(), 0);
+    }
+
+    #[test]
+    fn non_positive_rate_is_clamped() {
+        assert_eq!(FixedTimestep::new(0.0).rate(), 1.0);
+        let mut timestep = timestep();
+        timestep.set_rate(-5.0);
+        assert_eq!(timestep.step(), 1.0);
+    }
+
+    #[derive(Component, Default)]
+    struct Received {
+        fixed: Vec<usize>,
+        alphas: Vec<f32>,
+    }
+
+    fn world() -> (World, EntityId) {
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.insert(entity, Received::default());
+        world.add_handler(
+            |r: Receiver<FixedTickEvent>, mut received: Single<&mut Received>| {
+                received.fixed.push(r.event.frame);
+            },
+        );
+        world.add_handler(
+            |r: Receiver<TickEvent>, mut received: Single<&mut Received>| {
+                received.alphas.push(r.event.alpha);
+            },
+        );
+        (world, entity)
+    }
+
+    #[test]
+    fn driver_sends_fixed_ticks_before_the_tick() {
+        let (mut world, entity) = world();
+        timestep().attach_to_ecs(&mut world);
+        let driver = FixedStepDriver::attach(&mut world);
+        driver.tick(&mut world, 0, 2.5 * STEP, 0.0);
+        driver.tick(&mut world, 1, 0.0, 2.5 * STEP);
+        driver.tick(&mut world, 2, 0.5 * STEP, 2.5 * STEP);
+
+        let received = world.get::<Received>(entity).unwrap();
+        assert_eq!(received.fixed, vec![0, 1, 2]);
+        assert_eq!(received.alphas, vec![0.5, 0.5, 0.0]);
+        driver.detach(&mut world);
+    }
+
+    #[test]
+    fn driver_without_timestep_sends_only_ticks() {
+        let (mut world, entity) = world();
+        let driver = FixedStepDriver::attach(&mut world);
+        driver.tick(&mut world, 0, 10.0, 0.0);
+
+        let received = world.get::<Received>(entity).unwrap();
+        assert!(received.fixed.is_empty());
+        assert_eq!(received.alphas, vec![1.0]);
+    }
+}
@@ -1,4 +1,5 @@
-use crate::events::{InterSyncEvent, ExitEvent, TickEvent};
+use crate::events::{InterSyncEvent, ExitEvent};
+use crate::main_loop::fixed_step::FixedStepDriver;
 use crate::main_loop::monitor::{DummyMainLoopMonitor, MainLoopMonitor, MainLoopMonitorTrait};
 use crate::main_loop::sync::{
     DummySynchronization, FixedRateSynchronization, RendezvousSynchronization, Synchronization,
@@ -13,19 +14,32 @@ use log::info;
 use std::time::{Duration, Instant};
 use dawn_util::profile::MonitorSample;
 
+mod determinism;
+mod fixed_step;
 mod monitor;
 mod sync;
 
+pub use determinism::{
+    deterministic_loop, read_hashes, verify_determinism, write_hashes, DeterminismError,
+    InputScript,
+};
+pub use fixed_step::FixedTimestep;
+
 /// Event sent every second with monitoring data about the main loop.
 #[derive(GlobalEvent)]
 pub struct MainLoopMonitorEvent {
     pub cycle_time: MonitorSample<Duration>,
     pub tps: MonitorSample<f32>,
     pub load: MonitorSample<f32>,
+    /// Time spent dispatching the `InterSyncEvent`, i.e. collecting
+    /// the data for the renderer (see `dawn_graphics` renderer).
+    pub collection_time: MonitorSample<Duration>,
 }
 
 /// Runs the main loop of the application.
 /// Every `tps` ticks per second, it sends a `Tick` event to the ECS.
+/// With the `FixedTimestep` attached, the `FixedTickEvent` events are sent
+/// before it at the fixed rate.
 /// You can stop the loop by sending a `ExitEvent` event to the ECS.
 ///
 /// The loop will synchronize with the given `Rendezvous` object,
@@ -98,6 +112,7 @@ fn run_loop_inner<M>(
     let entity = world.spawn();
     world.insert(entity, PrivateData { stopped: false });
     world.add_handler(stop_event_loop_handler.low());
+    let fixed_step = FixedStepDriver::attach(world);
 
     let mut prev_tick = Instant::now();
     let loop_start = Instant::now();
@@ -126,11 +141,7 @@ fn run_loop_inner<M>(
 
         // Dispatch the Tick event
         monitor.cycle_start();
-        world.send(TickEvent {
-            frame,
-            delta,
-            time: total_time,
-        });
+        fixed_step.tick(world, frame, delta, total_time);
         monitor.tick_end();
         frame += 1;
 
@@ -138,6 +149,8 @@ fn run_loop_inner<M>(
         prev_tick = start;
         after_frame.wait(start.elapsed());
 
+        monitor.collection_start();
         world.send(InterSyncEvent { frame });
+        monitor.collection_end();
     }
 }
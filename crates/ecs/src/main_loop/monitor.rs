@@ -6,11 +6,14 @@ use std::time::{Duration, Instant};
 pub(crate) trait MainLoopMonitorTrait {
     fn cycle_start(&mut self) {}
     fn tick_end(&mut self) {}
+    fn collection_start(&mut self) {}
+    fn collection_end(&mut self) {}
     fn cycle(&mut self, _world: &mut World) {}
 }
 
 pub(crate) struct MainLoopMonitor {
     cycle_time: Stopwatch,
+    collection_time: Stopwatch,
     tps: Counter,
     las_update: Instant,
     counter: usize,
@@ -20,6 +23,7 @@ impl MainLoopMonitor {
     pub fn new() -> Self {
         MainLoopMonitor {
             cycle_time: Stopwatch::new(0.5),
+            collection_time: Stopwatch::new(0.5),
             tps: Counter::new(Duration::from_secs(1), 0.5),
             las_update: Instant::now(),
             counter: 0,
@@ -38,6 +42,16 @@ impl MainLoopMonitorTrait for MainLoopMonitor {
         self.cycle_time.stop();
     }
 
+    #[inline(always)]
+    fn collection_start(&mut self) {
+        self.collection_time.start();
+    }
+
+    #[inline(always)]
+    fn collection_end(&mut self) {
+        self.collection_time.stop();
+    }
+
     #[inline(always)]
     fn cycle(&mut self, world: &mut World) {
         self.tps.count(1);
@@ -49,6 +63,7 @@ impl MainLoopMonitorTrait for MainLoopMonitor {
 
             // Calculate the average load of the main loop
             let cycle_time = self.cycle_time.get();
+            let collection_time = self.collection_time.get();
             let tps = self.tps.get();
             let load = MonitorSample::new(
                 cycle_time.min().as_secs_f32() / tps.min(),
@@ -57,8 +72,9 @@ impl MainLoopMonitorTrait for MainLoopMonitor {
             );
 
             // Reset the counters each 5 seconds to get more smooth data
-            if self.counter % 5 == 0 {
+            if self.counter.is_multiple_of(5) {
                 self.cycle_time.reset();
+                self.collection_time.reset();
                 self.tps.reset();
             }
             self.counter += 1;
@@ -68,6 +84,7 @@ impl MainLoopMonitorTrait for MainLoopMonitor {
                 cycle_time,
                 tps,
                 load,
+                collection_time,
             });
         }
     }
@@ -0,0 +1,416 @@
+use crate::events::TickEvent;
+use evenio::component::Component;
+use evenio::event::{GlobalEvent, Receiver, Sender};
+use evenio::fetch::Single;
+use evenio::handler::IntoHandler;
+use evenio::world::World;
+use std::any::{Any, TypeId};
+
+/// Delay or interval of the scheduled event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Delay {
+    /// Seconds of the scaled time: affected by the time scale,
+    /// and not passing while the scheduler is paused.
+    Seconds(f32),
+    /// Seconds of the real time, e.g. for the UI working while the game is paused.
+    RealSeconds(f32),
+    /// Ticks of the main loop. Not counted while the scheduler is paused.
+    Ticks(usize),
+}
+
+/// Handle of the scheduled event, used to cancel it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimerHandle(u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Clock {
+    Scaled,
+    Real,
+    Ticks,
+}
+
+struct Timer {
+    handle: TimerHandle,
+    clock: Clock,
+    due: f64,
+    interval: Option<f64>,
+    // Tick the timer was scheduled on, it's not sent on the same one
+    scheduled: u64,
+    event: TypeId,
+    // Box<dyn FnMut() -> E + Send + Sync>, called each time the timer fires
+    make: Box<dyn Any + Send + Sync>,
+}
+
+type MakeEvent<E> = Box<dyn FnMut() -> E + Send + Sync>;
+
+/// Sends the events after the delay, or repeatedly with the interval,
+/// instead of comparing the `TickEvent::time` in each handler.
+/// Spawned as the single entity by `attach_to_ecs`, so the handlers
+/// schedule the events through `Single<&mut Scheduler>`.
+///
+/// The events are sent on the first tick their time has come on, so the precision
+/// is the tick interval. The repeating events are sent at most once per tick,
+/// the intervals missed in between (e.g. because of the long tick) are skipped.
+///
+/// Each type of the scheduled events must be registered with `register`,
+/// since the handlers sending them are added to the world per type.
+#[derive(Component)]
+pub struct Scheduler {
+    timers: Vec<Timer>,
+    next_handle: u64,
+    time_scale: f64,
+    paused: bool,
+    // Ticks received, regardless of the pause
+    tick: u64,
+    // Current time of each of the clocks
+    scaled: f64,
+    real: f64,
+    ticks: f64,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler {
+            timers: Vec::new(),
+            next_handle: 0,
+            time_scale: 1.0,
+            paused: false,
+            tick: 0,
+            scaled: 0.0,
+            real: 0.0,
+            ticks: 0.0,
+        }
+    }
+
+    fn clock(&self, delay: Delay) -> (Clock, f64) {
+        match delay {
+            Delay::Seconds(seconds) => (Clock::Scaled, seconds.max(0.0) as f64),
+            Delay::RealSeconds(seconds) => (Clock::Real, seconds.max(0.0) as f64),
+            Delay::Ticks(ticks) => (Clock::Ticks, ticks as f64),
+        }
+    }
+
+    fn now(&self, clock: Clock) -> f64 {
+        match clock {
+            Clock::Scaled => self.scaled,
+            Clock::Real => self.real,
+            Clock::Ticks => self.ticks,
+        }
+    }
+
+    fn push<E: GlobalEvent + Send + Sync + 'static>(
+        &mut self,
+        delay: Delay,
+        repeating: bool,
+        make: MakeEvent<E>,
+    ) -> TimerHandle {
+        let (clock, length) = self.clock(delay);
+        let handle = TimerHandle(self.next_handle);
+        self.next_handle += 1;
+        self.timers.push(Timer {
+            handle,
+            clock,
+            due: self.now(clock) + length,
+            // Zero interval would fire on each tick, that's at least a tick
+            interval: repeating.then_some(length.max(f64::EPSILON)),
+            scheduled: self.tick,
+            event: TypeId::of::<E>(),
+            make: Box::new(make),
+        });
+        handle
+    }
+
+    /// Sends the event once after the delay.
+    pub fn schedule<E: GlobalEvent + Send + Sync + 'static>(
+        &mut self,
+        delay: Delay,
+        event: E,
+    ) -> TimerHandle {
+        let mut event = Some(event);
+        self.push::<E>(
+            delay,
+            false,
+            Box::new(move || event.take().expect("One-shot timer fired twice")),
+        )
+    }
+
+    /// Sends the event each interval, starting after the first one,
+    /// until the timer is cancelled.
+    pub fn schedule_repeating<E: GlobalEvent + Clone + Send + Sync + 'static>(
+        &mut self,
+        interval: Delay,
+        event: E,
+    ) -> TimerHandle {
+        self.push::<E>(interval, true, Box::new(move || event.clone()))
+    }
+
+    /// Cancels the scheduled event. Returns `false` if it's already sent
+    /// (for the one-shot events) or cancelled.
+    pub fn cancel(&mut self, handle: TimerHandle) -> bool {
+        let count = self.timers.len();
+        self.timers.retain(|timer| timer.handle != handle);
+        self.timers.len() != count
+    }
+
+    /// Whether the event is still to be sent.
+    pub fn is_scheduled(&self, handle: TimerHandle) -> bool {
+        self.timers.iter().any(|timer| timer.handle == handle)
+    }
+
+    /// Time left until the event is sent, in the units of its delay.
+    pub fn remaining(&self, handle: TimerHandle) -> Option<f64> {
+        let timer = self.timers.iter().find(|timer| timer.handle == handle)?;
+        Some((timer.due - self.now(timer.clock)).max(0.0))
+    }
+
+    /// Cancels all the scheduled events.
+    pub fn clear(&mut self) {
+        self.timers.clear();
+    }
+
+    /// Speed of the scaled time, 1.0 by default. Negative values are clamped to zero.
+    pub fn set_time_scale(&mut self, scale: f32) {
+        self.time_scale = scale.max(0.0) as f64;
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale as f32
+    }
+
+    /// Stops the scaled time and the ticks. Only the `Delay::RealSeconds` events are sent.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Scaled time passed since the scheduler is attached, in seconds.
+    pub fn time(&self) -> f64 {
+        self.scaled
+    }
+
+    fn advance(&mut self, delta: f32) {
+        let delta = delta.max(0.0) as f64;
+        self.tick += 1;
+        self.real += delta;
+        if !self.paused {
+            self.scaled += delta * self.time_scale;
+            self.ticks += 1.0;
+        }
+    }
+
+    fn fire<E: GlobalEvent + 'static>(&mut self, mut send: impl FnMut(E)) {
+        let (tick, scaled, real, ticks) = (self.tick, self.scaled, self.real, self.ticks);
+        self.timers.retain_mut(|timer| {
+            let now = match timer.clock {
+                Clock::Scaled => scaled,
+                Clock::Real => real,
+                Clock::Ticks => ticks,
+            };
+            if timer.event != TypeId::of::<E>() || timer.due > now || timer.scheduled == tick {
+                return true;
+            }
+
+            let make = timer
+                .make
+                .downcast_mut::<MakeEvent<E>>()
+                .expect("Timer event type mismatch");
+            send(make());
+
+            match timer.interval {
+                Some(interval) => {
+                    let missed = ((now - timer.due) / interval).floor();
+                    timer.due += (missed + 1.0) * interval;
+                    true
+                }
+                None => false,
+            }
+        });
+    }
+
+    /// Adds the handler sending the scheduled events of the type.
+    /// Registering the same type again does nothing.
+    pub fn register<E: GlobalEvent + Send + Sync + 'static>(world: &mut World) {
+        fn fire_handler<E: GlobalEvent + Send + Sync + 'static>(
+            _: Receiver<TickEvent>,
+            mut scheduler: Single<&mut Scheduler>,
+            mut sender: Sender<E>,
+        ) {
+            scheduler.fire::<E>(|event| sender.send(event));
+        }
+
+        world.add_handler(fire_handler::<E>);
+    }
+
+    /// Spawns the scheduler in the world. Its clocks are advanced on each `TickEvent`
+    /// before the other handlers, so the events scheduled in them are sent
+    /// on the next tick at the earliest.
+    pub fn attach_to_ecs(self, world: &mut World) {
+        let entity = world.spawn();
+        world.insert(entity, self);
+
+        fn advance_handler(r: Receiver<TickEvent>, mut scheduler: Single<&mut Scheduler>) {
+            scheduler.advance(r.event.delta);
+        }
+
+        world.add_handler(advance_handler.high());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(GlobalEvent, Clone, Debug, PartialEq)]
+    struct Ping(u32);
+
+    // Advances the clocks and collects the sent events, as on a tick
+    fn step(scheduler: &mut Scheduler, delta: f32) -> Vec<u32> {
+        scheduler.advance(delta);
+        let mut sent = Vec::new();
+        scheduler.fire::<Ping>(|Ping(id)| sent.push(id));
+        sent
+    }
+
+    #[test]
+    fn seconds_delay_follows_scaled_time() {
+        let mut scheduler = Scheduler::new();
+        let handle = scheduler.schedule(Delay::Seconds(1.0), Ping(1));
+        assert_eq!(step(&mut scheduler, 0.5), vec![]);
+        assert_eq!(scheduler.remaining(handle), Some(0.5));
+        assert_eq!(step(&mut scheduler, 0.5), vec![1]);
+        assert!(!scheduler.is_scheduled(handle));
+        assert_eq!(step(&mut scheduler, 1.0), vec![]);
+    }
+
+    #[test]
+    fn real_seconds_ignore_pause_and_time_scale() {
+        let mut scheduler = Scheduler::new();
+        scheduler.set_paused(true);
+        scheduler.set_time_scale(0.0);
+        scheduler.schedule(Delay::RealSeconds(1.0), Ping(1));
+        scheduler.schedule(Delay::Seconds(1.0), Ping(2));
+        assert_eq!(step(&mut scheduler, 0.5), vec![]);
+        assert_eq!(step(&mut scheduler, 0.5), vec![1]);
+        assert_eq!(scheduler.time(), 0.0);
+    }
+
+    #[test]
+    fn ticks_delay_counts_unpaused_ticks() {
+        let mut scheduler = Scheduler::new();
+        scheduler.schedule(Delay::Ticks(2), Ping(1));
+        assert_eq!(step(&mut scheduler, 0.0), vec![]);
+        scheduler.set_paused(true);
+        assert_eq!(step(&mut scheduler, 0.0), vec![]);
+        assert_eq!(step(&mut scheduler, 0.0), vec![]);
+        scheduler.set_paused(false);
+        assert_eq!(step(&mut scheduler, 0.0), vec![1]);
+    }
+
+    #[test]
+    fn time_scale_changes_scaled_delays() {
+        let mut scheduler = Scheduler::new();
+        scheduler.set_time_scale(2.0);
+        scheduler.schedule(Delay::Seconds(1.0), Ping(1));
+        assert_eq!(step(&mut scheduler, 0.5), vec![1]);
+        assert_eq!(scheduler.time(), 1.0);
+
+        scheduler.set_time_scale(-1.0);
+        assert_eq!(scheduler.time_scale(), 0.0);
+        scheduler.schedule(Delay::Seconds(1.0), Ping(2));
+        assert_eq!(step(&mut scheduler, 10.0), vec![]);
+    }
+
+    #[test]
+    fn pause_stops_scaled_time() {
+        let mut scheduler = Scheduler::new();
+        scheduler.schedule(Delay::Seconds(1.0), Ping(1));
+        assert_eq!(step(&mut scheduler, 0.5), vec![]);
+        scheduler.set_paused(true);
+        assert_eq!(step(&mut scheduler, 10.0), vec![]);
+        assert_eq!(scheduler.time(), 0.5);
+        scheduler.set_paused(false);
+        assert_eq!(step(&mut scheduler, 0.5), vec![1]);
+    }
+
+    #[test]
+    fn cancelled_events_are_not_sent() {
+        let mut scheduler = Scheduler::new();
+        let cancelled = scheduler.schedule(Delay::Seconds(1.0), Ping(1));
+        let sent = scheduler.schedule(Delay::Seconds(1.0), Ping(2));
+        assert!(scheduler.cancel(cancelled));
+        assert!(!scheduler.cancel(cancelled));
+        assert_eq!(step(&mut scheduler, 1.0), vec![2]);
+        assert!(!scheduler.cancel(sent));
+
+        let repeating = scheduler.schedule_repeating(Delay::Seconds(1.0), Ping(3));
+        assert_eq!(step(&mut scheduler, 1.0), vec![3]);
+        assert!(scheduler.cancel(repeating));
+        assert_eq!(step(&mut scheduler, 1.0), vec![]);
+    }
+
+    #[test]
+    fn repeating_events_skip_missed_intervals() {
+        let mut scheduler = Scheduler::new();
+        let handle = scheduler.schedule_repeating(Delay::Seconds(1.0), Ping(1));
+        assert_eq!(step(&mut scheduler, 0.5), vec![]);
+        // Three intervals passed, but the event is sent once
+        assert_eq!(step(&mut scheduler, 3.0), vec![1]);
+        assert_eq!(scheduler.remaining(handle), Some(0.5));
+        assert_eq!(step(&mut scheduler, 0.5), vec![1]);
+        assert_eq!(step(&mut scheduler, 0.5), vec![]);
+    }
+
+    #[test]
+    fn events_are_not_sent_on_the_tick_they_are_scheduled_on() {
+        let mut scheduler = Scheduler::new();
+        scheduler.advance(0.1);
+        // As if scheduled by a handler of the current tick
+        scheduler.schedule(Delay::Seconds(0.0), Ping(1));
+        let mut sent = Vec::new();
+        scheduler.fire::<Ping>(|Ping(id)| sent.push(id));
+        assert_eq!(sent, vec![]);
+        assert_eq!(step(&mut scheduler, 0.1), vec![1]);
+    }
+
+    #[derive(Component, Default)]
+    struct Received {
+        ids: Vec<u32>,
+    }
+
+    #[test]
+    fn registering_twice_sends_events_once() {
+        let mut world = World::new();
+        let mut scheduler = Scheduler::new();
+        scheduler.schedule(Delay::Ticks(1), Ping(1));
+        scheduler.attach_to_ecs(&mut world);
+        Scheduler::register::<Ping>(&mut world);
+        let handlers = world.handlers().iter().count();
+        Scheduler::register::<Ping>(&mut world);
+        assert_eq!(world.handlers().iter().count(), handlers);
+
+        let entity = world.spawn();
+        world.insert(entity, Received::default());
+        world.add_handler(|r: Receiver<Ping>, mut received: Single<&mut Received>| {
+            received.ids.push(r.event.0);
+        });
+
+        for frame in 0..3 {
+            world.send(TickEvent {
+                frame,
+                delta: 0.1,
+                time: frame as f32 * 0.1,
+                alpha: 1.0,
+            });
+        }
+        assert_eq!(world.get::<Received>(entity).unwrap().ids, vec![1]);
+    }
+}
@@ -0,0 +1,233 @@
+use evenio::component::Component;
+use evenio::entity::EntityId;
+use evenio::world::World;
+use glam::{Mat3, Mat4, Quat, Vec2, Vec3, Vec4};
+use std::hash::{Hash, Hasher};
+
+/// Hashes the simulation state of the component. Used to check that
+/// the same input produces the same world on every run (see `WorldHasher`).
+/// Hash only what affects the simulation: skip the caches, the handles
+/// of the GPU resources, etc.
+pub trait StateHash {
+    fn state_hash<H: Hasher>(&self, state: &mut H);
+}
+
+macro_rules! impl_basic {
+    ($($t:ty),*) => {
+        $(
+            impl StateHash for $t {
+                #[inline]
+                fn state_hash<H: Hasher>(&self, state: &mut H) {
+                    self.hash(state);
+                }
+            }
+        )*
+    };
+}
+
+impl_basic!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, String, char, bool);
+
+impl StateHash for f32 {
+    #[inline]
+    fn state_hash<H: Hasher>(&self, state: &mut H) {
+        // Any difference in the bits is a desync, even between -0 and +0
+        self.to_bits().hash(state);
+    }
+}
+
+impl StateHash for f64 {
+    #[inline]
+    fn state_hash<H: Hasher>(&self, state: &mut H) {
+        self.to_bits().hash(state);
+    }
+}
+
+impl StateHash for str {
+    fn state_hash<H: Hasher>(&self, state: &mut H) {
+        self.hash(state);
+    }
+}
+
+impl<T: StateHash> StateHash for [T] {
+    fn state_hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for item in self {
+            item.state_hash(state);
+        }
+    }
+}
+
+impl<T: StateHash, const N: usize> StateHash for [T; N] {
+    fn state_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().state_hash(state);
+    }
+}
+
+impl<T: StateHash> StateHash for Vec<T> {
+    fn state_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().state_hash(state);
+    }
+}
+
+impl<T: StateHash> StateHash for Option<T> {
+    fn state_hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            None => 0u8.hash(state),
+            Some(value) => {
+                1u8.hash(state);
+                value.state_hash(state);
+            }
+        }
+    }
+}
+
+impl<A: StateHash, B: StateHash> StateHash for (A, B) {
+    fn state_hash<H: Hasher>(&self, state: &mut H) {
+        self.0.state_hash(state);
+        self.1.state_hash(state);
+    }
+}
+
+impl StateHash for EntityId {
+    fn state_hash<H: Hasher>(&self, state: &mut H) {
+        self.index().0.hash(state);
+        self.generation().hash(state);
+    }
+}
+
+macro_rules! impl_glam {
+    ($($t:ty),*) => {
+        $(
+            impl StateHash for $t {
+                #[inline]
+                fn state_hash<H: Hasher>(&self, state: &mut H) {
+                    self.to_array().state_hash(state);
+                }
+            }
+        )*
+    };
+}
+
+impl_glam!(Vec2, Vec3, Vec4, Quat);
+
+impl StateHash for Mat3 {
+    fn state_hash<H: Hasher>(&self, state: &mut H) {
+        self.to_cols_array().state_hash(state);
+    }
+}
+
+impl StateHash for Mat4 {
+    fn state_hash<H: Hasher>(&self, state: &mut H) {
+        self.to_cols_array().state_hash(state);
+    }
+}
+
+/// FNV-1a. Unlike the `DefaultHasher`, it is the same in every build,
+/// and the integers are hashed little-endian, so the hashes can be stored
+/// and compared on the other machines.
+pub struct StateHasher(u64);
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        StateHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StateHasher {
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16);
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32);
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_u64(i as u64);
+    }
+}
+
+type ComponentHashFn = fn(&World, EntityId, &mut StateHasher) -> bool;
+
+/// Hashes the registered components of all the entities of the world.
+/// The entities are visited in the order of their IDs, so the hash
+/// does not depend on the archetypes the entities are stored in.
+#[derive(Default)]
+pub struct WorldHasher {
+    components: Vec<ComponentHashFn>,
+}
+
+impl WorldHasher {
+    pub fn new() -> Self {
+        WorldHasher::default()
+    }
+
+    /// Includes the component into the hash.
+    /// The order of the registration is a part of the hash.
+    pub fn register<C: Component + StateHash>(mut self) -> Self {
+        self.components.push(|world, entity, state| {
+            if let Some(component) = world.get::<C>(entity) {
+                component.state_hash(state);
+                true
+            } else {
+                false
+            }
+        });
+        self
+    }
+
+    pub fn hash(&self, world: &World) -> u64 {
+        let mut entities = world
+            .archetypes()
+            .iter()
+            .flat_map(|archetype| archetype.entity_ids().iter().copied())
+            .collect::<Vec<_>>();
+        entities.sort_by_key(|entity| (entity.index().0, entity.generation()));
+
+        let mut state = StateHasher::default();
+        for entity in entities {
+            for (i, hash) in self.components.iter().enumerate() {
+                let mut component = StateHasher::default();
+                if hash(world, entity, &mut component) {
+                    entity.state_hash(&mut state);
+                    i.hash(&mut state);
+                    component.finish().hash(&mut state);
+                }
+            }
+        }
+        state.finish()
+    }
+}
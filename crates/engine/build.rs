@@ -0,0 +1,26 @@
+use std::process::Command;
+
+// The commit and the target of the build, reported by `runtime_info`
+fn main() {
+    let sha = Command::new("git")
+        .args(["rev-parse", "--short=12", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|sha| sha.trim().to_string())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=DAWN_GIT_SHA={}", sha);
+    println!(
+        "cargo:rustc-env=DAWN_TARGET={}",
+        std::env::var("TARGET").unwrap_or_default()
+    );
+    println!(
+        "cargo:rustc-env=DAWN_PROFILE={}",
+        std::env::var("PROFILE").unwrap_or_default()
+    );
+
+    // Rebuilt on the commits and the branch switches
+    println!("cargo:rerun-if-changed=../../.git/HEAD");
+    println!("cargo:rerun-if-changed=../../.git/refs/heads");
+}
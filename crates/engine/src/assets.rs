@@ -0,0 +1,147 @@
+use dawn_assets::hot_reload::{changed_assets, FileWatcher};
+use dawn_assets::reader::{BasicReader, EnumeratedAssets, ReaderBinding};
+use dawn_dac::encryption::EncryptionKey;
+use dawn_dac::prefetch::PrefetchReader;
+use dawn_dac::reader::{verify_container, ReadLimits};
+use dawn_dac::signing::TrustConfig;
+use dawn_dac::ContainerError;
+use log::{info, warn};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Size of the payloads read ahead of the requests.
+const PREFETCH_BUDGET: usize = 64 << 20;
+/// Maximal number of the threads decompressing and deserializing the assets.
+const READ_WORKERS: usize = 4;
+/// How often the thread checks if it should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How often the container is checked for the changes with the hot reload.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+/// The rebuilt container is reopened once it's not written for that long.
+const WATCH_SETTLE: Duration = Duration::from_secs(1);
+
+/// Thread answering the requests of the hub from the DAC container.
+/// Stopped on drop.
+pub(crate) struct ContainerReader {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+// The signature is checked on the same file the assets are read from.
+// It covers the digests of the payloads, checked on every read.
+fn open(
+    path: &Path,
+    key: Option<&EncryptionKey>,
+    trust: Option<&TrustConfig>,
+) -> Result<PrefetchReader, ContainerError> {
+    let mut file = BufReader::new(File::open(path)?);
+    if let Some(trust) = trust {
+        verify_container(&mut file, trust)?;
+    }
+    let container = PrefetchReader::new(file, ReadLimits::default(), PREFETCH_BUDGET)?;
+    Ok(match key {
+        Some(key) => container.with_key(key.clone()),
+        None => container,
+    })
+}
+
+impl ContainerReader {
+    /// With the `hot_reload`, the container is reopened once it's rebuilt,
+    /// and the hub is told which assets have changed (see `dawn_assets::hot_reload`).
+    /// The `key` decrypts the encrypted assets. With the `trust`, the signature
+    /// of the container is verified on open and on every reopen.
+    pub fn spawn(
+        path: &Path,
+        binding: ReaderBinding,
+        hot_reload: bool,
+        key: Option<EncryptionKey>,
+        trust: Option<TrustConfig>,
+    ) -> Result<Self, ContainerError> {
+        let mut container = open(path, key.as_ref(), trust.as_ref())?;
+        info!("Opened asset container {}", path.display());
+        let path = path.to_path_buf();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = std::thread::Builder::new()
+            .name("dac-reader".to_string())
+            .spawn({
+                let stop = Arc::clone(&stop);
+                move || {
+                    let mut reader = BasicReader::new();
+                    reader.bind(binding);
+                    let mut watcher = hot_reload.then(|| {
+                        FileWatcher::new(vec![path.clone()], WATCH_INTERVAL, WATCH_SETTLE)
+                    });
+                    while !stop.load(Ordering::Relaxed) {
+                        reader.process_events_pooled(
+                            || {
+                                let manifest = container.manifest();
+                                Ok(EnumeratedAssets {
+                                    headers: manifest.headers.clone(),
+                                    variants: manifest.variants.clone(),
+                                    guids: manifest.guids.clone(),
+                                })
+                            },
+                            |id| Ok(container.read(id)?),
+                            |ids| container.prefetch(&ids),
+                            READ_WORKERS,
+                            POLL_INTERVAL,
+                        );
+
+                        let changed = watcher.as_mut().is_some_and(|w| !w.poll().is_empty());
+                        if changed {
+                            reopen(&path, key.as_ref(), trust.as_ref(), &mut container, &reader);
+                        }
+                    }
+                }
+            })?;
+
+        Ok(ContainerReader {
+            stop,
+            handle: Some(handle),
+        })
+    }
+}
+
+fn reopen(
+    path: &Path,
+    key: Option<&EncryptionKey>,
+    trust: Option<&TrustConfig>,
+    container: &mut PrefetchReader,
+    reader: &BasicReader,
+) {
+    match open(path, key, trust) {
+        Ok(reopened) => {
+            let changed =
+                changed_assets(&container.manifest().headers, &reopened.manifest().headers);
+            info!(
+                "Asset container {} changed, {} assets to reload",
+                path.display(),
+                changed.len()
+            );
+            *container = reopened;
+            reader.notify_changed(changed);
+        }
+        Err(e) => warn!(
+            "Failed to reopen the changed asset container {}: {}",
+            path.display(),
+            e
+        ),
+    }
+}
+
+impl Drop for ContainerReader {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                warn!("Asset reader thread panicked");
+            }
+        }
+    }
+}
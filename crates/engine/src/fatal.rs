@@ -0,0 +1,158 @@
+use crate::runtime::runtime_info;
+use crate::EngineError;
+use dawn_graphics::renderer::{capabilities, RendererFailed};
+use dawn_graphics::view::error_box;
+use evenio::component::Component;
+use evenio::entity::EntityId;
+use evenio::event::Receiver;
+use evenio::fetch::Single;
+use evenio::world::World;
+use log::{error, info, warn};
+use std::backtrace::Backtrace;
+use std::fmt::Write;
+use std::panic::PanicHookInfo;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Only the first fatal error is presented, the rest are usually its consequences
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// How the fatal errors (the errors of `EngineBuilder::run` and the panics)
+/// are presented to the user. The console is not seen by anybody in the
+/// release builds, so by default they are shown in the native message box
+/// with a short explanation, and the details are written to the report file.
+#[derive(Debug, Clone)]
+pub struct FatalErrorConfig {
+    /// Show the native message box. Enabled in the release builds by default.
+    pub message_box: bool,
+    /// Directory the reports are written to. `None` disables the reports.
+    /// By default, the `crash` directory in the data directory (see `Paths::data_dir`).
+    pub report_dir: Option<PathBuf>,
+    /// Present the panics of any thread too. The previous panic hook is still called.
+    /// Enabled in the release builds by default.
+    pub panics: bool,
+}
+
+impl Default for FatalErrorConfig {
+    fn default() -> Self {
+        FatalErrorConfig {
+            message_box: !cfg!(debug_assertions),
+            report_dir: None,
+            panics: !cfg!(debug_assertions),
+        }
+    }
+}
+
+// Fatal error presentation resolved for the application
+#[derive(Debug, Clone)]
+pub(crate) struct FatalErrorReporter {
+    app: String,
+    config: FatalErrorConfig,
+}
+
+impl FatalErrorReporter {
+    pub(crate) fn new(app: String, config: FatalErrorConfig) -> Self {
+        FatalErrorReporter { app, config }
+    }
+
+    pub(crate) fn report_error(&self, err: &EngineError) {
+        self.report(&err.user_message(), &format!("{}\n\n{:?}", err, err));
+    }
+
+    // Wraps the current panic hook, so the panic is still printed before the box is shown
+    pub(crate) fn install_panic_hook(&self) {
+        if !self.config.panics {
+            return;
+        }
+
+        let reporter = self.clone();
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info: &PanicHookInfo| {
+            previous(info);
+
+            let thread = std::thread::current();
+            let details = format!(
+                "Thread '{}' panicked: {}\n\n{}",
+                thread.name().unwrap_or("<unnamed>"),
+                info,
+                Backtrace::force_capture()
+            );
+            reporter.report(
+                "The application has encountered an unexpected error and has to close.",
+                &details,
+            );
+        }));
+    }
+
+    fn report(&self, message: &str, details: &str) {
+        if REPORTED.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        error!("Fatal error: {}", details);
+
+        let report = self.config.report_dir.as_deref().and_then(|dir| {
+            write_report(dir, &self.app, message, details)
+                .inspect_err(|err| warn!("Failed to write the error report: {}", err))
+                .ok()
+        });
+        if let Some(path) = &report {
+            info!("Error report written to {}", path.display());
+        }
+
+        if self.config.message_box {
+            let text = match &report {
+                Some(path) => format!("{}\n\nDetails were saved to {}", message, path.display()),
+                None => message.to_string(),
+            };
+            if !error_box(&self.app, &text) {
+                warn!("Cannot show the error message box");
+            }
+        }
+    }
+}
+
+fn write_report(dir: &Path, app: &str, message: &str, details: &str) -> std::io::Result<PathBuf> {
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let mut report = String::new();
+    let _ = writeln!(report, "Application: {}", app);
+    let _ = writeln!(report, "Time: {} (UNIX)", time);
+    let _ = write!(report, "{}", runtime_info());
+    let _ = writeln!(
+        report,
+        "System: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    if let Some(capabilities) = capabilities() {
+        let _ = writeln!(report, "\nRenderer capabilities:\n{}", capabilities);
+    }
+    let _ = writeln!(report, "\n{}\n\n{}", message, details);
+
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("crash-{}.txt", time));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
+
+// Keeps the failure of the renderer thread until the main loop is finished
+#[derive(Component, Default)]
+pub(crate) struct RendererFailure {
+    pub(crate) reason: Option<String>,
+}
+
+pub(crate) fn attach_renderer_failure(world: &mut World) -> EntityId {
+    let entity = world.spawn();
+    world.insert(entity, RendererFailure::default());
+
+    fn handler(r: Receiver<RendererFailed>, mut failure: Single<&mut RendererFailure>) {
+        failure.reason = Some(r.event.reason.clone());
+    }
+
+    world.add_handler(handler);
+    entity
+}
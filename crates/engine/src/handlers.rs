@@ -0,0 +1,67 @@
+use dawn_assets::hub::AssetHubMonitorEvent;
+use dawn_audio::player::PlayerMonitorEvent;
+use dawn_ecs::events::ExitEvent;
+use dawn_ecs::main_loop::MainLoopMonitorEvent;
+use dawn_graphics::input::{InputEvent, KeyCode};
+use dawn_graphics::renderer::RendererMonitorEvent;
+use evenio::event::{Receiver, Sender};
+use evenio::handler::IntoHandler;
+use evenio::world::World;
+use log::info;
+
+pub(crate) fn attach_quit_on_escape(world: &mut World) {
+    fn handler(r: Receiver<InputEvent>, mut sender: Sender<ExitEvent>) {
+        if let InputEvent::KeyPress(KeyCode::Escape) = r.event {
+            info!("Escape pressed, exiting");
+            sender.send(ExitEvent);
+        }
+    }
+
+    world.add_handler(handler);
+}
+
+pub(crate) fn attach_monitoring_log(world: &mut World) {
+    fn main_loop_handler(r: Receiver<MainLoopMonitorEvent>) {
+        info!(
+            "Main loop: {:.1} TPS, load {:.1}%",
+            r.event.tps.average(),
+            r.event.load.average() * 100.0
+        );
+    }
+
+    fn renderer_handler(r: Receiver<RendererMonitorEvent>) {
+        info!(
+            "Renderer: {:.1} FPS, render {:?}, {:.0} draw calls, {} triangles, {} state changes, {} bytes uploaded, output {}",
+            r.event.fps.average(),
+            r.event.render.average(),
+            r.event.draw_calls.average(),
+            r.event.frame_stats.triangles,
+            r.event.frame_stats.state_changes,
+            r.event.frame_stats.uploaded_bytes,
+            r.event.output_format
+        );
+    }
+
+    fn player_handler(r: Receiver<PlayerMonitorEvent>) {
+        info!(
+            "Audio: load {:.1}%, {} underruns, buffer {} samples",
+            r.event.load.average() * 100.0,
+            r.event.underruns,
+            r.event.buffer_size
+        );
+    }
+
+    fn hub_handler(r: Receiver<AssetHubMonitorEvent>) {
+        info!(
+            "Assets: {:.1} reads/s, IO wait {:?}, decode {:?}",
+            r.event.reads.average(),
+            r.event.io_wait.average(),
+            r.event.decode.average()
+        );
+    }
+
+    world.add_handler(main_loop_handler.low());
+    world.add_handler(renderer_handler.low());
+    world.add_handler(player_handler.low());
+    world.add_handler(hub_handler.low());
+}
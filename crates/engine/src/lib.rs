@@ -0,0 +1,579 @@
+//! High-level entry point of the engine. Wires the window, renderer, audio player
+//! and the asset container together, attaches them to the ECS and runs the main loop:
+//!
+//! ```no_run
+//! use dawn_engine::Engine;
+//!
+//! Engine::builder()
+//!     .with_assets("game.dac")
+//!     .with_monitoring(true)
+//!     .run(|_world| {
+//!         // Spawn the entities and add the game handlers here
+//!     })
+//!     .unwrap();
+//! ```
+
+mod assets;
+mod fatal;
+mod handlers;
+mod runtime;
+mod signals;
+
+use crate::assets::ContainerReader;
+use crate::fatal::{FatalErrorReporter, RendererFailure};
+use dawn_assets::hub::AssetHub;
+use dawn_assets::requests::AssetRequest;
+use dawn_assets::AssetType;
+use dawn_audio::assets::{
+    AudioAssetFactory, CaptionsAssetFactory, MusicAssetFactory, NotesAssetFactory,
+};
+use dawn_audio::backend::PlayerBackendConfig;
+use dawn_audio::captions::CaptionPlayer;
+use dawn_audio::entities::sinks::InterleavedSink;
+use dawn_audio::entities::Source;
+use dawn_audio::player::{Player, PlayerError};
+use dawn_audio::SampleRate;
+use dawn_dac::encryption::EncryptionKey;
+use dawn_dac::signing::TrustConfig;
+use dawn_dac::ContainerError;
+use dawn_ecs::av_sync::AvSyncClock;
+use dawn_ecs::cleanup::Cleanup;
+use dawn_ecs::main_loop::{
+    synchronized_loop, synchronized_loop_with_monitoring, unsynchronized_loop,
+    unsynchronized_loop_with_monitoring, FixedTimestep,
+};
+use dawn_ecs::scheduler::Scheduler;
+use dawn_graphics::accessibility::{Accessibility, AccessibilitySettings};
+use dawn_graphics::passes::chain::RenderChain;
+use dawn_graphics::passes::events::PassEventTrait;
+use dawn_graphics::renderer::{
+    RenderChainConstructor, RenderSettingsFactory, Renderer, RendererBackendConfig, RendererError,
+};
+use dawn_graphics::view::{ViewConfig, ViewSynchronization};
+use dawn_paths::Paths;
+use dawn_util::rendezvous::Rendezvous;
+use evenio::world::World;
+use log::info;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub use fatal::FatalErrorConfig;
+pub use runtime::{runtime_info, RuntimeInfo};
+
+/// Tick rate of the main loop without the window.
+const DEFAULT_TICK_RATE: f32 = 60.0;
+/// Time spent each tick on the queued despawns and the deferred drops.
+const DEFAULT_CLEANUP_BUDGET: Duration = Duration::from_millis(2);
+/// Time the shutdown requested by the signal may take before the process is killed.
+const DEFAULT_SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum EngineError {
+    /// The window is opened by the renderer, so they are configured together.
+    WindowWithoutRenderer,
+    RendererWithoutWindow,
+    Renderer(RendererError),
+    Audio(PlayerError),
+    Assets(PathBuf, ContainerError),
+    /// The renderer thread has stopped with the error, e.g. the GL context cannot be created.
+    RendererFailed(String),
+}
+
+impl EngineError {
+    /// Short explanation of the error for the players, without the technical details.
+    pub fn user_message(&self) -> String {
+        match self {
+            EngineError::WindowWithoutRenderer | EngineError::RendererWithoutWindow => {
+                "The application is misconfigured and cannot start.".to_string()
+            }
+            EngineError::Renderer(_) | EngineError::RendererFailed(_) => {
+                "The graphics cannot be initialized. \
+                Make sure the graphics drivers are installed, up to date and support OpenGL."
+                    .to_string()
+            }
+            EngineError::Audio(_) => "No audio device can be opened. \
+                Make sure the audio output device is connected and enabled."
+                .to_string(),
+            EngineError::Assets(path, _) => format!(
+                "The game data file {} is missing or damaged. Try reinstalling the game.",
+                path.file_name()
+                    .unwrap_or(path.as_os_str())
+                    .to_string_lossy()
+            ),
+        }
+    }
+}
+
+impl Display for EngineError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineError::WindowWithoutRenderer => {
+                write!(f, "Window is configured without the renderer")
+            }
+            EngineError::RendererWithoutWindow => {
+                write!(f, "Renderer is configured without the window")
+            }
+            EngineError::Renderer(err) => write!(f, "Failed to create renderer: {}", err),
+            EngineError::Audio(err) => write!(f, "Failed to create audio player: {}", err),
+            EngineError::RendererFailed(reason) => write!(f, "Renderer failed: {}", reason),
+            EngineError::Assets(path, err) => {
+                write!(
+                    f,
+                    "Failed to open asset container {}: {}",
+                    path.display(),
+                    err
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+/// Configuration of the audio player.
+pub struct AudioConfig {
+    pub sample_rate: SampleRate,
+    pub backend: PlayerBackendConfig,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        AudioConfig {
+            sample_rate: 44100,
+            backend: PlayerBackendConfig::default(),
+        }
+    }
+}
+
+// State shared by the subsystems while they are set up
+struct SetupContext<'a> {
+    world: &'a mut World,
+    hub: Option<&'a mut AssetHub>,
+    cache_dir: Option<PathBuf>,
+    monitoring: bool,
+    // Tick rate the renderer interpolates at, if decoupled from the logic
+    interpolation: Option<f32>,
+}
+
+type RendererSetup = Box<dyn FnOnce(ViewConfig, &mut SetupContext) -> Result<(), EngineError>>;
+type AudioSetup = Box<dyn FnOnce(&mut SetupContext) -> Result<(), EngineError>>;
+
+pub struct Engine;
+
+impl Engine {
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder {
+            window: None,
+            renderer: None,
+            audio: None,
+            assets: None,
+            assets_key: None,
+            assets_trust: None,
+            paths: None,
+            accessibility: AccessibilitySettings::default(),
+            monitoring: false,
+            hot_reload: false,
+            quit_on_escape: true,
+            tick_rate: DEFAULT_TICK_RATE,
+            decoupled: false,
+            fixed_rate: None,
+            cleanup_budget: DEFAULT_CLEANUP_BUDGET,
+            signals: true,
+            shutdown_grace: DEFAULT_SHUTDOWN_GRACE,
+            fatal_errors: None,
+        }
+    }
+}
+
+/// Collects the subsystems of the application. Everything is created in `run`.
+pub struct EngineBuilder {
+    window: Option<ViewConfig>,
+    renderer: Option<RendererSetup>,
+    audio: Option<AudioSetup>,
+    assets: Option<PathBuf>,
+    assets_key: Option<EncryptionKey>,
+    assets_trust: Option<TrustConfig>,
+    paths: Option<Paths>,
+    accessibility: AccessibilitySettings,
+    monitoring: bool,
+    hot_reload: bool,
+    quit_on_escape: bool,
+    tick_rate: f32,
+    decoupled: bool,
+    fixed_rate: Option<f32>,
+    cleanup_budget: Duration,
+    signals: bool,
+    shutdown_grace: Duration,
+    fatal_errors: Option<FatalErrorConfig>,
+}
+
+impl EngineBuilder {
+    /// Opens the window. Requires the renderer (see `with_renderer`).
+    /// The synchronization of the window is set up by the engine.
+    pub fn with_window(mut self, config: ViewConfig) -> Self {
+        self.window = Some(config);
+        self
+    }
+
+    /// Renders the window with the pipeline created by the `constructor`
+    /// (see `Renderer::new`). The factory bindings of the config left empty
+    /// are filled with the ones of the asset container, if any.
+    /// The shader programs are cached in the cache directory (see `with_paths`)
+    /// unless the config sets it. The settings assets are loaded by the
+    /// `RenderSettingsFactory`, to be applied with the `RenderSettingsRequest`.
+    pub fn with_renderer<C, E>(
+        mut self,
+        config: RendererBackendConfig,
+        constructor: impl RenderChainConstructor<C, E>,
+    ) -> Self
+    where
+        C: RenderChain<E>,
+        E: PassEventTrait,
+    {
+        self.renderer = Some(Box::new(move |view, ctx| {
+            let mut config = config;
+            if let Some(hub) = ctx.hub.as_deref_mut() {
+                let mut bind = |binding: &mut Option<_>, asset_type| {
+                    if binding.is_none() {
+                        *binding = Some(hub.get_factory_biding(asset_type));
+                    }
+                };
+                bind(&mut config.texture_factory_binding, AssetType::Texture);
+                bind(&mut config.shader_factory_binding, AssetType::Shader);
+                bind(&mut config.mesh_factory_binding, AssetType::Mesh);
+                bind(&mut config.material_factory_binding, AssetType::Material);
+                bind(&mut config.font_factory_binding, AssetType::Font);
+
+                let mut settings = RenderSettingsFactory::new();
+                settings.bind(hub.get_factory_biding(AssetType::Settings));
+                settings.attach_to_ecs(ctx.world);
+                let entity = ctx.world.spawn();
+                ctx.world.insert(entity, settings);
+            }
+            if config.program_cache_dir.is_none() {
+                config.program_cache_dir = ctx.cache_dir.as_ref().map(|dir| dir.join("programs"));
+            }
+
+            let mut renderer = if ctx.monitoring {
+                Renderer::<E>::new_with_monitoring(view, config, constructor)
+            } else {
+                Renderer::<E>::new(view, config, constructor)
+            }
+            .map_err(EngineError::Renderer)?;
+            renderer.set_interpolation(ctx.interpolation);
+            renderer.attach_to_ecs(ctx.world);
+            Ok(())
+        }));
+        self
+    }
+
+    /// Plays the audio of the sink. With the asset container, the audio,
+    /// notes, music and captions assets are loaded too.
+    /// The captions are shown by the `CaptionPlayer` component.
+    pub fn with_audio<S>(mut self, config: AudioConfig, sink: InterleavedSink<S>) -> Self
+    where
+        S: Source + Send + Sync + 'static,
+    {
+        self.audio = Some(Box::new(move |ctx| {
+            if let Some(hub) = ctx.hub.as_deref_mut() {
+                let mut audio = AudioAssetFactory::new(config.sample_rate);
+                audio.bind(hub.get_factory_biding(AssetType::Audio));
+                audio.attach_to_ecs(ctx.world);
+                let mut notes = NotesAssetFactory::new();
+                notes.bind(hub.get_factory_biding(AssetType::Notes));
+                notes.attach_to_ecs(ctx.world);
+                let mut music = MusicAssetFactory::new();
+                music.bind(hub.get_factory_biding(AssetType::Music));
+                music.attach_to_ecs(ctx.world);
+                let mut captions = CaptionsAssetFactory::new();
+                captions.bind(hub.get_factory_biding(AssetType::Captions));
+                captions.attach_to_ecs(ctx.world);
+
+                let entity = ctx.world.spawn();
+                ctx.world.insert(entity, audio);
+                ctx.world.insert(entity, notes);
+                ctx.world.insert(entity, music);
+                ctx.world.insert(entity, captions);
+            }
+
+            let player = Player::new(config.sample_rate, config.backend, sink, ctx.monitoring)
+                .map_err(EngineError::Audio)?;
+            CaptionPlayer::new(player.clock()).attach_to_ecs(ctx.world);
+            player.attach_to_ecs(ctx.world);
+            Ok(())
+        }));
+        self
+    }
+
+    /// Reads the assets from the DAC container. The assets are enumerated
+    /// on start, the rest is requested through the `AssetHub` component.
+    pub fn with_assets(mut self, path: impl Into<PathBuf>) -> Self {
+        self.assets = Some(path.into());
+        self
+    }
+
+    /// Key decrypting the encrypted assets of the container
+    /// (see `dawn_dac::writer::ContainerOptions::encryption`).
+    pub fn with_assets_key(mut self, key: EncryptionKey) -> Self {
+        self.assets_key = Some(key);
+        self
+    }
+
+    /// Verifies the signature of the container before reading the assets
+    /// (see `dawn_dac::reader::verify_container`). With `TrustPolicy::Reject`
+    /// the engine refuses to start with an untrusted container, and the
+    /// rebuilt one is not reloaded. The payloads not matching the signed
+    /// digests fail to load either way.
+    pub fn with_assets_trust(mut self, trust: TrustConfig) -> Self {
+        self.assets_trust = Some(trust);
+        self
+    }
+
+    /// Resolves the paths not set explicitly: the asset container
+    /// (if `with_assets` is not called) and the cache directory.
+    pub fn with_paths(mut self, paths: Paths) -> Self {
+        self.paths = Some(paths);
+        self
+    }
+
+    /// Initial accessibility settings, e.g. loaded from the settings of the game.
+    /// The `Accessibility` component is always attached, change the settings
+    /// live with the `SetAccessibility` event.
+    pub fn with_accessibility(mut self, settings: AccessibilitySettings) -> Self {
+        self.accessibility = settings;
+        self
+    }
+
+    /// Enables the monitoring of all the subsystems and logs it every second.
+    pub fn with_monitoring(mut self, enabled: bool) -> Self {
+        self.monitoring = enabled;
+        self
+    }
+
+    /// Reloads the assets changed in the asset container while the game runs,
+    /// e.g. once it's rebuilt by `dawn_dacgen::update_container`.
+    /// The changed assets are reported with `AssetHubEvent::AssetReloaded`.
+    /// Meant for the development builds.
+    pub fn with_hot_reload(mut self, enabled: bool) -> Self {
+        self.hot_reload = enabled;
+        self
+    }
+
+    /// Stops the main loop when Escape is pressed. Enabled by default.
+    pub fn with_quit_on_escape(mut self, enabled: bool) -> Self {
+        self.quit_on_escape = enabled;
+        self
+    }
+
+    /// Tick rate of the main loop without the window or with the decoupled
+    /// rendering (see `with_decoupled_rendering`). Otherwise, the loop is
+    /// synchronized with the renderer.
+    pub fn with_tick_rate(mut self, tick_rate: f32) -> Self {
+        self.tick_rate = tick_rate;
+        self
+    }
+
+    /// Runs the logic at the fixed tick rate (see `with_tick_rate`) independently
+    /// of the renderer, which renders at its own rate (e.g. of the display)
+    /// interpolating the renderables between the last two ticks
+    /// (see `Renderer::set_interpolation`). So the logic doesn't have to run
+    /// at 144 Hz on the high refresh rate monitors. Disabled by default.
+    pub fn with_decoupled_rendering(mut self, enabled: bool) -> Self {
+        self.decoupled = enabled;
+        self
+    }
+
+    /// Sends the `FixedTickEvent` events at the fixed rate, whatever the tick rate is
+    /// (see `FixedTimestep`), and the interpolation factor in the `TickEvent::alpha`.
+    pub fn with_fixed_timestep(mut self, rate: f32) -> Self {
+        self.fixed_rate = Some(rate);
+        self
+    }
+
+    /// Time spent each tick on tearing down the things queued to the `Cleanup`
+    /// component, 2 ms by default. The rest is left for the next tick.
+    pub fn with_cleanup_budget(mut self, budget: Duration) -> Self {
+        self.cleanup_budget = budget;
+        self
+    }
+
+    /// Stops the main loop with the `ExitEvent` on SIGINT, SIGTERM and SIGHUP
+    /// (Ctrl+C, closing the console and the like on Windows), so the files being
+    /// written, e.g. the caches and the frame captures, are finished instead of
+    /// being cut off. Enabled by default. The handler is process-wide, so the
+    /// application can't install its own with it enabled.
+    pub fn with_signal_handling(mut self, enabled: bool) -> Self {
+        self.signals = enabled;
+        self
+    }
+
+    /// Time the shutdown requested by the signal may take, 5 s by default.
+    /// The process exits once it's over, or on the second signal.
+    pub fn with_shutdown_grace(mut self, grace: Duration) -> Self {
+        self.shutdown_grace = grace;
+        self
+    }
+
+    /// Presents the errors of `run` and the panics to the user (see `FatalErrorConfig`).
+    /// Without it, they are only returned and logged.
+    pub fn with_fatal_errors(mut self, config: FatalErrorConfig) -> Self {
+        self.fatal_errors = Some(config);
+        self
+    }
+
+    /// Creates everything, calls the `setup` and runs the main loop
+    /// until the `ExitEvent` is sent or the window is closed.
+    /// Fails with `EngineError::RendererFailed` if the renderer has stopped the loop.
+    /// The world has the `AvSyncClock`, the `Scheduler` and the `Cleanup` attached;
+    /// register the scheduled event types in the `setup` (see `Scheduler::register`).
+    pub fn run(mut self, setup: impl FnOnce(&mut World)) -> Result<(), EngineError> {
+        let reporter = self.fatal_errors.take().map(|mut config| {
+            if config.report_dir.is_none() {
+                config.report_dir = self
+                    .paths
+                    .as_ref()
+                    .and_then(Paths::data_dir)
+                    .map(|dir| dir.join("crash"));
+            }
+            FatalErrorReporter::new(self.app_name(), config)
+        });
+        if let Some(reporter) = &reporter {
+            reporter.install_panic_hook();
+        }
+
+        let result = self.run_inner(setup);
+        if let (Err(err), Some(reporter)) = (&result, &reporter) {
+            reporter.report_error(err);
+        }
+        result
+    }
+
+    // Name of the application shown in the titles of the message boxes
+    fn app_name(&self) -> String {
+        if let Some(paths) = &self.paths {
+            return paths.app().to_string();
+        }
+        std::env::current_exe()
+            .ok()
+            .and_then(|exe| {
+                exe.file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+            })
+            .unwrap_or_else(|| "Application".to_string())
+    }
+
+    fn run_inner(self, setup: impl FnOnce(&mut World)) -> Result<(), EngineError> {
+        info!("{}", runtime_info());
+        let mut world = World::new();
+
+        let assets = self
+            .assets
+            .or_else(|| self.paths.as_ref().and_then(Paths::assets));
+        let mut reader = None;
+        let mut hub = match &assets {
+            Some(path) => {
+                let mut hub = AssetHub::new();
+                if self.monitoring {
+                    hub.enable_monitoring();
+                }
+                reader = Some(
+                    ContainerReader::spawn(
+                        path,
+                        hub.get_read_binding(),
+                        self.hot_reload,
+                        self.assets_key.clone(),
+                        self.assets_trust.clone(),
+                    )
+                    .map_err(|err| EngineError::Assets(path.clone(), err))?,
+                );
+                Some(hub)
+            }
+            None => None,
+        };
+
+        let mut ctx = SetupContext {
+            world: &mut world,
+            hub: hub.as_mut(),
+            cache_dir: self.paths.as_ref().and_then(Paths::cache_dir),
+            monitoring: self.monitoring,
+            interpolation: self.decoupled.then_some(self.tick_rate),
+        };
+
+        // The logic and the renderer threads meet before and after each frame
+        let sync = match (self.window, self.renderer) {
+            (Some(mut view), Some(renderer)) if self.decoupled => {
+                view.synchronization = None;
+                renderer(view, &mut ctx)?;
+                None
+            }
+            (Some(mut view), Some(renderer)) => {
+                let sync = ViewSynchronization {
+                    before_frame: Rendezvous::new(2),
+                    after_frame: Rendezvous::new(2),
+                };
+                view.synchronization = Some(sync.clone());
+                renderer(view, &mut ctx)?;
+                Some(sync)
+            }
+            (Some(_), None) => return Err(EngineError::WindowWithoutRenderer),
+            (None, Some(_)) => return Err(EngineError::RendererWithoutWindow),
+            (None, None) => None,
+        };
+        if let Some(audio) = self.audio {
+            audio(&mut ctx)?;
+        }
+
+        if let Some(mut hub) = hub {
+            hub.request(AssetRequest::Enumerate);
+            hub.attach_to_ecs(&mut world);
+        }
+        Accessibility::new(self.accessibility).attach_to_ecs(&mut world);
+        // Fed by the renderer and the audio player, if any
+        AvSyncClock::new().attach_to_ecs(&mut world);
+        Scheduler::new().attach_to_ecs(&mut world);
+        Cleanup::new(self.cleanup_budget).attach_to_ecs(&mut world);
+        if let Some(rate) = self.fixed_rate {
+            FixedTimestep::new(rate).attach_to_ecs(&mut world);
+        }
+        let failure = fatal::attach_renderer_failure(&mut world);
+        if self.quit_on_escape {
+            handlers::attach_quit_on_escape(&mut world);
+        }
+        if self.monitoring {
+            handlers::attach_monitoring_log(&mut world);
+        }
+        if self.signals {
+            signals::attach_signal_handling(&mut world, self.shutdown_grace);
+        }
+
+        setup(&mut world);
+
+        info!("Starting the main loop");
+        match (sync, self.monitoring) {
+            (Some(sync), false) => {
+                synchronized_loop(&mut world, sync.before_frame, sync.after_frame)
+            }
+            (Some(sync), true) => {
+                synchronized_loop_with_monitoring(&mut world, sync.before_frame, sync.after_frame)
+            }
+            (None, false) => unsynchronized_loop(&mut world, self.tick_rate),
+            (None, true) => unsynchronized_loop_with_monitoring(&mut world, self.tick_rate),
+        }
+
+        let failure = world
+            .get::<RendererFailure>(failure)
+            .and_then(|failure| failure.reason.clone());
+
+        // The renderer and the player are stopped with the world
+        drop(world);
+        drop(reader);
+        if self.signals {
+            signals::detach_signal_handling();
+        }
+        match failure {
+            Some(reason) => Err(EngineError::RendererFailed(reason)),
+            None => Ok(()),
+        }
+    }
+}
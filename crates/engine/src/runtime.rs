@@ -0,0 +1,67 @@
+use dawn_util::build_info::CrateBuildInfo;
+use std::fmt::{Display, Formatter};
+
+/// Versions and the features of the engine crates and the build they are from.
+/// Log it on start or show it on the About screen.
+#[derive(Debug, Clone)]
+pub struct RuntimeInfo {
+    pub crates: Vec<CrateBuildInfo>,
+    /// Commit the engine was built from, `None` if built outside the git checkout.
+    pub git_sha: Option<&'static str>,
+    /// Target triple, e.g. `x86_64-unknown-linux-gnu`.
+    pub target: &'static str,
+    /// Cargo profile, `debug` or `release`.
+    pub profile: &'static str,
+}
+
+impl RuntimeInfo {
+    /// Version of the engine itself.
+    pub fn version(&self) -> &'static str {
+        env!("CARGO_PKG_VERSION")
+    }
+
+    pub fn crate_info(&self, name: &str) -> Option<&CrateBuildInfo> {
+        self.crates.iter().find(|info| info.name == name)
+    }
+}
+
+impl Display for RuntimeInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Dawn {} ({}, {}",
+            self.version(),
+            self.target,
+            self.profile
+        )?;
+        if let Some(sha) = self.git_sha {
+            write!(f, ", {}", sha)?;
+        }
+        writeln!(f, ")")?;
+        for info in &self.crates {
+            writeln!(f, "  {}", info)?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects the build info of all the engine crates.
+pub fn runtime_info() -> RuntimeInfo {
+    let git_sha = env!("DAWN_GIT_SHA");
+    RuntimeInfo {
+        crates: vec![
+            // The engine itself
+            dawn_util::crate_build_info![],
+            dawn_assets::build_info(),
+            dawn_audio::build_info(),
+            dawn_dac::build_info(),
+            dawn_ecs::build_info(),
+            dawn_graphics::build_info(),
+            dawn_paths::build_info(),
+            dawn_util::build_info(),
+        ],
+        git_sha: (!git_sha.is_empty()).then_some(git_sha),
+        target: env!("DAWN_TARGET"),
+        profile: env!("DAWN_PROFILE"),
+    }
+}
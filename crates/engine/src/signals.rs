@@ -0,0 +1,74 @@
+use dawn_ecs::events::{ExitEvent, TickEvent};
+use evenio::event::{Receiver, Sender};
+use evenio::world::World;
+use log::{error, info, warn};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Once;
+use std::time::Duration;
+
+// Exit code of the process killed by SIGINT, used for the forced exits
+const FORCED_EXIT_CODE: i32 = 130;
+
+// The handler is process-wide and can be installed only once,
+// so the state is shared by all the engines run in the process
+static INSTALL: Once = Once::new();
+static SIGNALS: AtomicUsize = AtomicUsize::new(0);
+static GRACE_MS: AtomicU64 = AtomicU64::new(0);
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+fn on_signal() {
+    match SIGNALS.fetch_add(1, Ordering::SeqCst) {
+        0 if RUNNING.load(Ordering::SeqCst) => {
+            let grace = Duration::from_millis(GRACE_MS.load(Ordering::SeqCst));
+            info!(
+                "Termination requested, shutting down (forced in {:?})",
+                grace
+            );
+            std::thread::Builder::new()
+                .name("shutdown-watchdog".to_string())
+                .spawn(move || {
+                    std::thread::sleep(grace);
+                    if RUNNING.load(Ordering::SeqCst) {
+                        error!("Shutdown took longer than {:?}, exiting", grace);
+                        std::process::exit(FORCED_EXIT_CODE);
+                    }
+                })
+                .expect("Failed to spawn the shutdown watchdog");
+        }
+        // Nothing to shut down gracefully
+        0 => std::process::exit(FORCED_EXIT_CODE),
+        _ => {
+            warn!("Termination requested again, exiting");
+            std::process::exit(FORCED_EXIT_CODE);
+        }
+    }
+}
+
+/// Turns SIGINT, SIGTERM and SIGHUP (the console control events on Windows)
+/// into the `ExitEvent`, so the main loop stops and everything is dropped
+/// in order, instead of the process being killed in the middle of writing
+/// the files. If the shutdown takes longer than the `grace`, or the signal
+/// is received again, the process exits right away.
+pub(crate) fn attach_signal_handling(world: &mut World, grace: Duration) {
+    GRACE_MS.store(grace.as_millis() as u64, Ordering::SeqCst);
+    SIGNALS.store(0, Ordering::SeqCst);
+    RUNNING.store(true, Ordering::SeqCst);
+    INSTALL.call_once(|| {
+        if let Err(e) = ctrlc::set_handler(on_signal) {
+            warn!("Failed to install the signal handler: {}", e);
+        }
+    });
+
+    fn handler(_: Receiver<TickEvent>, mut sender: Sender<ExitEvent>) {
+        if SIGNALS.load(Ordering::SeqCst) > 0 {
+            sender.send(ExitEvent);
+        }
+    }
+
+    world.add_handler(handler);
+}
+
+/// Called once the engine is shut down, the signals kill the process again.
+pub(crate) fn detach_signal_handling() {
+    RUNNING.store(false, Ordering::SeqCst);
+}
@@ -7,6 +7,10 @@ use std::path::Path;
 fn main() {
     #[cfg(target_os = "linux")]
     println!("cargo:rustc-link-lib=X11");
+    #[cfg(target_os = "linux")]
+    println!("cargo:rustc-link-lib=Xrandr");
+    #[cfg(target_os = "linux")]
+    println!("cargo:rustc-link-lib=Xcursor");
 
     #[cfg(all(target_os = "linux", feature = "gl"))]
     println!("cargo:rustc-link-lib=GL");
@@ -14,7 +18,7 @@ fn main() {
     #[cfg(feature = "gl")]
     {
         let dest = env::var("OUT_DIR").unwrap();
-        let mut file = File::create(&Path::new(&dest).join("gl_bindings.rs")).unwrap();
+        let mut file = File::create(Path::new(&dest).join("gl_bindings.rs")).unwrap();
         Registry::new(
             Api::Gl,
             (4, 5),
@@ -0,0 +1,206 @@
+use dawn_ecs::events::TickEvent;
+use evenio::component::Component;
+use evenio::event::{GlobalEvent, Receiver, Sender};
+use evenio::fetch::Single;
+use evenio::world::World;
+use glam::{Mat3, Vec3};
+
+pub const MIN_UI_SCALE: f32 = 0.5;
+pub const MAX_UI_SCALE: f32 = 3.0;
+
+/// Color vision deficiency the color filter is set up for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorVision {
+    #[default]
+    Normal,
+    /// No red cones.
+    Protanopia,
+    /// No green cones.
+    Deuteranopia,
+    /// No blue cones.
+    Tritanopia,
+    /// No color vision at all.
+    Achromatopsia,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorFilterMode {
+    /// Shows the image as seen with the deficiency. Meant for checking the content.
+    Simulate,
+    /// Shifts the colors indistinguishable with the deficiency
+    /// to the distinguishable ones (daltonization).
+    #[default]
+    Correct,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AccessibilitySettings {
+    pub color_vision: ColorVision,
+    pub color_filter: ColorFilterMode,
+    /// Multiplies the sizes of the UI widgets and the text,
+    /// clamped to `MIN_UI_SCALE..=MAX_UI_SCALE`.
+    pub ui_scale: f32,
+    /// Asks the systems to avoid the flashes and the strobing:
+    /// the particles, the post effects, the camera shakes, etc.
+    pub reduce_flashing: bool,
+}
+
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        AccessibilitySettings {
+            color_vision: ColorVision::Normal,
+            color_filter: ColorFilterMode::Correct,
+            ui_scale: 1.0,
+            reduce_flashing: false,
+        }
+    }
+}
+
+// Rows of the simulation matrices in the linear RGB (Machado et al. 2009, full severity)
+const PROTANOPIA: [[f32; 3]; 3] = [
+    [0.152286, 1.052583, -0.204868],
+    [0.114503, 0.786281, 0.099216],
+    [-0.003882, -0.048116, 1.051998],
+];
+const DEUTERANOPIA: [[f32; 3]; 3] = [
+    [0.367322, 0.860646, -0.227968],
+    [0.280085, 0.672501, 0.047413],
+    [-0.011820, 0.042940, 0.968881],
+];
+const TRITANOPIA: [[f32; 3]; 3] = [
+    [1.255528, -0.076749, -0.178779],
+    [-0.078411, 0.930809, 0.147602],
+    [0.004733, 0.691367, 0.303900],
+];
+const LUMINANCE: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+// Rows of the matrices moving the lost information to the channels still seen
+const RED_GREEN_SHIFT: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [0.7, 1.0, 0.0], [0.7, 0.0, 1.0]];
+const BLUE_SHIFT: [[f32; 3]; 3] = [[1.0, 0.0, 0.7], [0.0, 1.0, 0.7], [0.0, 0.0, 0.0]];
+
+fn from_rows(rows: [[f32; 3]; 3]) -> Mat3 {
+    Mat3::from_cols_array_2d(&rows).transpose()
+}
+
+impl AccessibilitySettings {
+    /// Matrix the post-processing multiplies the linear RGB of the frame by.
+    /// The identity if no filter is needed. There is no correction
+    /// for the achromatopsia, only the simulation.
+    pub fn color_matrix(&self) -> Mat3 {
+        let (simulation, shift) = match self.color_vision {
+            ColorVision::Normal => return Mat3::IDENTITY,
+            ColorVision::Protanopia => (from_rows(PROTANOPIA), RED_GREEN_SHIFT),
+            ColorVision::Deuteranopia => (from_rows(DEUTERANOPIA), RED_GREEN_SHIFT),
+            ColorVision::Tritanopia => (from_rows(TRITANOPIA), BLUE_SHIFT),
+            ColorVision::Achromatopsia => {
+                let luminance = Vec3::from(LUMINANCE);
+                let simulation = Mat3::from_cols(luminance, luminance, luminance).transpose();
+                match self.color_filter {
+                    ColorFilterMode::Simulate => return simulation,
+                    ColorFilterMode::Correct => return Mat3::IDENTITY,
+                }
+            }
+        };
+
+        match self.color_filter {
+            ColorFilterMode::Simulate => simulation,
+            // The color plus the shifted error of the simulation
+            ColorFilterMode::Correct => {
+                Mat3::IDENTITY + from_rows(shift) * (Mat3::IDENTITY - simulation)
+            }
+        }
+    }
+
+    fn clamped(mut self) -> Self {
+        self.ui_scale = if self.ui_scale.is_finite() {
+            self.ui_scale.clamp(MIN_UI_SCALE, MAX_UI_SCALE)
+        } else {
+            1.0
+        };
+        self
+    }
+}
+
+/// Changes the accessibility settings. Applied immediately.
+#[derive(GlobalEvent, Debug, Clone, Copy)]
+pub struct SetAccessibility(pub AccessibilitySettings);
+
+/// Sent when the accessibility settings change and on the first tick,
+/// so the passes and the UI can apply them live.
+#[derive(GlobalEvent, Debug, Clone, Copy)]
+pub struct AccessibilityChanged(pub AccessibilitySettings);
+
+/// Current accessibility settings. The systems query it with
+/// `Single<&Accessibility>`, e.g. to check `reduce_flashing`.
+/// The `Ui` applies the scale by itself, the post-processing pass should
+/// apply the `color_matrix` when it receives the `AccessibilityChanged`.
+#[derive(Component, Debug)]
+pub struct Accessibility {
+    settings: AccessibilitySettings,
+    announced: bool,
+}
+
+impl Default for Accessibility {
+    fn default() -> Self {
+        Self::new(AccessibilitySettings::default())
+    }
+}
+
+impl Accessibility {
+    pub fn new(settings: AccessibilitySettings) -> Self {
+        Accessibility {
+            settings: settings.clamped(),
+            announced: false,
+        }
+    }
+
+    pub fn settings(&self) -> &AccessibilitySettings {
+        &self.settings
+    }
+
+    #[inline(always)]
+    pub fn reduce_flashing(&self) -> bool {
+        self.settings.reduce_flashing
+    }
+
+    #[inline(always)]
+    pub fn ui_scale(&self) -> f32 {
+        self.settings.ui_scale
+    }
+
+    pub fn color_matrix(&self) -> Mat3 {
+        self.settings.color_matrix()
+    }
+
+    /// Moves the settings into the ECS world. Change them with the `SetAccessibility` event.
+    pub fn attach_to_ecs(self, world: &mut World) {
+        let entity = world.spawn();
+        world.insert(entity, self);
+
+        fn set_handler(
+            r: Receiver<SetAccessibility>,
+            mut accessibility: Single<&mut Accessibility>,
+            mut sender: Sender<AccessibilityChanged>,
+        ) {
+            let settings = r.event.0.clamped();
+            if settings != accessibility.settings {
+                accessibility.settings = settings;
+                sender.send(AccessibilityChanged(settings));
+            }
+        }
+
+        fn tick_handler(
+            _: Receiver<TickEvent>,
+            mut accessibility: Single<&mut Accessibility>,
+            mut sender: Sender<AccessibilityChanged>,
+        ) {
+            if !accessibility.announced {
+                accessibility.announced = true;
+                sender.send(AccessibilityChanged(accessibility.settings));
+            }
+        }
+
+        world.add_handler(set_handler);
+        world.add_handler(tick_handler);
+    }
+}
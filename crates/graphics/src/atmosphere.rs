@@ -0,0 +1,281 @@
+//! Parameters and the analytic models of the fog and the sky rendered by the
+//! passes of `gl::atmosphere`. The CPU versions match the shaders, e.g. to fade
+//! the distant objects out of the logic or to tint the ambient light by the sky:
+//!
+//! ```
+//! use dawn_graphics::atmosphere::SkySettings;
+//! use dawn_graphics::renderable::RenderableSunLight;
+//! use glam::Vec3;
+//!
+//! let sun = RenderableSunLight {
+//!     direction: Vec3::new(0.0, 0.5, 1.0),
+//!     color: Vec3::ONE,
+//!     intensity: 1.0,
+//! };
+//! let sky = SkySettings::default();
+//! let towards = sky.radiance(Vec3::new(0.0, 0.4, 1.0).normalize(), &sun);
+//! let away = sky.radiance(Vec3::new(0.0, 0.4, -1.0).normalize(), &sun);
+//! assert!(towards.length() > away.length());
+//! // The clear sky is blue away from the sun
+//! assert!(away.z > away.x);
+//! ```
+
+use crate::passes::events::PassEventTrait;
+use crate::renderable::RenderableSunLight;
+use dawn_assets::ir::settings::IRSettings;
+use glam::{Mat4, Vec3};
+
+/// Exponential fog thickening towards the ground.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FogSettings {
+    /// Linear color of the fog.
+    pub color: Vec3,
+    /// Extinction per unit of the distance at the `base_height`.
+    pub density: f32,
+    /// How fast the fog thins out with the height, per unit.
+    /// Zero for the uniform distance fog.
+    pub height_falloff: f32,
+    pub base_height: f32,
+    /// Distance from the camera the fog starts at.
+    pub start: f32,
+    /// Opacity the fog is limited to, so the far objects are never fully hidden.
+    pub max_opacity: f32,
+    /// Amount of the sun light scattered towards the camera looking at the sun.
+    pub sun_scattering: f32,
+    /// Distance the pixels without the geometry (the sky) are fogged at.
+    /// Zero leaves them clear.
+    pub sky_distance: f32,
+}
+
+impl Default for FogSettings {
+    fn default() -> Self {
+        FogSettings {
+            color: Vec3::new(0.6, 0.7, 0.8),
+            density: 0.02,
+            height_falloff: 0.1,
+            base_height: 0.0,
+            start: 0.0,
+            max_opacity: 1.0,
+            sun_scattering: 0.5,
+            sky_distance: 0.0,
+        }
+    }
+}
+
+fn apply_vec3(settings: &IRSettings, key: &str, value: &mut Vec3) {
+    if let Some(&[x, y, z]) = settings.vector(key) {
+        *value = Vec3::new(x, y, z);
+    }
+}
+
+fn apply_f32(settings: &IRSettings, key: &str, value: &mut f32) {
+    if let Some(v) = settings.float(key) {
+        *value = v;
+    }
+}
+
+impl FogSettings {
+    /// Reads the `fog.*` keys of the preset (e.g. `fog.density`),
+    /// keeping the current values of the missing ones.
+    pub fn apply(&mut self, settings: &IRSettings) {
+        apply_vec3(settings, "fog.color", &mut self.color);
+        apply_f32(settings, "fog.density", &mut self.density);
+        apply_f32(settings, "fog.height_falloff", &mut self.height_falloff);
+        apply_f32(settings, "fog.base_height", &mut self.base_height);
+        apply_f32(settings, "fog.start", &mut self.start);
+        apply_f32(settings, "fog.max_opacity", &mut self.max_opacity);
+        apply_f32(settings, "fog.sun_scattering", &mut self.sun_scattering);
+        apply_f32(settings, "fog.sky_distance", &mut self.sky_distance);
+    }
+
+    /// Opacity of the fog between the camera and the point.
+    pub fn opacity(&self, camera: Vec3, point: Vec3) -> f32 {
+        let ray = point - camera;
+        let distance = ray.length();
+        if distance <= self.start {
+            return 0.0;
+        }
+
+        // The density integrated along the ray past the start
+        let direction = ray / distance;
+        let length = distance - self.start;
+        let height = camera.y + direction.y * self.start - self.base_height;
+        let k = self.height_falloff * direction.y;
+        let path = if k.abs() > 1e-4 {
+            (1.0 - (-k * length).exp()) / k
+        } else {
+            length
+        };
+        let amount = self.density * (-self.height_falloff * height).exp() * path;
+        (1.0 - (-amount).exp()).min(self.max_opacity)
+    }
+
+    /// Color of the fog seen in the direction, brighter towards the sun.
+    pub fn color(&self, direction: Vec3, sun: Option<&RenderableSunLight>) -> Vec3 {
+        let Some(sun) = sun else {
+            return self.color;
+        };
+        let facing = direction.dot(sun.direction.normalize_or(Vec3::Y)).max(0.0);
+        let amount = (facing.powi(8) * self.sun_scattering).min(1.0);
+        self.color.lerp(sun.color * sun.intensity, amount)
+    }
+}
+
+/// Clear sky of the Preetham model, lit by the `RenderableSunLight`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkySettings {
+    /// Haziness of the air, from 2 (clear) to 10 (hazy).
+    pub turbidity: f32,
+    /// Scale of the radiance. The model gives the luminance in kcd/m²,
+    /// around ten at the daylight.
+    pub exposure: f32,
+    /// Linear color below the horizon.
+    pub ground_color: Vec3,
+    /// Angular radius of the sun disk in radians. Zero hides the disk.
+    pub sun_disk: f32,
+}
+
+impl Default for SkySettings {
+    fn default() -> Self {
+        SkySettings {
+            turbidity: 2.5,
+            exposure: 0.05,
+            ground_color: Vec3::new(0.2, 0.2, 0.2),
+            sun_disk: 0.01,
+        }
+    }
+}
+
+impl SkySettings {
+    /// Reads the `sky.*` keys of the preset (e.g. `sky.turbidity`),
+    /// keeping the current values of the missing ones.
+    pub fn apply(&mut self, settings: &IRSettings) {
+        apply_f32(settings, "sky.turbidity", &mut self.turbidity);
+        apply_f32(settings, "sky.exposure", &mut self.exposure);
+        apply_vec3(settings, "sky.ground_color", &mut self.ground_color);
+        apply_f32(settings, "sky.sun_disk", &mut self.sun_disk);
+    }
+
+    /// Linear radiance of the sky in the direction.
+    pub fn radiance(&self, direction: Vec3, sun: &RenderableSunLight) -> Vec3 {
+        if direction.y < 0.0 {
+            return self.ground_color;
+        }
+
+        let sun_direction = sun.direction.normalize_or(Vec3::Y);
+        let model = Preetham::new(self.turbidity, sun_direction);
+        let cos_gamma = direction.dot(sun_direction).clamp(-1.0, 1.0);
+        let gamma = cos_gamma.acos();
+        let yxy = model.zenith * perez(&model, direction.y.max(0.01), gamma, cos_gamma);
+
+        let mut radiance = xyy_to_rgb(yxy) * self.exposure;
+        if gamma < self.sun_disk {
+            radiance += sun.color * sun.intensity;
+        }
+        radiance
+    }
+}
+
+/// Coefficients of the Perez distribution for the luminance Y and the
+/// chromaticity x, y (in this order), depending only on the turbidity and the sun.
+/// Evaluated once per frame, the shader only applies them per pixel.
+pub(crate) struct Preetham {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub c: Vec3,
+    pub d: Vec3,
+    pub e: Vec3,
+    /// Values at the zenith divided by the distribution there,
+    /// so the distribution in any direction scales them.
+    pub zenith: Vec3,
+}
+
+impl Preetham {
+    pub fn new(turbidity: f32, sun: Vec3) -> Self {
+        let t = turbidity;
+        let mut model = Preetham {
+            a: Vec3::new(
+                0.1787 * t - 1.4630,
+                -0.0193 * t - 0.2592,
+                -0.0167 * t - 0.2608,
+            ),
+            b: Vec3::new(
+                -0.3554 * t + 0.4275,
+                -0.0665 * t + 0.0008,
+                -0.0950 * t + 0.0092,
+            ),
+            c: Vec3::new(
+                -0.0227 * t + 5.3251,
+                -0.0004 * t + 0.2125,
+                -0.0079 * t + 0.2102,
+            ),
+            d: Vec3::new(
+                0.1206 * t - 2.5771,
+                -0.0641 * t - 0.8989,
+                -0.0441 * t - 1.6537,
+            ),
+            e: Vec3::new(
+                -0.0670 * t + 0.3703,
+                -0.0033 * t + 0.0452,
+                -0.0109 * t + 0.0529,
+            ),
+            zenith: Vec3::ZERO,
+        };
+
+        // The model holds for the sun above the horizon
+        let theta = sun
+            .y
+            .clamp(0.0, 1.0)
+            .acos()
+            .min(std::f32::consts::FRAC_PI_2 - 0.01);
+        let (theta2, theta3) = (theta * theta, theta * theta * theta);
+        let chi = (4.0 / 9.0 - t / 120.0) * (std::f32::consts::PI - 2.0 * theta);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let x = t * t * (0.00166 * theta3 - 0.00375 * theta2 + 0.00209 * theta)
+            + t * (-0.02903 * theta3 + 0.06377 * theta2 - 0.03202 * theta + 0.00394)
+            + (0.11693 * theta3 - 0.21196 * theta2 + 0.06052 * theta + 0.25886);
+        let y = t * t * (0.00275 * theta3 - 0.00610 * theta2 + 0.00317 * theta)
+            + t * (-0.04214 * theta3 + 0.08970 * theta2 - 0.04153 * theta + 0.00516)
+            + (0.15346 * theta3 - 0.26756 * theta2 + 0.06670 * theta + 0.26688);
+
+        let at_zenith = perez(&model, 1.0, theta, theta.cos());
+        model.zenith = Vec3::new(luminance, x, y) / at_zenith;
+        model
+    }
+}
+
+// Perez distribution of the sky, theta is the angle from the zenith,
+// gamma is the angle from the sun
+fn perez(model: &Preetham, cos_theta: f32, gamma: f32, cos_gamma: f32) -> Vec3 {
+    let exp = |v: Vec3| Vec3::new(v.x.exp(), v.y.exp(), v.z.exp());
+    (Vec3::ONE + model.a * exp(model.b / cos_theta))
+        * (Vec3::ONE + model.c * exp(model.d * gamma) + model.e * cos_gamma * cos_gamma)
+}
+
+fn xyy_to_rgb(yxy: Vec3) -> Vec3 {
+    let (luminance, x, y) = (yxy.x, yxy.y, yxy.z);
+    let xyz = Vec3::new(x * luminance / y, luminance, (1.0 - x - y) * luminance / y);
+    Vec3::new(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    )
+    .max(Vec3::ZERO)
+}
+
+/// Runtime parameters of the `gl::atmosphere` passes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AtmosphereEvent {
+    /// Camera the frame is rendered from.
+    SetCamera {
+        view: Mat4,
+        projection: Mat4,
+    },
+    SetFog(FogSettings),
+    SetSky(SkySettings),
+}
+
+/// Render pass events the `gl::atmosphere` passes take their parameters from.
+/// Implement `TryFrom<E> for AtmosphereEvent` for the events of the pipeline,
+/// the other events are ignored by the passes.
+pub trait AtmosphereEventTrait = PassEventTrait + TryInto<AtmosphereEvent>;
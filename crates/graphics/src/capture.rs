@@ -0,0 +1,519 @@
+use crate::passes::events::{PassEventTrait, RenderPassEvent, RenderPassTargetId};
+use crate::renderable::{
+    ObjectMaterial, ObjectMesh, ObjectPosition, ObjectRotation, ObjectScale, RenderLayers,
+    Renderable,
+};
+use crate::renderer::ecs::{to_renderable, RenderableQuery};
+use dawn_assets::hub::AssetHub;
+use dawn_assets::requests::{AssetRequest, AssetRequestQuery};
+use dawn_assets::{Asset, AssetID, TypedAsset};
+use dawn_ecs::events::{ExitEvent, InterSyncEvent, TickEvent};
+use evenio::component::Component;
+use evenio::entity::EntityId;
+use evenio::event::{Despawn, GlobalEvent, Insert, Receiver, Sender, Spawn};
+use evenio::fetch::{Fetcher, Single};
+use evenio::handler::IntoHandler;
+use evenio::world::World;
+use glam::Mat4;
+use log::{info, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+const MAGIC: &[u8; 4] = b"DSFC";
+const VERSION: u32 = 1;
+
+/// Render pass events that can be captured along with the frames.
+pub trait CaptureEventTrait = PassEventTrait + Serialize + DeserializeOwned;
+
+#[derive(Debug, Error)]
+pub enum CaptureError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Not a frame capture")]
+    NotACapture,
+    #[error("Unsupported capture version {0}, expected {VERSION}")]
+    UnsupportedVersion(u32),
+    #[error("Failed to encode the capture: {0}")]
+    Encode(String),
+    #[error("Failed to decode the capture: {0}")]
+    Decode(String),
+}
+
+/// Renderable of the captured frame. The assets are the indices into `FrameCapture::assets`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CapturedRenderable {
+    pub model: [f32; 16],
+    pub mesh: u32,
+    /// `None` for the default material.
+    pub material: Option<u32>,
+    pub layers: u32,
+}
+
+/// Content of the data stream of one frame, with the render pass events sent before it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapturedFrame<E> {
+    /// Epoch of the frame in the captured run.
+    pub epoch: usize,
+    /// `None` if the renderables are the same as in the previous frame.
+    pub renderables: Option<Vec<CapturedRenderable>>,
+    /// Events with the IDs of their targets.
+    pub events: Vec<(usize, E)>,
+}
+
+/// Recorded frames of the renderer, replayed by the `FramePlayer`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FrameCapture<E> {
+    /// Assets referenced by the renderables.
+    pub assets: Vec<AssetID>,
+    pub frames: Vec<CapturedFrame<E>>,
+}
+
+impl<E: CaptureEventTrait> FrameCapture<E> {
+    pub fn new() -> Self {
+        FrameCapture {
+            assets: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), CaptureError> {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        let encoded = bincode::serde::encode_to_vec(self, bincode::config::standard())
+            .map_err(|e| CaptureError::Encode(e.to_string()))?;
+        data.extend_from_slice(&encoded);
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<Self, CaptureError> {
+        let data = std::fs::read(path)?;
+        if data.len() < 8 || &data[..4] != MAGIC {
+            return Err(CaptureError::NotACapture);
+        }
+        let version = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+        if version != VERSION {
+            return Err(CaptureError::UnsupportedVersion(version));
+        }
+        let (capture, _) =
+            bincode::serde::decode_from_slice(&data[8..], bincode::config::standard())
+                .map_err(|e| CaptureError::Decode(e.to_string()))?;
+        Ok(capture)
+    }
+}
+
+impl<E: CaptureEventTrait> Default for FrameCapture<E> {
+    fn default() -> Self {
+        FrameCapture::new()
+    }
+}
+
+/// Starts capturing the next `frames` frames into the file.
+/// A capture already running is finished first.
+#[derive(GlobalEvent, Debug, Clone)]
+pub struct StartFrameCapture {
+    pub path: PathBuf,
+    pub frames: usize,
+}
+
+/// Finishes the running capture early.
+#[derive(GlobalEvent, Debug, Clone, Copy)]
+pub struct StopFrameCapture;
+
+#[derive(GlobalEvent, Debug, Clone)]
+pub enum FrameCaptureEvent {
+    Written { path: PathBuf, frames: usize },
+    Failed { path: PathBuf, reason: String },
+}
+
+struct Recording<E> {
+    path: PathBuf,
+    frames: usize,
+    capture: FrameCapture<E>,
+    // Asset address to its index in the capture
+    indices: HashMap<usize, u32>,
+    last: Option<Vec<CapturedRenderable>>,
+}
+
+/// Captures the data stream of the renderer (the renderables and the render
+/// pass events) to the file, to replay the frames later with the `FramePlayer`,
+/// e.g. on the GPU the issue was reported on. The assets are stored by their IDs,
+/// so the replay needs the same containers.
+///
+/// The events are stored with the IDs of their targets. The pipeline of the replay
+/// must create its passes in the same order as the captured one for them to match,
+/// i.e. use the same constructor.
+/// Requires the renderer and the asset hub attached to the same world.
+#[derive(Component)]
+pub struct FrameRecorder<E: CaptureEventTrait> {
+    recording: Option<Recording<E>>,
+    // Events of the frame being collected
+    events: Vec<(usize, E)>,
+}
+
+impl<E: CaptureEventTrait> FrameRecorder<E> {
+    pub fn new() -> Self {
+        FrameRecorder {
+            recording: None,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Moves the recorder into the ECS world.
+    /// Start the capture with the `StartFrameCapture` event,
+    /// the result is reported with the `FrameCaptureEvent`.
+    pub fn attach_to_ecs(self, world: &mut World) {
+        let entity = world.spawn();
+        world.insert(entity, self);
+        world.add_handler(Self::start_handler);
+        world.add_handler(Self::stop_handler);
+        world.add_handler(Self::event_handler);
+        world.add_handler(Self::frame_handler);
+        world.add_handler(Self::exit_handler);
+    }
+
+    fn finish(&mut self) -> Option<FrameCaptureEvent> {
+        let recording = self.recording.take()?;
+        let frames = recording.capture.frames.len();
+        Some(match recording.capture.write(&recording.path) {
+            Ok(()) => {
+                info!("Captured {} frames to {}", frames, recording.path.display());
+                FrameCaptureEvent::Written {
+                    path: recording.path,
+                    frames,
+                }
+            }
+            Err(e) => FrameCaptureEvent::Failed {
+                path: recording.path,
+                reason: e.to_string(),
+            },
+        })
+    }
+
+    fn start_handler(
+        r: Receiver<StartFrameCapture>,
+        mut recorder: Single<&mut FrameRecorder<E>>,
+        mut sender: Sender<FrameCaptureEvent>,
+    ) {
+        if let Some(event) = recorder.finish() {
+            sender.send(event);
+        }
+        info!("Capturing {} frames", r.event.frames);
+        recorder.events.clear();
+        recorder.recording = Some(Recording {
+            path: r.event.path.clone(),
+            frames: r.event.frames,
+            capture: FrameCapture::new(),
+            indices: HashMap::new(),
+            last: None,
+        });
+    }
+
+    fn stop_handler(
+        _: Receiver<StopFrameCapture>,
+        mut recorder: Single<&mut FrameRecorder<E>>,
+        mut sender: Sender<FrameCaptureEvent>,
+    ) {
+        if let Some(event) = recorder.finish() {
+            sender.send(event);
+        }
+    }
+
+    // Writes the frames captured so far, the capture is not cut off by the exit
+    fn exit_handler(
+        _: Receiver<ExitEvent>,
+        mut recorder: Single<&mut FrameRecorder<E>>,
+        mut sender: Sender<FrameCaptureEvent>,
+    ) {
+        if let Some(event) = recorder.finish() {
+            sender.send(event);
+        }
+    }
+
+    fn event_handler(r: Receiver<RenderPassEvent<E>>, mut recorder: Single<&mut FrameRecorder<E>>) {
+        if recorder.recording.is_some() {
+            let target = r.event.get_target_id().as_usize();
+            recorder.events.push((target, r.event.clone().event()));
+        }
+    }
+
+    // Runs at the same point the renderer collects its data stream
+    fn frame_handler(
+        t: Receiver<InterSyncEvent>,
+        mut recorder: Single<&mut FrameRecorder<E>>,
+        hub: Single<&AssetHub>,
+        fetcher: Fetcher<RenderableQuery>,
+        mut sender: Sender<FrameCaptureEvent>,
+    ) {
+        let recorder = &mut *recorder;
+        let Some(recording) = &mut recorder.recording else {
+            return;
+        };
+
+        let renderables = fetcher.iter().map(to_renderable).collect::<Vec<_>>();
+        let captured = recording.capture_renderables(&renderables, &hub);
+        let renderables = if recording.last.as_ref() == Some(&captured) {
+            None
+        } else {
+            recording.last = Some(captured.clone());
+            Some(captured)
+        };
+        recording.capture.frames.push(CapturedFrame {
+            epoch: t.event.frame,
+            renderables,
+            events: std::mem::take(&mut recorder.events),
+        });
+
+        if recording.capture.frames.len() >= recording.frames {
+            if let Some(event) = recorder.finish() {
+                sender.send(event);
+            }
+        }
+    }
+}
+
+impl<E: CaptureEventTrait> Default for FrameRecorder<E> {
+    fn default() -> Self {
+        FrameRecorder::new()
+    }
+}
+
+impl<E: CaptureEventTrait> Recording<E> {
+    fn capture_renderables(
+        &mut self,
+        renderables: &[Renderable],
+        hub: &AssetHub,
+    ) -> Vec<CapturedRenderable> {
+        let default_material = ObjectMaterial::default_material().asset().address();
+        let mut loaded: Option<HashMap<usize, AssetID>> = None;
+        let mut index = |asset: &Asset| -> Option<u32> {
+            let address = asset.address();
+            if let Some(index) = self.indices.get(&address) {
+                return Some(*index);
+            }
+            // Looked up only when the new assets appear
+            let loaded = loaded.get_or_insert_with(|| {
+                hub.loaded_assets()
+                    .into_iter()
+                    .map(|(id, asset)| (asset.address(), id))
+                    .collect()
+            });
+            let id = loaded.get(&address)?.clone();
+            let index = self.capture.assets.len() as u32;
+            self.capture.assets.push(id);
+            self.indices.insert(address, index);
+            Some(index)
+        };
+
+        renderables
+            .iter()
+            .filter_map(|renderable| {
+                let Some(mesh) = index(renderable.mesh.asset()) else {
+                    warn!("Renderable with the mesh unknown to the asset hub is not captured");
+                    return None;
+                };
+                let material = renderable.material.asset();
+                let material = if material.address() == default_material {
+                    None
+                } else {
+                    Some(index(material)?)
+                };
+                Some(CapturedRenderable {
+                    model: renderable.model.to_cols_array(),
+                    mesh,
+                    material,
+                    layers: renderable.layers.0,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Sent by the `FramePlayer`.
+#[derive(GlobalEvent, Debug, Clone)]
+pub enum FramePlayerEvent {
+    /// All the assets are loaded, the frames are played from the next tick.
+    Started,
+    /// The last frame was played. Not sent if the player loops.
+    Finished,
+    /// The asset is not in the enumerated containers, the player is stopped.
+    MissingAsset(AssetID),
+}
+
+// Everything the player sends into the world
+type PlayerEvents<E> = (
+    Spawn,
+    Despawn,
+    Insert<ObjectMesh>,
+    Insert<ObjectMaterial>,
+    Insert<ObjectPosition>,
+    Insert<ObjectRotation>,
+    Insert<ObjectScale>,
+    Insert<RenderLayers>,
+    RenderPassEvent<E>,
+    FramePlayerEvent,
+);
+
+enum PlayerState {
+    Loading { requested: bool },
+    Playing { frame: usize },
+    Stopped,
+}
+
+/// Replays the captured frames: feeds the renderables and the render pass events
+/// into the world one frame per tick, so the renderer draws exactly what was captured.
+/// Meant to run in the world with only the renderer and the asset hub attached,
+/// without the game logic. Loads the referenced assets first.
+#[derive(Component)]
+pub struct FramePlayer<E: CaptureEventTrait> {
+    capture: FrameCapture<E>,
+    looped: bool,
+    state: PlayerState,
+    assets: Vec<Option<Asset>>,
+    entities: Vec<EntityId>,
+}
+
+impl<E: CaptureEventTrait> FramePlayer<E> {
+    pub fn new(capture: FrameCapture<E>) -> Self {
+        FramePlayer {
+            assets: vec![None; capture.assets.len()],
+            capture,
+            looped: false,
+            state: PlayerState::Loading { requested: false },
+            entities: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, CaptureError> {
+        Ok(FramePlayer::new(FrameCapture::read(path)?))
+    }
+
+    /// Starts over after the last frame, e.g. to profile the frames.
+    pub fn looped(mut self, looped: bool) -> Self {
+        self.looped = looped;
+        self
+    }
+
+    pub fn capture(&self) -> &FrameCapture<E> {
+        &self.capture
+    }
+
+    /// Moves the player into the ECS world.
+    /// Requires the asset hub attached to the same world.
+    pub fn attach_to_ecs(self, world: &mut World) {
+        let entity = world.spawn();
+        world.insert(entity, self);
+        // Before the other handlers, the assets are requested on the same tick
+        world.add_handler(Self::tick_handler.high());
+    }
+
+    fn tick_handler(
+        _: Receiver<TickEvent>,
+        mut player: Single<&mut FramePlayer<E>>,
+        mut hub: Single<&mut AssetHub>,
+        mut sender: Sender<PlayerEvents<E>>,
+    ) {
+        let player = &mut *player;
+        match player.state {
+            PlayerState::Stopped => {}
+            PlayerState::Loading { requested } => {
+                if !requested {
+                    hub.request(AssetRequest::Load(AssetRequestQuery::ByIDs(
+                        player.capture.assets.clone(),
+                    )));
+                    player.state = PlayerState::Loading { requested: true };
+                }
+                match player.resolve_assets(&hub) {
+                    Ok(true) => {
+                        info!("Playing {} captured frames", player.capture.frames.len());
+                        player.state = PlayerState::Playing { frame: 0 };
+                        sender.send(FramePlayerEvent::Started);
+                    }
+                    Ok(false) => {}
+                    Err(id) => {
+                        warn!("Captured asset {} is not found", id);
+                        player.state = PlayerState::Stopped;
+                        sender.send(FramePlayerEvent::MissingAsset(id));
+                    }
+                }
+            }
+            PlayerState::Playing { frame } => {
+                let Some(captured) = player.capture.frames.get(frame) else {
+                    return;
+                };
+
+                if let Some(renderables) = &captured.renderables {
+                    // Reuse the entities of the previous frame
+                    while player.entities.len() > renderables.len() {
+                        sender.despawn(player.entities.pop().unwrap());
+                    }
+                    while player.entities.len() < renderables.len() {
+                        player.entities.push(sender.spawn());
+                    }
+
+                    for (renderable, entity) in renderables.iter().zip(&player.entities) {
+                        // Resolved before the playing starts
+                        let asset = |index: u32| player.assets[index as usize].clone().unwrap();
+                        let model = Mat4::from_cols_array(&renderable.model);
+                        let (scale, rotation, position) = model.to_scale_rotation_translation();
+                        sender.insert(*entity, ObjectMesh(TypedAsset::new(asset(renderable.mesh))));
+                        sender.insert(
+                            *entity,
+                            ObjectMaterial(
+                                renderable
+                                    .material
+                                    .map_or_else(ObjectMaterial::default_material, |index| {
+                                        TypedAsset::new(asset(index))
+                                    }),
+                            ),
+                        );
+                        sender.insert(*entity, ObjectPosition(position));
+                        sender.insert(*entity, ObjectRotation(rotation));
+                        sender.insert(*entity, ObjectScale(scale));
+                        sender.insert(*entity, RenderLayers(renderable.layers));
+                    }
+                }
+
+                for (target, event) in &captured.events {
+                    sender.send(RenderPassEvent::new(
+                        RenderPassTargetId::from_usize(*target),
+                        event.clone(),
+                    ));
+                }
+
+                let next = frame + 1;
+                if next < player.capture.frames.len() {
+                    player.state = PlayerState::Playing { frame: next };
+                } else if player.looped {
+                    player.state = PlayerState::Playing { frame: 0 };
+                } else {
+                    player.state = PlayerState::Stopped;
+                    sender.send(FramePlayerEvent::Finished);
+                }
+            }
+        }
+    }
+
+    // Whether all the assets are loaded, or the ID of the missing one
+    fn resolve_assets(&mut self, hub: &AssetHub) -> Result<bool, AssetID> {
+        let mut loaded = true;
+        for (id, asset) in self.capture.assets.iter().zip(self.assets.iter_mut()) {
+            if asset.is_some() {
+                continue;
+            }
+            match hub.get(id.clone()) {
+                Ok(found) => *asset = Some(found),
+                Err(dawn_assets::hub::GetAssetError::NotFound(_)) => return Err(id.clone()),
+                Err(_) => loaded = false,
+            }
+        }
+        Ok(loaded)
+    }
+}
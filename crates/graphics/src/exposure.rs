@@ -0,0 +1,359 @@
+use crate::renderer::readback::{
+    ReadbackCancel, ReadbackData, ReadbackEvent, ReadbackFormat, ReadbackRequest, ReadbackSource,
+};
+use dawn_ecs::events::TickEvent;
+use evenio::component::Component;
+use evenio::event::{GlobalEvent, Receiver, Sender};
+use evenio::fetch::Single;
+use evenio::world::World;
+use log::warn;
+
+pub const HISTOGRAM_BINS: usize = 64;
+// Pixels of the larger sources are skipped evenly down to that count
+const MAX_SAMPLES: usize = 1 << 16;
+// Relative changes of the exposure smaller than that are not announced
+const EXPOSURE_EPSILON: f32 = 1e-3;
+const LUMINANCE: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+#[derive(Debug, Clone)]
+pub struct AutoExposureSettings {
+    /// HDR image the luminance is measured on. Reading the full-resolution
+    /// target is expensive, point it to the small downsampled copy
+    /// (e.g. the 1/8 target of the bloom chain) instead.
+    pub source: ReadbackSource,
+    /// Tag of the readback of the source. Must not clash with the other readbacks.
+    pub tag: u64,
+    /// Range of the measured luminance in log2 units. The scenes darker or
+    /// brighter than that are exposed as the range limits, so the night
+    /// stays dark and the sun does not blind forever.
+    pub min_log_luminance: f32,
+    pub max_log_luminance: f32,
+    /// Share of the darkest and the brightest pixels ignored by the average, from 0 to 1.
+    /// Keeps the sky and the small highlights from dominating the exposure.
+    pub low_percentile: f32,
+    pub high_percentile: f32,
+    /// Adaptation speed to the brighter and the darker scenes, per second.
+    /// The eye adapts to the light faster than to the dark.
+    pub speed_up: f32,
+    pub speed_down: f32,
+    /// Luminance the average is mapped to (the middle gray).
+    pub key: f32,
+    /// Exposure compensation in stops, added after the adaptation.
+    pub compensation: f32,
+}
+
+impl AutoExposureSettings {
+    pub fn new(source: ReadbackSource, tag: u64) -> Self {
+        AutoExposureSettings {
+            source,
+            tag,
+            min_log_luminance: -8.0,
+            max_log_luminance: 4.0,
+            low_percentile: 0.5,
+            high_percentile: 0.95,
+            speed_up: 3.0,
+            speed_down: 1.0,
+            key: 0.18,
+            compensation: 0.0,
+        }
+    }
+}
+
+/// Histogram of the log2 luminance of the image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LuminanceHistogram {
+    pub bins: [u32; HISTOGRAM_BINS],
+    pub min_log: f32,
+    pub max_log: f32,
+}
+
+impl LuminanceHistogram {
+    pub fn new(min_log: f32, max_log: f32) -> Self {
+        LuminanceHistogram {
+            bins: [0; HISTOGRAM_BINS],
+            min_log,
+            max_log,
+        }
+    }
+
+    /// Counts the linear luminance. The values out of the range go to the edge bins.
+    pub fn add(&mut self, luminance: f32) {
+        let log = luminance.max(f32::MIN_POSITIVE).log2();
+        let t = (log - self.min_log) / (self.max_log - self.min_log);
+        let bin = (t * HISTOGRAM_BINS as f32).clamp(0.0, (HISTOGRAM_BINS - 1) as f32);
+        self.bins[bin as usize] += 1;
+    }
+
+    /// Builds the histogram of the read back image. The `Rgba8` pixels
+    /// are taken as the linear colors, the `RF32` ones as the luminance.
+    /// Returns `None` for the `Raw` data.
+    pub fn from_readback(data: &ReadbackData, min_log: f32, max_log: f32) -> Option<Self> {
+        let mut histogram = LuminanceHistogram::new(min_log, max_log);
+        let pixels = data.width * data.height;
+        let step = pixels.div_ceil(MAX_SAMPLES).max(1);
+        match data.format {
+            ReadbackFormat::RgbaF32 => {
+                let floats = data.to_f32();
+                for pixel in floats.chunks_exact(4).step_by(step) {
+                    histogram.add(luminance([pixel[0], pixel[1], pixel[2]]));
+                }
+            }
+            ReadbackFormat::RF32 => {
+                for value in data.to_f32().into_iter().step_by(step) {
+                    histogram.add(value);
+                }
+            }
+            ReadbackFormat::Rgba8 => {
+                for pixel in data.data.chunks_exact(4).step_by(step) {
+                    let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|c| c as f32 / 255.0);
+                    histogram.add(luminance([r, g, b]));
+                }
+            }
+            ReadbackFormat::Raw => return None,
+        }
+        Some(histogram)
+    }
+
+    /// Average log2 luminance of the pixels between the percentiles.
+    /// `None` if the histogram is empty.
+    pub fn average_log(&self, low_percentile: f32, high_percentile: f32) -> Option<f32> {
+        let total = self.bins.iter().map(|&count| count as f32).sum::<f32>();
+        if total == 0.0 {
+            return None;
+        }
+
+        let low = total * low_percentile.clamp(0.0, 1.0);
+        let high = (total * high_percentile.clamp(0.0, 1.0)).max(low);
+        let width = (self.max_log - self.min_log) / HISTOGRAM_BINS as f32;
+        let (mut seen, mut sum, mut weight) = (0.0, 0.0, 0.0);
+        for (i, &count) in self.bins.iter().enumerate() {
+            // Part of the bin between the percentiles
+            let count = count as f32;
+            let counted = (seen + count).min(high) - seen.max(low);
+            seen += count;
+            if counted > 0.0 {
+                sum += (self.min_log + (i as f32 + 0.5) * width) * counted;
+                weight += counted;
+            }
+        }
+
+        if weight > 0.0 {
+            Some(sum / weight)
+        } else {
+            // The percentiles are too close, take the bin they fall into
+            let mut seen = 0.0;
+            self.bins.iter().enumerate().find_map(|(i, &count)| {
+                seen += count as f32;
+                (seen >= low).then_some(self.min_log + (i as f32 + 0.5) * width)
+            })
+        }
+    }
+}
+
+fn luminance(rgb: [f32; 3]) -> f32 {
+    rgb[0] * LUMINANCE[0] + rgb[1] * LUMINANCE[1] + rgb[2] * LUMINANCE[2]
+}
+
+/// Changes the settings of the auto exposure.
+/// The readback is restarted with the new source.
+#[derive(GlobalEvent, Debug, Clone)]
+pub struct SetAutoExposure(pub AutoExposureSettings);
+
+/// Fixes the exposure to the value (the multiplier of the HDR color),
+/// e.g. for the cutscenes. `None` returns to the automatic exposure,
+/// which adapts from the fixed value.
+#[derive(GlobalEvent, Debug, Clone, Copy)]
+pub struct SetExposure(pub Option<f32>);
+
+/// Sent when the exposure changes and on the first tick.
+/// The tonemapping pass multiplies the HDR color by the `exposure`.
+#[derive(GlobalEvent, Debug, Clone, Copy, PartialEq)]
+pub struct ExposureChanged {
+    pub exposure: f32,
+    /// Log2 of the adapted scene luminance, `None` while the exposure is fixed
+    /// or before the first measurement.
+    pub log_luminance: Option<f32>,
+}
+
+/// Eye adaptation. Measures the luminance of the HDR image with the persistent
+/// readback, and moves the exposure towards it smoothly over the ticks.
+/// Requires the renderer attached to the same world.
+#[derive(Component, Debug)]
+pub struct AutoExposure {
+    settings: AutoExposureSettings,
+    // Log2 luminance measured on the last readback
+    target: Option<f32>,
+    // Log2 luminance adapted to so far
+    adapted: Option<f32>,
+    manual: Option<f32>,
+    exposure: f32,
+    announced: Option<f32>,
+    reading: bool,
+}
+
+impl AutoExposure {
+    pub fn new(settings: AutoExposureSettings) -> Self {
+        AutoExposure {
+            settings,
+            target: None,
+            adapted: None,
+            manual: None,
+            exposure: 1.0,
+            announced: None,
+            reading: false,
+        }
+    }
+
+    pub fn settings(&self) -> &AutoExposureSettings {
+        &self.settings
+    }
+
+    /// Current multiplier of the HDR color.
+    #[inline(always)]
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    /// Exposure mapping the log2 luminance to the key.
+    pub fn exposure_for(&self, log_luminance: f32) -> f32 {
+        let settings = &self.settings;
+        let log = log_luminance.clamp(settings.min_log_luminance, settings.max_log_luminance);
+        settings.key / log.exp2() * settings.compensation.exp2()
+    }
+
+    /// Moves the adapted luminance towards the measured one.
+    pub fn adapt(&mut self, delta: f32) {
+        if let Some(exposure) = self.manual {
+            self.exposure = exposure;
+            return;
+        }
+        let Some(target) = self.target else {
+            return;
+        };
+
+        let adapted = match self.adapted {
+            None => target,
+            Some(adapted) => {
+                let speed = if target > adapted {
+                    self.settings.speed_up
+                } else {
+                    self.settings.speed_down
+                };
+                // Exponential, so the result does not depend on the tick rate
+                adapted + (target - adapted) * (1.0 - (-delta * speed).exp())
+            }
+        };
+        self.adapted = Some(adapted);
+        self.exposure = self.exposure_for(adapted);
+    }
+
+    fn request(&self) -> ReadbackRequest {
+        ReadbackRequest {
+            tag: self.settings.tag,
+            source: self.settings.source.clone(),
+            rect: None,
+            format: ReadbackFormat::RgbaF32,
+            persistent: true,
+        }
+    }
+
+    /// Moves the auto exposure into the ECS world.
+    /// Change it with the `SetAutoExposure` and the `SetExposure` events.
+    pub fn attach_to_ecs(self, world: &mut World) {
+        let entity = world.spawn();
+        world.insert(entity, self);
+
+        fn readback_handler(r: Receiver<ReadbackEvent>, mut exposure: Single<&mut AutoExposure>) {
+            let settings = &exposure.settings;
+            match r.event {
+                ReadbackEvent::Ready(data) if data.tag == settings.tag => {
+                    let Some(histogram) = LuminanceHistogram::from_readback(
+                        data,
+                        settings.min_log_luminance,
+                        settings.max_log_luminance,
+                    ) else {
+                        return;
+                    };
+                    let target =
+                        histogram.average_log(settings.low_percentile, settings.high_percentile);
+                    if target.is_some() {
+                        exposure.target = target;
+                    }
+                }
+                ReadbackEvent::Failed { tag, reason } if *tag == settings.tag => {
+                    // Keeps the last exposure until the settings are changed
+                    warn!("Auto exposure cannot read its source: {}", reason);
+                }
+                _ => {}
+            }
+        }
+
+        fn settings_handler(
+            r: Receiver<SetAutoExposure>,
+            mut exposure: Single<&mut AutoExposure>,
+            mut cancel: Sender<ReadbackCancel>,
+        ) {
+            if exposure.reading {
+                cancel.send(ReadbackCancel {
+                    tag: exposure.settings.tag,
+                });
+                exposure.reading = false;
+            }
+            exposure.settings = r.event.0.clone();
+            exposure.target = None;
+        }
+
+        fn manual_handler(
+            r: Receiver<SetExposure>,
+            mut exposure: Single<&mut AutoExposure>,
+            mut cancel: Sender<ReadbackCancel>,
+        ) {
+            exposure.manual = r.event.0;
+            if let Some(fixed) = exposure.manual {
+                // Adapt from the fixed exposure when returning to the automatic one
+                exposure.adapted = Some(
+                    (exposure.settings.key / fixed * exposure.settings.compensation.exp2()).log2(),
+                );
+                if exposure.reading {
+                    cancel.send(ReadbackCancel {
+                        tag: exposure.settings.tag,
+                    });
+                    exposure.reading = false;
+                }
+            }
+        }
+
+        fn tick_handler(
+            t: Receiver<TickEvent>,
+            mut exposure: Single<&mut AutoExposure>,
+            mut sender: Sender<(ExposureChanged, ReadbackRequest)>,
+        ) {
+            if exposure.manual.is_none() && !exposure.reading {
+                exposure.reading = true;
+                sender.send(exposure.request());
+            }
+
+            exposure.adapt(t.event.delta);
+            let current = exposure.exposure;
+            let changed = exposure.announced.is_none_or(|announced| {
+                (announced - current).abs() > EXPOSURE_EPSILON * announced.abs()
+            });
+            if changed {
+                exposure.announced = Some(exposure.exposure);
+                sender.send(ExposureChanged {
+                    exposure: exposure.exposure,
+                    log_luminance: exposure
+                        .manual
+                        .is_none()
+                        .then_some(exposure.adapted)
+                        .flatten(),
+                });
+            }
+        }
+
+        world.add_handler(readback_handler);
+        world.add_handler(settings_handler);
+        world.add_handler(manual_handler);
+        world.add_handler(tick_handler);
+    }
+}
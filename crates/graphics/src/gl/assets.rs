@@ -1,17 +1,22 @@
 use crate::gl::font::Font;
 use crate::gl::material::Material;
 use crate::gl::mesh::Mesh;
+use crate::gl::program_cache::ProgramCache;
 use crate::gl::raii::shader_program::ShaderProgram;
 use crate::gl::raii::texture::Texture;
+use crate::gl::texture_array::TextureArrayPool;
 use crate::passes::events::PassEventTrait;
-use anyhow::Context;
+use crate::renderer::resource::{GpuShader, GpuTexture, ResourceTable};
+use crate::renderer::RendererCapabilities;
 use dawn_assets::factory::{BasicFactory, FactoryBinding};
+use dawn_assets::ir::texture::IRTextureType;
 use dawn_assets::ir::IRAsset;
-use dawn_assets::AssetType;
+use dawn_assets::{AssetMemoryUsage, AssetType};
+use std::cell::RefCell;
 use std::time::Duration;
 
 pub(crate) struct ShaderAssetFactory {
-    basic_factory: BasicFactory<ShaderProgram>,
+    basic_factory: BasicFactory<GpuShader>,
 }
 
 impl ShaderAssetFactory {
@@ -26,26 +31,71 @@ impl ShaderAssetFactory {
         self.basic_factory.bind(binding);
     }
 
-    pub fn process_events<E: PassEventTrait>(&mut self) {
-        self.basic_factory.process_events(
+    pub fn process_events(
+        &mut self,
+        shaders: &mut ResourceTable<GpuShader, ShaderProgram>,
+        cache: Option<&mut ProgramCache>,
+    ) {
+        let shaders = RefCell::new(shaders);
+        let cache = RefCell::new(cache);
+        self.basic_factory.process_events_with_reload(
             |message| {
                 if let IRAsset::Shader(shader) = message.ir {
-                    let res = ShaderProgram::from_ir::<E>(shader)?;
-                    Ok(res)
+                    let checksum = message.asset_header.checksum;
+                    let mut cache = cache.borrow_mut();
+                    if let Some(program) = cache.as_mut().and_then(|c| c.load(&checksum)) {
+                        let handle = shaders.borrow_mut().insert(program);
+                        let usage = AssetMemoryUsage::new(size_of::<ShaderProgram>(), 0);
+                        return Ok((GpuShader::new(handle), usage));
+                    }
+
+                    let (program, usage) = ShaderProgram::from_ir(shader, cache.is_some())?;
+                    if let Some(cache) = cache.as_ref() {
+                        cache.store(&checksum, &program);
+                    }
+                    let handle = shaders.borrow_mut().insert(program);
+                    Ok((GpuShader::new(handle), usage))
                 } else {
                     Err(anyhow::anyhow!("Expected shader metadata"))
                 }
             },
-            |_| {
-                // Free will be handled in the Drop implementation of ShaderProgram
+            |shader, message| {
+                // Recompiled into the same handle, the passes keep using it.
+                // The reloaded sources are usually short-lived, so they are not cached
+                if let IRAsset::Shader(ir) = message.ir {
+                    let (program, usage) = ShaderProgram::from_ir(ir, false)?;
+                    shaders.borrow_mut().replace(shader.handle(), program);
+                    Ok(usage)
+                } else {
+                    Err(anyhow::anyhow!("Expected shader metadata"))
+                }
+            },
+            |shader| {
+                // The program is deleted in the Drop implementation of ShaderProgram
+                shaders.borrow_mut().remove(shader.handle());
             },
             Duration::ZERO,
         );
     }
 }
 
+// Whether the device can create the texture. Otherwise the driver fails
+// with the bare GL_INVALID_VALUE, or worse, silently creates an empty texture
+fn fits_limits(texture_type: &IRTextureType, capabilities: &RendererCapabilities) -> bool {
+    let size = capabilities.max_texture_size;
+    match *texture_type {
+        IRTextureType::Texture2D { width, height } => width <= size && height <= size,
+        IRTextureType::Texture2DArray {
+            width,
+            height,
+            layers,
+        } => width <= size && height <= size && layers <= capabilities.max_array_texture_layers,
+        _ => true,
+    }
+}
+
 pub(crate) struct TextureAssetFactory {
-    basic_factory: BasicFactory<Texture>,
+    basic_factory: BasicFactory<GpuTexture>,
 }
 
 impl TextureAssetFactory {
@@ -60,18 +110,51 @@ impl TextureAssetFactory {
         self.basic_factory.bind(binding);
     }
 
-    pub fn process_events<E: PassEventTrait>(&mut self) {
+    pub fn process_events<E: PassEventTrait>(
+        &mut self,
+        textures: &mut ResourceTable<GpuTexture, Texture>,
+        arrays: Option<&mut TextureArrayPool>,
+        capabilities: &RendererCapabilities,
+    ) {
+        let textures = RefCell::new(textures);
+        let arrays = RefCell::new(arrays);
         self.basic_factory.process_events(
             |message| {
                 if let IRAsset::Texture(texture) = message.ir {
-                    let res = Texture::from_ir::<E>(texture)?;
-                    Ok(res)
+                    let (texture_type, pixel_format) = (texture.texture_type, texture.pixel_format);
+                    if !fits_limits(&texture_type, capabilities) {
+                        return Err(anyhow::anyhow!(
+                            "Texture {:?} exceeds the limits of the device (max size {}, max layers {})",
+                            texture_type,
+                            capabilities.max_texture_size,
+                            capabilities.max_array_texture_layers
+                        ));
+                    }
+                    if let Some(arrays) = arrays.borrow_mut().as_mut() {
+                        if let Some((handle, layer)) =
+                            arrays.insert(&texture, &mut textures.borrow_mut())?
+                        {
+                            let asset = GpuTexture::new(handle, texture_type, pixel_format);
+                            let usage = AssetMemoryUsage::new(size_of::<GpuTexture>(), 0);
+                            return Ok((asset.with_layer(layer), usage));
+                        }
+                    }
+
+                    let (texture, usage) = Texture::from_ir::<E>(texture)?;
+                    let handle = textures.borrow_mut().insert(texture);
+                    Ok((GpuTexture::new(handle, texture_type, pixel_format), usage))
                 } else {
                     Err(anyhow::anyhow!("Expected texture metadata"))
                 }
             },
-            |_| {
-                // Free will be handled in the Drop implementation of Texture
+            |texture| match (texture.layer(), arrays.borrow_mut().as_mut()) {
+                (Some(layer), Some(arrays)) => {
+                    arrays.remove(texture.handle(), layer, &mut textures.borrow_mut());
+                }
+                _ => {
+                    // The texture is deleted in the Drop implementation of Texture
+                    textures.borrow_mut().remove(texture.handle());
+                }
             },
             Duration::ZERO,
         );
@@ -94,7 +177,7 @@ impl MeshAssetFactory {
         self.basic_factory.bind(binding);
     }
 
-    pub fn process_events<E: PassEventTrait>(&mut self) {
+    pub fn process_events(&mut self) {
         self.basic_factory.process_events(
             |message| {
                 if let IRAsset::Mesh(mesh) = message.ir {
@@ -128,11 +211,11 @@ impl MaterialAssetFactory {
         self.basic_factory.bind(binding);
     }
 
-    pub fn process_events<E: PassEventTrait>(&mut self) {
+    pub fn process_events(&mut self) {
         self.basic_factory.process_events(
             |message| {
                 if let IRAsset::Material(material) = message.ir {
-                    let res = Material::from_ir::<E>(material, message.dependencies)?;
+                    let res = Material::from_ir(material, message.dependencies)?;
                     Ok(res)
                 } else {
                     Err(anyhow::anyhow!("Expected material metadata"))
@@ -162,11 +245,11 @@ impl FontAssetFactory {
         self.basic_factory.bind(binding);
     }
 
-    pub fn process_events<E: PassEventTrait>(&mut self) {
+    pub fn process_events(&mut self) {
         self.basic_factory.process_events(
             |message| {
                 if let IRAsset::Font(font) = message.ir {
-                    let res = Font::from_ir::<E>(font, message.dependencies)?;
+                    let res = Font::from_ir(font, message.dependencies)?;
                     Ok(res)
                 } else {
                     Err(anyhow::anyhow!("Expected font metadata"))
@@ -0,0 +1,571 @@
+use crate::atmosphere::{
+    AtmosphereEvent, AtmosphereEventTrait, FogSettings, Preetham, SkySettings,
+};
+use crate::gl::bindings;
+use crate::gl::bindings::types::{GLboolean, GLint};
+use crate::gl::raii::shader::ShaderError;
+use crate::gl::raii::shader_program::{ShaderProgram, UniformLocation};
+use crate::gl::raii::texture::Texture;
+use crate::gl::raii::vertex_array::VertexArray;
+use crate::passes::events::{PassEventTarget, RenderPassTargetId};
+use crate::passes::result::RenderResult;
+use crate::passes::RenderPass;
+use crate::renderable::{RenderLayers, RenderableSunLight};
+use crate::renderer::backend::RendererBackend;
+use crate::renderer::target::RenderTargetId;
+use dawn_assets::ir::mesh::{IRIndexType, IRTopology};
+use dawn_assets::ir::settings::IRSettings;
+use dawn_assets::ir::shader::{IRShader, IRShaderSourceKind};
+use glam::{Mat4, Vec3};
+use log::error;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AtmosphereError {
+    #[error("Failed to compile the atmosphere shader: {0}")]
+    Shader(#[from] ShaderError),
+    #[error("Failed to allocate VertexArray")]
+    VertexArrayAllocationFailed,
+}
+
+// Sun lighting the sky when the scene has no RenderableSunLight
+const ZENITH_SUN: RenderableSunLight = RenderableSunLight {
+    direction: Vec3::Y,
+    color: Vec3::ONE,
+    intensity: 1.0,
+};
+
+// Triangle covering the whole screen at the far plane, built from the vertex index
+const FULLSCREEN_VERTEX: &str = r#"#version 330 core
+out vec2 v_ndc;
+
+void main() {
+    vec2 position = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2)) * 2.0 - 1.0;
+    v_ndc = position;
+    gl_Position = vec4(position, 1.0, 1.0);
+}
+"#;
+
+const VIEW_RAY: &str = r#"
+uniform mat4 u_inv_projection;
+uniform mat4 u_inv_view;
+
+vec3 view_ray(vec2 ndc) {
+    vec4 near = u_inv_projection * vec4(ndc, -1.0, 1.0);
+    return normalize(mat3(u_inv_view) * (near.xyz / near.w));
+}
+"#;
+
+const SKY_FRAGMENT: &str = r#"
+in vec2 v_ndc;
+out vec4 color;
+
+uniform vec3 u_a;
+uniform vec3 u_b;
+uniform vec3 u_c;
+uniform vec3 u_d;
+uniform vec3 u_e;
+uniform vec3 u_zenith;
+uniform vec3 u_sun_direction;
+uniform vec3 u_sun_radiance;
+uniform vec3 u_ground_color;
+uniform float u_exposure;
+uniform float u_sun_disk;
+
+vec3 perez(float cos_theta, float gamma, float cos_gamma) {
+    return (1.0 + u_a * exp(u_b / cos_theta))
+        * (1.0 + u_c * exp(u_d * gamma) + u_e * cos_gamma * cos_gamma);
+}
+
+vec3 xyy_to_rgb(vec3 yxy) {
+    vec3 xyz = vec3(yxy.y * yxy.x / yxy.z, yxy.x, (1.0 - yxy.y - yxy.z) * yxy.x / yxy.z);
+    return max(vec3(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z
+    ), vec3(0.0));
+}
+
+void main() {
+    vec3 direction = view_ray(v_ndc);
+    if (direction.y < 0.0) {
+        color = vec4(u_ground_color, 1.0);
+        return;
+    }
+
+    float cos_gamma = clamp(dot(direction, u_sun_direction), -1.0, 1.0);
+    float gamma = acos(cos_gamma);
+    vec3 yxy = u_zenith * perez(max(direction.y, 0.01), gamma, cos_gamma);
+    vec3 radiance = xyy_to_rgb(yxy) * u_exposure;
+    if (gamma < u_sun_disk) {
+        radiance += u_sun_radiance;
+    }
+    color = vec4(radiance, 1.0);
+}
+"#;
+
+const FOG_FRAGMENT: &str = r#"
+in vec2 v_ndc;
+out vec4 color;
+
+uniform sampler2D u_distance;
+uniform vec3 u_camera;
+uniform vec3 u_color;
+uniform vec3 u_sun_direction;
+uniform vec3 u_sun_radiance;
+uniform float u_density;
+uniform float u_height_falloff;
+uniform float u_base_height;
+uniform float u_start;
+uniform float u_max_opacity;
+uniform float u_sun_scattering;
+uniform float u_sky_distance;
+
+void main() {
+    float distance = texture(u_distance, v_ndc * 0.5 + 0.5).r;
+    if (distance <= 0.0) {
+        distance = u_sky_distance;
+    }
+    if (distance <= u_start) {
+        discard;
+    }
+
+    // The density integrated along the ray past the start
+    vec3 direction = view_ray(v_ndc);
+    float span = distance - u_start;
+    float height = u_camera.y + direction.y * u_start - u_base_height;
+    float k = u_height_falloff * direction.y;
+    float path = abs(k) > 1e-4 ? (1.0 - exp(-k * span)) / k : span;
+    float amount = u_density * exp(-u_height_falloff * height) * path;
+    float opacity = min(1.0 - exp(-amount), u_max_opacity);
+
+    float facing = max(dot(direction, u_sun_direction), 0.0);
+    float scattering = min(pow(facing, 8.0) * u_sun_scattering, 1.0);
+    color = vec4(mix(u_color, u_sun_radiance, scattering), opacity);
+}
+"#;
+
+// Program drawing the fullscreen triangle, the array is empty
+// since the vertices are built in the shader
+struct FullscreenProgram {
+    program: ShaderProgram,
+    vao: VertexArray,
+    inv_projection: UniformLocation,
+    inv_view: UniformLocation,
+}
+
+impl FullscreenProgram {
+    fn new(fragment: &str) -> Result<Self, AtmosphereError> {
+        let fragment = format!("#version 330 core\n{}{}", VIEW_RAY, fragment);
+        let ir = IRShader {
+            compile_options: vec![],
+            sources: HashMap::from([
+                (
+                    IRShaderSourceKind::Vertex,
+                    FULLSCREEN_VERTEX.as_bytes().to_vec(),
+                ),
+                (IRShaderSourceKind::Fragment, fragment.into_bytes()),
+            ]),
+        };
+        let (program, _) = ShaderProgram::from_ir(ir, false)?;
+        let vao = VertexArray::new(IRTopology::Triangles, IRIndexType::U16)
+            .ok_or(AtmosphereError::VertexArrayAllocationFailed)?;
+        Ok(FullscreenProgram {
+            inv_projection: program.get_uniform_location("u_inv_projection")?,
+            inv_view: program.get_uniform_location("u_inv_view")?,
+            program,
+            vao,
+        })
+    }
+
+    fn location(&self, name: &str) -> Result<UniformLocation, AtmosphereError> {
+        Ok(self.program.get_uniform_location(name)?)
+    }
+
+    fn bind(&self, camera: &Camera) {
+        ShaderProgram::bind(&self.program);
+        self.program
+            .set_uniform(self.inv_projection, camera.inv_projection);
+        self.program.set_uniform(self.inv_view, camera.inv_view);
+    }
+
+    fn draw(&self) -> RenderResult {
+        let _binding = self.vao.bind();
+        unsafe {
+            bindings::DrawArrays(bindings::TRIANGLES, 0, 3);
+        }
+        ShaderProgram::unbind();
+        RenderResult::ok(1, 1)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Camera {
+    position: Vec3,
+    inv_projection: Mat4,
+    inv_view: Mat4,
+}
+
+impl Camera {
+    fn new(view: Mat4, projection: Mat4) -> Self {
+        let inv_view = view.inverse();
+        Camera {
+            position: inv_view.w_axis.truncate(),
+            inv_projection: projection.inverse(),
+            inv_view,
+        }
+    }
+}
+
+// Depth and blending state changed by the passes, restored once they are done
+struct SavedState {
+    depth_test: GLboolean,
+    depth_mask: GLboolean,
+    depth_func: GLint,
+    blend: GLboolean,
+    blend_func: [GLint; 4],
+}
+
+impl SavedState {
+    unsafe fn save() -> Self {
+        let mut state = SavedState {
+            depth_test: bindings::IsEnabled(bindings::DEPTH_TEST),
+            depth_mask: 0,
+            depth_func: 0,
+            blend: bindings::IsEnabled(bindings::BLEND),
+            blend_func: [0; 4],
+        };
+        bindings::GetBooleanv(bindings::DEPTH_WRITEMASK, &mut state.depth_mask);
+        bindings::GetIntegerv(bindings::DEPTH_FUNC, &mut state.depth_func);
+        for (value, name) in state.blend_func.iter_mut().zip([
+            bindings::BLEND_SRC_RGB,
+            bindings::BLEND_DST_RGB,
+            bindings::BLEND_SRC_ALPHA,
+            bindings::BLEND_DST_ALPHA,
+        ]) {
+            bindings::GetIntegerv(name, value);
+        }
+        state
+    }
+
+    unsafe fn restore(&self) {
+        let toggle = |capability, enabled: GLboolean| {
+            if enabled != 0 {
+                bindings::Enable(capability);
+            } else {
+                bindings::Disable(capability);
+            }
+        };
+        toggle(bindings::DEPTH_TEST, self.depth_test);
+        toggle(bindings::BLEND, self.blend);
+        bindings::DepthMask(self.depth_mask);
+        bindings::DepthFunc(self.depth_func as _);
+        let [src_rgb, dst_rgb, src_alpha, dst_alpha] = self.blend_func.map(|f| f as _);
+        bindings::BlendFuncSeparate(src_rgb, dst_rgb, src_alpha, dst_alpha);
+    }
+}
+
+fn dispatch_event<P, E>(ptr: *mut u8, event: E)
+where
+    P: RenderPass<E>,
+    E: AtmosphereEventTrait,
+{
+    let pass = unsafe { &mut *(ptr as *mut P) };
+    pass.dispatch(event);
+}
+
+// Binds the output target, or keeps the current framebuffer if there is none
+macro_rules! bind_output {
+    ($backend:expr, $output:expr) => {
+        match $output {
+            Some(id) => match $backend.render_target(id) {
+                Ok(target) => Some(target.bind()),
+                Err(e) => {
+                    error!("Atmosphere pass output is not available: {}", e);
+                    return RenderResult::failed();
+                }
+            },
+            None => None,
+        }
+    };
+}
+
+/// Draws the Preetham sky (see `SkySettings`) behind the geometry.
+/// The sky is drawn at the far plane with the depth test passing only where
+/// nothing was rendered, so it can run either before or after the opaque pass
+/// of the forward pipeline, or over the lit output of the deferred one.
+/// The pass skips the frames until it receives `AtmosphereEvent::SetCamera`.
+pub struct SkyPass<E: AtmosphereEventTrait> {
+    id: RenderPassTargetId,
+    output: Option<RenderTargetId>,
+    settings: SkySettings,
+    camera: Option<Camera>,
+    program: FullscreenProgram,
+    // A..E coefficients, zenith, sun direction and radiance, ground color, exposure, sun disk
+    uniforms: [UniformLocation; 11],
+    _marker: PhantomData<E>,
+}
+
+impl<E: AtmosphereEventTrait> SkyPass<E> {
+    /// Renders into the `output` target, or into the current framebuffer if `None`.
+    pub fn new(
+        id: RenderPassTargetId,
+        output: Option<RenderTargetId>,
+        settings: SkySettings,
+    ) -> Result<Self, AtmosphereError> {
+        let program = FullscreenProgram::new(SKY_FRAGMENT)?;
+        let mut uniforms = [0; 11];
+        for (location, name) in uniforms.iter_mut().zip([
+            "u_a",
+            "u_b",
+            "u_c",
+            "u_d",
+            "u_e",
+            "u_zenith",
+            "u_sun_direction",
+            "u_sun_radiance",
+            "u_ground_color",
+            "u_exposure",
+            "u_sun_disk",
+        ]) {
+            *location = program.location(name)?;
+        }
+        Ok(SkyPass {
+            id,
+            output,
+            settings,
+            camera: None,
+            program,
+            uniforms,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn settings(&self) -> &SkySettings {
+        &self.settings
+    }
+}
+
+impl<E: AtmosphereEventTrait> RenderPass<E> for SkyPass<E> {
+    fn get_target(&self) -> Vec<PassEventTarget<E>> {
+        vec![PassEventTarget::new(
+            dispatch_event::<Self, E>,
+            self.id,
+            self,
+        )]
+    }
+
+    fn dispatch(&mut self, event: E) {
+        match event.try_into() {
+            Ok(AtmosphereEvent::SetCamera { view, projection }) => {
+                self.camera = Some(Camera::new(view, projection));
+            }
+            Ok(AtmosphereEvent::SetSky(settings)) => self.settings = settings,
+            _ => {}
+        }
+    }
+
+    fn name(&self) -> &str {
+        "SkyPass"
+    }
+
+    fn layers(&self) -> RenderLayers {
+        RenderLayers::NONE
+    }
+
+    fn on_settings(&mut self, _backend: &mut RendererBackend<E>, settings: &IRSettings) {
+        self.settings.apply(settings);
+    }
+
+    fn begin(&mut self, backend: &RendererBackend<E>) -> RenderResult {
+        let Some(camera) = self.camera else {
+            return RenderResult::default();
+        };
+        let sun = backend.sun_light().unwrap_or(ZENITH_SUN);
+        let model = Preetham::new(self.settings.turbidity, sun.direction);
+        let _target = bind_output!(backend, self.output);
+
+        self.program.bind(&camera);
+        let program = &self.program.program;
+        let [a, b, c, d, e, zenith, sun_direction, sun_radiance, ground, exposure, disk] =
+            self.uniforms;
+        program.set_uniform(a, model.a);
+        program.set_uniform(b, model.b);
+        program.set_uniform(c, model.c);
+        program.set_uniform(d, model.d);
+        program.set_uniform(e, model.e);
+        program.set_uniform(zenith, model.zenith);
+        program.set_uniform(sun_direction, sun.direction);
+        program.set_uniform(sun_radiance, sun.color * sun.intensity);
+        program.set_uniform(ground, self.settings.ground_color);
+        program.set_uniform(exposure, self.settings.exposure);
+        program.set_uniform(disk, self.settings.sun_disk);
+
+        unsafe {
+            let state = SavedState::save();
+            bindings::Enable(bindings::DEPTH_TEST);
+            bindings::DepthFunc(bindings::LEQUAL);
+            bindings::DepthMask(bindings::FALSE);
+            bindings::Disable(bindings::BLEND);
+            let result = self.program.draw();
+            state.restore();
+            result
+        }
+    }
+}
+
+/// Blends the height fog (see `FogSettings`) over the rendered frame.
+/// The distances from the camera are read from the red channel of the color
+/// attachment `distance` of the render target (e.g. the float one written by the
+/// geometry pass of the deferred pipeline or by the depth prepass of the forward
+/// one), zero meaning no geometry. Runs after the lighting and before the transparent objects.
+/// The pass skips the frames until it receives `AtmosphereEvent::SetCamera`.
+pub struct FogPass<E: AtmosphereEventTrait> {
+    id: RenderPassTargetId,
+    distance: (RenderTargetId, usize),
+    output: Option<RenderTargetId>,
+    settings: FogSettings,
+    camera: Option<Camera>,
+    program: FullscreenProgram,
+    // Distance, camera, color, sun direction and radiance, then the scalars
+    // in the order of the FogSettings fields
+    uniforms: [UniformLocation; 12],
+    _marker: PhantomData<E>,
+}
+
+impl<E: AtmosphereEventTrait> FogPass<E> {
+    /// Reads the distances from the attachment `distance.1` of the target `distance.0`
+    /// and renders into the `output` target, or into the current framebuffer if `None`.
+    pub fn new(
+        id: RenderPassTargetId,
+        distance: (RenderTargetId, usize),
+        output: Option<RenderTargetId>,
+        settings: FogSettings,
+    ) -> Result<Self, AtmosphereError> {
+        let program = FullscreenProgram::new(FOG_FRAGMENT)?;
+        let mut uniforms = [0; 12];
+        for (location, name) in uniforms.iter_mut().zip([
+            "u_distance",
+            "u_camera",
+            "u_color",
+            "u_sun_direction",
+            "u_sun_radiance",
+            "u_density",
+            "u_height_falloff",
+            "u_base_height",
+            "u_start",
+            "u_max_opacity",
+            "u_sun_scattering",
+            "u_sky_distance",
+        ]) {
+            *location = program.location(name)?;
+        }
+        Ok(FogPass {
+            id,
+            distance,
+            output,
+            settings,
+            camera: None,
+            program,
+            uniforms,
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn settings(&self) -> &FogSettings {
+        &self.settings
+    }
+}
+
+impl<E: AtmosphereEventTrait> RenderPass<E> for FogPass<E> {
+    fn get_target(&self) -> Vec<PassEventTarget<E>> {
+        vec![PassEventTarget::new(
+            dispatch_event::<Self, E>,
+            self.id,
+            self,
+        )]
+    }
+
+    fn dispatch(&mut self, event: E) {
+        match event.try_into() {
+            Ok(AtmosphereEvent::SetCamera { view, projection }) => {
+                self.camera = Some(Camera::new(view, projection));
+            }
+            Ok(AtmosphereEvent::SetFog(settings)) => self.settings = settings,
+            _ => {}
+        }
+    }
+
+    fn name(&self) -> &str {
+        "FogPass"
+    }
+
+    fn layers(&self) -> RenderLayers {
+        RenderLayers::NONE
+    }
+
+    fn on_settings(&mut self, _backend: &mut RendererBackend<E>, settings: &IRSettings) {
+        self.settings.apply(settings);
+    }
+
+    fn begin(&mut self, backend: &RendererBackend<E>) -> RenderResult {
+        let Some(camera) = self.camera else {
+            return RenderResult::default();
+        };
+        let (target, index) = self.distance;
+        let Some(distance) = backend
+            .render_target(target)
+            .ok()
+            .and_then(|target| target.color(index))
+        else {
+            error!(
+                "Fog distance attachment {} of {} is not available",
+                index, target
+            );
+            return RenderResult::failed();
+        };
+        // Without the sun the fog is not lit towards it
+        let (sun, scattering) = match backend.sun_light() {
+            Some(sun) => (sun, self.settings.sun_scattering),
+            None => (ZENITH_SUN, 0.0),
+        };
+        let _target = bind_output!(backend, self.output);
+
+        self.program.bind(&camera);
+        let program = &self.program.program;
+        let s = &self.settings;
+        let [texture, position, color, sun_direction, sun_radiance, scalars @ ..] = self.uniforms;
+        program.set_uniform(texture, 0);
+        program.set_uniform(position, camera.position);
+        program.set_uniform(color, s.color);
+        program.set_uniform(sun_direction, sun.direction);
+        program.set_uniform(sun_radiance, sun.color * sun.intensity);
+        for (location, value) in scalars.into_iter().zip([
+            s.density,
+            s.height_falloff,
+            s.base_height,
+            s.start,
+            s.max_opacity,
+            scattering,
+            s.sky_distance,
+        ]) {
+            program.set_uniform(location, value);
+        }
+
+        Texture::bind(bindings::TEXTURE_2D, distance, 0);
+        let result = unsafe {
+            let state = SavedState::save();
+            bindings::Disable(bindings::DEPTH_TEST);
+            bindings::DepthMask(bindings::FALSE);
+            bindings::Enable(bindings::BLEND);
+            bindings::BlendFunc(bindings::SRC_ALPHA, bindings::ONE_MINUS_SRC_ALPHA);
+            let result = self.program.draw();
+            state.restore();
+            result
+        };
+        Texture::unbind(bindings::TEXTURE_2D, 0);
+        result
+    }
+}
@@ -1 +1,3 @@
+// Generated by gl_generator in build.rs, not worth linting.
+#![allow(clippy::all)]
 include!(concat!(env!("OUT_DIR"), "/gl_bindings.rs"));
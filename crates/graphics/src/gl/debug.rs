@@ -166,14 +166,12 @@ impl Debugger {
 
             let bytes =
                 unsafe { std::slice::from_raw_parts(message as *const u8, length as usize) };
-            let msg = std::borrow::Cow::from(String::from_utf8_lossy(bytes)); // Cow<'_, str>
+            let msg = String::from_utf8_lossy(bytes); // Cow<'_, str>
 
             // Restore the outer Box from the raw pointer
-            let outer: &Box<Callback> = unsafe { &*(user_param as *mut Box<Callback>) };
-            // Convert the outer Box to a reference to the inner Box
-            let f = &**outer;
+            let outer: &Callback = unsafe { &*(user_param as *mut Box<Callback>) };
             // Call stored callback function
-            f(source, message_type, severity, &*msg);
+            outer(source, message_type, severity, &msg);
         }
 
         // Enable OpenGL debug output
@@ -4,7 +4,6 @@ use crate::gl::raii::vertex_array::VertexArray;
 use crate::passes::events::PassEventTrait;
 use crate::passes::result::RenderResult;
 use dawn_assets::ir::font::{IRFont, IRGlyph, IRGlyphVertex};
-use dawn_assets::ir::mesh::IRIndexType;
 use dawn_assets::{Asset, AssetCastable, AssetID, AssetMemoryUsage};
 use log::debug;
 use std::collections::HashMap;
@@ -35,7 +34,7 @@ pub struct Font {
 impl AssetCastable for Font {}
 
 impl Font {
-    pub(crate) fn from_ir<E: PassEventTrait>(
+    pub(crate) fn from_ir(
         ir: IRFont,
         deps: HashMap<AssetID, Asset>,
     ) -> Result<(Self, AssetMemoryUsage), FontError> {
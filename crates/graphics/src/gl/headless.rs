@@ -0,0 +1,232 @@
+//! OpenGL context without the window, see `GLRenderer::new_headless`.
+//! The frames are rendered to the pbuffer of the EGL surfaceless platform,
+//! so neither X11 nor the GPU is required (e.g. Mesa llvmpipe in the CI).
+
+use crate::gl::GLRendererError;
+use std::ffi::c_void;
+
+#[cfg(target_os = "linux")]
+mod egl {
+    use std::ffi::{c_char, c_void};
+
+    pub type EGLDisplay = *mut c_void;
+    pub type EGLConfig = *mut c_void;
+    pub type EGLContext = *mut c_void;
+    pub type EGLSurface = *mut c_void;
+    pub type EGLint = i32;
+    pub type EGLenum = u32;
+    pub type EGLBoolean = u32;
+
+    pub const DEFAULT_DISPLAY: *mut c_void = std::ptr::null_mut();
+    pub const NO_CONTEXT: EGLContext = std::ptr::null_mut();
+    pub const NO_SURFACE: EGLSurface = std::ptr::null_mut();
+
+    pub const NONE: EGLint = 0x3038;
+    pub const ALPHA_SIZE: EGLint = 0x3021;
+    pub const BLUE_SIZE: EGLint = 0x3022;
+    pub const GREEN_SIZE: EGLint = 0x3023;
+    pub const RED_SIZE: EGLint = 0x3024;
+    pub const DEPTH_SIZE: EGLint = 0x3025;
+    pub const STENCIL_SIZE: EGLint = 0x3026;
+    pub const SURFACE_TYPE: EGLint = 0x3033;
+    pub const RENDERABLE_TYPE: EGLint = 0x3040;
+    pub const HEIGHT: EGLint = 0x3056;
+    pub const WIDTH: EGLint = 0x3057;
+    pub const PBUFFER_BIT: EGLint = 0x0001;
+    pub const OPENGL_BIT: EGLint = 0x0008;
+    pub const OPENGL_API: EGLenum = 0x30A2;
+    pub const PLATFORM_SURFACELESS_MESA: EGLenum = 0x31DD;
+
+    pub type GetPlatformDisplayEXT =
+        unsafe extern "C" fn(EGLenum, *mut c_void, *const EGLint) -> EGLDisplay;
+
+    #[link(name = "EGL")]
+    unsafe extern "C" {
+        pub fn eglGetDisplay(display_id: *mut c_void) -> EGLDisplay;
+        pub fn eglInitialize(dpy: EGLDisplay, major: *mut EGLint, minor: *mut EGLint)
+            -> EGLBoolean;
+        pub fn eglTerminate(dpy: EGLDisplay) -> EGLBoolean;
+        pub fn eglBindAPI(api: EGLenum) -> EGLBoolean;
+        pub fn eglChooseConfig(
+            dpy: EGLDisplay,
+            attrib_list: *const EGLint,
+            configs: *mut EGLConfig,
+            config_size: EGLint,
+            num_config: *mut EGLint,
+        ) -> EGLBoolean;
+        pub fn eglCreatePbufferSurface(
+            dpy: EGLDisplay,
+            config: EGLConfig,
+            attrib_list: *const EGLint,
+        ) -> EGLSurface;
+        pub fn eglDestroySurface(dpy: EGLDisplay, surface: EGLSurface) -> EGLBoolean;
+        pub fn eglCreateContext(
+            dpy: EGLDisplay,
+            config: EGLConfig,
+            share_context: EGLContext,
+            attrib_list: *const EGLint,
+        ) -> EGLContext;
+        pub fn eglDestroyContext(dpy: EGLDisplay, ctx: EGLContext) -> EGLBoolean;
+        pub fn eglMakeCurrent(
+            dpy: EGLDisplay,
+            draw: EGLSurface,
+            read: EGLSurface,
+            ctx: EGLContext,
+        ) -> EGLBoolean;
+        pub fn eglGetProcAddress(procname: *const c_char) -> *const c_void;
+        pub fn eglGetError() -> EGLint;
+    }
+}
+
+/// Pbuffer surface and the context current on the thread that created it.
+/// The renderer using it must not leave that thread.
+pub(crate) struct HeadlessContext {
+    #[cfg(target_os = "linux")]
+    display: egl::EGLDisplay,
+    #[cfg(target_os = "linux")]
+    surface: egl::EGLSurface,
+    #[cfg(target_os = "linux")]
+    context: egl::EGLContext,
+}
+
+#[cfg(target_os = "linux")]
+fn egl_error(what: &str) -> GLRendererError {
+    let code = unsafe { egl::eglGetError() };
+    GLRendererError::HeadlessError(format!("{} (EGL error {:#x})", what, code))
+}
+
+#[cfg(target_os = "linux")]
+impl HeadlessContext {
+    pub fn new(width: usize, height: usize) -> Result<Self, GLRendererError> {
+        unsafe {
+            // The surfaceless platform does not need any display server.
+            // Fall back to the default display of the older drivers
+            let name = c"eglGetPlatformDisplayEXT";
+            let get_platform_display = egl::eglGetProcAddress(name.as_ptr());
+            let mut display = std::ptr::null_mut();
+            if !get_platform_display.is_null() {
+                let get_platform_display: egl::GetPlatformDisplayEXT =
+                    std::mem::transmute(get_platform_display);
+                display = get_platform_display(
+                    egl::PLATFORM_SURFACELESS_MESA,
+                    egl::DEFAULT_DISPLAY,
+                    std::ptr::null(),
+                );
+            }
+            if display.is_null() {
+                display = egl::eglGetDisplay(egl::DEFAULT_DISPLAY);
+            }
+            if display.is_null() {
+                return Err(egl_error("Failed to get the EGL display"));
+            }
+
+            let (mut major, mut minor) = (0, 0);
+            if egl::eglInitialize(display, &mut major, &mut minor) == 0 {
+                return Err(egl_error("Failed to initialize EGL"));
+            }
+            let mut context = HeadlessContext {
+                display,
+                surface: egl::NO_SURFACE,
+                context: egl::NO_CONTEXT,
+            };
+            log::info!("Initialized EGL {}.{}", major, minor);
+
+            if egl::eglBindAPI(egl::OPENGL_API) == 0 {
+                return Err(egl_error("OpenGL API is not supported by EGL"));
+            }
+
+            #[rustfmt::skip]
+            let attributes = [
+                egl::SURFACE_TYPE, egl::PBUFFER_BIT,
+                egl::RENDERABLE_TYPE, egl::OPENGL_BIT,
+                egl::RED_SIZE, 8,
+                egl::GREEN_SIZE, 8,
+                egl::BLUE_SIZE, 8,
+                egl::ALPHA_SIZE, 8,
+                egl::DEPTH_SIZE, 24,
+                egl::STENCIL_SIZE, 8,
+                egl::NONE,
+            ];
+            let mut config = std::ptr::null_mut();
+            let mut count = 0;
+            if egl::eglChooseConfig(display, attributes.as_ptr(), &mut config, 1, &mut count) == 0
+                || count == 0
+            {
+                return Err(egl_error("No EGL config supports the pbuffers"));
+            }
+
+            let surface_attributes = [
+                egl::WIDTH,
+                width as egl::EGLint,
+                egl::HEIGHT,
+                height as egl::EGLint,
+                egl::NONE,
+            ];
+            context.surface =
+                egl::eglCreatePbufferSurface(display, config, surface_attributes.as_ptr());
+            if context.surface.is_null() {
+                return Err(egl_error("Failed to create the pbuffer surface"));
+            }
+
+            // Same as the GLX context of the view: the highest version available
+            let context_attributes = [egl::NONE];
+            context.context = egl::eglCreateContext(
+                display,
+                config,
+                egl::NO_CONTEXT,
+                context_attributes.as_ptr(),
+            );
+            if context.context.is_null() {
+                return Err(egl_error("Failed to create the EGL context"));
+            }
+
+            if egl::eglMakeCurrent(display, context.surface, context.surface, context.context) == 0
+            {
+                return Err(egl_error("Failed to make the EGL context current"));
+            }
+
+            Ok(context)
+        }
+    }
+
+    pub fn get_proc_addr(&self, symbol: &str) -> *const c_void {
+        match std::ffi::CString::new(symbol) {
+            Ok(symbol) => unsafe { egl::eglGetProcAddress(symbol.as_ptr()) },
+            Err(_) => std::ptr::null(),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for HeadlessContext {
+    fn drop(&mut self) {
+        unsafe {
+            egl::eglMakeCurrent(
+                self.display,
+                egl::NO_SURFACE,
+                egl::NO_SURFACE,
+                egl::NO_CONTEXT,
+            );
+            if !self.context.is_null() {
+                egl::eglDestroyContext(self.display, self.context);
+            }
+            if !self.surface.is_null() {
+                egl::eglDestroySurface(self.display, self.surface);
+            }
+            egl::eglTerminate(self.display);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl HeadlessContext {
+    pub fn new(_width: usize, _height: usize) -> Result<Self, GLRendererError> {
+        Err(GLRendererError::HeadlessError(
+            "Headless rendering is supported only on Linux".to_string(),
+        ))
+    }
+
+    pub fn get_proc_addr(&self, _symbol: &str) -> *const c_void {
+        std::ptr::null()
+    }
+}
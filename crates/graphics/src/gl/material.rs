@@ -1,4 +1,4 @@
-use crate::passes::events::PassEventTrait;
+use crate::renderer::resource::{GpuHandle, GpuTexture};
 use dawn_assets::ir::material::IRMaterial;
 use dawn_assets::{Asset, AssetCastable, AssetID, AssetMemoryUsage};
 use glam::Vec4;
@@ -16,6 +16,22 @@ pub enum MaterialError {
     RoughnessTextureNotFound(AssetID),
 }
 
+/// Reference to the texture packed into the texture array.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureLayer {
+    pub array: GpuHandle<GpuTexture>,
+    pub layer: u32,
+}
+
+/// Array layers of the material textures. `None` for the textures
+/// that are missing or not packed into the arrays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MaterialLayers {
+    pub base_color: Option<TextureLayer>,
+    pub metallic: Option<TextureLayer>,
+    pub roughness: Option<TextureLayer>,
+}
+
 pub struct Material {
     pub base_color_factor: Vec4,
     pub base_color_texture: Option<Asset>,
@@ -47,7 +63,7 @@ impl Default for Material {
 impl AssetCastable for Material {}
 
 impl Material {
-    pub(crate) fn from_ir<E: PassEventTrait>(
+    pub(crate) fn from_ir(
         ir: IRMaterial,
         deps: HashMap<AssetID, Asset>,
     ) -> Result<(Self, AssetMemoryUsage), MaterialError> {
@@ -57,7 +73,7 @@ impl Material {
             Some(
                 deps.get(&texture_id)
                     .cloned()
-                    .ok_or_else(|| MaterialError::BaseColorTextureNotFound(texture_id))?,
+                    .ok_or(MaterialError::BaseColorTextureNotFound(texture_id))?,
             )
         } else {
             None
@@ -66,7 +82,7 @@ impl Material {
             Some(
                 deps.get(&texture_id)
                     .cloned()
-                    .ok_or_else(|| MaterialError::MetallicTextureNotFound(texture_id))?,
+                    .ok_or(MaterialError::MetallicTextureNotFound(texture_id))?,
             )
         } else {
             None
@@ -75,7 +91,7 @@ impl Material {
             Some(
                 deps.get(&texture_id)
                     .cloned()
-                    .ok_or_else(|| MaterialError::RoughnessTextureNotFound(texture_id))?,
+                    .ok_or(MaterialError::RoughnessTextureNotFound(texture_id))?,
             )
         } else {
             None
@@ -95,4 +111,21 @@ impl Material {
             AssetMemoryUsage::new(size_of::<Material>(), 0),
         ))
     }
+
+    /// Array layers of the textures, for the shaders sampling the texture arrays.
+    /// Materials whose textures share the arrays can be drawn without rebinding them.
+    pub fn layers(&self) -> MaterialLayers {
+        let layer = |texture: &Option<Asset>| {
+            let texture = texture.as_ref()?.cast::<GpuTexture>();
+            Some(TextureLayer {
+                array: texture.handle(),
+                layer: texture.layer()?,
+            })
+        };
+        MaterialLayers {
+            base_color: layer(&self.base_color_texture),
+            metallic: layer(&self.metallic_texture),
+            roughness: layer(&self.roughness_texture),
+        }
+    }
 }
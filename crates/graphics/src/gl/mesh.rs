@@ -2,13 +2,27 @@ use crate::gl::raii::array_buffer::{ArrayBuffer, ArrayBufferUsage};
 use crate::gl::raii::element_array_buffer::{ElementArrayBuffer, ElementArrayBufferUsage};
 use crate::gl::raii::vertex_array::VertexArray;
 use crate::passes::result::RenderResult;
-use dawn_assets::ir::mesh::{IRIndexType, IRMesh, IRMeshVertex, IRSubMesh, IRTopology};
+use crate::renderer::BatchingStats;
+use dawn_assets::ir::mesh::{IRIndexType, IRMesh, IRMeshBvh, IRMeshVertex, IRSubMesh, IRTopology};
 use dawn_assets::{Asset, AssetCastable, AssetID, AssetMemoryUsage};
 use glam::Vec3;
 use log::debug;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use thiserror::Error;
 
+// Statistics of the loaded meshes, reported by the renderer monitor
+static BATCHED_SUBMESHES: AtomicUsize = AtomicUsize::new(0);
+static BATCHES: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the statistics of the static batching of all the loaded meshes.
+pub(crate) fn batching_stats() -> BatchingStats {
+    BatchingStats {
+        submeshes: BATCHED_SUBMESHES.load(Ordering::Relaxed),
+        batches: BATCHES.load(Ordering::Relaxed),
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum MeshError {
     #[error("Material with ID '{0}' not found for submesh")]
@@ -36,22 +50,85 @@ pub struct TopologyBucket {
     pub ebo: ElementArrayBuffer,
     pub indices_count: usize, // In units (u32 or u16)
     pub submesh: Vec<SubMesh>,
+    /// Consecutive submeshes sharing the material, merged into a single draw.
+    /// Empty if the mesh is not batched.
+    pub batches: Vec<SubMesh>,
+}
+
+impl TopologyBucket {
+    /// Submeshes to be drawn: the batches if the mesh is batched.
+    #[inline(always)]
+    pub fn draws(&self) -> &[SubMesh] {
+        if self.batches.is_empty() {
+            &self.submesh
+        } else {
+            &self.batches
+        }
+    }
 }
 
 pub struct Mesh {
     pub buckets: Vec<TopologyBucket>,
     pub min: Vec3,
     pub max: Vec3,
+    /// Hierarchy over the triangles, if it was built at the pack time.
+    /// Kept in the RAM for the picking and the collision cooking.
+    pub bvh: Option<IRMeshBvh>,
 }
 
 struct IRBucket {
     topology: IRTopology,
     index_type: IRIndexType,
     irs: Vec<IRSubMesh>,
+    batch: bool,
 }
 
 impl IRBucket {
-    pub fn into_bucket(self, deps: &HashMap<AssetID, Asset>) -> Result<TopologyBucket, MeshError> {
+    /// Orders the submeshes by the material and makes the indices
+    /// relative to the start of the joined vertex buffer,
+    /// so the submeshes of the same material form a contiguous index range.
+    /// Returns `false` if the indices do not fit the index type.
+    fn prepare_batching(&mut self) -> bool {
+        let vertices: usize =
+            self.irs.iter().map(|ir| ir.vertices.len()).sum::<usize>() / size_of::<IRMeshVertex>();
+        if matches!(self.index_type, IRIndexType::U16) && vertices > u16::MAX as usize + 1 {
+            return false;
+        }
+
+        self.irs.sort_by(|a, b| a.material.cmp(&b.material));
+        let mut base = 0;
+        for ir in &mut self.irs {
+            match self.index_type {
+                IRIndexType::U16 => {
+                    for index in ir.indices.chunks_exact_mut(2) {
+                        let value = u16::from_ne_bytes([index[0], index[1]]) + base as u16;
+                        index.copy_from_slice(&value.to_ne_bytes());
+                    }
+                }
+                IRIndexType::U32 => {
+                    for index in ir.indices.chunks_exact_mut(4) {
+                        let bytes = [index[0], index[1], index[2], index[3]];
+                        let value = u32::from_ne_bytes(bytes) + base as u32;
+                        index.copy_from_slice(&value.to_ne_bytes());
+                    }
+                }
+            }
+            base += ir.vertices.len() / size_of::<IRMeshVertex>();
+        }
+        true
+    }
+}
+
+impl IRBucket {
+    pub fn into_bucket(
+        mut self,
+        deps: &HashMap<AssetID, Asset>,
+    ) -> Result<TopologyBucket, MeshError> {
+        let batch = self.batch && self.prepare_batching();
+        if self.batch && !batch {
+            debug!("Mesh is too large for the static batching with 16-bit indices");
+        }
+
         let vao = VertexArray::new(self.topology, self.index_type.clone())
             .ok_or(MeshError::VertexArrayAllocationFailed)?;
         let mut vbo = ArrayBuffer::new().ok_or(MeshError::ArrayBufferAllocationFailed)?;
@@ -80,8 +157,9 @@ impl IRBucket {
             vao_binding.setup_attribute(i, layout);
         }
 
+        // The element buffer binding is part of the vertex array state, so it
+        // is not unbound and only goes out of scope.
         drop(vbo_binding);
-        drop(ebo_binding);
         drop(vao_binding);
 
         let divider = match self.index_type {
@@ -90,6 +168,7 @@ impl IRBucket {
         };
 
         let mut submesh = Vec::with_capacity(self.irs.len());
+        let mut materials = Vec::with_capacity(self.irs.len());
         let mut index_offset = 0;
         let mut vertex_offset = 0;
         for submesh_ir in self.irs {
@@ -106,12 +185,41 @@ impl IRBucket {
                 min: submesh_ir.bounds.min(),
                 max: submesh_ir.bounds.max(),
                 index_offset: index_offset / divider,
-                vertex_offset: vertex_offset / size_of::<IRMeshVertex>(),
+                // The batched indices already include the offset
+                vertex_offset: if batch {
+                    0
+                } else {
+                    vertex_offset / size_of::<IRMeshVertex>()
+                },
                 index_count: submesh_ir.indices.len() / divider,
             });
 
             index_offset += submesh_ir.indices.len();
             vertex_offset += submesh_ir.vertices.len();
+            materials.push(submesh_ir.material);
+        }
+
+        let mut batches: Vec<SubMesh> = Vec::new();
+        if batch {
+            for (i, part) in submesh.iter().enumerate() {
+                match batches.last_mut() {
+                    Some(last) if materials[i - 1] == materials[i] => {
+                        last.min = last.min.min(part.min);
+                        last.max = last.max.max(part.max);
+                        last.index_count += part.index_count;
+                    }
+                    _ => batches.push(SubMesh {
+                        material: part.material.clone(),
+                        min: part.min,
+                        max: part.max,
+                        index_offset: part.index_offset,
+                        vertex_offset: 0,
+                        index_count: part.index_count,
+                    }),
+                }
+            }
+            BATCHED_SUBMESHES.fetch_add(submesh.len(), Ordering::Relaxed);
+            BATCHES.fetch_add(batches.len(), Ordering::Relaxed);
         }
 
         Ok(TopologyBucket {
@@ -120,10 +228,20 @@ impl IRBucket {
             ebo,
             indices_count: index_offset / divider,
             submesh,
+            batches,
         })
     }
 }
 
+impl Drop for TopologyBucket {
+    fn drop(&mut self) {
+        if !self.batches.is_empty() {
+            BATCHED_SUBMESHES.fetch_sub(self.submesh.len(), Ordering::Relaxed);
+            BATCHES.fetch_sub(self.batches.len(), Ordering::Relaxed);
+        }
+    }
+}
+
 impl AssetCastable for Mesh {}
 
 impl Mesh {
@@ -132,6 +250,13 @@ impl Mesh {
         deps: HashMap<AssetID, Asset>,
     ) -> Result<(Self, AssetMemoryUsage), MeshError> {
         debug!("Creating Mesh from IR: {:?}", ir);
+        #[cfg(debug_assertions)]
+        for issue in dawn_assets::units::check_mesh(&ir) {
+            log::warn!(
+                "Mesh does not seem to follow the engine conventions: {}",
+                issue
+            );
+        }
 
         // Group submeshes by topology
         let mut ir_buckets = HashMap::new();
@@ -142,6 +267,7 @@ impl Mesh {
                     topology: submesh.topology.clone(),
                     index_type: ir.index_type.clone(),
                     irs: Vec::new(),
+                    batch: ir.static_batching,
                 });
             bucket.irs.push(submesh);
         }
@@ -151,28 +277,27 @@ impl Mesh {
             buckets.push(bucket.into_bucket(&deps)?);
         }
 
+        let ram = size_of::<Mesh>() + ir.bvh.as_ref().map_or(0, |bvh| bvh.memory_usage());
         Ok((
             Mesh {
                 buckets,
                 min: ir.bounds.min(),
                 max: ir.bounds.max(),
+                bvh: ir.bvh,
             },
-            AssetMemoryUsage::new(size_of::<Mesh>(), 0),
+            AssetMemoryUsage::new(ram, 0),
         ))
     }
 }
 
 impl Mesh {
     #[inline(always)]
-    pub fn draw(
-        &self,
-        on_submesh: impl Fn(&SubMesh) -> (bool, RenderResult),
-    ) -> RenderResult {
+    pub fn draw(&self, on_submesh: impl Fn(&SubMesh) -> (bool, RenderResult)) -> RenderResult {
         let mut result = RenderResult::default();
 
         for bucket in &self.buckets {
             let binding = bucket.vao.bind();
-            for submesh in &bucket.submesh {
+            for submesh in bucket.draws() {
                 let (skip, new_result) = on_submesh(submesh);
                 result += new_result;
 
@@ -1,29 +1,69 @@
 pub mod assets;
+pub mod atmosphere;
 pub mod bindings;
 mod debug;
 pub mod font;
+#[cfg(feature = "headless")]
+mod headless;
 pub mod material;
 pub mod mesh;
 mod probe;
+mod program_cache;
 pub mod raii;
+mod readback;
+mod stats;
+pub mod target;
+pub(crate) mod texture_array;
+mod warm_up;
 
 use crate::gl::assets::{
     FontAssetFactory, MaterialAssetFactory, MeshAssetFactory, ShaderAssetFactory,
     TextureAssetFactory,
 };
+use crate::gl::bindings::types::GLuint;
 use crate::gl::debug::{Debugger, MessageType};
+use crate::gl::program_cache::ProgramCache;
+use crate::gl::raii::shader::ShaderError;
+use crate::gl::raii::shader_program::ShaderProgram;
+use crate::gl::raii::texture::Texture;
+use crate::gl::readback::{ReadbackTexture, Readbacks};
+use crate::gl::target::{RenderTarget, RenderTargetDescriptor, RenderTargetError};
+use crate::gl::texture_array::TextureArrayPool;
 use crate::passes::events::PassEventTrait;
-use crate::renderer::backend::{RendererBackendConfig, RendererBackendError, RendererBackendTrait};
-use crate::view::{ViewError, ViewHandle};
+use crate::renderable::RenderableSunLight;
+use crate::renderer::backend::{RendererBackendError, RendererBackendTrait};
+use crate::renderer::readback::{ReadbackCommand, ReadbackEvent, ReadbackSource};
+use crate::renderer::resource::{GpuHandle, GpuShader, GpuTexture, ResourceTable};
+use crate::renderer::target::{ContentRect, RenderTargetId};
+use crate::renderer::{BatchingStats, FrameStats, ProgramCacheStats, RendererCapabilities};
+use crate::view::{OutputFormat, ViewError, ViewHandle};
+use crossbeam_channel::Sender;
 use dawn_assets::factory::FactoryBinding;
+use dawn_assets::ir::shader::IRShader;
+use dawn_assets::ir::texture::IRTextureType;
+use glam::Vec3;
 use log::{debug, error, info, warn};
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+// What the frames are presented to
+enum GLSurface {
+    View(ViewHandle),
+    #[cfg(feature = "headless")]
+    // Only kept alive while the renderer is
+    Headless {
+        _context: headless::HeadlessContext,
+    },
+}
 
 pub struct GLRenderer<E: PassEventTrait> {
     _marker: std::marker::PhantomData<E>,
 
-    view_handle: ViewHandle,
-    _debugger: Debugger,
+    surface: GLSurface,
+    // Not created if the driver has no debug output
+    _debugger: Option<Debugger>,
+    capabilities: RendererCapabilities,
 
     // Factories for texture and shader assets
     texture_factory: Option<TextureAssetFactory>,
@@ -31,8 +71,36 @@ pub struct GLRenderer<E: PassEventTrait> {
     mesh_factory: Option<MeshAssetFactory>,
     material_factory: Option<MaterialAssetFactory>,
     font_factory: Option<FontAssetFactory>,
+
+    // GL objects of the texture and shader assets, addressed by their handles
+    textures: ResourceTable<GpuTexture, Texture>,
+    shaders: ResourceTable<GpuShader, ShaderProgram>,
+    // Binaries of the linked programs stored on the disk
+    program_cache: Option<ProgramCache>,
+    // Texture arrays the 2D textures of the same layout are packed into
+    texture_arrays: Option<TextureArrayPool>,
+
+    // Off-screen render targets. Recreated on view resize
+    render_targets: HashMap<RenderTargetId, RenderTarget>,
+    view_size: (usize, usize),
+
+    // Letterboxing. The content is rendered to the rect, the rest is covered by the bars
+    aspect_ratio: Option<f32>,
+    bars_color: [f32; 3],
+    content_rect: ContentRect,
+
+    // Logs of the shaders whose last reload failed. The banner is shown until it's empty
+    shader_errors: HashMap<GpuHandle<GpuShader>, String>,
+    shader_error_banner: Option<[f32; 3]>,
+
+    // Reads of the targets, textures and buffers back to the ECS
+    readbacks: Readbacks,
+
+    // Sun of the frame being rendered
+    sun_light: Option<RenderableSunLight>,
 }
 
+#[derive(Default)]
 pub struct GLRendererConfig {
     // pub fps: usize,
     // pub vsync: bool,
@@ -41,11 +109,39 @@ pub struct GLRendererConfig {
     pub mesh_factory_binding: Option<FactoryBinding>,
     pub material_factory_binding: Option<FactoryBinding>,
     pub font_factory_binding: Option<FactoryBinding>,
+    /// Fixed aspect ratio (width / height) of the content.
+    /// If set, the content is rendered to the largest centered rectangle of that ratio,
+    /// and the rest of the window is covered by the bars (letterbox or pillarbox).
+    /// The render targets follow the size of the content instead of the window.
+    pub aspect_ratio: Option<f32>,
+    /// Color of the bars around the content, RGB.
+    pub bars_color: [f32; 3],
+    /// Number of layers in each texture array the 2D textures are packed into.
+    /// The textures of the same size, format and sampling share the arrays,
+    /// so they can be addressed by the layer index without rebinding.
+    /// 0 disables packing, each texture gets its own GL object.
+    pub texture_array_layers: u32,
+    /// Color of the strip drawn over the top edge of the content while any
+    /// of the reloaded shaders fails to compile, RGB. `None` disables it.
+    /// The compiler log itself is reported with the `ShaderReloadEvent`.
+    pub shader_error_banner: Option<[f32; 3]>,
+    /// Directory the binaries of the linked shader programs are cached in,
+    /// usually the user cache directory of the application. The programs are
+    /// loaded from there instead of being compiled on the next start.
+    /// `None` disables the cache.
+    pub program_cache_dir: Option<PathBuf>,
+    /// Requested format of the default framebuffer. If the system does not provide it,
+    /// the view falls back to the lower precision ones (see `OutputFormat::fallbacks`),
+    /// the obtained format is reported in `RendererCapabilities::output_format`.
+    pub output_format: OutputFormat,
 }
 
 #[derive(Debug, Clone)]
 pub enum GLRendererError {
     ViewError(ViewError),
+    RenderTargetError(RenderTargetError),
+    /// The context of the headless backend cannot be created.
+    HeadlessError(String),
 }
 
 // OpenGL has a lot of platform-dependent code,
@@ -56,11 +152,17 @@ pub(crate) trait ViewHandleOpenGL {
     fn create_context(&mut self, fps: usize, vsync: bool) -> Result<(), ViewError>;
     fn get_proc_addr(&mut self, symbol: &str) -> Result<*const std::ffi::c_void, ViewError>;
     fn swap_buffers(&self) -> Result<(), ViewError>;
+    /// Format of the default framebuffer actually obtained from the system.
+    fn output_format(&self) -> OutputFormat;
 }
 
 impl Display for GLRendererError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "An error occurred in the graphics module")
+        match self {
+            GLRendererError::ViewError(e) => write!(f, "View error: {}", e),
+            GLRendererError::RenderTargetError(e) => write!(f, "Render target error: {}", e),
+            GLRendererError::HeadlessError(e) => write!(f, "Headless context error: {}", e),
+        }
     }
 }
 
@@ -113,10 +215,10 @@ fn stat_opengl_context() {
 // loaded asynchronously.
 // So OpenGL renderer handles events for these assets on each draw tick.
 impl<E: PassEventTrait> RendererBackendTrait<E> for GLRenderer<E> {
-    fn new(
-        cfg: RendererBackendConfig,
-        mut view_handle: ViewHandle,
-    ) -> Result<Self, RendererBackendError>
+    type Config = GLRendererConfig;
+    type Error = GLRendererError;
+
+    fn new(cfg: GLRendererConfig, mut view_handle: ViewHandle) -> Result<Self, GLRendererError>
     where
         Self: Sized,
     {
@@ -129,8 +231,106 @@ impl<E: PassEventTrait> RendererBackendTrait<E> for GLRenderer<E> {
                 .expect("Failed to load OpenGL function")
         });
 
+        let output_format = view_handle.output_format();
+        Ok(Self::with_surface(
+            cfg,
+            GLSurface::View(view_handle),
+            output_format,
+        ))
+    }
+
+    #[inline(always)]
+    fn before_frame(&mut self) -> Result<(), RendererBackendError> {
+        // Process events asset factories
+        if let Some(factory) = &mut self.texture_factory {
+            factory.process_events::<E>(
+                &mut self.textures,
+                self.texture_arrays.as_mut(),
+                &self.capabilities,
+            );
+        }
+        if let Some(factory) = &mut self.shader_factory {
+            factory.process_events(&mut self.shaders, self.program_cache.as_mut());
+        }
+        if let Some(factory) = &mut self.mesh_factory {
+            factory.process_events();
+        }
+        if let Some(factory) = &mut self.material_factory {
+            factory.process_events();
+        }
+        if let Some(factory) = &mut self.font_factory {
+            factory.process_events();
+        }
+
+        // User will handle clearing the screen in the render passes.
+
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn after_frame(&mut self) -> Result<(), RendererBackendError> {
+        // Passes may render outside the content (e.g. clear the whole screen),
+        // so the bars are drawn over the finished frame
+        self.draw_bars();
+        self.draw_shader_banner();
+
+        match &self.surface {
+            GLSurface::View(view_handle) => view_handle
+                .swap_buffers()
+                .map_err(GLRendererError::ViewError)?,
+            // Nothing to present, the frame is read back
+            #[cfg(feature = "headless")]
+            GLSurface::Headless { .. } => {}
+        }
+
+        Ok(())
+    }
+
+    fn resize(&mut self, width: usize, height: usize) -> Result<ContentRect, RendererBackendError> {
+        debug!("Resizing view to {}x{}", width, height);
+        self.view_size = (width, height);
+        self.content_rect = ContentRect::fit(width, height, self.aspect_ratio);
+        if self.content_rect.width != width || self.content_rect.height != height {
+            debug!("Content rect: {:?}", self.content_rect);
+        }
+        self.set_screen_viewport();
+
+        // Recreate the render targets according to their policies
+        let content = self.content_rect;
+        for target in self.render_targets.values_mut() {
+            target
+                .on_view_resize(content.width, content.height)
+                .map_err(GLRendererError::RenderTargetError)?;
+        }
+
+        Ok(content)
+    }
+}
+
+impl<E: PassEventTrait> GLRenderer<E> {
+    // Sets up the renderer once the context of the surface is current
+    // and the functions are loaded
+    fn with_surface(
+        cfg: GLRendererConfig,
+        surface: GLSurface,
+        output_format: OutputFormat,
+    ) -> Self {
         // Stat the OpenGL context
         stat_opengl_context();
+        let mut capabilities = unsafe { probe::get_capabilities() };
+        capabilities.output_format = output_format;
+        if capabilities.output_format != cfg.output_format {
+            warn!(
+                "Output format {} is not available, using {}",
+                cfg.output_format, capabilities.output_format
+            );
+        }
+        info!("OpenGL capabilities");
+        for line in capabilities.to_string().lines() {
+            info!("  {}", line);
+        }
+        debug!("OpenGL extensions: {:?}", capabilities.extensions);
+        crate::renderer::capabilities::publish(&capabilities);
 
         // Setup factories for texture and shader assets
         // These factories are used to load and manage texture and shader assets.
@@ -171,60 +371,372 @@ impl<E: PassEventTrait> RendererBackendTrait<E> for GLRenderer<E> {
         };
 
         // Setup the debug output for OpenGL.
-        let debugger = Debugger::new(|source, rtype, severity, message| match rtype {
-            MessageType::Error => {
-                error!("OpenGL: {}: {}: {}", source, severity, message);
-            }
-            MessageType::DeprecatedBehavior | MessageType::UndefinedBehavior => {
-                warn!("OpenGL: {}: {}: {}", source, severity, message);
-            }
-            _ => {
-                info!("OpenGL: {}: {}: {}", source, severity, message);
-            }
+        let debugger = capabilities.debug_output.then(|| {
+            Debugger::new(|source, rtype, severity, message| match rtype {
+                MessageType::Error => {
+                    error!("OpenGL: {}: {}: {}", source, severity, message);
+                }
+                MessageType::DeprecatedBehavior | MessageType::UndefinedBehavior => {
+                    warn!("OpenGL: {}: {}: {}", source, severity, message);
+                }
+                _ => {
+                    info!("OpenGL: {}: {}: {}", source, severity, message);
+                }
+            })
         });
+        if debugger.is_none() {
+            warn!("OpenGL debug output is not supported, the driver errors are not reported");
+        }
 
-        Ok(GLRenderer::<E> {
+        // The arrays cannot have more layers than the device allows
+        let texture_array_layers = cfg
+            .texture_array_layers
+            .min(capabilities.max_array_texture_layers);
+
+        GLRenderer::<E> {
             _marker: Default::default(),
             _debugger: debugger,
-            view_handle,
+            surface,
+            capabilities,
             texture_factory,
             shader_factory,
             mesh_factory,
             material_factory,
             font_factory,
-        })
+            textures: ResourceTable::default(),
+            shaders: ResourceTable::default(),
+            program_cache: cfg.program_cache_dir.and_then(ProgramCache::new),
+            texture_arrays: (texture_array_layers > 0)
+                .then(|| TextureArrayPool::new(texture_array_layers)),
+            render_targets: HashMap::new(),
+            view_size: (0, 0),
+            aspect_ratio: cfg.aspect_ratio,
+            bars_color: cfg.bars_color,
+            content_rect: ContentRect::default(),
+            shader_errors: HashMap::new(),
+            shader_error_banner: cfg.shader_error_banner,
+            readbacks: Readbacks::new(),
+            sun_light: None,
+        }
+    }
+
+    /// Creates the backend rendering without the window, e.g. for the tests
+    /// and the golden-image comparisons in the CI (see `golden` module).
+    /// The frames are rendered to the off-screen surface of the given size,
+    /// read them back with `read_back_framebuffer`. The context is current
+    /// on the calling thread, so the backend must be used there.
+    /// No assets are loaded, see `new_headless_with_config` to bind the factories.
+    ///
+    /// ```no_run
+    /// use dawn_graphics::renderer::RendererBackend;
+    /// # #[derive(Clone)]
+    /// # enum Event {}
+    ///
+    /// let backend = RendererBackend::<Event>::new_headless(64, 64).unwrap();
+    /// // ... execute the render passes ...
+    /// let pixels = backend.read_back_framebuffer();
+    /// assert_eq!(pixels.len(), 64 * 64 * 4);
+    /// ```
+    #[cfg(feature = "headless")]
+    pub fn new_headless(width: usize, height: usize) -> Result<Self, GLRendererError> {
+        Self::new_headless_with_config(GLRendererConfig::default(), width, height)
+    }
+
+    /// Same as `new_headless`, with the factories and the options of the config.
+    #[cfg(feature = "headless")]
+    pub fn new_headless_with_config(
+        cfg: GLRendererConfig,
+        width: usize,
+        height: usize,
+    ) -> Result<Self, GLRendererError> {
+        let context = headless::HeadlessContext::new(width, height)?;
+        bindings::load_with(|symbol| context.get_proc_addr(symbol));
+
+        let mut renderer = Self::with_surface(
+            cfg,
+            GLSurface::Headless { _context: context },
+            OutputFormat::Rgba8,
+        );
+        renderer.resize(width, height)?;
+        Ok(renderer)
+    }
+
+    /// Reads the default framebuffer (the window or the headless surface)
+    /// back as the RGBA8 pixels of the view size, top row first.
+    /// Call it after the passes, but before the frame is presented.
+    /// Stalls the pipeline, meant for the tests and the screenshots.
+    pub fn read_back_framebuffer(&self) -> Vec<u8> {
+        let (width, height) = self.view_size;
+        unsafe {
+            bindings::BindFramebuffer(bindings::READ_FRAMEBUFFER, 0);
+        }
+        target::read_rgba8(width, height)
+    }
+
+    /// Creates a new off-screen render target owned by the backend.
+    /// The target is sized according to its resize policy and will be
+    /// automatically recreated when the view is resized.
+    pub fn create_render_target(
+        &mut self,
+        descriptor: RenderTargetDescriptor,
+    ) -> Result<RenderTargetId, RenderTargetError> {
+        let content = self.content_rect;
+        let target = RenderTarget::new(descriptor, content.width, content.height)?;
+        let id = RenderTargetId::new();
+        self.render_targets.insert(id, target);
+        Ok(id)
+    }
+
+    /// Destroys the render target, freeing all of its attachments.
+    pub fn destroy_render_target(&mut self, id: RenderTargetId) -> Result<(), RenderTargetError> {
+        self.render_targets
+            .remove(&id)
+            .map(|_| ())
+            .ok_or(RenderTargetError::NotFound(id))
+    }
+
+    /// Sun of the frame being rendered, see `RenderableSunLight`.
+    /// The direction is normalized.
+    pub fn sun_light(&self) -> Option<RenderableSunLight> {
+        self.sun_light
     }
 
+    pub(crate) fn set_sun_light(&mut self, sun_light: Option<RenderableSunLight>) {
+        self.sun_light = sun_light.map(|sun| RenderableSunLight {
+            direction: sun.direction.normalize_or(Vec3::Y),
+            ..sun
+        });
+    }
+
+    /// Returns the render target by its ID.
+    pub fn render_target(&self, id: RenderTargetId) -> Result<&RenderTarget, RenderTargetError> {
+        self.render_targets
+            .get(&id)
+            .ok_or(RenderTargetError::NotFound(id))
+    }
+
+    /// Resolves the texture asset into the GL texture.
+    /// For the textures packed into the arrays, that is the array
+    /// (see `GpuTexture::layer`). Returns `None` if the texture was freed.
     #[inline(always)]
-    fn before_frame(&mut self) -> Result<(), RendererBackendError> {
-        // Process events asset factories
-        if let Some(factory) = &mut self.texture_factory {
-            factory.process_events::<E>();
+    pub fn texture(&self, texture: &GpuTexture) -> Option<&Texture> {
+        self.textures.get(texture.handle())
+    }
+
+    /// Resolves the shader asset into the GL shader program.
+    /// Returns `None` if the shader was freed.
+    #[inline(always)]
+    pub fn shader(&self, shader: &GpuShader) -> Option<&ShaderProgram> {
+        self.shaders.get(shader.handle())
+    }
+
+    /// Returns the limits and features of the device, queried when the backend was created.
+    /// Use them to select the code paths in the passes.
+    pub fn capabilities(&self) -> &RendererCapabilities {
+        &self.capabilities
+    }
+
+    /// Returns the current size of the view in pixels.
+    pub fn view_size(&self) -> (usize, usize) {
+        self.view_size
+    }
+
+    /// Returns the usage of the program cache.
+    /// All zeros if the cache is disabled.
+    pub fn program_cache_stats(&self) -> ProgramCacheStats {
+        self.program_cache
+            .as_ref()
+            .map(|cache| cache.stats())
+            .unwrap_or_default()
+    }
+
+    /// Returns the statistics of the static batching of the loaded meshes.
+    pub fn batching_stats(&self) -> BatchingStats {
+        mesh::batching_stats()
+    }
+
+    /// Returns the work submitted to the GPU since the last call:
+    /// the draws, the state changes and the uploads, including the ones
+    /// of the assets loaded in between the frames.
+    pub fn take_frame_stats(&mut self) -> FrameStats {
+        stats::take_frame_stats()
+    }
+
+    /// Returns the part of the view the content is rendered to.
+    /// Covers the whole view unless the aspect ratio is fixed.
+    pub fn content_rect(&self) -> ContentRect {
+        self.content_rect
+    }
+
+    /// Sets the viewport of the default framebuffer to the content rect.
+    /// The render targets restore it when their bindings are dropped.
+    pub fn set_screen_viewport(&self) {
+        let rect = self.content_rect;
+        // GL counts the rows from the bottom of the view
+        let y = self.view_size.1.saturating_sub(rect.y + rect.height);
+        stats::count_state_change();
+        unsafe {
+            bindings::Viewport(rect.x as _, y as _, rect.width as _, rect.height as _);
         }
-        if let Some(factory) = &mut self.shader_factory {
-            factory.process_events::<E>();
+    }
+
+    /// Recompiles the shader program from the new sources.
+    /// On success the program behind the handle is replaced, so the passes
+    /// keep using the same asset. On failure the previous program stays active
+    /// and the error banner is shown until the shader is reloaded successfully.
+    pub fn reload_shader(&mut self, shader: &GpuShader, ir: IRShader) -> Result<(), ShaderError> {
+        let handle = shader.handle();
+        // The reloaded sources are usually short-lived, so they are not cached
+        match ShaderProgram::from_ir(ir, false) {
+            Ok((program, _)) => {
+                // The previous program is deleted in the Drop implementation of ShaderProgram
+                self.shaders.replace(handle, program);
+                if self.shader_errors.remove(&handle).is_some() {
+                    info!("Shader {} reloaded, the error is resolved", handle);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to reload shader {}, keeping the previous one: {}",
+                    handle, e
+                );
+                self.shader_errors.insert(handle, e.to_string());
+                Err(e)
+            }
         }
-        if let Some(factory) = &mut self.mesh_factory {
-            factory.process_events::<E>();
+    }
+
+    /// Reads the buffer back to the ECS, e.g. the output of the compute shader.
+    /// Call it from the pass after the buffer was written (e.g. in `RenderPass::end`):
+    /// the copy is queued after the frame, and the bytes are delivered with the
+    /// `ReadbackEvent` of the tag once the GPU has finished it.
+    pub fn read_buffer(&mut self, tag: u64, buffer: GLuint, offset: usize, size: usize) {
+        self.readbacks.read_buffer(tag, buffer, offset, size);
+    }
+
+    pub(crate) fn readback_command(&mut self, command: ReadbackCommand) {
+        match command {
+            ReadbackCommand::Request(request) => self.readbacks.request(request),
+            ReadbackCommand::Cancel(tag) => self.readbacks.cancel(tag),
         }
-        if let Some(factory) = &mut self.material_factory {
-            factory.process_events::<E>();
+    }
+
+    /// Delivers the finished readbacks and queues the new ones after the frame.
+    pub(crate) fn process_readbacks(&mut self, frame: usize, events: &Sender<ReadbackEvent>) {
+        self.readbacks.poll(events);
+
+        for request in self.readbacks.due() {
+            let result = self
+                .resolve_readback(&request.source)
+                .and_then(|texture| self.readbacks.issue_texture(&request, texture, frame));
+            if let Err(reason) = result {
+                warn!("Readback {} failed: {}", request.tag, reason);
+                if request.persistent {
+                    self.readbacks.cancel(request.tag);
+                }
+                // The ECS side may be already gone, that's fine
+                let _ = events.send(ReadbackEvent::Failed {
+                    tag: request.tag,
+                    reason,
+                });
+            }
         }
-        if let Some(factory) = &mut self.font_factory {
-            factory.process_events::<E>();
+
+        for (tag, reason) in self.readbacks.issue_buffers(frame) {
+            warn!("Readback {} failed: {}", tag, reason);
+            let _ = events.send(ReadbackEvent::Failed { tag, reason });
         }
+    }
 
-        // User will handle clearing the screen in the render passes.
+    fn resolve_readback(&self, source: &ReadbackSource) -> Result<ReadbackTexture, String> {
+        match source {
+            ReadbackSource::RenderTarget { target, attachment } => {
+                let target = self.render_target(*target).map_err(|e| e.to_string())?;
+                let texture = target
+                    .color(*attachment)
+                    .ok_or_else(|| format!("Render target has no attachment {}", attachment))?;
+                let (width, height) = target.size();
+                Ok(ReadbackTexture {
+                    id: texture.id(),
+                    layer: None,
+                    width,
+                    height,
+                })
+            }
+            ReadbackSource::Texture(asset) => {
+                let gpu = asset.cast();
+                let IRTextureType::Texture2D { width, height } = gpu.texture_type() else {
+                    return Err(format!(
+                        "Texture {:?} cannot be read back, only the 2D ones can",
+                        gpu.texture_type()
+                    ));
+                };
+                let texture = self
+                    .texture(gpu)
+                    .ok_or_else(|| format!("Texture {} was freed", gpu.handle()))?;
+                Ok(ReadbackTexture {
+                    id: texture.id(),
+                    layer: gpu.layer(),
+                    width: width as usize,
+                    height: height as usize,
+                })
+            }
+        }
+    }
 
-        Ok(())
+    fn draw_shader_banner(&mut self) {
+        // The shaders freed since the failed reload do not count
+        let shaders = &self.shaders;
+        self.shader_errors
+            .retain(|handle, _| shaders.get(*handle).is_some());
+        let Some(color) = self.shader_error_banner else {
+            return;
+        };
+        if self.shader_errors.is_empty() {
+            return;
+        }
+
+        const BANNER_HEIGHT: usize = 6;
+        let banner = ContentRect {
+            height: BANNER_HEIGHT.min(self.content_rect.height),
+            ..self.content_rect
+        };
+        self.clear_rects(&[banner], color);
     }
 
-    #[inline(always)]
-    fn after_frame(&mut self) -> Result<(), RendererBackendError> {
-        self.view_handle
-            .swap_buffers()
-            .map_err(GLRendererError::ViewError)?;
+    fn draw_bars(&self) {
+        let (width, height) = self.view_size;
+        let bars = self.content_rect.bars(width, height);
+        if bars.is_empty() {
+            return;
+        }
+        self.clear_rects(&bars, self.bars_color);
+    }
 
-        Ok(())
+    // Fills the rects of the default framebuffer with the color
+    fn clear_rects(&self, rects: &[ContentRect], color: [f32; 3]) {
+        let height = self.view_size.1;
+        unsafe {
+            // Keep the clear color set by the passes
+            let mut clear_color = [0.0f32; 4];
+            bindings::GetFloatv(bindings::COLOR_CLEAR_VALUE, clear_color.as_mut_ptr());
+            let scissor = bindings::IsEnabled(bindings::SCISSOR_TEST) == bindings::TRUE;
+
+            bindings::Enable(bindings::SCISSOR_TEST);
+            let [r, g, b] = color;
+            bindings::ClearColor(r, g, b, 1.0);
+            for rect in rects {
+                let y = height.saturating_sub(rect.y + rect.height);
+                bindings::Scissor(rect.x as _, y as _, rect.width as _, rect.height as _);
+                bindings::Clear(bindings::COLOR_BUFFER_BIT);
+            }
+
+            let [r, g, b, a] = clear_color;
+            bindings::ClearColor(r, g, b, a);
+            if !scissor {
+                bindings::Disable(bindings::SCISSOR_TEST);
+            }
+        }
     }
 }
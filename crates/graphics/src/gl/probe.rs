@@ -1,4 +1,5 @@
 use crate::gl::bindings;
+use crate::renderer::RendererCapabilities;
 
 pub struct GlVersion {
     pub major: u32,
@@ -69,6 +70,20 @@ pub(crate) unsafe fn get_renderer() -> Option<String> {
     }
 }
 
+/// Full version string, including the driver version.
+pub(crate) unsafe fn get_version_string() -> Option<String> {
+    let version_ptr = bindings::GetString(bindings::VERSION);
+    if !version_ptr.is_null() {
+        Some(
+            std::ffi::CStr::from_ptr(version_ptr as *const i8)
+                .to_string_lossy()
+                .into_owned(),
+        )
+    } else {
+        None
+    }
+}
+
 pub(crate) unsafe fn get_shading_language_version() -> Option<ShadingLanguageVersion> {
     let version_ptr = bindings::GetString(bindings::SHADING_LANGUAGE_VERSION);
     if !version_ptr.is_null() {
@@ -118,18 +133,81 @@ pub(crate) unsafe fn get_binary_formats() -> Vec<u32> {
     formats.into_iter().map(|f| f as u32).collect()
 }
 
-pub(crate) unsafe fn get_extensions() -> Vec<String> {
-    let extensions_ptr = bindings::GetString(bindings::EXTENSIONS);
-    if extensions_ptr.is_null() {
+pub(crate) unsafe fn get_program_binary_formats() -> Vec<u32> {
+    let mut num_formats = 0;
+    bindings::GetIntegerv(bindings::NUM_PROGRAM_BINARY_FORMATS, &mut num_formats);
+    if num_formats <= 0 {
         return Vec::new();
     }
 
-    let extensions_str = std::ffi::CStr::from_ptr(extensions_ptr as *const i8)
-        .to_string_lossy()
-        .into_owned();
+    let mut formats = vec![0; num_formats as usize];
+    bindings::GetIntegerv(bindings::PROGRAM_BINARY_FORMATS, formats.as_mut_ptr());
+
+    formats.into_iter().map(|f| f as u32).collect()
+}
+
+// Not in the core profile before 4.6, same value in EXT_texture_filter_anisotropic
+const MAX_TEXTURE_MAX_ANISOTROPY: bindings::types::GLenum = 0x84FF;
+
+unsafe fn get_integer(name: bindings::types::GLenum) -> u32 {
+    let mut value = 0;
+    bindings::GetIntegerv(name, &mut value);
+    value.max(0) as u32
+}
 
-    extensions_str
-        .split_whitespace()
-        .map(|s| s.to_string())
+/// Extensions listed one by one: `GetString(EXTENSIONS)` is not available in the core profile.
+pub(crate) unsafe fn get_extensions_list() -> Vec<String> {
+    let count = get_integer(bindings::NUM_EXTENSIONS);
+    (0..count)
+        .filter_map(|i| {
+            let ptr = bindings::GetStringi(bindings::EXTENSIONS, i);
+            (!ptr.is_null()).then(|| {
+                std::ffi::CStr::from_ptr(ptr as *const i8)
+                    .to_string_lossy()
+                    .into_owned()
+            })
+        })
         .collect()
 }
+
+pub(crate) unsafe fn get_capabilities() -> RendererCapabilities {
+    let version = get_version();
+    let at_least = |major: u32, minor: u32| {
+        version
+            .as_ref()
+            .is_some_and(|v| (v.major, v.minor) >= (major, minor))
+    };
+
+    let extensions = get_extensions_list();
+    let has = |name: &str| extensions.iter().any(|extension| extension == name);
+
+    let max_anisotropy = (at_least(4, 6)
+        || has("GL_ARB_texture_filter_anisotropic")
+        || has("GL_EXT_texture_filter_anisotropic"))
+    .then(|| {
+        let mut value = 0.0;
+        bindings::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut value);
+        value
+    });
+
+    RendererCapabilities {
+        api: match &version {
+            Some(version) => format!("OpenGL {}", version),
+            None => "OpenGL".to_string(),
+        },
+        driver: get_version_string().unwrap_or_default(),
+        renderer: get_renderer().unwrap_or_default(),
+        vendor: get_vendor().unwrap_or_default(),
+        max_texture_size: get_integer(bindings::MAX_TEXTURE_SIZE),
+        max_array_texture_layers: get_integer(bindings::MAX_ARRAY_TEXTURE_LAYERS),
+        max_samples: get_integer(bindings::MAX_SAMPLES),
+        max_color_attachments: get_integer(bindings::MAX_COLOR_ATTACHMENTS),
+        max_anisotropy,
+        compute_shaders: at_least(4, 3) || has("GL_ARB_compute_shader"),
+        bindless_textures: has("GL_ARB_bindless_texture") || has("GL_NV_bindless_texture"),
+        debug_output: at_least(4, 3) || has("GL_KHR_debug"),
+        extensions,
+        // Known to the view, not to the context
+        output_format: Default::default(),
+    }
+}
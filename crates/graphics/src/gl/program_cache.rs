@@ -0,0 +1,134 @@
+use crate::gl::bindings::types::GLenum;
+use crate::gl::probe;
+use crate::gl::raii::shader_program::ShaderProgram;
+use crate::renderer::ProgramCacheStats;
+use dawn_assets::AssetChecksum;
+use log::{debug, info, warn};
+use std::path::PathBuf;
+
+const MAGIC: &[u8; 4] = b"DPRG";
+const VERSION: u32 = 1;
+
+/// Caches the binaries of the linked shader programs on the disk,
+/// so the programs are not compiled again on the next start.
+/// The binaries are keyed by the checksum of the shader asset and are valid only
+/// for the driver they were produced by, so the driver identity is stored
+/// with each binary and a binary of another driver is a miss.
+pub(crate) struct ProgramCache {
+    directory: PathBuf,
+    driver: String,
+    stats: ProgramCacheStats,
+}
+
+impl ProgramCache {
+    /// Returns `None` if the driver cannot provide the program binaries.
+    pub fn new(directory: PathBuf) -> Option<Self> {
+        if unsafe { probe::get_program_binary_formats() }.is_empty() {
+            warn!("Program binaries are not supported by the driver, the cache is disabled");
+            return None;
+        }
+        if let Err(e) = std::fs::create_dir_all(&directory) {
+            warn!(
+                "Failed to create program cache directory {:?}: {}",
+                directory, e
+            );
+            return None;
+        }
+
+        let driver = unsafe {
+            format!(
+                "{}|{}|{}",
+                probe::get_vendor().unwrap_or_default(),
+                probe::get_renderer().unwrap_or_default(),
+                probe::get_version_string().unwrap_or_default(),
+            )
+        };
+        info!("Program cache at {:?}", directory);
+        Some(ProgramCache {
+            directory,
+            driver,
+            stats: ProgramCacheStats::default(),
+        })
+    }
+
+    pub fn stats(&self) -> ProgramCacheStats {
+        self.stats
+    }
+
+    fn path(&self, checksum: &AssetChecksum) -> PathBuf {
+        self.directory
+            .join(format!("{}.bin", checksum.hex_string()))
+    }
+
+    /// Loads the program cached for the shader asset.
+    /// Returns `None` on a miss or if the driver rejected the binary.
+    pub fn load(&mut self, checksum: &AssetChecksum) -> Option<ShaderProgram> {
+        let Some((format, binary)) = self.read(checksum) else {
+            self.stats.misses += 1;
+            return None;
+        };
+
+        match ShaderProgram::from_binary(format, &binary) {
+            Ok(program) => {
+                debug!("Program cache hit for {}", checksum.hex_string());
+                self.stats.hits += 1;
+                Some(program)
+            }
+            Err(e) => {
+                // Overwritten by the program compiled from the sources
+                debug!("Cached program {} rejected: {}", checksum.hex_string(), e);
+                self.stats.rejected += 1;
+                None
+            }
+        }
+    }
+
+    fn read(&self, checksum: &AssetChecksum) -> Option<(GLenum, Vec<u8>)> {
+        let data = std::fs::read(self.path(checksum)).ok()?;
+
+        let (magic, data) = data.split_at_checked(MAGIC.len())?;
+        let (version, data) = split_u32(data)?;
+        if magic != MAGIC || version != VERSION {
+            return None;
+        }
+        let (length, data) = split_u32(data)?;
+        let (driver, data) = data.split_at_checked(length as usize)?;
+        if driver != self.driver.as_bytes() {
+            return None;
+        }
+        let (format, binary) = split_u32(data)?;
+        Some((format, binary.to_vec()))
+    }
+
+    /// Stores the binary of the program compiled from the sources.
+    /// Failures are not fatal: the program is just compiled again next time.
+    pub fn store(&self, checksum: &AssetChecksum, program: &ShaderProgram) {
+        let Some((format, binary)) = program.binary() else {
+            warn!("Driver returned no binary for program {}", program.id());
+            return;
+        };
+
+        let mut data = Vec::with_capacity(MAGIC.len() + self.driver.len() + binary.len() + 12);
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend_from_slice(&(self.driver.len() as u32).to_le_bytes());
+        data.extend_from_slice(self.driver.as_bytes());
+        data.extend_from_slice(&format.to_le_bytes());
+        data.extend_from_slice(&binary);
+
+        // Write to the temporary file first, so the concurrent readers
+        // never see the partially written binary
+        let path = self.path(checksum);
+        let temporary = path.with_extension("tmp");
+        let result =
+            std::fs::write(&temporary, &data).and_then(|_| std::fs::rename(&temporary, &path));
+        if let Err(e) = result {
+            warn!("Failed to write cached program {:?}: {}", path, e);
+        }
+    }
+}
+
+fn split_u32(data: &[u8]) -> Option<(u32, &[u8])> {
+    let (value, rest) = data.split_first_chunk::<4>()?;
+    Some((u32::from_le_bytes(*value), rest))
+}
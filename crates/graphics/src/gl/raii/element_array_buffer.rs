@@ -1,6 +1,8 @@
 use crate::gl::bindings;
 use crate::gl::bindings::types::GLuint;
+use crate::gl::stats;
 use log::debug;
+use std::marker::PhantomData;
 
 pub enum ElementArrayBufferUsage {
     StaticDraw,
@@ -17,7 +19,8 @@ impl ElementArrayBufferUsage {
     }
 }
 pub struct ElementArrayBufferBinding<'a> {
-    array_buffer: &'a mut ElementArrayBuffer,
+    // Keeps the buffer borrowed for as long as it is bound.
+    _buffer: PhantomData<&'a mut ElementArrayBuffer>,
 }
 
 impl<'a> ElementArrayBufferBinding<'a> {
@@ -27,14 +30,17 @@ impl<'a> ElementArrayBufferBinding<'a> {
         unsafe {
             bindings::BindBuffer(bindings::ELEMENT_ARRAY_BUFFER, array_buffer.id());
         }
-        Self { array_buffer }
+        Self {
+            _buffer: PhantomData,
+        }
     }
 
     pub fn feed<T>(&self, data: &[T], usage: ElementArrayBufferUsage) {
+        stats::count_upload(std::mem::size_of_val(data));
         unsafe {
             bindings::BufferData(
                 bindings::ELEMENT_ARRAY_BUFFER,
-                (data.len() * size_of::<T>()) as isize,
+                std::mem::size_of_val(data) as isize,
                 data.as_ptr() as *const _,
                 usage.gl_type(),
             );
@@ -0,0 +1,106 @@
+use crate::gl::bindings;
+use crate::gl::bindings::types::{GLenum, GLuint};
+use crate::gl::raii::renderbuffer::Renderbuffer;
+use crate::gl::raii::texture::Texture;
+use crate::gl::stats;
+use log::debug;
+
+#[derive(Debug)]
+pub struct Framebuffer {
+    id: GLuint,
+}
+
+pub struct FramebufferBinding<'a> {
+    #[allow(dead_code)]
+    framebuffer: &'a Framebuffer,
+}
+
+impl<'a> FramebufferBinding<'a> {
+    #[inline(always)]
+    fn new(framebuffer: &'a Framebuffer) -> Self {
+        stats::count_state_change();
+        unsafe {
+            bindings::BindFramebuffer(bindings::FRAMEBUFFER, framebuffer.id);
+        }
+        Self { framebuffer }
+    }
+
+    pub fn attach_texture_2d(&self, attachment: GLenum, texture: &Texture) {
+        unsafe {
+            bindings::FramebufferTexture2D(
+                bindings::FRAMEBUFFER,
+                attachment,
+                bindings::TEXTURE_2D,
+                texture.id(),
+                0,
+            );
+        }
+    }
+
+    pub fn attach_renderbuffer(&self, attachment: GLenum, renderbuffer: &Renderbuffer) {
+        unsafe {
+            bindings::FramebufferRenderbuffer(
+                bindings::FRAMEBUFFER,
+                attachment,
+                bindings::RENDERBUFFER,
+                renderbuffer.id(),
+            );
+        }
+    }
+
+    /// Sets the color attachments that fragment shader outputs are written to.
+    pub fn draw_buffers(&self, attachments: &[GLenum]) {
+        unsafe {
+            bindings::DrawBuffers(attachments.len() as _, attachments.as_ptr());
+        }
+    }
+
+    /// Returns `None` if the framebuffer is complete, otherwise the status code.
+    pub fn check_status(&self) -> Option<GLenum> {
+        let status = unsafe { bindings::CheckFramebufferStatus(bindings::FRAMEBUFFER) };
+        if status == bindings::FRAMEBUFFER_COMPLETE {
+            None
+        } else {
+            Some(status)
+        }
+    }
+}
+
+impl<'a> Drop for FramebufferBinding<'a> {
+    #[inline(always)]
+    fn drop(&mut self) {
+        unsafe {
+            bindings::BindFramebuffer(bindings::FRAMEBUFFER, 0);
+        }
+    }
+}
+
+impl Framebuffer {
+    pub fn new() -> Option<Self> {
+        let mut id: GLuint = 0;
+        unsafe {
+            bindings::GenFramebuffers(1, &mut id);
+            if id == 0 {
+                return None;
+            }
+        }
+
+        debug!("Allocated Framebuffer ID: {}", id);
+        Some(Framebuffer { id })
+    }
+
+    #[inline(always)]
+    #[must_use]
+    pub fn bind(&self) -> FramebufferBinding<'_> {
+        FramebufferBinding::new(self)
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        debug!("Dropping Framebuffer ID: {}", self.id);
+        unsafe {
+            bindings::DeleteFramebuffers(1, &self.id);
+        }
+    }
+}
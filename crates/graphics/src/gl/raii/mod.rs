@@ -1,5 +1,7 @@
 pub mod array_buffer;
 pub mod element_array_buffer;
+pub mod framebuffer;
+pub mod renderbuffer;
 pub mod shader;
 pub mod shader_program;
 pub mod texture;
@@ -0,0 +1,47 @@
+use crate::gl::bindings;
+use crate::gl::bindings::types::{GLenum, GLsizei, GLuint};
+use log::debug;
+
+#[derive(Debug)]
+pub struct Renderbuffer {
+    id: GLuint,
+}
+
+impl Renderbuffer {
+    /// Allocates a new renderbuffer with the given internal format and size.
+    pub fn new(internal_format: GLenum, width: usize, height: usize) -> Option<Self> {
+        let mut id: GLuint = 0;
+        unsafe {
+            bindings::GenRenderbuffers(1, &mut id);
+            if id == 0 {
+                return None;
+            }
+
+            bindings::BindRenderbuffer(bindings::RENDERBUFFER, id);
+            bindings::RenderbufferStorage(
+                bindings::RENDERBUFFER,
+                internal_format,
+                width as GLsizei,
+                height as GLsizei,
+            );
+            bindings::BindRenderbuffer(bindings::RENDERBUFFER, 0);
+        }
+
+        debug!("Allocated Renderbuffer ID: {} ({}x{})", id, width, height);
+        Some(Renderbuffer { id })
+    }
+
+    #[inline(always)]
+    pub(crate) fn id(&self) -> GLuint {
+        self.id
+    }
+}
+
+impl Drop for Renderbuffer {
+    fn drop(&mut self) {
+        debug!("Dropping Renderbuffer ID: {}", self.id);
+        unsafe {
+            bindings::DeleteRenderbuffers(1, &self.id);
+        }
+    }
+}
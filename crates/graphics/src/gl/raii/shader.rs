@@ -1,7 +1,7 @@
 use crate::gl::bindings;
 use crate::gl::bindings::types::GLuint;
 use dawn_assets::ir::shader::IRShaderSourceKind;
-use log::{debug, error};
+use log::debug;
 use thiserror::Error;
 
 // RAII wrapper for OpenGL shader
@@ -25,6 +25,8 @@ pub enum ShaderError {
     UTFError(#[from] std::string::FromUtf8Error),
     #[error("Unknown uniform location: {0}")]
     UnknownUniformLocation(String),
+    #[error("Program binary rejected by the driver")]
+    BinaryRejected,
 }
 
 impl Shader {
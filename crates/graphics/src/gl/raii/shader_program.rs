@@ -1,9 +1,9 @@
 use crate::gl::bindings;
-use crate::gl::bindings::types::GLuint;
+use crate::gl::bindings::types::{GLenum, GLuint};
+use crate::gl::stats;
 use crate::passes::events::PassEventTrait;
-use anyhow::Context;
 use dawn_assets::ir::shader::IRShader;
-use dawn_assets::{AssetCastable, AssetMemoryUsage};
+use dawn_assets::AssetMemoryUsage;
 use log::debug;
 
 // RAII wrapper for OpenGL shader program
@@ -14,8 +14,6 @@ pub struct ShaderProgram {
 
 pub type UniformLocation = GLuint;
 
-impl AssetCastable for ShaderProgram {}
-
 pub trait UniformTarget {
     fn set_uniform(location: UniformLocation, value: Self);
 }
@@ -61,13 +59,25 @@ use glam::{IVec2, IVec3, IVec4, UVec2, UVec3, UVec4, Vec2, Vec3, Vec4};
 }
 
 use crate::gl::raii::shader::{Shader, ShaderError};
-pub use targets::*;
 
 impl ShaderProgram {
-    pub(crate) fn from_ir<E: PassEventTrait>(
+    /// Compiles and links the program from the sources.
+    /// If `retrievable` is set, the driver is hinted that the binary
+    /// of the program will be requested with `binary`.
+    pub(crate) fn from_ir(
         ir: IRShader,
+        retrievable: bool,
     ) -> Result<(Self, AssetMemoryUsage), ShaderError> {
         let program = ShaderProgram::new()?;
+        if retrievable {
+            unsafe {
+                bindings::ProgramParameteri(
+                    program.id,
+                    bindings::PROGRAM_BINARY_RETRIEVABLE_HINT,
+                    bindings::TRUE as _,
+                );
+            }
+        }
 
         for (source_type, source) in &ir.sources {
             let shader = Shader::new(*source_type)?;
@@ -87,6 +97,58 @@ impl ShaderProgram {
         ))
     }
 
+    /// Loads the program from the binary returned by `binary`.
+    /// The driver may reject the binary (e.g. after the driver update),
+    /// then the program must be compiled from the sources.
+    pub(crate) fn from_binary(format: GLenum, binary: &[u8]) -> Result<Self, ShaderError> {
+        let program = ShaderProgram::new()?;
+        let status = unsafe {
+            bindings::ProgramBinary(
+                program.id,
+                format,
+                binary.as_ptr() as *const _,
+                binary.len() as _,
+            );
+            let mut status = 0;
+            bindings::GetProgramiv(program.id, bindings::LINK_STATUS, &mut status);
+            status
+        };
+        if status == 0 {
+            return Err(ShaderError::BinaryRejected);
+        }
+
+        debug!("Loaded shader program ID {} from binary", program.id);
+        Ok(program)
+    }
+
+    /// Returns the format and the binary of the linked program,
+    /// or `None` if the driver does not provide it.
+    pub(crate) fn binary(&self) -> Option<(GLenum, Vec<u8>)> {
+        unsafe {
+            let mut length = 0;
+            bindings::GetProgramiv(self.id, bindings::PROGRAM_BINARY_LENGTH, &mut length);
+            if length <= 0 {
+                return None;
+            }
+
+            let mut binary = vec![0u8; length as usize];
+            let mut written = 0;
+            let mut format = 0;
+            bindings::GetProgramBinary(
+                self.id,
+                length,
+                &mut written,
+                &mut format,
+                binary.as_mut_ptr() as *mut _,
+            );
+            if written <= 0 {
+                return None;
+            }
+            binary.truncate(written as usize);
+            Some((format, binary))
+        }
+    }
+
     fn new() -> Result<ShaderProgram, ShaderError> {
         debug!("Creating program");
         let id = unsafe { bindings::CreateProgram() };
@@ -133,6 +195,7 @@ impl ShaderProgram {
 
     #[inline(always)]
     pub fn bind(shader: &Self) {
+        stats::count_state_change();
         unsafe {
             bindings::UseProgram(shader.id);
         }
@@ -1,10 +1,11 @@
 use crate::gl::bindings;
 use crate::gl::bindings::types::{GLenum, GLint, GLsizei, GLuint};
+use crate::gl::stats;
 use crate::passes::events::PassEventTrait;
 use dawn_assets::ir::texture::{
     IRPixelFormat, IRTexture, IRTextureFilter, IRTextureType, IRTextureWrap,
 };
-use dawn_assets::{AssetCastable, AssetMemoryUsage};
+use dawn_assets::AssetMemoryUsage;
 use log::debug;
 use thiserror::Error;
 
@@ -30,13 +31,12 @@ pub enum TextureError {
     UnsupportedPixelType(IRPixelFormat),
 }
 
-impl AssetCastable for Texture {}
-
 fn tex_type_to_gl(tex_type: &IRTextureType) -> Result<GLuint, TextureError> {
     Ok(match tex_type {
         IRTextureType::Texture2D { .. } => bindings::TEXTURE_2D,
         IRTextureType::TextureCube { .. } => bindings::TEXTURE_CUBE_MAP,
-        _ => return Err(TextureError::UnsupportedTextureType(tex_type.clone())),
+        IRTextureType::Texture2DArray { .. } => bindings::TEXTURE_2D_ARRAY,
+        _ => return Err(TextureError::UnsupportedTextureType(*tex_type)),
     })
 }
 
@@ -57,7 +57,6 @@ fn filter_to_gl(filter: &IRTextureFilter) -> Result<GLenum, TextureError> {
         // TextureFilter::LinearMipmapNearest => bindings::LINEAR_MIPMAP_NEAREST,
         // TextureFilter::NearestMipmapLinear => bindings::NEAREST_MIPMAP_LINEAR,
         // TextureFilter::LinearMipmapLinear => bindings::LINEAR_MIPMAP_LINEAR,
-        _ => return Err(TextureError::UnsupportedTextureFilter(filter.clone())),
     })
 }
 
@@ -73,7 +72,7 @@ fn pf_to_format(format: &IRPixelFormat) -> Result<GLenum, TextureError> {
         IRPixelFormat::R16G16B16A16 => bindings::RGBA,
         IRPixelFormat::R32G32B32FLOAT => bindings::RGB,
         IRPixelFormat::R32G32B32A32FLOAT => bindings::RGBA,
-        _ => return Err(TextureError::UnsupportedPixelFormat(format.clone())),
+        _ => return Err(TextureError::UnsupportedPixelFormat(*format)),
     })
 }
 
@@ -89,7 +88,7 @@ fn pf_to_internal(format: &IRPixelFormat) -> Result<GLenum, TextureError> {
         IRPixelFormat::R16G16B16A16 => bindings::RGBA,
         IRPixelFormat::R32G32B32FLOAT => bindings::RGB,
         IRPixelFormat::R32G32B32A32FLOAT => bindings::RGBA,
-        _ => return Err(TextureError::UnsupportedPixelFormat(format.clone())),
+        _ => return Err(TextureError::UnsupportedPixelFormat(*format)),
     })
 }
 
@@ -104,7 +103,7 @@ fn pixel_format_to_gl_type(format: &IRPixelFormat) -> Result<GLenum, TextureErro
         | IRPixelFormat::R16G16B16
         | IRPixelFormat::R16G16B16A16 => bindings::UNSIGNED_SHORT,
         IRPixelFormat::R32G32B32FLOAT | IRPixelFormat::R32G32B32A32FLOAT => bindings::FLOAT,
-        _ => return Err(TextureError::UnsupportedPixelType(format.clone())),
+        _ => return Err(TextureError::UnsupportedPixelType(*format)),
     })
 }
 
@@ -112,7 +111,7 @@ impl Texture {
     pub fn from_ir<E: PassEventTrait>(
         ir: IRTexture,
     ) -> Result<(Self, AssetMemoryUsage), TextureError> {
-        let texture = Self::new(ir.texture_type.clone())?;
+        let texture = Self::new(ir.texture_type)?;
 
         Texture::bind(texture.texture_type, &texture, 0);
         texture.set_wrap_s(ir.wrap_s.clone())?;
@@ -130,13 +129,25 @@ impl Texture {
                     width as usize,
                     height as usize,
                     false,
-                    ir.pixel_format.clone(),
+                    ir.pixel_format,
                     &ir.data,
                 )?;
             }
-            _ => Err(TextureError::UnsupportedTextureType(
-                ir.texture_type.clone(),
-            ))?,
+            // Layers are stored one after another
+            IRTextureType::Texture2DArray {
+                width,
+                height,
+                layers,
+            } => {
+                texture.texture_image_3d(
+                    width as usize,
+                    height as usize,
+                    layers as usize,
+                    ir.pixel_format,
+                    Some(&ir.data),
+                )?;
+            }
+            _ => Err(TextureError::UnsupportedTextureType(ir.texture_type))?,
         }
         Texture::unbind(texture.texture_type, 0);
         Ok((texture, AssetMemoryUsage::new(size_of::<Texture>(), 0)))
@@ -145,6 +156,7 @@ impl Texture {
     pub fn bind(texture_type: GLenum, texture: &Self, texture_index: usize) {
         assert!(texture_index < 32);
         assert_eq!(texture_type, texture.texture_type);
+        stats::count_texture_bind();
         unsafe {
             bindings::ActiveTexture(bindings::TEXTURE0 + texture_index as GLenum);
             bindings::BindTexture(texture_type, texture.id);
@@ -215,6 +227,7 @@ impl Texture {
             "Uploading texture ID: {} ({}x{}, format: {}, type: {}, level {})",
             self.id, width, height, format, data_type, level
         );
+        stats::count_upload(data.len());
         unsafe {
             bindings::TexImage2D(
                 self.texture_type,
@@ -232,8 +245,139 @@ impl Texture {
         Ok(())
     }
 
+    /// Specifies the whole image of the 3D texture or the 2D texture array.
+    /// The storage is left uninitialized if there is no data.
+    pub fn texture_image_3d(
+        &self,
+        width: usize,
+        height: usize,
+        depth: usize,
+        pixel_format: IRPixelFormat,
+        data: Option<&[u8]>,
+    ) -> Result<(), TextureError> {
+        let internal = pf_to_internal(&pixel_format)?;
+        let format = pf_to_format(&pixel_format)?;
+        let data_type = pixel_format_to_gl_type(&pixel_format)?;
+
+        debug!(
+            "Uploading texture ID: {} ({}x{}x{}, format: {}, type: {})",
+            self.id, width, height, depth, format, data_type
+        );
+        stats::count_upload(data.map_or(0, |data| data.len()));
+        unsafe {
+            bindings::TexImage3D(
+                self.texture_type,
+                0,
+                internal as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                depth as GLsizei,
+                0,
+                format,
+                data_type,
+                data.map_or(std::ptr::null(), |data| data.as_ptr() as *const _),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Replaces one layer of the 2D texture array.
+    pub fn texture_layer(
+        &self,
+        layer: usize,
+        width: usize,
+        height: usize,
+        pixel_format: IRPixelFormat,
+        data: &[u8],
+    ) -> Result<(), TextureError> {
+        let format = pf_to_format(&pixel_format)?;
+        let data_type = pixel_format_to_gl_type(&pixel_format)?;
+
+        debug!(
+            "Uploading layer {} of texture ID: {} ({}x{})",
+            layer, self.id, width, height
+        );
+        stats::count_upload(data.len());
+        unsafe {
+            bindings::TexSubImage3D(
+                self.texture_type,
+                0,
+                0,
+                0,
+                layer as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                1,
+                format,
+                data_type,
+                data.as_ptr() as *const _,
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Allocates an uninitialized 2D texture array. The layers are filled by `texture_layer`.
+    pub fn allocate_2d_array(
+        width: usize,
+        height: usize,
+        layers: usize,
+        pixel_format: IRPixelFormat,
+    ) -> Result<Self, TextureError> {
+        let texture = Self::new(IRTextureType::Texture2DArray {
+            width: width as u32,
+            height: height as u32,
+            layers: layers as u32,
+        })?;
+
+        Texture::bind(texture.texture_type, &texture, 0);
+        texture.texture_image_3d(width, height, layers, pixel_format, None)?;
+        Texture::unbind(texture.texture_type, 0);
+
+        Ok(texture)
+    }
+
+    /// Allocates an uninitialized 2D texture that can be used as a render target attachment.
+    pub fn allocate_2d(
+        width: usize,
+        height: usize,
+        pixel_format: IRPixelFormat,
+    ) -> Result<Self, TextureError> {
+        let texture = Self::new(IRTextureType::Texture2D {
+            width: width as u32,
+            height: height as u32,
+        })?;
+
+        let internal = pf_to_internal(&pixel_format)?;
+        let format = pf_to_format(&pixel_format)?;
+        let data_type = pixel_format_to_gl_type(&pixel_format)?;
+
+        Texture::bind(texture.texture_type, &texture, 0);
+        texture.set_min_filter(IRTextureFilter::Linear)?;
+        texture.set_mag_filter(IRTextureFilter::Linear)?;
+        texture.set_wrap_s(IRTextureWrap::ClampToEdge)?;
+        texture.set_wrap_t(IRTextureWrap::ClampToEdge)?;
+        unsafe {
+            bindings::TexImage2D(
+                texture.texture_type,
+                0,
+                internal as GLint,
+                width as GLsizei,
+                height as GLsizei,
+                0,
+                format,
+                data_type,
+                std::ptr::null(),
+            );
+        }
+        Texture::unbind(texture.texture_type, 0);
+
+        Ok(texture)
+    }
+
     #[inline(always)]
-    fn id(&self) -> GLuint {
+    pub(crate) fn id(&self) -> GLuint {
         self.id
     }
 
@@ -1,5 +1,6 @@
 use crate::gl::bindings;
 use crate::gl::bindings::types::{GLint, GLsizei, GLuint};
+use crate::gl::stats;
 use crate::passes::result::RenderResult;
 use dawn_assets::ir::mesh::{IRIndexType, IRLayout, IRLayoutSampleType, IRTopology};
 use log::debug;
@@ -20,6 +21,7 @@ pub struct VertexArrayBinding<'a> {
 impl<'a> VertexArrayBinding<'a> {
     #[inline(always)]
     fn new(vertex_array: &'a VertexArray) -> Self {
+        stats::count_state_change();
         unsafe {
             bindings::BindVertexArray(vertex_array.id());
         }
@@ -60,6 +62,7 @@ impl<'a> VertexArrayBinding<'a> {
                 base_vertex as GLint,
             );
         }
+        stats::count_draw(self.vertex_array.draw_mode, index_count, 1);
 
         RenderResult::ok(1, index_count / self.vertex_array.topology_size)
     }
@@ -73,6 +76,7 @@ impl<'a> VertexArrayBinding<'a> {
                 (index_offset * self.vertex_array.index_size) as *const _,
             );
         }
+        stats::count_draw(self.vertex_array.draw_mode, index_count, 1);
 
         RenderResult::ok(1, index_count / self.vertex_array.topology_size)
     }
@@ -131,6 +135,12 @@ impl VertexArray {
     pub(crate) fn id(&self) -> GLuint {
         self.id
     }
+
+    /// Draw mode and index type of the array.
+    #[inline(always)]
+    pub(crate) fn format(&self) -> (GLuint, GLuint) {
+        (self.draw_mode, self.index_type)
+    }
 }
 
 impl Drop for VertexArray {
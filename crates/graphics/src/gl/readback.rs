@@ -0,0 +1,403 @@
+use crate::gl::bindings;
+use crate::gl::bindings::types::{GLenum, GLsync, GLuint};
+use crate::renderer::readback::{
+    ReadbackData, ReadbackEvent, ReadbackFormat, ReadbackRect, ReadbackRequest,
+};
+use crossbeam_channel::Sender;
+use log::debug;
+use std::collections::HashMap;
+
+// Reads of the persistent request in flight. The next read goes into
+// the other buffer while the previous one is still being copied
+const MAX_IN_FLIGHT: usize = 2;
+// Free pack buffers kept for the next reads
+const MAX_FREE_BUFFERS: usize = 8;
+
+// Pixel pack buffer the data is copied into by the GPU
+struct PackBuffer {
+    id: GLuint,
+    capacity: usize,
+}
+
+impl PackBuffer {
+    fn new(capacity: usize) -> Option<Self> {
+        let mut id: GLuint = 0;
+        unsafe {
+            bindings::GenBuffers(1, &mut id);
+            if id == 0 {
+                return None;
+            }
+            bindings::BindBuffer(bindings::PIXEL_PACK_BUFFER, id);
+            bindings::BufferData(
+                bindings::PIXEL_PACK_BUFFER,
+                capacity as isize,
+                std::ptr::null(),
+                bindings::STREAM_READ,
+            );
+            bindings::BindBuffer(bindings::PIXEL_PACK_BUFFER, 0);
+        }
+
+        debug!("Allocated readback buffer ID: {} ({} bytes)", id, capacity);
+        Some(PackBuffer { id, capacity })
+    }
+}
+
+impl Drop for PackBuffer {
+    fn drop(&mut self) {
+        debug!("Dropping readback buffer ID: {}", self.id);
+        unsafe {
+            bindings::DeleteBuffers(1, &self.id);
+        }
+    }
+}
+
+// Texture resolved from the `ReadbackSource`
+pub(crate) struct ReadbackTexture {
+    pub id: GLuint,
+    pub layer: Option<u32>,
+    pub width: usize,
+    pub height: usize,
+}
+
+struct InFlight {
+    data: ReadbackData,
+    buffer: PackBuffer,
+    fence: GLsync,
+    // Size of the row to flip the rows, `None` for the buffers
+    row: Option<usize>,
+}
+
+struct BufferRead {
+    tag: u64,
+    buffer: GLuint,
+    offset: usize,
+    size: usize,
+}
+
+/// Asynchronous reads from the GPU: the data is copied into the pack
+/// buffers after the frame, and mapped once their fences are signaled.
+pub(crate) struct Readbacks {
+    framebuffer: GLuint,
+    persistent: HashMap<u64, ReadbackRequest>,
+    one_shot: Vec<ReadbackRequest>,
+    buffer_reads: Vec<BufferRead>,
+    in_flight: Vec<InFlight>,
+    free: Vec<PackBuffer>,
+}
+
+fn format_to_gl(format: ReadbackFormat) -> Option<(GLenum, GLenum)> {
+    Some(match format {
+        ReadbackFormat::Rgba8 => (bindings::RGBA, bindings::UNSIGNED_BYTE),
+        ReadbackFormat::RgbaF32 => (bindings::RGBA, bindings::FLOAT),
+        ReadbackFormat::RF32 => (bindings::RED, bindings::FLOAT),
+        ReadbackFormat::Raw => return None,
+    })
+}
+
+impl Readbacks {
+    pub fn new() -> Self {
+        let mut framebuffer = 0;
+        unsafe {
+            bindings::GenFramebuffers(1, &mut framebuffer);
+        }
+        Readbacks {
+            framebuffer,
+            persistent: HashMap::new(),
+            one_shot: vec![],
+            buffer_reads: vec![],
+            in_flight: vec![],
+            free: vec![],
+        }
+    }
+
+    pub fn request(&mut self, request: ReadbackRequest) {
+        if request.persistent {
+            self.persistent.insert(request.tag, request);
+        } else {
+            self.one_shot.push(request);
+        }
+    }
+
+    pub fn cancel(&mut self, tag: u64) {
+        self.persistent.remove(&tag);
+        let cancelled = self
+            .in_flight
+            .extract_if(.., |read| read.data.tag == tag)
+            .collect::<Vec<_>>();
+        for read in cancelled {
+            unsafe {
+                bindings::DeleteSync(read.fence);
+            }
+            self.release(read.buffer);
+        }
+    }
+
+    pub fn read_buffer(&mut self, tag: u64, buffer: GLuint, offset: usize, size: usize) {
+        self.buffer_reads.push(BufferRead {
+            tag,
+            buffer,
+            offset,
+            size,
+        });
+    }
+
+    /// Requests to be read after this frame: the one-shot ones, and the
+    /// persistent ones that are not waiting for the GPU already.
+    pub fn due(&mut self) -> Vec<ReadbackRequest> {
+        let mut due = std::mem::take(&mut self.one_shot);
+        for request in self.persistent.values() {
+            let in_flight = self
+                .in_flight
+                .iter()
+                .filter(|read| read.data.tag == request.tag)
+                .count();
+            if in_flight < MAX_IN_FLIGHT {
+                due.push(request.clone());
+            }
+        }
+        due
+    }
+
+    /// Delivers the reads the GPU has finished, in the order they were issued.
+    pub fn poll(&mut self, events: &Sender<ReadbackEvent>) {
+        let mut i = 0;
+        while i < self.in_flight.len() {
+            let status = unsafe {
+                bindings::ClientWaitSync(
+                    self.in_flight[i].fence,
+                    bindings::SYNC_FLUSH_COMMANDS_BIT,
+                    0,
+                )
+            };
+            let event = match status {
+                bindings::TIMEOUT_EXPIRED => {
+                    i += 1;
+                    continue;
+                }
+                bindings::ALREADY_SIGNALED | bindings::CONDITION_SATISFIED => {
+                    let mut read = self.in_flight.remove(i);
+                    unsafe {
+                        bindings::DeleteSync(read.fence);
+                    }
+                    let event = match Self::map(&read.buffer, read.data.data.len()) {
+                        Some(bytes) => {
+                            read.data.data = match read.row {
+                                // OpenGL stores the bottom row first
+                                Some(row) => {
+                                    bytes.chunks_exact(row).rev().flatten().copied().collect()
+                                }
+                                None => bytes,
+                            };
+                            ReadbackEvent::Ready(read.data)
+                        }
+                        None => ReadbackEvent::Failed {
+                            tag: read.data.tag,
+                            reason: "Failed to map the readback buffer".to_string(),
+                        },
+                    };
+                    self.release(read.buffer);
+                    event
+                }
+                _ => {
+                    let read = self.in_flight.remove(i);
+                    unsafe {
+                        bindings::DeleteSync(read.fence);
+                    }
+                    self.release(read.buffer);
+                    ReadbackEvent::Failed {
+                        tag: read.data.tag,
+                        reason: "Failed to wait for the readback fence".to_string(),
+                    }
+                }
+            };
+
+            // The ECS side may be already gone, that's fine
+            let _ = events.send(event);
+        }
+    }
+
+    /// Queues the copy of the texture into the pack buffer.
+    pub fn issue_texture(
+        &mut self,
+        request: &ReadbackRequest,
+        texture: ReadbackTexture,
+        frame: usize,
+    ) -> Result<(), String> {
+        let Some((format, data_type)) = format_to_gl(request.format) else {
+            return Err(format!(
+                "Format {:?} is not valid for the textures",
+                request.format
+            ));
+        };
+        let rect = request.rect.unwrap_or(ReadbackRect {
+            x: 0,
+            y: 0,
+            width: texture.width,
+            height: texture.height,
+        });
+        if rect.width == 0
+            || rect.height == 0
+            || rect.x + rect.width > texture.width
+            || rect.y + rect.height > texture.height
+        {
+            return Err(format!(
+                "Rect {:?} is outside the {}x{} source",
+                rect, texture.width, texture.height
+            ));
+        }
+
+        let row = rect.width * request.format.pixel_size();
+        let size = row * rect.height;
+        let buffer = self.acquire(size)?;
+        unsafe {
+            bindings::BindFramebuffer(bindings::READ_FRAMEBUFFER, self.framebuffer);
+            match texture.layer {
+                Some(layer) => bindings::FramebufferTextureLayer(
+                    bindings::READ_FRAMEBUFFER,
+                    bindings::COLOR_ATTACHMENT0,
+                    texture.id,
+                    0,
+                    layer as _,
+                ),
+                None => bindings::FramebufferTexture2D(
+                    bindings::READ_FRAMEBUFFER,
+                    bindings::COLOR_ATTACHMENT0,
+                    bindings::TEXTURE_2D,
+                    texture.id,
+                    0,
+                ),
+            }
+            bindings::ReadBuffer(bindings::COLOR_ATTACHMENT0);
+            let status = bindings::CheckFramebufferStatus(bindings::READ_FRAMEBUFFER);
+            if status != bindings::FRAMEBUFFER_COMPLETE {
+                bindings::BindFramebuffer(bindings::READ_FRAMEBUFFER, 0);
+                self.release(buffer);
+                return Err(format!("Source is not readable (status {:#x})", status));
+            }
+
+            // GL counts the rows from the bottom
+            let y = texture.height - rect.y - rect.height;
+            bindings::BindBuffer(bindings::PIXEL_PACK_BUFFER, buffer.id);
+            bindings::PixelStorei(bindings::PACK_ALIGNMENT, 1);
+            bindings::ReadPixels(
+                rect.x as _,
+                y as _,
+                rect.width as _,
+                rect.height as _,
+                format,
+                data_type,
+                std::ptr::null_mut(),
+            );
+            bindings::BindBuffer(bindings::PIXEL_PACK_BUFFER, 0);
+            bindings::BindFramebuffer(bindings::READ_FRAMEBUFFER, 0);
+        }
+
+        self.push(
+            ReadbackData {
+                tag: request.tag,
+                frame,
+                width: rect.width,
+                height: rect.height,
+                format: request.format,
+                data: vec![0; size],
+            },
+            buffer,
+            Some(row),
+        );
+        Ok(())
+    }
+
+    /// Queues the copies of the buffers requested by the passes.
+    /// Returns the failed ones.
+    pub fn issue_buffers(&mut self, frame: usize) -> Vec<(u64, String)> {
+        let mut failed = vec![];
+        for read in std::mem::take(&mut self.buffer_reads) {
+            let buffer = match self.acquire(read.size) {
+                Ok(buffer) => buffer,
+                Err(e) => {
+                    failed.push((read.tag, e));
+                    continue;
+                }
+            };
+            unsafe {
+                bindings::BindBuffer(bindings::COPY_READ_BUFFER, read.buffer);
+                bindings::BindBuffer(bindings::COPY_WRITE_BUFFER, buffer.id);
+                bindings::CopyBufferSubData(
+                    bindings::COPY_READ_BUFFER,
+                    bindings::COPY_WRITE_BUFFER,
+                    read.offset as _,
+                    0,
+                    read.size as _,
+                );
+                bindings::BindBuffer(bindings::COPY_READ_BUFFER, 0);
+                bindings::BindBuffer(bindings::COPY_WRITE_BUFFER, 0);
+            }
+            self.push(
+                ReadbackData {
+                    tag: read.tag,
+                    frame,
+                    width: read.size,
+                    height: 1,
+                    format: ReadbackFormat::Raw,
+                    data: vec![0; read.size],
+                },
+                buffer,
+                None,
+            );
+        }
+        failed
+    }
+
+    fn push(&mut self, data: ReadbackData, buffer: PackBuffer, row: Option<usize>) {
+        let fence = unsafe { bindings::FenceSync(bindings::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+        self.in_flight.push(InFlight {
+            data,
+            buffer,
+            fence,
+            row,
+        });
+    }
+
+    fn map(buffer: &PackBuffer, size: usize) -> Option<Vec<u8>> {
+        unsafe {
+            bindings::BindBuffer(bindings::COPY_READ_BUFFER, buffer.id);
+            let ptr = bindings::MapBufferRange(
+                bindings::COPY_READ_BUFFER,
+                0,
+                size as _,
+                bindings::MAP_READ_BIT,
+            );
+            let bytes = (!ptr.is_null())
+                .then(|| std::slice::from_raw_parts(ptr as *const u8, size).to_vec());
+            if bytes.is_some() {
+                bindings::UnmapBuffer(bindings::COPY_READ_BUFFER);
+            }
+            bindings::BindBuffer(bindings::COPY_READ_BUFFER, 0);
+            bytes
+        }
+    }
+
+    fn acquire(&mut self, size: usize) -> Result<PackBuffer, String> {
+        if let Some(i) = self.free.iter().position(|buffer| buffer.capacity >= size) {
+            return Ok(self.free.swap_remove(i));
+        }
+        PackBuffer::new(size).ok_or_else(|| "Failed to create the readback buffer".to_string())
+    }
+
+    fn release(&mut self, buffer: PackBuffer) {
+        if self.free.len() < MAX_FREE_BUFFERS {
+            self.free.push(buffer);
+        }
+    }
+}
+
+impl Drop for Readbacks {
+    fn drop(&mut self) {
+        unsafe {
+            for read in &self.in_flight {
+                bindings::DeleteSync(read.fence);
+            }
+            bindings::DeleteFramebuffers(1, &self.framebuffer);
+        }
+    }
+}
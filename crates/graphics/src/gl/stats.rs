@@ -0,0 +1,48 @@
+use crate::gl::bindings;
+use crate::gl::bindings::types::GLenum;
+use crate::renderer::FrameStats;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Work submitted since the last `take_frame_stats`, counted by the GL wrappers
+static DRAW_CALLS: AtomicUsize = AtomicUsize::new(0);
+static INSTANCES: AtomicUsize = AtomicUsize::new(0);
+static TRIANGLES: AtomicUsize = AtomicUsize::new(0);
+static STATE_CHANGES: AtomicUsize = AtomicUsize::new(0);
+static TEXTURE_BINDS: AtomicUsize = AtomicUsize::new(0);
+static UPLOADED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+#[inline(always)]
+pub(crate) fn count_draw(draw_mode: GLenum, index_count: usize, instances: usize) {
+    DRAW_CALLS.fetch_add(1, Ordering::Relaxed);
+    INSTANCES.fetch_add(instances, Ordering::Relaxed);
+    if draw_mode == bindings::TRIANGLES {
+        TRIANGLES.fetch_add(index_count / 3 * instances, Ordering::Relaxed);
+    }
+}
+
+#[inline(always)]
+pub(crate) fn count_state_change() {
+    STATE_CHANGES.fetch_add(1, Ordering::Relaxed);
+}
+
+#[inline(always)]
+pub(crate) fn count_texture_bind() {
+    TEXTURE_BINDS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[inline(always)]
+pub(crate) fn count_upload(bytes: usize) {
+    UPLOADED_BYTES.fetch_add(bytes, Ordering::Relaxed);
+}
+
+/// Returns the work counted since the last call and resets the counters.
+pub(crate) fn take_frame_stats() -> FrameStats {
+    FrameStats {
+        draw_calls: DRAW_CALLS.swap(0, Ordering::Relaxed),
+        instances: INSTANCES.swap(0, Ordering::Relaxed),
+        triangles: TRIANGLES.swap(0, Ordering::Relaxed),
+        state_changes: STATE_CHANGES.swap(0, Ordering::Relaxed),
+        texture_binds: TEXTURE_BINDS.swap(0, Ordering::Relaxed),
+        uploaded_bytes: UPLOADED_BYTES.swap(0, Ordering::Relaxed),
+    }
+}
@@ -0,0 +1,231 @@
+use crate::gl::bindings;
+use crate::gl::bindings::types::GLenum;
+use crate::gl::raii::framebuffer::{Framebuffer, FramebufferBinding};
+use crate::gl::raii::renderbuffer::Renderbuffer;
+use crate::gl::raii::texture::Texture;
+use crate::renderer::target::{RenderTargetId, ResizePolicy};
+use dawn_assets::ir::texture::IRPixelFormat;
+use log::debug;
+use std::ops::Deref;
+use thiserror::Error;
+
+/// Describes the layout of the render target.
+#[derive(Debug, Clone)]
+pub struct RenderTargetDescriptor {
+    /// How the target follows the size of the view.
+    pub policy: ResizePolicy,
+    /// Pixel formats of the color attachments.
+    /// Attachments are bound to `COLOR_ATTACHMENT0 + index`.
+    pub color_attachments: Vec<IRPixelFormat>,
+    /// Whether to allocate a combined depth-stencil attachment.
+    pub depth_stencil: bool,
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum RenderTargetError {
+    #[error("Failed to create framebuffer")]
+    FailedToCreateFramebuffer,
+    #[error("Failed to create renderbuffer")]
+    FailedToCreateRenderbuffer,
+    #[error("Failed to create attachment: {0}")]
+    FailedToCreateAttachment(String),
+    #[error("Framebuffer is incomplete (status {0:#x})")]
+    IncompleteFramebuffer(GLenum),
+    #[error("Render target {0} not found")]
+    NotFound(RenderTargetId),
+}
+
+/// Off-screen framebuffer with its attachments.
+/// The attachments are recreated by the backend when the view is resized,
+/// so do not hold references to them between frames.
+#[derive(Debug)]
+pub struct RenderTarget {
+    descriptor: RenderTargetDescriptor,
+    width: usize,
+    height: usize,
+    framebuffer: Framebuffer,
+    colors: Vec<Texture>,
+    depth_stencil: Option<Renderbuffer>,
+}
+
+impl RenderTarget {
+    pub(crate) fn new(
+        descriptor: RenderTargetDescriptor,
+        view_width: usize,
+        view_height: usize,
+    ) -> Result<Self, RenderTargetError> {
+        let (width, height) = descriptor.policy.resolve(view_width, view_height);
+        let framebuffer = Framebuffer::new().ok_or(RenderTargetError::FailedToCreateFramebuffer)?;
+
+        let mut target = RenderTarget {
+            descriptor,
+            width,
+            height,
+            framebuffer,
+            colors: vec![],
+            depth_stencil: None,
+        };
+        target.allocate()?;
+        Ok(target)
+    }
+
+    /// (Re)creates all the attachments using the current size.
+    fn allocate(&mut self) -> Result<(), RenderTargetError> {
+        debug!(
+            "Allocating render target {}x{} ({} color attachments, depth-stencil: {})",
+            self.width,
+            self.height,
+            self.descriptor.color_attachments.len(),
+            self.descriptor.depth_stencil
+        );
+
+        // Old attachments will be dropped here
+        self.colors.clear();
+        self.depth_stencil = None;
+
+        for format in &self.descriptor.color_attachments {
+            let texture = Texture::allocate_2d(self.width, self.height, *format)
+                .map_err(|e| RenderTargetError::FailedToCreateAttachment(e.to_string()))?;
+            self.colors.push(texture);
+        }
+        if self.descriptor.depth_stencil {
+            self.depth_stencil = Some(
+                Renderbuffer::new(bindings::DEPTH24_STENCIL8, self.width, self.height)
+                    .ok_or(RenderTargetError::FailedToCreateRenderbuffer)?,
+            );
+        }
+
+        let binding = self.framebuffer.bind();
+        let mut draw_buffers = Vec::with_capacity(self.colors.len());
+        for (i, texture) in self.colors.iter().enumerate() {
+            let attachment = bindings::COLOR_ATTACHMENT0 + i as GLenum;
+            binding.attach_texture_2d(attachment, texture);
+            draw_buffers.push(attachment);
+        }
+        if let Some(renderbuffer) = &self.depth_stencil {
+            binding.attach_renderbuffer(bindings::DEPTH_STENCIL_ATTACHMENT, renderbuffer);
+        }
+        binding.draw_buffers(&draw_buffers);
+
+        if let Some(status) = binding.check_status() {
+            return Err(RenderTargetError::IncompleteFramebuffer(status));
+        }
+
+        Ok(())
+    }
+
+    /// Applies the resize policy to the new view size.
+    /// Returns `true` if the attachments were recreated.
+    pub(crate) fn on_view_resize(
+        &mut self,
+        view_width: usize,
+        view_height: usize,
+    ) -> Result<bool, RenderTargetError> {
+        let (width, height) = self.descriptor.policy.resolve(view_width, view_height);
+        if width == self.width && height == self.height {
+            return Ok(false);
+        }
+
+        self.width = width;
+        self.height = height;
+        self.allocate()?;
+        Ok(true)
+    }
+
+    /// Binds the framebuffer as the current draw target.
+    /// The default framebuffer and the previous viewport (e.g. the content rect
+    /// of the letterboxed view) are restored when the binding is dropped.
+    #[inline(always)]
+    #[must_use]
+    pub fn bind(&self) -> RenderTargetBinding<'_> {
+        let mut viewport = [0; 4];
+        unsafe {
+            bindings::GetIntegerv(bindings::VIEWPORT, viewport.as_mut_ptr());
+            bindings::Viewport(0, 0, self.width as _, self.height as _);
+        }
+        RenderTargetBinding {
+            binding: self.framebuffer.bind(),
+            viewport,
+        }
+    }
+
+    /// Returns the color attachment with the given index.
+    #[inline(always)]
+    pub fn color(&self, index: usize) -> Option<&Texture> {
+        self.colors.get(index)
+    }
+
+    /// Returns the current size of the target in pixels.
+    #[inline(always)]
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    #[inline(always)]
+    pub fn policy(&self) -> ResizePolicy {
+        self.descriptor.policy
+    }
+
+    /// Reads the color attachment back as the RGBA8 pixels, top row first.
+    /// Stalls the pipeline, meant for the tests and the screenshots.
+    pub fn read_pixels(&self, index: usize) -> Option<Vec<u8>> {
+        if index >= self.colors.len() {
+            return None;
+        }
+
+        let _binding = self.bind();
+        unsafe {
+            bindings::ReadBuffer(bindings::COLOR_ATTACHMENT0 + index as GLenum);
+        }
+        Some(read_rgba8(self.width, self.height))
+    }
+}
+
+// Reads the bound read buffer as the RGBA8 pixels, top row first
+pub(crate) fn read_rgba8(width: usize, height: usize) -> Vec<u8> {
+    let mut pixels = vec![0u8; width * height * 4];
+    unsafe {
+        bindings::PixelStorei(bindings::PACK_ALIGNMENT, 1);
+        bindings::ReadPixels(
+            0,
+            0,
+            width as _,
+            height as _,
+            bindings::RGBA,
+            bindings::UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut _,
+        );
+    }
+
+    // OpenGL stores the bottom row first
+    let row = width * 4;
+    let mut flipped = Vec::with_capacity(pixels.len());
+    for y in (0..height).rev() {
+        flipped.extend_from_slice(&pixels[y * row..(y + 1) * row]);
+    }
+    flipped
+}
+
+/// Binding of the render target. Dereferences to the framebuffer binding.
+pub struct RenderTargetBinding<'a> {
+    binding: FramebufferBinding<'a>,
+    viewport: [i32; 4],
+}
+
+impl<'a> Deref for RenderTargetBinding<'a> {
+    type Target = FramebufferBinding<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.binding
+    }
+}
+
+impl Drop for RenderTargetBinding<'_> {
+    fn drop(&mut self) {
+        // The framebuffer itself is unbound by the inner binding
+        let [x, y, width, height] = self.viewport;
+        unsafe {
+            bindings::Viewport(x, y, width, height);
+        }
+    }
+}
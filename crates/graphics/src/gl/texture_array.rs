@@ -0,0 +1,153 @@
+use crate::gl::bindings;
+use crate::gl::raii::texture::{Texture, TextureError};
+use crate::renderer::resource::{GpuHandle, GpuTexture, ResourceTable};
+use dawn_assets::ir::texture::{
+    IRPixelFormat, IRTexture, IRTextureFilter, IRTextureType, IRTextureWrap,
+};
+use log::debug;
+
+/// Textures sharing an array must have the same size, format and sampling,
+/// since these are the properties of the whole array.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ArrayKey {
+    width: u32,
+    height: u32,
+    pixel_format: IRPixelFormat,
+    use_mipmaps: bool,
+    min_filter: IRTextureFilter,
+    mag_filter: IRTextureFilter,
+    wrap_s: IRTextureWrap,
+    wrap_t: IRTextureWrap,
+}
+
+struct ArrayPage {
+    key: ArrayKey,
+    handle: GpuHandle<GpuTexture>,
+    free: Vec<u32>,
+    used: u32,
+}
+
+/// Packs the 2D textures of the same layout into the GL texture arrays on load,
+/// so the draws using different textures do not need to rebind them.
+/// Each array has a fixed number of layers, a new one is allocated when
+/// all the arrays of the layout are full. The array is deleted with its last layer.
+pub(crate) struct TextureArrayPool {
+    layers: u32,
+    pages: Vec<ArrayPage>,
+}
+
+impl TextureArrayPool {
+    pub fn new(layers: u32) -> Self {
+        TextureArrayPool {
+            layers,
+            pages: vec![],
+        }
+    }
+
+    /// Uploads the texture into a free layer of the matching array.
+    /// Returns `None` if the texture cannot be pooled (e.g. it's not a 2D texture).
+    pub fn insert(
+        &mut self,
+        ir: &IRTexture,
+        textures: &mut ResourceTable<GpuTexture, Texture>,
+    ) -> Result<Option<(GpuHandle<GpuTexture>, u32)>, TextureError> {
+        let IRTextureType::Texture2D { width, height } = ir.texture_type else {
+            return Ok(None);
+        };
+        let key = ArrayKey {
+            width,
+            height,
+            pixel_format: ir.pixel_format,
+            use_mipmaps: ir.use_mipmaps,
+            min_filter: ir.min_filter.clone(),
+            mag_filter: ir.mag_filter.clone(),
+            wrap_s: ir.wrap_s.clone(),
+            wrap_t: ir.wrap_t.clone(),
+        };
+
+        let index = match self
+            .pages
+            .iter()
+            .position(|page| page.key == key && !page.free.is_empty())
+        {
+            Some(index) => index,
+            None => {
+                let page = self.allocate(key, textures)?;
+                self.pages.push(page);
+                self.pages.len() - 1
+            }
+        };
+
+        let page = &mut self.pages[index];
+        let layer = page.free.pop().unwrap();
+        page.used += 1;
+
+        let texture = textures.get(page.handle).unwrap();
+        Texture::bind(bindings::TEXTURE_2D_ARRAY, texture, 0);
+        texture.texture_layer(
+            layer as usize,
+            width as usize,
+            height as usize,
+            ir.pixel_format,
+            &ir.data,
+        )?;
+        if ir.use_mipmaps {
+            texture.generate_mipmap();
+        }
+        Texture::unbind(bindings::TEXTURE_2D_ARRAY, 0);
+
+        Ok(Some((page.handle, layer)))
+    }
+
+    fn allocate(
+        &self,
+        key: ArrayKey,
+        textures: &mut ResourceTable<GpuTexture, Texture>,
+    ) -> Result<ArrayPage, TextureError> {
+        debug!(
+            "Allocating texture array {}x{}x{} ({:?})",
+            key.width, key.height, self.layers, key.pixel_format
+        );
+        let texture = Texture::allocate_2d_array(
+            key.width as usize,
+            key.height as usize,
+            self.layers as usize,
+            key.pixel_format,
+        )?;
+
+        Texture::bind(bindings::TEXTURE_2D_ARRAY, &texture, 0);
+        texture.set_wrap_s(key.wrap_s.clone())?;
+        texture.set_wrap_t(key.wrap_t.clone())?;
+        texture.set_min_filter(key.min_filter.clone())?;
+        texture.set_mag_filter(key.mag_filter.clone())?;
+        Texture::unbind(bindings::TEXTURE_2D_ARRAY, 0);
+
+        Ok(ArrayPage {
+            key,
+            handle: textures.insert(texture),
+            // Popped from the end, so the layers are filled in order
+            free: (0..self.layers).rev().collect(),
+            used: 0,
+        })
+    }
+
+    /// Releases the layer of the array.
+    pub fn remove(
+        &mut self,
+        handle: GpuHandle<GpuTexture>,
+        layer: u32,
+        textures: &mut ResourceTable<GpuTexture, Texture>,
+    ) {
+        let Some(index) = self.pages.iter().position(|page| page.handle == handle) else {
+            return;
+        };
+        let page = &mut self.pages[index];
+        page.free.push(layer);
+        page.used -= 1;
+        if page.used == 0 {
+            // The texture is deleted in the Drop implementation of Texture
+            textures.remove(handle);
+            self.pages.swap_remove(index);
+        }
+    }
+}
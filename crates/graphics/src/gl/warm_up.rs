@@ -0,0 +1,88 @@
+use crate::gl::bindings;
+use crate::gl::mesh::TopologyBucket;
+use crate::gl::raii::shader_program::ShaderProgram;
+use crate::gl::target::{RenderTarget, RenderTargetDescriptor, RenderTargetError};
+use crate::gl::GLRenderer;
+use crate::passes::events::PassEventTrait;
+use crate::renderable::Renderable;
+use crate::renderer::target::ResizePolicy;
+use dawn_assets::ir::texture::IRPixelFormat;
+use log::{debug, info};
+
+impl<E: PassEventTrait> GLRenderer<E> {
+    /// Draws each loaded shader program with each vertex format (topology
+    /// and index type) of the meshes of the renderables into a 1x1 off-screen
+    /// target. The drivers usually finish compiling the program for the actual
+    /// vertex format and state only on the first draw, so this moves the hitch
+    /// to the loading screen. `progress` is called after each program with the
+    /// number of the programs done and the total.
+    /// Returns the number of the program and vertex format pairs drawn.
+    pub fn warm_up(
+        &mut self,
+        renderables: &[Renderable],
+        mut progress: impl FnMut(usize, usize),
+    ) -> Result<usize, RenderTargetError> {
+        // All the meshes share the vertex layout, so the buckets
+        // differ only by the topology and the index type
+        let mut buckets: Vec<&TopologyBucket> = vec![];
+        for renderable in renderables {
+            for bucket in &renderable.mesh.cast().buckets {
+                if bucket.draws().is_empty() {
+                    continue;
+                }
+                let format = bucket.vao.format();
+                if !buckets.iter().any(|b| b.vao.format() == format) {
+                    buckets.push(bucket);
+                }
+            }
+        }
+
+        let target = RenderTarget::new(
+            RenderTargetDescriptor {
+                policy: ResizePolicy::Fixed {
+                    width: 1,
+                    height: 1,
+                },
+                color_attachments: vec![IRPixelFormat::R8G8B8A8],
+                depth_stencil: true,
+            },
+            1,
+            1,
+        )?;
+
+        let total = self.shaders.len();
+        debug!(
+            "Warming up {} shader programs with {} vertex formats",
+            total,
+            buckets.len()
+        );
+
+        let binding = target.bind();
+        let mut permutations = 0;
+        for (done, program) in self.shaders.values().enumerate() {
+            ShaderProgram::bind(program);
+            for bucket in &buckets {
+                let vao = bucket.vao.bind();
+                let submesh = &bucket.draws()[0];
+                // A single primitive is enough, the pixels do not matter
+                let _ = vao.draw_elements_base_vertex(
+                    submesh.index_count.min(3),
+                    submesh.index_offset,
+                    submesh.vertex_offset,
+                );
+                permutations += 1;
+            }
+            progress(done + 1, total);
+        }
+        ShaderProgram::unbind();
+
+        unsafe {
+            // Make sure the driver has actually done the work
+            bindings::Finish();
+        }
+        drop(binding);
+
+        info!("Warmed up {} shader permutations", permutations);
+        Ok(permutations)
+    }
+}
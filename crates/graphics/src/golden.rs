@@ -0,0 +1,254 @@
+//! Golden-image regression testing of the render passes. The pass renders
+//! the canned scene into the render target, the test reads it back with
+//! `RenderTarget::read_pixels` and checks it against the stored reference:
+//!
+//! ```no_run
+//! use dawn_graphics::golden::{GoldenImage, GoldenSuite, Tolerance};
+//!
+//! let suite = GoldenSuite::new("tests/golden", "target/golden");
+//! # let (width, height, pixels) = (1, 1, vec![0; 4]);
+//! let image = GoldenImage::new(width, height, pixels);
+//! suite.check("bloom_default", &image, &Tolerance::default()).unwrap();
+//! ```
+//!
+//! The missing references are written instead of compared when
+//! `DAWN_UPDATE_GOLDEN` is set, the same way all of them are regenerated after
+//! the intended visual change. On the mismatch the rendered image and the diff
+//! (the mismatched pixels in red over the faded reference) are written to the
+//! output directory.
+//!
+//! On the CI without a display the passes are run by the backend created with
+//! `RendererBackend::new_headless` (the `headless` feature), the whole frame is
+//! read back with `read_back_framebuffer`.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Set to write the references instead of comparing with them.
+pub const UPDATE_ENV: &str = "DAWN_UPDATE_GOLDEN";
+
+/// RGBA8 image, top row first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoldenImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Debug, Error)]
+pub enum GoldenError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to decode PNG: {0}")]
+    Decode(#[from] png::DecodingError),
+    #[error("Failed to encode PNG: {0}")]
+    Encode(#[from] png::EncodingError),
+    #[error("Unsupported PNG color type {0:?}")]
+    UnsupportedColor(png::ColorType),
+    #[error("Reference {0} is missing, set {UPDATE_ENV} to create it")]
+    MissingReference(PathBuf),
+    #[error("Image is {0}x{1}, the reference is {2}x{3}")]
+    SizeMismatch(usize, usize, usize, usize),
+    #[error("{mismatched} pixels differ (max delta {max_delta:.3}), see {diff}")]
+    Mismatch {
+        mismatched: usize,
+        max_delta: f32,
+        diff: PathBuf,
+    },
+}
+
+impl GoldenImage {
+    pub fn new(width: usize, height: usize, pixels: Vec<u8>) -> Self {
+        assert_eq!(pixels.len(), width * height * 4);
+        GoldenImage {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn load_png(path: &Path) -> Result<Self, GoldenError> {
+        let mut decoder = png::Decoder::new(File::open(path)?);
+        decoder.set_transformations(
+            png::Transformations::normalize_to_color8() | png::Transformations::ALPHA,
+        );
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        buffer.truncate(info.buffer_size());
+
+        let pixels = match info.color_type {
+            png::ColorType::Rgba => buffer,
+            png::ColorType::GrayscaleAlpha => buffer
+                .chunks_exact(2)
+                .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            other => return Err(GoldenError::UnsupportedColor(other)),
+        };
+        Ok(GoldenImage::new(
+            info.width as usize,
+            info.height as usize,
+            pixels,
+        ))
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<(), GoldenError> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let writer = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)?;
+        Ok(())
+    }
+}
+
+/// How different the images may be to still match.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// Perceptual difference of the pixel considered a mismatch, from 0 to 1.
+    /// Absorbs the rounding and the dithering differences between the drivers.
+    pub threshold: f32,
+    /// Share of the pixels allowed to mismatch, from 0 to 1.
+    /// Absorbs the rasterization differences on the edges.
+    pub max_mismatched: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance {
+            threshold: 0.1,
+            max_mismatched: 0.001,
+        }
+    }
+}
+
+/// Result of the comparison of the same-sized images.
+pub struct GoldenDiff {
+    pub mismatched: usize,
+    /// Largest perceptual difference of the pixels, from 0 to 1.
+    pub max_delta: f32,
+    /// Mismatched pixels in red over the faded gray expected image.
+    pub diff: GoldenImage,
+}
+
+// Maximum of the YIQ distance between the RGB colors
+const MAX_YIQ_DELTA: f32 = 35215.0;
+
+// Blends the pixel over the white, so the transparent pixels compare by the visible color
+fn blend(pixel: &[u8]) -> [f32; 3] {
+    let alpha = pixel[3] as f32 / 255.0;
+    std::array::from_fn(|i| 255.0 + (pixel[i] as f32 - 255.0) * alpha)
+}
+
+// Perceptual difference of the colors in the YIQ space, weighted as the
+// eye is more sensitive to the brightness (Kotsarenko and Ramos, 2010)
+fn color_delta(a: &[u8], b: &[u8]) -> f32 {
+    let [r1, g1, b1] = blend(a);
+    let [r2, g2, b2] = blend(b);
+    let (r, g, b) = (r1 - r2, g1 - g2, b1 - b2);
+    let y = r * 0.298_895_3 + g * 0.586_622_5 + b * 0.114_482_2;
+    let i = r * 0.595_978 - g * 0.274_176_1 - b * 0.321_801_9;
+    let q = r * 0.211_470_2 - g * 0.522_617_1 + b * 0.311_146_9;
+    (0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q) / MAX_YIQ_DELTA
+}
+
+/// Compares the same-sized images pixel by pixel.
+pub fn compare(actual: &GoldenImage, expected: &GoldenImage, threshold: f32) -> GoldenDiff {
+    assert_eq!(
+        (actual.width, actual.height),
+        (expected.width, expected.height)
+    );
+
+    let mut mismatched = 0;
+    let mut max_delta = 0.0f32;
+    let mut diff = Vec::with_capacity(expected.pixels.len());
+    for (a, e) in actual
+        .pixels
+        .chunks_exact(4)
+        .zip(expected.pixels.chunks_exact(4))
+    {
+        let delta = color_delta(a, e);
+        max_delta = max_delta.max(delta);
+        if delta > threshold * threshold {
+            mismatched += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let [r, g, b] = blend(e);
+            let gray = (r * 0.299 + g * 0.587 + b * 0.114) as u8;
+            // Faded towards the white
+            let faded = 255 - (255 - gray) / 4;
+            diff.extend_from_slice(&[faded, faded, faded, 255]);
+        }
+    }
+
+    GoldenDiff {
+        mismatched,
+        max_delta: max_delta.sqrt(),
+        diff: GoldenImage::new(expected.width, expected.height, diff),
+    }
+}
+
+/// Directory of the reference images and where the failures are written to.
+pub struct GoldenSuite {
+    references: PathBuf,
+    output: PathBuf,
+    update: bool,
+}
+
+impl GoldenSuite {
+    /// The references are `<references>/<name>.png`, the failures are
+    /// written as `<output>/<name>.actual.png` and `<output>/<name>.diff.png`.
+    pub fn new(references: impl Into<PathBuf>, output: impl Into<PathBuf>) -> Self {
+        GoldenSuite {
+            references: references.into(),
+            output: output.into(),
+            update: std::env::var_os(UPDATE_ENV).is_some_and(|value| !value.is_empty()),
+        }
+    }
+
+    /// Checks the rendered image against the reference of the same name.
+    pub fn check(
+        &self,
+        name: &str,
+        image: &GoldenImage,
+        tolerance: &Tolerance,
+    ) -> Result<(), GoldenError> {
+        let reference = self.references.join(format!("{name}.png"));
+        if self.update {
+            return image.save_png(&reference);
+        }
+        if !reference.exists() {
+            return Err(GoldenError::MissingReference(reference));
+        }
+
+        let expected = GoldenImage::load_png(&reference)?;
+        if (image.width, image.height) != (expected.width, expected.height) {
+            image.save_png(&self.output.join(format!("{name}.actual.png")))?;
+            return Err(GoldenError::SizeMismatch(
+                image.width,
+                image.height,
+                expected.width,
+                expected.height,
+            ));
+        }
+
+        let result = compare(image, &expected, tolerance.threshold);
+        let allowed = (tolerance.max_mismatched * (image.width * image.height) as f32) as usize;
+        if result.mismatched > allowed {
+            let diff = self.output.join(format!("{name}.diff.png"));
+            image.save_png(&self.output.join(format!("{name}.actual.png")))?;
+            result.diff.save_png(&diff)?;
+            return Err(GoldenError::Mismatch {
+                mismatched: result.mismatched,
+                max_delta: result.max_delta,
+                diff,
+            });
+        }
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use crate::renderer::target::ContentRect;
 use evenio::event::GlobalEvent;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -136,9 +137,28 @@ pub enum InputEvent {
     KeyPress(KeyCode),
     KeyRelease(KeyCode),
     CharInput(char),
-    MouseMove { x: f32, y: f32 },
-    MouseScroll { delta_x: f32, delta_y: f32 },
+    MouseMove {
+        x: f32,
+        y: f32,
+    },
+    MouseScroll {
+        delta_x: f32,
+        delta_y: f32,
+    },
     MouseButtonPress(MouseButton),
     MouseButtonRelease(MouseButton),
-    Resize { width: usize, height: usize },
+    Resize {
+        width: usize,
+        height: usize,
+    },
+    /// Sent by the renderer after each resize with the part of the view
+    /// the content is rendered to (see `GLRendererConfig::aspect_ratio`).
+    /// Use `ContentRect::content_point` to map the mouse position into the content space.
+    ContentResize(ContentRect),
+    /// The window was minimized or fully covered by other windows.
+    /// The renderer does not render frames until the window is restored,
+    /// so the game logic may pause the music or reduce the tick rate.
+    WindowOccluded,
+    /// The window became visible again after being occluded.
+    WindowRestored,
 }
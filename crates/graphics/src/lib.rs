@@ -1,9 +1,25 @@
 #![feature(trait_alias)]
 
+pub mod accessibility;
+pub mod atmosphere;
+pub mod capture;
+pub mod exposure;
 #[cfg(feature = "gl")]
 pub mod gl;
+#[cfg(feature = "golden")]
+pub mod golden;
 pub mod input;
+pub mod output;
 pub mod passes;
+pub mod picking;
 pub mod renderable;
 pub mod renderer;
+pub mod ui;
 pub mod view;
+#[cfg(feature = "vulkan")]
+pub mod vulkan;
+
+/// Version and the enabled features of the crate.
+pub fn build_info() -> dawn_util::build_info::CrateBuildInfo {
+    dawn_util::crate_build_info!["gl", "golden", "vulkan"]
+}
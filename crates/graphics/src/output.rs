@@ -0,0 +1,144 @@
+use crate::view::{ViewConfig, ViewGeometry};
+use evenio::component::Component;
+use evenio::event::GlobalEvent;
+
+/// Requests to the view. Sent from the ECS and processed by the renderer thread
+/// between the frames.
+#[derive(GlobalEvent, Debug, Clone)]
+pub enum OutputEvent {
+    /// Switches between the windowed, borderless and exclusive fullscreen modes,
+    /// or moves the window to another monitor.
+    /// See `view::enumerate_monitors` for the available monitors and video modes.
+    SetGeometry(ViewGeometry),
+    /// Changes the title of the window.
+    SetTitle(String),
+    /// Changes the size of the window in the windowed mode, in pixels.
+    /// In the fullscreen modes, the size is applied when returning to the windowed one.
+    SetSize { width: usize, height: usize },
+    /// Limits the size the window can be resized to by the user.
+    /// `None` removes the limit.
+    SetSizeLimits {
+        min: Option<(usize, usize)>,
+        max: Option<(usize, usize)>,
+    },
+    /// Changes the mouse cursor over the window.
+    SetCursor(Cursor),
+}
+
+/// Standard cursor shapes of the system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CursorShape {
+    #[default]
+    Arrow,
+    /// Text input (I-beam)
+    Text,
+    /// Link or the clickable element
+    Hand,
+    Crosshair,
+    Wait,
+    /// Moving in any direction
+    Move,
+    ResizeHorizontal,
+    ResizeVertical,
+    /// Resizing along the top-left to bottom-right diagonal
+    ResizeDiagonal,
+    /// Resizing along the top-right to bottom-left diagonal
+    ResizeAntiDiagonal,
+    NotAllowed,
+}
+
+/// Custom cursor image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CursorImage {
+    pub width: usize,
+    pub height: usize,
+    /// Point of the image that is the position of the cursor, from the top-left corner
+    pub hotspot: (usize, usize),
+    /// Pixels, top row first, RGBA with 8 bits per channel, not premultiplied
+    pub rgba: Vec<u8>,
+}
+
+impl CursorImage {
+    /// The image is not empty, has all the pixels and the hotspot is inside it.
+    pub fn is_valid(&self) -> bool {
+        self.width > 0
+            && self.height > 0
+            && self.rgba.len() == self.width * self.height * 4
+            && self.hotspot.0 < self.width
+            && self.hotspot.1 < self.height
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cursor {
+    Shape(CursorShape),
+    Image(CursorImage),
+    /// No cursor is shown over the window, e.g. for the mouse look.
+    Hidden,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Cursor::Shape(CursorShape::Arrow)
+    }
+}
+
+/// Declarative state of the window, an alternative to sending the `OutputEvent`s.
+/// Insert it into any entity (only one per world is used) and change its fields:
+/// the renderer compares it with the state applied last time on every tick,
+/// and sends the `OutputEvent`s for the changed fields only.
+/// Being the plain data, it can be saved with the rest of the world and restored.
+///
+/// It is the requested state: resizing the window by the user is reported
+/// with the `InputEvent::Resize` and does not change the component.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct WindowState {
+    pub title: String,
+    /// Size of the window in the windowed mode, in pixels.
+    pub width: usize,
+    pub height: usize,
+    /// Windowed, borderless or exclusive fullscreen mode.
+    pub geometry: ViewGeometry,
+    pub cursor: Cursor,
+}
+
+impl WindowState {
+    /// Requests turning the `applied` state into this one.
+    /// `None` means nothing is known about the window, so all the fields are requested.
+    pub fn changes(&self, applied: Option<&WindowState>) -> Vec<OutputEvent> {
+        let mut events = Vec::new();
+        if applied.is_none_or(|applied| applied.title != self.title) {
+            events.push(OutputEvent::SetTitle(self.title.clone()));
+        }
+        // The size is applied before the geometry, so leaving the fullscreen
+        // in the same tick restores the new size
+        if applied
+            .is_none_or(|applied| (applied.width, applied.height) != (self.width, self.height))
+        {
+            events.push(OutputEvent::SetSize {
+                width: self.width,
+                height: self.height,
+            });
+        }
+        if applied.is_none_or(|applied| applied.geometry != self.geometry) {
+            events.push(OutputEvent::SetGeometry(self.geometry.clone()));
+        }
+        if applied.is_none_or(|applied| applied.cursor != self.cursor) {
+            events.push(OutputEvent::SetCursor(self.cursor.clone()));
+        }
+        events
+    }
+}
+
+/// The state the window is opened with.
+impl From<&ViewConfig> for WindowState {
+    fn from(config: &ViewConfig) -> Self {
+        WindowState {
+            title: config.title.clone(),
+            width: config.width,
+            height: config.height,
+            geometry: config.geometry.clone(),
+            cursor: Cursor::default(),
+        }
+    }
+}
@@ -1,6 +1,9 @@
 use crate::passes::events::{PassEventTarget, PassEventTrait};
 use crate::passes::result::RenderResult;
 use crate::passes::{ChainExecuteCtx, RenderPass};
+use crate::renderer::backend::RendererBackend;
+use crate::renderer::resource::{GpuHandle, GpuShader};
+use dawn_assets::ir::settings::IRSettings;
 use std::marker::PhantomData;
 
 // Compile-time Heterogeneous List (HList) for Render Passes
@@ -9,6 +12,12 @@ pub struct ChainNil<E: PassEventTrait> {
     _marker: PhantomData<E>,
 }
 
+impl<E: PassEventTrait> Default for ChainNil<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<E: PassEventTrait> ChainNil<E> {
     pub fn new() -> Self {
         ChainNil {
@@ -41,6 +50,18 @@ pub trait RenderChain<E: PassEventTrait> {
         RenderResult::default()
     }
 
+    /// Notify all the passes in the chain about the view resize.
+    #[inline(always)]
+    fn on_resize(&mut self, _: &mut RendererBackend<E>, _: usize, _: usize) {}
+
+    /// Notify all the passes in the chain about the reloaded shader.
+    #[inline(always)]
+    fn on_shader_reload(&mut self, _: &mut RendererBackend<E>, _: GpuHandle<GpuShader>) {}
+
+    /// Pass the render settings preset to all the passes in the chain.
+    #[inline(always)]
+    fn on_settings(&mut self, _: &mut RendererBackend<E>, _: &IRSettings) {}
+
     /// Get the length of the chain.
     #[inline(always)]
     fn length(&self) -> usize {
@@ -82,6 +103,24 @@ where
         result
     }
 
+    #[inline(always)]
+    fn on_resize(&mut self, backend: &mut RendererBackend<E>, width: usize, height: usize) {
+        self.head.on_resize(backend, width, height);
+        self.tail.on_resize(backend, width, height);
+    }
+
+    #[inline(always)]
+    fn on_shader_reload(&mut self, backend: &mut RendererBackend<E>, shader: GpuHandle<GpuShader>) {
+        self.head.on_shader_reload(backend, shader);
+        self.tail.on_shader_reload(backend, shader);
+    }
+
+    #[inline(always)]
+    fn on_settings(&mut self, backend: &mut RendererBackend<E>, settings: &IRSettings) {
+        self.head.on_settings(backend, settings);
+        self.tail.on_settings(backend, settings);
+    }
+
     #[inline(always)]
     fn length(&self) -> usize {
         // Count the head pass and add the count of the tail.
@@ -111,12 +150,14 @@ where
 ///
 /// # Example:
 /// ```
+/// use dawn_graphics::passes::chain::RenderChain;
 /// use dawn_graphics::passes::RenderPass;
 ///
 /// struct PassA;
 /// struct PassB;
 /// struct PassC;
 ///
+/// #[derive(Clone)]
 /// struct Event;
 ///
 /// impl RenderPass<Event> for PassA {
@@ -131,9 +172,12 @@ where
 ///
 /// use dawn_graphics::construct_chain;
 /// let chain = construct_chain!(PassA, PassB, PassC);
+/// assert_eq!(RenderChain::<Event>::length(&chain), 3);
 /// ```
 #[macro_export]
 macro_rules! construct_chain {
-    () => { ChainNil::new() };
-    ($head:expr $(, $tail:expr)* $(,)?) => { ChainCons::new($head, construct_chain!($($tail),*)) };
+    () => { $crate::passes::chain::ChainNil::new() };
+    ($head:expr $(, $tail:expr)* $(,)?) => {
+        $crate::passes::chain::ChainCons::new($head, $crate::construct_chain!($($tail),*))
+    };
 }
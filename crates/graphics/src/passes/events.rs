@@ -51,6 +51,11 @@ impl RenderPassTargetId {
     pub(crate) fn as_usize(&self) -> usize {
         self.0
     }
+
+    // The ID of the target recorded by the frame capture
+    pub(crate) fn from_usize(id: usize) -> Self {
+        RenderPassTargetId(id)
+    }
 }
 
 type EventDispatcher<E> = fn(*mut u8, E);
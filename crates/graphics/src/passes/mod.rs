@@ -1,7 +1,9 @@
 use crate::passes::events::{PassEventTarget, PassEventTrait};
 use crate::passes::result::RenderResult;
-use crate::renderable::Renderable;
+use crate::renderable::{RenderLayers, Renderable};
 use crate::renderer::backend::RendererBackend;
+use crate::renderer::resource::{GpuHandle, GpuShader};
+use dawn_assets::ir::settings::IRSettings;
 use std::time::Duration;
 
 pub mod chain;
@@ -31,6 +33,44 @@ pub trait RenderPass<E: PassEventTrait>: Send + Sync + 'static {
     /// Get the name of the render pass.
     fn name(&self) -> &str;
 
+    /// Layers of the renderables processed by the pass.
+    /// The renderables not sharing any layer with the mask are skipped.
+    /// The mask is queried every frame, so a pass rendering from several
+    /// cameras may return the mask of the current one.
+    #[inline(always)]
+    fn layers(&self) -> RenderLayers {
+        RenderLayers::ALL
+    }
+
+    /// Called after the view was resized and the render targets owned by
+    /// the backend were recreated according to their resize policies.
+    /// Passes that cache anything derived from the targets (sizes, attachments,
+    /// projection matrices) should refresh it here.
+    #[inline(always)]
+    fn on_resize(&mut self, _backend: &mut RendererBackend<E>, _width: usize, _height: usize) {
+        // The default implementation does nothing.
+    }
+
+    /// Called after the shader program behind the handle was replaced
+    /// by the `ShaderReloadRequest`. The uniform locations may differ
+    /// in the new program, so the passes caching them should query them again.
+    #[inline(always)]
+    fn on_shader_reload(
+        &mut self,
+        _backend: &mut RendererBackend<E>,
+        _shader: GpuHandle<GpuShader>,
+    ) {
+        // The default implementation does nothing.
+    }
+
+    /// Called with the preset sent by the `RenderSettingsRequest`.
+    /// Passes read the keys they understand (e.g. `bloom.intensity`)
+    /// and keep their current values for the missing ones.
+    #[inline(always)]
+    fn on_settings(&mut self, _backend: &mut RendererBackend<E>, _settings: &IRSettings) {
+        // The default implementation does nothing.
+    }
+
     /// Begin the render pass execution.
     /// This method is called before processing any renderables or meshes.
     #[inline(always)]
@@ -84,7 +124,11 @@ impl<'a, E: PassEventTrait> ChainExecuteCtx<'a, E> {
 
         let mut result = RenderResult::default();
         result += pass.begin(self.backend);
+        let layers = pass.layers();
         for renderable in self.renderables {
+            if !renderable.layers.intersects(layers) {
+                continue;
+            }
             result += pass.on_renderable(self.backend, renderable);
         }
         result += pass.end(self.backend);
@@ -2,6 +2,9 @@ use crate::passes::chain::RenderChain;
 use crate::passes::events::{PassEventTarget, PassEventTrait, RenderPassEvent};
 use crate::passes::result::RenderResult;
 use crate::passes::{ChainExecuteCtx, MAX_RENDER_PASSES};
+use crate::renderer::backend::RendererBackend;
+use crate::renderer::resource::{GpuHandle, GpuShader};
+use dawn_assets::ir::settings::IRSettings;
 use std::mem::MaybeUninit;
 
 const ROUTER_CAPACITY: usize = 64;
@@ -87,6 +90,30 @@ where
         self.chain.get_names()
     }
 
+    pub(crate) fn on_resize(
+        &mut self,
+        backend: &mut RendererBackend<E>,
+        width: usize,
+        height: usize,
+    ) {
+        // Notify all the passes about the new view size.
+        self.chain.on_resize(backend, width, height)
+    }
+
+    pub(crate) fn on_shader_reload(
+        &mut self,
+        backend: &mut RendererBackend<E>,
+        shader: GpuHandle<GpuShader>,
+    ) {
+        // Notify all the passes about the replaced program.
+        self.chain.on_shader_reload(backend, shader)
+    }
+
+    pub(crate) fn on_settings(&mut self, backend: &mut RendererBackend<E>, settings: &IRSettings) {
+        // Let all the passes pick the values they understand.
+        self.chain.on_settings(backend, settings)
+    }
+
     #[inline(always)]
     pub(crate) fn execute(&mut self, ctx: &mut ChainExecuteCtx<E>) -> RenderResult {
         // Execute the chain of render passes.
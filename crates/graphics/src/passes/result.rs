@@ -62,7 +62,6 @@ impl ops::Add for RenderResult {
 impl ops::AddAssign for RenderResult {
     #[inline(always)]
     fn add_assign(&mut self, other: Self) {
-        let other = other.clone();
         *self = *self + other;
     }
 }
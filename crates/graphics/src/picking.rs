@@ -0,0 +1,404 @@
+use crate::input::InputEvent;
+use crate::renderable::{ObjectMesh, ObjectPosition, ObjectRotation, ObjectScale};
+use crate::renderer::target::ContentRect;
+use evenio::component::Component;
+use evenio::entity::EntityId;
+use evenio::event::{GlobalEvent, Receiver, Sender};
+use evenio::fetch::{Fetcher, Single};
+use evenio::query::Query;
+use evenio::world::World;
+use glam::{Mat4, Quat, Vec2, Vec3};
+
+/// Ray in the world space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    /// Normalized direction of the ray.
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Ray {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    /// Creates the ray going from the camera through the point of the screen.
+    /// The point is in the normalized device coordinates: `(-1, -1)` is the bottom-left
+    /// corner, `(1, 1)` is the top-right one. The projection follows the OpenGL convention
+    /// (depth from -1 to 1), the infinite far plane is fine.
+    pub fn from_ndc(ndc: Vec2, view: Mat4, projection: Mat4) -> Self {
+        let inverse = (projection * view).inverse();
+        let near = inverse.project_point3(ndc.extend(-1.0));
+        let middle = inverse.project_point3(ndc.extend(0.0));
+        Ray::new(near, middle - near)
+    }
+
+    #[inline(always)]
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    /// Returns the distance along the ray to the box, or `None` if the ray misses it.
+    /// Zero if the origin is inside the box.
+    pub fn intersect_aabb(&self, min: Vec3, max: Vec3) -> Option<f32> {
+        // Slab method. Division by zero gives infinities, which work out
+        let inverse = self.direction.recip();
+        let t1 = (min - self.origin) * inverse;
+        let t2 = (max - self.origin) * inverse;
+        let near = t1.min(t2).max_element();
+        let far = t1.max(t2).min_element();
+        if near > far || far < 0.0 {
+            return None;
+        }
+        Some(near.max(0.0))
+    }
+}
+
+/// Overrides the bounds used for picking the entity, in the local space.
+/// Useful for the entities without a mesh, or to make the small objects easier to pick.
+#[derive(Component, Debug, Clone, Copy)]
+#[component(immutable)]
+pub struct PickBounds {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+/// Excludes the entity from picking.
+#[derive(Component, Debug)]
+pub struct NotPickable;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickResult {
+    pub entity: EntityId,
+    /// Distance from the camera along the ray.
+    pub distance: f32,
+    /// Point of the hit in the world space.
+    pub point: Vec3,
+}
+
+/// Requests picking the entity under the point of the view.
+/// Answered with the `PickResponse` event in the same tick.
+#[derive(GlobalEvent, Debug, Clone)]
+pub struct PickRequest {
+    /// Arbitrary value copied into the response, to tell the requests apart.
+    pub tag: u64,
+    /// Point of the view in pixels (same as the `MouseMove` coordinates).
+    /// If not set, the last known cursor position is used.
+    pub position: Option<Vec2>,
+}
+
+#[derive(GlobalEvent, Debug, Clone)]
+pub struct PickResponse {
+    pub tag: u64,
+    /// The nearest entity hit, or `None` if nothing was hit, the point is on the bars
+    /// around the content, or the camera is not set yet.
+    pub result: Option<PickResult>,
+}
+
+/// Sets the camera the picking rays are cast from.
+/// Should be sent each time the camera changes, with the same matrices
+/// the render passes use.
+#[derive(GlobalEvent, Debug, Clone, Copy)]
+pub struct PickCameraEvent {
+    pub view: Mat4,
+    pub projection: Mat4,
+}
+
+/// Entities considered by picking. Fetch it in the handler to use `Picker::pick`.
+#[derive(Query)]
+pub struct PickQuery<'a> {
+    pub entity: EntityId,
+    pub mesh: Option<&'a ObjectMesh>,
+    pub bounds: Option<&'a PickBounds>,
+    pub position: Option<&'a ObjectPosition>,
+    pub rotation: Option<&'a ObjectRotation>,
+    pub scale: Option<&'a ObjectScale>,
+    pub not_pickable: Option<&'a NotPickable>,
+}
+
+/// Converts the cursor position into the world rays and finds the entities hit by them.
+/// Tracks the cursor and the content rect from the `InputEvent` events,
+/// and the camera from the `PickCameraEvent` events.
+/// Entities are tested against the bounding boxes of their meshes
+/// (or the `PickBounds`), transformed the same way the renderer does it.
+/// The meshes packed with the BVH are then tested against their triangles.
+#[derive(Component, Default)]
+pub struct Picker {
+    cursor: Option<Vec2>,
+    content: Option<ContentRect>,
+    camera: Option<PickCameraEvent>,
+}
+
+impl Picker {
+    pub fn new() -> Self {
+        Picker::default()
+    }
+
+    /// Last known cursor position in the view pixels.
+    pub fn cursor(&self) -> Option<Vec2> {
+        self.cursor
+    }
+
+    pub fn set_camera(&mut self, view: Mat4, projection: Mat4) {
+        self.camera = Some(PickCameraEvent { view, projection });
+    }
+
+    /// Casts the ray through the point of the view (in pixels).
+    /// Returns `None` if the camera is not set or the point is outside the content.
+    pub fn ray(&self, position: Vec2) -> Option<Ray> {
+        let camera = self.camera?;
+        // The content rect is sent by the renderer once the view is created
+        let content = self.content?;
+        let (x, y) = content.normalize_point(position.x, position.y)?;
+        // The view rows go from the top, the NDC ones from the bottom
+        let ndc = Vec2::new(x * 2.0 - 1.0, 1.0 - y * 2.0);
+        Some(Ray::from_ndc(ndc, camera.view, camera.projection))
+    }
+
+    /// Casts the ray through the last known cursor position.
+    pub fn cursor_ray(&self) -> Option<Ray> {
+        self.ray(self.cursor?)
+    }
+
+    /// Finds the nearest entity hit by the ray.
+    pub fn pick(ray: &Ray, fetcher: &Fetcher<PickQuery>) -> Option<PickResult> {
+        let mut nearest: Option<PickResult> = None;
+        for query in fetcher.iter() {
+            if query.not_pickable.is_some() {
+                continue;
+            }
+            let Some((min, max)) = Self::local_bounds(&query) else {
+                continue;
+            };
+
+            // Test in the local space of the entity, so the rotated boxes are exact
+            let model = Mat4::from_scale_rotation_translation(
+                query.scale.map_or(Vec3::ONE, |s| s.0),
+                query.rotation.map_or(Quat::IDENTITY, |r| r.0),
+                query.position.map_or(Vec3::ZERO, |p| p.0),
+            );
+            let inverse = model.inverse();
+            let local = Ray::new(
+                inverse.transform_point3(ray.origin),
+                inverse.transform_vector3(ray.direction),
+            );
+            let Some(mut distance) = local.intersect_aabb(min, max) else {
+                continue;
+            };
+            if query.bounds.is_none() {
+                if let Some(bvh) = query.mesh.and_then(|mesh| mesh.0.cast().bvh.as_ref()) {
+                    let Some((hit, _)) = bvh.intersect_ray(local.origin, local.direction) else {
+                        continue;
+                    };
+                    distance = hit;
+                }
+            }
+
+            let point = model.transform_point3(local.at(distance));
+            let distance = point.distance(ray.origin);
+            if nearest.is_none_or(|n| distance < n.distance) {
+                nearest = Some(PickResult {
+                    entity: query.entity,
+                    distance,
+                    point,
+                });
+            }
+        }
+        nearest
+    }
+
+    fn local_bounds(query: &PickQuery) -> Option<(Vec3, Vec3)> {
+        if let Some(bounds) = query.bounds {
+            return Some((bounds.min, bounds.max));
+        }
+        let mesh = query.mesh?.0.cast();
+        Some((mesh.min, mesh.max))
+    }
+
+    /// Moves the picker into the ECS world.
+    /// `PickRequest` events are answered by the `PickResponse` events.
+    /// Requires the renderer attached to the same world to receive the input events.
+    pub fn attach_to_ecs(self, world: &mut World) {
+        let entity = world.spawn();
+        world.insert(entity, self);
+        world.add_handler(Self::input_handler);
+        world.add_handler(Self::camera_handler);
+        world.add_handler(Self::request_handler);
+    }
+
+    fn input_handler(r: Receiver<InputEvent>, mut picker: Single<&mut Picker>) {
+        match r.event {
+            InputEvent::MouseMove { x, y } => picker.cursor = Some(Vec2::new(*x, *y)),
+            InputEvent::ContentResize(rect) => picker.content = Some(*rect),
+            _ => {}
+        }
+    }
+
+    fn camera_handler(r: Receiver<PickCameraEvent>, mut picker: Single<&mut Picker>) {
+        picker.camera = Some(*r.event);
+    }
+
+    fn request_handler(
+        r: Receiver<PickRequest>,
+        picker: Single<&Picker>,
+        fetcher: Fetcher<PickQuery>,
+        mut sender: Sender<PickResponse>,
+    ) {
+        let ray = match r.event.position {
+            Some(position) => picker.ray(position),
+            None => picker.cursor_ray(),
+        };
+        sender.send(PickResponse {
+            tag: r.event.tag,
+            result: ray.and_then(|ray| Picker::pick(&ray, &fetcher)),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    const EPSILON: f32 = 1e-4;
+
+    #[test]
+    fn ray_direction_is_normalized() {
+        let ray = Ray::new(Vec3::ONE, Vec3::new(0.0, 0.0, -5.0));
+        assert_eq!(ray.origin, Vec3::ONE);
+        assert_eq!(ray.direction, Vec3::NEG_Z);
+        assert_eq!(ray.at(2.0), Vec3::new(1.0, 1.0, -1.0));
+    }
+
+    #[test]
+    fn ndc_ray_goes_from_the_near_plane() {
+        let view = Mat4::look_at_rh(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y);
+        let projection = Mat4::perspective_rh_gl(FRAC_PI_2, 1.0, 0.1, 100.0);
+
+        let center = Ray::from_ndc(Vec2::ZERO, view, projection);
+        assert!(center.origin.abs_diff_eq(Vec3::new(0.0, 0.0, 4.9), EPSILON));
+        assert!(center.direction.abs_diff_eq(Vec3::NEG_Z, EPSILON));
+
+        // The right edge of the 90 degrees frustum
+        let right = Ray::from_ndc(Vec2::X, view, projection);
+        let expected = Vec3::new(1.0, 0.0, -1.0).normalize();
+        assert!(right.origin.abs_diff_eq(Vec3::new(0.1, 0.0, 4.9), EPSILON));
+        assert!(right.direction.abs_diff_eq(expected, EPSILON));
+    }
+
+    #[test]
+    fn aabb_hits_and_misses() {
+        let (min, max) = (Vec3::NEG_ONE, Vec3::ONE);
+        let hit = Ray::new(Vec3::new(0.5, 0.5, 5.0), Vec3::NEG_Z);
+        assert_eq!(hit.intersect_aabb(min, max), Some(4.0));
+        let aside = Ray::new(Vec3::new(3.0, 0.0, 5.0), Vec3::NEG_Z);
+        assert_eq!(aside.intersect_aabb(min, max), None);
+        let behind = Ray::new(Vec3::new(0.0, 0.0, -5.0), Vec3::NEG_Z);
+        assert_eq!(behind.intersect_aabb(min, max), None);
+        let diagonal = Ray::new(Vec3::splat(-3.0), Vec3::ONE);
+        let distance = diagonal.intersect_aabb(min, max).unwrap();
+        assert!((distance - 2.0 * 3f32.sqrt()).abs() < EPSILON);
+    }
+
+    #[test]
+    fn aabb_contains_origin() {
+        let inside = Ray::new(Vec3::new(0.5, 0.0, 0.0), Vec3::X);
+        assert_eq!(inside.intersect_aabb(Vec3::NEG_ONE, Vec3::ONE), Some(0.0));
+    }
+
+    #[derive(GlobalEvent)]
+    struct Cast(Ray);
+
+    #[derive(Component, Default)]
+    struct Picked {
+        result: Option<PickResult>,
+    }
+
+    fn world() -> (World, EntityId) {
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.insert(entity, Picked::default());
+        world.add_handler(
+            |r: Receiver<Cast>, fetcher: Fetcher<PickQuery>, mut picked: Single<&mut Picked>| {
+                picked.result = Picker::pick(&r.event.0, &fetcher);
+            },
+        );
+        (world, entity)
+    }
+
+    /// Spawns the unit box (from -1 to 1 on each axis) with the transform.
+    fn spawn(world: &mut World, position: Vec3, scale: Vec3) -> EntityId {
+        let entity = world.spawn();
+        world.insert(
+            entity,
+            PickBounds {
+                min: Vec3::NEG_ONE,
+                max: Vec3::ONE,
+            },
+        );
+        world.insert(entity, ObjectPosition(position));
+        world.insert(entity, ObjectScale(scale));
+        entity
+    }
+
+    fn cast(world: &mut World, picked: EntityId, origin: Vec3) -> Option<PickResult> {
+        world.send(Cast(Ray::new(origin, Vec3::NEG_Z)));
+        world.get::<Picked>(picked).unwrap().result
+    }
+
+    fn assert_hit(result: Option<PickResult>, entity: EntityId, distance: f32, point: Vec3) {
+        let result = result.expect("Ray must hit");
+        assert_eq!(result.entity, entity);
+        assert!((result.distance - distance).abs() < EPSILON);
+        assert!(result.point.abs_diff_eq(point, EPSILON));
+    }
+
+    #[test]
+    fn pick_hits_and_misses() {
+        let (mut world, picked) = world();
+        let entity = spawn(&mut world, Vec3::ZERO, Vec3::ONE);
+
+        let result = cast(&mut world, picked, Vec3::new(0.5, 0.0, 5.0));
+        assert_hit(result, entity, 4.0, Vec3::new(0.5, 0.0, 1.0));
+        assert_eq!(cast(&mut world, picked, Vec3::new(3.0, 0.0, 5.0)), None);
+    }
+
+    #[test]
+    fn pick_uses_scaled_bounds() {
+        let (mut world, picked) = world();
+        // Spans from 6 to 14 on X and from -3 to 3 on Z
+        let scale = Vec3::new(4.0, 1.0, 3.0);
+        let entity = spawn(&mut world, Vec3::new(10.0, 0.0, 0.0), scale);
+
+        // The distance in the local space is 2/3, converted back to the world one
+        let result = cast(&mut world, picked, Vec3::new(13.0, 0.0, 5.0));
+        assert_hit(result, entity, 2.0, Vec3::new(13.0, 0.0, 3.0));
+        assert_eq!(cast(&mut world, picked, Vec3::new(15.0, 0.0, 5.0)), None);
+    }
+
+    #[test]
+    fn pick_from_inside() {
+        let (mut world, picked) = world();
+        let entity = spawn(&mut world, Vec3::ZERO, Vec3::splat(2.0));
+
+        let origin = Vec3::new(0.5, 0.5, 1.0);
+        assert_hit(cast(&mut world, picked, origin), entity, 0.0, origin);
+    }
+
+    #[test]
+    fn pick_returns_nearest() {
+        let (mut world, picked) = world();
+        let far = spawn(&mut world, Vec3::new(0.0, 0.0, -5.0), Vec3::ONE);
+        let near = spawn(&mut world, Vec3::ZERO, Vec3::ONE);
+
+        let origin = Vec3::new(0.0, 0.0, 5.0);
+        assert_hit(cast(&mut world, picked, origin), near, 4.0, Vec3::Z);
+
+        world.insert(near, NotPickable);
+        let result = cast(&mut world, picked, origin);
+        assert_hit(result, far, 9.0, Vec3::new(0.0, 0.0, -4.0));
+    }
+}
@@ -7,38 +7,101 @@ use std::any::TypeId;
 use std::ptr::NonNull;
 use std::sync::OnceLock;
 
+// The renderable components are immutable: they are changed by inserting
+// the new value (`Insert` event), so the renderer can track the changes
+// and skip collecting the renderables if nothing has changed.
+
 /// ECS component for specifying the rotation of a renderable object.
 /// If entity has no `Rotation` component, it will use the default rotation (0, 0, 0).
 /// The rotation is specified in radians around the x, y, and z axes.
 #[derive(Component)]
+#[component(immutable)]
 pub struct ObjectRotation(pub Quat);
 
 /// ECS component for specifying the position of a renderable object.
 /// If entity has no `Position` component, it will use the default position (0, 0, 0).
 /// The position is specified in world coordinates.
 #[derive(Component)]
+#[component(immutable)]
 pub struct ObjectPosition(pub Vec3);
 
 /// ECS component for specifying the scale of a renderable object.
 #[derive(Component)]
+#[component(immutable)]
 pub struct ObjectScale(pub Vec3);
 
 /// ECS component for specifying the mesh to be rendered.
 /// Also used as a marker to indicate that the entity is renderable.
 /// If entity has no `RenderableMesh` component, it will not be rendered.
 #[derive(Component)]
+#[component(immutable)]
 pub struct ObjectMesh(pub TypedAsset<Mesh>);
 
 #[derive(Component)]
+#[component(immutable)]
 pub struct ObjectMaterial(pub TypedAsset<Material>);
 
+/// ECS component of the directional light of the sun, one per world.
+/// It's sent to the renderer with every frame, the passes read it
+/// with `RendererBackend::sun_light` (e.g. the sky and the fog of the
+/// `gl::atmosphere` passes).
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct RenderableSunLight {
+    /// Direction towards the sun in world coordinates.
+    pub direction: Vec3,
+    /// Linear color of the light.
+    pub color: Vec3,
+    pub intensity: f32,
+}
+
+/// ECS component for specifying the layers the renderable object belongs to.
+/// Each render pass processes only the objects sharing at least one layer
+/// with its mask (see `RenderPass::layers`), e.g. first-person arms rendered
+/// only by the main camera pass, or the icons visible only on the minimap.
+/// If entity has no `RenderLayers` component, it belongs to the default layer.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[component(immutable)]
+pub struct RenderLayers(pub u32);
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        RenderLayers::DEFAULT
+    }
+}
+
+impl RenderLayers {
+    /// Layer of the objects without the component.
+    pub const DEFAULT: RenderLayers = RenderLayers(1);
+    pub const ALL: RenderLayers = RenderLayers(u32::MAX);
+    pub const NONE: RenderLayers = RenderLayers(0);
+
+    /// Mask with the single layer. Layers are numbered from 0 to 31.
+    pub const fn layer(layer: u32) -> Self {
+        RenderLayers(1 << layer)
+    }
+
+    /// Adds the layer to the mask.
+    pub const fn with(self, layer: u32) -> Self {
+        RenderLayers(self.0 | (1 << layer))
+    }
+
+    /// Removes the layer from the mask.
+    pub const fn without(self, layer: u32) -> Self {
+        RenderLayers(self.0 & !(1 << layer))
+    }
+
+    #[inline(always)]
+    pub const fn intersects(&self, other: RenderLayers) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
 impl ObjectMaterial {
     pub fn default_material() -> TypedAsset<Material> {
-        const LOCK: OnceLock<TypedAsset<Material>> = OnceLock::new();
+        static LOCK: OnceLock<TypedAsset<Material>> = OnceLock::new();
 
         // Use OnceLock to ensure the material is created only once
-        let binding = LOCK;
-        let material = binding.get_or_init(|| {
+        let material = LOCK.get_or_init(|| {
             let material = Material::default();
             let ptr = Box::into_raw(Box::new(material));
             let asset = Asset::new(
@@ -57,4 +120,5 @@ pub struct Renderable {
     pub model: Mat4,
     pub material: TypedAsset<Material>,
     pub mesh: TypedAsset<Mesh>,
+    pub layers: RenderLayers,
 }
@@ -1,17 +1,27 @@
 use crate::passes::events::PassEventTrait;
+use crate::renderer::target::ContentRect;
 use crate::view::ViewHandle;
 
+/// Graphics API the renderer runs on. Implemented by `GLRenderer` and, with
+/// the `vulkan` feature, by the experimental clear-and-present `VulkanRenderer`.
+/// The render passes are written against the `RendererBackend`, which is always
+/// the `gl` one, so the `Renderer` doesn't select the backend.
 pub(crate) trait RendererBackendTrait<E: PassEventTrait>
 where
     Self: Sized,
 {
-    fn new(
-        config: RendererBackendConfig,
-        view_handle: ViewHandle,
-    ) -> Result<Self, RendererBackendError>;
+    type Config;
+    type Error;
 
-    fn before_frame(&mut self) -> Result<(), RendererBackendError>;
-    fn after_frame(&mut self) -> Result<(), RendererBackendError>;
+    fn new(config: Self::Config, view_handle: ViewHandle) -> Result<Self, Self::Error>;
+
+    fn before_frame(&mut self) -> Result<(), Self::Error>;
+    fn after_frame(&mut self) -> Result<(), Self::Error>;
+
+    /// Called when the view is resized.
+    /// The backend must recreate all the render targets according to their resize policies.
+    /// Returns the part of the view the content is rendered to.
+    fn resize(&mut self, width: usize, height: usize) -> Result<ContentRect, Self::Error>;
 }
 
 #[cfg(feature = "gl")]
@@ -0,0 +1,83 @@
+use crate::view::OutputFormat;
+use std::fmt::{Display, Formatter};
+use std::sync::{Mutex, PoisonError};
+
+// Published by the backend of the last created renderer,
+// so they can be included in the crash reports from any thread
+static PUBLISHED: Mutex<Option<RendererCapabilities>> = Mutex::new(None);
+
+/// Limits and features of the device the renderer runs on, queried once
+/// when the backend is created. The passes and the asset factories use them
+/// to select the code paths, e.g. skip the anisotropic filtering
+/// or fall back from the compute shaders.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RendererCapabilities {
+    /// Name and version of the API, e.g. "OpenGL 4.6"
+    pub api: String,
+    /// Full version string of the driver
+    pub driver: String,
+    pub renderer: String,
+    pub vendor: String,
+    /// Maximum width and height of the 2D textures
+    pub max_texture_size: u32,
+    pub max_array_texture_layers: u32,
+    /// Maximum number of the MSAA samples
+    pub max_samples: u32,
+    pub max_color_attachments: u32,
+    /// Maximum anisotropy of the texture filtering, `None` if not supported
+    pub max_anisotropy: Option<f32>,
+    pub compute_shaders: bool,
+    pub bindless_textures: bool,
+    /// The driver reports the errors and the warnings through the debug callback
+    pub debug_output: bool,
+    pub extensions: Vec<String>,
+    /// Format of the default framebuffer obtained from the system,
+    /// may be lower than the requested one (see `GLRendererConfig::output_format`)
+    pub output_format: OutputFormat,
+}
+
+impl RendererCapabilities {
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.iter().any(|extension| extension == name)
+    }
+}
+
+impl Display for RendererCapabilities {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let yes_no = |supported: bool| if supported { "yes" } else { "no" };
+        writeln!(f, "API: {}", self.api)?;
+        writeln!(f, "Driver: {}", self.driver)?;
+        writeln!(f, "Renderer: {}", self.renderer)?;
+        writeln!(f, "Vendor: {}", self.vendor)?;
+        writeln!(f, "Max texture size: {}", self.max_texture_size)?;
+        writeln!(
+            f,
+            "Max array texture layers: {}",
+            self.max_array_texture_layers
+        )?;
+        writeln!(f, "Max samples: {}", self.max_samples)?;
+        writeln!(f, "Max color attachments: {}", self.max_color_attachments)?;
+        match self.max_anisotropy {
+            Some(anisotropy) => writeln!(f, "Max anisotropy: {}", anisotropy)?,
+            None => writeln!(f, "Max anisotropy: not supported")?,
+        }
+        writeln!(f, "Compute shaders: {}", yes_no(self.compute_shaders))?;
+        writeln!(f, "Bindless textures: {}", yes_no(self.bindless_textures))?;
+        writeln!(f, "Debug output: {}", yes_no(self.debug_output))?;
+        writeln!(f, "Output format: {}", self.output_format)?;
+        write!(f, "Extensions: {}", self.extensions.len())
+    }
+}
+
+pub(crate) fn publish(capabilities: &RendererCapabilities) {
+    *PUBLISHED.lock().unwrap_or_else(PoisonError::into_inner) = Some(capabilities.clone());
+}
+
+/// Capabilities of the last created renderer backend (see `RendererBackend::capabilities`).
+/// `None` until the backend is created.
+pub fn capabilities() -> Option<RendererCapabilities> {
+    PUBLISHED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
@@ -1,21 +1,59 @@
 use crate::input::InputEvent;
+use crate::output::{OutputEvent, WindowState};
 use crate::passes::events::{PassEventTrait, RenderPassEvent};
 use crate::renderable::{
-    ObjectMaterial, ObjectMesh, ObjectPosition, ObjectRotation, ObjectScale, Renderable,
+    ObjectMaterial, ObjectMesh, ObjectPosition, ObjectRotation, ObjectScale, RenderLayers,
+    Renderable, RenderableSunLight,
 };
 use crate::renderer::monitor::RendererMonitorEvent;
-use crate::renderer::Renderer;
-use dawn_ecs::events::{InterSyncEvent, ExitEvent, TickEvent};
+use crate::renderer::readback::{ReadbackCancel, ReadbackCommand, ReadbackEvent, ReadbackRequest};
+use crate::renderer::reload::{ShaderReloadEvent, ShaderReloadRequest};
+use crate::renderer::settings::RenderSettingsRequest;
+use crate::renderer::warm_up::{WarmUpEvent, WarmUpRequest};
+use crate::renderer::{Renderer, RendererFailed};
+use dawn_ecs::av_sync::FrameSyncEvent;
+use dawn_ecs::events::{ExitEvent, InterSyncEvent, TickEvent};
 use evenio::component::Component;
-use evenio::event::{Receiver, Sender};
-use evenio::fetch::{Fetcher, Single};
+use evenio::entity::EntityId;
+use evenio::event::{Despawn, Insert, Receiver, Remove, Sender};
+use evenio::fetch::{Fetcher, Single, TrySingle};
 use evenio::handler::IntoHandler;
-use evenio::query::Query;
+use evenio::query::{Query, With};
+use evenio::rayon::prelude::*;
 use evenio::world::World;
 use glam::{Mat4, Quat, Vec3};
 use log::info;
+use std::collections::HashMap;
 use std::ptr::NonNull;
 use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+#[derive(Query)]
+pub(crate) struct RenderableQuery<'a> {
+    entity: EntityId,
+    mesh: &'a ObjectMesh,
+    position: Option<&'a ObjectPosition>,
+    rotation: Option<&'a ObjectRotation>,
+    scale: Option<&'a ObjectScale>,
+    material: Option<&'a ObjectMaterial>,
+    layers: Option<&'a RenderLayers>,
+}
+
+pub(crate) fn to_renderable(query: RenderableQuery) -> Renderable {
+    let position = query.position.map_or(Vec3::ZERO, |p| p.0);
+    let rotation = query.rotation.map_or(Quat::IDENTITY, |r| r.0);
+    let scale = query.scale.map_or(Vec3::ONE, |s| s.0);
+    let material = query
+        .material
+        .map_or_else(ObjectMaterial::default_material, |m| m.0.clone());
+
+    Renderable {
+        model: Mat4::from_scale_rotation_translation(scale, rotation, position),
+        material,
+        mesh: query.mesh.0.clone(),
+        layers: query.layers.copied().unwrap_or_default(),
+    }
+}
 
 pub fn attach_to_ecs<E: PassEventTrait>(renderer: Renderer<E>, world: &mut World) {
     #[derive(Component)]
@@ -66,11 +104,15 @@ pub fn attach_to_ecs<E: PassEventTrait>(renderer: Renderer<E>, world: &mut World
     fn view_closed_handler<E: PassEventTrait>(
         _: Receiver<TickEvent>,
         renderer: Single<&Boxed>,
-        mut sender: Sender<ExitEvent>,
+        mut sender: Sender<(RendererFailed, ExitEvent)>,
     ) {
         // Check if the view was closed, if so, send a global event to stop the event loop
         let renderer = renderer.cast::<E>();
-        if renderer.stop_signal.load(Ordering::Relaxed) {
+        // Acquires the failure stored before the signal
+        if renderer.stop_signal.load(Ordering::SeqCst) {
+            if let Some(reason) = renderer.failure.lock().unwrap().take() {
+                sender.send(RendererFailed { reason });
+            }
             info!("View closed, stopping the event loop");
             sender.send(ExitEvent);
         }
@@ -89,6 +131,18 @@ pub fn attach_to_ecs<E: PassEventTrait>(renderer: Renderer<E>, world: &mut World
         }
     }
 
+    // Report the last presented frame to the audio/visual sync clock
+    fn frame_sync_handler<E: PassEventTrait>(
+        _: Receiver<TickEvent>,
+        renderer: Single<&Boxed>,
+        mut sender: Sender<FrameSyncEvent>,
+    ) {
+        let renderer = renderer.cast::<E>();
+        if let Some((frame, presented)) = renderer.presented.lock().unwrap().take() {
+            sender.send(FrameSyncEvent { frame, presented });
+        }
+    }
+
     // Check if there's any input event to process.
     // If so, push them to the ECS
     fn inputs_handler<E: PassEventTrait>(
@@ -111,15 +165,159 @@ pub fn attach_to_ecs<E: PassEventTrait>(renderer: Renderer<E>, world: &mut World
         renderer.renderer_sender.send(rpe.event.clone()).unwrap();
     }
 
-    #[derive(Query)]
-    struct Query<'a> {
-        mesh: &'a ObjectMesh,
-        position: Option<&'a ObjectPosition>,
-        rotation: Option<&'a ObjectRotation>,
-        scale: Option<&'a ObjectScale>,
-        material: Option<&'a ObjectMaterial>,
+    // Transfer view requests from the ECS to the renderer thread
+    fn output_event_handler<E: PassEventTrait>(
+        oe: Receiver<OutputEvent>,
+        renderer: Single<&Boxed>,
+    ) {
+        let renderer = renderer.cast::<E>();
+        // The renderer thread may be already gone, that's fine
+        let _ = renderer.outputs_sender.send(oe.event.clone());
+    }
+
+    // Turn the changes of the window state into the view requests.
+    // Runs after the user's tick handlers, so the changes are applied in the same tick
+    fn window_state_handler<E: PassEventTrait>(
+        _: Receiver<TickEvent>,
+        state: TrySingle<&WindowState>,
+        mut renderer: Single<&mut Boxed>,
+        mut sender: Sender<OutputEvent>,
+    ) {
+        let Ok(state) = state.0 else {
+            return;
+        };
+        let renderer = renderer.cast_mut::<E>();
+        if renderer.window_state == *state {
+            return;
+        }
+        for event in state.changes(Some(&renderer.window_state)) {
+            sender.send(event);
+        }
+        renderer.window_state = state.clone();
+    }
+
+    // Transfer shader reload requests from the ECS to the renderer thread
+    fn reload_request_handler<E: PassEventTrait>(
+        r: Receiver<ShaderReloadRequest>,
+        renderer: Single<&Boxed>,
+    ) {
+        let renderer = renderer.cast::<E>();
+        // The renderer thread may be already gone, that's fine
+        let _ = renderer.reload_sender.send(r.event.clone());
+    }
+
+    // Push the results of the shader reloads to the ECS
+    fn reload_event_handler<E: PassEventTrait>(
+        _: Receiver<TickEvent>,
+        renderer: Single<&Boxed>,
+        mut sender: Sender<ShaderReloadEvent>,
+    ) {
+        let renderer = renderer.cast::<E>();
+        for event in renderer.reload_receiver.try_iter() {
+            sender.send(event);
+        }
+    }
+
+    // Transfer render settings presets from the ECS to the renderer thread
+    fn settings_request_handler<E: PassEventTrait>(
+        r: Receiver<RenderSettingsRequest>,
+        renderer: Single<&Boxed>,
+    ) {
+        let renderer = renderer.cast::<E>();
+        // The renderer thread may be already gone, that's fine
+        let _ = renderer.settings_sender.send(r.event.clone());
+    }
+
+    // Transfer warm-up requests from the ECS to the renderer thread
+    fn warm_up_request_handler<E: PassEventTrait>(
+        r: Receiver<WarmUpRequest>,
+        renderer: Single<&Boxed>,
+    ) {
+        let renderer = renderer.cast::<E>();
+        // The renderer thread may be already gone, that's fine
+        let _ = renderer.warm_up_sender.send(*r.event);
+    }
+
+    // Push the warm-up progress to the ECS
+    fn warm_up_event_handler<E: PassEventTrait>(
+        _: Receiver<TickEvent>,
+        renderer: Single<&Boxed>,
+        mut sender: Sender<WarmUpEvent>,
+    ) {
+        let renderer = renderer.cast::<E>();
+        for event in renderer.warm_up_receiver.try_iter() {
+            sender.send(event);
+        }
+    }
+
+    // Transfer readback requests from the ECS to the renderer thread
+    fn readback_request_handler<E: PassEventTrait>(
+        r: Receiver<ReadbackRequest>,
+        renderer: Single<&Boxed>,
+    ) {
+        let renderer = renderer.cast::<E>();
+        // The renderer thread may be already gone, that's fine
+        let _ = renderer
+            .readback_sender
+            .send(ReadbackCommand::Request(r.event.clone()));
+    }
+
+    fn readback_cancel_handler<E: PassEventTrait>(
+        r: Receiver<ReadbackCancel>,
+        renderer: Single<&Boxed>,
+    ) {
+        let renderer = renderer.cast::<E>();
+        let _ = renderer
+            .readback_sender
+            .send(ReadbackCommand::Cancel(r.event.tag));
+    }
+
+    // Push the read back data to the ECS
+    fn readback_event_handler<E: PassEventTrait>(
+        _: Receiver<TickEvent>,
+        renderer: Single<&Boxed>,
+        mut sender: Sender<ReadbackEvent>,
+    ) {
+        let renderer = renderer.cast::<E>();
+        for event in renderer.readback_receiver.try_iter() {
+            sender.send(event);
+        }
     }
 
+    // Track the changes of the renderable components.
+    // The components are immutable, so they can only be changed via events.
+    fn mesh_changed_handler<E: PassEventTrait>(
+        _: Receiver<Insert<ObjectMesh>, ()>,
+        mut renderer: Single<&mut Boxed>,
+    ) {
+        renderer.cast_mut::<E>().renderables_changed = true;
+    }
+
+    fn component_inserted_handler<E: PassEventTrait, C: Component>(
+        _: Receiver<Insert<C>, With<&ObjectMesh>>,
+        mut renderer: Single<&mut Boxed>,
+    ) {
+        renderer.cast_mut::<E>().renderables_changed = true;
+    }
+
+    fn component_removed_handler<E: PassEventTrait, C: Component>(
+        _: Receiver<Remove<C>, With<&ObjectMesh>>,
+        mut renderer: Single<&mut Boxed>,
+    ) {
+        renderer.cast_mut::<E>().renderables_changed = true;
+    }
+
+    fn despawn_handler<E: PassEventTrait>(
+        _: Receiver<Despawn, With<&ObjectMesh>>,
+        mut renderer: Single<&mut Boxed>,
+    ) {
+        renderer.cast_mut::<E>().renderables_changed = true;
+    }
+
+    // Below this number of entities the parallel collection is slower
+    // than the sequential one because of the scheduling overhead.
+    const PARALLEL_COLLECTION_THRESHOLD: usize = 4096;
+
     // Collect renderables from the ECS and send them to the renderer thread
     // This function will be called every tick to collect the renderables
     // and send them to the renderer thread.
@@ -139,40 +337,99 @@ pub fn attach_to_ecs<E: PassEventTrait>(renderer: Renderer<E>, world: &mut World
     fn stream_data_handle<E: PassEventTrait>(
         t: Receiver<InterSyncEvent>,
         mut renderer: Single<&mut Boxed>,
-        fetcher: Fetcher<Query>,
+        fetcher: Fetcher<RenderableQuery>,
+        sun: TrySingle<&RenderableSunLight>,
     ) {
         let renderer = renderer.cast_mut::<E>();
 
+        // When running unsynchronized, the renderer may skip frames.
+        // Keep sending the renderables until it picks them up.
+        let missed = renderer.content_published && !renderer.data_stream.consumed();
+        let collect = renderer.renderables_changed || missed;
+        renderer.renderables_changed = false;
+        renderer.content_published = collect;
+
+        if collect {
+            renderer.content_epoch = t.event.frame;
+        }
+
         // Update the renderables buffer in-place
         let frame = renderer.data_stream.input_buffer_mut();
+        frame.epoch = t.event.frame;
+        frame.content_epoch = renderer.content_epoch;
+        frame.interval = renderer.interpolation;
+        frame.sun = sun.0.ok().copied();
 
+        // Nothing has changed, send only the marker.
+        // The renderer keeps the renderables of the `content_epoch`.
         frame.renderables.clear();
-        for query in fetcher.iter() {
-            // Collect the renderable data from the query
-            let mesh_asset = query.mesh.0.clone();
-            let position = query.position.map_or(Vec3::ZERO, |p| p.0);
-            let rotation = query.rotation.map_or(Quat::IDENTITY, |r| r.0);
-            let scale = query.scale.map_or(Vec3::ONE, |s| s.0);
-            let material = query
-                .material
-                .map_or_else(|| ObjectMaterial::default_material(), |m| m.0.clone());
-
-            // Push the renderable to the vector
-            frame.renderables.push(Renderable {
-                model: Mat4::from_scale_rotation_translation(scale, rotation, position),
-                material,
-                mesh: mesh_asset,
-            });
+        frame.previous.clear();
+        if !collect {
+            renderer.data_stream.publish();
+            return;
+        }
+        frame.published = Instant::now();
+
+        if renderer.interpolation.is_some() {
+            // Pair each renderable with its model at the previous collection.
+            // The lookups dominate here, so the collection is sequential.
+            let mut models = HashMap::with_capacity(renderer.previous_models.len());
+            for query in fetcher.iter() {
+                let entity = query.entity;
+                let renderable = to_renderable(query);
+                let previous = renderer
+                    .previous_models
+                    .get(&entity)
+                    .copied()
+                    .unwrap_or(renderable.model);
+                models.insert(entity, renderable.model);
+                frame.previous.push(previous);
+                frame.renderables.push(renderable);
+            }
+            // The despawned entities are forgotten
+            renderer.previous_models = models;
+        } else if fetcher.iter().len() < PARALLEL_COLLECTION_THRESHOLD {
+            frame.renderables.extend(fetcher.iter().map(to_renderable));
+        } else {
+            // Archetypes are split into chunks processed by the rayon workers.
+            // Each worker fills its own buffer, the buffers are appended
+            // to the frame at the end, so the workers never contend.
+            frame
+                .renderables
+                .par_extend(fetcher.par_iter().map(to_renderable));
         }
-        frame.epoch = t.event.frame;
 
         // Send the collected renderables to the renderer thread
         renderer.data_stream.publish();
     }
 
     world.add_handler(monitoring_handler::<E>.low());
+    world.add_handler(frame_sync_handler::<E>.low());
     world.add_handler(inputs_handler::<E>.high());
     world.add_handler(view_closed_handler::<E>.low());
     world.add_handler(stream_data_handle::<E>);
     world.add_handler(render_pass_event_handler::<E>.high());
+    world.add_handler(output_event_handler::<E>);
+    world.add_handler(window_state_handler::<E>.low());
+    world.add_handler(reload_request_handler::<E>);
+    world.add_handler(reload_event_handler::<E>.low());
+    world.add_handler(settings_request_handler::<E>);
+    world.add_handler(warm_up_request_handler::<E>);
+    world.add_handler(warm_up_event_handler::<E>.low());
+    world.add_handler(readback_request_handler::<E>);
+    world.add_handler(readback_cancel_handler::<E>);
+    world.add_handler(readback_event_handler::<E>.low());
+    world.add_handler(mesh_changed_handler::<E>);
+    world.add_handler(component_removed_handler::<E, ObjectMesh>);
+    world.add_handler(component_inserted_handler::<E, ObjectPosition>);
+    world.add_handler(component_removed_handler::<E, ObjectPosition>);
+    world.add_handler(component_inserted_handler::<E, ObjectRotation>);
+    world.add_handler(component_removed_handler::<E, ObjectRotation>);
+    world.add_handler(component_inserted_handler::<E, ObjectScale>);
+    world.add_handler(component_removed_handler::<E, ObjectScale>);
+    world.add_handler(component_inserted_handler::<E, ObjectMaterial>);
+    world.add_handler(component_removed_handler::<E, ObjectMaterial>);
+    world.add_handler(component_inserted_handler::<E, RenderLayers>);
+    world.add_handler(component_removed_handler::<E, RenderLayers>);
+    world.add_handler(despawn_handler::<E>);
 }
@@ -1,50 +1,194 @@
 pub(crate) mod backend;
-mod ecs;
+pub(crate) mod capabilities;
+pub(crate) mod ecs;
 mod monitor;
+pub mod readback;
+mod reload;
+pub mod resource;
+mod settings;
+pub mod target;
+mod warm_up;
 
 use crate::input::InputEvent;
+use crate::output::{Cursor, OutputEvent, WindowState};
 use crate::passes::chain::RenderChain;
 use crate::passes::events::{PassEventTrait, RenderPassEvent};
 use crate::passes::pipeline::RenderPipeline;
 use crate::passes::result::RenderResult;
 use crate::passes::ChainExecuteCtx;
-use crate::renderable::Renderable;
+use crate::renderable::{Renderable, RenderableSunLight};
 use crate::renderer::backend::{RendererBackendError, RendererBackendTrait};
 use crate::renderer::ecs::attach_to_ecs;
 use crate::renderer::monitor::{DummyRendererMonitor, RendererMonitor, RendererMonitorTrait};
+use crate::renderer::readback::{ReadbackCommand, ReadbackEvent};
 use crate::view::{TickResult, View, ViewConfig, ViewError, ViewTrait};
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use evenio::component::Component;
+use evenio::entity::EntityId;
+use evenio::event::GlobalEvent;
 use evenio::world::World;
+use glam::Mat4;
 use log::{info, warn};
+use std::collections::HashMap;
 use std::panic::UnwindSafe;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread::{Builder, JoinHandle};
+use std::time::{Duration, Instant};
 use triple_buffer::{triple_buffer, Input, Output};
 
 // Re-export the necessary types for user
 pub use backend::{RendererBackend, RendererBackendConfig};
+pub use capabilities::{capabilities, RendererCapabilities};
 use dawn_util::rendezvous::Rendezvous;
-pub use monitor::RendererMonitorEvent;
+pub use monitor::{BatchingStats, FrameStats, ProgramCacheStats, RendererMonitorEvent};
+pub use reload::{ShaderReloadEvent, ShaderReloadRequest};
+pub use settings::{RenderSettings, RenderSettingsFactory, RenderSettingsRequest};
+pub use warm_up::{WarmUpEvent, WarmUpRequest};
+
+// Interval of the renderer loop while the window is occluded.
+// Nothing is rendered in the meantime, but the queues are still drained.
+// Note that the synchronized logic thread is throttled to the same rate.
+const OCCLUDED_TICK_INTERVAL: Duration = Duration::from_millis(50);
 
 #[derive(Clone)]
 pub(crate) struct DataStreamFrame {
     epoch: usize,
+    // Epoch of the frame the renderables were collected at.
+    // If it's older than `epoch`, nothing has changed since then and the
+    // renderables are not sent: the renderer reuses the ones it has cached.
+    content_epoch: usize,
+    renderables: Vec<Renderable>,
+    // With the interpolation, the models of the renderables at the previous
+    // collection, in the same order. Empty otherwise.
+    previous: Vec<Mat4>,
+    // Instant the frame was published at
+    published: Instant,
+    // Tick interval of the logic, if the renderer interpolates
+    interval: Option<Duration>,
+    // Sent with every frame, unlike the renderables
+    sun: Option<RenderableSunLight>,
+}
+
+// Renderables kept by the renderer thread between the frames
+struct RenderablesCache {
+    epoch: Option<usize>,
     renderables: Vec<Renderable>,
+    // Models of the renderables to interpolate between, see `interpolate`
+    previous: Vec<Mat4>,
+    current: Vec<Mat4>,
+    published: Instant,
+    interval: Option<Duration>,
+    sun: Option<RenderableSunLight>,
+}
+
+impl RenderablesCache {
+    fn new() -> Self {
+        RenderablesCache {
+            epoch: None,
+            renderables: vec![],
+            previous: vec![],
+            current: vec![],
+            published: Instant::now(),
+            interval: None,
+            sun: None,
+        }
+    }
+
+    // Moves the renderables to the state between the previous and the current
+    // collection. The renderer runs one tick behind the logic: the state of the
+    // current collection is reached one tick interval after it was published.
+    fn interpolate(&mut self) {
+        let Some(interval) = self.interval else {
+            // Interpolation is disabled, restore the models it has changed
+            if !self.current.is_empty() {
+                for (renderable, model) in self.renderables.iter_mut().zip(&self.current) {
+                    renderable.model = *model;
+                }
+                self.current.clear();
+                self.previous.clear();
+            }
+            return;
+        };
+        if self.previous.len() != self.renderables.len() {
+            return;
+        }
+
+        let alpha = (self.published.elapsed().as_secs_f32() / interval.as_secs_f32()).min(1.0);
+        for ((renderable, previous), current) in self
+            .renderables
+            .iter_mut()
+            .zip(&self.previous)
+            .zip(&self.current)
+        {
+            renderable.model = interpolate_model(previous, current, alpha);
+        }
+    }
+}
+
+fn interpolate_model(previous: &Mat4, current: &Mat4, alpha: f32) -> Mat4 {
+    if alpha >= 1.0 || previous == current {
+        return *current;
+    }
+    let (scale_a, rotation_a, position_a) = previous.to_scale_rotation_translation();
+    let (scale_b, rotation_b, position_b) = current.to_scale_rotation_translation();
+    Mat4::from_scale_rotation_translation(
+        scale_a.lerp(scale_b, alpha),
+        rotation_a.slerp(rotation_b, alpha),
+        position_a.lerp(position_b, alpha),
+    )
+}
+
+/// Sent once, right before the `ExitEvent`, if the renderer thread has stopped
+/// because of the error, e.g. the view or the GL context cannot be created.
+#[derive(GlobalEvent, Debug, Clone)]
+pub struct RendererFailed {
+    pub reason: String,
 }
 
 #[derive(Component)]
 pub struct Renderer<E: PassEventTrait> {
     stop_signal: Arc<AtomicBool>,
+    // Error the renderer thread has stopped with, taken by the ECS
+    failure: Arc<Mutex<Option<String>>>,
+    // The last presented frame and the instant it was presented at, taken by the ECS
+    presented: Arc<Mutex<Option<(usize, Instant)>>>,
     // Used for streaming renderables to the renderer thread
     // This is a triple buffer, so it can be used to read and write renderables
     // without blocking the renderer thread.
     data_stream: Input<DataStreamFrame>,
+    // Set when any of the renderable components has changed since the last collection.
+    renderables_changed: bool,
+    // Set if the last published frame carried the renderables.
+    content_published: bool,
+    // Epoch of the last frame that carried the renderables.
+    content_epoch: usize,
+    // Tick interval of the logic, if the renderer interpolates the renderables.
+    interpolation: Option<Duration>,
+    // Models of the renderables at the last collection, to interpolate from.
+    previous_models: HashMap<EntityId, Mat4>,
     // Used for transferring input events from the renderer thread to the ECS.
     inputs_receiver: Receiver<InputEvent>,
     // Used for transferring render pass events from the ECS to the renderer thread.
     renderer_sender: Sender<RenderPassEvent<E>>,
+    // Used for transferring view requests from the ECS to the renderer thread.
+    outputs_sender: Sender<OutputEvent>,
+    // The window state requested last time, see `WindowState`.
+    window_state: WindowState,
+    // Used for transferring shader reload requests from the ECS to the renderer thread.
+    reload_sender: Sender<ShaderReloadRequest>,
+    // Used for transferring shader reload results from the renderer thread to the ECS.
+    reload_receiver: Receiver<ShaderReloadEvent>,
+    // Used for transferring render settings presets from the ECS to the renderer thread.
+    settings_sender: Sender<RenderSettingsRequest>,
+    // Used for transferring warm-up requests from the ECS to the renderer thread.
+    warm_up_sender: Sender<WarmUpRequest>,
+    // Used for transferring warm-up progress from the renderer thread to the ECS.
+    warm_up_receiver: Receiver<WarmUpEvent>,
+    // Used for transferring readback requests from the ECS to the renderer thread.
+    readback_sender: Sender<ReadbackCommand>,
+    // Used for transferring read back data from the renderer thread to the ECS.
+    readback_receiver: Receiver<ReadbackEvent>,
     monitor_receiver: Receiver<RendererMonitorEvent>,
     handle: Option<JoinHandle<()>>,
 }
@@ -225,34 +369,66 @@ impl<E: PassEventTrait> Renderer<E> {
         // Setup renderer
         let (inputs_sender, inputs_receiver) = unbounded();
         let (renderer_sender, renderer_receiver) = unbounded();
+        let (outputs_sender, outputs_receiver) = unbounded();
+        let window_state = WindowState::from(&view_config);
+        let (reload_sender, reload_requests) = unbounded();
+        let (reload_events, reload_receiver) = unbounded();
+        let (settings_sender, settings_requests) = unbounded();
+        let (warm_up_sender, warm_up_requests) = unbounded();
+        let (warm_up_events, warm_up_receiver) = unbounded();
+        let (readback_sender, readback_requests) = unbounded();
+        let (readback_events, readback_receiver) = unbounded();
         let (stream_input, mut stream_output) =
             triple_buffer::<DataStreamFrame>(&DataStreamFrame {
                 epoch: 0,
+                content_epoch: 0,
                 renderables: vec![],
+                previous: vec![],
+                published: Instant::now(),
+                interval: None,
+                sun: None,
             });
+        let synchronized = view_config.synchronization.is_some();
         let stop_signal = Arc::new(AtomicBool::new(false));
 
         let stop_signal_clone = stop_signal.clone();
+        let failure = Arc::new(Mutex::new(None));
+        let failure_clone = failure.clone();
+        let presented = Arc::new(Mutex::new(None));
+        let presented_clone = presented.clone();
         let handle = Builder::new()
             .name("renderer".to_string())
             .spawn(move || {
                 info!("Renderer thread started");
 
                 let func = || {
+                    // The view events are routed through the renderer thread,
+                    // so it can react to resizes before forwarding them to the ECS.
+                    let (view_sender, view_receiver) = unbounded();
+                    let (width, height) = (view_config.width, view_config.height);
+
                     // Create the view, backend and the rendering pipeline
-                    let mut view = View::open(view_config, inputs_sender)
-                        .map_err(RendererError::ViewCreateError)?;
+                    let mut view =
+                        View::open(view_config, backend_config.output_format, view_sender)
+                            .map_err(RendererError::ViewCreateError)?;
                     let mut backend = RendererBackend::<E>::new(backend_config, view.get_handle())
                         .map_err(RendererError::BackendCreateError)?;
+                    let rect = backend
+                        .resize(width, height)
+                        .map_err(RendererError::BackendCreateError)?;
+                    let _ = inputs_sender.send(InputEvent::ContentResize(rect));
                     let mut pipeline =
                         constructor(&mut backend).map_err(RendererError::PipelineCreateError)?;
 
                     // Notify the monitor about the pass names
                     let pass_names = pipeline.get_names();
                     monitor.set_pass_names(&pass_names);
+                    monitor.set_output_format(backend.capabilities().output_format);
 
                     info!("Starting renderer loop");
                     let mut frame_index = 0;
+                    let mut cache = RenderablesCache::new();
+                    let mut occluded = false;
                     while !stop_signal_clone.load(Ordering::SeqCst) {
                         // This has no sense if no synchronization is disabled,
                         // but if it is, it is a good idea to process all the events between frames.
@@ -261,6 +437,13 @@ impl<E: PassEventTrait> Renderer<E> {
                         // It also guarantees that all the events the user produced will be processed
                         // before the next frame.
                         Self::handle_events(&mut monitor, &mut pipeline, &renderer_receiver)?;
+                        Self::handle_reloads(
+                            &mut backend,
+                            &mut pipeline,
+                            &reload_requests,
+                            &reload_events,
+                        );
+                        Self::handle_settings(&mut backend, &mut pipeline, &settings_requests);
 
                         // Meet with the Main thread
                         before_frame.wait();
@@ -278,17 +461,59 @@ impl<E: PassEventTrait> Renderer<E> {
                             _ => {}
                         }
 
-                        // Render the frame
-                        frame_index = Self::handle_render(
-                            frame_index,
-                            &mut monitor,
+                        // Apply the view requests from the ECS
+                        Self::handle_outputs(&mut view, &outputs_receiver);
+
+                        // Forward the view events to the ECS, handling resizes on the way
+                        Self::handle_inputs(
                             &mut backend,
-                            &mut stream_output,
                             &mut pipeline,
+                            &view_receiver,
+                            &inputs_sender,
+                            &mut occluded,
                         )?;
 
+                        if occluded {
+                            // Presenting to the hidden window either spins or blocks
+                            // in the swapchain, so just keep up with the data stream.
+                            frame_index =
+                                Self::handle_skip(frame_index, &mut stream_output, &mut cache);
+                        } else {
+                            // Render the frame
+                            frame_index = Self::handle_render(
+                                frame_index,
+                                synchronized,
+                                &mut monitor,
+                                &mut backend,
+                                &mut stream_output,
+                                &mut cache,
+                                &mut pipeline,
+                            )?;
+                            *presented_clone.lock().unwrap() = Some((frame_index, Instant::now()));
+                        }
+
+                        // Warm up with the renderables of the frame just received
+                        Self::handle_warm_up(
+                            &mut backend,
+                            &cache,
+                            &warm_up_requests,
+                            &warm_up_events,
+                        );
+
+                        // Read back the rendered frame
+                        Self::handle_readbacks(
+                            &mut backend,
+                            frame_index,
+                            &readback_requests,
+                            &readback_events,
+                        );
+
                         // Meet with the Main thread again.
                         after_frame.wait();
+
+                        if occluded {
+                            std::thread::sleep(OCCLUDED_TICK_INTERVAL);
+                        }
                     }
 
                     Ok(())
@@ -297,21 +522,38 @@ impl<E: PassEventTrait> Renderer<E> {
                 // TODO: Handle panics in the renderer thread
                 let err: Result<(), RendererError> = func();
 
-                // Request other threads to stop
-                stop_signal_clone.store(true, Ordering::SeqCst);
-                info!("Renderer thread finished");
-
                 if let Err(e) = err {
                     warn!("Renderer thread error: {:?}", e);
+                    *failure_clone.lock().unwrap() = Some(e.to_string());
                 }
+
+                // Request other threads to stop
+                stop_signal_clone.store(true, Ordering::SeqCst);
+                info!("Renderer thread finished");
             })
             .map_err(|_| RendererError::RendererThreadSetupFailed)?;
 
         Ok(Self {
             stop_signal,
+            failure,
+            presented,
             data_stream: stream_input,
+            renderables_changed: true,
+            content_published: false,
+            content_epoch: 0,
+            interpolation: None,
+            previous_models: HashMap::new(),
             inputs_receiver,
             renderer_sender,
+            outputs_sender,
+            window_state,
+            reload_sender,
+            reload_receiver,
+            settings_sender,
+            warm_up_sender,
+            warm_up_receiver,
+            readback_sender,
+            readback_receiver,
             monitor_receiver,
             handle: Some(handle),
         })
@@ -344,6 +586,98 @@ impl<E: PassEventTrait> Renderer<E> {
         Ok(true)
     }
 
+    #[inline(always)]
+    fn handle_outputs(view: &mut View, outputs_queue: &Receiver<OutputEvent>) {
+        for event in outputs_queue.try_iter() {
+            match event {
+                // The view stays as it was on failures, no reason to stop the renderer
+                OutputEvent::SetGeometry(geometry) => {
+                    if let Err(e) = view.set_geometry(geometry) {
+                        warn!("Failed to set view geometry: {}", e);
+                    }
+                }
+                OutputEvent::SetTitle(title) => {
+                    if let Err(e) = view.set_title(&title) {
+                        warn!("Failed to set view title: {}", e);
+                    }
+                }
+                OutputEvent::SetSize { width, height } => {
+                    if width == 0 || height == 0 {
+                        warn!("Ignoring the empty view size {}x{}", width, height);
+                    } else if let Err(e) = view.set_size(width, height) {
+                        warn!("Failed to set view size: {}", e);
+                    }
+                }
+                OutputEvent::SetSizeLimits { min, max } => {
+                    if let Err(e) = view.set_size_limits(min, max) {
+                        warn!("Failed to set view size limits: {}", e);
+                    }
+                }
+                OutputEvent::SetCursor(cursor) => {
+                    if let Cursor::Image(image) = &cursor {
+                        if !image.is_valid() {
+                            warn!("Ignoring the invalid cursor image");
+                            continue;
+                        }
+                    }
+                    if let Err(e) = view.set_cursor(&cursor) {
+                        warn!("Failed to set view cursor: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn handle_inputs<C>(
+        backend: &mut RendererBackend<E>,
+        pipeline: &mut RenderPipeline<C, E>,
+        view_queue: &Receiver<InputEvent>,
+        inputs_sender: &Sender<InputEvent>,
+        occluded: &mut bool,
+    ) -> Result<(), RendererError>
+    where
+        C: RenderChain<E>,
+    {
+        // Only the last resize matters, there's no sense to recreate
+        // the render targets several times per frame.
+        let mut resize = None;
+        for event in view_queue.try_iter() {
+            match event {
+                InputEvent::Resize { width, height } => {
+                    resize = Some((width, height));
+                }
+                // The platforms may report the same state several times
+                // (e.g. unmapped and obscured), forward only the changes.
+                InputEvent::WindowOccluded if *occluded => continue,
+                InputEvent::WindowRestored if !*occluded => continue,
+                InputEvent::WindowOccluded => {
+                    info!("Window occluded, pausing rendering");
+                    *occluded = true;
+                }
+                InputEvent::WindowRestored => {
+                    info!("Window restored, resuming rendering");
+                    *occluded = false;
+                }
+                _ => {}
+            }
+
+            // The ECS side may be already gone, that's fine
+            let _ = inputs_sender.send(event);
+        }
+
+        if let Some((width, height)) = resize {
+            let rect = backend
+                .resize(width, height)
+                .map_err(RendererError::BackendRenderError)?;
+            // The passes render to the content, not to the whole view
+            pipeline.on_resize(backend, rect.width, rect.height);
+            let _ = inputs_sender.send(InputEvent::ContentResize(rect));
+        }
+
+        Ok(())
+    }
+
     #[inline(always)]
     fn handle_events<C>(
         monitor: &mut impl RendererMonitorTrait,
@@ -362,12 +696,144 @@ impl<E: PassEventTrait> Renderer<E> {
         Ok(())
     }
 
+    #[inline(always)]
+    fn handle_reloads<C>(
+        backend: &mut RendererBackend<E>,
+        pipeline: &mut RenderPipeline<C, E>,
+        requests: &Receiver<ShaderReloadRequest>,
+        events: &Sender<ShaderReloadEvent>,
+    ) where
+        C: RenderChain<E>,
+    {
+        for request in requests.try_iter() {
+            let shader = request.shader.cast();
+            let handle = shader.handle();
+            let event = match backend.reload_shader(shader, request.ir) {
+                Ok(()) => {
+                    pipeline.on_shader_reload(backend, handle);
+                    ShaderReloadEvent::Reloaded(handle)
+                }
+                Err(e) => ShaderReloadEvent::Failed {
+                    shader: handle,
+                    log: e.to_string(),
+                },
+            };
+
+            // The ECS side may be already gone, that's fine
+            let _ = events.send(event);
+        }
+    }
+
+    #[inline(always)]
+    fn handle_settings<C>(
+        backend: &mut RendererBackend<E>,
+        pipeline: &mut RenderPipeline<C, E>,
+        requests: &Receiver<RenderSettingsRequest>,
+    ) where
+        C: RenderChain<E>,
+    {
+        for request in requests.try_iter() {
+            pipeline.on_settings(backend, &request.settings.cast().0);
+        }
+    }
+
+    #[inline(always)]
+    fn handle_warm_up(
+        backend: &mut RendererBackend<E>,
+        cache: &RenderablesCache,
+        requests: &Receiver<WarmUpRequest>,
+        events: &Sender<WarmUpEvent>,
+    ) {
+        // Several requests in a row are warmed up once
+        if requests.try_iter().count() == 0 {
+            return;
+        }
+
+        // The ECS side may be already gone, that's fine
+        let start = std::time::Instant::now();
+        let result = backend.warm_up(&cache.renderables, |done, total| {
+            let _ = events.send(WarmUpEvent::Progress { done, total });
+        });
+        let _ = events.send(match result {
+            Ok(permutations) => WarmUpEvent::Finished {
+                permutations,
+                duration: start.elapsed(),
+            },
+            Err(e) => {
+                warn!("Failed to warm up the shader programs: {}", e);
+                WarmUpEvent::Failed
+            }
+        });
+    }
+
+    #[inline(always)]
+    fn handle_readbacks(
+        backend: &mut RendererBackend<E>,
+        frame_index: usize,
+        requests: &Receiver<ReadbackCommand>,
+        events: &Sender<ReadbackEvent>,
+    ) {
+        for command in requests.try_iter() {
+            backend.readback_command(command);
+        }
+        // The frame index already points to the next frame
+        backend.process_readbacks(frame_index.saturating_sub(1), events);
+    }
+
+    #[inline(always)]
+    fn receive_frame(stream: &mut Output<DataStreamFrame>, cache: &mut RenderablesCache) -> usize {
+        stream.update();
+        let frame = stream.output_buffer_mut();
+
+        // Take the renderables if they were collected at this frame.
+        // The same buffer may be read several times, so check the cache epoch as well.
+        cache.interval = frame.interval;
+        cache.sun = frame.sun;
+        if frame.content_epoch == frame.epoch && cache.epoch != Some(frame.content_epoch) {
+            std::mem::swap(&mut cache.renderables, &mut frame.renderables);
+            std::mem::swap(&mut cache.previous, &mut frame.previous);
+            cache.current.clear();
+            if cache.interval.is_some() {
+                cache
+                    .current
+                    .extend(cache.renderables.iter().map(|renderable| renderable.model));
+            }
+            cache.published = frame.published;
+            cache.epoch = Some(frame.content_epoch);
+        } else if cache.epoch != Some(frame.content_epoch) {
+            warn!(
+                "Missed the renderables of epoch {}, using ones of epoch {:?}",
+                frame.content_epoch, cache.epoch
+            );
+        }
+
+        frame.epoch
+    }
+
+    #[inline(always)]
+    fn handle_skip(
+        frame_index: usize,
+        stream: &mut Output<DataStreamFrame>,
+        cache: &mut RenderablesCache,
+    ) -> usize {
+        // Consume the published frame, so the epochs and the cache are still
+        // in sync when the rendering is resumed.
+        let epoch = Self::receive_frame(stream, cache);
+        if epoch != frame_index {
+            epoch
+        } else {
+            frame_index + 1
+        }
+    }
+
     #[inline(always)]
     fn handle_render<C>(
         mut frame_index: usize,
+        synchronized: bool,
         monitor: &mut impl RendererMonitorTrait,
         backend: &mut RendererBackend<E>,
         stream: &mut Output<DataStreamFrame>,
+        cache: &mut RenderablesCache,
         pipeline: &mut RenderPipeline<C, E>,
     ) -> Result<usize, RendererError>
     where
@@ -378,18 +844,27 @@ impl<E: PassEventTrait> Renderer<E> {
             return Err(RendererError::BackendRenderError(e));
         }
 
-        let frame = stream.read();
-        if frame.epoch != frame_index {
+        monitor.set_batching(backend.batching_stats());
+        monitor.set_program_cache(backend.program_cache_stats());
+
+        let epoch = Self::receive_frame(stream, cache);
+        if epoch != frame_index && !synchronized {
+            // Not synchronized, the renderer runs at its own rate:
+            // the same tick is rendered several times, or the ticks are skipped
+            frame_index = epoch + 1;
+        } else if epoch != frame_index {
             warn!(
                 "Renderer is out of sync! Expected epoch {}, got {}",
-                frame_index, frame.epoch
+                frame_index, epoch
             );
-            frame_index = frame.epoch;
+            frame_index = epoch;
         } else {
             frame_index += 1;
         }
 
-        let mut ctx = ChainExecuteCtx::new(frame.renderables.as_slice(), backend);
+        cache.interpolate();
+        backend.set_sun_light(cache.sun);
+        let mut ctx = ChainExecuteCtx::new(cache.renderables.as_slice(), backend);
 
         let pass_result = pipeline.execute(&mut ctx);
         if let RenderResult::Failed = pass_result {
@@ -399,23 +874,53 @@ impl<E: PassEventTrait> Renderer<E> {
         // Do not include after frame in the monitoring, because it usually synchronizes
         // the rendered frame with the OS by swapping buffer, that usually is synchronized
         // with the refresh rate of the display. So this will not be informative.
-        monitor.render_stop(pass_result, &ctx.durations);
+        let durations = ctx.durations;
+        monitor.set_frame_stats(backend.take_frame_stats());
+        monitor.render_stop(pass_result, &durations);
 
         if let Err(e) = backend.after_frame() {
-            return Err(RendererError::BackendRenderError(e))?;
+            Err(RendererError::BackendRenderError(e))?;
         }
 
         Ok(frame_index)
     }
 
+    /// Interpolates the renderables between the last two ticks of the logic
+    /// running at the `tick_rate`, so the movement is smooth when the renderer runs
+    /// at the display rate, faster than the logic. Meant for the renderer without
+    /// the synchronization (see `ViewConfig::synchronization`): the rendered state
+    /// lags behind the logic by up to one tick. `None` disables it.
+    ///
+    /// The models of the renderables are interpolated per entity, the renderables
+    /// appearing since the previous tick are rendered as they are.
+    pub fn set_interpolation(&mut self, tick_rate: Option<f32>) {
+        self.interpolation = tick_rate
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| Duration::from_secs_f32(1.0 / rate));
+        self.previous_models.clear();
+        self.renderables_changed = true;
+    }
+
     /// After attaching the renderer to the ECS, it will automatically collect the renderables
     /// and send them to the renderer thread (see `renderable` mod for more details).
     ///
     /// When any input event is received, it will be sent to the ECS as `InputEvent` events.
+    /// That includes `InputEvent::WindowOccluded` and `InputEvent::WindowRestored`:
+    /// the renderer does not render while the window is occluded.
     /// It will capture all user's render pass events as `RenderPassEvent<E>` events and
     /// send them to the renderer thread for processing.
+    /// The `OutputEvent` events are sent to the renderer thread as well,
+    /// they are applied to the view between the frames. The changes of the
+    /// `WindowState` component are turned into the `OutputEvent` events every tick.
+    /// The `ShaderReloadRequest` events are handled the same way, and answered
+    /// with the `ShaderReloadEvent` events, and the `WarmUpRequest` events
+    /// with the `WarmUpEvent` ones, the `ReadbackRequest` and `ReadbackCancel`
+    /// events with the `ReadbackEvent` ones. The `RenderSettingsRequest` events
+    /// are passed to the passes with `RenderPass::on_settings`.
     /// Also, if you've enabled monitoring, it will send monitor data as `RendererMonitoring`
     /// events to the ECS every second.
+    /// The presented frames are sent as `FrameSyncEvent` events
+    /// (see `dawn_ecs::av_sync::AvSyncClock`).
     /// Additionally, if the Window or Renderer is closed/failed the event loop will be stopped
     /// by sending a `ExitEvent` event to the ECS.
     ///
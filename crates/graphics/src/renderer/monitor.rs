@@ -1,13 +1,83 @@
 use crate::passes::result::RenderResult;
 use crate::passes::MAX_RENDER_PASSES;
+use crate::view::OutputFormat;
+use crossbeam_channel::Sender;
+use dawn_util::profile::{Counter, MonitorSample, Stopwatch};
 use evenio::event::GlobalEvent;
-use log::{debug, warn};
+use log::debug;
 use std::collections::HashMap;
 use std::panic::UnwindSafe;
-use std::sync::Arc;
 use std::time::Duration;
-use crossbeam_channel::Sender;
-use dawn_util::profile::{Counter, MonitorSample, Stopwatch};
+
+/// Statistics of the static batching of the loaded meshes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BatchingStats {
+    /// Number of the submeshes in the batched meshes.
+    pub submeshes: usize,
+    /// Number of the draws the submeshes were merged into.
+    pub batches: usize,
+}
+
+/// Statistics of the on-disk cache of the linked shader programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ProgramCacheStats {
+    /// Programs loaded from the cached binaries.
+    pub hits: usize,
+    /// Programs without the cached binary (or with the one of another driver).
+    pub misses: usize,
+    /// Cached binaries rejected by the driver and compiled from the sources.
+    pub rejected: usize,
+}
+
+/// Work submitted to the GPU in the frame, counted by the backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameStats {
+    /// Number of the draw commands issued.
+    pub draw_calls: usize,
+    /// Number of the instances drawn by them.
+    pub instances: usize,
+    /// Number of the triangles drawn, the other primitives are not counted.
+    pub triangles: usize,
+    /// Binds of the shader programs, vertex arrays and framebuffers,
+    /// and the viewport changes.
+    pub state_changes: usize,
+    /// Binds of the textures to the texture units.
+    pub texture_binds: usize,
+    /// Bytes of the buffer and the texture data sent to the GPU.
+    pub uploaded_bytes: usize,
+}
+
+impl FrameStats {
+    fn add(&mut self, other: &FrameStats) {
+        self.draw_calls += other.draw_calls;
+        self.instances += other.instances;
+        self.triangles += other.triangles;
+        self.state_changes += other.state_changes;
+        self.texture_binds += other.texture_binds;
+        self.uploaded_bytes += other.uploaded_bytes;
+    }
+
+    fn max(&mut self, other: &FrameStats) {
+        self.draw_calls = self.draw_calls.max(other.draw_calls);
+        self.instances = self.instances.max(other.instances);
+        self.triangles = self.triangles.max(other.triangles);
+        self.state_changes = self.state_changes.max(other.state_changes);
+        self.texture_binds = self.texture_binds.max(other.texture_binds);
+        self.uploaded_bytes = self.uploaded_bytes.max(other.uploaded_bytes);
+    }
+
+    fn divide(&self, frames: usize) -> FrameStats {
+        let frames = frames.max(1);
+        FrameStats {
+            draw_calls: self.draw_calls / frames,
+            instances: self.instances / frames,
+            triangles: self.triangles / frames,
+            state_changes: self.state_changes / frames,
+            texture_binds: self.texture_binds / frames,
+            uploaded_bytes: self.uploaded_bytes / frames,
+        }
+    }
+}
 
 #[derive(GlobalEvent)]
 pub struct RendererMonitorEvent {
@@ -35,6 +105,16 @@ pub struct RendererMonitorEvent {
     /// The number of draw calls made in the frame.
     /// This is the number of times the GPU was instructed
     pub draw_calls: MonitorSample<f32>,
+    /// Work submitted to the GPU per frame, averaged over the last second.
+    pub frame_stats: FrameStats,
+    /// The largest counts of a single frame in the last second.
+    pub peak_frame_stats: FrameStats,
+    /// Static batching of the currently loaded meshes.
+    pub static_batching: BatchingStats,
+    /// Program cache usage since the start.
+    pub program_cache: ProgramCacheStats,
+    /// Format of the default framebuffer obtained from the system.
+    pub output_format: OutputFormat,
 }
 
 pub(crate) trait RendererMonitorTrait: Send + Sync + 'static + UnwindSafe {
@@ -47,8 +127,11 @@ pub(crate) trait RendererMonitorTrait: Send + Sync + 'static + UnwindSafe {
     fn events_stop(&mut self) {}
 
     fn render_start(&mut self) {}
-    fn render_stop(&mut self, _result: RenderResult, _passes: &[Duration; MAX_RENDER_PASSES]) {
-    }
+    fn set_batching(&mut self, _stats: BatchingStats) {}
+    fn set_program_cache(&mut self, _stats: ProgramCacheStats) {}
+    fn set_output_format(&mut self, _format: OutputFormat) {}
+    fn set_frame_stats(&mut self, _stats: FrameStats) {}
+    fn render_stop(&mut self, _result: RenderResult, _passes: &[Duration; MAX_RENDER_PASSES]) {}
 }
 
 pub(crate) struct RendererMonitor {
@@ -58,6 +141,12 @@ pub(crate) struct RendererMonitor {
     render: Stopwatch,
     draw_calls: Counter,
     drawn_primitives: Counter,
+    batching: BatchingStats,
+    program_cache: ProgramCacheStats,
+    output_format: OutputFormat,
+    frame_stats_sum: FrameStats,
+    frame_stats_peak: FrameStats,
+    frame_stats_frames: usize,
     pass_names: Vec<String>,
     pass_samples: Vec<MonitorSample<Duration>>,
     last_send: std::time::Instant,
@@ -105,6 +194,24 @@ impl RendererMonitorTrait for RendererMonitor {
         self.render.start();
     }
 
+    fn set_batching(&mut self, stats: BatchingStats) {
+        self.batching = stats;
+    }
+
+    fn set_program_cache(&mut self, stats: ProgramCacheStats) {
+        self.program_cache = stats;
+    }
+
+    fn set_output_format(&mut self, format: OutputFormat) {
+        self.output_format = format;
+    }
+
+    fn set_frame_stats(&mut self, stats: FrameStats) {
+        self.frame_stats_sum.add(&stats);
+        self.frame_stats_peak.max(&stats);
+        self.frame_stats_frames += 1;
+    }
+
     fn render_stop(&mut self, result: RenderResult, passes: &[Duration; MAX_RENDER_PASSES]) {
         self.render.stop();
 
@@ -140,7 +247,7 @@ impl RendererMonitorTrait for RendererMonitor {
             if let Some(sender) = &self.sender {
                 let mut passes = HashMap::with_capacity(self.pass_names.len());
                 for (i, name) in self.pass_names.iter().enumerate() {
-                    passes.insert(name.clone(), self.pass_samples[i].clone());
+                    passes.insert(name.clone(), self.pass_samples[i]);
                 }
 
                 let frame = RendererMonitorEvent {
@@ -151,13 +258,21 @@ impl RendererMonitorTrait for RendererMonitor {
                     passes,
                     drawn_primitives: self.drawn_primitives.get(),
                     draw_calls: self.draw_calls.get(),
+                    frame_stats: self.frame_stats_sum.divide(self.frame_stats_frames),
+                    peak_frame_stats: self.frame_stats_peak,
+                    static_batching: self.batching,
+                    program_cache: self.program_cache,
+                    output_format: self.output_format,
                 };
 
                 sender.send(frame).unwrap();
             }
+            self.frame_stats_sum = FrameStats::default();
+            self.frame_stats_peak = FrameStats::default();
+            self.frame_stats_frames = 0;
 
             // Reset the counters each 5 seconds to get more smooth data
-            if self.counter % 5 == 0 {
+            if self.counter.is_multiple_of(5) {
                 self.fps.reset();
                 self.view.reset();
                 self.events.reset();
@@ -178,6 +293,12 @@ impl RendererMonitor {
             render: Stopwatch::new(0.5),
             draw_calls: Counter::new(Duration::from_secs(1), 0.5),
             drawn_primitives: Counter::new(Duration::from_secs(1), 0.5),
+            batching: BatchingStats::default(),
+            program_cache: ProgramCacheStats::default(),
+            output_format: OutputFormat::default(),
+            frame_stats_sum: FrameStats::default(),
+            frame_stats_peak: FrameStats::default(),
+            frame_stats_frames: 0,
             pass_names: Vec::with_capacity(MAX_RENDER_PASSES),
             pass_samples: Vec::with_capacity(MAX_RENDER_PASSES),
             last_send: std::time::Instant::now(),
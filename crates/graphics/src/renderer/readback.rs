@@ -0,0 +1,130 @@
+use crate::renderer::resource::GpuTexture;
+use crate::renderer::target::RenderTargetId;
+use dawn_assets::TypedAsset;
+use evenio::event::GlobalEvent;
+
+/// What the data is read from.
+#[derive(Debug, Clone)]
+pub enum ReadbackSource {
+    /// Color attachment of the off-screen render target.
+    RenderTarget {
+        target: RenderTargetId,
+        attachment: usize,
+    },
+    /// 2D texture asset. For the textures packed into the arrays,
+    /// the layer of the texture is read.
+    Texture(TypedAsset<GpuTexture>),
+}
+
+/// Layout of the data delivered by the readback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReadbackFormat {
+    /// RGBA, 8 bits per channel.
+    Rgba8,
+    /// RGBA, 32-bit float per channel. Use it for the HDR targets.
+    RgbaF32,
+    /// Red channel only, 32-bit float. E.g. the depth or the object IDs.
+    RF32,
+    /// Bytes of the buffer as they are. Used by the buffer readbacks
+    /// (see `RendererBackend::read_buffer`), not valid for the textures.
+    Raw,
+}
+
+impl ReadbackFormat {
+    /// Size of the pixel in bytes. 1 for `Raw`.
+    pub fn pixel_size(&self) -> usize {
+        match self {
+            ReadbackFormat::Rgba8 => 4,
+            ReadbackFormat::RgbaF32 => 16,
+            ReadbackFormat::RF32 => 4,
+            ReadbackFormat::Raw => 1,
+        }
+    }
+}
+
+/// Part of the source to read, in pixels. The origin is the top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ReadbackRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Requests reading the data back from the GPU. Sent from the ECS and
+/// processed by the renderer thread after the frame is rendered.
+/// The read does not stall the renderer: the copy is queued after the
+/// frame and fenced, and the result is delivered with the `ReadbackEvent`
+/// once the GPU has finished it, usually one or two frames later.
+#[derive(GlobalEvent, Debug, Clone)]
+pub struct ReadbackRequest {
+    /// Arbitrary value copied into the result, to tell the requests apart.
+    /// Sending the request with the tag of the persistent one replaces it.
+    pub tag: u64,
+    pub source: ReadbackSource,
+    /// Part of the source to read. `None` reads the whole of it,
+    /// following the size of the render target if it is resized.
+    pub rect: Option<ReadbackRect>,
+    pub format: ReadbackFormat,
+    /// Read after every frame until cancelled with the `ReadbackCancel`.
+    /// At most two reads of the request are in flight, so the renderer
+    /// never waits for the GPU: the frames are skipped instead.
+    pub persistent: bool,
+}
+
+/// Stops the persistent readback. The results still in flight are dropped.
+#[derive(GlobalEvent, Debug, Clone, Copy)]
+pub struct ReadbackCancel {
+    pub tag: u64,
+}
+
+/// Data read back from the GPU.
+#[derive(Debug, Clone)]
+pub struct ReadbackData {
+    pub tag: u64,
+    /// Renderer frame the data was read after.
+    pub frame: usize,
+    /// Size in pixels. For the buffers, the width is the size in bytes and the height is 1.
+    pub width: usize,
+    pub height: usize,
+    pub format: ReadbackFormat,
+    /// Pixels, top row first, tightly packed.
+    pub data: Vec<u8>,
+}
+
+impl ReadbackData {
+    /// Bytes of the pixel, `None` if it is outside the data.
+    pub fn pixel(&self, x: usize, y: usize) -> Option<&[u8]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let size = self.format.pixel_size();
+        let start = (y * self.width + x) * size;
+        self.data.get(start..start + size)
+    }
+
+    /// Data as the floats, for the `RgbaF32` and `RF32` formats.
+    pub fn to_f32(&self) -> Vec<f32> {
+        self.data
+            .chunks_exact(4)
+            .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect()
+    }
+}
+
+/// Result of the `ReadbackRequest` or the buffer readback.
+#[derive(GlobalEvent, Debug, Clone)]
+pub enum ReadbackEvent {
+    Ready(ReadbackData),
+    /// The source does not exist (anymore), or cannot be read in the format.
+    /// The persistent readback is stopped.
+    Failed {
+        tag: u64,
+        reason: String,
+    },
+}
+
+pub(crate) enum ReadbackCommand {
+    Request(ReadbackRequest),
+    Cancel(u64),
+}
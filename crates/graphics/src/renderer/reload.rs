@@ -0,0 +1,29 @@
+use crate::renderer::resource::{GpuHandle, GpuShader};
+use dawn_assets::ir::shader::IRShader;
+use dawn_assets::TypedAsset;
+use evenio::event::GlobalEvent;
+
+/// Recompiles the shader asset from the new sources, e.g. when the shader
+/// file is edited. Sent from the ECS and processed by the renderer thread
+/// between the frames. The result is reported with the `ShaderReloadEvent`.
+#[derive(GlobalEvent, Debug, Clone)]
+pub struct ShaderReloadRequest {
+    pub shader: TypedAsset<GpuShader>,
+    pub ir: IRShader,
+}
+
+/// Result of the `ShaderReloadRequest`.
+#[derive(GlobalEvent, Debug, Clone)]
+pub enum ShaderReloadEvent {
+    /// The new program replaced the previous one behind the handle.
+    /// The passes were notified with `RenderPass::on_shader_reload`.
+    Reloaded(GpuHandle<GpuShader>),
+    /// The new sources failed to compile or link.
+    /// The previous program is still used, and the error banner is shown
+    /// until the shader is reloaded successfully.
+    Failed {
+        shader: GpuHandle<GpuShader>,
+        /// Compiler or linker log.
+        log: String,
+    },
+}
@@ -0,0 +1,187 @@
+use dawn_assets::ir::texture::{IRPixelFormat, IRTextureType};
+use dawn_assets::AssetCastable;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Opaque identifier of a GPU resource owned by the renderer backend.
+/// The handle itself is backend-agnostic: it's resolved into the actual
+/// backend object (e.g. `gl::raii::texture::Texture`) only by the active backend,
+/// so the assets and the code storing them do not depend on the backend.
+pub struct GpuHandle<T> {
+    id: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> GpuHandle<T> {
+    fn new() -> Self {
+        // Shared between all the resource kinds, so the handles are never reused
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+        GpuHandle {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// Derives would require the bounds on the marker type
+impl<T> Clone for GpuHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for GpuHandle<T> {}
+
+impl<T> PartialEq for GpuHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<T> Eq for GpuHandle<T> {}
+
+impl<T> Hash for GpuHandle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T> Debug for GpuHandle<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GpuHandle({})", self.id)
+    }
+}
+
+impl<T> Display for GpuHandle<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GpuHandle({})", self.id)
+    }
+}
+
+/// Texture asset. Holds the handle of the texture uploaded to the GPU
+/// and its description. Use `RendererBackend::texture` to get the backend object.
+/// The 2D textures may be packed into the texture arrays on load:
+/// then the handle addresses the array shared with other textures,
+/// and `layer` is the index of the texture in it.
+#[derive(Debug)]
+pub struct GpuTexture {
+    handle: GpuHandle<GpuTexture>,
+    texture_type: IRTextureType,
+    pixel_format: IRPixelFormat,
+    layer: Option<u32>,
+}
+
+impl AssetCastable for GpuTexture {}
+
+impl GpuTexture {
+    pub(crate) fn new(
+        handle: GpuHandle<GpuTexture>,
+        texture_type: IRTextureType,
+        pixel_format: IRPixelFormat,
+    ) -> Self {
+        GpuTexture {
+            handle,
+            texture_type,
+            pixel_format,
+            layer: None,
+        }
+    }
+
+    pub(crate) fn with_layer(mut self, layer: u32) -> Self {
+        self.layer = Some(layer);
+        self
+    }
+
+    #[inline(always)]
+    pub fn handle(&self) -> GpuHandle<GpuTexture> {
+        self.handle
+    }
+
+    #[inline(always)]
+    pub fn texture_type(&self) -> IRTextureType {
+        self.texture_type
+    }
+
+    #[inline(always)]
+    pub fn pixel_format(&self) -> IRPixelFormat {
+        self.pixel_format
+    }
+
+    /// Layer of the texture array the texture is packed into.
+    /// `None` if the texture has its own backend object.
+    #[inline(always)]
+    pub fn layer(&self) -> Option<u32> {
+        self.layer
+    }
+}
+
+/// Shader asset. Holds the handle of the linked shader program.
+/// Use `RendererBackend::shader` to get the backend object.
+#[derive(Debug)]
+pub struct GpuShader {
+    handle: GpuHandle<GpuShader>,
+}
+
+impl AssetCastable for GpuShader {}
+
+impl GpuShader {
+    pub(crate) fn new(handle: GpuHandle<GpuShader>) -> Self {
+        GpuShader { handle }
+    }
+
+    #[inline(always)]
+    pub fn handle(&self) -> GpuHandle<GpuShader> {
+        self.handle
+    }
+}
+
+/// Backend objects addressed by the handles.
+/// `K` is the kind of the handle (e.g. `GpuTexture`), `T` is the backend object.
+pub(crate) struct ResourceTable<K, T> {
+    resources: HashMap<GpuHandle<K>, T>,
+}
+
+impl<K, T> Default for ResourceTable<K, T> {
+    fn default() -> Self {
+        ResourceTable {
+            resources: HashMap::new(),
+        }
+    }
+}
+
+impl<K, T> ResourceTable<K, T> {
+    pub fn insert(&mut self, resource: T) -> GpuHandle<K> {
+        let handle = GpuHandle::new();
+        self.resources.insert(handle, resource);
+        handle
+    }
+
+    #[inline(always)]
+    pub fn get(&self, handle: GpuHandle<K>) -> Option<&T> {
+        self.resources.get(&handle)
+    }
+
+    pub fn remove(&mut self, handle: GpuHandle<K>) -> Option<T> {
+        self.resources.remove(&handle)
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.resources.values()
+    }
+
+    /// Replaces the object behind the handle, returning the previous one.
+    /// Does nothing if the handle is not in the table.
+    pub fn replace(&mut self, handle: GpuHandle<K>, resource: T) -> Option<T> {
+        self.resources
+            .get_mut(&handle)
+            .map(|old| std::mem::replace(old, resource))
+    }
+}
@@ -0,0 +1,76 @@
+use dawn_assets::factory::{BasicFactory, FactoryBinding};
+use dawn_assets::ir::settings::IRSettings;
+use dawn_assets::ir::IRAsset;
+use dawn_assets::{AssetCastable, AssetMemoryUsage, AssetType, TypedAsset};
+use dawn_ecs::events::TickEvent;
+use evenio::component::Component;
+use evenio::event::{GlobalEvent, Receiver};
+use evenio::fetch::Single;
+use evenio::world::World;
+use std::time::Duration;
+
+/// Preset of the render settings, e.g. the shadow resolution, the bloom
+/// and fog parameters or the tonemap curve of the level.
+/// What the keys mean is up to the passes reading them.
+#[derive(Debug)]
+pub struct RenderSettings(pub IRSettings);
+
+impl AssetCastable for RenderSettings {}
+
+/// Loads the settings assets in the ECS, so they can be sent
+/// to the renderer with the `RenderSettingsRequest`.
+#[derive(Component)]
+pub struct RenderSettingsFactory {
+    basic_factory: BasicFactory<RenderSettings>,
+}
+
+impl Default for RenderSettingsFactory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenderSettingsFactory {
+    pub fn new() -> Self {
+        RenderSettingsFactory {
+            basic_factory: BasicFactory::new(),
+        }
+    }
+
+    pub fn bind(&mut self, binding: FactoryBinding) {
+        assert_eq!(binding.asset_type(), AssetType::Settings);
+        self.basic_factory.bind(binding);
+    }
+
+    pub fn process_events(&mut self) {
+        self.basic_factory.process_events(
+            |message| {
+                if let IRAsset::Settings(data) = message.ir {
+                    let size = data.memory_usage();
+                    Ok((RenderSettings(data), AssetMemoryUsage::new(size, 0)))
+                } else {
+                    Err(anyhow::anyhow!("Expected settings metadata"))
+                }
+            },
+            |_| {},
+            Duration::ZERO,
+        );
+    }
+
+    pub fn attach_to_ecs(&mut self, world: &mut World) {
+        fn handler(_: Receiver<TickEvent>, mut factory: Single<&mut RenderSettingsFactory>) {
+            factory.process_events();
+        }
+
+        world.add_handler(handler);
+    }
+}
+
+/// Applies the preset to all the passes of the pipeline with
+/// `RenderPass::on_settings`. Sent from the ECS and processed by the renderer
+/// thread between the frames, so switching the presets (per level, or for
+/// comparing the visual configurations) needs no recompilation.
+#[derive(GlobalEvent, Debug, Clone)]
+pub struct RenderSettingsRequest {
+    pub settings: TypedAsset<RenderSettings>,
+}
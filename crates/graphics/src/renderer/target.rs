@@ -0,0 +1,179 @@
+/// Describes how the size of a render target follows the size of the view.
+/// Render targets are recreated by the backend every time the view is resized,
+/// unless the policy results in the same size as before.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ResizePolicy {
+    /// The target always has the same size as the view.
+    #[default]
+    MatchWindow,
+    /// The target has a fixed size and is never recreated on resize.
+    Fixed { width: usize, height: usize },
+    /// The target is the size of the view multiplied by the factor.
+    /// Useful for down-sampled post-processing or supersampling.
+    Scaled(f32),
+}
+
+impl ResizePolicy {
+    /// Calculates the size of the target for the given view size.
+    /// The result is never smaller than 1x1.
+    pub fn resolve(&self, view_width: usize, view_height: usize) -> (usize, usize) {
+        let (width, height) = match *self {
+            ResizePolicy::MatchWindow => (view_width, view_height),
+            ResizePolicy::Fixed { width, height } => (width, height),
+            ResizePolicy::Scaled(factor) => (
+                (view_width as f32 * factor).round() as usize,
+                (view_height as f32 * factor).round() as usize,
+            ),
+        };
+
+        (width.max(1), height.max(1))
+    }
+}
+
+/// Unique identifier of a render target owned by the renderer backend.
+/// Render passes should keep the ID instead of the target itself,
+/// since the underlying resources may be recreated on resize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RenderTargetId(usize);
+
+impl std::fmt::Display for RenderTargetId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "RenderTargetId({})", self.0)
+    }
+}
+
+impl RenderTargetId {
+    pub(crate) fn new() -> Self {
+        static NEXT_ID: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(1);
+        let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        RenderTargetId(id)
+    }
+}
+
+/// Part of the view the content is rendered to, in pixels.
+/// The origin is the top-left corner of the view, same as for the mouse events.
+/// With a fixed aspect ratio the rest of the view is covered by the bars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ContentRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl ContentRect {
+    /// Calculates the largest rectangle of the given aspect ratio (width / height)
+    /// centered in the view. Without the aspect ratio, the content covers the whole view.
+    /// The result is never smaller than 1x1.
+    pub fn fit(view_width: usize, view_height: usize, aspect_ratio: Option<f32>) -> Self {
+        let view_width = view_width.max(1);
+        let view_height = view_height.max(1);
+        let Some(aspect_ratio) = aspect_ratio.filter(|r| r.is_finite() && *r > 0.0) else {
+            return ContentRect {
+                x: 0,
+                y: 0,
+                width: view_width,
+                height: view_height,
+            };
+        };
+
+        let (width, height) = if view_width as f32 / view_height as f32 > aspect_ratio {
+            // View is wider than the content: pillarbox
+            let width = (view_height as f32 * aspect_ratio).round() as usize;
+            (width.clamp(1, view_width), view_height)
+        } else {
+            // View is taller than the content: letterbox
+            let height = (view_width as f32 / aspect_ratio).round() as usize;
+            (view_width, height.clamp(1, view_height))
+        };
+
+        ContentRect {
+            x: (view_width - width) / 2,
+            y: (view_height - height) / 2,
+            width,
+            height,
+        }
+    }
+
+    /// Transforms the point of the view (e.g. the mouse position)
+    /// into the content space, where `(0, 0)` is the top-left corner of the content.
+    /// Returns `None` if the point is on the bars.
+    pub fn content_point(&self, x: f32, y: f32) -> Option<(f32, f32)> {
+        let x = x - self.x as f32;
+        let y = y - self.y as f32;
+        if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {
+            return None;
+        }
+        Some((x, y))
+    }
+
+    /// Same as `content_point`, but the result is normalized to `[0, 1)`.
+    pub fn normalize_point(&self, x: f32, y: f32) -> Option<(f32, f32)> {
+        self.content_point(x, y)
+            .map(|(x, y)| (x / self.width as f32, y / self.height as f32))
+    }
+
+    /// Rectangles of the view not covered by the content, in the same coordinates.
+    /// Empty if the content covers the whole view.
+    pub fn bars(&self, view_width: usize, view_height: usize) -> Vec<ContentRect> {
+        let mut bars = Vec::new();
+        let mut push = |x, y, width, height| {
+            if width > 0 && height > 0 {
+                bars.push(ContentRect {
+                    x,
+                    y,
+                    width,
+                    height,
+                });
+            }
+        };
+        let right = self.x + self.width;
+        let bottom = self.y + self.height;
+        push(0, 0, view_width, self.y);
+        push(0, bottom, view_width, view_height.saturating_sub(bottom));
+        push(0, self.y, self.x, self.height);
+        push(right, self.y, view_width.saturating_sub(right), self.height);
+        bars
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn match_window_follows_view() {
+        assert_eq!(ResizePolicy::MatchWindow.resolve(1280, 720), (1280, 720));
+        assert_eq!(ResizePolicy::default().resolve(640, 480), (640, 480));
+    }
+
+    #[test]
+    fn fixed_ignores_view() {
+        let policy = ResizePolicy::Fixed {
+            width: 256,
+            height: 128,
+        };
+        assert_eq!(policy.resolve(1280, 720), (256, 128));
+        assert_eq!(policy.resolve(1, 1), (256, 128));
+    }
+
+    #[test]
+    fn scaled_rounds_to_nearest() {
+        assert_eq!(ResizePolicy::Scaled(0.5).resolve(1280, 720), (640, 360));
+        assert_eq!(ResizePolicy::Scaled(0.5).resolve(1281, 719), (641, 360));
+        assert_eq!(ResizePolicy::Scaled(2.0).resolve(100, 50), (200, 100));
+        assert_eq!(ResizePolicy::Scaled(1.0 / 3.0).resolve(100, 100), (33, 33));
+    }
+
+    #[test]
+    fn never_smaller_than_one_pixel() {
+        assert_eq!(ResizePolicy::MatchWindow.resolve(0, 0), (1, 1));
+        assert_eq!(ResizePolicy::Scaled(0.1).resolve(4, 4), (1, 1));
+        assert_eq!(ResizePolicy::Scaled(0.0).resolve(1280, 720), (1, 1));
+        let fixed = ResizePolicy::Fixed {
+            width: 0,
+            height: 16,
+        };
+        assert_eq!(fixed.resolve(1280, 720), (1, 16));
+    }
+}
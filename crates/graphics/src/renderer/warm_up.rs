@@ -0,0 +1,26 @@
+use evenio::event::GlobalEvent;
+use std::time::Duration;
+
+/// Requests warming up the shader programs, so the driver does not finish
+/// compiling them on the first use in the middle of the gameplay.
+/// Send it once the assets are loaded and the scene is spawned: the programs
+/// are warmed up with the vertex formats of the meshes being rendered.
+/// Processed by the renderer thread after the next frame, the progress
+/// is reported with the `WarmUpEvent` events.
+#[derive(GlobalEvent, Debug, Clone, Copy, Default)]
+pub struct WarmUpRequest;
+
+#[derive(GlobalEvent, Debug, Clone, Copy, PartialEq)]
+pub enum WarmUpEvent {
+    /// Sent after each warmed up shader program. Useful for the loading screens.
+    Progress { done: usize, total: usize },
+    /// The warm-up is complete.
+    Finished {
+        /// Number of the program and vertex format pairs drawn.
+        permutations: usize,
+        duration: Duration,
+    },
+    /// The off-screen target for the warm-up could not be created.
+    /// Nothing was warmed up.
+    Failed,
+}
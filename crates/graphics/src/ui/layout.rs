@@ -0,0 +1,173 @@
+use glam::Vec2;
+
+/// Axis-aligned rectangle in the content space, in pixels.
+/// The origin is the top-left corner of the content, Y goes down.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Rect {
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Rect { min, max }
+    }
+
+    pub fn from_size(min: Vec2, size: Vec2) -> Self {
+        Rect {
+            min,
+            max: min + size,
+        }
+    }
+
+    #[inline(always)]
+    pub fn size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    #[inline(always)]
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.cmpge(self.min).all() && point.cmplt(self.max).all()
+    }
+
+    /// Shrinks the rectangle by the padding. Never turns it inside out.
+    pub fn shrink(&self, padding: &Padding) -> Rect {
+        let min = self.min + Vec2::new(padding.left, padding.top);
+        let max = self.max - Vec2::new(padding.right, padding.bottom);
+        Rect {
+            min,
+            max: max.max(min),
+        }
+    }
+}
+
+/// Distances from the edges of the container to its children.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Padding {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl Padding {
+    pub fn all(value: f32) -> Self {
+        Padding {
+            left: value,
+            top: value,
+            right: value,
+            bottom: value,
+        }
+    }
+}
+
+/// Places the widget relative to its parent.
+/// The anchors are the fractions of the parent rectangle, `(0, 0)` is its top-left corner
+/// and `(1, 1)` is the bottom-right one. If the anchors differ, the widget stretches
+/// with the parent by the span between them, and `size` is added to that span.
+/// The pivot is the point of the widget (in the fractions of its own size)
+/// placed at the anchor point moved by `offset`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RectTransform {
+    pub anchor_min: Vec2,
+    pub anchor_max: Vec2,
+    pub pivot: Vec2,
+    pub offset: Vec2,
+    pub size: Vec2,
+}
+
+impl Default for RectTransform {
+    /// Fills the parent.
+    fn default() -> Self {
+        RectTransform::stretch()
+    }
+}
+
+impl RectTransform {
+    /// Widget of the fixed size with its pivot at the anchor point.
+    /// E.g. `anchored(Vec2::new(0.5, 1.0), Vec2::new(0.5, 1.0), ..)` sticks
+    /// to the middle of the bottom edge of the parent.
+    pub fn anchored(anchor: Vec2, pivot: Vec2, offset: Vec2, size: Vec2) -> Self {
+        RectTransform {
+            anchor_min: anchor,
+            anchor_max: anchor,
+            pivot,
+            offset,
+            size,
+        }
+    }
+
+    /// Widget of the fixed size centered in the parent.
+    pub fn centered(size: Vec2) -> Self {
+        let center = Vec2::splat(0.5);
+        RectTransform::anchored(center, center, Vec2::ZERO, size)
+    }
+
+    /// Widget filling the parent.
+    pub fn stretch() -> Self {
+        RectTransform {
+            anchor_min: Vec2::ZERO,
+            anchor_max: Vec2::ONE,
+            pivot: Vec2::splat(0.5),
+            offset: Vec2::ZERO,
+            size: Vec2::ZERO,
+        }
+    }
+
+    /// Calculates the rectangle of the widget within the parent one.
+    pub fn resolve(&self, parent: Rect) -> Rect {
+        let parent_size = parent.size();
+        let anchor_min = parent.min + parent_size * self.anchor_min;
+        let anchor_max = parent.min + parent_size * self.anchor_max;
+        let size = ((anchor_max - anchor_min) + self.size).max(Vec2::ZERO);
+
+        let pivot = anchor_min + (anchor_max - anchor_min) * self.pivot + self.offset;
+        Rect::from_size(pivot - size * self.pivot, size)
+    }
+}
+
+/// How the container places its children.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Layout {
+    /// Each child is placed by its own transform within the padded rectangle.
+    #[default]
+    Free,
+    /// Children are stacked top to bottom, taking the full width.
+    /// The height of each child is the height of its transform `size`.
+    Vertical { spacing: f32 },
+    /// Children are stacked left to right, taking the full height.
+    /// The width of each child is the width of its transform `size`.
+    Horizontal { spacing: f32 },
+}
+
+impl Layout {
+    /// Calculates the rectangles of the children within the container.
+    pub fn arrange(&self, content: Rect, children: &[RectTransform], out: &mut Vec<Rect>) {
+        out.clear();
+        match *self {
+            Layout::Free => out.extend(children.iter().map(|child| child.resolve(content))),
+            Layout::Vertical { spacing } => {
+                let mut y = content.min.y;
+                for child in children {
+                    let height = child.size.y.max(0.0);
+                    out.push(Rect::new(
+                        Vec2::new(content.min.x, y),
+                        Vec2::new(content.max.x, y + height),
+                    ));
+                    y += height + spacing;
+                }
+            }
+            Layout::Horizontal { spacing } => {
+                let mut x = content.min.x;
+                for child in children {
+                    let width = child.size.x.max(0.0);
+                    out.push(Rect::new(
+                        Vec2::new(x, content.min.y),
+                        Vec2::new(x + width, content.max.y),
+                    ));
+                    x += width + spacing;
+                }
+            }
+        }
+    }
+}
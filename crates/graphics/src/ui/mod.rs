@@ -0,0 +1,452 @@
+pub mod layout;
+pub mod navigation;
+
+use crate::accessibility::AccessibilityChanged;
+use crate::gl::font::Font;
+use crate::input::{InputEvent, KeyCode, MouseButton};
+use crate::renderer::resource::GpuTexture;
+use crate::renderer::target::ContentRect;
+use crate::ui::layout::{Layout, Padding, Rect, RectTransform};
+use crate::ui::navigation::{NavDirection, UiNavigateEvent, VirtualCursor};
+use dawn_assets::TypedAsset;
+use dawn_ecs::events::TickEvent;
+use evenio::component::Component;
+use evenio::event::{GlobalEvent, Receiver, Sender};
+use evenio::fetch::Single;
+use evenio::handler::IntoHandler;
+use evenio::world::World;
+use glam::{Vec2, Vec4};
+use std::sync::Arc;
+
+/// Identifier of a widget within the `Ui`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WidgetId(usize);
+
+impl std::fmt::Display for WidgetId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WidgetId({})", self.0)
+    }
+}
+
+#[derive(Clone)]
+pub enum WidgetKind {
+    /// Invisible widget grouping its children.
+    Container { layout: Layout, padding: Padding },
+    /// Textured or plain colored rectangle. The color multiplies the texture.
+    Image {
+        texture: Option<TypedAsset<GpuTexture>>,
+        color: Vec4,
+    },
+    /// Single line of text starting at the top-left corner of the rectangle.
+    Label {
+        text: String,
+        font: TypedAsset<Font>,
+        size: f32,
+        color: Vec4,
+    },
+}
+
+pub struct Widget {
+    pub transform: RectTransform,
+    pub kind: WidgetKind,
+    /// Hidden widgets are not drawn and do not receive the input, with all their children.
+    pub visible: bool,
+    /// Only the interactive widgets receive the hover and click events.
+    pub interactive: bool,
+    parent: Option<WidgetId>,
+    children: Vec<WidgetId>,
+    rect: Rect,
+}
+
+impl Widget {
+    pub fn parent(&self) -> Option<WidgetId> {
+        self.parent
+    }
+
+    /// Children in the painting order, the last one is on top.
+    pub fn children(&self) -> &[WidgetId] {
+        &self.children
+    }
+}
+
+/// Sent by the `Ui` when the pointer interacts with the interactive widgets.
+#[derive(GlobalEvent, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UiEvent {
+    HoverEnter(WidgetId),
+    HoverLeave(WidgetId),
+    /// The left button was pressed over the widget.
+    Pressed(WidgetId),
+    /// The left button was pressed and released over the same widget.
+    Clicked(WidgetId),
+}
+
+/// Primitive of the UI frame in the content space, see `UiFrame`.
+#[derive(Clone)]
+pub enum UiDrawCommand {
+    Image {
+        rect: Rect,
+        texture: Option<TypedAsset<GpuTexture>>,
+        color: Vec4,
+    },
+    Label {
+        rect: Rect,
+        text: String,
+        font: TypedAsset<Font>,
+        size: f32,
+        color: Vec4,
+    },
+}
+
+/// Sent by the `Ui` each time the layout or the widgets change.
+/// The commands are in the painting order (parents before children).
+/// The UI does not render by itself: forward the frame to the sprite and text
+/// passes of the pipeline with the `RenderPassEvent`.
+#[derive(GlobalEvent, Clone)]
+pub struct UiFrame(pub Arc<Vec<UiDrawCommand>>);
+
+/// Minimal retained UI. The widgets form a tree with the root covering
+/// the whole content of the view. Layout is recalculated lazily
+/// when something changes, the hit-testing uses the last calculated layout.
+#[derive(Component)]
+pub struct Ui {
+    widgets: Vec<Option<Widget>>,
+    root: WidgetId,
+    dirty: bool,
+    hovered: Option<WidgetId>,
+    pressed: Option<WidgetId>,
+    cursor: Option<Vec2>,
+    content: ContentRect,
+    // The hovered widget is chosen by the navigation, not by the cursor
+    focus_mode: bool,
+    keyboard_navigation: bool,
+    virtual_cursor: Option<VirtualCursor>,
+    scale: f32,
+}
+
+impl Default for Ui {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ui {
+    pub fn new() -> Self {
+        let root = Widget {
+            transform: RectTransform::stretch(),
+            kind: WidgetKind::Container {
+                layout: Layout::Free,
+                padding: Padding::default(),
+            },
+            visible: true,
+            interactive: false,
+            parent: None,
+            children: vec![],
+            rect: Rect::default(),
+        };
+        Ui {
+            widgets: vec![Some(root)],
+            root: WidgetId(0),
+            dirty: true,
+            hovered: None,
+            pressed: None,
+            cursor: None,
+            content: ContentRect::default(),
+            focus_mode: false,
+            keyboard_navigation: false,
+            virtual_cursor: None,
+            scale: 1.0,
+        }
+    }
+
+    /// The container covering the whole content.
+    pub fn root(&self) -> WidgetId {
+        self.root
+    }
+
+    /// Adds the widget as the last (topmost) child of the parent.
+    pub fn add(
+        &mut self,
+        parent: WidgetId,
+        transform: RectTransform,
+        kind: WidgetKind,
+    ) -> WidgetId {
+        let id = WidgetId(self.widgets.len());
+        self.widgets.push(Some(Widget {
+            transform,
+            kind,
+            visible: true,
+            interactive: false,
+            parent: Some(parent),
+            children: vec![],
+            rect: Rect::default(),
+        }));
+        if let Some(parent) = self.widget_mut(parent) {
+            parent.children.push(id);
+        }
+        id
+    }
+
+    /// Removes the widget with all its children. The root cannot be removed.
+    pub fn remove(&mut self, id: WidgetId) {
+        if id == self.root {
+            return;
+        }
+        let Some(widget) = self.widgets.get_mut(id.0).and_then(Option::take) else {
+            return;
+        };
+        if let Some(parent) = widget.parent.and_then(|p| self.widget_mut(p)) {
+            parent.children.retain(|child| *child != id);
+        }
+        for child in widget.children {
+            self.remove(child);
+        }
+        if self.hovered == Some(id) {
+            self.hovered = None;
+        }
+        if self.pressed == Some(id) {
+            self.pressed = None;
+        }
+        self.dirty = true;
+    }
+
+    pub fn widget(&self, id: WidgetId) -> Option<&Widget> {
+        self.widgets.get(id.0).and_then(Option::as_ref)
+    }
+
+    /// Marks the layout as changed, so the frame is rebuilt on the next tick.
+    pub fn widget_mut(&mut self, id: WidgetId) -> Option<&mut Widget> {
+        self.dirty = true;
+        self.widgets.get_mut(id.0).and_then(Option::as_mut)
+    }
+
+    pub fn set_text(&mut self, id: WidgetId, new_text: &str) {
+        if let Some(Widget {
+            kind: WidgetKind::Label { text, .. },
+            ..
+        }) = self.widget_mut(id)
+        {
+            *text = new_text.to_string();
+        }
+    }
+
+    /// Multiplies the sizes and the offsets of the widgets and the text sizes,
+    /// so the UI covers the same content with the bigger widgets.
+    /// Follows the `AccessibilitySettings::ui_scale` when attached to the ECS.
+    pub fn set_scale(&mut self, scale: f32) {
+        if scale != self.scale {
+            self.scale = scale;
+            self.dirty = true;
+        }
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Rectangle of the widget calculated by the last layout.
+    pub fn rect(&self, id: WidgetId) -> Option<Rect> {
+        self.widget(id).map(|widget| widget.rect)
+    }
+
+    /// Recalculates the rectangles of all the widgets.
+    pub fn layout(&mut self) {
+        // The layout is calculated unscaled in the content shrunk by the scale
+        let size = Vec2::new(self.content.width as f32, self.content.height as f32) / self.scale;
+        let root = self.root;
+        let rect = self.widgets[root.0]
+            .as_ref()
+            .unwrap()
+            .transform
+            .resolve(Rect::from_size(Vec2::ZERO, size));
+        self.layout_widget(root, rect);
+    }
+
+    fn layout_widget(&mut self, id: WidgetId, rect: Rect) {
+        let Some(widget) = self.widgets[id.0].as_mut() else {
+            return;
+        };
+        widget.rect = Rect::new(rect.min * self.scale, rect.max * self.scale);
+        let (layout, padding) = match &widget.kind {
+            WidgetKind::Container { layout, padding } => (*layout, *padding),
+            _ => (Layout::Free, Padding::default()),
+        };
+
+        let children = widget.children.clone();
+        let transforms: Vec<RectTransform> = children
+            .iter()
+            .map(|child| self.widgets[child.0].as_ref().unwrap().transform)
+            .collect();
+        let mut rects = Vec::with_capacity(children.len());
+        layout.arrange(rect.shrink(&padding), &transforms, &mut rects);
+        for (child, rect) in children.into_iter().zip(rects) {
+            self.layout_widget(child, rect);
+        }
+    }
+
+    /// Returns the topmost visible interactive widget under the point of the content.
+    pub fn hit_test(&self, point: Vec2) -> Option<WidgetId> {
+        self.hit_test_widget(self.root, point)
+    }
+
+    fn hit_test_widget(&self, id: WidgetId, point: Vec2) -> Option<WidgetId> {
+        let widget = self.widget(id).filter(|widget| widget.visible)?;
+        // Children are painted over the parent, the last one on top
+        for child in widget.children.iter().rev() {
+            if let Some(hit) = self.hit_test_widget(*child, point) {
+                return Some(hit);
+            }
+        }
+        (widget.interactive && widget.rect.contains(point)).then_some(id)
+    }
+
+    /// Collects the draw commands of the visible widgets in the painting order.
+    pub fn draw_list(&self) -> Vec<UiDrawCommand> {
+        let mut commands = Vec::new();
+        self.draw_widget(self.root, &mut commands);
+        if let Some(command) = self.virtual_cursor_command() {
+            commands.push(command);
+        }
+        commands
+    }
+
+    fn draw_widget(&self, id: WidgetId, commands: &mut Vec<UiDrawCommand>) {
+        let Some(widget) = self.widget(id).filter(|widget| widget.visible) else {
+            return;
+        };
+        match &widget.kind {
+            WidgetKind::Container { .. } => {}
+            WidgetKind::Image { texture, color } => commands.push(UiDrawCommand::Image {
+                rect: widget.rect,
+                texture: texture.clone(),
+                color: *color,
+            }),
+            WidgetKind::Label {
+                text,
+                font,
+                size,
+                color,
+            } => commands.push(UiDrawCommand::Label {
+                rect: widget.rect,
+                text: text.clone(),
+                font: font.clone(),
+                size: *size * self.scale,
+                color: *color,
+            }),
+        }
+        for child in &widget.children {
+            self.draw_widget(*child, commands);
+        }
+    }
+
+    fn set_hovered(&mut self, hovered: Option<WidgetId>, events: &mut Vec<UiEvent>) {
+        if hovered != self.hovered {
+            if let Some(old) = self.hovered {
+                events.push(UiEvent::HoverLeave(old));
+            }
+            if let Some(new) = hovered {
+                events.push(UiEvent::HoverEnter(new));
+            }
+            self.hovered = hovered;
+        }
+    }
+
+    /// Updates the hover state for the cursor in the view pixels.
+    fn update_hover(&mut self, events: &mut Vec<UiEvent>) {
+        let hovered = self
+            .cursor
+            .and_then(|cursor| self.content.content_point(cursor.x, cursor.y))
+            .and_then(|(x, y)| self.hit_test(Vec2::new(x, y)));
+        self.set_hovered(hovered, events);
+    }
+
+    fn handle_input(&mut self, event: &InputEvent) -> Vec<UiEvent> {
+        let mut events = Vec::new();
+        match event {
+            InputEvent::MouseMove { x, y } => {
+                self.cursor = Some(Vec2::new(*x, *y));
+                self.focus_mode = false;
+                self.update_hover(&mut events);
+            }
+            InputEvent::ContentResize(rect) => {
+                self.content = *rect;
+                self.dirty = true;
+            }
+            InputEvent::MouseButtonPress(MouseButton::Left) => {
+                self.pressed = self.hovered;
+                if let Some(id) = self.pressed {
+                    events.push(UiEvent::Pressed(id));
+                }
+            }
+            InputEvent::MouseButtonRelease(MouseButton::Left) => {
+                if let Some(id) = self.pressed.take() {
+                    if self.hovered == Some(id) {
+                        events.push(UiEvent::Clicked(id));
+                    }
+                }
+            }
+            InputEvent::KeyPress(key) if self.keyboard_navigation => {
+                let event = match key {
+                    KeyCode::Up => UiNavigateEvent::Move(NavDirection::Up),
+                    KeyCode::Down => UiNavigateEvent::Move(NavDirection::Down),
+                    KeyCode::Left => UiNavigateEvent::Move(NavDirection::Left),
+                    KeyCode::Right => UiNavigateEvent::Move(NavDirection::Right),
+                    KeyCode::Return => UiNavigateEvent::Submit,
+                    _ => return events,
+                };
+                self.navigate(&event, &mut events);
+            }
+            _ => {}
+        }
+        events
+    }
+
+    /// Moves the UI into the ECS world.
+    /// The input is taken from the `InputEvent` events, the changes are reported
+    /// with the `UiEvent` and `UiFrame` events. Modify the widgets through the
+    /// `Single<&mut Ui>` in the handlers.
+    /// Requires the renderer attached to the same world.
+    pub fn attach_to_ecs(self, world: &mut World) {
+        let entity = world.spawn();
+        world.insert(entity, self);
+        world.add_handler(Self::input_handler);
+        world.add_handler(Self::navigate_handler);
+        world.add_handler(Self::accessibility_handler);
+        world.add_handler(Self::tick_handler.low());
+    }
+
+    fn input_handler(
+        r: Receiver<InputEvent>,
+        mut ui: Single<&mut Ui>,
+        mut sender: Sender<UiEvent>,
+    ) {
+        for event in ui.handle_input(r.event) {
+            sender.send(event);
+        }
+    }
+
+    fn accessibility_handler(r: Receiver<AccessibilityChanged>, mut ui: Single<&mut Ui>) {
+        ui.set_scale(r.event.0.ui_scale);
+    }
+
+    fn tick_handler(
+        _: Receiver<TickEvent>,
+        mut ui: Single<&mut Ui>,
+        mut sender: Sender<(UiEvent, UiFrame)>,
+    ) {
+        if !ui.dirty {
+            return;
+        }
+        ui.dirty = false;
+        ui.layout();
+
+        // Widgets may have moved under the cursor
+        let mut events = Vec::new();
+        if !ui.focus_mode {
+            ui.update_hover(&mut events);
+        }
+        for event in events {
+            sender.send(event);
+        }
+        sender.send(UiFrame(Arc::new(ui.draw_list())));
+    }
+}
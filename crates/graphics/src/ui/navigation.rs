@@ -0,0 +1,164 @@
+use crate::renderer::resource::GpuTexture;
+use crate::ui::layout::Rect;
+use crate::ui::{Ui, UiDrawCommand, UiEvent, WidgetId};
+use dawn_assets::TypedAsset;
+use evenio::event::{GlobalEvent, Receiver, Sender};
+use evenio::fetch::Single;
+use glam::{Vec2, Vec4};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl NavDirection {
+    fn vector(&self) -> Vec2 {
+        // Y goes down in the content space
+        match self {
+            NavDirection::Up => Vec2::NEG_Y,
+            NavDirection::Down => Vec2::Y,
+            NavDirection::Left => Vec2::NEG_X,
+            NavDirection::Right => Vec2::X,
+        }
+    }
+}
+
+/// Drives the UI without the mouse. Send it from the handlers of the device
+/// used for the menus (e.g. map the d-pad or the stick flicks to `Move`,
+/// the stick deflection to `MoveCursor` and the confirm button to `Submit`).
+/// Arrow keys and Return are mapped by the `Ui` itself when the keyboard
+/// navigation is enabled.
+#[derive(GlobalEvent, Debug, Clone, Copy, PartialEq)]
+pub enum UiNavigateEvent {
+    /// Moves the focus to the nearest interactive widget in the direction.
+    /// The focused widget is reported as hovered.
+    Move(NavDirection),
+    /// Presses and clicks the focused (or hovered by the virtual cursor) widget.
+    Submit,
+    /// Moves the virtual cursor by the delta in the content pixels.
+    /// Ignored if the virtual cursor is disabled.
+    MoveCursor(Vec2),
+}
+
+/// Cursor drawn by the UI itself and moved with `UiNavigateEvent::MoveCursor`.
+/// Hovers the widgets the same way the mouse does.
+#[derive(Clone)]
+pub struct VirtualCursor {
+    /// Position of the hotspot in the content space.
+    pub position: Vec2,
+    pub size: Vec2,
+    /// Point of the image at the position, in the fractions of its size.
+    pub hotspot: Vec2,
+    pub texture: Option<TypedAsset<GpuTexture>>,
+    pub color: Vec4,
+}
+
+impl Ui {
+    /// Enables mapping the arrow keys and Return to the `UiNavigateEvent`.
+    /// Disabled by default, since the game usually uses the arrows itself.
+    pub fn set_keyboard_navigation(&mut self, enabled: bool) {
+        self.keyboard_navigation = enabled;
+    }
+
+    /// Shows the virtual cursor, or hides it with `None`.
+    pub fn set_virtual_cursor(&mut self, cursor: Option<VirtualCursor>) {
+        self.virtual_cursor = cursor;
+        self.dirty = true;
+    }
+
+    /// The widget hovered by the cursor or focused by the navigation.
+    pub fn hovered(&self) -> Option<WidgetId> {
+        self.hovered
+    }
+
+    pub(super) fn virtual_cursor_command(&self) -> Option<UiDrawCommand> {
+        let cursor = self.virtual_cursor.as_ref()?;
+        Some(UiDrawCommand::Image {
+            rect: Rect::from_size(cursor.position - cursor.size * cursor.hotspot, cursor.size),
+            texture: cursor.texture.clone(),
+            color: cursor.color,
+        })
+    }
+
+    fn collect_interactive(&self, id: WidgetId, out: &mut Vec<(WidgetId, Rect)>) {
+        let Some(widget) = self.widget(id).filter(|widget| widget.visible) else {
+            return;
+        };
+        if widget.interactive {
+            out.push((id, widget.rect));
+        }
+        for child in &widget.children {
+            self.collect_interactive(*child, out);
+        }
+    }
+
+    /// Finds the interactive widget nearest to the focused one in the direction.
+    /// Without the focus, the first interactive widget is chosen.
+    fn find_neighbour(&self, direction: NavDirection) -> Option<WidgetId> {
+        let mut candidates = Vec::new();
+        self.collect_interactive(self.root, &mut candidates);
+        let Some(from) = self.hovered.and_then(|id| self.rect(id)) else {
+            return candidates.first().map(|(id, _)| *id);
+        };
+
+        let from = (from.min + from.max) * 0.5;
+        let axis = direction.vector();
+        candidates
+            .into_iter()
+            .filter(|(id, _)| Some(*id) != self.hovered)
+            .filter_map(|(id, rect)| {
+                let delta = (rect.min + rect.max) * 0.5 - from;
+                let along = delta.dot(axis);
+                let across = (delta - axis * along).length();
+                // Only the widgets within 45 degrees of the direction
+                (along > 0.0 && across <= along).then_some((id, along + across * 2.0))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(id, _)| id)
+    }
+
+    pub(super) fn navigate(&mut self, event: &UiNavigateEvent, events: &mut Vec<UiEvent>) {
+        match *event {
+            UiNavigateEvent::Move(direction) => {
+                if let Some(id) = self.find_neighbour(direction) {
+                    self.focus_mode = true;
+                    self.set_hovered(Some(id), events);
+                }
+            }
+            UiNavigateEvent::Submit => {
+                if let Some(id) = self.hovered {
+                    events.push(UiEvent::Pressed(id));
+                    events.push(UiEvent::Clicked(id));
+                }
+            }
+            UiNavigateEvent::MoveCursor(delta) => {
+                let size = Vec2::new(self.content.width as f32, self.content.height as f32);
+                let Some(cursor) = &mut self.virtual_cursor else {
+                    return;
+                };
+                cursor.position = (cursor.position + delta).clamp(Vec2::ZERO, size);
+                // The hover is tracked in the view pixels, same as for the mouse
+                let offset = Vec2::new(self.content.x as f32, self.content.y as f32);
+                self.cursor = Some(cursor.position + offset);
+                self.focus_mode = false;
+                self.dirty = true;
+                self.update_hover(events);
+            }
+        }
+    }
+
+    pub(super) fn navigate_handler(
+        r: Receiver<UiNavigateEvent>,
+        mut ui: Single<&mut Ui>,
+        mut sender: Sender<UiEvent>,
+    ) {
+        let mut events = Vec::new();
+        ui.navigate(r.event, &mut events);
+        for event in events {
+            sender.send(event);
+        }
+    }
+}
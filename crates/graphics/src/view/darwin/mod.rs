@@ -1,6 +1,7 @@
 use crate::gl::ViewHandleOpenGL;
 use crate::input::InputEvent;
-use crate::view::{TickResult, ViewConfig, ViewTrait};
+use crate::output::Cursor;
+use crate::view::{Monitor, OutputFormat, TickResult, ViewConfig, ViewGeometry, ViewTrait};
 use std::sync::Arc;
 use crossbeam_channel::Sender;
 
@@ -23,6 +24,7 @@ pub(crate) struct View {}
 impl ViewTrait for View {
     fn open(
         cfg: ViewConfig,
+        output_format: OutputFormat,
         events_sender: Sender<InputEvent>,
     ) -> Result<Self, crate::view::ViewError>
     where
@@ -39,17 +41,67 @@ impl ViewTrait for View {
         todo!()
     }
 
-    fn set_size(&self, width: usize, height: usize) {
+    fn enumerate_monitors() -> Result<Vec<Monitor>, ViewError> {
         todo!()
     }
 
-    fn set_title(&self, title: &str) {
+    fn error_box(title: &str, message: &str) -> bool {
+        // AppleScript string literals only need the quotes and the backslashes escaped
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let script = format!(
+            "display alert \"{}\" message \"{}\" as critical",
+            escape(title),
+            escape(message)
+        );
+        std::process::Command::new("osascript")
+            .arg("-e")
+            .arg(script)
+            .status()
+            .is_ok()
+    }
+
+    fn set_geometry(&mut self, geometry: ViewGeometry) -> Result<(), ViewError> {
+        todo!()
+    }
+
+    fn set_size(&mut self, width: usize, height: usize) -> Result<(), ViewError> {
+        todo!()
+    }
+
+    fn set_size_limits(
+        &mut self,
+        min: Option<(usize, usize)>,
+        max: Option<(usize, usize)>,
+    ) -> Result<(), ViewError> {
+        todo!()
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), ViewError> {
+        todo!()
+    }
+
+    fn set_cursor(&mut self, cursor: &Cursor) -> Result<(), ViewError> {
         todo!()
     }
 }
 
 pub struct ViewHandle {}
 
+#[cfg(feature = "vulkan")]
+impl crate::vulkan::ViewHandleVulkan for ViewHandle {
+    fn surface_extension(&self) -> &'static std::ffi::CStr {
+        todo!()
+    }
+
+    fn create_surface(
+        &self,
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+    ) -> Result<ash::vk::SurfaceKHR, ash::vk::Result> {
+        todo!()
+    }
+}
+
 #[cfg(feature = "gl")]
 impl ViewHandleOpenGL for ViewHandle {
     fn create_context(&mut self, fps: usize, vsync: bool) -> Result<(), ViewError> {
@@ -63,4 +115,8 @@ impl ViewHandleOpenGL for ViewHandle {
     fn swap_buffers(&self) -> Result<(), ViewError> {
         todo!()
     }
+
+    fn output_format(&self) -> OutputFormat {
+        todo!()
+    }
 }
@@ -7,8 +7,10 @@ mod windows;
 mod x11;
 
 use crate::input::InputEvent;
+use crate::output::Cursor;
 use crossbeam_channel::Sender;
 use dawn_util::rendezvous::Rendezvous;
+use std::fmt::{Display, Formatter};
 
 #[cfg(target_os = "macos")]
 pub mod view_impl {
@@ -29,7 +31,7 @@ pub mod view_impl {
     pub type ViewError = x11::ViewError;
     pub(crate) type View = x11::View;
 
-    pub use crate::view::x11::ViewHandle;
+    pub use crate::view::x11::{ViewHandle, XError};
 }
 
 #[cfg(target_os = "windows")]
@@ -45,6 +47,137 @@ pub mod view_impl {
 
 pub use view_impl::*;
 
+/// Video mode of the monitor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoMode {
+    pub width: usize,
+    pub height: usize,
+    /// Refresh rate in Hz
+    pub refresh_rate: f32,
+}
+
+impl VideoMode {
+    // Refresh rates reported by the platforms are not exact (e.g. 59.94 vs 60)
+    pub(crate) fn matches(&self, other: &VideoMode) -> bool {
+        self.width == other.width
+            && self.height == other.height
+            && (self.refresh_rate - other.refresh_rate).abs() < 0.5
+    }
+}
+
+/// Monitor connected to the system.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    /// Platform-specific name of the monitor, e.g. `HDMI-1` on X11
+    /// or `\\.\DISPLAY1` on Windows. Used to select the monitor in the `ViewGeometry`.
+    pub name: String,
+    pub primary: bool,
+    /// Position of the monitor in the virtual screen
+    pub x: i32,
+    pub y: i32,
+    /// Current video mode
+    pub mode: VideoMode,
+    /// All the video modes supported by the monitor
+    pub modes: Vec<VideoMode>,
+}
+
+/// How the window is placed on the screen.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum ViewGeometry {
+    /// Regular window of the size set in the `ViewConfig`.
+    #[default]
+    Windowed,
+    /// Borderless window covering the whole monitor.
+    /// Does not change the video mode, so switching to and from it is fast.
+    /// `None` selects the primary monitor.
+    BorderlessFullscreen { monitor: Option<String> },
+    /// Exclusive fullscreen, switching the monitor to the given video mode.
+    /// The original mode is restored when leaving it or closing the view.
+    /// `None` selects the primary monitor.
+    Fullscreen {
+        monitor: Option<String>,
+        mode: VideoMode,
+    },
+}
+
+/// Pixel format of the default framebuffer the frames are presented from.
+/// The higher precision formats reduce the banding of the gradients, and the
+/// half-float one can hold the values above 1.0 for the HDR displays.
+/// The HDR10 metadata cannot be set through GLX and WGL, so the compositor or
+/// the driver maps the output to the display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum OutputFormat {
+    /// 8 bits per channel
+    #[default]
+    Rgba8,
+    /// 10 bits per color channel and 2 bits of alpha
+    Rgb10A2,
+    /// 16-bit floating point per channel, not clamped to 1.0
+    RgbaF16,
+}
+
+impl OutputFormat {
+    /// The format itself followed by the ones it falls back to
+    /// if the system does not provide it, ending with `Rgba8`.
+    pub fn fallbacks(self) -> &'static [OutputFormat] {
+        match self {
+            OutputFormat::Rgba8 => &[OutputFormat::Rgba8],
+            OutputFormat::Rgb10A2 => &[OutputFormat::Rgb10A2, OutputFormat::Rgba8],
+            OutputFormat::RgbaF16 => &[
+                OutputFormat::RgbaF16,
+                OutputFormat::Rgb10A2,
+                OutputFormat::Rgba8,
+            ],
+        }
+    }
+
+    /// Bits of each of the color channels.
+    pub fn color_bits(self) -> u32 {
+        match self {
+            OutputFormat::Rgba8 => 8,
+            OutputFormat::Rgb10A2 => 10,
+            OutputFormat::RgbaF16 => 16,
+        }
+    }
+
+    /// Bits of the alpha channel.
+    pub fn alpha_bits(self) -> u32 {
+        match self {
+            OutputFormat::Rgba8 => 8,
+            OutputFormat::Rgb10A2 => 2,
+            OutputFormat::RgbaF16 => 16,
+        }
+    }
+
+    pub fn is_float(self) -> bool {
+        self == OutputFormat::RgbaF16
+    }
+}
+
+impl Display for OutputFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputFormat::Rgba8 => write!(f, "RGBA8"),
+            OutputFormat::Rgb10A2 => write!(f, "RGB10_A2"),
+            OutputFormat::RgbaF16 => write!(f, "RGBA16F"),
+        }
+    }
+}
+
+/// Lists the monitors connected to the system with their video modes.
+/// Does not require the view to be opened.
+pub fn enumerate_monitors() -> Result<Vec<Monitor>, ViewError> {
+    View::enumerate_monitors()
+}
+
+/// Shows the native modal error box and waits until it is dismissed.
+/// Does not require the view to be opened, so it can be used to report
+/// the failures of the startup or from the panic hook.
+/// Returns false if the box cannot be shown on the system.
+pub fn error_box(title: &str, message: &str) -> bool {
+    View::error_box(title, message)
+}
+
 #[derive(Clone)]
 pub struct ViewSynchronization {
     pub before_frame: Rendezvous,
@@ -66,16 +199,27 @@ pub struct ViewConfig {
     pub width: usize,
     /// Height of the window in pixels
     pub height: usize,
+    /// Initial placement of the window.
+    /// Can be changed at runtime by sending the `OutputEvent::SetGeometry`.
+    pub geometry: ViewGeometry,
 }
 
 pub(crate) enum TickResult {
     Continue,
     Closed,
+    // Only the X11 backend fails a tick so far.
+    #[allow(dead_code)]
     Failed(ViewError),
 }
 
 pub(crate) trait ViewTrait {
-    fn open(cfg: ViewConfig, events_sender: Sender<InputEvent>) -> Result<Self, ViewError>
+    /// Opens the window with the default framebuffer of the format,
+    /// or of its fallbacks (see `OutputFormat::fallbacks`).
+    fn open(
+        cfg: ViewConfig,
+        output_format: OutputFormat,
+        events_sender: Sender<InputEvent>,
+    ) -> Result<Self, ViewError>
     where
         Self: Sized;
 
@@ -83,6 +227,25 @@ pub(crate) trait ViewTrait {
 
     fn tick(&mut self) -> TickResult;
 
-    fn set_size(&self, width: usize, height: usize);
-    fn set_title(&self, title: &str);
+    fn enumerate_monitors() -> Result<Vec<Monitor>, ViewError>
+    where
+        Self: Sized;
+
+    fn error_box(title: &str, message: &str) -> bool
+    where
+        Self: Sized;
+
+    fn set_geometry(&mut self, geometry: ViewGeometry) -> Result<(), ViewError>;
+
+    fn set_size(&mut self, width: usize, height: usize) -> Result<(), ViewError>;
+
+    fn set_size_limits(
+        &mut self,
+        min: Option<(usize, usize)>,
+        max: Option<(usize, usize)>,
+    ) -> Result<(), ViewError>;
+
+    fn set_title(&mut self, title: &str) -> Result<(), ViewError>;
+
+    fn set_cursor(&mut self, cursor: &Cursor) -> Result<(), ViewError>;
 }
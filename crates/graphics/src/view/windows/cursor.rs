@@ -0,0 +1,109 @@
+use crate::output::{Cursor, CursorImage, CursorShape};
+use crate::view::windows::{get_last_error, ViewError};
+use std::ffi::c_void;
+use windows::core::{BOOL, PCWSTR};
+use windows::Win32::Graphics::Gdi::{
+    CreateBitmap, CreateDIBSection, DeleteObject, BITMAPINFO, BITMAPINFOHEADER, BI_RGB,
+    DIB_RGB_COLORS,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    CreateIconIndirect, LoadCursorW, HCURSOR, ICONINFO, IDC_ARROW, IDC_CROSS, IDC_HAND, IDC_IBEAM,
+    IDC_NO, IDC_SIZEALL, IDC_SIZENESW, IDC_SIZENS, IDC_SIZENWSE, IDC_SIZEWE, IDC_WAIT,
+};
+
+/// Cursor set by the window procedure on WM_SETCURSOR.
+#[derive(Clone, Copy)]
+pub(super) struct LoadedCursor {
+    /// `None` hides the cursor
+    pub(super) handle: Option<HCURSOR>,
+    /// Created from the image, so it must be destroyed when replaced
+    pub(super) owned: bool,
+}
+
+fn system_cursor(shape: CursorShape) -> PCWSTR {
+    match shape {
+        CursorShape::Arrow => IDC_ARROW,
+        CursorShape::Text => IDC_IBEAM,
+        CursorShape::Hand => IDC_HAND,
+        CursorShape::Crosshair => IDC_CROSS,
+        CursorShape::Wait => IDC_WAIT,
+        CursorShape::Move => IDC_SIZEALL,
+        CursorShape::ResizeHorizontal => IDC_SIZEWE,
+        CursorShape::ResizeVertical => IDC_SIZENS,
+        CursorShape::ResizeDiagonal => IDC_SIZENWSE,
+        CursorShape::ResizeAntiDiagonal => IDC_SIZENESW,
+        CursorShape::NotAllowed => IDC_NO,
+    }
+}
+
+pub(super) fn load(cursor: &Cursor) -> Result<LoadedCursor, ViewError> {
+    match cursor {
+        Cursor::Shape(shape) => unsafe {
+            let handle = LoadCursorW(None, system_cursor(*shape))
+                .map_err(|_| ViewError::CreateCursorError(get_last_error()))?;
+            Ok(LoadedCursor {
+                handle: Some(handle),
+                owned: false,
+            })
+        },
+        Cursor::Image(image) => Ok(LoadedCursor {
+            handle: Some(create_image(image)?),
+            owned: true,
+        }),
+        Cursor::Hidden => Ok(LoadedCursor {
+            handle: None,
+            owned: false,
+        }),
+    }
+}
+
+fn create_image(image: &CursorImage) -> Result<HCURSOR, ViewError> {
+    unsafe {
+        // Top-down 32-bit DIB, the alpha channel is used as the mask
+        let info = BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: image.width as i32,
+                biHeight: -(image.height as i32),
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut bits: *mut c_void = std::ptr::null_mut();
+        let color = CreateDIBSection(None, &info, DIB_RGB_COLORS, &mut bits, None, 0)
+            .map_err(|_| ViewError::CreateCursorError(get_last_error()))?;
+
+        // BGRA, premultiplied
+        let pixels =
+            std::slice::from_raw_parts_mut(bits as *mut u8, image.width * image.height * 4);
+        for (pixel, rgba) in pixels.chunks_exact_mut(4).zip(image.rgba.chunks_exact(4)) {
+            let alpha = rgba[3] as u32;
+            let premultiply = |channel: u8| ((channel as u32 * alpha + 127) / 255) as u8;
+            pixel.copy_from_slice(&[
+                premultiply(rgba[2]),
+                premultiply(rgba[1]),
+                premultiply(rgba[0]),
+                rgba[3],
+            ]);
+        }
+
+        // Unused with the alpha, but required
+        let mask = CreateBitmap(image.width as i32, image.height as i32, 1, 1, None);
+
+        let icon = CreateIconIndirect(&ICONINFO {
+            fIcon: BOOL(0),
+            xHotspot: image.hotspot.0 as u32,
+            yHotspot: image.hotspot.1 as u32,
+            hbmMask: mask,
+            hbmColor: color,
+        });
+        let _ = DeleteObject(color.into());
+        let _ = DeleteObject(mask.into());
+
+        icon.map(|icon| HCURSOR(icon.0))
+            .map_err(|_| ViewError::CreateCursorError(get_last_error()))
+    }
+}
@@ -1,30 +1,38 @@
+mod cursor;
 mod input;
+mod output;
 
 use crate::gl::ViewHandleOpenGL;
 use crate::input::{InputEvent, MouseButton};
+use crate::output::{Cursor, CursorShape};
+use crate::view::windows::cursor::LoadedCursor;
 use crate::view::windows::input::convert_key;
-use crate::view::{TickResult, ViewConfig, ViewTrait};
+use crate::view::{Monitor, OutputFormat, TickResult, ViewConfig, ViewGeometry, ViewTrait};
 use crossbeam_channel::Sender;
 use log::{debug, info, warn};
 use std::ffi::c_void;
 use windows::core::{s, HSTRING, PCSTR, PCWSTR};
 use windows::Win32::Foundation::{
-    FreeLibrary, GetLastError, HINSTANCE, HMODULE, HWND, LPARAM, LRESULT, WIN32_ERROR, WPARAM,
+    FreeLibrary, GetLastError, HINSTANCE, HMODULE, HWND, LPARAM, LRESULT, POINT, WIN32_ERROR,
+    WPARAM,
 };
 use windows::Win32::Graphics::Gdi::{GetDC, ReleaseDC, HDC};
 use windows::Win32::Graphics::OpenGL::{
     wglCreateContext, wglDeleteContext, wglGetCurrentContext, wglGetProcAddress, wglMakeCurrent,
-    ChoosePixelFormat, SetPixelFormat, SwapBuffers, HGLRC, PFD_DOUBLEBUFFER, PFD_DRAW_TO_WINDOW,
-    PFD_SUPPORT_OPENGL, PFD_TYPE_RGBA, PIXELFORMATDESCRIPTOR,
+    ChoosePixelFormat, DescribePixelFormat, SetPixelFormat, SwapBuffers, HGLRC, PFD_DOUBLEBUFFER,
+    PFD_DRAW_TO_WINDOW, PFD_SUPPORT_OPENGL, PFD_TYPE_RGBA, PIXELFORMATDESCRIPTOR,
 };
 use windows::Win32::System::LibraryLoader::{GetModuleHandleA, GetModuleHandleW, GetProcAddress};
 use windows::Win32::UI::Input::KeyboardAndMouse::VIRTUAL_KEY;
 use windows::Win32::UI::WindowsAndMessaging::{
-    CreateWindowExW, DefWindowProcA, DestroyWindow, DispatchMessageA, GetMessageA, PostMessageW,
-    PostQuitMessage, RegisterClassW, CS_HREDRAW, CS_VREDRAW, CW_USEDEFAULT, MSG, WINDOW_EX_STYLE,
-    WM_APP, WM_CLOSE, WM_DESTROY, WM_KEYDOWN, WM_KEYUP, WM_LBUTTONDOWN, WM_LBUTTONUP,
+    CreateWindowExW, DefWindowProcA, DestroyCursor, DestroyWindow, DispatchMessageA, GetMessageA,
+    GetWindowLongPtrW, PostMessageW, PostQuitMessage, RegisterClassW, SetCursor, SetWindowLongPtrW,
+    SetWindowPos, SetWindowTextW, CS_HREDRAW, CS_VREDRAW, CW_USEDEFAULT, GWLP_USERDATA, HTCLIENT,
+    MINMAXINFO, MSG, SIZE_MINIMIZED, SWP_NOMOVE, SWP_NOZORDER, WINDOW_EX_STYLE, WM_APP, WM_CLOSE,
+    WM_DESTROY, WM_GETMINMAXINFO, WM_KEYDOWN, WM_KEYUP, WM_LBUTTONDOWN, WM_LBUTTONUP,
     WM_MBUTTONDOWN, WM_MBUTTONUP, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_PAINT, WM_RBUTTONDOWN,
-    WM_RBUTTONUP, WM_SIZE, WM_WINDOWPOSCHANGED, WNDCLASSW, WS_OVERLAPPEDWINDOW, WS_VISIBLE,
+    WM_RBUTTONUP, WM_SETCURSOR, WM_SIZE, WM_WINDOWPOSCHANGED, WNDCLASSW, WS_OVERLAPPEDWINDOW,
+    WS_VISIBLE,
 };
 
 #[derive(Clone, Debug)]
@@ -47,6 +55,12 @@ pub enum ViewError {
     InvalidPixelFormat,
     ContextCreationError(WIN32_ERROR),
     FunctionLoadError(WIN32_ERROR, String),
+    EnumerateMonitorsError(WIN32_ERROR),
+    MonitorNotFound(String),
+    VideoModeNotSupported(String),
+    ChangeDisplaySettingsError(i32),
+    SetWindowPosError(WIN32_ERROR),
+    CreateCursorError(WIN32_ERROR),
 }
 
 impl std::fmt::Display for ViewError {
@@ -75,6 +89,20 @@ impl std::fmt::Display for ViewError {
             ViewError::FunctionLoadError(err, symbol) => {
                 write!(f, "Failed to load function '{}': {:?}", symbol, err)
             }
+            ViewError::EnumerateMonitorsError(err) => {
+                write!(f, "Failed to enumerate monitors: {:?}", err)
+            }
+            ViewError::MonitorNotFound(name) => write!(f, "Monitor not found: {}", name),
+            ViewError::VideoModeNotSupported(name) => {
+                write!(f, "Video mode is not supported by the monitor {}", name)
+            }
+            ViewError::ChangeDisplaySettingsError(code) => {
+                write!(f, "Failed to change display settings: {}", code)
+            }
+            ViewError::SetWindowPosError(err) => {
+                write!(f, "Failed to set window position: {:?}", err)
+            }
+            ViewError::CreateCursorError(err) => write!(f, "Failed to create cursor: {:?}", err),
         }
     }
 }
@@ -84,15 +112,35 @@ impl std::error::Error for ViewError {}
 const CLASS_NAME: &str = "DAWN Window Class";
 pub const WM_APP_QUIT_REQUESTED: u32 = WM_APP + 1;
 
+/* State read by the window procedure. Stored in the GWLP_USERDATA of the window,
+ * owned by the view */
+struct WindowState {
+    min_size: Option<(usize, usize)>,
+    max_size: Option<(usize, usize)>,
+    cursor: LoadedCursor,
+}
+
 pub(crate) struct View {
     hwnd: HWND,
     hinstance: HINSTANCE,
     events_sender: Sender<InputEvent>,
+    minimized: bool,
+
+    /* Size of the window in the windowed mode */
+    width: usize,
+    height: usize,
+    geometry: ViewGeometry,
+    /* Device switched to the exclusive fullscreen mode */
+    fullscreen_device: Option<Vec<u16>>,
+    state: Box<WindowState>,
+    /* The pixel format is set on the device context when the GL context is created */
+    output_format: OutputFormat,
 }
 
 impl ViewTrait for View {
     fn open(
         cfg: ViewConfig,
+        output_format: OutputFormat,
         events_sender: Sender<InputEvent>,
     ) -> Result<Self, crate::view::ViewError> {
         unsafe {
@@ -116,7 +164,7 @@ impl ViewTrait for View {
             }?;
 
             debug!("Creating window. w={}, h={}", cfg.width, cfg.height);
-            let title = HSTRING::from("TItle");
+            let title = HSTRING::from(cfg.title.as_str());
             let hwnd = match CreateWindowExW(
                 WINDOW_EX_STYLE(0),
                 PCWSTR(class_name.as_ptr()),
@@ -135,6 +183,18 @@ impl ViewTrait for View {
                 Err(_) => Err(ViewError::CreateWindowError(get_last_error())),
             }?;
 
+            /* The messages sent while creating the window are handled without the state */
+            let mut state = Box::new(WindowState {
+                min_size: None,
+                max_size: None,
+                cursor: cursor::load(&Cursor::Shape(CursorShape::Arrow))?,
+            });
+            SetWindowLongPtrW(
+                hwnd,
+                GWLP_USERDATA,
+                &mut *state as *mut WindowState as isize,
+            );
+
             // Send fake resize event to initialize the viewport
             events_sender
                 .send(InputEvent::Resize {
@@ -144,11 +204,23 @@ impl ViewTrait for View {
                 .unwrap();
 
             info!("WIN32 Window created successfully");
-            Ok(View {
+            let mut view = View {
                 hwnd,
                 hinstance,
                 events_sender,
-            })
+                minimized: false,
+                width: cfg.width,
+                height: cfg.height,
+                geometry: ViewGeometry::Windowed,
+                fullscreen_device: None,
+                state,
+                output_format,
+            };
+
+            if cfg.geometry != ViewGeometry::Windowed {
+                view.set_geometry(cfg.geometry)?;
+            }
+            Ok(view)
         }
     }
 
@@ -159,6 +231,7 @@ impl ViewTrait for View {
             ctx: None,
             hdc: None,
             opengl32_hmod: None,
+            output_format: self.output_format,
         }
     }
 
@@ -181,12 +254,23 @@ impl ViewTrait for View {
                 }
                 // Catch resize event
                 WM_SIZE => {
-                    let width = (msg.lParam.0 & 0xFFFF) as u32;
-                    let height = (msg.lParam.0 >> 16) as u32;
-                    event = InputEvent::Resize {
-                        width: width as usize,
-                        height: height as usize,
-                    };
+                    // Minimized window reports zero size, do not resize to it
+                    if msg.wParam.0 as u32 == SIZE_MINIMIZED {
+                        self.minimized = true;
+                        event = InputEvent::WindowOccluded;
+                    } else {
+                        if self.minimized {
+                            self.minimized = false;
+                            self.events_sender.send(InputEvent::WindowRestored).unwrap();
+                        }
+
+                        let width = (msg.lParam.0 & 0xFFFF) as u32;
+                        let height = (msg.lParam.0 >> 16) as u32;
+                        event = InputEvent::Resize {
+                            width: width as usize,
+                            height: height as usize,
+                        };
+                    }
                 }
                 WM_KEYDOWN => {
                     event = InputEvent::KeyPress(convert_key(VIRTUAL_KEY(msg.wParam.0 as u16)));
@@ -243,22 +327,142 @@ impl ViewTrait for View {
         }
     }
 
-    fn set_size(&self, _width: usize, _height: usize) {
-        todo!()
+    fn error_box(title: &str, message: &str) -> bool {
+        ViewHandle::error_box(title, message)
     }
 
-    fn set_title(&self, _title: &str) {
-        todo!()
+    fn enumerate_monitors() -> Result<Vec<Monitor>, ViewError> {
+        Ok(output::enumerate()?
+            .into_iter()
+            .map(|raw| raw.monitor)
+            .collect())
+    }
+
+    fn set_geometry(&mut self, geometry: ViewGeometry) -> Result<(), ViewError> {
+        // Leaving the exclusive fullscreen (or switching its mode),
+        // restore the original mode first
+        if let Some(device) = self.fullscreen_device.take() {
+            output::restore_mode(&device)?;
+        }
+
+        match &geometry {
+            ViewGeometry::Windowed => {
+                info!("Switching to windowed mode");
+                output::set_style(self.hwnd, false, 0, 0, self.width, self.height)
+            }
+            ViewGeometry::BorderlessFullscreen { monitor } => {
+                let raw = output::find(monitor.as_deref())?;
+                info!("Switching to borderless fullscreen on {}", raw.monitor.name);
+                output::set_style(
+                    self.hwnd,
+                    true,
+                    raw.monitor.x,
+                    raw.monitor.y,
+                    raw.monitor.mode.width,
+                    raw.monitor.mode.height,
+                )
+            }
+            ViewGeometry::Fullscreen { monitor, mode } => {
+                let raw = output::find(monitor.as_deref())?;
+                let supported = raw.monitor.modes.iter().find(|m| m.matches(mode));
+                let mode = *supported
+                    .ok_or_else(|| ViewError::VideoModeNotSupported(raw.monitor.name.clone()))?;
+                info!(
+                    "Switching to fullscreen on {}: {}x{}@{:.2}",
+                    raw.monitor.name, mode.width, mode.height, mode.refresh_rate
+                );
+
+                output::set_mode(&raw.device, &mode)?;
+                self.fullscreen_device = Some(raw.device);
+                output::set_style(
+                    self.hwnd,
+                    true,
+                    raw.monitor.x,
+                    raw.monitor.y,
+                    mode.width,
+                    mode.height,
+                )
+            }
+        }?;
+
+        self.geometry = geometry;
+        Ok(())
+    }
+
+    fn set_size(&mut self, width: usize, height: usize) -> Result<(), ViewError> {
+        self.width = width;
+        self.height = height;
+        if self.geometry != ViewGeometry::Windowed {
+            return Ok(());
+        }
+
+        unsafe {
+            SetWindowPos(
+                self.hwnd,
+                None,
+                0,
+                0,
+                width as i32,
+                height as i32,
+                SWP_NOMOVE | SWP_NOZORDER,
+            )
+            .map_err(|_| ViewError::SetWindowPosError(get_last_error()))
+        }
+    }
+
+    fn set_size_limits(
+        &mut self,
+        min: Option<(usize, usize)>,
+        max: Option<(usize, usize)>,
+    ) -> Result<(), ViewError> {
+        /* Applied by the window procedure on WM_GETMINMAXINFO */
+        self.state.min_size = min;
+        self.state.max_size = max;
+        Ok(())
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), ViewError> {
+        let title = HSTRING::from(title);
+        unsafe {
+            SetWindowTextW(self.hwnd, PCWSTR(title.as_ptr()))
+                .map_err(|_| ViewError::SetWindowTextError(get_last_error()))
+        }
+    }
+
+    fn set_cursor(&mut self, cursor: &Cursor) -> Result<(), ViewError> {
+        let loaded = cursor::load(cursor)?;
+        let previous = std::mem::replace(&mut self.state.cursor, loaded);
+        unsafe {
+            /* The cursor is only updated by WM_SETCURSOR when the mouse moves */
+            SetCursor(loaded.handle);
+            if let (Some(handle), true) = (previous.handle, previous.owned) {
+                let _ = DestroyCursor(handle);
+            }
+        }
+        Ok(())
     }
 }
 
 impl Drop for View {
     fn drop(&mut self) {
         info!("Destroying WIN32 window and releasing resources");
+        /* Do not leave the monitor in the fullscreen video mode */
+        if let Some(device) = self.fullscreen_device.take() {
+            if let Err(e) = output::restore_mode(&device) {
+                warn!("Failed to restore the video mode: {}", e);
+            }
+        }
+
         unsafe {
             if !self.hwnd.is_invalid() {
+                /* The window procedure must not see the state after it is dropped */
+                SetWindowLongPtrW(self.hwnd, GWLP_USERDATA, 0);
                 DestroyWindow(self.hwnd).ok();
             }
+
+            if let (Some(handle), true) = (self.state.cursor.handle, self.state.cursor.owned) {
+                let _ = DestroyCursor(handle);
+            }
         }
     }
 }
@@ -270,6 +474,8 @@ pub struct ViewHandle {
     hdc: Option<HDC>,
 
     opengl32_hmod: Option<HMODULE>,
+    /* Requested format until the context is created, then the obtained one */
+    output_format: OutputFormat,
 }
 
 #[cfg(feature = "gl")]
@@ -313,23 +519,43 @@ impl ViewHandle {
         }
     }
 
-    pub fn error_box(title: &str, message: &str) {
-        use windows::Win32::UI::WindowsAndMessaging::{MessageBoxA, MB_ICONERROR, MB_OK};
+    pub fn error_box(title: &str, message: &str) -> bool {
+        use windows::Win32::UI::WindowsAndMessaging::{
+            MessageBoxW, MB_ICONERROR, MB_OK, MB_SETFOREGROUND, MB_TASKMODAL,
+        };
 
-        // Leak strings to get static pointers
-        let _title = Box::leak(title.to_string().into_boxed_str());
-        let _message = Box::leak(message.to_string().into_boxed_str());
+        // HSTRING is NUL-terminated and keeps the non-ASCII text intact
+        let title = HSTRING::from(title);
+        let message = HSTRING::from(message);
+        unsafe {
+            MessageBoxW(
+                None,
+                PCWSTR(message.as_ptr()),
+                PCWSTR(title.as_ptr()),
+                MB_OK | MB_ICONERROR | MB_TASKMODAL | MB_SETFOREGROUND,
+            )
+            .0 != 0
+        }
+    }
+}
 
-        let _title_pcstr = PCSTR(_title.as_ptr() as _);
-        let _message_pcstr = PCSTR(_message.as_ptr() as _);
+#[cfg(feature = "vulkan")]
+impl crate::vulkan::ViewHandleVulkan for ViewHandle {
+    fn surface_extension(&self) -> &'static std::ffi::CStr {
+        ash::khr::win32_surface::NAME
+    }
 
+    fn create_surface(
+        &self,
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+    ) -> Result<ash::vk::SurfaceKHR, ash::vk::Result> {
+        let info = ash::vk::Win32SurfaceCreateInfoKHR::default()
+            .hinstance(self.hinstance.0 as ash::vk::HINSTANCE)
+            .hwnd(self.hwnd.0 as ash::vk::HWND);
         unsafe {
-            let _ = MessageBoxA(
-                None,
-                _message_pcstr,
-                _title_pcstr,
-                MB_OK | MB_ICONERROR,
-            );
+            ash::khr::win32_surface::Instance::new(entry, instance)
+                .create_win32_surface(&info, None)
         }
     }
 }
@@ -338,12 +564,26 @@ impl ViewHandle {
 impl ViewHandleOpenGL for ViewHandle {
     fn create_context(&mut self, fps: usize, vsync: bool) -> Result<(), crate::view::ViewError> {
         unsafe {
+            // The float formats are not described by the PFD (they need
+            // WGL_ARB_pixel_format_float), so they fall back to the integer ones
+            let requested = self
+                .output_format
+                .fallbacks()
+                .iter()
+                .copied()
+                .find(|format| !format.is_float())
+                .unwrap_or(OutputFormat::Rgba8);
+            let (color, alpha) = (requested.color_bits() as u8, requested.alpha_bits() as u8);
             let pfd = PIXELFORMATDESCRIPTOR {
                 nSize: size_of::<PIXELFORMATDESCRIPTOR>() as u16,
                 nVersion: 1,
                 dwFlags: PFD_DRAW_TO_WINDOW | PFD_SUPPORT_OPENGL | PFD_DOUBLEBUFFER,
                 iPixelType: PFD_TYPE_RGBA,
-                cColorBits: 32,
+                cColorBits: color * 3,
+                cRedBits: color,
+                cGreenBits: color,
+                cBlueBits: color,
+                cAlphaBits: alpha,
                 cDepthBits: 24,
                 cStencilBits: 8,
                 ..Default::default()
@@ -357,6 +597,25 @@ impl ViewHandleOpenGL for ViewHandle {
             let pixel_format = ChoosePixelFormat(hdc, &pfd);
             SetPixelFormat(hdc, pixel_format, &pfd).map_err(|_| ViewError::InvalidPixelFormat)?;
 
+            // ChoosePixelFormat returns the closest match, so check what was obtained
+            let mut obtained = PIXELFORMATDESCRIPTOR::default();
+            DescribePixelFormat(
+                hdc,
+                pixel_format,
+                size_of::<PIXELFORMATDESCRIPTOR>() as u32,
+                Some(&mut obtained),
+            );
+            let format = if obtained.cRedBits >= 10 {
+                OutputFormat::Rgb10A2
+            } else {
+                OutputFormat::Rgba8
+            };
+            info!(
+                "Output format: {} (requested {})",
+                format, self.output_format
+            );
+            self.output_format = format;
+
             let hglrc = wglCreateContext(hdc)
                 .map_err(|_| ViewError::ContextCreationError(get_last_error()))?;
             wglMakeCurrent(hdc, hglrc)
@@ -404,6 +663,10 @@ impl ViewHandleOpenGL for ViewHandle {
             Ok(())
         }
     }
+
+    fn output_format(&self) -> OutputFormat {
+        self.output_format
+    }
 }
 
 impl Drop for ViewHandle {
@@ -434,6 +697,10 @@ fn get_last_error() -> WIN32_ERROR {
     unsafe { GetLastError() }
 }
 
+unsafe fn window_state<'a>(hwnd: HWND) -> Option<&'a WindowState> {
+    unsafe { (GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *const WindowState).as_ref() }
+}
+
 unsafe extern "system" fn default_proc(
     hwnd: HWND,
     message: u32,
@@ -450,6 +717,38 @@ unsafe extern "system" fn default_proc(
             LRESULT(0)
         }
 
+        WM_GETMINMAXINFO => {
+            if let Some(state) = unsafe { window_state(hwnd) } {
+                let info = unsafe { &mut *(lparam.0 as *mut MINMAXINFO) };
+                if let Some((width, height)) = state.min_size {
+                    info.ptMinTrackSize = POINT {
+                        x: width as i32,
+                        y: height as i32,
+                    };
+                }
+                if let Some((width, height)) = state.max_size {
+                    info.ptMaxTrackSize = POINT {
+                        x: width as i32,
+                        y: height as i32,
+                    };
+                }
+            }
+            LRESULT(0)
+        }
+
+        /* The cursor is set over the client area only, the borders keep the resize ones */
+        WM_SETCURSOR if (lparam.0 & 0xFFFF) as u32 == HTCLIENT => {
+            match unsafe { window_state(hwnd) } {
+                Some(state) => {
+                    unsafe {
+                        SetCursor(state.cursor.handle);
+                    }
+                    LRESULT(1)
+                }
+                None => unsafe { DefWindowProcA(hwnd, message, wparam, lparam) },
+            }
+        }
+
         WM_DESTROY => {
             debug!("WM_DESTROY received, destroying window");
             unsafe {
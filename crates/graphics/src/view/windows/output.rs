@@ -0,0 +1,236 @@
+use crate::view::windows::{get_last_error, ViewError};
+use crate::view::{Monitor, VideoMode};
+use log::debug;
+use windows::core::{BOOL, PCWSTR};
+use windows::Win32::Foundation::{HWND, LPARAM, RECT};
+use windows::Win32::Graphics::Gdi::{
+    ChangeDisplaySettingsExW, EnumDisplayMonitors, EnumDisplaySettingsW, GetMonitorInfoW,
+    CDS_FULLSCREEN, CDS_TYPE, DEVMODEW, DISP_CHANGE_SUCCESSFUL, DM_DISPLAYFREQUENCY, DM_PELSHEIGHT,
+    DM_PELSWIDTH, ENUM_CURRENT_SETTINGS, ENUM_DISPLAY_SETTINGS_MODE, HDC, HMONITOR, MONITORINFO,
+    MONITORINFOEXW,
+};
+use windows::Win32::UI::WindowsAndMessaging::{
+    SetWindowLongPtrW, SetWindowPos, GWL_STYLE, MONITORINFOF_PRIMARY, SWP_FRAMECHANGED, SWP_NOMOVE,
+    SWP_NOZORDER, WS_OVERLAPPEDWINDOW, WS_POPUP, WS_VISIBLE,
+};
+
+/// Monitor with the GDI device name required to change its mode.
+pub(super) struct RawMonitor {
+    pub(super) monitor: Monitor,
+    /// Null-terminated device name
+    pub(super) device: Vec<u16>,
+}
+
+unsafe extern "system" fn enum_proc(
+    hmonitor: HMONITOR,
+    _hdc: HDC,
+    _rect: *mut RECT,
+    data: LPARAM,
+) -> BOOL {
+    let handles = unsafe { &mut *(data.0 as *mut Vec<HMONITOR>) };
+    handles.push(hmonitor);
+    BOOL(1)
+}
+
+fn convert_mode(devmode: &DEVMODEW) -> VideoMode {
+    VideoMode {
+        width: devmode.dmPelsWidth as usize,
+        height: devmode.dmPelsHeight as usize,
+        refresh_rate: devmode.dmDisplayFrequency as f32,
+    }
+}
+
+fn empty_devmode() -> DEVMODEW {
+    DEVMODEW {
+        dmSize: size_of::<DEVMODEW>() as u16,
+        ..Default::default()
+    }
+}
+
+/// Lists the monitors attached to the desktop.
+pub(super) fn enumerate() -> Result<Vec<RawMonitor>, ViewError> {
+    let mut handles: Vec<HMONITOR> = Vec::new();
+    unsafe {
+        if !EnumDisplayMonitors(
+            None,
+            None,
+            Some(enum_proc),
+            LPARAM(&mut handles as *mut Vec<HMONITOR> as isize),
+        )
+        .as_bool()
+        {
+            return Err(ViewError::EnumerateMonitorsError(get_last_error()));
+        }
+    }
+
+    let mut monitors = Vec::with_capacity(handles.len());
+    for hmonitor in handles {
+        let mut info = MONITORINFOEXW::default();
+        info.monitorInfo.cbSize = size_of::<MONITORINFOEXW>() as u32;
+        let ok = unsafe {
+            GetMonitorInfoW(
+                hmonitor,
+                &mut info as *mut MONITORINFOEXW as *mut MONITORINFO,
+            )
+        };
+        if !ok.as_bool() {
+            continue;
+        }
+
+        let len = info
+            .szDevice
+            .iter()
+            .position(|c| *c == 0)
+            .unwrap_or(info.szDevice.len());
+        let mut device = info.szDevice[..len].to_vec();
+        let name = String::from_utf16_lossy(&device);
+        device.push(0);
+
+        let mut modes = Vec::new();
+        let mut devmode = empty_devmode();
+        let mut index = 0;
+        while unsafe {
+            EnumDisplaySettingsW(
+                PCWSTR(device.as_ptr()),
+                ENUM_DISPLAY_SETTINGS_MODE(index),
+                &mut devmode,
+            )
+        }
+        .as_bool()
+        {
+            let mode = convert_mode(&devmode);
+            // The same mode is reported for every color depth
+            if !modes.iter().any(|m: &VideoMode| m.matches(&mode)) {
+                modes.push(mode);
+            }
+            index += 1;
+        }
+
+        let rect = info.monitorInfo.rcMonitor;
+        let mut current = empty_devmode();
+        let mode = if unsafe {
+            EnumDisplaySettingsW(PCWSTR(device.as_ptr()), ENUM_CURRENT_SETTINGS, &mut current)
+        }
+        .as_bool()
+        {
+            convert_mode(&current)
+        } else {
+            VideoMode {
+                width: (rect.right - rect.left) as usize,
+                height: (rect.bottom - rect.top) as usize,
+                refresh_rate: 0.0,
+            }
+        };
+
+        debug!("Found monitor {} ({} modes)", name, modes.len());
+        monitors.push(RawMonitor {
+            monitor: Monitor {
+                name,
+                primary: info.monitorInfo.dwFlags & MONITORINFOF_PRIMARY != 0,
+                x: rect.left,
+                y: rect.top,
+                mode,
+                modes,
+            },
+            device,
+        });
+    }
+
+    Ok(monitors)
+}
+
+/// Finds the monitor by name, or the primary one if no name is given.
+pub(super) fn find(name: Option<&str>) -> Result<RawMonitor, ViewError> {
+    let mut monitors = enumerate()?;
+    let index = match name {
+        Some(name) => monitors.iter().position(|m| m.monitor.name == name),
+        None => monitors
+            .iter()
+            .position(|m| m.monitor.primary)
+            .or(if monitors.is_empty() { None } else { Some(0) }),
+    };
+
+    match index {
+        Some(index) => Ok(monitors.swap_remove(index)),
+        None => Err(ViewError::MonitorNotFound(
+            name.unwrap_or("primary").to_string(),
+        )),
+    }
+}
+
+/// Switches the monitor to the given mode. The change is temporary,
+/// it is not stored in the registry.
+pub(super) fn set_mode(device: &[u16], mode: &VideoMode) -> Result<(), ViewError> {
+    let mut devmode = empty_devmode();
+    devmode.dmPelsWidth = mode.width as u32;
+    devmode.dmPelsHeight = mode.height as u32;
+    devmode.dmDisplayFrequency = mode.refresh_rate.round() as u32;
+    devmode.dmFields = DM_PELSWIDTH | DM_PELSHEIGHT | DM_DISPLAYFREQUENCY;
+
+    let result = unsafe {
+        ChangeDisplaySettingsExW(
+            PCWSTR(device.as_ptr()),
+            Some(&devmode),
+            None,
+            CDS_FULLSCREEN,
+            None,
+        )
+    };
+    if result != DISP_CHANGE_SUCCESSFUL {
+        return Err(ViewError::ChangeDisplaySettingsError(result.0));
+    }
+
+    Ok(())
+}
+
+/// Restores the mode stored in the registry.
+pub(super) fn restore_mode(device: &[u16]) -> Result<(), ViewError> {
+    let result =
+        unsafe { ChangeDisplaySettingsExW(PCWSTR(device.as_ptr()), None, None, CDS_TYPE(0), None) };
+    if result != DISP_CHANGE_SUCCESSFUL {
+        return Err(ViewError::ChangeDisplaySettingsError(result.0));
+    }
+
+    Ok(())
+}
+
+/// Switches between the regular window and the borderless popup covering the given area.
+pub(super) fn set_style(
+    hwnd: HWND,
+    fullscreen: bool,
+    x: i32,
+    y: i32,
+    width: usize,
+    height: usize,
+) -> Result<(), ViewError> {
+    unsafe {
+        if fullscreen {
+            SetWindowLongPtrW(hwnd, GWL_STYLE, (WS_POPUP | WS_VISIBLE).0 as isize);
+            SetWindowPos(
+                hwnd,
+                None,
+                x,
+                y,
+                width as i32,
+                height as i32,
+                SWP_FRAMECHANGED | SWP_NOZORDER,
+            )
+        } else {
+            SetWindowLongPtrW(
+                hwnd,
+                GWL_STYLE,
+                (WS_OVERLAPPEDWINDOW | WS_VISIBLE).0 as isize,
+            );
+            SetWindowPos(
+                hwnd,
+                None,
+                0,
+                0,
+                width as i32,
+                height as i32,
+                SWP_FRAMECHANGED | SWP_NOZORDER | SWP_NOMOVE,
+            )
+        }
+        .map_err(|_| ViewError::SetWindowPosError(get_last_error()))
+    }
+}
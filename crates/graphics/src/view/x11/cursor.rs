@@ -0,0 +1,100 @@
+use crate::output::{Cursor, CursorImage, CursorShape};
+use crate::view::x11::ViewError;
+use std::ffi::c_uint;
+use x11::xcursor::{XcursorImageCreate, XcursorImageDestroy, XcursorImageLoadCursor};
+use x11::xlib;
+use x11::xlib::{
+    Display, XColor, XCreateBitmapFromData, XCreateFontCursor, XCreatePixmapCursor, XFreePixmap,
+};
+
+/* Glyphs of the X cursor font, see X11/cursorfont.h */
+const XC_BOTTOM_LEFT_CORNER: c_uint = 12;
+const XC_BOTTOM_RIGHT_CORNER: c_uint = 14;
+const XC_CIRCLE: c_uint = 24;
+const XC_CROSSHAIR: c_uint = 34;
+const XC_FLEUR: c_uint = 52;
+const XC_HAND2: c_uint = 60;
+const XC_LEFT_PTR: c_uint = 68;
+const XC_SB_H_DOUBLE_ARROW: c_uint = 108;
+const XC_SB_V_DOUBLE_ARROW: c_uint = 116;
+const XC_WATCH: c_uint = 150;
+const XC_XTERM: c_uint = 152;
+
+fn font_glyph(shape: CursorShape) -> c_uint {
+    match shape {
+        CursorShape::Arrow => XC_LEFT_PTR,
+        CursorShape::Text => XC_XTERM,
+        CursorShape::Hand => XC_HAND2,
+        CursorShape::Crosshair => XC_CROSSHAIR,
+        CursorShape::Wait => XC_WATCH,
+        CursorShape::Move => XC_FLEUR,
+        CursorShape::ResizeHorizontal => XC_SB_H_DOUBLE_ARROW,
+        CursorShape::ResizeVertical => XC_SB_V_DOUBLE_ARROW,
+        CursorShape::ResizeDiagonal => XC_BOTTOM_RIGHT_CORNER,
+        CursorShape::ResizeAntiDiagonal => XC_BOTTOM_LEFT_CORNER,
+        CursorShape::NotAllowed => XC_CIRCLE,
+    }
+}
+
+/// Creates the cursor resource on the display. It must be freed with `XFreeCursor`.
+pub(super) unsafe fn create(
+    display: *mut Display,
+    window: xlib::Window,
+    cursor: &Cursor,
+) -> Result<xlib::Cursor, ViewError> {
+    let id = unsafe {
+        match cursor {
+            Cursor::Shape(shape) => XCreateFontCursor(display, font_glyph(*shape)),
+            Cursor::Image(image) => create_image(display, image),
+            Cursor::Hidden => {
+                // Cursor of the single transparent pixel
+                let data = [0u8];
+                let pixmap = XCreateBitmapFromData(display, window, data.as_ptr() as _, 1, 1);
+                if pixmap == 0 {
+                    return Err(ViewError::CreateCursorError);
+                }
+                let mut black: XColor = std::mem::zeroed();
+                let id = XCreatePixmapCursor(display, pixmap, pixmap, &mut black, &mut black, 0, 0);
+                XFreePixmap(display, pixmap);
+                id
+            }
+        }
+    };
+
+    if id == 0 {
+        Err(ViewError::CreateCursorError)
+    } else {
+        Ok(id)
+    }
+}
+
+unsafe fn create_image(display: *mut Display, image: &CursorImage) -> xlib::Cursor {
+    unsafe {
+        let cursor_image = XcursorImageCreate(image.width as _, image.height as _);
+        if cursor_image.is_null() {
+            return 0;
+        }
+
+        (*cursor_image).xhot = image.hotspot.0 as _;
+        (*cursor_image).yhot = image.hotspot.1 as _;
+        let pixels =
+            std::slice::from_raw_parts_mut((*cursor_image).pixels, image.width * image.height);
+        for (pixel, rgba) in pixels.iter_mut().zip(image.rgba.chunks_exact(4)) {
+            *pixel = premultiplied_argb(rgba);
+        }
+
+        let id = XcursorImageLoadCursor(display, cursor_image);
+        XcursorImageDestroy(cursor_image);
+        id
+    }
+}
+
+// Xcursor expects the premultiplied ARGB pixels
+fn premultiplied_argb(rgba: &[u8]) -> u32 {
+    let alpha = rgba[3] as u32;
+    let premultiply = |channel: u8| (channel as u32 * alpha + 127) / 255;
+    (alpha << 24)
+        | (premultiply(rgba[0]) << 16)
+        | (premultiply(rgba[1]) << 8)
+        | premultiply(rgba[2])
+}
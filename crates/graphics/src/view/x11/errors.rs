@@ -0,0 +1,222 @@
+use crate::view::x11::ViewError;
+use log::{debug, warn};
+use std::ffi::{c_char, c_int, CStr};
+use std::sync::{Mutex, Once, PoisonError};
+use x11::xlib;
+use x11::xlib::{Display, XErrorEvent, XGetErrorText, XSetErrorHandler, XSync};
+
+/* Xlib reports the protocol errors asynchronously, through the process-wide
+ * handler. The default one prints the error and exits the process, so e.g.
+ * BadWindow of the window already destroyed while shutting down kills the
+ * whole application. The handler below records the errors per display instead,
+ * and the view decides what to do with them (see `classify`) */
+static INSTALL: Once = Once::new();
+static PENDING: Mutex<Vec<(usize, XError)>> = Mutex::new(Vec::new());
+
+/// Protocol error reported by the X server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct XError {
+    pub error_code: u8,
+    pub request_code: u8,
+    pub minor_code: u8,
+    /// The resource (e.g. the window) the failed request was about
+    pub resource: u64,
+    pub serial: u64,
+    pub description: String,
+}
+
+impl std::fmt::Display for XError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (request {}.{}, resource {:#x})",
+            self.description, self.request_code, self.minor_code, self.resource
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum XErrorAction {
+    Ignore,
+    Log,
+    Fail,
+}
+
+/// Installs the error handler. Must be called before opening the display connections.
+pub(super) fn install() {
+    INSTALL.call_once(|| unsafe {
+        XSetErrorHandler(Some(handler));
+    });
+}
+
+unsafe extern "C" fn handler(display: *mut Display, event: *mut XErrorEvent) -> c_int {
+    let event = unsafe { &*event };
+    let description = unsafe { describe(display, event.error_code) };
+    record(display.addr(), event, description);
+
+    // The return value is ignored by Xlib
+    0
+}
+
+fn record(display: usize, event: &XErrorEvent, description: String) {
+    let error = XError {
+        error_code: event.error_code,
+        request_code: event.request_code,
+        minor_code: event.minor_code,
+        resource: event.resourceid,
+        serial: event.serial,
+        description,
+    };
+    PENDING
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push((display, error));
+}
+
+unsafe fn describe(display: *mut Display, code: u8) -> String {
+    if display.is_null() {
+        return format!("X error {}", code);
+    }
+
+    // Only looks up the local error database, so it is safe to call from the handler
+    let mut buffer = [0 as c_char; 256];
+    unsafe {
+        XGetErrorText(
+            display,
+            code as c_int,
+            buffer.as_mut_ptr(),
+            buffer.len() as c_int,
+        );
+        CStr::from_ptr(buffer.as_ptr())
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Takes the errors of the display recorded so far.
+pub(super) fn take(display: *mut Display) -> Vec<XError> {
+    let mut pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+    let (taken, rest) = pending
+        .drain(..)
+        .partition(|(owner, _)| *owner == display.addr());
+    *pending = rest;
+    taken.into_iter().map(|(_, error)| error).collect()
+}
+
+/// Waits for the server to process the requests sent so far,
+/// and fails with the first error they have caused.
+pub(super) unsafe fn check(display: *mut Display) -> Result<(), ViewError> {
+    unsafe {
+        XSync(display, 0);
+    }
+    let mut errors = take(display).into_iter();
+    match errors.next() {
+        Some(first) => {
+            for error in errors {
+                warn!("X error: {}", error);
+            }
+            Err(ViewError::XError(first))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Drops the errors of the display that is being closed.
+/// The address can be reused by the next connection, so they must not stay.
+pub(super) fn discard(display: *mut Display) {
+    for error in take(display) {
+        debug!("Ignoring X error while closing the display: {}", error);
+    }
+}
+
+/// What to do with the error, reported while the view of the `window` is running.
+/// While the view is `closing`, the window may already be destroyed
+/// by the server or the window manager, so the errors are expected.
+pub(super) fn classify(error: &XError, window: xlib::Window, closing: bool) -> XErrorAction {
+    if closing {
+        return XErrorAction::Ignore;
+    }
+
+    match error.error_code {
+        // The window of the view is gone, nothing can be rendered anymore
+        xlib::BadWindow | xlib::BadDrawable if error.resource == window => XErrorAction::Fail,
+        xlib::BadAlloc => XErrorAction::Fail,
+        _ => XErrorAction::Log,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: xlib::Window = 0x4400001;
+
+    fn error(error_code: u8, resource: u64) -> XError {
+        XError {
+            error_code,
+            request_code: 2,
+            minor_code: 0,
+            resource,
+            serial: 1,
+            description: String::new(),
+        }
+    }
+
+    // There is no display in the tests, so the errors are recorded
+    // as the handler does, under the fake display addresses
+    fn report(display: usize, error_code: u8, resource: u64) {
+        let event = XErrorEvent {
+            type_: 0,
+            display: std::ptr::null_mut(),
+            resourceid: resource,
+            serial: 7,
+            error_code,
+            request_code: 4,
+            minor_code: 0,
+        };
+        let description = unsafe { describe(std::ptr::null_mut(), error_code) };
+        record(display, &event, description);
+    }
+
+    #[test]
+    fn errors_of_the_view_window_fail_it() {
+        let bad_window = error(xlib::BadWindow, WINDOW);
+        assert_eq!(classify(&bad_window, WINDOW, false), XErrorAction::Fail);
+        let bad_drawable = error(xlib::BadDrawable, WINDOW);
+        assert_eq!(classify(&bad_drawable, WINDOW, false), XErrorAction::Fail);
+
+        // Windows of the others (e.g. the one the WM has reparented us into) are not ours
+        let foreign = error(xlib::BadWindow, WINDOW + 1);
+        assert_eq!(classify(&foreign, WINDOW, false), XErrorAction::Log);
+        let bad_match = error(xlib::BadMatch, WINDOW);
+        assert_eq!(classify(&bad_match, WINDOW, false), XErrorAction::Log);
+    }
+
+    #[test]
+    fn errors_while_closing_are_ignored() {
+        for code in [xlib::BadWindow, xlib::BadDrawable, xlib::BadAlloc] {
+            let error = error(code, WINDOW);
+            assert_eq!(classify(&error, WINDOW, true), XErrorAction::Ignore);
+        }
+    }
+
+    #[test]
+    fn handler_records_errors_per_display() {
+        let (first, second) = (0x1000, 0x2000);
+        report(first, xlib::BadWindow, WINDOW);
+        report(second, xlib::BadMatch, 1);
+        report(first, xlib::BadAlloc, 2);
+
+        let errors = take(std::ptr::without_provenance_mut(first));
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].error_code, xlib::BadWindow);
+        assert_eq!(errors[0].resource, WINDOW);
+        assert_eq!(errors[0].serial, 7);
+        assert_eq!(errors[0].description, "X error 3");
+        assert_eq!(errors[1].error_code, xlib::BadAlloc);
+        assert!(take(std::ptr::without_provenance_mut(first)).is_empty());
+
+        discard(std::ptr::without_provenance_mut(second));
+        assert!(take(std::ptr::without_provenance_mut(second)).is_empty());
+    }
+}
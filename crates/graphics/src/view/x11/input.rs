@@ -236,7 +236,7 @@ pub(crate) fn convert_key(display: *mut Display, keycode: c_uint, keystate: c_ui
         x11::keysym::XK_KP_8 => KeyCode::KPDigit(8),
         x11::keysym::XK_KP_9 => KeyCode::KPDigit(9),
 
-        _ => KeyCode::Unknown(keycode as u32, keysym as u32),
+        _ => KeyCode::Unknown(keycode, keysym as u32),
     }
 }
 
@@ -248,6 +248,6 @@ pub(crate) fn convert_mouse(button: c_uint) -> MouseButton {
         x11::xlib::Button4 => MouseButton::Special(0),
         x11::xlib::Button5 => MouseButton::Special(1),
 
-        _ => MouseButton::Unknown(button as u32),
+        _ => MouseButton::Unknown(button),
     }
 }
@@ -1,9 +1,11 @@
 use crate::gl::ViewHandleOpenGL;
 use crate::input::InputEvent;
-use crate::view::{TickResult, ViewConfig, ViewTrait};
+use crate::output::Cursor;
+use crate::view::x11::errors::XErrorAction;
+use crate::view::{Monitor, OutputFormat, TickResult, ViewConfig, ViewGeometry, ViewTrait};
 use crossbeam_channel::Sender;
 use log::{debug, info, warn};
-use std::ffi::{c_char, c_int, c_uint};
+use std::ffi::{c_int, c_uint, CString};
 use std::ptr::addr_of_mut;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
@@ -15,26 +17,44 @@ use x11::glx::{
 };
 use x11::xlib;
 use x11::xlib::{
-    Atom, ButtonPressMask, ButtonReleaseMask, CWColormap, CWEventMask, ClientMessage,
-    ConfigureNotify, CopyFromParent, CurrentTime, Display, ExposureMask, InputOutput, KeyPressMask,
-    KeyReleaseMask, NoEventMask, PointerMotionMask, StructureNotifyMask, Visual, XAutoRepeatOff,
+    Atom, ButtonPressMask, ButtonReleaseMask, CWColormap, CWEventMask, ClientMessage, CopyFromParent, CurrentTime, Display, ExposureMask, InputOutput, KeyPressMask,
+    KeyReleaseMask, NoEventMask, PointerMotionMask, StructureNotifyMask, Visual,
+    VisibilityChangeMask, VisibilityFullyObscured, XAutoRepeatOff,
     XAutoRepeatOn, XClearWindow, XCloseDisplay, XCreateColormap, XCreateWindow, XDefaultScreen,
     XDestroyWindow, XEvent, XFlush, XFree, XFreeColormap, XInternAtom, XMapRaised, XMapWindow,
     XNextEvent, XOpenDisplay, XRootWindow, XSendEvent, XSetWMProtocols, XSetWindowAttributes,
-    XStoreName, XSync, XVisualInfo,
+    XMoveResizeWindow, XResizeWindow, XStoreName, XSync, XVisualInfo,
 };
+use x11::xlib::{
+    PMaxSize, PMinSize, PropModeReplace, XChangeProperty, XDefineCursor, XFreeCursor,
+    XSetWMNormalHints, XSizeHints,
+};
+use x11::xrandr::{RRCrtc, RRMode};
 
+mod cursor;
+mod errors;
 mod input;
+mod output;
+
+pub use errors::XError;
 
 #[derive(Clone, Debug)]
 pub struct PlatformSpecificViewConfig {}
 
+// Named like the ViewError variants of the other backends.
 #[derive(Clone, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum ViewError {
     OpenDisplayError,
     CreateWindowError,
     SpawnEventsThreadError,
     JoinEventsThreadError,
+    MonitorNotFound(String),
+    VideoModeNotSupported(String),
+    XRandRError(String),
+    CreateCursorError,
+    /// Asynchronous protocol error, e.g. BadMatch of the unsupported visual
+    XError(XError),
     #[cfg(feature = "gl")]
     GLXError(String),
 }
@@ -46,6 +66,13 @@ impl std::fmt::Display for ViewError {
             ViewError::CreateWindowError => write!(f, "Failed to create X11 window"),
             ViewError::SpawnEventsThreadError => write!(f, "Failed to spawn events thread"),
             ViewError::JoinEventsThreadError => write!(f, "Failed to join events thread"),
+            ViewError::MonitorNotFound(name) => write!(f, "Monitor not found: {}", name),
+            ViewError::VideoModeNotSupported(name) => {
+                write!(f, "Video mode is not supported by the monitor {}", name)
+            }
+            ViewError::XRandRError(msg) => write!(f, "XRandR error: {}", msg),
+            ViewError::CreateCursorError => write!(f, "Failed to create cursor"),
+            ViewError::XError(error) => write!(f, "X error: {}", error),
             #[cfg(feature = "gl")]
             ViewError::GLXError(msg) => write!(f, "GLX error: {}", msg),
         }
@@ -58,10 +85,24 @@ pub(crate) struct View {
     display: *mut Display,
     window: xlib::Window,
     fb_config: GLXFBConfig,
+    output_format: OutputFormat,
     color_map: xlib::Colormap,
 
     delete_message: Atom,
 
+    /* Size of the window in the windowed mode */
+    width: usize,
+    height: usize,
+    geometry: ViewGeometry,
+    /* CRTC and its mode before switching to the exclusive fullscreen */
+    original_mode: Option<(RRCrtc, RRMode)>,
+
+    /* The window is changed through the separate connection (see output.rs).
+     * The cursor is its resource, so the connection is kept while the view lives */
+    control: output::DisplayConnection,
+    /* Cursor defined for the window, 0 for the default one */
+    cursor: xlib::Cursor,
+
     /* A signal to stop the event handling thread */
     stop_signal: Arc<AtomicBool>,
     events_thread: Option<thread::JoinHandle<Result<(), ViewError>>>,
@@ -136,6 +177,26 @@ fn process_events_sync(
                 .unwrap();
         }
 
+        // Minimized windows are unmapped by the window manager
+        xlib::UnmapNotify => {
+            events_sender.send(InputEvent::WindowOccluded).unwrap();
+        }
+
+        xlib::MapNotify => {
+            events_sender.send(InputEvent::WindowRestored).unwrap();
+        }
+
+        xlib::VisibilityNotify => {
+            let state = unsafe { event.visibility.state };
+            events_sender
+                .send(if state == VisibilityFullyObscured {
+                    InputEvent::WindowOccluded
+                } else {
+                    InputEvent::WindowRestored
+                })
+                .unwrap();
+        }
+
         xlib::MotionNotify => {
             let x = unsafe { event.motion.x };
             let y = unsafe { event.motion.y };
@@ -155,27 +216,49 @@ fn process_events_sync(
     Ok(true)
 }
 
+// GLX_ARB_fbconfig_float, not defined by the x11 crate
 #[cfg(feature = "gl")]
-fn select_fb(display: *mut Display) -> Result<((GLXFBConfig, *mut XVisualInfo)), ViewError> {
-    unsafe {
-        #[rustfmt::skip]
-        static FB_ATTRIBS: [i32; 27] = [
-            x11::glx::GLX_X_RENDERABLE, 1,
-            x11::glx::GLX_DRAWABLE_TYPE, x11::glx::GLX_WINDOW_BIT,
-            x11::glx::GLX_RENDER_TYPE, x11::glx::GLX_RGBA_BIT,
-            x11::glx::GLX_X_VISUAL_TYPE, x11::glx::GLX_TRUE_COLOR,
-            x11::glx::GLX_RED_SIZE, 8,
-            x11::glx::GLX_GREEN_SIZE, 8,
-            x11::glx::GLX_BLUE_SIZE, 8,
-            x11::glx::GLX_ALPHA_SIZE, 8,
-            x11::glx::GLX_DEPTH_SIZE, 24,
-            x11::glx::GLX_STENCIL_SIZE, 8,
-            x11::glx::GLX_DOUBLEBUFFER, 1,
-            x11::glx::GLX_SAMPLE_BUFFERS, 1, // <-- MSAA
-            x11::glx::GLX_SAMPLES, 4, // <-- MSAA
-            0, // Terminate the list of attributes
-        ];
+const GLX_RGBA_FLOAT_BIT_ARB: i32 = 0x4;
+#[cfg(feature = "gl")]
+const GLX_RGBA_FLOAT_TYPE_ARB: i32 = 0x20B9;
+
+#[cfg(feature = "gl")]
+fn fb_attribs(format: OutputFormat) -> [i32; 27] {
+    let render_type = if format.is_float() {
+        GLX_RGBA_FLOAT_BIT_ARB
+    } else {
+        x11::glx::GLX_RGBA_BIT
+    };
+    let (color, alpha) = (format.color_bits() as i32, format.alpha_bits() as i32);
+
+    #[rustfmt::skip]
+    let attribs = [
+        x11::glx::GLX_X_RENDERABLE, 1,
+        x11::glx::GLX_DRAWABLE_TYPE, x11::glx::GLX_WINDOW_BIT,
+        x11::glx::GLX_RENDER_TYPE, render_type,
+        x11::glx::GLX_X_VISUAL_TYPE, x11::glx::GLX_TRUE_COLOR,
+        x11::glx::GLX_RED_SIZE, color,
+        x11::glx::GLX_GREEN_SIZE, color,
+        x11::glx::GLX_BLUE_SIZE, color,
+        x11::glx::GLX_ALPHA_SIZE, alpha,
+        x11::glx::GLX_DEPTH_SIZE, 24,
+        x11::glx::GLX_STENCIL_SIZE, 8,
+        x11::glx::GLX_DOUBLEBUFFER, 1,
+        x11::glx::GLX_SAMPLE_BUFFERS, 1, // <-- MSAA
+        x11::glx::GLX_SAMPLES, 4, // <-- MSAA
+        0, // Terminate the list of attributes
+    ];
+    attribs
+}
 
+/// Selects the FB config of the format or of its fallbacks.
+/// Returns the config, its visual and the format it actually has.
+#[cfg(feature = "gl")]
+fn select_fb(
+    display: *mut Display,
+    format: OutputFormat,
+) -> Result<(GLXFBConfig, *mut XVisualInfo, OutputFormat), ViewError> {
+    unsafe {
         let (mut gl_major, mut gl_minor) = (0, 0);
         if glXQueryVersion(display, addr_of_mut!(gl_major), addr_of_mut!(gl_minor)) == 0 {
             return Err(ViewError::GLXError(
@@ -188,20 +271,57 @@ fn select_fb(display: *mut Display) -> Result<((GLXFBConfig, *mut XVisualInfo)),
             return Err(ViewError::GLXError("GLX version too low".to_string()));
         }
 
+        for &candidate in format.fallbacks() {
+            let Some(best_fbc) = choose_fb(display, candidate) else {
+                warn!("No framebuffer configurations of the {} format", candidate);
+                continue;
+            };
+
+            // Get visual info for the best FBConfig
+            let visual_info_ptr = glXGetVisualFromFBConfig(display, best_fbc);
+            if visual_info_ptr.is_null() {
+                return Err(ViewError::GLXError(
+                    "Failed to get visual info from FBConfig".to_string(),
+                ));
+            }
+
+            info!("Output format: {} (requested {})", candidate, format);
+            return Ok((best_fbc, visual_info_ptr, candidate));
+        }
+
+        Err(ViewError::GLXError(
+            "Failed to choose FB config".to_string(),
+        ))
+    }
+}
+
+// The config of the format with the most MSAA samples
+#[cfg(feature = "gl")]
+unsafe fn choose_fb(display: *mut Display, format: OutputFormat) -> Option<GLXFBConfig> {
+    unsafe {
+        let attribs = fb_attribs(format);
         let mut fb_count = 0;
         let fb_configs = glXChooseFBConfig(
             display,
             XDefaultScreen(display),
-            FB_ATTRIBS.as_ptr(),
+            attribs.as_ptr(),
             addr_of_mut!(fb_count),
         );
+        // Without GLX_ARB_fbconfig_float the float render type may be rejected
+        if format.is_float() && errors::check(display).is_err() {
+            if !fb_configs.is_null() {
+                XFree(fb_configs as *mut _);
+            }
+            return None;
+        }
         if fb_configs.is_null() || fb_count <= 0 {
-            return Err(ViewError::GLXError(
-                "Failed to choose FB config".to_string(),
-            ));
+            return None;
         }
 
-        info!("Selected {} framebuffer configurations", fb_count);
+        info!(
+            "Selected {} framebuffer configurations of the {} format",
+            fb_count, format
+        );
         let (mut best_fbc_index, mut worst_fbc_index, mut best_num_samp, mut worst_num_samp) =
             (-1, -1, -1, 999);
 
@@ -212,7 +332,9 @@ fn select_fb(display: *mut Display) -> Result<((GLXFBConfig, *mut XVisualInfo)),
                 continue;
             }
 
-            let (mut samp_buf, mut samples) = (0, 0);
+            // The configs of the deeper colors match the attributes as well,
+            // so the requested depth is picked exactly
+            let (mut samp_buf, mut samples, mut red_size) = (0, 0, 0);
             glXGetFBConfigAttrib(
                 display,
                 *fb_configs.add(i as usize),
@@ -225,15 +347,26 @@ fn select_fb(display: *mut Display) -> Result<((GLXFBConfig, *mut XVisualInfo)),
                 x11::glx::GLX_SAMPLES,
                 addr_of_mut!(samples),
             );
+            glXGetFBConfigAttrib(
+                display,
+                *fb_configs.add(i as usize),
+                x11::glx::GLX_RED_SIZE,
+                addr_of_mut!(red_size),
+            );
 
             info!(
-                "FBConfig[{}]: Visual ID: {}, Sample Buffers: {}, Samples: {}",
+                "FBConfig[{}]: Visual ID: {}, Sample Buffers: {}, Samples: {}, Red Size: {}",
                 i,
                 (*visual).visualid,
                 samp_buf,
-                samples
+                samples,
+                red_size
             );
+            XFree(visual as *mut _);
 
+            if red_size != format.color_bits() as i32 {
+                continue;
+            }
             if best_fbc_index < 0 || (samp_buf != 0 && samples > best_num_samp) {
                 best_fbc_index = i;
                 best_num_samp = samples;
@@ -242,33 +375,32 @@ fn select_fb(display: *mut Display) -> Result<((GLXFBConfig, *mut XVisualInfo)),
                 worst_fbc_index = i;
                 worst_num_samp = samples;
             }
-
-            XFree(visual as *mut _);
         }
 
-        let best_fbc = *fb_configs.add(best_fbc_index as usize);
+        let best_fbc = (best_fbc_index >= 0).then(|| *fb_configs.add(best_fbc_index as usize));
         XFree(fb_configs as *mut _);
 
-        info!(
-            "Best FBConfig: Index: {}, Sample Buffers: {}, Samples: {}",
-            best_fbc_index, best_num_samp, worst_num_samp
-        );
-
-        // Get visual info for the best FBConfig
-        let visual_info_ptr = glXGetVisualFromFBConfig(display, best_fbc);
-        if visual_info_ptr.is_null() {
-            return Err(ViewError::GLXError(
-                "Failed to get visual info from FBConfig".to_string(),
-            ));
+        if best_fbc.is_some() {
+            info!(
+                "Best FBConfig: Index: {}, Sample Buffers: {}, Samples: {}",
+                best_fbc_index, best_num_samp, worst_num_samp
+            );
         }
-
-        Ok((best_fbc, visual_info_ptr))
+        best_fbc
     }
 }
 
 impl ViewTrait for View {
-    fn open(cfg: ViewConfig, events_sender: Sender<InputEvent>) -> Result<Self, ViewError> {
+    // The defaults below are only read without the gl feature.
+    #[allow(unused_assignments)]
+    fn open(
+        cfg: ViewConfig,
+        output_format: OutputFormat,
+        events_sender: Sender<InputEvent>,
+    ) -> Result<Self, ViewError> {
         unsafe {
+            errors::install();
+
             debug!("Opening X11 display");
             let display = XOpenDisplay(std::ptr::null());
             if display.is_null() {
@@ -279,13 +411,15 @@ impl ViewTrait for View {
             let mut color_map = 0;
             let mut visual_info: *mut XVisualInfo = std::ptr::null_mut();
             let mut fb_config: GLXFBConfig = std::ptr::null_mut();
+            let mut obtained_format = OutputFormat::Rgba8;
 
             #[cfg(feature = "gl")]
             {
-                let (fbc, vi) = select_fb(display)?;
+                let (fbc, vi, format) = select_fb(display, output_format)?;
                 screen_id = (*vi).screen;
                 visual_info = vi;
                 fb_config = fbc;
+                obtained_format = format;
 
                 color_map = XCreateColormap(
                     display,
@@ -316,6 +450,7 @@ impl ViewTrait for View {
                     | ButtonPressMask
                     | ButtonReleaseMask
                     | StructureNotifyMask
+                    | VisibilityChangeMask
                     | PointerMotionMask,
                 do_not_propagate_mask: 0,
                 override_redirect: 0,
@@ -356,16 +491,17 @@ impl ViewTrait for View {
             }
 
             debug!("Setting up X11 window attributes");
-            XStoreName(display, window, b"Title\0".as_ptr() as *const c_char);
-            XSync(display, 0);
+            store_title(display, window, &cfg.title);
+            // Fails on the errors of the window creation (e.g. BadMatch of the visual)
+            errors::check(display)?;
             XAutoRepeatOff(display);
             XClearWindow(display, window);
             XMapRaised(display, window);
 
-            let delete_message =
-                XInternAtom(display, b"WM_DELETE_WINDOW\0".as_ptr() as *const c_char, 0);
+            let delete_message = XInternAtom(display, c"WM_DELETE_WINDOW".as_ptr(), 0);
             XSetWMProtocols(display, window, &delete_message as *const _ as *mut _, 1);
 
+            let control = output::DisplayConnection::open()?;
             let stop_signal = Arc::new(AtomicBool::new(false));
 
             let signal_stop = stop_signal.clone();
@@ -401,15 +537,27 @@ impl ViewTrait for View {
                 })?;
 
             info!("X11 Window created successfully");
-            Ok(View {
+            let mut view = View {
                 display,
                 window,
                 fb_config,
+                output_format: obtained_format,
                 color_map,
                 delete_message,
+                width: cfg.width,
+                height: cfg.height,
+                geometry: ViewGeometry::Windowed,
+                original_mode: None,
+                control,
+                cursor: 0,
                 stop_signal: stop_signal.clone(),
                 events_thread: Some(events_thread),
-            })
+            };
+
+            if cfg.geometry != ViewGeometry::Windowed {
+                view.set_geometry(cfg.geometry)?;
+            }
+            Ok(view)
         }
     }
 
@@ -418,33 +566,239 @@ impl ViewTrait for View {
             display: self.display,
             window: self.window,
             fbc: self.fb_config,
+            output_format: self.output_format,
             ctx: None,
         }
     }
 
     fn tick(&mut self) -> TickResult {
-        /* if the events thread is dead, we need to stop as well */
-        if let Some(ref thread) = self.events_thread {
-            if thread.is_finished() {
-                debug!("X11 events thread is dead, stopping window tick");
-                return TickResult::Closed;
+        let closing = self
+            .events_thread
+            .as_ref()
+            .is_some_and(|thread| thread.is_finished());
+
+        /* The errors are recorded by the handler, whatever thread has received them */
+        for error in errors::take(self.display) {
+            match errors::classify(&error, self.window, closing) {
+                XErrorAction::Ignore => debug!("Ignoring X error while closing: {}", error),
+                XErrorAction::Log => warn!("X error: {}", error),
+                XErrorAction::Fail => return TickResult::Failed(ViewError::XError(error)),
             }
         }
 
+        /* if the events thread is dead, we need to stop as well */
+        if closing {
+            debug!("X11 events thread is dead, stopping window tick");
+            return TickResult::Closed;
+        }
+
         TickResult::Continue
     }
 
-    fn set_size(&self, width: usize, height: usize) {
-        todo!()
+    fn error_box(title: &str, message: &str) -> bool {
+        // Xlib has no dialogs, so the first available desktop tool is used.
+        // The tool failing to start means it is not installed, the exit code
+        // is not checked, since dismissing the box is not a failure.
+        let tools: [(&str, Vec<String>); 3] = [
+            (
+                "zenity",
+                vec![
+                    "--error".into(),
+                    "--no-markup".into(),
+                    format!("--title={}", title),
+                    format!("--text={}", message),
+                ],
+            ),
+            (
+                "kdialog",
+                vec![
+                    "--title".into(),
+                    title.into(),
+                    "--error".into(),
+                    message.into(),
+                ],
+            ),
+            (
+                "xmessage",
+                vec!["-center".into(), format!("{}\n\n{}", title, message)],
+            ),
+        ];
+        for (tool, args) in tools {
+            if std::process::Command::new(tool).args(args).status().is_ok() {
+                return true;
+            }
+        }
+        false
     }
 
-    fn set_title(&self, title: &str) {
-        todo!()
+    fn enumerate_monitors() -> Result<Vec<Monitor>, ViewError> {
+        let conn = output::DisplayConnection::open()?;
+        Ok(output::enumerate(&conn)?
+            .into_iter()
+            .map(|raw| raw.monitor)
+            .collect())
+    }
+
+    fn set_geometry(&mut self, geometry: ViewGeometry) -> Result<(), ViewError> {
+        let conn = output::DisplayConnection::open()?;
+
+        // Leaving the exclusive fullscreen (or switching its mode),
+        // restore the original mode first
+        if let Some((crtc, mode)) = self.original_mode.take() {
+            output::set_mode(&conn, crtc, mode)?;
+        }
+
+        match &geometry {
+            ViewGeometry::Windowed => unsafe {
+                info!("Switching to windowed mode");
+                output::set_fullscreen(&conn, self.window, false);
+                XResizeWindow(
+                    conn.0,
+                    self.window,
+                    self.width as c_uint,
+                    self.height as c_uint,
+                );
+            },
+            ViewGeometry::BorderlessFullscreen { monitor } => unsafe {
+                let raw = output::find(&conn, monitor.as_deref())?;
+                info!("Switching to borderless fullscreen on {}", raw.monitor.name);
+
+                // The window manager makes the window fullscreen
+                // on the monitor it's placed on
+                XMoveResizeWindow(
+                    conn.0,
+                    self.window,
+                    raw.monitor.x,
+                    raw.monitor.y,
+                    raw.monitor.mode.width as c_uint,
+                    raw.monitor.mode.height as c_uint,
+                );
+                output::set_fullscreen(&conn, self.window, true);
+            },
+            ViewGeometry::Fullscreen { monitor, mode } => unsafe {
+                let raw = output::find(&conn, monitor.as_deref())?;
+                let mode_id = raw
+                    .find_mode(mode)
+                    .ok_or_else(|| ViewError::VideoModeNotSupported(raw.monitor.name.clone()))?;
+                info!(
+                    "Switching to fullscreen on {}: {}x{}@{:.2}",
+                    raw.monitor.name, mode.width, mode.height, mode.refresh_rate
+                );
+
+                output::set_mode(&conn, raw.crtc, mode_id)?;
+                self.original_mode = Some((raw.crtc, raw.current_mode));
+
+                XMoveResizeWindow(
+                    conn.0,
+                    self.window,
+                    raw.monitor.x,
+                    raw.monitor.y,
+                    mode.width as c_uint,
+                    mode.height as c_uint,
+                );
+                output::set_fullscreen(&conn, self.window, true);
+            },
+        }
+
+        unsafe { errors::check(conn.0) }?;
+        self.geometry = geometry;
+        Ok(())
+    }
+
+    fn set_size(&mut self, width: usize, height: usize) -> Result<(), ViewError> {
+        self.width = width;
+        self.height = height;
+        if self.geometry != ViewGeometry::Windowed {
+            return Ok(());
+        }
+
+        unsafe {
+            XResizeWindow(
+                self.control.0,
+                self.window,
+                width as c_uint,
+                height as c_uint,
+            );
+            errors::check(self.control.0)
+        }
+    }
+
+    fn set_size_limits(
+        &mut self,
+        min: Option<(usize, usize)>,
+        max: Option<(usize, usize)>,
+    ) -> Result<(), ViewError> {
+        unsafe {
+            let mut hints: XSizeHints = std::mem::zeroed();
+            if let Some((width, height)) = min {
+                hints.flags |= PMinSize;
+                hints.min_width = width as c_int;
+                hints.min_height = height as c_int;
+            }
+            if let Some((width, height)) = max {
+                hints.flags |= PMaxSize;
+                hints.max_width = width as c_int;
+                hints.max_height = height as c_int;
+            }
+            XSetWMNormalHints(self.control.0, self.window, &mut hints);
+            errors::check(self.control.0)
+        }
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), ViewError> {
+        unsafe {
+            store_title(self.control.0, self.window, title);
+            errors::check(self.control.0)
+        }
+    }
+
+    fn set_cursor(&mut self, cursor: &Cursor) -> Result<(), ViewError> {
+        unsafe {
+            let id = cursor::create(self.control.0, self.window, cursor)?;
+            XDefineCursor(self.control.0, self.window, id);
+            /* The window keeps the cursor it is using */
+            if self.cursor != 0 {
+                XFreeCursor(self.control.0, self.cursor);
+            }
+            self.cursor = id;
+            errors::check(self.control.0)
+        }
+    }
+}
+
+/* The legacy WM_NAME is Latin-1, the window managers prefer the UTF-8 _NET_WM_NAME */
+unsafe fn store_title(display: *mut Display, window: xlib::Window, title: &str) {
+    let title = title.replace('\0', "");
+    let c_title = CString::new(title.as_str()).unwrap_or_default();
+    unsafe {
+        XStoreName(display, window, c_title.as_ptr());
+        let net_wm_name = XInternAtom(display, c"_NET_WM_NAME".as_ptr(), 0);
+        let utf8_string = XInternAtom(display, c"UTF8_STRING".as_ptr(), 0);
+        XChangeProperty(
+            display,
+            window,
+            net_wm_name,
+            utf8_string,
+            8,
+            PropModeReplace,
+            title.as_ptr(),
+            title.len() as c_int,
+        );
     }
 }
 
 impl Drop for View {
     fn drop(&mut self) {
+        /* Do not leave the monitor in the fullscreen video mode */
+        if let Some((crtc, mode)) = self.original_mode.take() {
+            debug!("Restoring the original video mode");
+            let restored = output::DisplayConnection::open()
+                .and_then(|conn| output::set_mode(&conn, crtc, mode));
+            if let Err(e) = restored {
+                warn!("Failed to restore the video mode: {}", e);
+            }
+        }
+
         /* If the events thread is running,
          * signal it to stop */
         if let Some(thread) = self.events_thread.take() {
@@ -456,7 +810,7 @@ impl Drop for View {
                 event.type_ = ClientMessage;
                 event.client_message.window = self.window;
                 event.client_message.message_type =
-                    XInternAtom(self.display, b"WM_PROTOCOLS\0".as_ptr() as *const c_char, 1);
+                    XInternAtom(self.display, c"WM_PROTOCOLS".as_ptr(), 1);
                 event.client_message.format = 32;
                 event
                     .client_message
@@ -484,6 +838,10 @@ impl Drop for View {
         }
 
         unsafe {
+            if self.cursor != 0 {
+                XFreeCursor(self.control.0, self.cursor);
+            }
+
             debug!("Destroying X11 window");
             XAutoRepeatOn(self.display);
 
@@ -497,6 +855,7 @@ impl Drop for View {
             if !self.display.is_null() {
                 debug!("Closing X11 display");
                 XCloseDisplay(self.display);
+                errors::discard(self.display);
             }
         }
     }
@@ -506,19 +865,24 @@ pub struct ViewHandle {
     display: *mut Display,
     window: xlib::Window,
     fbc: GLXFBConfig,
+    output_format: OutputFormat,
     #[cfg(feature = "gl")]
     ctx: Option<GLXContext>,
 }
 
 #[cfg(feature = "gl")]
 impl ViewHandleOpenGL for ViewHandle {
-    fn create_context(&mut self, fps: usize, vsync: bool) -> Result<(), crate::view::ViewError> {
+    fn create_context(&mut self, _fps: usize, _vsync: bool) -> Result<(), crate::view::ViewError> {
         unsafe {
             debug!("Creating GLX context");
             let ctx = glXCreateNewContext(
                 self.display,
                 self.fbc,
-                x11::glx::GLX_RGBA_TYPE as c_int,
+                if self.output_format.is_float() {
+                    GLX_RGBA_FLOAT_TYPE_ARB
+                } else {
+                    x11::glx::GLX_RGBA_TYPE as c_int
+                },
                 std::ptr::null_mut(),
                 1, // Direct rendering
             );
@@ -529,7 +893,11 @@ impl ViewHandleOpenGL for ViewHandle {
                 ));
             }
 
-            XSync(self.display, 0);
+            // E.g. BadMatch of the FB config not compatible with the window
+            if let Err(e) = errors::check(self.display) {
+                x11::glx::glXDestroyContext(self.display, ctx);
+                return Err(e);
+            }
 
             self.ctx = Some(ctx);
 
@@ -578,6 +946,30 @@ impl ViewHandleOpenGL for ViewHandle {
             Ok(())
         }
     }
+
+    fn output_format(&self) -> OutputFormat {
+        self.output_format
+    }
+}
+
+#[cfg(feature = "vulkan")]
+impl crate::vulkan::ViewHandleVulkan for ViewHandle {
+    fn surface_extension(&self) -> &'static std::ffi::CStr {
+        ash::khr::xlib_surface::NAME
+    }
+
+    fn create_surface(
+        &self,
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+    ) -> Result<ash::vk::SurfaceKHR, ash::vk::Result> {
+        let info = ash::vk::XlibSurfaceCreateInfoKHR::default()
+            .dpy(self.display as *mut ash::vk::Display)
+            .window(self.window);
+        unsafe {
+            ash::khr::xlib_surface::Instance::new(entry, instance).create_xlib_surface(&info, None)
+        }
+    }
 }
 
 #[cfg(feature = "gl")]
@@ -0,0 +1,259 @@
+use crate::view::x11::ViewError;
+use crate::view::{Monitor, VideoMode};
+use log::debug;
+use std::ffi::CStr;
+use x11::xlib::{
+    ClientMessage, CurrentTime, Display, SubstructureNotifyMask, SubstructureRedirectMask,
+    XCloseDisplay, XDefaultRootWindow, XEvent, XInternAtom, XOpenDisplay, XSendEvent, XSync,
+};
+use x11::xrandr::{
+    RRCrtc, RRMode, RR_Connected, RR_DoubleScan, RR_Interlace, XRRCrtcInfo, XRRFreeCrtcInfo,
+    XRRFreeOutputInfo, XRRFreeScreenResources, XRRGetCrtcInfo, XRRGetOutputInfo,
+    XRRGetOutputPrimary, XRRGetScreenResourcesCurrent, XRRModeInfo, XRRScreenResources,
+    XRRSetCrtcConfig,
+};
+
+/* The view's display connection is owned by the events thread,
+ * that is blocked in XNextEvent most of the time.
+ * So the outputs are configured via a separate connection */
+pub(super) struct DisplayConnection(pub(super) *mut Display);
+
+impl DisplayConnection {
+    pub(super) fn open() -> Result<Self, ViewError> {
+        super::errors::install();
+        let display = unsafe { XOpenDisplay(std::ptr::null()) };
+        if display.is_null() {
+            return Err(ViewError::OpenDisplayError);
+        }
+        Ok(DisplayConnection(display))
+    }
+}
+
+impl Drop for DisplayConnection {
+    fn drop(&mut self) {
+        unsafe {
+            XSync(self.0, 0);
+            XCloseDisplay(self.0);
+        }
+        super::errors::discard(self.0);
+    }
+}
+
+/// Monitor with the XRandR IDs required to change its mode.
+pub(super) struct RawMonitor {
+    pub(super) monitor: Monitor,
+    pub(super) crtc: RRCrtc,
+    pub(super) current_mode: RRMode,
+    modes: Vec<(RRMode, VideoMode)>,
+}
+
+impl RawMonitor {
+    pub(super) fn find_mode(&self, mode: &VideoMode) -> Option<RRMode> {
+        self.modes
+            .iter()
+            .find(|(_, m)| m.matches(mode))
+            .map(|(id, _)| *id)
+    }
+}
+
+fn convert_mode(info: &XRRModeInfo) -> VideoMode {
+    let mut v_total = info.vTotal as f32;
+    if info.modeFlags & RR_DoubleScan as u64 != 0 {
+        v_total *= 2.0;
+    }
+    if info.modeFlags & RR_Interlace as u64 != 0 {
+        v_total /= 2.0;
+    }
+
+    let refresh_rate = if info.hTotal != 0 && v_total != 0.0 {
+        info.dotClock as f32 / (info.hTotal as f32 * v_total)
+    } else {
+        0.0
+    };
+
+    VideoMode {
+        width: info.width as usize,
+        height: info.height as usize,
+        refresh_rate,
+    }
+}
+
+/// Lists the connected and active outputs of the screen.
+pub(super) fn enumerate(conn: &DisplayConnection) -> Result<Vec<RawMonitor>, ViewError> {
+    unsafe {
+        let root = XDefaultRootWindow(conn.0);
+        let resources = XRRGetScreenResourcesCurrent(conn.0, root);
+        if resources.is_null() {
+            return Err(ViewError::XRandRError(
+                "Failed to get screen resources".to_string(),
+            ));
+        }
+
+        let res = &*resources;
+        let primary = XRRGetOutputPrimary(conn.0, root);
+        let all_modes = std::slice::from_raw_parts(res.modes, res.nmode as usize);
+        let outputs = std::slice::from_raw_parts(res.outputs, res.noutput as usize);
+
+        let mut monitors = Vec::new();
+        for &output in outputs {
+            let info = XRRGetOutputInfo(conn.0, resources, output);
+            if info.is_null() {
+                continue;
+            }
+
+            // Skip the disconnected and disabled outputs
+            if (*info).connection != RR_Connected as u16 || (*info).crtc == 0 {
+                XRRFreeOutputInfo(info);
+                continue;
+            }
+
+            let crtc_info = XRRGetCrtcInfo(conn.0, resources, (*info).crtc);
+            if crtc_info.is_null() {
+                XRRFreeOutputInfo(info);
+                continue;
+            }
+
+            let name = CStr::from_ptr((*info).name).to_string_lossy().to_string();
+            let output_modes = std::slice::from_raw_parts((*info).modes, (*info).nmode as usize);
+            let modes: Vec<(RRMode, VideoMode)> = output_modes
+                .iter()
+                .filter_map(|id| all_modes.iter().find(|m| m.id == *id))
+                .map(|m| (m.id, convert_mode(m)))
+                .collect();
+            let current = modes
+                .iter()
+                .find(|(id, _)| *id == (*crtc_info).mode)
+                .map(|(_, mode)| *mode)
+                .unwrap_or(VideoMode {
+                    width: (*crtc_info).width as usize,
+                    height: (*crtc_info).height as usize,
+                    refresh_rate: 0.0,
+                });
+
+            debug!("Found output {} ({} modes)", name, modes.len());
+            monitors.push(RawMonitor {
+                monitor: Monitor {
+                    name,
+                    primary: output == primary,
+                    x: (*crtc_info).x,
+                    y: (*crtc_info).y,
+                    mode: current,
+                    modes: modes.iter().map(|(_, mode)| *mode).collect(),
+                },
+                crtc: (*info).crtc,
+                current_mode: (*crtc_info).mode,
+                modes,
+            });
+
+            XRRFreeCrtcInfo(crtc_info);
+            XRRFreeOutputInfo(info);
+        }
+
+        XRRFreeScreenResources(resources);
+        Ok(monitors)
+    }
+}
+
+/// Finds the monitor by name, or the primary one if no name is given.
+pub(super) fn find(conn: &DisplayConnection, name: Option<&str>) -> Result<RawMonitor, ViewError> {
+    let monitors = enumerate(conn)?;
+    let found = match name {
+        Some(name) => monitors.into_iter().find(|m| m.monitor.name == name),
+        // Some setups have no primary output, fall back to the first one
+        None => {
+            let mut monitors = monitors;
+            match monitors.iter().position(|m| m.monitor.primary) {
+                Some(index) => Some(monitors.swap_remove(index)),
+                None => monitors.into_iter().next(),
+            }
+        }
+    };
+
+    found.ok_or_else(|| ViewError::MonitorNotFound(name.unwrap_or("primary").to_string()))
+}
+
+/// Switches the CRTC driving the monitor to the given mode.
+pub(super) fn set_mode(
+    conn: &DisplayConnection,
+    crtc: RRCrtc,
+    mode: RRMode,
+) -> Result<(), ViewError> {
+    unsafe {
+        let root = XDefaultRootWindow(conn.0);
+        let resources: *mut XRRScreenResources = XRRGetScreenResourcesCurrent(conn.0, root);
+        if resources.is_null() {
+            return Err(ViewError::XRandRError(
+                "Failed to get screen resources".to_string(),
+            ));
+        }
+
+        let crtc_info: *mut XRRCrtcInfo = XRRGetCrtcInfo(conn.0, resources, crtc);
+        if crtc_info.is_null() {
+            XRRFreeScreenResources(resources);
+            return Err(ViewError::XRandRError(
+                "Failed to get CRTC info".to_string(),
+            ));
+        }
+
+        // Keep the position, rotation and the outputs of the CRTC
+        let status = XRRSetCrtcConfig(
+            conn.0,
+            resources,
+            crtc,
+            CurrentTime,
+            (*crtc_info).x,
+            (*crtc_info).y,
+            mode,
+            (*crtc_info).rotation,
+            (*crtc_info).outputs,
+            (*crtc_info).noutput,
+        );
+
+        XRRFreeCrtcInfo(crtc_info);
+        XRRFreeScreenResources(resources);
+
+        // RRSetConfigSuccess
+        if status != 0 {
+            return Err(ViewError::XRandRError(format!(
+                "Failed to set CRTC mode, status {}",
+                status
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Asks the window manager to add or remove the fullscreen state (EWMH).
+pub(super) fn set_fullscreen(
+    conn: &DisplayConnection,
+    window: x11::xlib::Window,
+    fullscreen: bool,
+) {
+    // _NET_WM_STATE_REMOVE / _NET_WM_STATE_ADD
+    let action = if fullscreen { 1 } else { 0 };
+
+    unsafe {
+        let mut event: XEvent = std::mem::zeroed();
+        event.type_ = ClientMessage;
+        event.client_message.window = window;
+        event.client_message.message_type = XInternAtom(conn.0, c"_NET_WM_STATE".as_ptr(), 0);
+        event.client_message.format = 32;
+        event.client_message.data.set_long(0, action);
+        event.client_message.data.set_long(
+            1,
+            XInternAtom(conn.0, c"_NET_WM_STATE_FULLSCREEN".as_ptr(), 0) as i64,
+        );
+        event.client_message.data.set_long(2, 0);
+        // Source indication: normal application
+        event.client_message.data.set_long(3, 1);
+
+        XSendEvent(
+            conn.0,
+            XDefaultRootWindow(conn.0),
+            0,
+            SubstructureRedirectMask | SubstructureNotifyMask,
+            &mut event,
+        );
+    }
+}
@@ -0,0 +1,551 @@
+//! Experimental Vulkan backend. Manages the instance, the device and the
+//! swapchain of the view and executes the frames: clears the content and the
+//! bars around it and presents the image. Nothing else is rendered.
+//! It's not selectable by the `Renderer` yet: the render passes and the GPU
+//! assets are implemented for OpenGL only, so the render pipelines always run
+//! on the `gl` backend (see `RendererBackend`).
+
+mod swapchain;
+
+use crate::passes::events::PassEventTrait;
+use crate::renderer::backend::RendererBackendTrait;
+use crate::renderer::target::ContentRect;
+use crate::view::ViewHandle;
+use ash::vk;
+use log::{debug, info, warn};
+use std::ffi::{c_char, CStr};
+use std::fmt::{Display, Formatter};
+use swapchain::Swapchain;
+
+/// Frames recorded while the previous ones are still executed by the GPU.
+const FRAMES_IN_FLIGHT: usize = 2;
+
+/// Instance and surface of the platform view (see `view` module).
+pub(crate) trait ViewHandleVulkan {
+    /// Instance extension the surface of the view is created with.
+    fn surface_extension(&self) -> &'static CStr;
+    fn create_surface(
+        &self,
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+    ) -> Result<vk::SurfaceKHR, vk::Result>;
+}
+
+pub struct VulkanRendererConfig {
+    /// Wait for the vertical blank when presenting (FIFO present mode).
+    /// Otherwise the mailbox or the immediate mode is used if supported.
+    pub vsync: bool,
+    /// Fixed aspect ratio (width / height) of the content, same as for the `gl` backend.
+    pub aspect_ratio: Option<f32>,
+    /// Color of the bars around the content, RGB.
+    pub bars_color: [f32; 3],
+    /// Color the content is cleared to at the beginning of the frame, RGBA.
+    pub clear_color: [f32; 4],
+    /// Enable `VK_LAYER_KHRONOS_validation` if it's installed.
+    pub validation: bool,
+}
+
+impl Default for VulkanRendererConfig {
+    fn default() -> Self {
+        VulkanRendererConfig {
+            vsync: true,
+            aspect_ratio: None,
+            bars_color: [0.0; 3],
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            validation: cfg!(debug_assertions),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum VulkanRendererError {
+    /// The Vulkan loader is not installed.
+    LoadingError(String),
+    VulkanError(vk::Result),
+    /// No device can present to the surface of the view.
+    NoSuitableDevice,
+}
+
+impl Display for VulkanRendererError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VulkanRendererError::LoadingError(e) => write!(f, "Failed to load Vulkan: {}", e),
+            VulkanRendererError::VulkanError(e) => write!(f, "Vulkan error: {}", e),
+            VulkanRendererError::NoSuitableDevice => {
+                write!(f, "No device can present to the view")
+            }
+        }
+    }
+}
+
+impl std::error::Error for VulkanRendererError {}
+
+impl From<vk::Result> for VulkanRendererError {
+    fn from(result: vk::Result) -> Self {
+        VulkanRendererError::VulkanError(result)
+    }
+}
+
+// Synchronization and the commands of the frame in flight
+struct Frame {
+    commands: vk::CommandBuffer,
+    image_available: vk::Semaphore,
+    in_flight: vk::Fence,
+}
+
+pub struct VulkanRenderer<E: PassEventTrait> {
+    _marker: std::marker::PhantomData<E>,
+
+    // Destroyed in the reverse order in `Drop`
+    _entry: ash::Entry,
+    instance: ash::Instance,
+    surface_instance: ash::khr::surface::Instance,
+    surface: vk::SurfaceKHR,
+    physical_device: vk::PhysicalDevice,
+    device: ash::Device,
+    queue: vk::Queue,
+    render_pass: vk::RenderPass,
+    command_pool: vk::CommandPool,
+    frames: Vec<Frame>,
+    frame: usize,
+    swapchain: Option<Swapchain>,
+    // Image acquired by `before_frame`, `None` while the view is minimized
+    image: Option<u32>,
+
+    view_size: (usize, usize),
+    content_rect: ContentRect,
+    config: VulkanRendererConfig,
+}
+
+impl<E: PassEventTrait> RendererBackendTrait<E> for VulkanRenderer<E> {
+    type Config = VulkanRendererConfig;
+    type Error = VulkanRendererError;
+
+    fn new(
+        config: VulkanRendererConfig,
+        view_handle: ViewHandle,
+    ) -> Result<Self, VulkanRendererError> {
+        unsafe {
+            let entry =
+                ash::Entry::load().map_err(|e| VulkanRendererError::LoadingError(e.to_string()))?;
+            let instance = create_instance(&entry, &view_handle, config.validation)?;
+            let surface_instance = ash::khr::surface::Instance::new(&entry, &instance);
+            let surface = match view_handle.create_surface(&entry, &instance) {
+                Ok(surface) => surface,
+                Err(e) => {
+                    instance.destroy_instance(None);
+                    return Err(e.into());
+                }
+            };
+
+            let Some((physical_device, family)) =
+                pick_device(&instance, &surface_instance, surface)
+            else {
+                surface_instance.destroy_surface(surface, None);
+                instance.destroy_instance(None);
+                return Err(VulkanRendererError::NoSuitableDevice);
+            };
+
+            let priorities = [1.0];
+            let queues = [vk::DeviceQueueCreateInfo::default()
+                .queue_family_index(family)
+                .queue_priorities(&priorities)];
+            let extensions = [ash::khr::swapchain::NAME.as_ptr()];
+            let device = instance.create_device(
+                physical_device,
+                &vk::DeviceCreateInfo::default()
+                    .queue_create_infos(&queues)
+                    .enabled_extension_names(&extensions),
+                None,
+            )?;
+            let queue = device.get_device_queue(family, 0);
+
+            // The resources created from now on are destroyed by `Drop`
+            let mut renderer = VulkanRenderer {
+                _marker: Default::default(),
+                _entry: entry,
+                instance,
+                surface_instance,
+                surface,
+                physical_device,
+                device,
+                queue,
+                render_pass: vk::RenderPass::null(),
+                command_pool: vk::CommandPool::null(),
+                frames: Vec::new(),
+                frame: 0,
+                swapchain: None,
+                image: None,
+                view_size: (0, 0),
+                content_rect: ContentRect::default(),
+                config,
+            };
+
+            let format =
+                swapchain::choose_format(&renderer.surface_instance, physical_device, surface)?;
+            renderer.render_pass = create_render_pass(&renderer.device, format.format)?;
+            renderer.command_pool = renderer.device.create_command_pool(
+                &vk::CommandPoolCreateInfo::default()
+                    .queue_family_index(family)
+                    .flags(vk::CommandPoolCreateFlags::RESET_COMMAND_BUFFER),
+                None,
+            )?;
+            let buffers = renderer.device.allocate_command_buffers(
+                &vk::CommandBufferAllocateInfo::default()
+                    .command_pool(renderer.command_pool)
+                    .level(vk::CommandBufferLevel::PRIMARY)
+                    .command_buffer_count(FRAMES_IN_FLIGHT as u32),
+            )?;
+            for commands in buffers {
+                let image_available = renderer
+                    .device
+                    .create_semaphore(&vk::SemaphoreCreateInfo::default(), None)?;
+                let in_flight = renderer.device.create_fence(
+                    &vk::FenceCreateInfo::default().flags(vk::FenceCreateFlags::SIGNALED),
+                    None,
+                );
+                let in_flight = match in_flight {
+                    Ok(fence) => fence,
+                    Err(e) => {
+                        renderer.device.destroy_semaphore(image_available, None);
+                        return Err(e.into());
+                    }
+                };
+                renderer.frames.push(Frame {
+                    commands,
+                    image_available,
+                    in_flight,
+                });
+            }
+
+            Ok(renderer)
+        }
+    }
+
+    fn before_frame(&mut self) -> Result<(), VulkanRendererError> {
+        self.image = None;
+        let Some(swapchain) = &self.swapchain else {
+            // Not resized yet or minimized
+            return Ok(());
+        };
+
+        unsafe {
+            let frame = &self.frames[self.frame];
+            self.device
+                .wait_for_fences(&[frame.in_flight], true, u64::MAX)?;
+
+            let image = match swapchain.acquire(frame.image_available) {
+                Ok(image) => image,
+                // Recreated with the current size on the next resize
+                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                    debug!("Swapchain is out of date, skipping the frame");
+                    return Ok(());
+                }
+                Err(e) => return Err(e.into()),
+            };
+            self.device.reset_fences(&[frame.in_flight])?;
+            self.image = Some(image);
+
+            self.device
+                .reset_command_buffer(frame.commands, vk::CommandBufferResetFlags::empty())?;
+            self.device
+                .begin_command_buffer(frame.commands, &vk::CommandBufferBeginInfo::default())?;
+
+            // User will handle clearing the content in the render passes,
+            // here the bars are cleared with the whole image
+            let [r, g, b] = self.config.bars_color;
+            let clear = [vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [r, g, b, 1.0],
+                },
+            }];
+            let extent = swapchain.extent;
+            self.device.cmd_begin_render_pass(
+                frame.commands,
+                &vk::RenderPassBeginInfo::default()
+                    .render_pass(self.render_pass)
+                    .framebuffer(swapchain.framebuffers[image as usize])
+                    .render_area(vk::Rect2D {
+                        offset: vk::Offset2D::default(),
+                        extent,
+                    })
+                    .clear_values(&clear),
+                vk::SubpassContents::INLINE,
+            );
+
+            let content = self.content_rect;
+            self.device.cmd_clear_attachments(
+                frame.commands,
+                &[vk::ClearAttachment {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    color_attachment: 0,
+                    clear_value: vk::ClearValue {
+                        color: vk::ClearColorValue {
+                            float32: self.config.clear_color,
+                        },
+                    },
+                }],
+                &[vk::ClearRect {
+                    rect: vk::Rect2D {
+                        offset: vk::Offset2D {
+                            x: content.x as i32,
+                            y: content.y as i32,
+                        },
+                        extent: vk::Extent2D {
+                            width: (content.width as u32).min(extent.width),
+                            height: (content.height as u32).min(extent.height),
+                        },
+                    },
+                    base_array_layer: 0,
+                    layer_count: 1,
+                }],
+            );
+        }
+
+        Ok(())
+    }
+
+    fn after_frame(&mut self) -> Result<(), VulkanRendererError> {
+        let (Some(image), Some(swapchain)) = (self.image.take(), &self.swapchain) else {
+            return Ok(());
+        };
+
+        unsafe {
+            let frame = &self.frames[self.frame];
+            self.device.cmd_end_render_pass(frame.commands);
+            self.device.end_command_buffer(frame.commands)?;
+
+            let render_finished = swapchain.render_finished[image as usize];
+            let wait = [frame.image_available];
+            let stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
+            let commands = [frame.commands];
+            let signal = [render_finished];
+            self.device.queue_submit(
+                self.queue,
+                &[vk::SubmitInfo::default()
+                    .wait_semaphores(&wait)
+                    .wait_dst_stage_mask(&stages)
+                    .command_buffers(&commands)
+                    .signal_semaphores(&signal)],
+                frame.in_flight,
+            )?;
+
+            match swapchain.present(self.queue, image, render_finished) {
+                Ok(false) => {}
+                // Recreated with the current size on the next resize
+                Ok(true) | Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                    debug!("Swapchain no longer matches the surface");
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        self.frame = (self.frame + 1) % FRAMES_IN_FLIGHT;
+        Ok(())
+    }
+
+    fn resize(&mut self, width: usize, height: usize) -> Result<ContentRect, VulkanRendererError> {
+        debug!("Resizing view to {}x{}", width, height);
+        self.view_size = (width, height);
+        self.content_rect = ContentRect::fit(width, height, self.config.aspect_ratio);
+
+        unsafe {
+            self.device.device_wait_idle()?;
+            let old = self.swapchain.take();
+            let swapchain = if width == 0 || height == 0 {
+                None
+            } else {
+                Some(Swapchain::new(
+                    &self.instance,
+                    &self.device,
+                    &self.surface_instance,
+                    self.physical_device,
+                    self.surface,
+                    self.render_pass,
+                    vk::Extent2D {
+                        width: width as u32,
+                        height: height as u32,
+                    },
+                    self.config.vsync,
+                    old.as_ref(),
+                )?)
+            };
+            if let Some(old) = old {
+                old.destroy(&self.device);
+            }
+            self.swapchain = swapchain;
+        }
+
+        Ok(self.content_rect)
+    }
+}
+
+impl<E: PassEventTrait> VulkanRenderer<E> {
+    /// Size of the view the swapchain is created for.
+    pub fn view_size(&self) -> (usize, usize) {
+        self.view_size
+    }
+
+    /// Part of the view the content is rendered to.
+    pub fn content_rect(&self) -> ContentRect {
+        self.content_rect
+    }
+}
+
+impl<E: PassEventTrait> Drop for VulkanRenderer<E> {
+    fn drop(&mut self) {
+        unsafe {
+            if let Err(e) = self.device.device_wait_idle() {
+                warn!("Failed to wait for the device: {}", e);
+            }
+            if let Some(swapchain) = self.swapchain.take() {
+                swapchain.destroy(&self.device);
+            }
+            for frame in self.frames.drain(..) {
+                self.device.destroy_semaphore(frame.image_available, None);
+                self.device.destroy_fence(frame.in_flight, None);
+            }
+            // Frees the command buffers too
+            self.device.destroy_command_pool(self.command_pool, None);
+            self.device.destroy_render_pass(self.render_pass, None);
+            self.device.destroy_device(None);
+            self.surface_instance.destroy_surface(self.surface, None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+const VALIDATION_LAYER: &CStr = c"VK_LAYER_KHRONOS_validation";
+
+unsafe fn create_instance(
+    entry: &ash::Entry,
+    view_handle: &ViewHandle,
+    validation: bool,
+) -> Result<ash::Instance, VulkanRendererError> {
+    unsafe {
+        let version = entry
+            .try_enumerate_instance_version()?
+            .unwrap_or(vk::API_VERSION_1_0);
+        info!(
+            "Vulkan instance version: {}.{}.{}",
+            vk::api_version_major(version),
+            vk::api_version_minor(version),
+            vk::api_version_patch(version)
+        );
+
+        let mut layers: Vec<*const c_char> = Vec::new();
+        if validation {
+            let available = entry.enumerate_instance_layer_properties()?;
+            if available
+                .iter()
+                .any(|layer| layer.layer_name_as_c_str() == Ok(VALIDATION_LAYER))
+            {
+                layers.push(VALIDATION_LAYER.as_ptr());
+            } else {
+                warn!("Vulkan validation layer is not installed");
+            }
+        }
+
+        let extensions = [
+            ash::khr::surface::NAME.as_ptr(),
+            view_handle.surface_extension().as_ptr(),
+        ];
+        let application = vk::ApplicationInfo::default()
+            .engine_name(c"dawn")
+            .api_version(vk::API_VERSION_1_0);
+        Ok(entry.create_instance(
+            &vk::InstanceCreateInfo::default()
+                .application_info(&application)
+                .enabled_layer_names(&layers)
+                .enabled_extension_names(&extensions),
+            None,
+        )?)
+    }
+}
+
+// Prefers the discrete GPUs. Returns the device and the family of the queue
+// supporting both the graphics and the presentation to the surface
+unsafe fn pick_device(
+    instance: &ash::Instance,
+    surface_instance: &ash::khr::surface::Instance,
+    surface: vk::SurfaceKHR,
+) -> Option<(vk::PhysicalDevice, u32)> {
+    unsafe {
+        let devices = instance.enumerate_physical_devices().ok()?;
+        let mut best = None;
+        for device in devices {
+            let properties = instance.get_physical_device_properties(device);
+            let name = properties
+                .device_name_as_c_str()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+
+            let family = instance
+                .get_physical_device_queue_family_properties(device)
+                .iter()
+                .enumerate()
+                .position(|(i, family)| {
+                    family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+                        && surface_instance
+                            .get_physical_device_surface_support(device, i as u32, surface)
+                            .unwrap_or(false)
+                });
+            let Some(family) = family else {
+                debug!("Device {} cannot present to the view", name);
+                continue;
+            };
+
+            let discrete = properties.device_type == vk::PhysicalDeviceType::DISCRETE_GPU;
+            debug!("Found device {} (discrete: {})", name, discrete);
+            if best.is_none() || discrete {
+                best = Some((device, family as u32, name));
+            }
+            if discrete {
+                break;
+            }
+        }
+
+        let (device, family, name) = best?;
+        info!("Using Vulkan device {}", name);
+        Some((device, family))
+    }
+}
+
+// Single subpass clearing the image and leaving it ready for the presentation
+unsafe fn create_render_pass(
+    device: &ash::Device,
+    format: vk::Format,
+) -> Result<vk::RenderPass, vk::Result> {
+    let attachments = [vk::AttachmentDescription::default()
+        .format(format)
+        .samples(vk::SampleCountFlags::TYPE_1)
+        .load_op(vk::AttachmentLoadOp::CLEAR)
+        .store_op(vk::AttachmentStoreOp::STORE)
+        .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+        .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+        .initial_layout(vk::ImageLayout::UNDEFINED)
+        .final_layout(vk::ImageLayout::PRESENT_SRC_KHR)];
+    let color = [vk::AttachmentReference::default()
+        .attachment(0)
+        .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)];
+    let subpasses = [vk::SubpassDescription::default()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .color_attachments(&color)];
+    // The image is acquired asynchronously, wait for it before writing
+    let dependencies = [vk::SubpassDependency::default()
+        .src_subpass(vk::SUBPASS_EXTERNAL)
+        .dst_subpass(0)
+        .src_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+        .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+        .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)];
+
+    unsafe {
+        device.create_render_pass(
+            &vk::RenderPassCreateInfo::default()
+                .attachments(&attachments)
+                .subpasses(&subpasses)
+                .dependencies(&dependencies),
+            None,
+        )
+    }
+}
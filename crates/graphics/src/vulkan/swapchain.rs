@@ -0,0 +1,220 @@
+use ash::vk;
+use log::{debug, info};
+
+/// Images presented to the surface of the view and their framebuffers.
+/// Recreated on each resize of the view.
+pub(super) struct Swapchain {
+    loader: ash::khr::swapchain::Device,
+    handle: vk::SwapchainKHR,
+    pub extent: vk::Extent2D,
+    views: Vec<vk::ImageView>,
+    pub framebuffers: Vec<vk::Framebuffer>,
+    // Signalled when the image is rendered, one per image,
+    // since the presentation does not report when it's done with them
+    pub render_finished: Vec<vk::Semaphore>,
+}
+
+/// Prefers 8-bit BGRA/RGBA in sRGB, otherwise takes the first format of the surface.
+pub(super) fn choose_format(
+    surface_instance: &ash::khr::surface::Instance,
+    physical_device: vk::PhysicalDevice,
+    surface: vk::SurfaceKHR,
+) -> Result<vk::SurfaceFormatKHR, vk::Result> {
+    let formats =
+        unsafe { surface_instance.get_physical_device_surface_formats(physical_device, surface)? };
+    let preferred = formats.iter().find(|format| {
+        matches!(
+            format.format,
+            vk::Format::B8G8R8A8_SRGB | vk::Format::R8G8B8A8_SRGB
+        ) && format.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR
+    });
+    preferred
+        .or(formats.first())
+        .copied()
+        .ok_or(vk::Result::ERROR_FORMAT_NOT_SUPPORTED)
+}
+
+fn choose_present_mode(modes: &[vk::PresentModeKHR], vsync: bool) -> vk::PresentModeKHR {
+    if vsync {
+        // Always supported
+        return vk::PresentModeKHR::FIFO;
+    }
+    [vk::PresentModeKHR::MAILBOX, vk::PresentModeKHR::IMMEDIATE]
+        .into_iter()
+        .find(|mode| modes.contains(mode))
+        .unwrap_or(vk::PresentModeKHR::FIFO)
+}
+
+impl Swapchain {
+    #[allow(clippy::too_many_arguments)]
+    pub unsafe fn new(
+        instance: &ash::Instance,
+        device: &ash::Device,
+        surface_instance: &ash::khr::surface::Instance,
+        physical_device: vk::PhysicalDevice,
+        surface: vk::SurfaceKHR,
+        render_pass: vk::RenderPass,
+        requested: vk::Extent2D,
+        vsync: bool,
+        old: Option<&Swapchain>,
+    ) -> Result<Self, vk::Result> {
+        unsafe {
+            let capabilities = surface_instance
+                .get_physical_device_surface_capabilities(physical_device, surface)?;
+            let modes = surface_instance
+                .get_physical_device_surface_present_modes(physical_device, surface)?;
+            let format = choose_format(surface_instance, physical_device, surface)?;
+            let present_mode = choose_present_mode(&modes, vsync);
+
+            // The surface either dictates the size or lets the swapchain choose it
+            let extent = if capabilities.current_extent.width != u32::MAX {
+                capabilities.current_extent
+            } else {
+                vk::Extent2D {
+                    width: requested.width.clamp(
+                        capabilities.min_image_extent.width,
+                        capabilities.max_image_extent.width,
+                    ),
+                    height: requested.height.clamp(
+                        capabilities.min_image_extent.height,
+                        capabilities.max_image_extent.height,
+                    ),
+                }
+            };
+            let mut images = capabilities.min_image_count + 1;
+            if capabilities.max_image_count > 0 {
+                images = images.min(capabilities.max_image_count);
+            }
+
+            let loader = ash::khr::swapchain::Device::new(instance, device);
+            let handle = loader.create_swapchain(
+                &vk::SwapchainCreateInfoKHR::default()
+                    .surface(surface)
+                    .min_image_count(images)
+                    .image_format(format.format)
+                    .image_color_space(format.color_space)
+                    .image_extent(extent)
+                    .image_array_layers(1)
+                    .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
+                    .image_sharing_mode(vk::SharingMode::EXCLUSIVE)
+                    .pre_transform(capabilities.current_transform)
+                    .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
+                    .present_mode(present_mode)
+                    .clipped(true)
+                    .old_swapchain(old.map_or(vk::SwapchainKHR::null(), |old| old.handle)),
+                None,
+            )?;
+            info!(
+                "Created swapchain {}x{} of {:?} ({:?})",
+                extent.width, extent.height, format.format, present_mode
+            );
+
+            // Partially created swapchain is destroyed on the error
+            let mut swapchain = Swapchain {
+                loader,
+                handle,
+                extent,
+                views: Vec::new(),
+                framebuffers: Vec::new(),
+                render_finished: Vec::new(),
+            };
+            if let Err(e) = swapchain.create_images(device, render_pass, format.format) {
+                swapchain.destroy(device);
+                return Err(e);
+            }
+            Ok(swapchain)
+        }
+    }
+
+    unsafe fn create_images(
+        &mut self,
+        device: &ash::Device,
+        render_pass: vk::RenderPass,
+        format: vk::Format,
+    ) -> Result<(), vk::Result> {
+        unsafe {
+            let images = self.loader.get_swapchain_images(self.handle)?;
+            debug!("Swapchain has {} images", images.len());
+            for image in images {
+                let view = device.create_image_view(
+                    &vk::ImageViewCreateInfo::default()
+                        .image(image)
+                        .view_type(vk::ImageViewType::TYPE_2D)
+                        .format(format)
+                        .subresource_range(vk::ImageSubresourceRange {
+                            aspect_mask: vk::ImageAspectFlags::COLOR,
+                            base_mip_level: 0,
+                            level_count: 1,
+                            base_array_layer: 0,
+                            layer_count: 1,
+                        }),
+                    None,
+                )?;
+                self.views.push(view);
+
+                let attachments = [view];
+                self.framebuffers.push(
+                    device.create_framebuffer(
+                        &vk::FramebufferCreateInfo::default()
+                            .render_pass(render_pass)
+                            .attachments(&attachments)
+                            .width(self.extent.width)
+                            .height(self.extent.height)
+                            .layers(1),
+                        None,
+                    )?,
+                );
+                self.render_finished
+                    .push(device.create_semaphore(&vk::SemaphoreCreateInfo::default(), None)?);
+            }
+            Ok(())
+        }
+    }
+
+    /// Index of the next image, `signal` is signalled once it can be written.
+    pub unsafe fn acquire(&self, signal: vk::Semaphore) -> Result<u32, vk::Result> {
+        unsafe {
+            self.loader
+                .acquire_next_image(self.handle, u64::MAX, signal, vk::Fence::null())
+                .map(|(image, _suboptimal)| image)
+        }
+    }
+
+    /// Presents the image once `wait` is signalled.
+    /// Returns `true` if the swapchain no longer matches the surface.
+    pub unsafe fn present(
+        &self,
+        queue: vk::Queue,
+        image: u32,
+        wait: vk::Semaphore,
+    ) -> Result<bool, vk::Result> {
+        let wait = [wait];
+        let swapchains = [self.handle];
+        let images = [image];
+        unsafe {
+            self.loader.queue_present(
+                queue,
+                &vk::PresentInfoKHR::default()
+                    .wait_semaphores(&wait)
+                    .swapchains(&swapchains)
+                    .image_indices(&images),
+            )
+        }
+    }
+
+    /// The device must be idle.
+    pub unsafe fn destroy(self, device: &ash::Device) {
+        unsafe {
+            for semaphore in self.render_finished {
+                device.destroy_semaphore(semaphore, None);
+            }
+            for framebuffer in self.framebuffers {
+                device.destroy_framebuffer(framebuffer, None);
+            }
+            for view in self.views {
+                device.destroy_image_view(view, None);
+            }
+            self.loader.destroy_swapchain(self.handle, None);
+        }
+    }
+}
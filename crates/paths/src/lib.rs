@@ -0,0 +1,242 @@
+//! Resolution of the paths used by the engine, the tools and the games:
+//! the asset container, the raw asset sources converted by dacgen,
+//! the cache directory and the user data directory.
+//! Each path is taken from the first of:
+//!
+//! 1. The explicit configuration (e.g. the command line), see `Paths::with_*`.
+//! 2. The environment variable (`DAWN_ASSETS`, `DAWN_ASSET_SOURCES`,
+//!    `DAWN_CACHE_DIR`, `DAWN_DATA_DIR`).
+//! 3. The platform default, derived from the name of the application.
+//!    The asset sources have no default.
+//!
+//! ```no_run
+//! use dawn_paths::Paths;
+//!
+//! let paths = Paths::new("dawn");
+//! // ~/.cache/dawn on Linux, unless DAWN_CACHE_DIR is set
+//! let cache = paths.cache_dir().unwrap();
+//! ```
+
+use log::debug;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Path of the asset container.
+pub const ASSETS_ENV: &str = "DAWN_ASSETS";
+/// Directory of the raw assets the container is built from.
+pub const SOURCES_ENV: &str = "DAWN_ASSET_SOURCES";
+/// Directory of the caches (converted assets, shader binaries, etc.).
+pub const CACHE_DIR_ENV: &str = "DAWN_CACHE_DIR";
+/// Directory of the user data (settings, saves, etc.).
+pub const DATA_DIR_ENV: &str = "DAWN_DATA_DIR";
+
+/// Name of the container looked up next to the executable.
+const DEFAULT_CONTAINER: &str = "assets.dac";
+
+/// Version and the enabled features of the crate.
+pub fn build_info() -> dawn_util::build_info::CrateBuildInfo {
+    dawn_util::crate_build_info![]
+}
+
+type EnvLookup = fn(&str) -> Option<OsString>;
+
+#[derive(Debug, Clone)]
+pub struct Paths {
+    app: String,
+    assets: Option<PathBuf>,
+    sources: Option<PathBuf>,
+    cache_dir: Option<PathBuf>,
+    data_dir: Option<PathBuf>,
+    env: EnvLookup,
+}
+
+impl Paths {
+    /// The name of the application is used for the platform default directories,
+    /// so the applications sharing the machine do not clash.
+    pub fn new(app: impl Into<String>) -> Self {
+        Paths {
+            app: app.into(),
+            assets: None,
+            sources: None,
+            cache_dir: None,
+            data_dir: None,
+            env: |name| std::env::var_os(name),
+        }
+    }
+
+    pub fn with_assets(mut self, path: impl Into<PathBuf>) -> Self {
+        self.assets = Some(path.into());
+        self
+    }
+
+    pub fn with_sources(mut self, path: impl Into<PathBuf>) -> Self {
+        self.sources = Some(path.into());
+        self
+    }
+
+    pub fn with_cache_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(path.into());
+        self
+    }
+
+    pub fn with_data_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.data_dir = Some(path.into());
+        self
+    }
+
+    pub fn app(&self) -> &str {
+        &self.app
+    }
+
+    /// Path of the asset container. The configured one is returned as is,
+    /// so the missing file is reported by the reader. The default one
+    /// (`assets.dac` next to the executable) only if it exists.
+    pub fn assets(&self) -> Option<PathBuf> {
+        self.configured(&self.assets, ASSETS_ENV).or_else(|| {
+            let exe = std::env::current_exe().ok()?;
+            let path = exe.parent()?.join(DEFAULT_CONTAINER);
+            path.is_file().then_some(path)
+        })
+    }
+
+    /// Directory of the raw assets, only if configured.
+    pub fn sources(&self) -> Option<PathBuf> {
+        self.configured(&self.sources, SOURCES_ENV)
+    }
+
+    /// Cache directory of the application. Not created.
+    /// `None` if nothing is configured and the home directory is unknown.
+    pub fn cache_dir(&self) -> Option<PathBuf> {
+        self.configured(&self.cache_dir, CACHE_DIR_ENV)
+            .or_else(|| platform::cache_dir(self.env).map(|dir| self.app_dir(dir, "cache")))
+    }
+
+    /// User data directory of the application. Not created.
+    /// `None` if nothing is configured and the home directory is unknown.
+    pub fn data_dir(&self) -> Option<PathBuf> {
+        self.configured(&self.data_dir, DATA_DIR_ENV)
+            .or_else(|| platform::data_dir(self.env).map(|dir| self.app_dir(dir, "data")))
+    }
+
+    fn configured(&self, explicit: &Option<PathBuf>, env: &str) -> Option<PathBuf> {
+        if let Some(path) = explicit {
+            return Some(path.clone());
+        }
+        // The empty variable is treated as unset, so it can be cleared in the shell
+        let path = (self.env)(env).filter(|value| !value.is_empty())?;
+        debug!("Using {} from {}", Path::new(&path).display(), env);
+        Some(PathBuf::from(path))
+    }
+
+    fn app_dir(&self, base: PathBuf, kind: &str) -> PathBuf {
+        // Windows keeps the caches and the data of the application in the same root
+        if cfg!(windows) {
+            base.join(&self.app).join(kind)
+        } else {
+            base.join(&self.app)
+        }
+    }
+}
+
+fn env_path(env: EnvLookup, name: &str) -> Option<PathBuf> {
+    env(name)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{env_path, EnvLookup};
+    use std::path::PathBuf;
+
+    // XDG Base Directory Specification
+    pub fn cache_dir(env: EnvLookup) -> Option<PathBuf> {
+        env_path(env, "XDG_CACHE_HOME").or_else(|| Some(env_path(env, "HOME")?.join(".cache")))
+    }
+
+    pub fn data_dir(env: EnvLookup) -> Option<PathBuf> {
+        env_path(env, "XDG_DATA_HOME")
+            .or_else(|| Some(env_path(env, "HOME")?.join(".local").join("share")))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use super::{env_path, EnvLookup};
+    use std::path::PathBuf;
+
+    pub fn cache_dir(env: EnvLookup) -> Option<PathBuf> {
+        Some(env_path(env, "HOME")?.join("Library").join("Caches"))
+    }
+
+    pub fn data_dir(env: EnvLookup) -> Option<PathBuf> {
+        Some(
+            env_path(env, "HOME")?
+                .join("Library")
+                .join("Application Support"),
+        )
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::{env_path, EnvLookup};
+    use std::path::PathBuf;
+
+    pub fn cache_dir(env: EnvLookup) -> Option<PathBuf> {
+        env_path(env, "LOCALAPPDATA")
+    }
+
+    pub fn data_dir(env: EnvLookup) -> Option<PathBuf> {
+        env_path(env, "APPDATA")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(env: EnvLookup) -> Paths {
+        Paths {
+            env,
+            ..Paths::new("game")
+        }
+    }
+
+    #[test]
+    fn explicit_overrides_env() {
+        let paths = paths(|name| (name == CACHE_DIR_ENV).then(|| "/env/cache".into()));
+        assert_eq!(paths.cache_dir(), Some(PathBuf::from("/env/cache")));
+
+        let paths = paths.with_cache_dir("/explicit/cache");
+        assert_eq!(paths.cache_dir(), Some(PathBuf::from("/explicit/cache")));
+    }
+
+    #[test]
+    fn empty_env_is_unset() {
+        let paths = paths(|name| (name == DATA_DIR_ENV).then(OsString::new));
+        assert_eq!(paths.data_dir(), None);
+        assert_eq!(paths.assets(), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn platform_defaults() {
+        let paths = paths(|name| (name == "HOME").then(|| "/home/user".into()));
+        assert_eq!(
+            paths.cache_dir(),
+            Some(PathBuf::from("/home/user/.cache/game"))
+        );
+        assert_eq!(
+            paths.data_dir(),
+            Some(PathBuf::from("/home/user/.local/share/game"))
+        );
+
+        let paths = self::paths(|name| match name {
+            "HOME" => Some("/home/user".into()),
+            "XDG_CACHE_HOME" => Some("/xdg/cache".into()),
+            _ => None,
+        });
+        assert_eq!(paths.cache_dir(), Some(PathBuf::from("/xdg/cache/game")));
+    }
+}
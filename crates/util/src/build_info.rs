@@ -0,0 +1,43 @@
+use std::fmt::{Display, Formatter};
+
+/// Version and the enabled features of the engine crate.
+/// Each crate provides its own with `build_info()`,
+/// the engine collects them into the runtime report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrateBuildInfo {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub features: Vec<&'static str>,
+}
+
+impl Display for CrateBuildInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name, self.version)?;
+        if !self.features.is_empty() {
+            write!(f, " [{}]", self.features.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Build info of the crate the macro is expanded in.
+/// Lists the features of the crate, the enabled ones are reported.
+/// ```
+/// let info = dawn_util::crate_build_info![];
+/// assert_eq!(info.name, "dawn-util");
+/// assert!(info.features.is_empty());
+/// ```
+#[macro_export]
+macro_rules! crate_build_info {
+    [$($feature:literal),* $(,)?] => {
+        $crate::build_info::CrateBuildInfo {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            features: [$(($feature, cfg!(feature = $feature))),*]
+                .into_iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(feature, _)| feature)
+                .collect(),
+        }
+    };
+}
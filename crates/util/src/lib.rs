@@ -1,4 +1,10 @@
 #![feature(trait_alias)]
 
+pub mod build_info;
 pub mod profile;
 pub mod rendezvous;
+
+/// Version and the enabled features of the crate.
+pub fn build_info() -> build_info::CrateBuildInfo {
+    crate_build_info![]
+}
@@ -94,8 +94,12 @@ impl Stopwatch {
     /// Stops the stopwatch and returns the elapsed time
     #[inline(always)]
     pub fn stop(&mut self) {
-        let elapsed = self.start.elapsed();
+        self.record(self.start.elapsed());
+    }
 
+    /// Adds the time measured elsewhere, e.g. on another thread
+    #[inline(always)]
+    pub fn record(&mut self, elapsed: Duration) {
         // Update the sample
         let old = self.sample.average.as_millis() as f32;
         let new = elapsed.as_millis() as f32;
@@ -0,0 +1,142 @@
+//! Asset stress: loads and frees the whole container in a loop. Every asset type
+//! is loaded by the factory keeping the IR as is, so the reader, the hub and
+//! the scheduler are exercised without the window or the audio device.
+//! After each load all the assets must be loaded, after each free none of them.
+
+use crate::Args;
+use dawn_assets::factory::{BasicFactory, FactoryBinding};
+use dawn_assets::hub::{AssetHub, AssetHubEvent, AssetInfoState};
+use dawn_assets::ir::IRAsset;
+use dawn_assets::requests::{AssetRequest, AssetRequestID, AssetRequestQuery};
+use dawn_assets::{AssetCastable, AssetMemoryUsage};
+use dawn_ecs::events::{ExitEvent, TickEvent};
+use dawn_engine::{EngineBuilder, EngineError};
+use evenio::component::Component;
+use evenio::event::{Receiver, Sender};
+use evenio::fetch::Single;
+use log::info;
+use std::collections::HashSet;
+use std::time::Duration;
+
+struct RawAsset(#[allow(dead_code)] IRAsset);
+
+impl AssetCastable for RawAsset {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Enumerating,
+    Loading(AssetRequestID),
+    Freeing(AssetRequestID),
+}
+
+#[derive(Component)]
+struct AssetsStress {
+    factories: Vec<BasicFactory<RawAsset>>,
+    stage: Stage,
+    cycles: usize,
+    max_cycles: Option<usize>,
+}
+
+impl AssetsStress {
+    fn bind(&mut self, binding: FactoryBinding) {
+        let mut factory = BasicFactory::new();
+        factory.bind(binding);
+        self.factories.push(factory);
+    }
+}
+
+pub(crate) fn run(builder: EngineBuilder, args: &Args) -> Result<(), EngineError> {
+    if args.assets.is_none() {
+        crate::failure("Asset scenario requires the container (--assets or DAWN_ASSETS)");
+        return Ok(());
+    }
+
+    let max_cycles = args.count;
+    builder.run(|world| {
+        let entity = world.spawn();
+        world.insert(
+            entity,
+            AssetsStress {
+                factories: vec![],
+                stage: Stage::Enumerating,
+                cycles: 0,
+                max_cycles,
+            },
+        );
+        world.add_handler(factories_handler);
+        world.add_handler(requests_handler);
+        crate::attach_common(world, args);
+    })
+}
+
+fn factories_handler(_: Receiver<TickEvent>, mut stress: Single<&mut AssetsStress>) {
+    for factory in &mut stress.factories {
+        factory.process_events(
+            |message| {
+                let usage = AssetMemoryUsage::new(message.ir.memory_usage(), 0);
+                Ok((RawAsset(message.ir), usage))
+            },
+            |_| {},
+            Duration::ZERO,
+        );
+    }
+}
+
+fn all_in_state(hub: &AssetHub, loaded: bool) -> bool {
+    hub.asset_infos()
+        .iter()
+        .all(|info| matches!(info.state, AssetInfoState::Loaded { .. }) == loaded)
+}
+
+fn requests_handler(
+    r: Receiver<AssetHubEvent>,
+    mut hub: Single<&mut AssetHub>,
+    mut stress: Single<&mut AssetsStress>,
+    mut sender: Sender<ExitEvent>,
+) {
+    let AssetHubEvent::RequestFinished(id, result) = r.event else {
+        return;
+    };
+    if let Err(err) = result {
+        crate::failure(format!("Asset request {} failed: {}", id, err));
+    }
+
+    match stress.stage {
+        // The first finished request is the enumeration made by the engine
+        Stage::Enumerating => {
+            let types = hub
+                .asset_infos()
+                .into_iter()
+                .map(|info| info.header.asset_type)
+                .collect::<HashSet<_>>();
+            info!("Enumerated {} asset types", types.len());
+            for asset_type in types {
+                let binding = hub.get_factory_biding(asset_type);
+                stress.bind(binding);
+            }
+        }
+        Stage::Loading(loading) if loading == *id => {
+            if !all_in_state(&hub, true) {
+                crate::failure("Not all the assets are loaded");
+            }
+            let id = hub.request(AssetRequest::Free(AssetRequestQuery::All));
+            stress.stage = Stage::Freeing(id);
+            return;
+        }
+        Stage::Freeing(freeing) if freeing == *id => {
+            if !all_in_state(&hub, false) {
+                crate::failure("Not all the assets are freed");
+            }
+            stress.cycles += 1;
+            if stress.max_cycles == Some(stress.cycles) {
+                info!("Finished {} load and free cycles", stress.cycles);
+                sender.send(ExitEvent);
+                return;
+            }
+        }
+        _ => return,
+    }
+
+    let id = hub.request(AssetRequest::Load(AssetRequestQuery::All));
+    stress.stage = Stage::Loading(id);
+}
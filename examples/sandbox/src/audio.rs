@@ -0,0 +1,93 @@
+//! Audio stress: a bank of the waveform voices mixed into the master bus
+//! with the limiter. Every tick the voices are retuned and their gains in the
+//! mixer are changed, flooding the player with the events.
+//! The load and the underruns of the player are logged with `--monitoring`.
+
+use crate::Args;
+use dawn_audio::entities::bus::Bus;
+use dawn_audio::entities::effects::limiter::LimiterEffect;
+use dawn_audio::entities::events::{AudioEvent, AudioEventTargetId, AudioEventType};
+use dawn_audio::entities::sinks::InterleavedSink;
+use dawn_audio::entities::sources::multiplexer::{MultiplexerSource, MultiplexerSourceEvent};
+use dawn_audio::entities::sources::waveform::{WaveformSource, WaveformSourceEvent, WaveformType};
+use dawn_ecs::events::TickEvent;
+use dawn_engine::{AudioConfig, EngineBuilder, EngineError};
+use evenio::component::Component;
+use evenio::event::{Receiver, Sender};
+use evenio::fetch::Single;
+use std::time::Duration;
+
+const VOICES: usize = 16;
+
+#[derive(Component)]
+struct Voices {
+    mixer: AudioEventTargetId,
+    voices: [AudioEventTargetId; VOICES],
+    retunes: usize,
+    next: usize,
+}
+
+pub(crate) fn run(builder: EngineBuilder, args: &Args) -> Result<(), EngineError> {
+    let config = AudioConfig::default();
+
+    let voices: [WaveformSource; VOICES] =
+        std::array::from_fn(|_| WaveformSource::new(Some(WaveformType::Disabled)));
+    let ids = voices.each_ref().map(|voice| voice.get_id());
+    let mixer = MultiplexerSource::new(voices);
+    let mixer_id = mixer.get_id();
+
+    // The voices sum up way above the full scale, the limiter keeps it below -1 dBTP
+    let limiter = LimiterEffect::new(config.sample_rate, -1.0, Duration::from_millis(100));
+    let master = Bus::new(limiter, mixer, Some(0.5), None);
+    let sink = InterleavedSink::new(master, config.sample_rate);
+
+    let retunes = args.count.unwrap_or(VOICES);
+    builder.with_audio(config, sink).run(|world| {
+        let entity = world.spawn();
+        world.insert(
+            entity,
+            Voices {
+                mixer: mixer_id,
+                voices: ids,
+                retunes,
+                next: 0,
+            },
+        );
+        world.add_handler(retune_handler);
+        crate::attach_common(world, args);
+    })
+}
+
+fn retune_handler(
+    r: Receiver<TickEvent>,
+    mut voices: Single<&mut Voices>,
+    mut sender: Sender<AudioEvent>,
+) {
+    let time = r.event.time;
+    for _ in 0..voices.retunes {
+        let index = voices.next % VOICES;
+        voices.next = voices.next.wrapping_add(1);
+
+        // Walk the pentatonic scale, so the stress is at least listenable
+        const SCALE: [f32; 5] = [0.0, 2.0, 4.0, 7.0, 9.0];
+        let step = (time * 4.0) as usize + index * 3;
+        let semitones = SCALE[step % SCALE.len()] + 12.0 * ((step / SCALE.len()) % 3) as f32;
+        let frequency = 110.0 * 2f32.powf(semitones / 12.0);
+        let waveform = match index % 4 {
+            0 => WaveformType::Sine(frequency),
+            1 => WaveformType::Triangle(frequency),
+            2 => WaveformType::Sawtooth(frequency),
+            _ => WaveformType::Square(frequency),
+        };
+        sender.send(AudioEvent::new(
+            voices.voices[index],
+            AudioEventType::Waveform(WaveformSourceEvent::SetWaveformType(waveform)),
+        ));
+
+        let gain = 0.5 + 0.5 * (time + index as f32).sin();
+        sender.send(AudioEvent::new(
+            voices.mixer,
+            AudioEventType::MuxSource(MultiplexerSourceEvent::SetGain(index, gain)),
+        ));
+    }
+}
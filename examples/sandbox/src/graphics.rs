@@ -0,0 +1,201 @@
+//! Graphics stress: a crowd of entities moved every tick, so the renderer
+//! collects all of them again each frame. With the asset container, the meshes
+//! are loaded and assigned to the entities round-robin. The pass only clears
+//! the screen, the meshes are counted but not drawn since that is up to the
+//! shaders of the game. Headless, only the ECS side of the crowd is exercised.
+
+use crate::Args;
+use dawn_assets::hub::{AssetHub, AssetHubEvent, AssetInfoState};
+use dawn_assets::requests::{AssetRequest, AssetRequestID, AssetRequestQuery};
+use dawn_assets::AssetType;
+use dawn_ecs::events::TickEvent;
+use dawn_engine::{EngineBuilder, EngineError};
+use dawn_graphics::construct_chain;
+use dawn_graphics::gl::bindings;
+use dawn_graphics::gl::mesh::Mesh;
+use dawn_graphics::passes::pipeline::RenderPipeline;
+use dawn_graphics::passes::result::RenderResult;
+use dawn_graphics::passes::RenderPass;
+use dawn_graphics::renderable::{ObjectMesh, ObjectPosition, ObjectRotation, Renderable};
+use dawn_graphics::renderer::{RendererBackend, RendererBackendConfig};
+use dawn_graphics::view::{OutputFormat, PlatformSpecificViewConfig, ViewConfig, ViewGeometry};
+use evenio::component::Component;
+use evenio::entity::EntityId;
+use evenio::event::{Insert, Receiver, Sender};
+use evenio::fetch::{Fetcher, Single};
+use evenio::world::World;
+use glam::{Quat, Vec3};
+use log::info;
+
+const DEFAULT_COUNT: usize = 1000;
+
+type SandboxPassEvent = ();
+
+#[derive(Component)]
+struct Mover {
+    origin: Vec3,
+    phase: f32,
+}
+
+#[derive(Component)]
+enum MeshLoading {
+    Enumerating,
+    Loading(AssetRequestID),
+    Done,
+}
+
+/// Clears the screen with a color cycling over time, counting the renderables.
+struct ClearPass {
+    frame: usize,
+    renderables: usize,
+}
+
+impl RenderPass<SandboxPassEvent> for ClearPass {
+    fn name(&self) -> &str {
+        "Clear"
+    }
+
+    fn begin(&mut self, _backend: &RendererBackend<SandboxPassEvent>) -> RenderResult {
+        self.frame += 1;
+        self.renderables = 0;
+        let t = self.frame as f32 / 120.0;
+        unsafe {
+            bindings::ClearColor(t.sin() * 0.5 + 0.5, 0.2, t.cos() * 0.5 + 0.5, 1.0);
+            bindings::Clear(bindings::COLOR_BUFFER_BIT | bindings::DEPTH_BUFFER_BIT);
+        }
+        RenderResult::default()
+    }
+
+    fn on_renderable(
+        &mut self,
+        _backend: &mut RendererBackend<SandboxPassEvent>,
+        _renderable: &Renderable,
+    ) -> RenderResult {
+        self.renderables += 1;
+        RenderResult::default()
+    }
+}
+
+pub(crate) fn run(builder: EngineBuilder, args: &Args) -> Result<(), EngineError> {
+    let mut builder = builder;
+    if !args.headless {
+        let view = ViewConfig {
+            platform_specific: PlatformSpecificViewConfig {},
+            synchronization: None,
+            title: "DAWN Sandbox".to_string(),
+            width: 1280,
+            height: 720,
+            geometry: ViewGeometry::Windowed,
+        };
+        let config = RendererBackendConfig {
+            texture_factory_binding: None,
+            shader_factory_binding: None,
+            mesh_factory_binding: None,
+            material_factory_binding: None,
+            font_factory_binding: None,
+            aspect_ratio: None,
+            bars_color: [0.0; 3],
+            texture_array_layers: 0,
+            shader_error_banner: None,
+            program_cache_dir: None,
+            output_format: OutputFormat::default(),
+        };
+        builder = builder.with_window(view).with_renderer(config, |_| {
+            Ok(RenderPipeline::new(construct_chain!(ClearPass {
+                frame: 0,
+                renderables: 0,
+            })))
+        });
+    }
+
+    let count = args.count.unwrap_or(DEFAULT_COUNT);
+    let load_meshes = args.assets.is_some() && !args.headless;
+    builder.run(|world| {
+        spawn_crowd(world, count);
+        world.add_handler(move_handler);
+        if load_meshes {
+            let entity = world.spawn();
+            world.insert(entity, MeshLoading::Enumerating);
+            world.add_handler(mesh_loading_handler);
+        }
+        crate::attach_common(world, args);
+    })
+}
+
+fn spawn_crowd(world: &mut World, count: usize) {
+    // Spread on the grid, so the crowd stays in a predictable volume
+    let side = (count as f32).sqrt().ceil() as usize;
+    for i in 0..count {
+        let origin = Vec3::new((i % side) as f32, 0.0, (i / side) as f32) * 2.0;
+        let entity = world.spawn();
+        world.insert(entity, ObjectPosition(origin));
+        world.insert(entity, ObjectRotation(Quat::IDENTITY));
+        world.insert(
+            entity,
+            Mover {
+                origin,
+                phase: i as f32 * 0.1,
+            },
+        );
+    }
+    info!("Spawned {} entities", count);
+}
+
+fn move_handler(
+    r: Receiver<TickEvent>,
+    movers: Fetcher<(EntityId, &Mover)>,
+    mut sender: Sender<(Insert<ObjectPosition>, Insert<ObjectRotation>)>,
+) {
+    let time = r.event.time;
+    for (entity, mover) in movers {
+        let angle = time + mover.phase;
+        let offset = Vec3::new(angle.cos(), angle.sin() * 0.5, angle.sin());
+        sender.insert(entity, ObjectPosition(mover.origin + offset));
+        sender.insert(entity, ObjectRotation(Quat::from_rotation_y(angle)));
+    }
+}
+
+fn mesh_loading_handler(
+    r: Receiver<AssetHubEvent>,
+    mut hub: Single<&mut AssetHub>,
+    mut loading: Single<&mut MeshLoading>,
+    movers: Fetcher<(EntityId, &Mover)>,
+    mut sender: Sender<Insert<ObjectMesh>>,
+) {
+    let AssetHubEvent::RequestFinished(id, result) = r.event else {
+        return;
+    };
+    if let Err(err) = result {
+        crate::failure(format!("Asset request {} failed: {}", id, err));
+    }
+
+    match **loading {
+        // The first finished request is the enumeration made by the engine
+        MeshLoading::Enumerating => {
+            let id = hub.request(AssetRequest::Load(AssetRequestQuery::ByType(
+                AssetType::Mesh,
+            )));
+            **loading = MeshLoading::Loading(id);
+        }
+        MeshLoading::Loading(loading_id) if loading_id == *id => {
+            let meshes = hub
+                .asset_infos()
+                .into_iter()
+                .filter(|info| {
+                    info.header.asset_type == AssetType::Mesh
+                        && matches!(info.state, AssetInfoState::Loaded { .. })
+                })
+                .filter_map(|info| hub.get_typed::<Mesh>(info.id).ok())
+                .collect::<Vec<_>>();
+            info!("Loaded {} meshes", meshes.len());
+
+            if !meshes.is_empty() {
+                for (i, (entity, _)) in movers.into_iter().enumerate() {
+                    sender.insert(entity, ObjectMesh(meshes[i % meshes.len()].clone()));
+                }
+            }
+            **loading = MeshLoading::Done;
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,181 @@
+//! Sandbox application driving the engine through its public API only.
+//! Each scenario stresses one of the subsystems and doubles as an example of using it:
+//!
+//! ```text
+//! dawn-sandbox <graphics|audio|assets> [options]
+//!     --headless        Do not open the window
+//!     --duration SECS   Stop after the given time
+//!     --assets PATH     Asset container to read, see `dawn_paths` for the defaults
+//!     --count N         Number of the entities (graphics), of the voices retuned
+//!                       per tick (audio) or of the load and free cycles to run (assets)
+//!     --monitoring      Log the monitoring of the subsystems every second
+//! ```
+//!
+//! Running headless with the duration turns the scenario into a soak test:
+//! the process exits with a non-zero code if the scenario reported any failure.
+
+mod assets;
+mod audio;
+mod graphics;
+
+use dawn_ecs::events::{ExitEvent, TickEvent};
+use dawn_engine::{Engine, FatalErrorConfig};
+use dawn_paths::Paths;
+use evenio::event::{Receiver, Sender};
+use evenio::world::World;
+use log::{error, info};
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const USAGE: &str = "Usage: dawn-sandbox <graphics|audio|assets> \
+    [--headless] [--duration SECS] [--assets PATH] [--count N] [--monitoring]";
+
+static FAILURES: AtomicUsize = AtomicUsize::new(0);
+
+/// Reports the failure of the scenario. Fails the soak run.
+pub(crate) fn failure(message: impl Display) {
+    error!("{}", message);
+    FAILURES.fetch_add(1, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scenario {
+    Graphics,
+    Audio,
+    Assets,
+}
+
+pub(crate) struct Args {
+    scenario: Scenario,
+    headless: bool,
+    duration: Option<f32>,
+    assets: Option<PathBuf>,
+    count: Option<usize>,
+    monitoring: bool,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+        let scenario = match args.next().as_deref() {
+            Some("graphics") => Scenario::Graphics,
+            Some("audio") => Scenario::Audio,
+            Some("assets") => Scenario::Assets,
+            Some(other) => return Err(format!("Unknown scenario: {}", other)),
+            None => return Err("Scenario is not specified".to_string()),
+        };
+
+        let mut parsed = Args {
+            scenario,
+            headless: false,
+            duration: None,
+            assets: None,
+            count: None,
+            monitoring: false,
+        };
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value of {}", arg));
+            match arg.as_str() {
+                "--headless" => parsed.headless = true,
+                "--monitoring" => parsed.monitoring = true,
+                "--assets" => parsed.assets = Some(PathBuf::from(value()?)),
+                "--duration" => {
+                    let duration = value()?;
+                    parsed.duration = Some(
+                        duration
+                            .parse()
+                            .map_err(|_| format!("Invalid duration: {}", duration))?,
+                    );
+                }
+                "--count" => {
+                    let count = value()?;
+                    parsed.count = Some(
+                        count
+                            .parse()
+                            .map_err(|_| format!("Invalid count: {}", count))?,
+                    );
+                }
+                _ => return Err(format!("Unknown option: {}", arg)),
+            }
+        }
+        Ok(parsed)
+    }
+}
+
+/// Attaches the handlers shared by all the scenarios.
+/// Called at the end of the setup of each scenario.
+pub(crate) fn attach_common(world: &mut World, args: &Args) {
+    if let Some(duration) = args.duration {
+        attach_duration(world, duration);
+    }
+}
+
+/// Stops the main loop after the given time in seconds.
+fn attach_duration(world: &mut World, duration: f32) {
+    world.add_handler(
+        move |r: Receiver<TickEvent>, mut sender: Sender<ExitEvent>| {
+            if r.event.time >= duration {
+                info!("Scenario finished after {:.1}s", r.event.time);
+                sender.send(ExitEvent);
+            }
+        },
+    );
+}
+
+fn setup_logging() {
+    struct Logger;
+    impl log::Log for Logger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::Level::Info
+        }
+
+        fn log(&self, record: &log::Record) {
+            eprintln!("{}: {}", record.level(), record.args());
+        }
+
+        fn flush(&self) {}
+    }
+
+    log::set_logger(&Logger).unwrap();
+    log::set_max_level(log::LevelFilter::Info);
+}
+
+fn main() -> ExitCode {
+    setup_logging();
+
+    let mut args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut paths = Paths::new("dawn-sandbox");
+    if let Some(path) = args.assets.take() {
+        paths = paths.with_assets(path);
+    }
+    args.assets = paths.assets();
+
+    let builder = Engine::builder()
+        .with_monitoring(args.monitoring)
+        .with_fatal_errors(FatalErrorConfig::default())
+        .with_paths(paths);
+    let result = match args.scenario {
+        Scenario::Graphics => graphics::run(builder, &args),
+        Scenario::Audio => audio::run(builder, &args),
+        Scenario::Assets => assets::run(builder, &args),
+    };
+    if let Err(err) = result {
+        error!("{}", err);
+        return ExitCode::FAILURE;
+    }
+
+    let failures = FAILURES.load(Ordering::Relaxed);
+    if failures > 0 {
+        error!("Scenario reported {} failures", failures);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}